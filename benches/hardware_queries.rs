@@ -0,0 +1,66 @@
+//! Hardware query overhead of configuring a project.
+//!
+//! Loading a project's configuration and applying it both query memory and
+//! disks. Compares reading them through the shared handle of
+//! `cargo_optimize::hardware::system` with a fresh `System::new_all()` and
+//! `refresh_all()` per query, the way every query used to, over a number of
+//! rounds (10 by default, or the first argument). Fails when repeated
+//! queries within the refresh age read the system again.
+//!
+//! ```text
+//! cargo bench --bench hardware_queries -- 50
+//! ```
+
+use cargo_optimize::config::ConfigManager;
+use cargo_optimize::hardware::system::{self, DEFAULT_MAX_AGE};
+use cargo_optimize::hardware::HardwareInfo;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use sysinfo::{Disks, System};
+
+/// Rounds when no count is given
+const DEFAULT_ROUNDS: u32 = 10;
+
+fn main() -> ExitCode {
+    let rounds = std::env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(DEFAULT_ROUNDS);
+    let project = tempfile::tempdir().expect("Failed to create project directory");
+
+    let (_, baseline) = time(|| {
+        for _ in 0..rounds {
+            let mut sys = System::new_all();
+            sys.refresh_all();
+            let _ = Disks::new_with_refreshed_list();
+        }
+    });
+    println!("System::new_all + refresh_all   {:>10.1?} per query", baseline / rounds);
+
+    let (_, cold) = time(HardwareInfo::detect);
+    println!("HardwareInfo::detect, first     {:>10.1?}", cold);
+    let before = system::refresh_counts();
+    let (_, warm) = time(|| {
+        for _ in 0..rounds {
+            HardwareInfo::detect();
+        }
+    });
+    let reads = system::refresh_counts().memory - before.memory;
+    println!("HardwareInfo::detect, cached    {:>10.1?} per query ({} reads)", warm / rounds, reads);
+
+    let (_, load) = time(|| {
+        for _ in 0..rounds {
+            ConfigManager::new_with_base_dir(project.path(), "CARGO_OPTIMIZE_BENCH_").expect("Failed to load config");
+        }
+    });
+    println!("ConfigManager load              {:>10.1?} per load", load / rounds);
+
+    if warm < DEFAULT_MAX_AGE && reads > 0 {
+        eprintln!("{} cached queries read the system {} times", rounds, reads);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let value = f();
+    (value, start.elapsed())
+}
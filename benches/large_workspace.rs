@@ -0,0 +1,89 @@
+//! Analysis time on large synthetic workspaces.
+//!
+//! Generates a workspace of path-only members (300 by default, or the first
+//! argument), each depending on a few earlier members, and times the
+//! analyses that walk every member. Fails when the full analysis takes longer
+//! than the budget, which is what 300+ member workspaces need it to stay under.
+//!
+//! ```text
+//! cargo bench --bench large_workspace -- 1000
+//! ```
+
+use cargo_optimize::analysis::{analyze_project, dependency_report};
+use cargo_optimize::workspace_hack::unification_report;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+/// Members generated when no count is given
+const DEFAULT_MEMBERS: usize = 300;
+
+/// Time the full analysis of the default workspace may take
+const BUDGET: Duration = Duration::from_secs(5);
+
+/// Earlier members each member depends on
+const FAN_IN: usize = 3;
+
+fn main() -> ExitCode {
+    let members = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_MEMBERS);
+    let workspace = tempfile::tempdir().expect("Failed to create workspace directory");
+    write_workspace(workspace.path(), members);
+    let manifest = workspace.path().join("Cargo.toml");
+
+    // Resolve once so every measurement starts from an existing Cargo.lock
+    let (_, resolve) = time(|| dependency_report(Some(&manifest)).expect("Failed to resolve workspace"));
+    println!("{} members, first resolve {:?}", members, resolve);
+
+    let (analysis, analyze) = time(|| analyze_project(Some(&manifest)).expect("Analysis failed"));
+    assert_eq!(analysis.workspace_members.len(), members);
+    println!("analyze_project      {:>10.1?}", analyze);
+    let (report, report_time) = time(|| dependency_report(Some(&manifest)).expect("Dependency report failed"));
+    // Every member depends on the one before it
+    assert_eq!(report.critical_path.len(), members);
+    println!("dependency_report    {:>10.1?}", report_time);
+    let (_, unification) = time(|| unification_report(Some(&manifest)).expect("Unification report failed"));
+    println!("unification_report   {:>10.1?}", unification);
+
+    let budget = BUDGET.mul_f64(members as f64 / DEFAULT_MEMBERS as f64).max(BUDGET);
+    if analyze > budget {
+        eprintln!("analyze_project took {:?}, over the {:?} budget", analyze, budget);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let value = f();
+    (value, start.elapsed())
+}
+
+/// Write a workspace of `members` crates, every tenth with a binary too
+fn write_workspace(root: &Path, members: usize) {
+    let names: Vec<String> = (0..members).map(|i| format!("member-{:04}", i)).collect();
+    let list: Vec<String> = names.iter().map(|name| format!("\"crates/{}\"", name)).collect();
+    fs::write(
+        root.join("Cargo.toml"),
+        format!("[workspace]\nresolver = \"2\"\nmembers = [{}]\n", list.join(", ")),
+    )
+    .unwrap();
+
+    for (i, name) in names.iter().enumerate() {
+        let dir = root.join("crates").join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let mut manifest = format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n", name);
+        for dep in (i.saturating_sub(FAN_IN)..i).map(|j| &names[j]) {
+            let _ = writeln!(manifest, "{} = {{ path = \"../{}\" }}", dep, dep);
+        }
+        fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), "pub fn f() -> u32 { 1 }\n".repeat(50)).unwrap();
+        if i % 10 == 9 {
+            fs::write(dir.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        }
+    }
+}
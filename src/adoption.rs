@@ -0,0 +1,201 @@
+//! Adopting cargo-optimize without a build script.
+//!
+//! A build dependency runs on every build of every contributor, which some
+//! teams do not want in their dependency tree. The `cargo-optimize` binary
+//! applies the same configuration on demand, as `cargo optimize`, and
+//! [`install_alias`] makes that command available to everyone who checks out
+//! the project:
+//!
+//! - [`AliasTarget::Package`] defines `cargo optimize` as a `cargo run` of
+//!   the binary, for workspaces that have cargo-optimize as a member (for
+//!   example vendored under `tools/`);
+//! - [`AliasTarget::Installed`] relies on `cargo install cargo-optimize`,
+//!   which provides `cargo optimize` itself, and defines
+//!   `cargo optimize-install` to install the version the project pins.
+//!
+//! The alias goes into the `[alias]` table of `.cargo/config.toml`, leaving
+//! the rest of the file untouched. Optionally, git hooks run `cargo optimize`
+//! when the files driving its decisions change (see
+//! [`hooks::install_git_hooks_with`]).
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table};
+use tracing::debug;
+
+use crate::audit;
+use crate::hooks::{self, HookAction};
+use crate::transaction::atomic_write;
+
+/// Alias running the binary through `cargo run`
+pub const RUN_ALIAS: &str = "optimize";
+
+/// Alias installing the pinned version of the binary
+pub const INSTALL_ALIAS: &str = "optimize-install";
+
+/// How `cargo optimize` reaches the binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AliasTarget {
+    /// `cargo run` of the `cargo-optimize` package in the workspace
+    #[default]
+    Package,
+    /// The binary installed with `cargo install`
+    Installed,
+}
+
+impl AliasTarget {
+    /// Name and command of the alias to define
+    pub fn alias(self) -> (&'static str, String) {
+        match self {
+            AliasTarget::Package => (RUN_ALIAS, "run --quiet --package cargo-optimize --bin cargo-optimize --".to_string()),
+            AliasTarget::Installed => (
+                INSTALL_ALIAS,
+                format!("install --locked cargo-optimize@{}", env!("CARGO_PKG_VERSION")),
+            ),
+        }
+    }
+}
+
+/// What [`install_alias`] changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AliasInstall {
+    /// Alias defined
+    pub alias: String,
+    /// Whether `.cargo/config.toml` was changed; false when the alias was already there
+    pub config_changed: bool,
+    /// Hook files written
+    pub hooks: Vec<PathBuf>,
+}
+
+/// Define the alias for `target` in `<project_root>/.cargo/config.toml`
+///
+/// With `git_hook`, the `post-checkout` and `post-merge` hooks run
+/// `cargo optimize` when `cargo-optimize.toml` or the toolchain file
+/// changed. Fails without changing anything when the project defines the
+/// alias as something else.
+pub fn install_alias(project_root: &Path, target: AliasTarget, git_hook: bool) -> Result<AliasInstall> {
+    let config_path = config_path(project_root);
+    let existing = read_config(&config_path)?;
+    let mut doc = parse(&config_path, existing.as_deref())?;
+    let (name, command) = target.alias();
+    let defined = doc.get("alias").and_then(|aliases| aliases.get(name)).and_then(Item::as_str);
+    if defined.is_some_and(|defined| defined != command) {
+        anyhow::bail!(
+            "alias `{}` in {:?} is already defined as {:?}; remove it to let cargo-optimize define it",
+            name,
+            config_path,
+            defined.unwrap_or_default()
+        );
+    }
+
+    let config_changed = defined.is_none();
+    if config_changed {
+        if !doc.contains_key("alias") {
+            doc["alias"] = Item::Table(Table::new());
+        }
+        let aliases = doc["alias"].as_table_like_mut().context("`alias` in .cargo/config.toml is not a table")?;
+        aliases.insert(name, toml_edit::value(command));
+        let content = doc.to_string();
+        atomic_write(&config_path, &content)?;
+        audit::record(project_root, "install-alias", &config_path, existing.as_deref(), Some(&content));
+        debug!("Defined alias `{}` in {:?}", name, config_path);
+    }
+    let hooks = if git_hook {
+        hooks::install_git_hooks_with(project_root, HookAction::Apply)?
+    } else {
+        Vec::new()
+    };
+    Ok(AliasInstall {
+        alias: name.to_string(),
+        config_changed,
+        hooks,
+    })
+}
+
+/// Remove the aliases cargo-optimize defined
+///
+/// Aliases the project changed since are left alone; hooks are removed with
+/// [`hooks::uninstall_git_hooks`]. Returns whether `.cargo/config.toml` was
+/// changed.
+pub fn uninstall_alias(project_root: &Path) -> Result<bool> {
+    let config_path = config_path(project_root);
+    let existing = read_config(&config_path)?;
+    let mut doc = parse(&config_path, existing.as_deref())?;
+    let mut changed = false;
+    if let Some(aliases) = doc.get_mut("alias").and_then(Item::as_table_like_mut) {
+        for target in [AliasTarget::Package, AliasTarget::Installed] {
+            let (name, command) = target.alias();
+            if aliases.get(name).and_then(Item::as_str) == Some(command.as_str()) {
+                aliases.remove(name);
+                changed = true;
+            }
+        }
+        if aliases.is_empty() {
+            doc.remove("alias");
+        }
+    }
+    if changed {
+        let content = doc.to_string();
+        atomic_write(&config_path, &content)?;
+        audit::record(project_root, "uninstall-alias", &config_path, existing.as_deref(), Some(&content));
+    }
+    Ok(changed)
+}
+
+fn config_path(project_root: &Path) -> PathBuf {
+    project_root.join(".cargo").join("config.toml")
+}
+
+fn read_config(config_path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(config_path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {:?}", config_path)),
+    }
+}
+
+fn parse(config_path: &Path, content: Option<&str>) -> Result<DocumentMut> {
+    content
+        .unwrap_or_default()
+        .parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {:?}", config_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_is_added_and_removed_without_touching_the_rest() {
+        let project = tempfile::TempDir::new().unwrap();
+        let config_path = config_path(project.path());
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        let original = "# team settings\n[build]\njobs = 8\n\n[alias]\nb = \"build\"\n";
+        fs::write(&config_path, original).unwrap();
+
+        let installed = install_alias(project.path(), AliasTarget::Package, false).unwrap();
+        assert!(installed.config_changed && installed.hooks.is_empty());
+        let content = fs::read_to_string(&config_path).unwrap();
+        assert!(content.starts_with(original), "{}", content);
+        assert!(content.contains("optimize = \"run --quiet --package cargo-optimize --bin cargo-optimize --\""));
+        assert!(!install_alias(project.path(), AliasTarget::Package, false).unwrap().config_changed);
+
+        assert!(uninstall_alias(project.path()).unwrap());
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_user_alias_is_not_overwritten() {
+        let project = tempfile::TempDir::new().unwrap();
+        let config_path = config_path(project.path());
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        let original = "[alias]\noptimize-install = \"install cargo-optimize\"\n";
+        fs::write(&config_path, original).unwrap();
+
+        let error = install_alias(project.path(), AliasTarget::Installed, false).unwrap_err();
+        assert!(error.to_string().contains("already defined"), "{}", error);
+        assert!(!uninstall_alias(project.path()).unwrap());
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), original);
+    }
+}
@@ -0,0 +1,664 @@
+//! Advisor turning optimization decisions into actionable advice.
+//!
+//! Some improvements cannot be applied by writing configuration alone: they
+//! need a toolchain component, a tool installed, or a change the user has to
+//! make themselves. The [`Advisor`] collects these as [`Advice`] items, each
+//! with the exact command to run when there is one.
+//!
+//! Advice the user may decline carries a stable [`Advice::key`]. A
+//! [`Rejection`] of that key, kept in the state file, hides the advice on
+//! later runs until the gain it quotes ([`Advice::measure`]) grows by
+//! [`SIGNIFICANT_GROWTH_PERCENT`] or more.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+use crate::analysis::targets::{Remedy, TargetReport};
+use crate::analysis::{MemberChurn, UncachedDependency, UncachedSource};
+use crate::container::{CacheIssue, DockerfileAnalysis};
+use crate::hardware::topology::format_cpu_list;
+use crate::hardware::HardwareInfo;
+use crate::i18n::{Lang, Message};
+use crate::config::PackageOverride;
+use crate::nextest::{self, NextestSettings};
+use crate::report::ComponentRequirement;
+use crate::sccache_dist::{self, DistScheduler, Reachability};
+use crate::toolchain;
+use crate::workspace_hack::{UnificationReport, MAX_REPLUMBING};
+
+/// One piece of advice for the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advice {
+    /// What to do and why
+    pub message: String,
+    /// Exact command that carries out the advice, if there is one
+    pub command: Option<String>,
+    /// Stable identity of the suggestion for rejecting it, e.g. `workspace-hack`
+    pub key: Option<String>,
+    /// Gain the advice is based on, compared when it was rejected before
+    pub measure: Option<u64>,
+}
+
+impl Advice {
+    /// Advice without a command
+    pub fn new(message: impl Into<String>) -> Self {
+        Advice { message: message.into(), command: None, key: None, measure: None }
+    }
+
+    /// Advice with the command that carries it out
+    pub fn with_command(message: impl Into<String>, command: impl Into<String>) -> Self {
+        Advice { message: message.into(), command: Some(command.into()), key: None, measure: None }
+    }
+
+    /// Make the advice rejectable under `key`, quoting `measure` as its gain
+    pub fn keyed(mut self, key: impl Into<String>, measure: Option<u64>) -> Self {
+        self.key = Some(key.into());
+        self.measure = measure;
+        self
+    }
+}
+
+/// Growth of an advice's measure, in percent, that brings a rejected advice back
+pub const SIGNIFICANT_GROWTH_PERCENT: u64 = 50;
+
+/// A suggestion the user declined, kept in the state file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// [`Advice::key`] of the declined suggestion
+    pub key: String,
+    /// Why the user declined it
+    pub reason: String,
+    /// [`Advice::measure`] at the time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measure: Option<u64>,
+    /// When it was declined (seconds since the Unix epoch)
+    pub rejected_at: u64,
+}
+
+impl Rejection {
+    /// Whether the rejection still hides `advice`
+    ///
+    /// It does for the same key unless the measured gain grew by
+    /// [`SIGNIFICANT_GROWTH_PERCENT`] or more; a shrinking gain makes the
+    /// advice no more convincing than when it was declined.
+    pub fn covers(&self, advice: &Advice) -> bool {
+        if advice.key.as_deref() != Some(self.key.as_str()) {
+            return false;
+        }
+        match (self.measure, advice.measure) {
+            (Some(then), Some(now)) => now.saturating_sub(then) * 100 < then.max(1) * SIGNIFICANT_GROWTH_PERCENT,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.command {
+            Some(command) => write!(f, "{}: {}", self.message, command),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Profile overrides proposed for workspace members that rarely change
+const STABLE_MEMBER_SETTINGS: &[(&str, &str)] = &[("incremental", "false"), ("opt-level", "1")];
+
+/// Collects advice produced while planning and applying optimizations
+#[derive(Debug, Clone, Default)]
+pub struct Advisor {
+    advice: Vec<Advice>,
+    lang: Lang,
+    rejections: Vec<Rejection>,
+    rejected: Vec<Advice>,
+}
+
+impl Advisor {
+    /// Create an empty advisor writing messages in the detected language
+    pub fn new() -> Self {
+        Self::with_lang(Lang::detect())
+    }
+
+    /// Create an empty advisor writing messages in `lang`
+    pub fn with_lang(lang: Lang) -> Self {
+        Advisor { advice: Vec::new(), lang, rejections: Vec::new(), rejected: Vec::new() }
+    }
+
+    /// Hide advice the user declined earlier
+    pub fn with_rejections(mut self, rejections: Vec<Rejection>) -> Self {
+        self.rejections = rejections;
+        self
+    }
+
+    /// Add a piece of advice, returning false when a rejection hides it
+    pub fn push(&mut self, advice: Advice) -> bool {
+        if self.rejections.iter().any(|rejection| rejection.covers(&advice)) {
+            self.rejected.push(advice);
+            return false;
+        }
+        self.advice.push(advice);
+        true
+    }
+
+    /// All advice collected so far
+    pub fn advice(&self) -> &[Advice] {
+        &self.advice
+    }
+
+    /// Advice hidden because the user declined it earlier
+    pub fn rejected(&self) -> &[Advice] {
+        &self.rejected
+    }
+
+    /// Whether there is no advice
+    pub fn is_empty(&self) -> bool {
+        self.advice.is_empty()
+    }
+
+    /// Check required components against the installed ones
+    ///
+    /// Adds a `rustup component add …` advice for every missing component and
+    /// returns the missing requirements (each component listed once).
+    pub fn check_components(
+        &mut self,
+        required: &[ComponentRequirement],
+        installed: &[String],
+    ) -> Vec<ComponentRequirement> {
+        let mut missing: Vec<ComponentRequirement> = Vec::new();
+        for requirement in required {
+            if toolchain::has_component(installed, &requirement.component)
+                || missing.iter().any(|m| m.component == requirement.component)
+            {
+                continue;
+            }
+            self.push(component_advice_in(self.lang, requirement));
+            missing.push(requirement.clone());
+        }
+        missing
+    }
+
+    /// Propose dev-profile overrides for rarely-changed workspace members
+    ///
+    /// Members with at most `max_commits` commits in the `window_days` window
+    /// are compiled once and then reused, so incremental state only costs
+    /// disk and a small opt-level makes the code they contribute faster.
+    /// Actively developed members keep the iterative defaults. Nothing is
+    /// proposed when every member is equally quiet or equally busy.
+    pub fn advise_churn(&mut self, churn: &[MemberChurn], max_commits: usize, window_days: u32) -> Vec<PackageOverride> {
+        let (stable, active): (Vec<&MemberChurn>, Vec<&MemberChurn>) =
+            churn.iter().partition(|member| member.commits <= max_commits);
+        if stable.is_empty() || active.is_empty() {
+            return Vec::new();
+        }
+
+        stable
+            .into_iter()
+            .filter(|member| {
+                let message = self.lang.format(Message::StableMember, &[&member.package, &member.commits, &window_days]);
+                self.push(Advice::new(message).keyed(format!("stable-member:{}", member.package), None))
+            })
+            .map(|member| {
+                PackageOverride {
+                    profile: "dev".to_string(),
+                    package: member.package.clone(),
+                    settings: STABLE_MEMBER_SETTINGS
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Quantify the test-phase speedup of cargo-nextest over `cargo test`
+    ///
+    /// Nothing is advised when the suite has a single test binary, since
+    /// nextest then gains little over the default harness.
+    pub fn advise_nextest(&mut self, settings: &NextestSettings, test_binaries: usize) {
+        let speedup = nextest::expected_speedup(test_binaries, settings.test_threads);
+        if speedup <= 1.0 {
+            return;
+        }
+        self.push(Advice::with_command(
+            self.lang.format(
+                Message::NextestSpeedup,
+                &[&format!("{:.1}", speedup), &test_binaries, &settings.test_threads],
+            ),
+            settings.command(),
+        )
+        .keyed("nextest", Some((speedup * 10.0).round() as u64)));
+    }
+
+    /// Stabilize dependencies that bypass the crates.io cache
+    ///
+    /// Git dependencies following a branch are advised to pin the commit
+    /// already in `Cargo.lock`; pinned git dependencies are advised to be
+    /// vendored, which covers them all with one `cargo vendor`. Local
+    /// `[patch]` checkouts cannot be cached, so only their cost is pointed out.
+    pub fn advise_uncached(&mut self, uncached: &[UncachedDependency]) {
+        let mut pinned = Vec::new();
+        for dependency in uncached {
+            match &dependency.source {
+                UncachedSource::GitBranch { branch, locked_rev, .. } => {
+                    let message = self.lang.format(
+                        Message::PinGitDependency,
+                        &[
+                            &dependency.name,
+                            &branch.as_deref().unwrap_or("HEAD"),
+                            &locked_rev.as_deref().unwrap_or("<commit>"),
+                            &dependency.dependents,
+                        ],
+                    );
+                    let key = format!("pin-git:{}", dependency.name);
+                    self.push(Advice::new(message).keyed(key, Some(dependency.rebuild_cost)));
+                }
+                UncachedSource::GitPinned { .. } => pinned.push(dependency.name.as_str()),
+                UncachedSource::Path => {
+                    let message = self.lang.format(Message::LocalPatch, &[&dependency.name, &dependency.dependents]);
+                    let key = format!("local-patch:{}", dependency.name);
+                    self.push(Advice::new(message).keyed(key, Some(dependency.rebuild_cost)));
+                }
+            }
+        }
+        if !pinned.is_empty() {
+            self.push(Advice::with_command(
+                self.lang.format(Message::VendorGitDependencies, &[&pinned.join(", ")]),
+                "cargo vendor",
+            )
+            .keyed("vendor-git", Some(pinned.len() as u64)));
+        }
+    }
+
+    /// Recommend a workspace-hack crate when feature unification costs rebuilds
+    ///
+    /// With cargo-hakari installed the advice is to generate one with it;
+    /// otherwise to install it first. Nothing is advised when
+    /// [`UnificationReport::recommends_hack`] is false.
+    pub fn advise_workspace_hack(&mut self, report: &UnificationReport, hakari_installed: bool) {
+        if !report.recommends_hack() {
+            return;
+        }
+        let message = self.lang.format(
+            Message::WorkspaceHack,
+            &[&report.dependencies.len(), &report.members.len(), &report.benefit_percent()],
+        );
+        let command = if hakari_installed {
+            "cargo hakari init workspace-hack && cargo hakari generate && cargo hakari manage-deps"
+        } else {
+            "cargo install cargo-hakari --locked"
+        };
+        self.push(Advice::with_command(message, command).keyed("workspace-hack", Some(report.benefit_percent())));
+    }
+    
+    /// Recommend fixing feature unification, naming the crates and features
+    ///
+    /// Up to [`MAX_REPLUMBING`] manifest edits are recommended one by one,
+    /// each enabling the features a member's own build lacks; beyond that,
+    /// or when the edits cannot be made, a workspace-hack crate
+    /// ([`Advisor::advise_workspace_hack`]).
+    pub fn advise_feature_unification(&mut self, report: &UnificationReport, hakari_installed: bool) {
+        if !report.worth_fixing() {
+            return;
+        }
+        let edits = report.replumbing();
+        if edits.is_empty() || edits.len() > MAX_REPLUMBING {
+            self.advise_workspace_hack(report, hakari_installed);
+            return;
+        }
+        for edit in edits {
+            let message = self.lang.format(
+                Message::FeatureReplumbing,
+                &[
+                    &edit.member,
+                    &edit.dependency,
+                    &edit.features.join(", "),
+                    &edit.enabled_by.join(", "),
+                    &edit.cost_percent,
+                ],
+            );
+            let advice = match edit.command {
+                Some(command) => Advice::with_command(message, command),
+                None => Advice::new(message),
+            };
+            let key = format!("unify-features:{}:{}", edit.member, edit.dependency);
+            self.push(advice.keyed(key, Some(edit.cost_percent)));
+        }
+    }
+    
+    /// Say what a build farm brings, what happens without it and how to connect to it
+    ///
+    /// `client_config` is where the sccache client config belongs; its
+    /// `[dist]` section is included when it does not name the scheduler yet.
+    pub fn advise_sccache_dist(
+        &mut self,
+        scheduler: &DistScheduler,
+        reachability: &Reachability,
+        sccache_installed: bool,
+        client_config: Option<&Path>,
+    ) {
+        if !sccache_installed {
+            let message = self.lang.format(Message::SccacheDistNotInstalled, &[&scheduler.url]);
+            self.push(Advice::with_command(message, "cargo install sccache --locked").keyed("sccache-dist", None));
+            return;
+        }
+        let message = match reachability {
+            Reachability::Reachable { .. } => self.lang.format(Message::SccacheDistSpeedup, &[&scheduler.url]),
+            Reachability::Unreachable { reason } => {
+                self.lang.format(Message::SccacheDistUnreachable, &[&scheduler.url, reason])
+            }
+        };
+        self.push(Advice::new(message).keyed("sccache-dist", None));
+        let fallback = self.lang.format(Message::SccacheDistFallback, &[&sccache_dist::NO_DIST_ENV_VAR]);
+        self.push(Advice::new(fallback).keyed("sccache-dist", None));
+        if !scheduler.client_configured {
+            let path = client_config.map(|path| path.display().to_string()).unwrap_or_else(|| "$SCCACHE_CONF".to_string());
+            let mut message = self.lang.format(Message::SccacheDistClientConfig, &[&scheduler.url, &path]);
+            for line in sccache_dist::client_config(&scheduler.url).lines() {
+                message.push_str("\n      ");
+                message.push_str(line);
+            }
+            self.push(Advice::new(message).keyed("sccache-dist", None));
+        }
+    }
+    
+    /// Propose keeping examples and benches out of routine builds
+    ///
+    /// One advice per [`TargetReport::suggestions`] entry, with the target
+    /// count and the estimated share of the workspace build it saves.
+    pub fn advise_targets(&mut self, report: &TargetReport) {
+        for suggestion in report.suggestions() {
+            let (package, targets, percent) = (&suggestion.package, &suggestion.targets, &suggestion.savings_percent);
+            let advice = match suggestion.remedy {
+                Remedy::RequiredFeatures => {
+                    Advice::new(self.lang.format(Message::ExamplesRequiredFeatures, &[package, targets, percent]))
+                }
+                Remedy::CiMatrix => Advice::with_command(
+                    self.lang.format(Message::BenchesCiJob, &[package, targets, percent]),
+                    "cargo bench --workspace --no-run",
+                ),
+                Remedy::SeparateCrate => Advice::new(self.lang.format(
+                    Message::TargetsSeparateCrate,
+                    &[package, targets, &suggestion.kind.directory(), percent],
+                )),
+            };
+            let key = format!("targets:{}:{}", package, suggestion.kind.directory());
+            self.push(advice.keyed(key, Some(*percent)));
+        }
+    }
+    
+    /// Size builds for the CPU topology and pin the heaviest steps
+    ///
+    /// On a NUMA machine the build is bound to its largest node with
+    /// `numactl`, using the jobs [`HardwareInfo::recommended_parallelism`]
+    /// sizes for it. On a hybrid CPU release builds are pinned to the
+    /// performance cores with `taskset`, so the final link does not land on
+    /// an efficiency core. Both commands are Linux tools.
+    pub fn advise_topology(&mut self, hardware: &HardwareInfo) {
+        let topology = &hardware.topology;
+        let jobs = hardware.recommended_parallelism();
+        if let Some(node) = topology.largest_node() {
+            self.push(Advice::with_command(
+                self.lang.format(
+                    Message::NumaJobs,
+                    &[&topology.numa_nodes.len(), &hardware.cpu_count, &jobs, &node.id],
+                ),
+                format!("numactl --cpunodebind={0} --membind={0} cargo build -j {1}", node.id, jobs),
+            )
+            .keyed("numa-binding", None));
+        }
+        if let Some(types) = &topology.core_types {
+            let performance: Vec<usize> = match topology.largest_node() {
+                Some(node) => types.performance.iter().copied().filter(|cpu| node.cpus.contains(cpu)).collect(),
+                None => types.performance.clone(),
+            };
+            if performance.is_empty() {
+                return;
+            }
+            self.push(Advice::with_command(
+                self.lang.format(
+                    Message::HybridLinkAffinity,
+                    &[&types.efficiency.len(), &hardware.cpu_count, &performance.len()],
+                ),
+                format!("taskset -c {} cargo build --release", format_cpu_list(&performance)),
+            )
+            .keyed("hybrid-affinity", None));
+        }
+    }
+    
+    /// Point out what keeps a Dockerfile from reusing cargo's caches
+    pub fn advise_dockerfile(&mut self, analysis: &DockerfileAnalysis) {
+        let target = format!("{}/target", analysis.workdir.trim_end_matches('/'));
+        for finding in &analysis.findings {
+            let location = format!("{}:{}", analysis.path.display(), finding.line);
+            let (message, issue) = match finding.issue {
+                CacheIssue::CopyBeforeDependencies => {
+                    (self.lang.format(Message::DockerCopyBeforeDependencies, &[&location]), "copy-before-dependencies")
+                }
+                CacheIssue::NoRegistryCacheMount => {
+                    (self.lang.format(Message::DockerNoRegistryCache, &[&location]), "no-registry-cache")
+                }
+                CacheIssue::NoTargetCacheMount => {
+                    (self.lang.format(Message::DockerNoTargetCache, &[&location, &target]), "no-target-cache")
+                }
+            };
+            let key = format!("dockerfile:{}:{}", analysis.path.display(), issue);
+            self.push(Advice::new(message).keyed(key, None));
+        }
+    }
+}
+
+/// Advice to install the component behind a requirement
+pub fn component_advice(requirement: &ComponentRequirement) -> Advice {
+    component_advice_in(Lang::detect(), requirement)
+}
+
+/// Advice to install the component behind a requirement, in `lang`
+pub fn component_advice_in(lang: Lang, requirement: &ComponentRequirement) -> Advice {
+    Advice::with_command(
+        lang.format(Message::ComponentNeeded, &[&requirement.needed_for, &requirement.component]),
+        format!("rustup component add {}", requirement.component),
+    )
+}
+
+impl fmt::Display for Advisor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for advice in &self.advice {
+            writeln!(f, "  - {}", advice)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirement(component: &str, needed_for: &str) -> ComponentRequirement {
+        ComponentRequirement { component: component.to_string(), needed_for: needed_for.to_string() }
+    }
+
+    #[test]
+    fn test_missing_component_advice() {
+        let mut advisor = Advisor::with_lang(Lang::En);
+        let installed = vec!["rust-src".to_string()];
+        let missing = advisor.check_components(
+            &[
+                requirement("rust-src", "-Zbuild-std"),
+                requirement("llvm-tools", "PGO"),
+                requirement("llvm-tools", "coverage"),
+            ],
+            &installed,
+        );
+
+        assert_eq!(missing, vec![requirement("llvm-tools", "PGO")]);
+        assert_eq!(advisor.advice().len(), 1);
+        assert_eq!(
+            advisor.advice()[0].to_string(),
+            "PGO needs the llvm-tools component: rustup component add llvm-tools"
+        );
+    }
+
+    #[test]
+    fn test_rejected_advice_returns_when_the_gain_grows() {
+        let rejection = Rejection {
+            key: "workspace-hack".to_string(),
+            reason: "we use feature flags per member on purpose".to_string(),
+            measure: Some(20),
+            rejected_at: 0,
+        };
+        let advice = |percent| Advice::new("workspace hack").keyed("workspace-hack", Some(percent));
+        assert!(rejection.covers(&advice(25)));
+        assert!(rejection.covers(&advice(5)));
+        assert!(!rejection.covers(&advice(30)));
+        assert!(!rejection.covers(&Advice::new("workspace hack")));
+
+        let mut advisor = Advisor::with_lang(Lang::En).with_rejections(vec![rejection]);
+        assert!(!advisor.push(advice(25)));
+        assert!(advisor.push(advice(40)));
+        assert_eq!(advisor.advice().len(), 1);
+        assert_eq!(advisor.rejected().len(), 1);
+
+        // A declined member gets no profile override either
+        let churn = |package: &str, commits| MemberChurn { package: package.to_string(), commits };
+        let rejection = Rejection {
+            key: "stable-member:core-types".to_string(),
+            reason: "changes soon".to_string(),
+            measure: None,
+            rejected_at: 0,
+        };
+        let mut advisor = Advisor::with_lang(Lang::En).with_rejections(vec![rejection]);
+        let overrides = advisor.advise_churn(&[churn("core-types", 0), churn("util", 0), churn("app", 25)], 1, 90);
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].package, "util");
+    }
+
+    #[test]
+    fn test_churn_advice() {
+        let churn = |package: &str, commits| MemberChurn { package: package.to_string(), commits };
+        let mut advisor = Advisor::with_lang(Lang::En);
+        let overrides = advisor.advise_churn(&[churn("core-types", 0), churn("app", 25)], 1, 90);
+
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].package, "core-types");
+        assert_eq!(overrides[0].profile, "dev");
+        assert!(overrides[0].settings.contains(&("incremental".to_string(), "false".to_string())));
+        assert!(advisor.advice()[0].message.starts_with("core-types changed in 0 commit(s)"));
+
+        // Nothing to split when every member is quiet
+        let mut advisor = Advisor::new();
+        assert!(advisor.advise_churn(&[churn("a", 0), churn("b", 1)], 1, 90).is_empty());
+        assert!(advisor.is_empty());
+    }
+
+    #[test]
+    fn test_uncached_dependency_advice() {
+        let dependency = |name: &str, source| UncachedDependency {
+            name: name.to_string(),
+            version: "0.1.0".to_string(),
+            source,
+            patched: false,
+            dependents: 3,
+            cascade_cost: 40,
+            rebuild_cost: 160,
+        };
+        let url = "https://example.com/repo".to_string();
+        let mut advisor = Advisor::with_lang(Lang::En);
+        advisor.advise_uncached(&[
+            dependency(
+                "tracked",
+                UncachedSource::GitBranch { url: url.clone(), branch: None, locked_rev: Some("1a2b3c".to_string()) },
+            ),
+            dependency("fixed-a", UncachedSource::GitPinned { url: url.clone() }),
+            dependency("fixed-b", UncachedSource::GitPinned { url }),
+            dependency("local", UncachedSource::Path),
+        ]);
+
+        let advice = advisor.advice();
+        assert_eq!(advice.len(), 3);
+        assert!(advice[0].message.starts_with("tracked follows HEAD"));
+        assert!(advice[0].message.contains("pin rev = \"1a2b3c\""));
+        assert!(advice[1].message.contains("rebuilds 3 crate(s)"));
+        assert_eq!(advice[2].command.as_deref(), Some("cargo vendor"));
+        assert!(advice[2].message.starts_with("fixed-a, fixed-b come from git"));
+    }
+
+    #[test]
+    fn test_dockerfile_advice() {
+        let (workdir, findings) = crate::container::analyze("FROM rust\nWORKDIR /src\nCOPY . .\nRUN cargo build\n").unwrap();
+        let mut advisor = Advisor::with_lang(Lang::En);
+        advisor.advise_dockerfile(&DockerfileAnalysis { path: "Dockerfile".into(), workdir, findings });
+
+        let advice = advisor.advice();
+        assert_eq!(advice.len(), 3);
+        assert!(advice[0].message.starts_with("Dockerfile:3: the whole project is copied"));
+        assert!(advice[1].message.starts_with("Dockerfile:4: cargo downloads crates"));
+        assert!(advice[2].message.ends_with("--mount=type=cache,target=/src/target"));
+    }
+
+    #[test]
+    fn test_sccache_dist_advice() {
+        let scheduler = DistScheduler {
+            url: "https://farm.example.com".to_string(),
+            source: crate::sccache_dist::SchedulerSource::Settings,
+            client_configured: false,
+        };
+        let mut advisor = Advisor::with_lang(Lang::En);
+        let reachable = Reachability::Reachable { latency_ms: 4 };
+        advisor.advise_sccache_dist(&scheduler, &reachable, true, Some(Path::new("/home/dev/.config/sccache/config")));
+
+        let advice = advisor.advice();
+        assert_eq!(advice.len(), 3);
+        assert!(advice[0].message.starts_with("Compilations are distributed to the build farm at https://farm.example.com"));
+        assert!(advice[1].message.ends_with("set SCCACHE_NO_DIST_COMPILE=1 to skip it right away"));
+        assert!(advice[2].message.contains("add this to /home/dev/.config/sccache/config"));
+        assert!(advice[2].message.contains("\n      scheduler_url = \"https://farm.example.com\"\n"));
+
+        let mut advisor = Advisor::with_lang(Lang::En);
+        advisor.advise_sccache_dist(&scheduler, &reachable, false, None);
+        assert_eq!(advisor.advice()[0].command.as_deref(), Some("cargo install sccache --locked"));
+    }
+
+    #[test]
+    fn test_topology_advice() {
+        use crate::hardware::topology::{CoreTypes, CpuTopology, NumaNode};
+
+        let node = |id, cpus: std::ops::Range<usize>| NumaNode { id, cpus: cpus.collect(), memory_bytes: None };
+        let numa = HardwareInfo {
+            cpu_count: 64,
+            available_memory: 256 << 30,
+            topology: CpuTopology { sockets: 2, numa_nodes: vec![node(0, 0..32), node(1, 32..64)], core_types: None },
+            ..crate::hardware::get_fallback_hardware()
+        };
+        assert_eq!(numa.recommended_parallelism(), 24);
+        let mut advisor = Advisor::with_lang(Lang::En);
+        advisor.advise_topology(&numa);
+        assert_eq!(advisor.advice().len(), 1);
+        assert!(advisor.advice()[0].message.contains("all 64 CPUs"), "{}", advisor.advice()[0]);
+        assert_eq!(
+            advisor.advice()[0].command.as_deref(),
+            Some("numactl --cpunodebind=0 --membind=0 cargo build -j 24")
+        );
+
+        // 8 performance and 16 efficiency CPUs count as 16
+        let hybrid = HardwareInfo {
+            cpu_count: 24,
+            available_memory: 32 << 30,
+            topology: CpuTopology {
+                sockets: 1,
+                numa_nodes: vec![node(0, 0..24)],
+                core_types: Some(CoreTypes { performance: (0..8).collect(), efficiency: (8..24).collect() }),
+            },
+            ..crate::hardware::get_fallback_hardware()
+        };
+        assert_eq!(hybrid.recommended_parallelism(), 12);
+        let mut advisor = Advisor::with_lang(Lang::En);
+        advisor.advise_topology(&hybrid);
+        assert_eq!(advisor.advice().len(), 1);
+        assert_eq!(advisor.advice()[0].command.as_deref(), Some("taskset -c 0-7 cargo build --release"));
+
+        let mut advisor = Advisor::with_lang(Lang::En);
+        advisor.advise_topology(&crate::hardware::get_fallback_hardware());
+        assert!(advisor.is_empty());
+    }
+}
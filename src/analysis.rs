@@ -5,18 +5,42 @@
 //! - Dependency graph analysis
 //! - Feature analysis
 //! - Build target detection
-//! - Build metrics collection
+//! - Build metrics collection, without vendored and generated code ([`sources`])
+//! - Per-dependency build reports (CSV/JSON)
+//! - Rebuild cost of git and `[patch]`-ed dependencies
+//! - Build cost of examples, benches and tests ([`targets`])
+//! - Time-bounded analysis from cached metadata for IDEs ([`quick`])
+//! - Per-member work spread over threads for large workspaces ([`parallel`])
+//! - Preliminary estimates from the manifests alone before a `Cargo.lock` exists
+//! - Analysis of the healthy members when others are broken ([`partial`])
+//! - DOT and Mermaid export of the dependency graph with build costs ([`graph`])
+//! - What `cargo build`, `check`, `test` and `doc` compile, and how long they take ([`simulate`])
 
 use anyhow::{Context, Result};
-use cargo_metadata::{MetadataCommand, Package, Metadata, DependencyKind};
+use cargo_metadata::{MetadataCommand, Package, PackageId, Metadata, DependencyKind, Node};
 use guppy::{
     graph::{PackageGraph, PackageMetadata, DependencyDirection, PackageSet},
     CargoMetadata,
 };
+use serde::Serialize;
 use serde_json;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use tracing::info;
+use toml_edit::DocumentMut;
+use tracing::{info, warn};
+
+use crate::audit;
+use crate::config::ProjectTemplate;
+use crate::output;
+
+pub mod build_weight;
+pub mod graph;
+pub mod parallel;
+pub mod partial;
+pub mod quick;
+pub mod simulate;
+pub mod sources;
+pub mod targets;
 
 /// Project analysis results
 #[derive(Debug, Clone)]
@@ -35,10 +59,27 @@ pub struct ProjectAnalysis {
     pub bottlenecks: Vec<BottleneckInfo>,
     /// Build targets (bins, libs, tests, etc.)
     pub targets: TargetAnalysis,
+    /// Build cost of each member's examples, benches and tests
+    pub target_costs: targets::TargetReport,
     /// Feature usage analysis
     pub features: FeatureAnalysis,
     /// Build metrics
     pub metrics: BuildMetrics,
+    /// Suggested `workspace.default-members` list, if one would help
+    pub default_members: Option<DefaultMembersSuggestion>,
+    /// Project archetype detected from target triples, crate types and dependencies
+    pub template: Option<ProjectTemplate>,
+    /// Estimated from the manifests alone because the project has no `Cargo.lock` yet
+    ///
+    /// Dependency counts are the dependencies the members declare, bottlenecks
+    /// and the dependency chain are unknown. [`ProjectAnalysis::refresh`] runs
+    /// the full analysis once the lock file exists.
+    pub preliminary: bool,
+    /// Members left out because cargo could not load them
+    ///
+    /// Non-empty when broken members would have failed `cargo metadata` for
+    /// the whole workspace: the rest of the analysis covers the healthy members.
+    pub member_diagnostics: Vec<partial::MemberDiagnostic>,
 }
 
 /// Information about a package
@@ -56,6 +97,21 @@ pub struct PackageInfo {
     pub dev_dependencies: usize,
     /// Number of build dependencies
     pub build_dependencies: usize,
+    /// Whether the package produces an application (bin or cdylib target)
+    pub is_application: bool,
+}
+
+/// Suggested `workspace.default-members` list
+///
+/// Plain `cargo build` in a virtual workspace compiles every member. Limiting
+/// the default set to the application crates still builds their library
+/// dependencies, but skips internal libraries nothing in the default set uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultMembersSuggestion {
+    /// Member paths (relative to the workspace root) to build by default
+    pub members: Vec<String>,
+    /// Member paths that would no longer be built by plain `cargo build`
+    pub excluded: Vec<String>,
 }
 
 /// Information about dependency bottlenecks
@@ -88,6 +144,8 @@ pub struct TargetAnalysis {
     pub build_scripts: usize,
     /// Number of procedural macros
     pub proc_macros: usize,
+    /// Library targets built as `cdylib` (also listed in `libraries`)
+    pub cdylibs: Vec<String>,
 }
 
 /// Feature usage analysis
@@ -128,8 +186,11 @@ pub enum ImpactLevel {
 /// Build metrics
 #[derive(Debug, Clone, Default)]
 pub struct BuildMetrics {
-    /// Estimated lines of code to compile
+    /// Estimated lines of code to compile: the workspace's own sources
+    /// plus a rough 1000 lines per dependency
     pub estimated_loc: usize,
+    /// Workspace sources, vendored and generated code left out
+    pub sources: sources::SourceStats,
     /// Number of crates to compile
     pub crate_count: usize,
     /// Potential parallelization factor
@@ -139,46 +200,124 @@ pub struct BuildMetrics {
 }
 
 /// Main analysis function
+///
+/// Fails without running `cargo metadata` while `CARGO_OPTIMIZE_DISABLE` is set.
+/// A project without a `Cargo.lock` (a new one) is not resolved, which would
+/// write the lock file and may need the network: it is analyzed from its
+/// manifests with `cargo metadata --no-deps --offline`, and the result is
+/// marked [`ProjectAnalysis::preliminary`].
+///
+/// Members whose manifest does not parse or that cargo cannot resolve are
+/// left out, reported in [`ProjectAnalysis::member_diagnostics`], instead of
+/// failing the analysis.
 pub fn analyze_project(manifest_path: Option<&Path>) -> Result<ProjectAnalysis> {
+    analyze_with(manifest_path, &[])
+}
+
+/// Analyze the project without letting cargo write `Cargo.lock`
+///
+/// Runs `cargo metadata --locked`, which fails instead of creating or
+/// updating the lock file. Broken members fail it too.
+pub fn analyze_project_locked(manifest_path: Option<&Path>) -> Result<ProjectAnalysis> {
+    analyze_with(manifest_path, &["--locked"])
+}
+
+fn analyze_with(manifest_path: Option<&Path>, cargo_options: &[&str]) -> Result<ProjectAnalysis> {
+    output::ensure_enabled()?;
     info!("Starting project analysis");
+    let locked = cargo_options.contains(&"--locked");
+    // A locked analysis fails on a missing lock file instead
+    let preliminary = !locked && !has_lockfile(manifest_path);
     
-    // Get cargo metadata
-    let mut cmd = MetadataCommand::new();
-    if let Some(path) = manifest_path {
-        cmd.manifest_path(path);
+    if preliminary {
+        info!("No Cargo.lock yet, analyzing the manifests only: estimates are preliminary");
     }
     
-    let metadata = cmd.exec()
-        .context("Failed to execute cargo metadata")?;
-    
-    // Convert cargo_metadata::Metadata to guppy::CargoMetadata
-    let metadata_json = serde_json::to_string(&metadata)
-        .context("Failed to serialize metadata")?;
-    let cargo_metadata: CargoMetadata = serde_json::from_str(&metadata_json)
-        .context("Failed to deserialize into CargoMetadata")?;
+    // Get cargo metadata
+    let command = |manifest_path: Option<&Path>| {
+        let mut cmd = MetadataCommand::new();
+        if let Some(path) = manifest_path {
+            cmd.manifest_path(path);
+        }
+        if preliminary {
+            cmd.no_deps();
+            cmd.other_options(vec!["--offline".to_string()]);
+        } else {
+            cmd.other_options(cargo_options.iter().map(|option| option.to_string()).collect::<Vec<_>>());
+        }
+        cmd
+    };
     
-    // Build guppy package graph
-    let package_graph = PackageGraph::from_metadata(cargo_metadata)
-        .context("Failed to build package graph")?;
+    let (metadata, member_diagnostics) = match command(manifest_path).exec() {
+        Ok(metadata) => (metadata, Vec::new()),
+        // A locked analysis stays strict
+        Err(e) => match (!locked)
+            .then(|| partial::metadata(manifest_path, &e.to_string(), |manifest| command(Some(manifest))))
+            .flatten()
+        {
+            Some(partial) => partial,
+            None => return Err(e).context("Failed to execute cargo metadata"),
+        },
+    };
+    // A locked analysis is read-only and leaves the target directory alone too;
+    // partial metadata is not cached, the quick analysis would not know members are missing
+    if !locked && member_diagnostics.is_empty() {
+        if let Err(e) = quick::cache_metadata(&metadata) {
+            warn!("Failed to cache metadata for quick analysis: {:#}", e);
+        }
+    }
+    // Without a resolve there is no graph; everything needing one is estimated
+    let package_graph = if preliminary { None } else { Some(package_graph(&metadata)?) };
     
     // Analyze workspace structure
-    let workspace_members = analyze_workspace_members(&metadata, &package_graph)?;
+    let workspace_members = analyze_workspace_members(&metadata)?;
     let is_workspace = workspace_members.len() > 1;
     
     // Analyze dependencies
-    let (total_deps, direct_deps) = count_dependencies(&metadata, &package_graph)?;
+    let (total_deps, direct_deps) = match &package_graph {
+        Some(graph) => count_dependencies(graph),
+        None => {
+            let declared = declared_dependencies(&metadata);
+            (declared, declared)
+        }
+    };
     
     // Find bottlenecks
-    let bottlenecks = find_bottlenecks(&package_graph)?;
+    let bottlenecks = match &package_graph {
+        Some(graph) => find_bottlenecks(graph)?,
+        None => Vec::new(),
+    };
     
     // Analyze build targets
     let targets = analyze_targets(&metadata)?;
+    let target_costs = targets::target_report_from(&metadata);
     
     // Analyze features
-    let features = analyze_features(&metadata, &package_graph)?;
+    let features = analyze_features(&metadata)?;
     
     // Calculate build metrics
-    let metrics = calculate_build_metrics(&metadata, &package_graph, &workspace_members)?;
+    let metrics = calculate_build_metrics(&metadata, package_graph.as_ref(), total_deps)?;
+    
+    // Suggest default members for workspaces that don't define them
+    let workspace_root: PathBuf = metadata.workspace_root.clone().into();
+    let default_members = if has_default_members(&workspace_root) {
+        None
+    } else {
+        suggest_default_members(&workspace_root, &workspace_members)
+    };
+    
+    // Detect the project archetype for template selection
+    let dependency_names: HashSet<String> = metadata.packages
+        .iter()
+        .filter(|p| metadata.workspace_members.contains(&p.id))
+        .flat_map(|p| p.dependencies.iter())
+        .filter(|dep| dep.kind == DependencyKind::Normal)
+        .map(|dep| dep.name.clone())
+        .collect();
+    let template = match embedded_signal(&workspace_root) {
+        Some(_) => Some(ProjectTemplate::Embedded),
+        None => detect_template(&configured_target_triples(&workspace_root), &targets, &dependency_names),
+    };
     
     Ok(ProjectAnalysis {
         workspace_root: metadata.workspace_root.clone().into(),
@@ -188,27 +327,70 @@ pub fn analyze_project(manifest_path: Option<&Path>) -> Result<ProjectAnalysis>
         direct_dependencies: direct_deps,
         bottlenecks,
         targets,
+        target_costs,
         features,
         metrics,
+        default_members,
+        template,
+        preliminary,
+        member_diagnostics,
     })
 }
 
+/// Whether a `Cargo.lock` exists next to the manifest or in a directory above it
+fn has_lockfile(manifest_path: Option<&Path>) -> bool {
+    let start = match manifest_path {
+        Some(path) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        None => std::env::current_dir().unwrap_or_default(),
+    };
+    // An empty parent is the current directory
+    let start = if start.as_os_str().is_empty() { PathBuf::from(".") } else { start };
+    start.ancestors().any(|dir| dir.join("Cargo.lock").is_file())
+}
+
+/// Distinct dependencies the workspace members declare, other members left out
+fn declared_dependencies(metadata: &Metadata) -> usize {
+    let members: Vec<&Package> = metadata.packages
+        .iter()
+        .filter(|p| metadata.workspace_members.contains(&p.id))
+        .collect();
+    let member_names: HashSet<&str> = members.iter().map(|p| p.name.as_str()).collect();
+    members
+        .iter()
+        .flat_map(|package| package.dependencies.iter())
+        .map(|dep| dep.name.as_str())
+        .filter(|name| !member_names.contains(name))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Build the guppy package graph of already loaded metadata
+pub(crate) fn package_graph(metadata: &Metadata) -> Result<PackageGraph> {
+    // Convert cargo_metadata::Metadata to guppy::CargoMetadata
+    let metadata_json = serde_json::to_string(metadata)
+        .context("Failed to serialize metadata")?;
+    let cargo_metadata: CargoMetadata = serde_json::from_str(&metadata_json)
+        .context("Failed to deserialize into CargoMetadata")?;
+    
+    PackageGraph::from_metadata(cargo_metadata)
+        .context("Failed to build package graph")
+}
+
 /// Analyze workspace members
-fn analyze_workspace_members(
-    metadata: &Metadata,
-    _graph: &PackageGraph,
-) -> Result<Vec<PackageInfo>> {
-    let mut members = Vec::new();
-    
-    for member_id in &metadata.workspace_members {
-        let package = metadata.packages
-            .iter()
-            .find(|p| &p.id == member_id)
-            .context("Failed to find workspace member package")?;
-        
+fn analyze_workspace_members(metadata: &Metadata) -> Result<Vec<PackageInfo>> {
+    let packages: HashMap<&PackageId, &Package> = metadata.packages.iter().map(|p| (&p.id, p)).collect();
+    let members = metadata.workspace_members
+        .iter()
+        .map(|member_id| packages.get(member_id).copied().context("Failed to find workspace member package"))
+        .collect::<Result<Vec<_>>>()?;
+    
+    Ok(parallel::par_map(&members, |package| {
         let (deps, dev_deps, build_deps) = count_package_dependencies(package);
+        let is_application = package.targets.iter().any(|target| {
+            target.kind.iter().any(|kind| kind == "bin" || kind == "cdylib")
+        });
         
-        members.push(PackageInfo {
+        PackageInfo {
             name: package.name.clone(),
             version: package.version.to_string(),
             path: package.manifest_path.parent()
@@ -218,10 +400,705 @@ fn analyze_workspace_members(
             dependencies: deps,
             dev_dependencies: dev_deps,
             build_dependencies: build_deps,
-        });
+            is_application,
+        }
+    }))
+}
+
+/// Check whether the workspace manifest already sets `default-members`
+fn has_default_members(workspace_root: &Path) -> bool {
+    std::fs::read_to_string(workspace_root.join("Cargo.toml"))
+        .ok()
+        .and_then(|content| content.parse::<DocumentMut>().ok())
+        .map(|doc| {
+            doc.get("workspace")
+                .and_then(|ws| ws.get("default-members"))
+                .is_some()
+        })
+        .unwrap_or(false)
+}
+
+/// Suggest a `default-members` list containing only application crates
+///
+/// Returns None unless the workspace has several members, at least one
+/// application and at least one library-only member that would be skipped.
+pub fn suggest_default_members(
+    workspace_root: &Path,
+    members: &[PackageInfo],
+) -> Option<DefaultMembersSuggestion> {
+    if members.len() < 2 {
+        return None;
+    }
+    
+    let relative = |member: &PackageInfo| -> String {
+        let path = member.path.strip_prefix(workspace_root).unwrap_or(&member.path);
+        let path = crate::paths::to_slash(path);
+        if path.is_empty() { ".".to_string() } else { path }
+    };
+    
+    let (apps, libs): (Vec<_>, Vec<_>) = members.iter().partition(|m| m.is_application);
+    if apps.is_empty() || libs.is_empty() {
+        return None;
+    }
+    
+    let mut suggestion = DefaultMembersSuggestion {
+        members: apps.into_iter().map(relative).collect(),
+        excluded: libs.into_iter().map(relative).collect(),
+    };
+    suggestion.members.sort();
+    suggestion.excluded.sort();
+    Some(suggestion)
+}
+
+/// Write a `default-members` suggestion to the workspace manifest
+///
+/// Only call this after the user agreed to the change. Formatting of the
+/// rest of the manifest is preserved; an existing `default-members` is never
+/// overwritten.
+pub fn apply_default_members(
+    manifest_path: &Path,
+    suggestion: &DefaultMembersSuggestion,
+) -> Result<()> {
+    let content = std::fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    let mut doc = content.parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+    
+    let workspace = doc
+        .get_mut("workspace")
+        .and_then(|ws| ws.as_table_mut())
+        .context("Manifest has no [workspace] table")?;
+    
+    if workspace.contains_key("default-members") {
+        anyhow::bail!("workspace.default-members is already set");
+    }
+    
+    let mut array = toml_edit::Array::new();
+    for member in &suggestion.members {
+        array.push(member.as_str());
     }
+    workspace["default-members"] = toml_edit::value(array);
+    
+    let updated = doc.to_string();
+    std::fs::write(manifest_path, &updated)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    let root = manifest_path.parent().unwrap_or(Path::new(""));
+    audit::record(root, "default-members", manifest_path, Some(&content), Some(&updated));
+    info!("Set workspace.default-members in {}", manifest_path.display());
+    Ok(())
+}
+
+/// Crates whose presence marks a project as a network server
+const SERVER_FRAMEWORKS: &[&str] = &["axum", "actix-web", "hyper", "rocket", "warp", "tonic", "poem", "salvo", "tide"];
+
+/// Target triples the project is configured to build for
+///
+/// Reads `build.target` from `.cargo/config.toml` and `toolchain.targets`
+/// from `rust-toolchain.toml`.
+pub fn configured_target_triples(workspace_root: &Path) -> Vec<String> {
+    let read = |path: PathBuf| {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| content.parse::<DocumentMut>().ok())
+    };
+    let strings = |item: Option<&toml_edit::Item>| -> Vec<String> {
+        match item {
+            Some(item) if item.is_str() => item.as_str().map(String::from).into_iter().collect(),
+            Some(item) => item
+                .as_array()
+                .map(|array| array.iter().filter_map(|v| v.as_str()).map(String::from).collect())
+                .unwrap_or_default(),
+            None => Vec::new(),
+        }
+    };
     
-    Ok(members)
+    let mut triples = Vec::new();
+    if let Some(doc) = read(workspace_root.join(".cargo").join("config.toml")) {
+        triples.extend(strings(doc.get("build").and_then(|b| b.get("target"))));
+    }
+    if let Some(doc) = read(workspace_root.join("rust-toolchain.toml")) {
+        triples.extend(strings(doc.get("toolchain").and_then(|t| t.get("targets"))));
+    }
+    triples
+}
+
+/// Programs that flash or debug a microcontroller when used as a cargo `runner`
+const FLASH_RUNNERS: &[&str] = &[
+    "probe-rs",
+    "probe-run",
+    "cargo-embed",
+    "cargo-flash",
+    "espflash",
+    "elf2uf2-rs",
+    "picotool",
+    "openocd",
+    "arm-none-eabi-gdb",
+    "gdb-multiarch",
+];
+
+/// Whether `triple` builds for bare metal (`thumbv7em-none-eabihf`, `riscv32imac-unknown-none-elf`)
+pub fn is_bare_metal(triple: &str) -> bool {
+    triple.contains("-none") || triple.starts_with("thumb")
+}
+
+/// Why the project looks like firmware for a microcontroller, if it does
+///
+/// Bare-metal triples from [`configured_target_triples`] count, and so do
+/// `runner` keys in `.cargo/config.toml` under a bare-metal `[target.<triple>]`,
+/// under a `cfg(...)` table with `target_os = "none"`, or naming a flashing
+/// tool such as probe-rs.
+pub fn embedded_signal(workspace_root: &Path) -> Option<String> {
+    if let Some(triple) = configured_target_triples(workspace_root).into_iter().find(|t| is_bare_metal(t)) {
+        return Some(format!("builds for {}", triple));
+    }
+    
+    let config = std::fs::read_to_string(workspace_root.join(".cargo").join("config.toml")).ok()?;
+    let doc = config.parse::<DocumentMut>().ok()?;
+    let targets = doc.get("target")?.as_table_like()?;
+    let signal = targets.iter().find_map(|(key, table)| {
+        let runner = table.get("runner")?;
+        let runner = runner
+            .as_str()
+            .map(String::from)
+            .or_else(|| runner.as_array().map(|args| {
+                args.iter().filter_map(|arg| arg.as_str()).collect::<Vec<_>>().join(" ")
+            }))?;
+        let program = runner.split_whitespace().next().unwrap_or_default();
+        let program = Path::new(program).file_name().and_then(|name| name.to_str()).unwrap_or(program);
+        let bare_metal_key = if key.starts_with("cfg(") {
+            key.replace(' ', "").contains("target_os=\"none\"")
+        } else {
+            is_bare_metal(key)
+        };
+        (bare_metal_key || FLASH_RUNNERS.contains(&program))
+            .then(|| format!("runner `{}` for target.{}", runner, key))
+    });
+    signal
+}
+
+/// Pick the project template matching the project's shape
+///
+/// Bare-metal targets win over wasm, wasm over server frameworks, and any
+/// remaining binary project is treated as a CLI. Pure libraries get no template.
+pub fn detect_template(
+    triples: &[String],
+    targets: &TargetAnalysis,
+    dependencies: &HashSet<String>,
+) -> Option<ProjectTemplate> {
+    let has_wasm_bindings = dependencies.contains("wasm-bindgen") && !targets.cdylibs.is_empty();
+    
+    if triples.iter().any(|t| is_bare_metal(t)) {
+        Some(ProjectTemplate::Embedded)
+    } else if triples.iter().any(|t| t.starts_with("wasm32")) || has_wasm_bindings {
+        Some(ProjectTemplate::WasmApp)
+    } else if targets.binaries.is_empty() {
+        None
+    } else if SERVER_FRAMEWORKS.iter().any(|name| dependencies.contains(*name)) {
+        Some(ProjectTemplate::Server)
+    } else {
+        Some(ProjectTemplate::Cli)
+    }
+}
+
+/// How often a workspace member changed recently
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberChurn {
+    /// Package name
+    pub package: String,
+    /// Number of commits touching the member within the window
+    pub commits: usize,
+}
+
+/// Measure per-member change frequency from git history
+///
+/// Counts the commits of the last `since_days` days touching files under each
+/// member's directory. Files are attributed to the most specific member, so a
+/// root package does not absorb its nested members' changes.
+pub fn member_churn(workspace_root: &Path, members: &[PackageInfo], since_days: u32) -> Result<Vec<MemberChurn>> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(workspace_root)
+        .args(["log", "--relative", "--name-only", "--format=commit:%H"])
+        .arg(format!("--since={}.days", since_days))
+        .output()
+        .context("Failed to run git log")?;
+    
+    if !output.status.success() {
+        anyhow::bail!(
+            "git log failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    
+    Ok(churn_from_git_log(&String::from_utf8_lossy(&output.stdout), workspace_root, members))
+}
+
+/// Attribute `git log --name-only --format=commit:%H` output to workspace members
+pub fn churn_from_git_log(log: &str, workspace_root: &Path, members: &[PackageInfo]) -> Vec<MemberChurn> {
+    // Member directories relative to the workspace root, most specific first
+    let mut dirs: Vec<(usize, PathBuf)> = members
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (i, m.path.strip_prefix(workspace_root).unwrap_or(&m.path).to_path_buf()))
+        .collect();
+    dirs.sort_by_key(|(_, dir)| std::cmp::Reverse(dir.components().count()));
+    
+    let mut counts = vec![0usize; members.len()];
+    let mut touched: HashSet<usize> = HashSet::new();
+    let mut flush = |touched: &mut HashSet<usize>| {
+        for index in touched.drain() {
+            counts[index] += 1;
+        }
+    };
+    
+    for line in log.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with("commit:") {
+            flush(&mut touched);
+            continue;
+        }
+        let file = Path::new(line);
+        if let Some((index, _)) = dirs.iter().find(|(_, dir)| file.starts_with(dir)) {
+            touched.insert(*index);
+        }
+    }
+    flush(&mut touched);
+    
+    members
+        .iter()
+        .zip(counts)
+        .map(|(member, commits)| MemberChurn { package: member.name.clone(), commits })
+        .collect()
+}
+
+/// One dependency in a [`DependencyReport`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DependencyBuildEntry {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Package source (registry or git URL), `None` for path dependencies
+    pub source: Option<String>,
+    /// License expression from the manifest
+    pub license: Option<String>,
+    /// Features enabled by feature resolution
+    pub features: Vec<String>,
+    /// Relative build cost (see [`estimate_build_cost`])
+    pub estimated_cost: u64,
+    /// Whether the package has a build script
+    pub build_script: bool,
+    /// Whether the package is a procedural macro
+    pub proc_macro: bool,
+    /// Whether the package is on the costliest dependency chain
+    pub on_critical_path: bool,
+}
+
+/// Per-dependency build report for vendoring, pruning and precompilation decisions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DependencyReport {
+    /// Workspace root the report was generated for
+    pub workspace_root: PathBuf,
+    /// Non-workspace packages, costliest first
+    pub dependencies: Vec<DependencyBuildEntry>,
+    /// Package names on the critical path, from the workspace member down
+    pub critical_path: Vec<String>,
+    /// Summed cost of the critical path
+    pub critical_path_cost: u64,
+    /// Git and `[patch]`-ed dependencies, costliest to rebuild first
+    pub uncached: Vec<UncachedDependency>,
+}
+
+/// Where a dependency that bypasses the crates.io cache comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum UncachedSource {
+    /// Git repository followed at a branch (the default branch when `None`)
+    GitBranch {
+        /// Repository URL
+        url: String,
+        /// Followed branch
+        branch: Option<String>,
+        /// Commit currently locked in `Cargo.lock`
+        locked_rev: Option<String>,
+    },
+    /// Git repository pinned to a rev or tag
+    GitPinned {
+        /// Repository URL
+        url: String,
+    },
+    /// Local checkout replacing a registry crate
+    Path,
+}
+
+/// A git or `[patch]`-ed dependency and what rebuilding it costs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UncachedDependency {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Where the package comes from
+    pub source: UncachedSource,
+    /// Whether the package replaces another through `[patch]`
+    pub patched: bool,
+    /// Packages, workspace members included, that rebuild along with it
+    pub dependents: usize,
+    /// Build cost of the package and its dependents (see [`estimate_build_cost`])
+    pub cascade_cost: u64,
+    /// Cascade cost weighted by how often the source moves
+    pub rebuild_cost: u64,
+}
+
+impl UncachedSource {
+    /// Parse a cargo source id (`git+https://host/repo?branch=main#<commit>`)
+    ///
+    /// Returns `None` for registry sources.
+    pub fn from_source_id(repr: &str) -> Option<Self> {
+        let rest = repr.strip_prefix("git+")?;
+        let (location, locked_rev) = match rest.split_once('#') {
+            Some((location, rev)) => (location, Some(rev.to_string())),
+            None => (rest, None),
+        };
+        let (url, query) = location.split_once('?').unwrap_or((location, ""));
+        let param = |name: &str| {
+            query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('=').map(str::to_string))
+        };
+        if param("rev").is_some() || param("tag").is_some() {
+            return Some(UncachedSource::GitPinned { url: url.to_string() });
+        }
+        Some(UncachedSource::GitBranch { url: url.to_string(), branch: param("branch"), locked_rev })
+    }
+
+    /// Relative rebuild frequency
+    ///
+    /// A followed branch moves with every `cargo update` and a local checkout
+    /// with every edit; a pinned rev only changes when the pin does.
+    pub fn rebuild_weight(&self) -> u64 {
+        match self {
+            UncachedSource::GitBranch { .. } | UncachedSource::Path => 4,
+            UncachedSource::GitPinned { .. } => 1,
+        }
+    }
+}
+
+/// Relative build cost of a package
+///
+/// One unit per KiB of Rust source (at least one). Build scripts add a fixed
+/// 8 units for compiling and running them, and proc macros count double since
+/// nothing that uses them can start compiling before they are done.
+pub fn estimate_build_cost(source_bytes: u64, build_script: bool, proc_macro: bool) -> u64 {
+    let mut cost = (source_bytes / 1024).max(1);
+    if build_script {
+        cost += 8;
+    }
+    if proc_macro {
+        cost *= 2;
+    }
+    cost
+}
+
+/// Generate a dependency build report for the project at `manifest_path`
+///
+/// Only normal and build dependencies count toward the critical path; dev
+/// dependencies are not needed to build the workspace members themselves.
+pub fn dependency_report(manifest_path: Option<&Path>) -> Result<DependencyReport> {
+    let mut cmd = MetadataCommand::new();
+    if let Some(path) = manifest_path {
+        cmd.manifest_path(path);
+    }
+    let metadata = cmd.exec()
+        .context("Failed to execute cargo metadata")?;
+    Ok(dependency_report_from(&metadata))
+}
+
+/// Export the dependency graph of the project at `manifest_path` (see [`graph::export_graph_from`])
+pub fn export_graph(
+    manifest_path: Option<&Path>,
+    format: graph::GraphFormat,
+    options: &graph::GraphOptions,
+) -> Result<String> {
+    let mut cmd = MetadataCommand::new();
+    if let Some(path) = manifest_path {
+        cmd.manifest_path(path);
+    }
+    let metadata = cmd.exec()
+        .context("Failed to execute cargo metadata")?;
+    Ok(graph::export_graph_from(&metadata, format, options))
+}
+
+/// Build the dependency report from already loaded metadata
+pub fn dependency_report_from(metadata: &Metadata) -> DependencyReport {
+    let packages: HashMap<&PackageId, &Package> = metadata.packages
+        .iter()
+        .map(|p| (&p.id, p))
+        .collect();
+    let nodes: HashMap<&PackageId, &Node> = metadata.resolve
+        .iter()
+        .flat_map(|r| r.nodes.iter())
+        .map(|n| (&n.id, n))
+        .collect();
+    
+    let costs = estimated_costs(metadata);
+    let (path, critical_path_cost) = critical_path(&metadata.workspace_members, &nodes, &costs);
+    let critical: HashSet<&PackageId> = path.iter().copied().collect();
+    let critical_path = path.iter().filter_map(|id| packages.get(id)).map(|p| p.name.clone()).collect();
+    
+    let mut dependencies: Vec<DependencyBuildEntry> = metadata.packages
+        .iter()
+        .filter(|p| !metadata.workspace_members.contains(&p.id))
+        .map(|package| {
+            let has_kind = |kind: &str| package.targets.iter().any(|t| t.kind.iter().any(|k| k == kind));
+            DependencyBuildEntry {
+                name: package.name.clone(),
+                version: package.version.to_string(),
+                source: package.source.as_ref().map(|s| s.repr.clone()),
+                license: package.license.clone(),
+                features: nodes.get(&package.id).map(|n| n.features.clone()).unwrap_or_default(),
+                estimated_cost: costs.get(&package.id).copied().unwrap_or(1),
+                build_script: has_kind("custom-build"),
+                proc_macro: has_kind("proc-macro"),
+                on_critical_path: critical.contains(&package.id),
+            }
+        })
+        .collect();
+    dependencies.sort_by(|a, b| {
+        b.estimated_cost.cmp(&a.estimated_cost)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.version.cmp(&b.version))
+    });
+    
+    let workspace_root: PathBuf = metadata.workspace_root.clone().into();
+    let uncached = uncached_dependencies(metadata, &workspace_root, &nodes, &costs);
+    DependencyReport {
+        workspace_root,
+        dependencies,
+        critical_path,
+        critical_path_cost,
+        uncached,
+    }
+}
+
+/// Estimated build cost of every package (see [`estimate_build_cost`])
+fn estimated_costs(metadata: &Metadata) -> HashMap<&PackageId, u64> {
+    // Summing sources reads every package directory, the slow part for large graphs
+    let package_costs = parallel::par_map(&metadata.packages, |package| {
+        let has_kind = |kind: &str| package.targets.iter().any(|t| t.kind.iter().any(|k| k == kind));
+        let root = package.manifest_path.parent().map(|p| p.as_std_path()).unwrap_or(Path::new("."));
+        estimate_build_cost(
+            rust_source_bytes(root),
+            has_kind("custom-build"),
+            has_kind("proc-macro"),
+        )
+    });
+    metadata.packages.iter().map(|p| &p.id).zip(package_costs).collect()
+}
+
+/// Costliest chain from one of `roots` down through normal and build dependencies, with its cost
+fn critical_path<'a>(
+    roots: impl IntoIterator<Item = &'a PackageId>,
+    nodes: &HashMap<&'a PackageId, &'a Node>,
+    costs: &HashMap<&'a PackageId, u64>,
+) -> (Vec<&'a PackageId>, u64) {
+    // Memoized per package
+    let mut chains: HashMap<&PackageId, (u64, Option<&PackageId>)> = HashMap::new();
+    let mut in_progress = HashSet::new();
+    let mut start: Option<(&PackageId, u64)> = None;
+    for root in roots {
+        let cost = chain_cost(root, nodes, costs, &mut chains, &mut in_progress);
+        if start.is_none_or(|(_, best)| cost > best) {
+            start = Some((root, cost));
+        }
+    }
+    
+    let mut path: Vec<&PackageId> = Vec::new();
+    let mut next = start.map(|(id, _)| id);
+    while let Some(id) = next {
+        if path.contains(&id) {
+            break;
+        }
+        path.push(id);
+        next = chains.get(id).and_then(|(_, next)| *next);
+    }
+    (path, start.map(|(_, cost)| cost).unwrap_or(0))
+}
+
+/// Names of the crates replaced in the `[patch]` tables of a workspace manifest
+pub fn patched_crates(manifest: &str) -> HashSet<String> {
+    let Ok(doc) = manifest.parse::<DocumentMut>() else {
+        return HashSet::new();
+    };
+    let Some(patch) = doc.get("patch").and_then(|p| p.as_table_like()) else {
+        return HashSet::new();
+    };
+    patch
+        .iter()
+        .filter_map(|(_, source)| source.as_table_like())
+        .flat_map(|source| source.iter())
+        .map(|(key, entry)| {
+            // `foo2 = { package = "foo", ... }` patches foo
+            entry
+                .get("package")
+                .and_then(|p| p.as_str())
+                .unwrap_or(key)
+                .to_string()
+        })
+        .collect()
+}
+
+/// Git and `[patch]`-ed packages with the cost of rebuilding them and their dependents
+fn uncached_dependencies(
+    metadata: &Metadata,
+    workspace_root: &Path,
+    nodes: &HashMap<&PackageId, &Node>,
+    costs: &HashMap<&PackageId, u64>,
+) -> Vec<UncachedDependency> {
+    let patched = std::fs::read_to_string(workspace_root.join("Cargo.toml"))
+        .map(|manifest| patched_crates(&manifest))
+        .unwrap_or_default();
+    
+    // Reverse edges through normal and build dependencies
+    let mut dependents_of: HashMap<&PackageId, Vec<&PackageId>> = HashMap::new();
+    for node in nodes.values() {
+        for dep in &node.deps {
+            if dep.dep_kinds.is_empty() || dep.dep_kinds.iter().any(|k| k.kind != DependencyKind::Development) {
+                dependents_of.entry(&dep.pkg).or_default().push(&node.id);
+            }
+        }
+    }
+    
+    let mut uncached: Vec<UncachedDependency> = metadata.packages
+        .iter()
+        .filter(|p| !metadata.workspace_members.contains(&p.id))
+        .filter_map(|package| {
+            let is_patched = patched.contains(package.name.as_str());
+            let source = match &package.source {
+                Some(source) => UncachedSource::from_source_id(&source.repr)?,
+                None if is_patched => UncachedSource::Path,
+                None => return None,
+            };
+            
+            let mut seen: HashSet<&PackageId> = HashSet::new();
+            let mut queue = vec![&package.id];
+            while let Some(id) = queue.pop() {
+                for dependent in dependents_of.get(id).map(Vec::as_slice).unwrap_or_default() {
+                    if seen.insert(dependent) {
+                        queue.push(dependent);
+                    }
+                }
+            }
+            let cascade_cost = costs.get(&package.id).copied().unwrap_or(1)
+                + seen.iter().map(|id| costs.get(id).copied().unwrap_or(1)).sum::<u64>();
+            Some(UncachedDependency {
+                name: package.name.clone(),
+                version: package.version.to_string(),
+                rebuild_cost: cascade_cost * source.rebuild_weight(),
+                source,
+                patched: is_patched,
+                dependents: seen.len(),
+                cascade_cost,
+            })
+        })
+        .collect();
+    uncached.sort_by(|a, b| b.rebuild_cost.cmp(&a.rebuild_cost).then_with(|| a.name.cmp(&b.name)));
+    uncached
+}
+
+/// Cost of the costliest chain starting at `id`, including `id` itself
+fn chain_cost<'a>(
+    id: &'a PackageId,
+    nodes: &HashMap<&'a PackageId, &'a Node>,
+    costs: &HashMap<&'a PackageId, u64>,
+    chains: &mut HashMap<&'a PackageId, (u64, Option<&'a PackageId>)>,
+    in_progress: &mut HashSet<&'a PackageId>,
+) -> u64 {
+    if let Some((cost, _)) = chains.get(id) {
+        return *cost;
+    }
+    if !in_progress.insert(id) {
+        // Cycles only arise through dev dependencies, which are filtered out
+        return 0;
+    }
+    
+    let mut best: Option<(&PackageId, u64)> = None;
+    for dep in nodes.get(id).map(|n| n.deps.as_slice()).unwrap_or_default() {
+        let needed = dep.dep_kinds.is_empty()
+            || dep.dep_kinds.iter().any(|k| k.kind != DependencyKind::Development);
+        if !needed {
+            continue;
+        }
+        let cost = chain_cost(&dep.pkg, nodes, costs, chains, in_progress);
+        if best.is_none_or(|(_, best)| cost > best) {
+            best = Some((&dep.pkg, cost));
+        }
+    }
+    in_progress.remove(id);
+    
+    let own = costs.get(id).copied().unwrap_or(1);
+    let total = own + best.map(|(_, cost)| cost).unwrap_or(0);
+    chains.insert(id, (total, best.map(|(id, _)| id)));
+    total
+}
+
+/// Total size of the `.rs` files under a package root, skipping `target` and hidden directories
+pub(crate) fn rust_source_bytes(root: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() && !name.starts_with('.') && name != "target" => rust_source_bytes(&path),
+                Ok(t) if t.is_file() && name.ends_with(".rs") => entry.metadata().map(|m| m.len()).unwrap_or(0),
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+impl DependencyReport {
+    /// Render the report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize dependency report")
+    }
+    
+    /// Render the report as CSV, one row per dependency
+    ///
+    /// Features are joined with `;` so each row keeps a fixed column count.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "name,version,source,license,estimated_cost,build_script,proc_macro,on_critical_path,features\n",
+        );
+        for dep in &self.dependencies {
+            let fields = [
+                dep.name.clone(),
+                dep.version.clone(),
+                dep.source.clone().unwrap_or_default(),
+                dep.license.clone().unwrap_or_default(),
+                dep.estimated_cost.to_string(),
+                dep.build_script.to_string(),
+                dep.proc_macro.to_string(),
+                dep.on_critical_path.to_string(),
+                dep.features.join(";"),
+            ];
+            let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
 }
 
 /// Count dependencies for a package
@@ -243,10 +1120,7 @@ fn count_package_dependencies(package: &Package) -> (usize, usize, usize) {
 }
 
 /// Count total and direct dependencies
-fn count_dependencies(
-    _metadata: &Metadata,
-    graph: &PackageGraph,
-) -> Result<(usize, usize)> {
+fn count_dependencies(graph: &PackageGraph) -> (usize, usize) {
     // Get workspace members
     let workspace_set = graph.resolve_workspace();
     
@@ -269,13 +1143,18 @@ fn count_dependencies(
     // Total dependencies is just the count of unique dependencies
     let total_deps = all_deps.len();
     
-    Ok((total_deps, direct_deps))
+    (total_deps, direct_deps)
 }
 
 /// Find dependency bottlenecks
 fn find_bottlenecks(graph: &PackageGraph) -> Result<Vec<BottleneckInfo>> {
     let mut bottlenecks = Vec::new();
     let workspace_set = graph.resolve_workspace();
+    let direct: HashSet<_> = workspace_set
+        .packages(DependencyDirection::Forward)
+        .flat_map(|p| p.direct_links())
+        .map(|link| link.to().id())
+        .collect();
     
     // For each package, count reverse dependencies
     for package in graph.packages() {
@@ -292,12 +1171,7 @@ fn find_bottlenecks(graph: &PackageGraph) -> Result<Vec<BottleneckInfo>> {
         if reverse_deps > 3 {
             // For simplicity, set depth to 1 for direct deps, 2 for others
             // A more sophisticated implementation would calculate actual depth
-            let is_direct = workspace_set
-                .packages(DependencyDirection::Forward)
-                .flat_map(|p| p.direct_links())
-                .any(|link| link.to().id() == package.id());
-            
-            let min_depth = if is_direct { 1 } else { 2 };
+            let min_depth = if direct.contains(package.id()) { 1 } else { 2 };
             
             bottlenecks.push(BottleneckInfo {
                 package: package.name().to_string(),
@@ -330,6 +1204,9 @@ fn analyze_targets(metadata: &Metadata) -> Result<TargetAnalysis> {
                 match kind.as_str() {
                     "bin" => targets.binaries.push(target.name.clone()),
                     "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" => {
+                        if kind == "cdylib" {
+                            targets.cdylibs.push(target.name.clone());
+                        }
                         targets.libraries.push(target.name.clone())
                     }
                     "test" => targets.tests.push(target.name.clone()),
@@ -347,10 +1224,7 @@ fn analyze_targets(metadata: &Metadata) -> Result<TargetAnalysis> {
 }
 
 /// Analyze feature usage
-fn analyze_features(
-    metadata: &Metadata,
-    graph: &PackageGraph,
-) -> Result<FeatureAnalysis> {
+fn analyze_features(metadata: &Metadata) -> Result<FeatureAnalysis> {
     let mut analysis = FeatureAnalysis::default();
     let mut feature_counts: Vec<(String, usize)> = Vec::new();
     
@@ -363,12 +1237,22 @@ fn analyze_features(
         }
     }
     
+    // Without a resolve only the members are loaded: count the features they request instead
+    if metadata.resolve.is_none() {
+        for dep in metadata.packages.iter().flat_map(|p| p.dependencies.iter()) {
+            if !dep.features.is_empty() {
+                feature_counts.push((dep.name.clone(), dep.features.len()));
+                analysis.total_features += dep.features.len();
+            }
+        }
+    }
+    
     // Sort by feature count
     feature_counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
     analysis.feature_heavy_packages = feature_counts.into_iter().take(5).collect();
     
     // Add suggestions for common optimizations
-    analysis.suggestions = generate_feature_suggestions(metadata, graph)?;
+    analysis.suggestions = generate_feature_suggestions(metadata)?;
     
     // Count enabled features (simplified - would need actual resolution in practice)
     analysis.enabled_features = analysis.total_features / 2; // Rough estimate
@@ -377,10 +1261,7 @@ fn analyze_features(
 }
 
 /// Generate feature optimization suggestions
-fn generate_feature_suggestions(
-    metadata: &Metadata,
-    _graph: &PackageGraph,
-) -> Result<Vec<FeatureSuggestion>> {
+fn generate_feature_suggestions(metadata: &Metadata) -> Result<Vec<FeatureSuggestion>> {
     let mut suggestions = Vec::new();
     
     // Check for common heavy dependencies
@@ -391,32 +1272,26 @@ fn generate_feature_suggestions(
         }
         
         match package.name.as_str() {
-            "tokio" => {
-                if package.features.len() > 10 {
-                    suggestions.push(FeatureSuggestion {
-                        package: "tokio".to_string(),
-                        suggestion: "Consider using only required tokio features instead of 'full'".to_string(),
-                        impact: ImpactLevel::High,
-                    });
-                }
+            "tokio" if package.features.len() > 10 => {
+                suggestions.push(FeatureSuggestion {
+                    package: "tokio".to_string(),
+                    suggestion: "Consider using only required tokio features instead of 'full'".to_string(),
+                    impact: ImpactLevel::High,
+                });
             }
-            "serde" => {
-                if !package.features.contains_key("derive") {
-                    suggestions.push(FeatureSuggestion {
-                        package: "serde".to_string(),
-                        suggestion: "serde without 'derive' feature detected - ensure this is intentional".to_string(),
-                        impact: ImpactLevel::Low,
-                    });
-                }
+            "serde" if !package.features.contains_key("derive") => {
+                suggestions.push(FeatureSuggestion {
+                    package: "serde".to_string(),
+                    suggestion: "serde without 'derive' feature detected - ensure this is intentional".to_string(),
+                    impact: ImpactLevel::Low,
+                });
             }
-            "reqwest" => {
-                if package.features.contains_key("blocking") && package.features.contains_key("tokio") {
-                    suggestions.push(FeatureSuggestion {
-                        package: "reqwest".to_string(),
-                        suggestion: "Both blocking and async features enabled - consider using only one".to_string(),
-                        impact: ImpactLevel::Medium,
-                    });
-                }
+            "reqwest" if package.features.contains_key("blocking") && package.features.contains_key("tokio") => {
+                suggestions.push(FeatureSuggestion {
+                    package: "reqwest".to_string(),
+                    suggestion: "Both blocking and async features enabled - consider using only one".to_string(),
+                    impact: ImpactLevel::Medium,
+                });
             }
             _ => {}
         }
@@ -426,23 +1301,35 @@ fn generate_feature_suggestions(
 }
 
 /// Calculate build metrics
+///
+/// Without a graph (a preliminary analysis) the crate count is the members
+/// plus `dependencies`, and the parallelization factor is left at 1.
 fn calculate_build_metrics(
     metadata: &Metadata,
-    graph: &PackageGraph,
-    _workspace_members: &[PackageInfo],
+    graph: Option<&PackageGraph>,
+    dependencies: usize,
 ) -> Result<BuildMetrics> {
-    let mut metrics = BuildMetrics::default();
-    
-    // Count crates to compile
-    metrics.crate_count = metadata.packages.len();
-    
-    // Estimate lines of code (very rough estimate based on dependency count)
-    metrics.estimated_loc = metadata.packages.len() * 1000; // Rough estimate
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let sources = sources::source_stats(workspace_root, &sources::AnalysisSettings::load(workspace_root));
+    let dependencies = match graph {
+        Some(_) => metadata.packages.len().saturating_sub(metadata.workspace_members.len()),
+        None => dependencies,
+    };
+    let mut metrics = BuildMetrics {
+        // Count crates to compile
+        crate_count: metadata.workspace_members.len() + dependencies,
+        // Dependencies are estimated (very roughly), the workspace is counted
+        estimated_loc: sources.lines + dependencies * 1000,
+        sources,
+        ..Default::default()
+    };
     
     // Calculate parallelization factor
     // Higher is better - indicates more opportunities for parallel compilation
-    let workspace_set = graph.resolve_workspace();
-    let max_chain_length = calculate_max_dependency_chain(graph, &workspace_set)?;
+    let max_chain_length = match graph {
+        Some(graph) => calculate_max_dependency_chain(graph, &graph.resolve_workspace())?,
+        None => 0,
+    };
     metrics.parallelization_factor = if max_chain_length > 0 {
         metrics.crate_count as f32 / max_chain_length as f32
     } else {
@@ -450,20 +1337,16 @@ fn calculate_build_metrics(
     };
     
     // Count internal dependencies between workspace members
-    for member_id in &metadata.workspace_members {
-        if let Some(package) = metadata.packages.iter().find(|p| &p.id == member_id) {
-            for dep in &package.dependencies {
-                if metadata.workspace_members.iter().any(|m| {
-                    metadata.packages.iter()
-                        .find(|p| &p.id == m)
-                        .map(|p| p.name == dep.name)
-                        .unwrap_or(false)
-                }) {
-                    metrics.internal_dependencies += 1;
-                }
-            }
-        }
-    }
+    let members: Vec<&Package> = metadata.packages
+        .iter()
+        .filter(|p| metadata.workspace_members.contains(&p.id))
+        .collect();
+    let member_names: HashSet<&str> = members.iter().map(|p| p.name.as_str()).collect();
+    metrics.internal_dependencies = members
+        .iter()
+        .flat_map(|package| package.dependencies.iter())
+        .filter(|dep| member_names.contains(dep.name.as_str()))
+        .count();
     
     Ok(metrics)
 }
@@ -473,15 +1356,11 @@ fn calculate_max_dependency_chain(
     _graph: &PackageGraph,
     workspace_set: &PackageSet<'_>,
 ) -> Result<usize> {
-    let mut max_length = 0;
-    
     // For each workspace member, find the longest dependency chain
-    for package in workspace_set.packages(DependencyDirection::Forward) {
-        let chain_length = calculate_chain_length_from(_graph, package)?;
-        max_length = max_length.max(chain_length);
-    }
-    
-    Ok(max_length)
+    let members: Vec<PackageMetadata<'_>> = workspace_set.packages(DependencyDirection::Forward).collect();
+    parallel::par_map(&members, |package| calculate_chain_length_from(_graph, *package))
+        .into_iter()
+        .try_fold(0, |max_length, chain_length| Ok(max_length.max(chain_length?)))
 }
 
 /// Calculate dependency chain length from a package
@@ -515,16 +1394,51 @@ fn calculate_chain_length_from(
 }
 
 impl ProjectAnalysis {
+    /// Analyze a project without blocking the async runtime (feature `async`)
+    ///
+    /// Runs [`analyze_project`] on tokio's blocking thread pool: the analysis
+    /// waits on `cargo metadata` and then walks the dependency graph, neither
+    /// of which is cooperative. Must be called from within a tokio runtime.
+    #[cfg(feature = "async")]
+    pub async fn load_async(manifest_path: Option<PathBuf>) -> Result<ProjectAnalysis> {
+        tokio::task::spawn_blocking(move || analyze_project(manifest_path.as_deref()))
+            .await
+            .context("Project analysis task failed")?
+    }
+    
+    /// Replace a preliminary analysis with the full one once `Cargo.lock` exists
+    ///
+    /// Returns the analysis unchanged when it is not preliminary or the
+    /// project still has no lock file.
+    pub fn refresh(self) -> Result<ProjectAnalysis> {
+        let manifest = self.workspace_root.join("Cargo.toml");
+        if !self.preliminary || !has_lockfile(Some(&manifest)) {
+            return Ok(self);
+        }
+        info!("Cargo.lock appeared, re-running the full analysis");
+        analyze_project(Some(&manifest))
+    }
+    
     /// Get a summary of the analysis suitable for display
     pub fn summary(&self) -> String {
         let mut summary = String::new();
         
+        if self.preliminary {
+            summary.push_str("Preliminary: no Cargo.lock yet, dependencies are counted from the manifests\n");
+        }
         summary.push_str(&format!("Workspace root: {}\n", self.workspace_root.display()));
         summary.push_str(&format!("Is workspace: {}\n", self.is_workspace));
         summary.push_str(&format!("Workspace members: {}\n", self.workspace_members.len()));
         summary.push_str(&format!("Total dependencies: {}\n", self.total_dependencies));
         summary.push_str(&format!("Direct dependencies: {}\n", self.direct_dependencies));
         
+        if !self.member_diagnostics.is_empty() {
+            summary.push_str("\nMembers left out of the analysis:\n");
+            for diagnostic in &self.member_diagnostics {
+                summary.push_str(&format!("  - {}: {}\n", diagnostic.path.display(), diagnostic.error));
+            }
+        }
+        
         if !self.bottlenecks.is_empty() {
             summary.push_str("\nTop dependency bottlenecks:\n");
             for (i, bottleneck) in self.bottlenecks.iter().take(3).enumerate() {
@@ -539,15 +1453,16 @@ impl ProjectAnalysis {
             }
         }
         
-        summary.push_str(&format!("\nBuild targets:\n"));
+        summary.push_str("\nBuild targets:\n");
         summary.push_str(&format!("  Binaries: {}\n", self.targets.binaries.len()));
         summary.push_str(&format!("  Libraries: {}\n", self.targets.libraries.len()));
         summary.push_str(&format!("  Tests: {}\n", self.targets.tests.len()));
         summary.push_str(&format!("  Examples: {}\n", self.targets.examples.len()));
+        summary.push_str(&format!("  Benches: {}\n", self.targets.benches.len()));
         summary.push_str(&format!("  Build scripts: {}\n", self.targets.build_scripts));
         summary.push_str(&format!("  Proc macros: {}\n", self.targets.proc_macros));
         
-        summary.push_str(&format!("\nFeature analysis:\n"));
+        summary.push_str("\nFeature analysis:\n");
         summary.push_str(&format!("  Total features: {}\n", self.features.total_features));
         summary.push_str(&format!("  Enabled features: ~{}\n", self.features.enabled_features));
         
@@ -563,11 +1478,36 @@ impl ProjectAnalysis {
             }
         }
         
-        summary.push_str(&format!("\nBuild metrics:\n"));
+        summary.push_str("\nBuild metrics:\n");
         summary.push_str(&format!("  Crates to compile: {}\n", self.metrics.crate_count));
         summary.push_str(&format!("  Parallelization factor: {:.1}\n", self.metrics.parallelization_factor));
         summary.push_str(&format!("  Internal dependencies: {}\n", self.metrics.internal_dependencies));
         
+        let suggestions = self.target_costs.suggestions();
+        if !suggestions.is_empty() {
+            summary.push_str("\nExamples and benches built by --all-targets:\n");
+            for suggestion in &suggestions {
+                summary.push_str(&format!(
+                    "  - {}: {} {}, ~{}% of the workspace build ({:?})\n",
+                    suggestion.package,
+                    suggestion.targets,
+                    suggestion.kind.directory(),
+                    suggestion.savings_percent,
+                    suggestion.remedy
+                ));
+            }
+        }
+        
+        if let Some(suggestion) = &self.default_members {
+            summary.push_str("\nSuggested workspace.default-members:\n");
+            summary.push_str(&format!("  Build by default: {}\n", suggestion.members.join(", ")));
+            summary.push_str(&format!("  Skipped by plain `cargo build`: {}\n", suggestion.excluded.join(", ")));
+        }
+        
+        if let Some(template) = &self.template {
+            summary.push_str(&format!("\nDetected template: {}\n", template));
+        }
+        
         summary
     }
 }
@@ -576,6 +1516,31 @@ impl ProjectAnalysis {
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_uncached_source_parsing() {
+        assert_eq!(
+            UncachedSource::from_source_id("git+https://github.com/o/r?branch=main#0f1e2d"),
+            Some(UncachedSource::GitBranch {
+                url: "https://github.com/o/r".to_string(),
+                branch: Some("main".to_string()),
+                locked_rev: Some("0f1e2d".to_string()),
+            })
+        );
+        assert_eq!(
+            UncachedSource::from_source_id("git+https://github.com/o/r?rev=0f1e2d#0f1e2d"),
+            Some(UncachedSource::GitPinned { url: "https://github.com/o/r".to_string() })
+        );
+        assert!(matches!(
+            UncachedSource::from_source_id("git+https://github.com/o/r#0f1e2d"),
+            Some(UncachedSource::GitBranch { branch: None, .. })
+        ));
+        assert_eq!(UncachedSource::from_source_id("registry+https://github.com/rust-lang/crates.io-index"), None);
+        
+        let manifest = "[patch.crates-io]\nfoo = { path = \"../foo\" }\nbar2 = { git = \"https://x\", package = \"bar\" }\n";
+        let patched = patched_crates(manifest);
+        assert!(patched.contains("foo") && patched.contains("bar") && !patched.contains("bar2"));
+    }
+    
     #[test]
     fn test_impact_level() {
         assert_eq!(
@@ -0,0 +1,269 @@
+//! What cargo-optimize itself costs the builds of projects depending on it.
+//!
+//! As a build-dependency, cargo-optimize and the crates only it pulls in
+//! are compiled before the project's build script can run. [`measure`] runs
+//! a clean `cargo build --timings` into a scratch target directory and
+//! [`weigh`] attributes the compile units of those crates, read from the
+//! timings report, to cargo-optimize. [`from_last_timings`] does the same
+//! with the report of the user's own last `--timings` build and the metadata
+//! a full analysis cached, without running cargo. When the cost exceeds
+//! `global.build_weight_warn_ratio` of the project's own compile time,
+//! [`BuildWeight::advice`] recommends the slim MVP apply of the
+//! `cargo optimize` binary, which adds nothing to the build.
+
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand, PackageId};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use super::quick::{load_cache, METADATA_CACHE};
+
+/// Package name of this crate
+pub const CRATE_NAME: &str = "cargo-optimize";
+
+/// Latest timings report, relative to the target directory
+pub const TIMINGS_REPORT: &str = "cargo-timings/cargo-timing.html";
+
+/// Scratch target directory of [`measure`], relative to the project's
+pub const MEASURE_TARGET_DIR: &str = "cargo-optimize/build-weight";
+
+/// Default share of the project's own compile time above which the cost is reported
+pub const DEFAULT_WARN_RATIO: f64 = 0.25;
+
+/// One compile unit of a timings report
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UnitTiming {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Target description (e.g. `" syn"` or `" serde build-script"`)
+    #[serde(default)]
+    pub target: String,
+    /// Time the unit took, in seconds
+    pub duration: f64,
+}
+
+/// Compile units of the `UNIT_DATA` table embedded in a `cargo build --timings` report
+pub fn parse_timings(html: &str) -> Result<Vec<UnitTiming>> {
+    const START: &str = "const UNIT_DATA = ";
+    let start = html.find(START).context("No unit data in the timings report")? + START.len();
+    let end = html[start..].find("\n];").context("Unterminated unit data in the timings report")? + start + 2;
+    serde_json::from_str(&html[start..end]).context("Failed to parse the unit data of the timings report")
+}
+
+/// Packages, by name and version, that only cargo-optimize brings into the build
+///
+/// Walks the resolved graph from the workspace members twice: once through
+/// everything, once without entering cargo-optimize. Packages reachable only
+/// the first way, cargo-optimize included, are its weight. Empty when
+/// cargo-optimize is not a dependency or is itself a workspace member.
+pub fn exclusive_packages(metadata: &Metadata) -> BTreeSet<(String, String)> {
+    let Some(resolve) = &metadata.resolve else {
+        return BTreeSet::new();
+    };
+    let ours: HashSet<&PackageId> = metadata
+        .packages
+        .iter()
+        .filter(|package| package.name == CRATE_NAME)
+        .map(|package| &package.id)
+        .collect();
+    if ours.is_empty() || metadata.workspace_members.iter().any(|member| ours.contains(member)) {
+        return BTreeSet::new();
+    }
+    let deps: HashMap<&PackageId, Vec<&PackageId>> = resolve
+        .nodes
+        .iter()
+        .map(|node| (&node.id, node.deps.iter().map(|dep| &dep.pkg).collect()))
+        .collect();
+    let reach = |skip_ours: bool| {
+        let mut seen: HashSet<&PackageId> = HashSet::new();
+        let mut stack: Vec<&PackageId> = metadata.workspace_members.iter().collect();
+        while let Some(id) = stack.pop() {
+            if (skip_ours && ours.contains(id)) || !seen.insert(id) {
+                continue;
+            }
+            stack.extend(deps.get(id).into_iter().flatten());
+        }
+        seen
+    };
+    let without = reach(true);
+    let names: HashMap<&PackageId, (String, String)> = metadata
+        .packages
+        .iter()
+        .map(|package| (&package.id, (package.name.clone(), package.version.to_string())))
+        .collect();
+    reach(false)
+        .into_iter()
+        .filter(|id| !without.contains(id))
+        .filter_map(|id| names.get(id).cloned())
+        .collect()
+}
+
+/// Compile time attributed to cargo-optimize in one build
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildWeight {
+    /// Packages attributed to cargo-optimize, itself included
+    pub packages: usize,
+    /// Their compile units
+    pub units: usize,
+    /// Their compile time, summed over units, in seconds
+    pub secs: f64,
+    /// Compile units of the whole build
+    pub total_units: usize,
+    /// Compile time of the whole build, summed over units, in seconds
+    pub total_secs: f64,
+}
+
+impl BuildWeight {
+    /// Compile time of everything else, the project's own build
+    pub fn project_secs(&self) -> f64 {
+        (self.total_secs - self.secs).max(0.0)
+    }
+
+    /// cargo-optimize's compile time relative to the project's own
+    pub fn ratio(&self) -> f64 {
+        match self.project_secs() {
+            secs if secs > 0.0 => self.secs / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Recommendation to switch to the slim MVP apply, if the cost exceeds `warn_ratio`
+    pub fn advice(&self, warn_ratio: f64) -> Option<String> {
+        if self.units == 0 || self.ratio() <= warn_ratio {
+            return None;
+        }
+        Some(format!(
+            "cargo-optimize compiles for {:.1}s as a build-dependency, {:.0}% on top of the project's own {:.1}s \
+             ({} units of {} packages); drop the build-dependency and run the slim MVP apply of the \
+             `cargo optimize` binary instead (`cargo install cargo-optimize`, then \
+             `cargo optimize install-alias --installed`)",
+            self.secs,
+            self.ratio() * 100.0,
+            self.project_secs(),
+            self.units,
+            self.packages
+        ))
+    }
+}
+
+impl fmt::Display for BuildWeight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} units of {} packages, {:.1}s of {:.1}s compile time",
+            self.units, self.packages, self.secs, self.total_secs
+        )
+    }
+}
+
+/// Attribute the units of a timings report to the `exclusive` packages
+pub fn weigh(units: &[UnitTiming], exclusive: &BTreeSet<(String, String)>) -> BuildWeight {
+    let (ours, others): (Vec<&UnitTiming>, Vec<&UnitTiming>) = units
+        .iter()
+        .partition(|unit| exclusive.contains(&(unit.name.clone(), unit.version.clone())));
+    let secs = ours.iter().map(|unit| unit.duration).sum::<f64>();
+    BuildWeight {
+        packages: exclusive.len(),
+        units: ours.len(),
+        secs,
+        total_units: units.len(),
+        total_secs: secs + others.iter().map(|unit| unit.duration).sum::<f64>(),
+    }
+}
+
+/// Weight in the last `--timings` build in `target_dir`, without running cargo
+///
+/// `None` without a timings report or cached metadata, or when the report
+/// has no units of cargo-optimize, e.g. because they were already fresh.
+pub fn from_last_timings(target_dir: &Path) -> Option<BuildWeight> {
+    let html = fs::read_to_string(target_dir.join(TIMINGS_REPORT)).ok()?;
+    let units = match parse_timings(&html) {
+        Ok(units) => units,
+        Err(e) => {
+            debug!("Ignoring the timings report: {:#}", e);
+            return None;
+        }
+    };
+    let (metadata, _) = load_cache(&target_dir.join(METADATA_CACHE))?;
+    Some(weigh(&units, &exclusive_packages(&metadata))).filter(|weight| weight.units > 0)
+}
+
+/// Measure the weight with a clean `cargo build --timings` of the project at `project_root`
+///
+/// Builds into a scratch directory under the project's target directory,
+/// emptied first so every unit is compiled, which takes as long as a clean
+/// build. Fails when cargo-optimize is not a dependency of the project.
+pub fn measure(project_root: &Path) -> Result<BuildWeight> {
+    crate::output::ensure_enabled()?;
+    let manifest = project_root.join("Cargo.toml");
+    let metadata = MetadataCommand::new()
+        .manifest_path(&manifest)
+        .exec()
+        .context("Failed to run cargo metadata")?;
+    let exclusive = exclusive_packages(&metadata);
+    if exclusive.is_empty() {
+        bail!("{} is not a dependency of {}", CRATE_NAME, manifest.display());
+    }
+
+    let target_dir: PathBuf = metadata.target_directory.as_std_path().join(MEASURE_TARGET_DIR);
+    if target_dir.exists() {
+        fs::remove_dir_all(&target_dir).with_context(|| format!("Failed to empty {:?}", target_dir))?;
+    }
+    let output = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+        .args(["build", "--timings", "--manifest-path"])
+        .arg(&manifest)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .output()
+        .context("Failed to run cargo build")?;
+    if !output.status.success() {
+        bail!("cargo build --timings failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+    let html = fs::read_to_string(target_dir.join(TIMINGS_REPORT)).context("cargo build wrote no timings report")?;
+    Ok(weigh(&parse_timings(&html)?, &exclusive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"<script>
+DURATION = 12;
+const UNIT_DATA = [
+  {"i": 0, "name": "serde", "version": "1.0.200", "mode": "todo", "target": " serde", "start": 0.1, "duration": 3.0},
+  {"i": 1, "name": "guppy", "version": "0.17.4", "mode": "todo", "target": " guppy", "start": 0.2, "duration": 4.0},
+  {"i": 2, "name": "cargo-optimize", "version": "0.1.0", "mode": "todo", "target": " cargo-optimize", "start": 4.0, "duration": 2.0},
+  {"i": 3, "name": "app", "version": "0.1.0", "mode": "todo", "target": " app build-script", "start": 6.0, "duration": 6.0}
+];
+const CONCURRENCY_DATA = [];
+</script>"#;
+
+    #[test]
+    fn test_weigh_timings_report() {
+        let units = parse_timings(REPORT).unwrap();
+        assert_eq!(units.len(), 4);
+        assert_eq!(units[3].target, " app build-script");
+        assert!(parse_timings("<html></html>").is_err());
+
+        let exclusive = BTreeSet::from([
+            ("cargo-optimize".to_string(), "0.1.0".to_string()),
+            ("guppy".to_string(), "0.17.4".to_string()),
+        ]);
+        let weight = weigh(&units, &exclusive);
+        assert_eq!((weight.packages, weight.units, weight.total_units), (2, 2, 4));
+        assert!((weight.secs - 6.0).abs() < 1e-9);
+        assert!((weight.ratio() - 6.0 / 9.0).abs() < 1e-9);
+        let advice = weight.advice(DEFAULT_WARN_RATIO).unwrap();
+        assert!(advice.starts_with("cargo-optimize compiles for 6.0s as a build-dependency, 67% on top of the project's own 9.0s (2 units of 2 packages)"), "{}", advice);
+        assert_eq!(weight.to_string(), "2 units of 2 packages, 6.0s of 15.0s compile time");
+        assert_eq!(weight.advice(0.7), None);
+        assert_eq!(weigh(&units, &BTreeSet::new()).advice(0.0), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! The workspace dependency graph as DOT or Mermaid, annotated with build cost.
+//!
+//! [`export_graph_from`] draws the packages the workspace members build,
+//! each labeled with its cost: the seconds the last `cargo build --timings`
+//! spent on it when that report covers every package drawn, the estimate of
+//! [`super::estimate_build_cost`] otherwise. The costliest chain from a
+//! member down, the one no amount of parallelism shortens, is highlighted.
+//! Graphs of large workspaces quickly become unreadable, so [`GraphOptions`]
+//! narrows them to the dependencies of one package, hides subtrees and cheap
+//! packages, and caps the number of nodes; members and the critical path are
+//! always kept.
+
+use cargo_metadata::{DependencyKind, Metadata, Node, Package, PackageId};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::fs;
+use tracing::debug;
+
+use super::build_weight::{parse_timings, TIMINGS_REPORT};
+use super::{critical_path, estimated_costs};
+
+/// Default cap on the number of packages drawn
+pub const DEFAULT_MAX_NODES: usize = 150;
+
+/// Color of the critical path
+const CRITICAL_COLOR: &str = "#d62728";
+
+/// Output format of [`export_graph_from`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz (`dot -Tsvg`)
+    Dot,
+    /// Mermaid flowchart, rendered by GitHub and GitLab in Markdown
+    Mermaid,
+}
+
+impl GraphFormat {
+    /// Parse a format name as given on the command line
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dot" | "graphviz" => Some(GraphFormat::Dot),
+            "mermaid" => Some(GraphFormat::Mermaid),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Mermaid => "mermaid",
+        })
+    }
+}
+
+/// What [`export_graph_from`] draws
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphOptions {
+    /// Most packages drawn; the costliest are kept
+    pub max_nodes: usize,
+    /// Packages cheaper than this are left out (estimated units or measured milliseconds)
+    pub min_cost: u64,
+    /// Also draw the dev-dependencies of the workspace members
+    pub include_dev: bool,
+    /// Only draw this package and what it depends on instead of the whole workspace
+    pub focus: Option<String>,
+    /// Packages left out together with whatever only they pull in
+    pub exclude: Vec<String>,
+}
+
+impl Default for GraphOptions {
+    fn default() -> Self {
+        Self { max_nodes: DEFAULT_MAX_NODES, min_cost: 0, include_dev: false, focus: None, exclude: Vec::new() }
+    }
+}
+
+/// How the costs in the graph were obtained
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CostSource {
+    /// Units of [`super::estimate_build_cost`]
+    Estimated,
+    /// Milliseconds from the last timings report
+    Measured,
+}
+
+impl CostSource {
+    fn label(self, cost: u64) -> String {
+        match self {
+            CostSource::Estimated => format!("{} units", cost),
+            CostSource::Measured => format!("{:.1}s", cost as f64 / 1000.0),
+        }
+    }
+}
+
+/// Render the dependency graph of already loaded metadata
+///
+/// Edges point from a package to its dependencies. Dev-dependency and
+/// build-dependency edges are drawn dashed. The critical path is computed
+/// over the whole graph, so an excluded package can leave a gap in it.
+pub fn export_graph_from(metadata: &Metadata, format: GraphFormat, options: &GraphOptions) -> String {
+    let packages: HashMap<&PackageId, &Package> = metadata.packages.iter().map(|p| (&p.id, p)).collect();
+    let nodes: HashMap<&PackageId, &Node> =
+        metadata.resolve.iter().flat_map(|r| r.nodes.iter()).map(|n| (&n.id, n)).collect();
+    let excluded = |id: &PackageId| packages.get(id).is_some_and(|p| options.exclude.contains(&p.name));
+
+    let roots: Vec<&PackageId> = match &options.focus {
+        Some(name) => metadata.packages.iter().filter(|p| &p.name == name).map(|p| &p.id).collect(),
+        None => metadata.workspace_members.iter().collect(),
+    };
+
+    // Packages reachable from the roots, with the edges between them
+    let mut reachable: Vec<&PackageId> = Vec::new();
+    let mut edges: Vec<(&PackageId, &PackageId, bool)> = Vec::new();
+    let mut queue: Vec<&PackageId> = roots.iter().copied().filter(|id| !excluded(id)).collect();
+    let mut seen: HashSet<&PackageId> = queue.iter().copied().collect();
+    while let Some(id) = queue.pop() {
+        reachable.push(id);
+        for dep in nodes.get(id).map(|n| n.deps.as_slice()).unwrap_or_default() {
+            let normal = dep.dep_kinds.is_empty() || dep.dep_kinds.iter().any(|k| k.kind == DependencyKind::Normal);
+            let needed =
+                dep.dep_kinds.is_empty() || dep.dep_kinds.iter().any(|k| k.kind != DependencyKind::Development);
+            if !(needed || options.include_dev) || excluded(&dep.pkg) {
+                continue;
+            }
+            edges.push((id, &dep.pkg, normal));
+            if seen.insert(&dep.pkg) {
+                queue.push(&dep.pkg);
+            }
+        }
+    }
+
+    let (source, costs) = match measured_costs(metadata, &packages, &reachable) {
+        Some(costs) => (CostSource::Measured, costs),
+        None => (CostSource::Estimated, estimated_costs(metadata)),
+    };
+    let cost = |id: &PackageId| costs.get(id).copied().unwrap_or(1);
+    let (path, path_cost) = critical_path(roots.iter().copied(), &nodes, &costs);
+    let critical: HashSet<&PackageId> = path.iter().copied().collect();
+    let critical_edges: HashSet<(&PackageId, &PackageId)> = path.windows(2).map(|pair| (pair[0], pair[1])).collect();
+
+    // Roots and the critical path first, then the costliest of the rest up to the cap
+    let pinned = |id: &PackageId| roots.contains(&id) || critical.contains(id);
+    let mut ranked = reachable.clone();
+    ranked.sort_by(|a, b| {
+        pinned(b).cmp(&pinned(a)).then_with(|| cost(b).cmp(&cost(a))).then_with(|| a.repr.cmp(&b.repr))
+    });
+    let shown: HashSet<&PackageId> = ranked
+        .iter()
+        .copied()
+        .filter(|id| pinned(id) || cost(id) >= options.min_cost)
+        .take(options.max_nodes.max(reachable.iter().filter(|id| pinned(id)).count()))
+        .collect();
+
+    let mut drawn: Vec<&Package> = shown.iter().filter_map(|id| packages.get(id).copied()).collect();
+    drawn.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+    let ids: HashMap<&PackageId, String> = drawn.iter().enumerate().map(|(i, p)| (&p.id, format!("n{}", i))).collect();
+    edges.retain(|(from, to, _)| ids.contains_key(from) && ids.contains_key(to));
+    edges.sort_by(|a, b| ids[a.0].cmp(&ids[b.0]).then_with(|| ids[a.1].cmp(&ids[b.1])));
+    edges.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1);
+
+    let mut notes = vec![match source {
+        CostSource::Estimated => "build cost estimated from the size of the sources".to_string(),
+        CostSource::Measured => "build time measured by the last `cargo build --timings`".to_string(),
+    }];
+    let names: Vec<String> = path.iter().filter_map(|id| packages.get(id)).map(|p| p.name.clone()).collect();
+    notes.push(format!("critical path: {} ({})", names.join(" -> "), source.label(path_cost)));
+    if drawn.len() < reachable.len() {
+        notes.push(format!("{} of {} packages shown", drawn.len(), reachable.len()));
+    }
+
+    let mut out = String::new();
+    let label = |package: &Package| format!("{} {}", package.name, package.version);
+    match format {
+        GraphFormat::Dot => {
+            out.push_str("digraph dependencies {\n");
+            for note in &notes {
+                let _ = writeln!(out, "    // {}", note);
+            }
+            out.push_str("    rankdir=LR;\n    node [shape=box, fontname=\"sans-serif\"];\n");
+            for package in &drawn {
+                let mut attributes =
+                    vec![format!("label=\"{}\\n{}\"", dot_escape(&label(package)), source.label(cost(&package.id)))];
+                if metadata.workspace_members.contains(&package.id) {
+                    attributes.push("peripheries=2".to_string());
+                }
+                if critical.contains(&package.id) {
+                    attributes.push(format!("color=\"{}\", penwidth=2", CRITICAL_COLOR));
+                }
+                let _ = writeln!(out, "    {} [{}];", ids[&package.id], attributes.join(", "));
+            }
+            for (from, to, normal) in &edges {
+                let mut attributes = Vec::new();
+                if !normal {
+                    attributes.push("style=dashed".to_string());
+                }
+                if critical_edges.contains(&(*from, *to)) {
+                    attributes.push(format!("color=\"{}\", penwidth=2", CRITICAL_COLOR));
+                }
+                let attributes =
+                    if attributes.is_empty() { String::new() } else { format!(" [{}]", attributes.join(", ")) };
+                let _ = writeln!(out, "    {} -> {}{};", ids[from], ids[to], attributes);
+            }
+            out.push_str("}\n");
+        }
+        GraphFormat::Mermaid => {
+            out.push_str("flowchart LR\n");
+            for note in &notes {
+                let _ = writeln!(out, "    %% {}", note);
+            }
+            for package in &drawn {
+                let text = format!("{}<br/>{}", mermaid_escape(&label(package)), source.label(cost(&package.id)));
+                let _ = writeln!(out, "    {}[\"{}\"]", ids[&package.id], text);
+            }
+            for (from, to, normal) in &edges {
+                let arrow = match (critical_edges.contains(&(*from, *to)), normal) {
+                    (true, _) => "==>",
+                    (false, true) => "-->",
+                    (false, false) => "-.->",
+                };
+                let _ = writeln!(out, "    {} {} {}", ids[from], arrow, ids[to]);
+            }
+            let class = |filter: &dyn Fn(&PackageId) -> bool| {
+                drawn.iter().filter(|p| filter(&p.id)).map(|p| ids[&p.id].as_str()).collect::<Vec<_>>().join(",")
+            };
+            let members = class(&|id| metadata.workspace_members.contains(id));
+            if !members.is_empty() {
+                let _ = writeln!(out, "    classDef member stroke-width:3px\n    class {} member", members);
+            }
+            let on_path = class(&|id| critical.contains(id));
+            if !on_path.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "    classDef critical stroke:{},stroke-width:3px\n    class {} critical",
+                    CRITICAL_COLOR, on_path
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Milliseconds per package from the last timings report, when it has every reachable package
+///
+/// Cargo only reports the units it compiled, so after an incremental build
+/// most packages are missing and the estimates are used instead.
+fn measured_costs<'a>(
+    metadata: &'a Metadata,
+    packages: &HashMap<&'a PackageId, &'a Package>,
+    reachable: &[&'a PackageId],
+) -> Option<HashMap<&'a PackageId, u64>> {
+    let html = fs::read_to_string(metadata.target_directory.as_std_path().join(TIMINGS_REPORT)).ok()?;
+    let units = match parse_timings(&html) {
+        Ok(units) => units,
+        Err(e) => {
+            debug!("Ignoring the timings report: {:#}", e);
+            return None;
+        }
+    };
+    let mut seconds: HashMap<(&str, &str), f64> = HashMap::new();
+    for unit in &units {
+        *seconds.entry((unit.name.as_str(), unit.version.as_str())).or_default() += unit.duration;
+    }
+    let mut costs = HashMap::new();
+    for package in &metadata.packages {
+        let version = package.version.to_string();
+        if let Some(duration) = seconds.get(&(package.name.as_str(), version.as_str())) {
+            costs.insert(&package.id, (duration * 1000.0).round().max(1.0) as u64);
+        }
+    }
+    let complete = reachable.iter().all(|id| costs.contains_key(id) || !packages.contains_key(id));
+    complete.then_some(costs)
+}
+
+/// Escape a DOT string
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escape the text of a Mermaid node
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(GraphFormat::parse("dot"), Some(GraphFormat::Dot));
+        assert_eq!(GraphFormat::parse("mermaid"), Some(GraphFormat::Mermaid));
+        assert_eq!(GraphFormat::parse("svg"), None);
+        assert_eq!(GraphFormat::Mermaid.to_string(), "mermaid");
+    }
+
+    #[test]
+    fn test_cost_labels() {
+        assert_eq!(CostSource::Estimated.label(12), "12 units");
+        assert_eq!(CostSource::Measured.label(1240), "1.2s");
+        assert_eq!(dot_escape(r#"a "b" \c"#), r#"a \"b\" \\c"#);
+        assert_eq!(mermaid_escape("<\">"), "#lt;#quot;#gt;");
+    }
+}
@@ -0,0 +1,89 @@
+//! Parallel iteration for analyzing large workspaces.
+//!
+//! Workspaces with hundreds of members spend most of the analysis in work
+//! repeated per member or per file: walking each member's dependency chain,
+//! summing source sizes, reading sources. [`par_map`] spreads that over a
+//! bounded set of scoped threads. Workers claim small chunks of the input as
+//! they go, so a few expensive members do not leave the other threads idle,
+//! and each keeps only the results of its own chunks: memory grows with the
+//! results, never with a copy of the input or a thread per item. Small inputs
+//! run on the calling thread, where spawning threads would cost more than it
+//! saves.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Environment variable capping the number of analysis threads
+pub const THREADS_ENV_VAR: &str = "CARGO_OPTIMIZE_ANALYSIS_THREADS";
+
+/// Inputs shorter than this are mapped on the calling thread
+const MIN_PARALLEL_ITEMS: usize = 32;
+
+/// Items a worker claims at a time
+const CHUNK_SIZE: usize = 8;
+
+/// Number of threads the analysis uses
+///
+/// The available parallelism, capped by [`THREADS_ENV_VAR`] when it is set
+/// to a positive number.
+pub fn analysis_threads() -> usize {
+    let available = thread::available_parallelism().map(NonZeroUsize::get).unwrap_or(1);
+    std::env::var(THREADS_ENV_VAR)
+        .ok()
+        .and_then(|threads| threads.trim().parse::<usize>().ok())
+        .filter(|&threads| threads > 0)
+        .map_or(available, |threads| threads.min(available))
+}
+
+/// Map `f` over `items` on up to [`analysis_threads`] threads, keeping the order
+pub fn par_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = analysis_threads().min(items.len().div_ceil(CHUNK_SIZE));
+    if items.len() < MIN_PARALLEL_ITEMS || threads < 2 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut chunks: Vec<(usize, Vec<R>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let start = next.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+                        if start >= items.len() {
+                            break done;
+                        }
+                        let end = (start + CHUNK_SIZE).min(items.len());
+                        done.push((start, items[start..end].iter().map(&f).collect()));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+    chunks.sort_unstable_by_key(|(start, _)| *start);
+    chunks.into_iter().flat_map(|(_, results)| results).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_map_keeps_order() {
+        let items: Vec<u64> = (0..1000).collect();
+        let squares = par_map(&items, |n| n * n);
+        assert_eq!(squares, items.iter().map(|n| n * n).collect::<Vec<_>>());
+        assert_eq!(par_map(&items[..3], |n| n + 1), [1, 2, 3]);
+        assert!(par_map(&[] as &[u64], |n| *n).is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+//! Analysis of the healthy part of a workspace with broken members.
+//!
+//! Cargo loads every member before it answers `cargo metadata`, so a single
+//! member whose `Cargo.toml` does not parse, or that cannot be resolved (a
+//! missing path dependency, an unknown workspace inheritance key), fails the
+//! analysis of the whole workspace. [`metadata`] isolates such members: it
+//! reruns cargo on an overlay of the workspace in a scratch directory, the
+//! root manifest without the failing members plus links to everything else,
+//! until cargo succeeds. Paths in the result point back into the real
+//! workspace, and every member left out becomes a [`MemberDiagnostic`].
+//!
+//! Members are first checked for TOML syntax errors; any other failure is
+//! attributed to the member whose directory cargo's error names. A failure
+//! no member can be blamed for is not isolated.
+
+use cargo_metadata::{Metadata, MetadataCommand};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use toml_edit::{Array, DocumentMut, Item};
+use tracing::{debug, warn};
+
+use super::quick::find_workspace_root;
+
+/// A workspace member left out of the analysis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberDiagnostic {
+    /// Directory of the member
+    pub path: PathBuf,
+    /// Why cargo could not load it
+    pub error: String,
+}
+
+/// Overlays created by this process, for unique scratch directory names
+static OVERLAYS: AtomicUsize = AtomicUsize::new(0);
+
+/// Metadata of the workspace without the members that make cargo fail
+///
+/// `error` is the failure of the full run; `command` builds the cargo
+/// invocation for a manifest. `None` when the failure cannot be pinned on
+/// members, in which case the caller reports the original error.
+pub(crate) fn metadata(
+    manifest_path: Option<&Path>,
+    error: &str,
+    command: impl Fn(&Path) -> MetadataCommand,
+) -> Option<(Metadata, Vec<MemberDiagnostic>)> {
+    let manifest = match manifest_path {
+        Some(path) => absolute(path),
+        None => absolute(Path::new("Cargo.toml")),
+    };
+    let root = find_workspace_root(manifest.parent()?);
+    let workspace: DocumentMut = fs::read_to_string(root.join("Cargo.toml")).ok()?.parse().ok()?;
+    let members = member_dirs(&root, &workspace);
+
+    let mut skipped: Vec<MemberDiagnostic> = members
+        .iter()
+        .filter_map(|dir| manifest_error(dir).map(|error| MemberDiagnostic { path: dir.clone(), error }))
+        .collect();
+    if skipped.is_empty() {
+        skipped.push(blame(&members, &skipped, &root, &root, error)?);
+    }
+
+    // Each retry leaves out one more member
+    for _ in 0..members.len() {
+        if manifest.starts_with(&skipped.last()?.path) {
+            debug!("The analyzed package {} itself is broken", manifest.display());
+            return None;
+        }
+        let overlay = std::env::temp_dir().join(format!(
+            "cargo-optimize-partial-{}-{}",
+            std::process::id(),
+            OVERLAYS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = run_on_overlay(&root, &workspace, &skipped, &overlay, &manifest, &command);
+        let _ = fs::remove_dir_all(&overlay);
+        match result {
+            Ok(Ok(metadata)) => {
+                for diagnostic in &skipped {
+                    warn!("Analyzing without member {}: {}", diagnostic.path.display(), diagnostic.error);
+                }
+                return Some((metadata, skipped));
+            }
+            Ok(Err(stderr)) => skipped.push(blame(&members, &skipped, &root, &overlay, &stderr)?),
+            Err(e) => {
+                debug!("Failed to analyze the workspace without its broken members: {}", e);
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/// Run cargo on an overlay of `root` at `overlay` without the `skipped` members
+///
+/// The inner error is cargo's stderr, with overlay paths.
+fn run_on_overlay(
+    root: &Path,
+    workspace: &DocumentMut,
+    skipped: &[MemberDiagnostic],
+    overlay: &Path,
+    manifest: &Path,
+    command: impl Fn(&Path) -> MetadataCommand,
+) -> io::Result<Result<Metadata, String>> {
+    let skipped: Vec<&Path> = skipped.iter().map(|diagnostic| diagnostic.path.as_path()).collect();
+    fs::create_dir_all(overlay)?;
+    link_tree(root, overlay, root, &skipped)?;
+    fs::write(overlay.join("Cargo.toml"), without_members(workspace, root, &skipped).to_string())?;
+    if root.join("Cargo.lock").is_file() {
+        fs::copy(root.join("Cargo.lock"), overlay.join("Cargo.lock"))?;
+    }
+
+    let manifest = overlay.join(manifest.strip_prefix(root).unwrap_or(Path::new("Cargo.toml")));
+    let output = command(&manifest).cargo_command().output()?;
+    if !output.status.success() {
+        return Ok(Err(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    // Paths are JSON strings in the output, escaped the same way
+    let escaped = |path: &Path| serde_json::to_string(&path.to_string_lossy()).unwrap_or_default();
+    let (from, to) = (escaped(overlay), escaped(root));
+    let json = String::from_utf8_lossy(&output.stdout).replace(from.trim_matches('"'), to.trim_matches('"'));
+    Ok(MetadataCommand::parse(json).map_err(|e| e.to_string()))
+}
+
+/// Link every entry of `source` into `destination`, descending only towards `skipped` directories
+fn link_tree(source: &Path, destination: &Path, root: &Path, skipped: &[&Path]) -> io::Result<()> {
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        // The overlay gets its own manifest, lock file and target directory
+        if source == root && ["Cargo.toml", "Cargo.lock", "target"].iter().any(|own| name == *own) {
+            continue;
+        }
+        if skipped.contains(&path.as_path()) {
+            continue;
+        }
+        let target = destination.join(&name);
+        if entry.file_type()?.is_dir() && skipped.iter().any(|dir| dir.starts_with(&path)) {
+            fs::create_dir(&target)?;
+            link_tree(&path, &target, root, skipped)?;
+        } else {
+            symlink(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+/// Needs Developer Mode or the symlink privilege; the analysis fails as before otherwise
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    if original.is_dir() {
+        std::os::windows::fs::symlink_dir(original, link)
+    } else {
+        std::os::windows::fs::symlink_file(original, link)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_original: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no symbolic links on this platform"))
+}
+
+/// The workspace manifest with the `skipped` members taken out of `members` and `default-members`
+///
+/// Glob patterns stay: the overlay has no directory for them to match.
+fn without_members(workspace: &DocumentMut, root: &Path, skipped: &[&Path]) -> DocumentMut {
+    let mut doc = workspace.clone();
+    for key in ["members", "default-members"] {
+        if let Some(array) = doc.get_mut("workspace").and_then(|ws| ws.get_mut(key)).and_then(Item::as_array_mut) {
+            array.retain(|entry| entry.as_str().is_none_or(|entry| !skipped.contains(&root.join(entry).as_path())));
+        }
+    }
+    doc
+}
+
+/// Member directories of the workspace at `root`, globs expanded and `exclude` left out
+fn member_dirs(root: &Path, workspace: &DocumentMut) -> Vec<PathBuf> {
+    let patterns = |key: &str| -> Vec<String> {
+        workspace
+            .get("workspace")
+            .and_then(|ws| ws.get(key))
+            .and_then(Item::as_array)
+            .map(|array: &Array| array.iter().filter_map(|entry| entry.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    };
+    let excluded: Vec<PathBuf> = patterns("exclude").iter().map(|entry| root.join(entry)).collect();
+    let mut dirs: Vec<PathBuf> = patterns("members")
+        .iter()
+        .flat_map(|pattern| expand(root, pattern))
+        .filter(|dir| !excluded.iter().any(|excluded| dir.starts_with(excluded)))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Directories matching a `members` entry; a glob only matches directories with a `Cargo.toml`
+fn expand(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    if !pattern.contains(['*', '?']) {
+        return vec![root.join(pattern)];
+    }
+    let mut dirs = vec![root.to_path_buf()];
+    for component in pattern.split('/').filter(|component| !component.is_empty()) {
+        dirs = dirs
+            .into_iter()
+            .flat_map(|dir| -> Vec<PathBuf> {
+                if !component.contains(['*', '?']) {
+                    return vec![dir.join(component)];
+                }
+                fs::read_dir(&dir)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .filter(|entry| entry.file_name().to_str().is_some_and(|name| wildcard_match(component, name)))
+                    .map(|entry| entry.path())
+                    .collect()
+            })
+            .collect();
+    }
+    dirs.retain(|dir| dir.join("Cargo.toml").is_file());
+    dirs
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters and `?` one
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, tried)) => {
+                    p = after;
+                    n = tried + 1;
+                    star = Some((after, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Why the manifest of the member at `dir` cannot be read or parsed
+fn manifest_error(dir: &Path) -> Option<String> {
+    let path = dir.join("Cargo.toml");
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            content.parse::<DocumentMut>().err().map(|e| format!("{} does not parse: {}", path.display(), e))
+        }
+        Err(e) => Some(format!("failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// The not yet skipped member cargo's `error` names, under `base` instead of `root`
+///
+/// The most specific directory wins, so a nested member is blamed over the
+/// member containing it.
+fn blame(
+    members: &[PathBuf],
+    skipped: &[MemberDiagnostic],
+    root: &Path,
+    base: &Path,
+    error: &str,
+) -> Option<MemberDiagnostic> {
+    let member = members
+        .iter()
+        .filter(|dir| !skipped.iter().any(|diagnostic| &diagnostic.path == *dir))
+        .filter(|dir| {
+            let shown = base.join(dir.strip_prefix(root).unwrap_or(dir));
+            mentions(error, &shown.to_string_lossy())
+        })
+        .max_by_key(|dir| dir.components().count())?;
+    Some(MemberDiagnostic { path: member.clone(), error: cargo_errors(error, base, root) })
+}
+
+/// Whether `text` contains `path` as a whole path, not as the prefix of a longer name
+fn mentions(text: &str, path: &str) -> bool {
+    text.match_indices(path).any(|(start, _)| {
+        text[start + path.len()..]
+            .chars()
+            .next()
+            .is_none_or(|next| !(next.is_alphanumeric() || next == '-' || next == '_' || next == '.'))
+    })
+}
+
+/// The `error:` lines of cargo's output and their causes, joined, with overlay paths shown as workspace paths
+fn cargo_errors(stderr: &str, base: &Path, root: &Path) -> String {
+    let lines: Vec<&str> = stderr
+        .lines()
+        .filter_map(|line| match line.trim().rsplit_once("error: ") {
+            Some((_, message)) => Some(message.trim()),
+            // Causes are indented; source snippets start with a line number or `|`
+            None if line.starts_with("  ") => Some(line.trim())
+                .filter(|cause| !cause.trim_start_matches(|c: char| c.is_ascii_digit()).starts_with(['|', ' '])),
+            None => None,
+        })
+        .filter(|message| !message.is_empty())
+        .collect();
+    let message = if lines.is_empty() { stderr.trim().to_string() } else { lines.join(": ") };
+    message.replace(&*base.to_string_lossy(), &root.to_string_lossy())
+}
+
+/// `path` relative to the current directory made absolute
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "core"));
+        assert!(wildcard_match("crate-*", "crate-core"));
+        assert!(!wildcard_match("crate-*", "core"));
+        assert!(wildcard_match("*-sys", "openssl-sys"));
+        assert!(wildcard_match("a?c*", "abcdef"));
+        assert!(!wildcard_match("a?c", "ac"));
+        assert!(wildcard_match("*a*b", "xxaxxb"));
+        assert!(!wildcard_match("*a*b", "xxaxxbx"));
+    }
+
+    #[test]
+    fn test_mentions_whole_paths() {
+        let error = "failed to load manifest for workspace member `/ws/crates/bad`\nreferenced via `crates/*`";
+        assert!(mentions(error, "/ws/crates/bad"));
+        assert!(!mentions(error, "/ws/crates/ba"));
+        assert!(mentions("package `a v0.1.0 (/ws/crates/a)`", "/ws/crates/a"));
+    }
+
+    #[test]
+    fn test_cargo_errors_keep_causes() {
+        let stderr = "error: failed to load manifest for workspace member `/tmp/ov/b`\n\
+                      referenced via `crates/*` by workspace at `/tmp/ov/Cargo.toml`\n\nCaused by:\n  \
+                      failed to parse manifest at `/tmp/ov/b/Cargo.toml`\n\nCaused by:\n  \
+                      TOML parse error at line 1\n  |\n1 | [package\n  |         ^\n";
+        assert_eq!(
+            cargo_errors(stderr, Path::new("/tmp/ov"), Path::new("/ws")),
+            "failed to load manifest for workspace member `/ws/b`: failed to parse manifest at `/ws/b/Cargo.toml`: \
+             TOML parse error at line 1"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Time-bounded, read-only analysis for IDE integrations.
+//!
+//! Editors ask for project facts on every keystroke or file save and cannot
+//! wait for `cargo metadata` and the guppy passes of [`super::analyze_project`].
+//! [`quick_analysis`] never spawns a process and works within
+//! [`QUICK_ANALYSIS_BUDGET`]: it reads the project's config files, then the
+//! metadata a full analysis cached in the target directory, and derives what
+//! needs no dependency graph from it. Bottlenecks, feature usage and build
+//! metrics are always left out. Every result is tagged with a [`Freshness`],
+//! and steps that would start after the budget ran out are skipped.
+
+use anyhow::{Context, Result};
+use cargo_metadata::{DependencyKind, Metadata};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use toml_edit::DocumentMut;
+use tracing::debug;
+
+use super::{analyze_targets, configured_target_triples, detect_template, embedded_signal, TargetAnalysis};
+use crate::config::{effective_target_dir, ProjectTemplate};
+use crate::explain;
+
+/// Time [`quick_analysis`] may take
+pub const QUICK_ANALYSIS_BUDGET: Duration = Duration::from_millis(100);
+
+/// Cached metadata, relative to the target directory
+pub const METADATA_CACHE: &str = "cargo-optimize/metadata.json";
+
+/// Largest cache parsed within the budget; bigger workspaces wait for a full analysis
+const MAX_CACHE_BYTES: u64 = 16 << 20;
+
+/// Passes of the full analysis the quick one never runs
+const FULL_ANALYSIS_ONLY: &[&str] = &["bottlenecks", "features", "build metrics"];
+
+/// How current a part of a [`QuickAnalysis`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Freshness {
+    /// Read from the project files during this call
+    Live,
+    /// From cached metadata newer than every manifest and the lock file
+    Cached,
+    /// From cached metadata older than a manifest or the lock file
+    Stale,
+    /// Not known without a full analysis
+    Missing,
+}
+
+/// What a quick analysis found
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickAnalysis {
+    /// Workspace root the analysis ran for
+    pub workspace_root: PathBuf,
+    /// Target triples configured for the project ([`Freshness::Live`])
+    pub configured_targets: Vec<String>,
+    /// Whether `.cargo/config.toml` carries values cargo-optimize wrote ([`Freshness::Live`])
+    pub optimized: bool,
+    /// Freshness of everything derived from the cached metadata
+    pub metadata: Freshness,
+    /// Age of the cached metadata
+    pub metadata_age: Option<Duration>,
+    /// Workspace member names
+    pub workspace_members: Vec<String>,
+    /// Dependencies, transitive ones included
+    pub total_dependencies: Option<usize>,
+    /// Dependencies declared by workspace members
+    pub direct_dependencies: Option<usize>,
+    /// Build targets of the workspace members
+    #[serde(skip)]
+    pub targets: Option<TargetAnalysis>,
+    /// Project archetype
+    pub template: Option<ProjectTemplate>,
+    /// Freshness of `template`; embedded projects are recognized live
+    pub template_freshness: Freshness,
+    /// Parts left out, either always or because the budget ran out
+    pub skipped: Vec<String>,
+    /// Time the analysis took
+    pub elapsed: Duration,
+}
+
+/// Analyze the project at `project_root` without running cargo
+///
+/// Never fails: what cannot be read is reported as [`Freshness::Missing`].
+/// Run [`super::analyze_project`] once to populate the metadata cache.
+pub fn quick_analysis(project_root: &Path) -> QuickAnalysis {
+    let started = Instant::now();
+    let within_budget = || started.elapsed() < QUICK_ANALYSIS_BUDGET;
+    let workspace_root = find_workspace_root(project_root);
+    let config = fs::read_to_string(workspace_root.join(".cargo").join("config.toml"))
+        .ok()
+        .and_then(|content| content.parse::<DocumentMut>().ok())
+        .unwrap_or_default();
+
+    let mut analysis = QuickAnalysis {
+        configured_targets: configured_target_triples(&workspace_root),
+        optimized: config.to_string().contains(explain::COMMENT_PREFIX),
+        metadata: Freshness::Missing,
+        metadata_age: None,
+        workspace_members: Vec::new(),
+        total_dependencies: None,
+        direct_dependencies: None,
+        targets: None,
+        template: None,
+        template_freshness: Freshness::Missing,
+        skipped: FULL_ANALYSIS_ONLY.iter().map(|pass| pass.to_string()).collect(),
+        elapsed: Duration::ZERO,
+        workspace_root,
+    };
+    if embedded_signal(&analysis.workspace_root).is_some() {
+        analysis.template = Some(ProjectTemplate::Embedded);
+        analysis.template_freshness = Freshness::Live;
+    }
+
+    let env_dir = std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from);
+    let cache = effective_target_dir(&analysis.workspace_root, env_dir.as_deref(), &config)
+        .path
+        .join(METADATA_CACHE);
+    let cached = if within_budget() {
+        load_cache(&cache)
+    } else {
+        analysis.skipped.push("cached metadata".to_string());
+        None
+    };
+    if let Some((metadata, written)) = cached {
+        analysis.metadata = if changed_since(&metadata, &analysis.workspace_root, written) {
+            Freshness::Stale
+        } else {
+            Freshness::Cached
+        };
+        analysis.metadata_age = SystemTime::now().duration_since(written).ok();
+        let members: Vec<_> = metadata
+            .packages
+            .iter()
+            .filter(|package| metadata.workspace_members.contains(&package.id))
+            .collect();
+        analysis.workspace_members = members.iter().map(|package| package.name.clone()).collect();
+        let (total, direct) = count_dependencies(&metadata);
+        analysis.total_dependencies = Some(total);
+        analysis.direct_dependencies = Some(direct);
+
+        if within_budget() {
+            let targets = analyze_targets(&metadata).ok();
+            if analysis.template.is_none() {
+                let dependencies: HashSet<String> = members
+                    .iter()
+                    .flat_map(|package| &package.dependencies)
+                    .filter(|dependency| dependency.kind == DependencyKind::Normal)
+                    .map(|dependency| dependency.name.clone())
+                    .collect();
+                if let Some(targets) = &targets {
+                    analysis.template = detect_template(&analysis.configured_targets, targets, &dependencies);
+                    analysis.template_freshness = analysis.metadata;
+                }
+            }
+            analysis.targets = targets;
+        } else {
+            analysis.skipped.push("targets".to_string());
+        }
+    }
+
+    analysis.elapsed = started.elapsed();
+    analysis
+}
+
+/// Cache the metadata of a full analysis for [`quick_analysis`]
+pub(crate) fn cache_metadata(metadata: &Metadata) -> Result<()> {
+    let path = metadata.target_directory.as_std_path().join(METADATA_CACHE);
+    let json = serde_json::to_string(metadata).context("Failed to serialize metadata")?;
+    crate::transaction::atomic_write(&path, &json)
+}
+
+/// The nearest directory from `project_root` up whose `Cargo.toml` has `[workspace]`
+pub(super) fn find_workspace_root(project_root: &Path) -> PathBuf {
+    project_root
+        .ancestors()
+        .find(|dir| {
+            fs::read_to_string(dir.join("Cargo.toml"))
+                .ok()
+                .and_then(|content| content.parse::<DocumentMut>().ok())
+                .is_some_and(|doc| doc.contains_key("workspace"))
+        })
+        .unwrap_or(project_root)
+        .to_path_buf()
+}
+
+/// Cached metadata and when it was written, if there is a cache small enough to parse
+pub(crate) fn load_cache(path: &Path) -> Option<(Metadata, SystemTime)> {
+    let file = fs::metadata(path).ok()?;
+    if file.len() > MAX_CACHE_BYTES {
+        debug!("Metadata cache {:?} is too large to parse within the budget", path);
+        return None;
+    }
+    let written = file.modified().ok()?;
+    let content = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(metadata) => Some((metadata, written)),
+        Err(e) => {
+            debug!("Ignoring unreadable metadata cache {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Whether a manifest of the workspace or its lock file changed after `written`
+fn changed_since(metadata: &Metadata, workspace_root: &Path, written: SystemTime) -> bool {
+    let manifests = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .map(|package| package.manifest_path.as_std_path().to_path_buf());
+    [workspace_root.join("Cargo.toml"), workspace_root.join("Cargo.lock")]
+        .into_iter()
+        .chain(manifests)
+        .any(|path| fs::metadata(path).and_then(|file| file.modified()).is_ok_and(|modified| modified > written))
+}
+
+/// Packages outside the workspace in the resolved graph, and those its members depend on directly
+fn count_dependencies(metadata: &Metadata) -> (usize, usize) {
+    let Some(resolve) = &metadata.resolve else {
+        return (0, 0);
+    };
+    let is_member = |id| metadata.workspace_members.contains(id);
+    let total = resolve.nodes.iter().filter(|node| !is_member(&node.id)).count();
+    let direct: HashSet<_> = resolve
+        .nodes
+        .iter()
+        .filter(|node| is_member(&node.id))
+        .flat_map(|node| &node.dependencies)
+        .filter(|id| !is_member(id))
+        .collect();
+    (total, direct.len())
+}
@@ -0,0 +1,606 @@
+//! What common cargo commands build, before running them.
+//!
+//! Few developers know that `cargo test` compiles every dev-dependency,
+//! every integration test and every example, or that `cargo check` still
+//! fully builds proc macros and build scripts. [`simulate`] lists, for
+//! `build`, `check`, `test` and `doc`, how many compile units the command
+//! runs, which crates weigh the most and how long it should take on this
+//! machine.
+//!
+//! The units come from cargo's unit graph (`--unit-graph`) on a nightly
+//! toolchain. Elsewhere they are modeled from `cargo metadata`: the
+//! dependency closure of the workspace members, dev-dependencies included
+//! for `test`, with build scripts and proc macros built fully and the other
+//! dependencies checked for `check`, checked and documented for `doc`.
+//! Durations spread the unit times of the last `cargo build --timings` over
+//! the cores, or else scale the median of the dev builds recorded in the
+//! metrics history, which are mostly incremental.
+
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, MetadataCommand, Node, Package, PackageId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+use super::build_weight::{parse_timings, TIMINGS_REPORT};
+use crate::metrics::{median, MetricsHistory};
+use crate::toolchain::ToolchainInfo;
+
+/// Crates listed as heavy per command
+pub const HEAVY_CRATES: usize = 5;
+
+/// Recorded dev builds the median duration is taken over
+const HISTORY_BUILDS: usize = 10;
+
+/// A cargo command to simulate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CargoCommand {
+    /// `cargo build`
+    Build,
+    /// `cargo check`
+    Check,
+    /// `cargo test`
+    Test,
+    /// `cargo doc`
+    Doc,
+}
+
+impl CargoCommand {
+    /// Every simulated command, in the order they are listed
+    pub const ALL: [CargoCommand; 4] =
+        [CargoCommand::Build, CargoCommand::Check, CargoCommand::Test, CargoCommand::Doc];
+
+    /// Subcommand name
+    pub fn name(self) -> &'static str {
+        match self {
+            CargoCommand::Build => "build",
+            CargoCommand::Check => "check",
+            CargoCommand::Test => "test",
+            CargoCommand::Doc => "doc",
+        }
+    }
+
+    /// Parse a subcommand name
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.name() == name)
+    }
+}
+
+impl fmt::Display for CargoCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cargo {}", self.name())
+    }
+}
+
+/// What cargo does with a unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnitMode {
+    /// Compiled with code generation
+    Build,
+    /// Checked only, producing metadata
+    Check,
+    /// Compiled as a test harness
+    Test,
+    /// Documented with rustdoc
+    Doc,
+    /// A compiled build script being run
+    RunBuildScript,
+}
+
+impl UnitMode {
+    /// Share of a package's full build cost the unit takes
+    ///
+    /// Checking skips code generation, roughly half of the work; running a
+    /// build script is accounted for in the package's cost already.
+    pub fn weight(self) -> f64 {
+        match self {
+            UnitMode::Build | UnitMode::Test => 1.0,
+            UnitMode::Check | UnitMode::Doc => 0.5,
+            UnitMode::RunBuildScript => 0.0,
+        }
+    }
+
+    /// Label in summaries
+    pub fn label(self) -> &'static str {
+        match self {
+            UnitMode::Build => "build",
+            UnitMode::Check => "check",
+            UnitMode::Test => "test",
+            UnitMode::Doc => "doc",
+            UnitMode::RunBuildScript => "build-script run",
+        }
+    }
+
+    /// Mode of a unit in cargo's unit graph; `None` for units not compiled by the command itself
+    fn from_unit_graph(mode: &str) -> Option<Self> {
+        match mode {
+            "build" => Some(UnitMode::Build),
+            "check" => Some(UnitMode::Check),
+            "test" | "bench" => Some(UnitMode::Test),
+            "doc" => Some(UnitMode::Doc),
+            "run-custom-build" => Some(UnitMode::RunBuildScript),
+            // Doctests compile when they run; scraping is opt-in
+            _ => None,
+        }
+    }
+}
+
+/// One unit of work a command runs
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlannedUnit {
+    /// Package id, as printed by `cargo metadata`
+    pub package: String,
+    /// Target name
+    pub target: String,
+    /// What is done with the target
+    pub mode: UnitMode,
+}
+
+/// Where the units of a summary come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnitSource {
+    /// Cargo's unit graph (nightly)
+    UnitGraph,
+    /// Modeled from `cargo metadata`
+    Metadata,
+}
+
+/// A crate taking a large share of a command's work
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeavyCrate {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Share of the command's estimated work, in percent
+    pub share_percent: u64,
+}
+
+/// What one command builds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandSummary {
+    /// The command
+    pub command: CargoCommand,
+    /// Units the command runs
+    pub units: usize,
+    /// Units by what is done with them
+    pub units_by_mode: BTreeMap<UnitMode, usize>,
+    /// Packages involved
+    pub packages: usize,
+    /// Costliest crates, heaviest first
+    pub heavy: Vec<HeavyCrate>,
+    /// Estimated wall-clock seconds, from a clean target directory unless scaled from incremental builds
+    pub estimated_secs: Option<f64>,
+    /// What the estimate is based on, or why there is none
+    pub estimate_basis: String,
+    /// Where the units come from
+    pub source: UnitSource,
+}
+
+impl fmt::Display for CommandSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modes: Vec<String> =
+            self.units_by_mode.iter().map(|(mode, count)| format!("{} {}", count, mode.label())).collect();
+        writeln!(f, "{}: {} units ({}) across {} packages", self.command, self.units, modes.join(", "), self.packages)?;
+        if !self.heavy.is_empty() {
+            let heavy: Vec<String> =
+                self.heavy.iter().map(|c| format!("{} {} ({}%)", c.name, c.version, c.share_percent)).collect();
+            writeln!(f, "  heavy crates: {}", heavy.join(", "))?;
+        }
+        match self.estimated_secs {
+            Some(secs) => write!(f, "  estimated: ~{} ({})", format_secs(secs), self.estimate_basis),
+            None => write!(f, "  estimated: unknown ({})", self.estimate_basis),
+        }
+    }
+}
+
+fn format_secs(secs: f64) -> String {
+    if secs >= 90.0 {
+        format!("{:.1} min", secs / 60.0)
+    } else {
+        format!("{:.0}s", secs.max(1.0))
+    }
+}
+
+/// What durations are derived from
+#[derive(Debug, Clone, PartialEq)]
+enum DurationBasis {
+    /// Seconds per package id from a timings report, spread over `cpus`
+    Timings { cpus: usize },
+    /// Median wall-clock seconds of recorded dev builds, taken to build what `cargo build` does
+    ///
+    /// Most of them are incremental, so the estimate is too.
+    History { median_secs: f64, builds: usize },
+    /// Nothing measured yet
+    Unmeasured,
+}
+
+/// Summarize what `commands` build for the project at `manifest_path`
+pub fn simulate(manifest_path: Option<&Path>, commands: &[CargoCommand]) -> Result<Vec<CommandSummary>> {
+    let mut cmd = MetadataCommand::new();
+    if let Some(path) = manifest_path {
+        cmd.manifest_path(path);
+    }
+    let toolchain = ToolchainInfo::detect().ok();
+    if let Some(toolchain) = &toolchain {
+        // Only what builds on this machine, not the dependencies of other platforms
+        cmd.other_options(vec!["--filter-platform".to_string(), toolchain.host.clone()]);
+    }
+    let metadata = cmd.exec().context("Failed to execute cargo metadata")?;
+    let nightly = toolchain.is_some_and(|toolchain| toolchain.is_nightly());
+    let units_of = |command: CargoCommand| {
+        let graph = if nightly { unit_graph(manifest_path, command) } else { Err(anyhow::anyhow!("not nightly")) };
+        match graph {
+            Ok(units) => (units, UnitSource::UnitGraph),
+            Err(e) => {
+                debug!("Modeling the units of {} from metadata: {:#}", command, e);
+                (model_units(&metadata, command), UnitSource::Metadata)
+            }
+        }
+    };
+
+    let (baseline, baseline_source) = units_of(CargoCommand::Build);
+    let estimated: HashMap<String, f64> =
+        super::estimated_costs(&metadata).into_iter().map(|(id, cost)| (id.repr.clone(), cost as f64)).collect();
+    let measured = measured_seconds(&metadata, &baseline);
+    let history = MetricsHistory::load(&MetricsHistory::path_in(&metadata.workspace_root.as_std_path().join(".cargo")));
+    let durations: Vec<f64> = history.recent(Some("dev"), HISTORY_BUILDS).map(|record| record.duration_secs).collect();
+    let builds = durations.len();
+    let (costs, basis) = match measured {
+        Some(measured) => (measured, DurationBasis::Timings { cpus: num_cpus::get() }),
+        None if builds > 0 => (estimated, DurationBasis::History { median_secs: median(durations), builds }),
+        None => (estimated, DurationBasis::Unmeasured),
+    };
+
+    let packages: HashMap<&str, &Package> =
+        metadata.packages.iter().map(|package| (package.id.repr.as_str(), package)).collect();
+    Ok(commands
+        .iter()
+        .map(|&command| {
+            let (units, source) =
+                if command == CargoCommand::Build { (baseline.clone(), baseline_source) } else { units_of(command) };
+            summarize(command, &units, &baseline, &packages, &costs, &basis, source)
+        })
+        .collect())
+}
+
+/// Units of `command` from cargo's unit graph (nightly only)
+pub fn unit_graph(manifest_path: Option<&Path>, command: CargoCommand) -> Result<Vec<PlannedUnit>> {
+    let mut cargo = Command::new("cargo");
+    cargo.args([command.name(), "--unit-graph", "-Z", "unstable-options"]);
+    if let Some(path) = manifest_path {
+        cargo.arg("--manifest-path").arg(path);
+    }
+    let output = cargo.output().context("Failed to run cargo --unit-graph")?;
+    if !output.status.success() {
+        bail!("cargo {} --unit-graph failed: {}", command.name(), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    parse_unit_graph(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[derive(Deserialize)]
+struct RawUnitGraph {
+    units: Vec<RawUnit>,
+}
+
+#[derive(Deserialize)]
+struct RawUnit {
+    pkg_id: String,
+    target: RawTarget,
+    mode: String,
+}
+
+#[derive(Deserialize)]
+struct RawTarget {
+    name: String,
+}
+
+/// Units of a `--unit-graph` JSON document
+pub fn parse_unit_graph(json: &str) -> Result<Vec<PlannedUnit>> {
+    let graph: RawUnitGraph = serde_json::from_str(json).context("Failed to parse the unit graph")?;
+    Ok(graph
+        .units
+        .into_iter()
+        .filter_map(|unit| {
+            Some(PlannedUnit {
+                package: unit.pkg_id,
+                target: unit.target.name,
+                mode: UnitMode::from_unit_graph(&unit.mode)?,
+            })
+        })
+        .collect())
+}
+
+/// Units of `command` modeled from the resolved dependency graph
+///
+/// Without `--target`, cargo shares one build of a package between host
+/// and target, so a package appears once per target and mode.
+pub fn model_units(metadata: &Metadata, command: CargoCommand) -> Vec<PlannedUnit> {
+    let Some(resolve) = &metadata.resolve else {
+        return Vec::new();
+    };
+    let nodes: HashMap<&PackageId, &Node> = resolve.nodes.iter().map(|node| (&node.id, node)).collect();
+    let packages: HashMap<&PackageId, &Package> =
+        metadata.packages.iter().map(|package| (&package.id, package)).collect();
+    let members: HashSet<&PackageId> = metadata.workspace_members.iter().collect();
+    let has_kind = |package: &Package, kind: &str| package.targets.iter().any(|t| t.kind.iter().any(|k| k == kind));
+
+    let mut units = HashSet::new();
+    let mut unit = |package: &Package, target: &str, mode: UnitMode| {
+        units.insert(PlannedUnit { package: package.id.repr.clone(), target: target.to_string(), mode });
+    };
+    // (package, built for the host): build dependencies and proc macros run at compile time
+    let mut seen: HashSet<(&PackageId, bool)> = HashSet::new();
+    let mut stack: Vec<(&PackageId, bool)> = metadata.workspace_members.iter().map(|id| (id, false)).collect();
+    while let Some((id, host)) = stack.pop() {
+        if !seen.insert((id, host)) {
+            continue;
+        }
+        let (Some(package), Some(node)) = (packages.get(id), nodes.get(id)) else {
+            continue;
+        };
+        let proc_macro = has_kind(package, "proc-macro");
+        for target in &package.targets {
+            if target.kind.iter().any(|k| k == "custom-build") {
+                unit(package, &target.name, UnitMode::Build);
+                unit(package, &target.name, UnitMode::RunBuildScript);
+            }
+        }
+        let member = members.contains(id) && !host;
+        for target in package.targets.iter().filter(|t| !t.kind.iter().any(|k| k == "custom-build")) {
+            let kind = |name: &str| target.kind.iter().any(|k| k == name);
+            let library = kind("lib") || kind("rlib") || kind("dylib") || kind("proc-macro");
+            let modes: &[UnitMode] = match (member, command) {
+                (false, _) if !library => &[],
+                (false, _) if host => &[UnitMode::Build],
+                // `cargo doc` documents every dependency on top of what it checks
+                (false, CargoCommand::Doc) if proc_macro => &[UnitMode::Build, UnitMode::Doc],
+                (false, CargoCommand::Doc) => &[UnitMode::Check, UnitMode::Doc],
+                (false, _) if proc_macro => &[UnitMode::Build],
+                (false, CargoCommand::Build | CargoCommand::Test) => &[UnitMode::Build],
+                (false, CargoCommand::Check) => &[UnitMode::Check],
+                (true, CargoCommand::Build) if library || kind("bin") => &[UnitMode::Build],
+                (true, CargoCommand::Check) if library || kind("bin") => &[UnitMode::Check],
+                (true, CargoCommand::Doc) if library => &[UnitMode::Doc],
+                // Integration tests link the library and run the binaries
+                (true, CargoCommand::Test) if library || kind("bin") => &[UnitMode::Build, UnitMode::Test],
+                (true, CargoCommand::Test) if kind("test") => &[UnitMode::Test],
+                (true, CargoCommand::Test) if kind("example") => &[UnitMode::Build],
+                _ => &[],
+            };
+            for &mode in modes {
+                unit(package, &target.name, mode);
+            }
+        }
+        for dep in &node.deps {
+            for info in &dep.dep_kinds {
+                let next = match info.kind {
+                    DependencyKind::Normal => Some(host || proc_macro),
+                    DependencyKind::Build => Some(true),
+                    DependencyKind::Development if member && command == CargoCommand::Test => Some(false),
+                    _ => None,
+                };
+                if let Some(next_host) = next {
+                    stack.push((&dep.pkg, next_host));
+                }
+            }
+        }
+    }
+    let mut units: Vec<PlannedUnit> = units.into_iter().collect();
+    units.sort();
+    units
+}
+
+/// Seconds per package id from the last timings report, when it covers every package `units` build
+fn measured_seconds(metadata: &Metadata, units: &[PlannedUnit]) -> Option<HashMap<String, f64>> {
+    let html = fs::read_to_string(metadata.target_directory.as_std_path().join(TIMINGS_REPORT)).ok()?;
+    let timings = match parse_timings(&html) {
+        Ok(timings) => timings,
+        Err(e) => {
+            debug!("Ignoring the timings report: {:#}", e);
+            return None;
+        }
+    };
+    let mut seconds: HashMap<(&str, String), f64> = HashMap::new();
+    for timing in &timings {
+        *seconds.entry((timing.name.as_str(), timing.version.clone())).or_default() += timing.duration;
+    }
+    let measured: HashMap<String, f64> = metadata
+        .packages
+        .iter()
+        .filter_map(|p| Some((p.id.repr.clone(), *seconds.get(&(p.name.as_str(), p.version.to_string()))?)))
+        .collect();
+    units.iter().all(|unit| measured.contains_key(&unit.package)).then_some(measured)
+}
+
+/// Work per package of `units`, each package and mode counted once
+fn package_work(units: &[PlannedUnit], costs: &HashMap<String, f64>) -> HashMap<String, f64> {
+    let mut counted = HashSet::new();
+    let mut work: HashMap<String, f64> = HashMap::new();
+    for unit in units {
+        if counted.insert((&unit.package, unit.mode)) {
+            let cost = costs.get(&unit.package).copied().unwrap_or(0.0);
+            *work.entry(unit.package.clone()).or_default() += cost * unit.mode.weight();
+        }
+    }
+    work
+}
+
+fn summarize(
+    command: CargoCommand,
+    units: &[PlannedUnit],
+    baseline: &[PlannedUnit],
+    packages: &HashMap<&str, &Package>,
+    costs: &HashMap<String, f64>,
+    basis: &DurationBasis,
+    source: UnitSource,
+) -> CommandSummary {
+    let mut units_by_mode = BTreeMap::new();
+    for unit in units {
+        *units_by_mode.entry(unit.mode).or_insert(0) += 1;
+    }
+    let work = package_work(units, costs);
+    let total: f64 = work.values().sum();
+
+    let mut ranked: Vec<(&String, f64)> = work.iter().map(|(id, work)| (id, *work)).filter(|(_, w)| *w > 0.0).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let heavy = ranked
+        .iter()
+        .take(HEAVY_CRATES)
+        .filter_map(|(id, work)| {
+            let package = packages.get(id.as_str())?;
+            Some(HeavyCrate {
+                name: package.name.clone(),
+                version: package.version.to_string(),
+                share_percent: (work / total * 100.0).round() as u64,
+            })
+        })
+        .collect();
+
+    let (estimated_secs, estimate_basis) = match basis {
+        DurationBasis::History { median_secs, builds } => {
+            let baseline_work: f64 = package_work(baseline, costs).values().sum();
+            let scale = if baseline_work > 0.0 { total / baseline_work } else { 1.0 };
+            (
+                Some(median_secs * scale),
+                format!(
+                    "incremental, scaled from the median of {} recorded dev builds, {}",
+                    builds,
+                    format_secs(*median_secs)
+                ),
+            )
+        }
+        DurationBasis::Timings { cpus } => {
+            // Never faster than the slowest single crate
+            let slowest = ranked.first().map_or(0.0, |(_, work)| *work);
+            (
+                Some((total / (*cpus).max(1) as f64).max(slowest)),
+                format!("unit times of the last `cargo build --timings` spread over {} cores", cpus),
+            )
+        }
+        DurationBasis::Unmeasured => {
+            (None, "no recorded builds; run `cargo build --timings` once to measure".to_string())
+        }
+    };
+
+    CommandSummary {
+        command,
+        units: units.len(),
+        units_by_mode,
+        packages: work.len(),
+        heavy,
+        estimated_secs,
+        estimate_basis,
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(package: &str, target: &str, mode: UnitMode) -> PlannedUnit {
+        PlannedUnit { package: package.to_string(), target: target.to_string(), mode }
+    }
+
+    #[test]
+    fn test_parse_unit_graph() {
+        let json = r#"{"version":1,"units":[
+            {"pkg_id":"app 0.1.0 (path+file:///app)","target":{"kind":["lib"],"name":"app"},"mode":"test","dependencies":[]},
+            {"pkg_id":"serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)","target":{"kind":["lib"],"name":"serde"},"mode":"build","dependencies":[]},
+            {"pkg_id":"serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)","target":{"kind":["custom-build"],"name":"build-script-build"},"mode":"run-custom-build","dependencies":[]},
+            {"pkg_id":"app 0.1.0 (path+file:///app)","target":{"kind":["lib"],"name":"app"},"mode":"doctest","dependencies":[]}
+        ],"roots":[0]}"#;
+        let units = parse_unit_graph(json).unwrap();
+        assert_eq!(
+            units.iter().map(|u| u.mode).collect::<Vec<_>>(),
+            [UnitMode::Test, UnitMode::Build, UnitMode::RunBuildScript]
+        );
+        assert_eq!(units[1].target, "serde");
+    }
+
+    #[test]
+    fn test_model_units_documents_dependencies() {
+        let package = |name: &str, kind: &str| {
+            format!(
+                r#"{{"name":"{name}","version":"1.0.0","id":"{name} 1.0.0","source":null,"dependencies":[],
+                "features":{{}},"manifest_path":"/{name}/Cargo.toml","targets":[{{"kind":["{kind}"],
+                "crate_types":["{kind}"],"name":"{name}","src_path":"/{name}/src/lib.rs"}}]}}"#
+            )
+        };
+        let node = |name: &str, deps: &[&str]| {
+            let deps: Vec<String> = deps
+                .iter()
+                .map(|dep| format!(r#"{{"name":"{dep}","pkg":"{dep} 1.0.0","dep_kinds":[{{"kind":null,"target":null}}]}}"#))
+                .collect();
+            format!(r#"{{"id":"{name} 1.0.0","dependencies":[],"features":[],"deps":[{}]}}"#, deps.join(","))
+        };
+        let json = format!(
+            r#"{{"packages":[{},{},{},{}],"workspace_members":["app 1.0.0"],
+            "resolve":{{"root":"app 1.0.0","nodes":[{},{},{},{}]}},
+            "target_directory":"/app/target","version":1,"workspace_root":"/app"}}"#,
+            package("app", "lib"),
+            package("serde", "lib"),
+            package("derive", "proc-macro"),
+            package("syn", "lib"),
+            node("app", &["serde", "derive"]),
+            node("serde", &[]),
+            node("derive", &["syn"]),
+            node("syn", &[]),
+        );
+        let metadata: Metadata = serde_json::from_str(&json).unwrap();
+        let modes = |command: CargoCommand, target: &str| -> Vec<UnitMode> {
+            model_units(&metadata, command).into_iter().filter(|u| u.target == target).map(|u| u.mode).collect()
+        };
+
+        assert_eq!(modes(CargoCommand::Doc, "app"), [UnitMode::Doc]);
+        assert_eq!(modes(CargoCommand::Doc, "serde"), [UnitMode::Check, UnitMode::Doc]);
+        assert_eq!(modes(CargoCommand::Doc, "derive"), [UnitMode::Build, UnitMode::Doc]);
+        // Only run by the compiler, inside the proc macro
+        assert_eq!(modes(CargoCommand::Doc, "syn"), [UnitMode::Build]);
+        assert_eq!(modes(CargoCommand::Check, "serde"), [UnitMode::Check]);
+    }
+
+    #[test]
+    fn test_summarize_scales_history() {
+        let baseline = vec![unit("app", "app", UnitMode::Build), unit("syn", "syn", UnitMode::Build)];
+        let check = vec![
+            unit("app", "app", UnitMode::Check),
+            unit("syn", "syn", UnitMode::Build),
+            unit("syn", "build-script-build", UnitMode::Build),
+            unit("syn", "build-script-build", UnitMode::RunBuildScript),
+        ];
+        let costs: HashMap<String, f64> = [("app".to_string(), 10.0), ("syn".to_string(), 30.0)].into();
+        let basis = DurationBasis::History { median_secs: 40.0, builds: 3 };
+        let summary =
+            summarize(CargoCommand::Check, &check, &baseline, &HashMap::new(), &costs, &basis, UnitSource::Metadata);
+
+        assert_eq!(summary.units, 4);
+        assert_eq!(summary.units_by_mode[&UnitMode::Build], 2);
+        assert_eq!(summary.packages, 2);
+        // syn is built in full either way, app only checked: (5 + 30) / 40 of the build
+        assert_eq!(summary.estimated_secs, Some(35.0));
+        assert!(summary.estimate_basis.starts_with("incremental"));
+        assert!(summary
+            .to_string()
+            .starts_with("cargo check: 4 units (2 build, 1 check, 1 build-script run) across 2 packages"));
+
+        let spread = summarize(
+            CargoCommand::Check,
+            &check,
+            &baseline,
+            &HashMap::new(),
+            &costs,
+            &DurationBasis::Timings { cpus: 8 },
+            UnitSource::Metadata,
+        );
+        assert_eq!(spread.estimated_secs, Some(30.0));
+    }
+}
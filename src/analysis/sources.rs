@@ -0,0 +1,306 @@
+//! Source lines of the workspace, without vendored and generated code.
+//!
+//! Vendored crates and checked-in protobuf or bindgen output can dwarf the
+//! code a team writes, and counting them makes a small project look like a
+//! large one. [`source_stats`] counts the `.rs` files below the workspace
+//! root and leaves out those that
+//!
+//! - live in a vendored directory (`vendor/`, `third_party/`),
+//! - `.gitattributes` marks `linguist-generated` or `linguist-vendored`,
+//! - are named like code copied out of `OUT_DIR` (`*.pb.rs`, `generated/`),
+//! - or start with a generator's header comment.
+//!
+//! `[analysis] exclude` globs in `cargo-optimize.toml` add exclusions and
+//! `include` globs overrule all of the above.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Directory names holding vendored code
+const VENDORED_DIRS: &[&str] = &["vendor", "third_party", "third-party"];
+
+/// Paths of code generated into `OUT_DIR` and copied into the source tree
+const GENERATED_PATTERNS: &[&str] = &["**/generated/**", "**/*.pb.rs", "**/*_generated.rs"];
+
+/// Generator header comments (prost, bindgen, other code generators)
+const GENERATED_MARKERS: &[&str] = &["@generated", "automatically generated by rust-bindgen", "DO NOT EDIT"];
+
+/// Header lines searched for a generator comment
+const HEADER_LINES: usize = 10;
+
+/// Files counted at most, to bound the walk on huge trees
+const MAX_FILES: usize = 50_000;
+
+/// Globs overriding the exclusion heuristics, from `[analysis]` in cargo-optimize.toml
+///
+/// Globs are relative to the workspace root; `*` matches within a path
+/// component, `**` any number of them, and a glob without `/` matches a
+/// file or directory name at any depth.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnalysisSettings {
+    /// Also leave out files matching these globs
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Count files matching these globs even when a heuristic excludes them
+    #[serde(default)]
+    pub include: Vec<String>,
+}
+
+impl AnalysisSettings {
+    /// The `[analysis]` section of the workspace's cargo-optimize.toml, if any
+    pub fn load(workspace_root: &Path) -> AnalysisSettings {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            analysis: AnalysisSettings,
+        }
+        let Ok(content) = fs::read_to_string(workspace_root.join("cargo-optimize.toml")) else {
+            return AnalysisSettings::default();
+        };
+        match toml::from_str::<File>(&content) {
+            Ok(file) => file.analysis,
+            Err(e) => {
+                debug!("Ignoring [analysis] of cargo-optimize.toml: {}", e);
+                AnalysisSettings::default()
+            }
+        }
+    }
+}
+
+/// Why source files were left out
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "kebab-case")]
+pub enum ExclusionReason {
+    /// Below a vendored directory, relative to the workspace root
+    Vendored(PathBuf),
+    /// Marked by a `.gitattributes` pattern
+    GitAttributes(String),
+    /// Named like code copied out of `OUT_DIR`
+    GeneratedName(&'static str),
+    /// Starts with a generator's header comment
+    GeneratedHeader(&'static str),
+    /// Matched by an `[analysis] exclude` glob
+    Configured(String),
+}
+
+impl fmt::Display for ExclusionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExclusionReason::Vendored(dir) => write!(f, "vendored in {}", dir.display()),
+            ExclusionReason::GitAttributes(pattern) => write!(f, "marked generated or vendored in .gitattributes ({})", pattern),
+            ExclusionReason::GeneratedName(pattern) => write!(f, "generated code ({})", pattern),
+            ExclusionReason::GeneratedHeader(marker) => write!(f, "generated code (header says {:?})", marker),
+            ExclusionReason::Configured(glob) => write!(f, "excluded by [analysis] ({})", glob),
+        }
+    }
+}
+
+/// Files and lines left out for one reason
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExcludedSources {
+    /// Why they were left out
+    pub reason: ExclusionReason,
+    /// Rust files
+    pub files: usize,
+    /// Lines in them
+    pub lines: usize,
+}
+
+/// Rust sources the team compiles and edits
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SourceStats {
+    /// Rust files counted
+    pub files: usize,
+    /// Lines in them
+    pub lines: usize,
+    /// What was left out, by reason
+    pub excluded: Vec<ExcludedSources>,
+}
+
+impl SourceStats {
+    /// Lines left out for any reason
+    pub fn excluded_lines(&self) -> usize {
+        self.excluded.iter().map(|excluded| excluded.lines).sum()
+    }
+}
+
+/// Count the Rust sources below `workspace_root`
+///
+/// Target directories and hidden directories are skipped.
+pub fn source_stats(workspace_root: &Path, settings: &AnalysisSettings) -> SourceStats {
+    let attributes = gitattributes_patterns(workspace_root);
+    let mut stats = SourceStats::default();
+    let mut excluded: BTreeMap<ExclusionReason, (usize, usize)> = BTreeMap::new();
+    let mut files = Vec::new();
+    collect_rust_files(workspace_root, &mut files);
+    // Each file is read, classified and dropped on a worker thread
+    let classified = super::parallel::par_map(&files, |path| {
+        let content = fs::read_to_string(path).ok()?;
+        let relative = path.strip_prefix(workspace_root).unwrap_or(path);
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let lines = content.lines().count();
+        let reason = if settings.include.iter().any(|glob| glob_matches(glob, &relative)) {
+            None
+        } else {
+            exclusion_reason(&relative, &content, settings, &attributes)
+        };
+        Some((reason, lines))
+    });
+    for (reason, lines) in classified.into_iter().flatten() {
+        match reason {
+            Some(reason) => {
+                let entry = excluded.entry(reason).or_default();
+                entry.0 += 1;
+                entry.1 += lines;
+            }
+            None => {
+                stats.files += 1;
+                stats.lines += lines;
+            }
+        }
+    }
+    stats.excluded = excluded
+        .into_iter()
+        .map(|(reason, (files, lines))| ExcludedSources { reason, files, lines })
+        .collect();
+    stats
+}
+
+/// Why the file at `relative` is left out, if it is
+fn exclusion_reason(
+    relative: &str,
+    content: &str,
+    settings: &AnalysisSettings,
+    attributes: &[String],
+) -> Option<ExclusionReason> {
+    if let Some(glob) = settings.exclude.iter().find(|glob| glob_matches(glob, relative)) {
+        return Some(ExclusionReason::Configured(glob.clone()));
+    }
+    let components: Vec<&str> = relative.split('/').collect();
+    if let Some(index) = components[..components.len() - 1]
+        .iter()
+        .position(|component| VENDORED_DIRS.contains(component))
+    {
+        return Some(ExclusionReason::Vendored(components[..=index].iter().collect()));
+    }
+    if let Some(pattern) = attributes.iter().find(|pattern| glob_matches(pattern, relative)) {
+        return Some(ExclusionReason::GitAttributes(pattern.clone()));
+    }
+    if let Some(pattern) = GENERATED_PATTERNS.iter().find(|pattern| glob_matches(pattern, relative)) {
+        return Some(ExclusionReason::GeneratedName(pattern));
+    }
+    content
+        .lines()
+        .take(HEADER_LINES)
+        .map(str::trim_start)
+        // Doc comments describe code; generators write plain comments
+        .filter(|line| (line.starts_with("//") && !line.starts_with("///") && !line.starts_with("//!")) || line.starts_with("/*"))
+        .find_map(|line| GENERATED_MARKERS.iter().find(|marker| line.contains(*marker)))
+        .map(|marker| ExclusionReason::GeneratedHeader(marker))
+}
+
+/// Patterns of the root `.gitattributes` setting `linguist-generated` or `linguist-vendored`
+fn gitattributes_patterns(workspace_root: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(workspace_root.join(".gitattributes")) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next()?;
+            let marked = fields.any(|attribute| {
+                matches!(
+                    attribute,
+                    "linguist-generated" | "linguist-generated=true" | "linguist-vendored" | "linguist-vendored=true"
+                )
+            });
+            marked.then(|| pattern.to_string())
+        })
+        .collect()
+}
+
+fn collect_rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    if files.len() >= MAX_FILES || dir.join("CACHEDIR.TAG").exists() {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                collect_rust_files(&path, files);
+            }
+        } else if file_type.is_file() && name.ends_with(".rs") && files.len() < MAX_FILES {
+            files.push(path);
+        }
+    }
+}
+
+/// Whether `glob` matches the `/`-separated `path`
+///
+/// A glob without `/` matches the name at any depth and one ending in `/`
+/// everything below the directory, as in `.gitignore`.
+pub(crate) fn glob_matches(glob: &str, path: &str) -> bool {
+    let mut glob = glob.trim_start_matches('/').to_string();
+    if !glob.trim_end_matches('/').contains('/') {
+        glob = format!("**/{}", glob);
+    }
+    if glob.ends_with('/') {
+        glob.push_str("**");
+    }
+    let glob: Vec<&str> = glob.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    // A glob naming a directory matches everything below it
+    (1..=path.len()).any(|end| match_components(&glob, &path[..end]))
+}
+
+fn match_components(glob: &[&str], path: &[&str]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(component, path_rest)| match_name(first.as_bytes(), component.as_bytes()) && match_components(rest, path_rest)),
+    }
+}
+
+/// Match one path component against `*` and `?` wildcards
+fn match_name(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_name(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*.pb.rs", "crates/api/src/user.pb.rs"));
+        assert!(glob_matches("src/proto/", "src/proto/v1/user.rs"));
+        assert!(glob_matches("/src/bindings.rs", "src/bindings.rs"));
+        assert!(glob_matches("**/generated/**", "src/generated/mod.rs"));
+        assert!(glob_matches("crates/*/src/ffi.rs", "crates/sys/src/ffi.rs"));
+        assert!(glob_matches("generated", "src/generated/mod.rs"));
+        assert!(!glob_matches("src/*.rs", "src/nested/lib.rs"));
+        assert!(!glob_matches("*.pb.rs", "src/lib.rs"));
+    }
+}
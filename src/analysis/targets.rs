@@ -0,0 +1,314 @@
+//! Example, bench and test targets and what they add to every build.
+//!
+//! `cargo build --all-targets`, `cargo test` and `cargo clippy --all-targets`
+//! compile every example and, for `--all-targets`, every bench, each linked
+//! into its own binary. Crates shipping dozens of them pay for that on every
+//! CI run. [`target_report`] enumerates the targets of each workspace member
+//! and estimates their build cost with [`super::estimate_build_cost`], plus a
+//! link per binary. [`TargetReport::suggestions`] then proposes gating them
+//! behind `required-features`, moving them into a separate crate, or building
+//! benches in their own CI job, each with the share of the workspace's own
+//! compile time it saves. Dev-dependencies are not included in the estimate.
+
+use anyhow::{Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand, Target};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{estimate_build_cost, rust_source_bytes};
+
+/// Cost of linking one binary, in the units of [`super::estimate_build_cost`]
+const LINK_COST: u64 = 4;
+
+/// Share of the workspace build cost, in percent, from which a kind of target is worth acting on
+const MIN_SAVINGS_PERCENT: u64 = 10;
+
+/// Targets of one kind in one crate from which a separate crate is suggested
+const SEPARATE_CRATE_TARGETS: usize = 10;
+
+/// Kind of auxiliary target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuxKind {
+    /// `examples/`
+    Example,
+    /// `benches/`
+    Bench,
+    /// `tests/`
+    Test,
+}
+
+impl AuxKind {
+    /// Conventional directory of the targets, as in `[[example]]` auto-discovery
+    pub fn directory(self) -> &'static str {
+        match self {
+            AuxKind::Example => "examples",
+            AuxKind::Bench => "benches",
+            AuxKind::Test => "tests",
+        }
+    }
+
+    fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "example" => Some(AuxKind::Example),
+            "bench" => Some(AuxKind::Bench),
+            "test" => Some(AuxKind::Test),
+            _ => None,
+        }
+    }
+}
+
+/// An example, bench or integration test target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuxTarget {
+    /// Target name
+    pub name: String,
+    /// Kind of target
+    pub kind: AuxKind,
+    /// `required-features`; gated targets are only built when they are enabled
+    pub required_features: Vec<String>,
+    /// Relative build cost, link included
+    pub cost: u64,
+}
+
+/// The targets of one workspace member
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrateTargets {
+    /// Package name
+    pub package: String,
+    /// Path of its `Cargo.toml`
+    pub manifest_path: PathBuf,
+    /// Build cost of the library and binaries
+    pub primary_cost: u64,
+    /// Examples, benches and integration tests
+    pub targets: Vec<AuxTarget>,
+}
+
+impl CrateTargets {
+    /// Targets of `kind` built by `--all-targets` without enabling features
+    pub fn ungated(&self, kind: AuxKind) -> impl Iterator<Item = &AuxTarget> {
+        self.targets
+            .iter()
+            .filter(move |target| target.kind == kind && target.required_features.is_empty())
+    }
+
+    /// Number of targets of `kind`
+    pub fn count(&self, kind: AuxKind) -> usize {
+        self.targets.iter().filter(|target| target.kind == kind).count()
+    }
+
+    /// Build cost of the targets of `kind` built without enabling features
+    pub fn cost(&self, kind: AuxKind) -> u64 {
+        self.ungated(kind).map(|target| target.cost).sum()
+    }
+}
+
+/// How to stop paying for targets on every build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Remedy {
+    /// Declare `required-features` so they are built only on request
+    RequiredFeatures,
+    /// Move them into a crate outside `workspace.default-members`
+    SeparateCrate,
+    /// Leave them out of the main CI job and build them in a job of their own
+    CiMatrix,
+}
+
+/// A proposal for one kind of target in one crate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetSuggestion {
+    /// Package name
+    pub package: String,
+    /// Kind of target
+    pub kind: AuxKind,
+    /// What to do
+    pub remedy: Remedy,
+    /// Targets affected
+    pub targets: usize,
+    /// Estimated share of the workspace's `--all-targets` build cost saved, in percent
+    pub savings_percent: u64,
+}
+
+/// Auxiliary targets of every workspace member
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetReport {
+    /// One entry per member
+    pub crates: Vec<CrateTargets>,
+}
+
+impl TargetReport {
+    /// Number of targets of `kind` across the workspace
+    pub fn count(&self, kind: AuxKind) -> usize {
+        self.crates.iter().map(|krate| krate.count(kind)).sum()
+    }
+
+    /// Build cost of the workspace's own code with `--all-targets`
+    pub fn total_cost(&self) -> u64 {
+        self.crates
+            .iter()
+            .map(|krate| {
+                krate.primary_cost
+                    + [AuxKind::Example, AuxKind::Bench, AuxKind::Test]
+                        .into_iter()
+                        .map(|kind| krate.cost(kind))
+                        .sum::<u64>()
+            })
+            .sum()
+    }
+
+    /// Proposals for examples and benches costing at least [`MIN_SAVINGS_PERCENT`]
+    ///
+    /// Many targets of one kind suggest a separate crate; fewer benches a CI
+    /// job of their own, and fewer examples `required-features`. Integration
+    /// tests are counted but never proposed for removal from builds.
+    pub fn suggestions(&self) -> Vec<TargetSuggestion> {
+        let total = self.total_cost();
+        let mut suggestions = Vec::new();
+        for krate in &self.crates {
+            for kind in [AuxKind::Example, AuxKind::Bench] {
+                let targets = krate.ungated(kind).count();
+                let savings_percent = (krate.cost(kind) * 100).checked_div(total).unwrap_or(0);
+                if targets == 0 || savings_percent < MIN_SAVINGS_PERCENT {
+                    continue;
+                }
+                let remedy = if targets >= SEPARATE_CRATE_TARGETS {
+                    Remedy::SeparateCrate
+                } else if kind == AuxKind::Bench {
+                    Remedy::CiMatrix
+                } else {
+                    Remedy::RequiredFeatures
+                };
+                suggestions.push(TargetSuggestion {
+                    package: krate.package.clone(),
+                    kind,
+                    remedy,
+                    targets,
+                    savings_percent,
+                });
+            }
+        }
+        suggestions.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.savings_percent));
+        suggestions
+    }
+}
+
+/// Enumerate the targets of the workspace at `manifest_path`
+///
+/// Uses `cargo metadata --no-deps`, so nothing is resolved or downloaded.
+pub fn target_report(manifest_path: Option<&Path>) -> Result<TargetReport> {
+    let mut cmd = MetadataCommand::new();
+    cmd.no_deps();
+    if let Some(path) = manifest_path {
+        cmd.manifest_path(path);
+    }
+    let metadata = cmd.exec().context("Failed to execute cargo metadata")?;
+    Ok(target_report_from(&metadata))
+}
+
+/// Build the target report from already loaded metadata
+pub fn target_report_from(metadata: &Metadata) -> TargetReport {
+    let crates = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .map(|package| {
+            let has_kind = |kind: &str| package.targets.iter().any(|t| t.kind.iter().any(|k| k == kind));
+            let root = package.manifest_path.parent().map(|p| p.as_std_path()).unwrap_or(Path::new("."));
+            let binaries = package.targets.iter().filter(|t| t.kind.iter().any(|k| k == "bin")).count() as u64;
+            let primary_cost = estimate_build_cost(
+                rust_source_bytes(&root.join("src")),
+                has_kind("custom-build"),
+                has_kind("proc-macro"),
+            ) + LINK_COST * binaries;
+            let targets = package
+                .targets
+                .iter()
+                .filter_map(|target| {
+                    let kind = target.kind.iter().find_map(|kind| AuxKind::from_kind(kind))?;
+                    Some(AuxTarget {
+                        name: target.name.clone(),
+                        kind,
+                        required_features: target.required_features.clone(),
+                        cost: estimate_build_cost(target_source_bytes(target), false, false) + LINK_COST,
+                    })
+                })
+                .collect();
+            CrateTargets {
+                package: package.name.clone(),
+                manifest_path: package.manifest_path.clone().into_std_path_buf(),
+                primary_cost,
+                targets,
+            }
+        })
+        .collect();
+    TargetReport { crates }
+}
+
+/// Source size of a target: its file, or its directory for `examples/<name>/main.rs`
+fn target_source_bytes(target: &Target) -> u64 {
+    let path = target.src_path.as_std_path();
+    match path.parent() {
+        Some(dir) if path.file_name().is_some_and(|name| name == "main.rs") && !dir.ends_with("src") => {
+            rust_source_bytes(dir)
+        }
+        _ => fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(name: &str, kind: AuxKind, cost: u64) -> AuxTarget {
+        AuxTarget { name: name.to_string(), kind, required_features: Vec::new(), cost }
+    }
+
+    #[test]
+    fn test_suggestions_by_count_and_kind() {
+        let mut demos: Vec<AuxTarget> = (0..12).map(|i| target(&format!("demo{}", i), AuxKind::Example, 5)).collect();
+        demos.push(AuxTarget { required_features: vec!["gpu".to_string()], ..target("gpu", AuxKind::Example, 50) });
+        demos.extend((0..3).map(|i| target(&format!("bench{}", i), AuxKind::Bench, 10)));
+        demos.push(target("integration", AuxKind::Test, 30));
+        let report = TargetReport {
+            crates: vec![
+                CrateTargets {
+                    package: "engine".to_string(),
+                    manifest_path: PathBuf::from("engine/Cargo.toml"),
+                    primary_cost: 80,
+                    targets: demos,
+                },
+                CrateTargets {
+                    package: "cli".to_string(),
+                    manifest_path: PathBuf::from("cli/Cargo.toml"),
+                    primary_cost: 20,
+                    targets: vec![target("tiny", AuxKind::Example, 5)],
+                },
+            ],
+        };
+
+        // Gated targets are not part of the --all-targets build
+        assert_eq!(report.total_cost(), 80 + 60 + 30 + 30 + 20 + 5);
+        assert_eq!(report.count(AuxKind::Example), 14);
+        assert_eq!(
+            report.suggestions(),
+            vec![
+                TargetSuggestion {
+                    package: "engine".to_string(),
+                    kind: AuxKind::Example,
+                    remedy: Remedy::SeparateCrate,
+                    targets: 12,
+                    savings_percent: 26,
+                },
+                TargetSuggestion {
+                    package: "engine".to_string(),
+                    kind: AuxKind::Bench,
+                    remedy: Remedy::CiMatrix,
+                    targets: 3,
+                    savings_percent: 13,
+                },
+            ]
+        );
+    }
+}
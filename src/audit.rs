@@ -0,0 +1,254 @@
+//! Append-only audit log of the files cargo-optimize changed.
+//!
+//! Every write cargo-optimize makes to a project file (an apply, a linker
+//! rollback, an uninstall, an accepted proposal, a restored backup, edits to
+//! `Cargo.toml` and git hooks) adds one JSON line to
+//! `.cargo-optimize/audit.log`: when, which version, which action, which
+//! file, and the SHA-256 of the file before and after. Lines are only ever
+//! appended, so the log can be committed and reviewed like a changelog of
+//! the build infrastructure. [`AuditLog::query`] filters the history and
+//! [`render`] formats it for people.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+use crate::config::sha256_hex;
+use crate::paths::to_slash;
+
+/// Location of the audit log, relative to the project root
+pub const AUDIT_LOG: &str = ".cargo-optimize/audit.log";
+
+/// Hex digits of a hash shown by [`render`]
+const SHORT_HASH: usize = 12;
+
+/// One change to one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the file was written (seconds since the Unix epoch)
+    pub timestamp: u64,
+    /// cargo-optimize version that wrote it
+    pub version: String,
+    /// What was done (e.g. "apply", "uninstall", "rollback-linker")
+    pub action: String,
+    /// File written, relative to the project root when inside it
+    pub file: String,
+    /// SHA-256 of the content before; `None` when the file did not exist
+    pub before: Option<String>,
+    /// SHA-256 of the content after; `None` when the file was removed
+    pub after: Option<String>,
+}
+
+impl AuditEntry {
+    /// Whether the change created the file
+    pub fn created(&self) -> bool {
+        self.before.is_none()
+    }
+
+    /// Whether the change removed the file
+    pub fn removed(&self) -> bool {
+        self.after.is_none()
+    }
+}
+
+/// Which entries [`AuditLog::query`] returns; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditQuery {
+    /// Only this action
+    pub action: Option<String>,
+    /// Only this file, as recorded in [`AuditEntry::file`]
+    pub file: Option<String>,
+    /// Only changes at or after this time (seconds since the Unix epoch)
+    pub since: Option<u64>,
+}
+
+impl AuditQuery {
+    /// Whether `entry` passes the filter
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.action.as_ref().is_none_or(|action| *action == entry.action)
+            && self.file.as_ref().is_none_or(|file| *file == entry.file)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+    }
+}
+
+/// The audit log of one project
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    root: PathBuf,
+}
+
+impl AuditLog {
+    /// The audit log of the project at `project_root`
+    pub fn new(project_root: &Path) -> Self {
+        AuditLog { root: project_root.to_path_buf() }
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> PathBuf {
+        self.root.join(AUDIT_LOG)
+    }
+
+    /// Append a change of `file` from `before` to `after` content
+    ///
+    /// `None` stands for a missing file. Nothing is recorded when the content
+    /// did not change.
+    pub fn record(&self, action: &str, file: &Path, before: Option<&str>, after: Option<&str>) -> Result<()> {
+        let before = before.map(sha256_hex);
+        let after = after.map(sha256_hex);
+        if before == after {
+            return Ok(());
+        }
+        let entry = AuditEntry {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            action: action.to_string(),
+            file: to_slash(file.strip_prefix(&self.root).unwrap_or(file)),
+            before,
+            after,
+        };
+        let mut line = serde_json::to_string(&entry).context("Failed to serialize audit entry")?;
+        line.push('\n');
+
+        let path = self.path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        // One write per line, so concurrent runs never interleave within an entry
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {:?}", path))?;
+        log.write_all(line.as_bytes()).with_context(|| format!("Failed to append to {:?}", path))
+    }
+
+    /// Every entry, oldest first
+    ///
+    /// A missing log is empty. Lines that do not parse are skipped.
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let path = self.path();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    debug!("Skipping unreadable audit log line: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Entries matching `query`, oldest first
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        Ok(self.entries()?.into_iter().filter(|entry| query.matches(entry)).collect())
+    }
+}
+
+/// Record a change in the audit log of `project_root`, warning on failure
+///
+/// The file has already been written by then, so a log that cannot be
+/// appended to does not fail the operation.
+pub fn record(project_root: &Path, action: &str, file: &Path, before: Option<&str>, after: Option<&str>) {
+    if let Err(e) = AuditLog::new(project_root).record(action, file, before, after) {
+        warn!("Failed to record {} of {:?} in the audit log: {:#}", action, file, e);
+    }
+}
+
+/// Render entries as a changelog, one section per day (UTC)
+pub fn render(entries: &[AuditEntry]) -> String {
+    let mut out = String::new();
+    let mut day = None;
+    for entry in entries {
+        let days = entry.timestamp / 86_400;
+        if day != Some(days) {
+            let (year, month, date) = civil_date(days);
+            let _ = writeln!(out, "{}## {:04}-{:02}-{:02}\n", if day.is_some() { "\n" } else { "" }, year, month, date);
+            day = Some(days);
+        }
+        let secs = entry.timestamp % 86_400;
+        let change = match (&entry.before, &entry.after) {
+            (None, Some(after)) => format!("created ({})", short(after)),
+            (Some(before), None) => format!("removed (was {})", short(before)),
+            (Some(before), Some(after)) => format!("modified ({} -> {})", short(before), short(after)),
+            (None, None) => "unchanged".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "- {:02}:{:02}:{:02} `{}` {} {}, cargo-optimize {}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            entry.action,
+            entry.file,
+            change,
+            entry.version
+        );
+    }
+    out
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(SHORT_HASH)]
+}
+
+/// Year, month and day of a day count since 1970-01-01
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Howard Hinnant's days-to-civil algorithm, shifted to eras starting in March
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_query_and_render() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = AuditLog::new(dir.path());
+        let config = dir.path().join(".cargo/config.toml");
+        log.record("apply", &config, None, Some("[build]\njobs = 4\n")).unwrap();
+        log.record("apply", &config, Some("same"), Some("same")).unwrap();
+        log.record("uninstall", &config, Some("[build]\njobs = 4\n"), None).unwrap();
+        fs::write(log.path(), fs::read_to_string(log.path()).unwrap() + "not json\n").unwrap();
+
+        let entries = log.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file, ".cargo/config.toml");
+        assert!(entries[0].created() && entries[1].removed());
+        assert_eq!(entries[0].after, entries[1].before);
+
+        let query = AuditQuery { action: Some("uninstall".to_string()), ..Default::default() };
+        assert_eq!(log.query(&query).unwrap(), [entries[1].clone()]);
+
+        let rendered = render(&[AuditEntry { timestamp: 1_760_659_391, ..entries[0].clone() }]);
+        assert!(rendered.starts_with("## 2025-10-17\n\n- 00:03:11 `apply` .cargo/config.toml created ("), "{}", rendered);
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(20_743), (2026, 10, 17));
+    }
+}
@@ -0,0 +1,287 @@
+//! cargo-optimize - apply build optimizations without a build script
+//!
+//! Runs what `cargo_optimize::auto_configure()` runs from a `build.rs`, on
+//! demand, and manages the `cargo optimize` alias that lets a project adopt
+//! it without a build dependency.
+//!
+//! Usage:
+//!   cargo optimize [apply] [--strict]
+//!   cargo optimize install-alias [--installed] [--git-hook]
+//!   cargo optimize uninstall-alias [--git-hook]
+//!   cargo optimize purge [--yes]
+//!   cargo optimize verify
+//!   cargo optimize doctor [--repair]
+//!   cargo optimize snapshot [FILE]
+//!   cargo optimize compare A B
+//!   cargo optimize weight
+//!   cargo optimize nix [devenv|flake]
+//!   cargo optimize graph [dot|mermaid]
+//!   cargo optimize what-builds [build|check|test|doc]...
+//!
+//! Exit codes are stable; see `cargo_optimize::exit::ExitStatus`.
+
+use cargo_optimize::adoption::{self, AliasTarget};
+use cargo_optimize::analysis::{self, build_weight};
+use cargo_optimize::analysis::graph::{GraphFormat, GraphOptions};
+use cargo_optimize::analysis::simulate::{self, CargoCommand};
+use cargo_optimize::config::ConfigManager;
+use cargo_optimize::exit::ExitStatus;
+use cargo_optimize::hooks;
+use cargo_optimize::mvp::{self, MvpConfig};
+use cargo_optimize::nix::NixFormat;
+use cargo_optimize::report::{self, Snapshot};
+use cargo_optimize::strict::Requirement;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: cargo optimize [COMMAND]
+
+Commands:
+  apply             Configure the fastest linker in .cargo/config.toml (default)
+  install-alias     Define the `cargo optimize` alias in .cargo/config.toml
+  uninstall-alias   Remove the alias again
+  purge             List everything cargo-optimize wrote or keeps; --yes removes it
+  verify            Check that .cargo/config.toml is what apply would write
+  doctor            Check the sccache cache; --repair fixes what it finds
+  snapshot [FILE]   Save this machine's build setup (default: snapshot.json)
+  compare A B       Explain the differences between two snapshots
+  weight            Measure what cargo-optimize adds to a clean build of the project
+  nix [FORMAT]      Print the settings for devenv.nix or a flake's mkShell instead of writing them
+  graph [FORMAT]    Print the dependency graph with build costs as dot (default) or mermaid
+  what-builds [CMD]...
+                    Show what build, check, test and doc compile and how long they take
+
+Options of apply:
+  --strict          Fail unless a fast linker is configured, besides what `required` declares
+
+Options of install-alias and uninstall-alias:
+  --installed       Rely on `cargo install`; defines `cargo optimize-install` instead
+  --git-hook        Also (un)install git hooks re-running `cargo optimize` on drift
+
+Exit codes:
+  0 success, 1 failure, 2 drift (verify), 3 policy violation,
+  4 unsupported platform, 5 rolled back, 6 invalid config, 7 busy,
+  8 required optimization missing, 64 usage";
+
+fn main() -> ExitCode {
+    // Cargo passes the subcommand name when run as `cargo optimize`
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .enumerate()
+        .filter(|(index, arg)| !(*index == 0 && arg == "optimize"))
+        .map(|(_, arg)| arg)
+        .collect();
+    let command = args.first().map(String::as_str).unwrap_or("apply");
+    let flag = |name: &str| args.iter().skip(1).any(|arg| arg == name);
+    let project_root = Path::new(".");
+
+    match command {
+        "apply" => {
+            // --strict fails unless a fast linker ends up configured
+            let required: &[Requirement] = if flag("--strict") { &[Requirement::Linker] } else { &[] };
+            mvp::auto_configure_requiring(MvpConfig::default(), None, required).into()
+        }
+        "install-alias" => {
+            let target = if flag("--installed") {
+                AliasTarget::Installed
+            } else {
+                AliasTarget::Package
+            };
+            match adoption::install_alias(project_root, target, flag("--git-hook")) {
+                Ok(installed) => {
+                    if installed.config_changed {
+                        println!("Defined `cargo {}` in .cargo/config.toml", installed.alias);
+                    } else {
+                        println!("`cargo {}` is already defined in .cargo/config.toml", installed.alias);
+                    }
+                    for hook in &installed.hooks {
+                        println!("Installed {}", hook.display());
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "uninstall-alias" => {
+            let result = adoption::uninstall_alias(project_root).and_then(|changed| {
+                let hooks = if flag("--git-hook") {
+                    hooks::uninstall_git_hooks(project_root)?
+                } else {
+                    Vec::new()
+                };
+                Ok((changed, hooks))
+            });
+            match result {
+                Ok((changed, hooks)) => {
+                    if changed {
+                        println!("Removed the cargo-optimize aliases from .cargo/config.toml");
+                    }
+                    for hook in &hooks {
+                        println!("Removed the drift check from {}", hook.display());
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "purge" => {
+            let yes = flag("--yes");
+            match ConfigManager::new().and_then(|manager| manager.purge(!yes)) {
+                Ok(summary) => {
+                    print!("{}", summary);
+                    if summary.dry_run && !summary.artifacts.is_empty() {
+                        println!("Run `cargo optimize purge --yes` to remove it");
+                    }
+                    if summary.failed.is_empty() {
+                        ExitCode::SUCCESS
+                    } else {
+                        ExitStatus::Failure.into()
+                    }
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "verify" => match ConfigManager::new().and_then(|manager| manager.verify()) {
+            Ok(verification) => {
+                let status = verification.exit_status();
+                for conflict in &verification.report.policy_conflicts {
+                    println!("policy conflict: {}: {}", conflict.setting, conflict.reason);
+                }
+                for key in &verification.drifted {
+                    println!("drifted: {}", key);
+                }
+                match status {
+                    ExitStatus::Success => println!(".cargo/config.toml is up to date"),
+                    ExitStatus::Drift => println!("Run `cargo optimize` to update .cargo/config.toml"),
+                    other => eprintln!("cargo-optimize: {}", other),
+                }
+                status.into()
+            }
+            Err(e) => fail(e),
+        },
+        "doctor" => {
+            let report = ConfigManager::new().and_then(|mut manager| {
+                if flag("--repair") {
+                    manager.config_mut().global.sccache_auto_repair = true;
+                }
+                manager.doctor()
+            });
+            match report {
+                Ok(report) => {
+                    print!("{}", report);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "snapshot" => {
+            let path = Path::new(args.get(1).map(String::as_str).unwrap_or("snapshot.json"));
+            match report::snapshot().and_then(|snapshot| snapshot.save(path)) {
+                Ok(()) => {
+                    println!("Saved the snapshot to {}", path.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "compare" => {
+            let [a, b] = [1, 2].map(|index| args.get(index).map(Path::new));
+            let (Some(a), Some(b)) = (a, b) else {
+                eprintln!("error: `compare` needs two snapshot files\n\n{}", USAGE);
+                return ExitStatus::Usage.into();
+            };
+            match Snapshot::load(a).and_then(|a| Ok(report::compare(&a, &Snapshot::load(b)?))) {
+                Ok(comparison) => {
+                    print!("{}", comparison);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "weight" => match build_weight::measure(project_root) {
+            Ok(weight) => {
+                println!("cargo-optimize as a build-dependency: {}", weight);
+                match weight.advice(ConfigManager::new().map_or(build_weight::DEFAULT_WARN_RATIO, |manager| {
+                    manager.config().global.build_weight_warn_ratio
+                })) {
+                    Some(advice) => println!("{}", advice),
+                    None => println!("{:.0}% of the project's own compile time", weight.ratio() * 100.0),
+                }
+                ExitCode::SUCCESS
+            }
+            Err(e) => fail(e),
+        },
+        "nix" => {
+            let format = match args.get(1).map(|format| (format, NixFormat::parse(format))) {
+                None => None,
+                Some((_, Some(format))) => Some(format),
+                Some((other, None)) => {
+                    eprintln!("error: unknown Nix format `{}`, expected devenv or flake\n\n{}", other, USAGE);
+                    return ExitStatus::Usage.into();
+                }
+            };
+            match ConfigManager::new().and_then(|manager| manager.nix_snippet(format)) {
+                Ok(snippet) => {
+                    print!("{}", snippet.render());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "graph" => {
+            let format = match args.get(1).map(|format| (format, GraphFormat::parse(format))) {
+                None => GraphFormat::Dot,
+                Some((_, Some(format))) => format,
+                Some((other, None)) => {
+                    eprintln!("error: unknown graph format `{}`, expected dot or mermaid\n\n{}", other, USAGE);
+                    return ExitStatus::Usage.into();
+                }
+            };
+            match analysis::export_graph(None, format, &GraphOptions::default()) {
+                Ok(graph) => {
+                    print!("{}", graph);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "what-builds" => {
+            let mut commands = Vec::new();
+            for name in &args[1..] {
+                match CargoCommand::parse(name) {
+                    Some(command) => commands.push(command),
+                    None => {
+                        eprintln!("error: unknown command `{}`, expected build, check, test or doc\n\n{}", name, USAGE);
+                        return ExitStatus::Usage.into();
+                    }
+                }
+            }
+            if commands.is_empty() {
+                commands = CargoCommand::ALL.to_vec();
+            }
+            match simulate::simulate(None, &commands) {
+                Ok(summaries) => {
+                    for summary in summaries {
+                        println!("{}", summary);
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => fail(e),
+            }
+        }
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        other => {
+            eprintln!("error: unknown command `{}`\n\n{}", other, USAGE);
+            ExitStatus::Usage.into()
+        }
+    }
+}
+
+fn fail(error: anyhow::Error) -> ExitCode {
+    eprintln!("error: {:#}", error);
+    ExitStatus::of_error(&error).into()
+}
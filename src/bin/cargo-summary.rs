@@ -78,18 +78,14 @@ fn main() {
     
     // Stream stdout in a separate thread
     let stdout_thread = thread::spawn(move || {
-        for line in stdout_reader.lines() {
-            if let Ok(line) = line {
-                println!("{}", line);
-            }
+        for line in stdout_reader.lines().map_while(Result::ok) {
+            println!("{}", line);
         }
     });
     
     // Stream stderr (where nextest output actually goes)
-    for line in stderr_reader.lines() {
-        if let Ok(line) = line {
-            eprintln!("{}", line);
-        }
+    for line in stderr_reader.lines().map_while(Result::ok) {
+        eprintln!("{}", line);
     }
     
     // Wait for stdout thread to finish
@@ -0,0 +1,161 @@
+//! Detection of cargo builds running concurrently with cargo-optimize.
+//!
+//! Cargo holds an exclusive file lock on `<target>/<profile>/.cargo-lock`
+//! while it builds. Rewriting `.cargo/config.toml` while another build is in
+//! flight leaves that build on the old settings and the next one on the new,
+//! so the apply is deferred instead.
+
+use std::env;
+use std::fs::{self, File, TryLockError};
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+use crate::config::effective_target_dir;
+
+/// Name of the lock file cargo keeps in each profile output directory
+pub const CARGO_LOCK_FILE: &str = ".cargo-lock";
+
+/// Target directory of a project
+///
+/// `CARGO_TARGET_DIR`, then `build.target-dir` in the project's
+/// `.cargo/config.toml`, then `<root>/target`; see [`effective_target_dir`].
+pub fn target_dir(project_root: &Path) -> PathBuf {
+    let env_dir = env::var_os("CARGO_TARGET_DIR").map(PathBuf::from);
+    let doc = fs::read_to_string(project_root.join(".cargo").join("config.toml"))
+        .ok()
+        .and_then(|content| content.parse::<DocumentMut>().ok())
+        .unwrap_or_default();
+    effective_target_dir(project_root, env_dir.as_deref(), &doc).path
+}
+
+/// Find build locks under the project's target directory held by other builds
+///
+/// When running inside a build script, the locks of the build that invoked
+/// us are ignored (see [`owned_by_build`]).
+pub fn active_build_locks(project_root: &Path) -> Vec<PathBuf> {
+    let own_build = env::var_os("OUT_DIR").map(PathBuf::from);
+    let triple = env::var("TARGET").ok();
+    lock_files(&target_dir(project_root))
+        .into_iter()
+        .filter(|lock| !own_build.as_ref().is_some_and(|out| owned_by_build(lock, out, triple.as_deref())))
+        .filter(|lock| is_locked(lock))
+        .collect()
+}
+
+/// Whether the build whose build script has `out_dir` as `OUT_DIR` holds `lock`
+///
+/// That is the profile directory containing `OUT_DIR`. A build for an
+/// explicit `--target triple` writes to `<target>/<triple>/<profile>` and
+/// also holds the host `<target>/<profile>`, where build scripts and proc
+/// macros are compiled.
+pub fn owned_by_build(lock: &Path, out_dir: &Path, triple: Option<&str>) -> bool {
+    let profile_dir = lock.parent().unwrap_or(lock);
+    if out_dir.starts_with(profile_dir) {
+        return true;
+    }
+    let (Some(target_dir), Some(profile), Some(triple)) = (profile_dir.parent(), profile_dir.file_name(), triple) else {
+        return false;
+    };
+    out_dir.starts_with(target_dir.join(triple).join(profile))
+}
+
+/// Lock files in `<target>/<profile>` and `<target>/<triple>/<profile>`
+fn lock_files(target_dir: &Path) -> Vec<PathBuf> {
+    let mut locks = Vec::new();
+    let Ok(entries) = fs::read_dir(target_dir) else {
+        return locks;
+    };
+
+    for dir in entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()) {
+        let lock = dir.join(CARGO_LOCK_FILE);
+        if lock.is_file() {
+            locks.push(lock);
+            continue;
+        }
+        // Cross-compilation output: <target>/<triple>/<profile>/.cargo-lock
+        if let Ok(nested) = fs::read_dir(&dir) {
+            for profile_dir in nested.filter_map(|e| e.ok()).map(|e| e.path()) {
+                let lock = profile_dir.join(CARGO_LOCK_FILE);
+                if lock.is_file() {
+                    locks.push(lock);
+                }
+            }
+        }
+    }
+    locks.sort();
+    locks
+}
+
+/// Whether another process holds the lock on `path`
+pub fn is_locked(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    // The lock is released again when `file` is dropped
+    matches!(file.try_lock(), Err(TryLockError::WouldBlock))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detects_held_lock() {
+        let temp = TempDir::new().unwrap();
+        let debug = temp.path().join("target").join("debug");
+        let cross = temp.path().join("target").join("thumbv7em-none-eabihf").join("release");
+        fs::create_dir_all(&debug).unwrap();
+        fs::create_dir_all(&cross).unwrap();
+        fs::write(debug.join(CARGO_LOCK_FILE), "").unwrap();
+        fs::write(cross.join(CARGO_LOCK_FILE), "").unwrap();
+
+        assert_eq!(lock_files(&temp.path().join("target")).len(), 2);
+        assert!(!is_locked(&debug.join(CARGO_LOCK_FILE)));
+
+        let holder = File::open(cross.join(CARGO_LOCK_FILE)).unwrap();
+        holder.lock().unwrap();
+        assert!(is_locked(&cross.join(CARGO_LOCK_FILE)));
+        drop(holder);
+        assert!(!is_locked(&cross.join(CARGO_LOCK_FILE)));
+    }
+
+    #[test]
+    fn test_configured_target_dir_is_checked() {
+        if env::var_os("CARGO_TARGET_DIR").is_some() {
+            return;
+        }
+        let temp = TempDir::new().unwrap();
+        assert_eq!(target_dir(temp.path()), temp.path().join("target"));
+
+        fs::create_dir_all(temp.path().join(".cargo")).unwrap();
+        fs::write(temp.path().join(".cargo/config.toml"), "[build]\ntarget-dir = \"build/out\"\n").unwrap();
+        assert_eq!(target_dir(temp.path()), temp.path().join("build/out"));
+
+        let debug = temp.path().join("build/out/debug");
+        fs::create_dir_all(&debug).unwrap();
+        fs::write(debug.join(CARGO_LOCK_FILE), "").unwrap();
+        let holder = File::open(debug.join(CARGO_LOCK_FILE)).unwrap();
+        holder.lock().unwrap();
+        assert_eq!(active_build_locks(temp.path()), [debug.join(CARGO_LOCK_FILE)]);
+    }
+
+    #[test]
+    fn test_own_build_locks() {
+        let target = Path::new("/p/target");
+        let host = target.join("debug").join(CARGO_LOCK_FILE);
+        let cross = target.join("thumbv7em-none-eabihf").join("debug").join(CARGO_LOCK_FILE);
+        let release = target.join("release").join(CARGO_LOCK_FILE);
+
+        let native_out = target.join("debug/build/app-1234/out");
+        assert!(owned_by_build(&host, &native_out, Some("x86_64-unknown-linux-gnu")));
+        assert!(!owned_by_build(&cross, &native_out, Some("x86_64-unknown-linux-gnu")));
+
+        // A cross build also holds the host profile directory
+        let cross_out = target.join("thumbv7em-none-eabihf/debug/build/app-1234/out");
+        assert!(owned_by_build(&cross, &cross_out, Some("thumbv7em-none-eabihf")));
+        assert!(owned_by_build(&host, &cross_out, Some("thumbv7em-none-eabihf")));
+        assert!(!owned_by_build(&release, &cross_out, Some("thumbv7em-none-eabihf")));
+        assert!(!owned_by_build(&host, &cross_out, None));
+    }
+}
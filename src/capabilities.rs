@@ -0,0 +1,181 @@
+//! Cargo features gated by toolchain version and channel.
+//!
+//! Cargo ships in lockstep with rustc, so the release reported by `rustc -vV`
+//! is the cargo version too. Generators ask [`Feature::availability`] whether
+//! a feature can be configured, and whether it is already on by default,
+//! instead of comparing versions themselves: [`MATRIX`] is the only place
+//! that knows when a feature was added to nightly, stabilized or turned on.
+
+use std::fmt;
+
+use crate::toolchain::{Channel, RustVersion, ToolchainSpec};
+
+/// A cargo or rustc feature whose availability depends on the toolchain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// `registries.crates-io.protocol = "sparse"`
+    SparseRegistry,
+    /// `-Zgitoxide` fetches of git dependencies and the index
+    Gitoxide,
+    /// `-Zbuild-std`
+    BuildStd,
+    /// `-Zprofile-rustflags`
+    ProfileRustflags,
+    /// rust-lld as the default linker of x86_64 Linux
+    RustLldDefault,
+}
+
+/// From which version a feature is usable on one channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gate {
+    /// First release where the feature can be configured
+    pub available: Option<RustVersion>,
+    /// First release where it is on without any configuration
+    pub default: Option<RustVersion>,
+}
+
+impl Gate {
+    /// Never usable on the channel
+    const NEVER: Gate = Gate { available: None, default: None };
+}
+
+/// One row of the capability matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability {
+    /// Feature described
+    pub feature: Feature,
+    /// Display name
+    pub name: &'static str,
+    /// Gate on stable and beta
+    pub stable: Gate,
+    /// Gate on nightly
+    pub nightly: Gate,
+}
+
+/// Whether a toolchain can use a feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Availability {
+    /// The toolchain is too old or on the wrong channel
+    Unavailable,
+    /// The feature works once configured
+    Configurable,
+    /// The toolchain already uses the feature; configuring it changes nothing
+    Default,
+}
+
+/// Every version-gated feature
+pub const MATRIX: &[Capability] = &[
+    Capability {
+        feature: Feature::SparseRegistry,
+        name: "sparse registry protocol",
+        stable: Gate { available: Some(RustVersion::new(1, 68, 0)), default: Some(RustVersion::new(1, 70, 0)) },
+        nightly: Gate { available: Some(RustVersion::new(1, 68, 0)), default: Some(RustVersion::new(1, 70, 0)) },
+    },
+    Capability {
+        feature: Feature::Gitoxide,
+        name: "gitoxide fetches",
+        stable: Gate::NEVER,
+        nightly: Gate { available: Some(RustVersion::new(1, 71, 0)), default: None },
+    },
+    Capability {
+        feature: Feature::BuildStd,
+        name: "build-std",
+        stable: Gate::NEVER,
+        nightly: Gate { available: Some(RustVersion::new(1, 40, 0)), default: None },
+    },
+    Capability {
+        feature: Feature::ProfileRustflags,
+        name: "per-profile rustflags",
+        stable: Gate::NEVER,
+        nightly: Gate { available: Some(RustVersion::new(1, 61, 0)), default: None },
+    },
+    Capability {
+        feature: Feature::RustLldDefault,
+        name: "rust-lld as default x86_64 Linux linker",
+        stable: Gate { available: Some(RustVersion::new(1, 90, 0)), default: Some(RustVersion::new(1, 90, 0)) },
+        nightly: Gate { available: Some(RustVersion::new(1, 80, 0)), default: Some(RustVersion::new(1, 80, 0)) },
+    },
+];
+
+impl Feature {
+    /// Row of the feature in [`MATRIX`]
+    pub fn capability(self) -> &'static Capability {
+        MATRIX
+            .iter()
+            .find(|capability| capability.feature == self)
+            .expect("every feature has a row in the capability matrix")
+    }
+
+    /// Whether `toolchain` can use the feature
+    pub fn availability(self, toolchain: &ToolchainSpec) -> Availability {
+        let capability = self.capability();
+        let gate = match toolchain.channel {
+            Channel::Nightly => capability.nightly,
+            _ => capability.stable,
+        };
+        let reached = |version: Option<RustVersion>| version.is_some_and(|version| toolchain.supports(version));
+        if reached(gate.default) {
+            Availability::Default
+        } else if reached(gate.available) {
+            Availability::Configurable
+        } else {
+            Availability::Unavailable
+        }
+    }
+
+    /// Whether `toolchain` can use the feature, configured or by default
+    pub fn is_available(self, toolchain: &ToolchainSpec) -> bool {
+        self.availability(toolchain) != Availability::Unavailable
+    }
+
+    /// What a toolchain needs for the feature, for skip reasons
+    pub fn requirement(self) -> String {
+        let capability = self.capability();
+        match (capability.stable.available, capability.nightly.available) {
+            (Some(stable), _) => format!("requires Rust {} or newer", stable),
+            (None, Some(nightly)) => format!("requires a nightly toolchain ({} or newer)", nightly),
+            (None, None) => "not available on any toolchain".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.capability().name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_rows_are_consistent() {
+        for capability in MATRIX {
+            assert_eq!(capability.feature.capability(), capability);
+            for gate in [capability.stable, capability.nightly] {
+                if let (Some(available), Some(default)) = (gate.available, gate.default) {
+                    assert!(available <= default, "{} is on by default before it exists", capability.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_availability_by_version_and_channel() {
+        let stable = |minor| ToolchainSpec::stable(RustVersion::new(1, minor, 0));
+        let nightly = |minor| ToolchainSpec::nightly(RustVersion::new(1, minor, 0));
+
+        assert_eq!(Feature::SparseRegistry.availability(&stable(67)), Availability::Unavailable);
+        assert_eq!(Feature::SparseRegistry.availability(&stable(69)), Availability::Configurable);
+        assert_eq!(Feature::SparseRegistry.availability(&stable(85)), Availability::Default);
+
+        assert_eq!(Feature::Gitoxide.availability(&stable(85)), Availability::Unavailable);
+        assert_eq!(Feature::Gitoxide.availability(&nightly(85)), Availability::Configurable);
+        assert_eq!(Feature::Gitoxide.requirement(), "requires a nightly toolchain (1.71.0 or newer)");
+
+        assert_eq!(Feature::RustLldDefault.availability(&stable(85)), Availability::Unavailable);
+        assert_eq!(Feature::RustLldDefault.availability(&nightly(85)), Availability::Default);
+        assert!(Feature::BuildStd.is_available(&nightly(80)));
+    }
+}
@@ -0,0 +1,367 @@
+//! Library API for CI bots that comment on pull requests.
+//!
+//! [`pr_summary`] composes a markdown comment from the project analysis, the
+//! planned or applied changes and, when measurements exist, build-time deltas.
+//! The output is stable for identical input so bots can update an existing
+//! comment instead of posting a new one, and it is capped in size. Sections
+//! that do not fit are shortened with an ellipsis note rather than cut mid-line.
+//!
+//! [`CiEnvironment`] detects the CI service, which selects the `ci` context
+//! of the configuration, and [`cache_snippet`] renders cache configuration
+//! for it.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::analysis::ProjectAnalysis;
+use crate::report::{DecisionOutcome, OptimizationReport};
+
+pub mod cache;
+
+pub use cache::{cache_snippet, cache_snippet_for, CacheLayout, CacheSnippet};
+
+/// Default size cap, safely below GitHub's 65536 character comment limit
+pub const DEFAULT_MAX_COMMENT_LEN: usize = 60_000;
+
+/// CI service the process is running under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiEnvironment {
+    /// GitHub Actions
+    GitHubActions,
+    /// GitLab CI
+    GitLabCi,
+    /// CircleCI
+    CircleCi,
+    /// Azure Pipelines
+    AzurePipelines,
+    /// Jenkins
+    Jenkins,
+    /// Buildkite
+    Buildkite,
+    /// Travis CI
+    Travis,
+    /// Any other service setting the conventional `CI` variable
+    Generic,
+}
+
+/// Variables identifying specific CI services, checked in order
+const CI_SERVICE_VARS: &[(&str, CiEnvironment)] = &[
+    ("GITHUB_ACTIONS", CiEnvironment::GitHubActions),
+    ("GITLAB_CI", CiEnvironment::GitLabCi),
+    ("CIRCLECI", CiEnvironment::CircleCi),
+    ("TF_BUILD", CiEnvironment::AzurePipelines),
+    ("JENKINS_URL", CiEnvironment::Jenkins),
+    ("BUILDKITE", CiEnvironment::Buildkite),
+    ("TRAVIS", CiEnvironment::Travis),
+];
+
+impl CiEnvironment {
+    /// Detect the CI service from the process environment
+    pub fn detect() -> Option<Self> {
+        Self::from_env_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Detect the CI service using a custom environment lookup (for testing)
+    ///
+    /// Empty values and `CI=false` (or `0`) do not count.
+    pub fn from_env_lookup<F>(lookup: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let is_set = |key: &str| {
+            lookup(key).is_some_and(|value| {
+                !matches!(value.trim().to_ascii_lowercase().as_str(), "" | "0" | "false")
+            })
+        };
+        CI_SERVICE_VARS
+            .iter()
+            .find(|(key, _)| is_set(key))
+            .map(|(_, service)| *service)
+            .or_else(|| is_set("CI").then_some(CiEnvironment::Generic))
+    }
+}
+
+/// Whether the changes in a report have been written yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    /// Computed but not written (dry run)
+    Planned,
+    /// Written to `.cargo/config.toml`
+    Applied,
+}
+
+/// Build time measured before and after optimization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildTimeDelta {
+    /// What was built (e.g. "clean debug build")
+    pub label: String,
+    /// Duration before optimization
+    pub before: Duration,
+    /// Duration after optimization
+    pub after: Duration,
+}
+
+impl BuildTimeDelta {
+    /// Relative change in percent (negative means faster)
+    pub fn percent_change(&self) -> f64 {
+        let before = self.before.as_secs_f64();
+        if before == 0.0 {
+            return 0.0;
+        }
+        (self.after.as_secs_f64() - before) / before * 100.0
+    }
+}
+
+/// Inputs for a PR summary; every part is optional
+#[derive(Debug, Clone, Copy)]
+pub struct SummaryInput<'a> {
+    /// Project analysis results
+    pub analysis: Option<&'a ProjectAnalysis>,
+    /// Decisions and recommendations from a plan or apply run
+    pub report: Option<&'a OptimizationReport>,
+    /// Whether the report's changes were applied or only planned
+    pub status: ChangeStatus,
+    /// Build-time measurements, if any
+    pub build_times: &'a [BuildTimeDelta],
+}
+
+/// Compose a markdown PR comment capped at [`DEFAULT_MAX_COMMENT_LEN`]
+pub fn pr_summary(input: &SummaryInput<'_>) -> String {
+    pr_summary_with_limit(input, DEFAULT_MAX_COMMENT_LEN)
+}
+
+/// Compose a markdown PR comment capped at `max_len` bytes
+pub fn pr_summary_with_limit(input: &SummaryInput<'_>, max_len: usize) -> String {
+    let mut sections = Vec::new();
+    if let Some(analysis) = input.analysis {
+        sections.push(("Analysis", analysis_section(analysis)));
+    }
+    if let Some(report) = input.report {
+        let title = match input.status {
+            ChangeStatus::Planned => "Planned changes",
+            ChangeStatus::Applied => "Applied changes",
+        };
+        sections.push((title, changes_section(report)));
+        if !report.recommendations.is_empty() {
+            sections.push(("Recommendations", list(report.recommendations.iter().map(String::as_str))));
+        }
+    }
+    if !input.build_times.is_empty() {
+        sections.push(("Build times", build_time_section(input.build_times)));
+    }
+
+    render(&sections, max_len)
+}
+
+const HEADER: &str = "## ⚡ cargo-optimize\n";
+
+fn render(sections: &[(&str, String)], max_len: usize) -> String {
+    let mut out = String::from(HEADER);
+    if sections.is_empty() {
+        out.push_str("\nNo changes to report.\n");
+    }
+
+    for (index, (title, body)) in sections.iter().enumerate() {
+        let heading = format!("\n### {}\n\n", title);
+        // Keep room for every later section to at least show it was cut
+        let reserved: usize = sections[index + 1..]
+            .iter()
+            .map(|(title, _)| format!("\n### {}\n\n", title).len() + omitted_note(0).len())
+            .sum();
+        let available = max_len.saturating_sub(out.len() + reserved + heading.len());
+
+        out.push_str(&heading);
+        if body.len() <= available {
+            out.push_str(body);
+        } else {
+            out.push_str(&truncate_lines(body, available));
+        }
+    }
+
+    if out.len() > max_len {
+        // Limit too small for the section skeleton; cut at a char boundary
+        let mut end = max_len;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.truncate(end);
+    }
+    out
+}
+
+fn omitted_note(lines: usize) -> String {
+    format!("_… {} more lines omitted (size limit)_\n", lines)
+}
+
+/// Keep whole lines that fit in `available` bytes, ending with an omission note
+fn truncate_lines(body: &str, available: usize) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut kept = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        let note = omitted_note(lines.len() - index - 1);
+        if kept.len() + line.len() + 1 + note.len() > available {
+            kept.push_str(&omitted_note(lines.len() - index));
+            return kept;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    kept
+}
+
+fn list<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    let mut out = String::new();
+    for item in items {
+        let _ = writeln!(out, "- {}", item);
+    }
+    out
+}
+
+fn analysis_section(analysis: &ProjectAnalysis) -> String {
+    let mut out = String::new();
+    if analysis.preliminary {
+        let _ = writeln!(out, "- Preliminary: no `Cargo.lock` yet, dependencies are counted from the manifests");
+    }
+    let _ = writeln!(out, "- Workspace members: {}", analysis.workspace_members.len());
+    let _ = writeln!(
+        out,
+        "- Dependencies: {} total, {} direct",
+        analysis.total_dependencies, analysis.direct_dependencies
+    );
+    let _ = writeln!(out, "- Crates to compile: {}", analysis.metrics.crate_count);
+    let sources = &analysis.metrics.sources;
+    let _ = writeln!(out, "- Source lines: {} in {} files", sources.lines, sources.files);
+    for excluded in &sources.excluded {
+        let _ = writeln!(out, "- Left out: {} lines in {} files, {}", excluded.lines, excluded.files, excluded.reason);
+    }
+    if let Some(template) = &analysis.template {
+        let _ = writeln!(out, "- Detected template: `{}`", template);
+    }
+    for bottleneck in analysis.bottlenecks.iter().take(3) {
+        let _ = writeln!(
+            out,
+            "- Bottleneck: `{}` v{} ({} reverse deps)",
+            bottleneck.package, bottleneck.version, bottleneck.reverse_dependencies
+        );
+    }
+    out
+}
+
+fn changes_section(report: &OptimizationReport) -> String {
+    if report.decisions.is_empty() {
+        return "No setting changes.\n".to_string();
+    }
+
+    let mut out = String::new();
+    for decision in &report.decisions {
+        let marker = match decision.outcome {
+            DecisionOutcome::Applied => "✅",
+            DecisionOutcome::Skipped => "⏭️",
+            DecisionOutcome::Suppressed => "🚫",
+        };
+        let _ = writeln!(out, "- {} `{}`: {}", marker, decision.setting, decision.reason);
+    }
+    out
+}
+
+fn build_time_section(build_times: &[BuildTimeDelta]) -> String {
+    let mut out = String::from("| Build | Before | After | Change |\n|---|---:|---:|---:|\n");
+    for delta in build_times {
+        let _ = writeln!(
+            out,
+            "| {} | {:.1}s | {:.1}s | {:+.1}% |",
+            delta.label,
+            delta.before.as_secs_f64(),
+            delta.after.as_secs_f64(),
+            delta.percent_change()
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::SkipReason;
+
+    #[test]
+    fn test_ci_environment_detection() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        assert_eq!(
+            CiEnvironment::from_env_lookup(env(&[("CI", "true"), ("GITHUB_ACTIONS", "true")])),
+            Some(CiEnvironment::GitHubActions)
+        );
+        assert_eq!(CiEnvironment::from_env_lookup(env(&[("CI", "1")])), Some(CiEnvironment::Generic));
+        assert_eq!(CiEnvironment::from_env_lookup(env(&[("CI", "false")])), None);
+        assert_eq!(CiEnvironment::from_env_lookup(env(&[])), None);
+    }
+
+    fn sample_report() -> OptimizationReport {
+        let mut report = OptimizationReport::new();
+        report.applied("target.x86_64-unknown-linux-gnu.linker", "mold via clang");
+        report.skipped("sccache", SkipReason::Resources, "low disk space on /");
+        report.recommend("Free up disk space");
+        report
+    }
+
+    #[test]
+    fn test_pr_summary_sections() {
+        let report = sample_report();
+        let build_times = [BuildTimeDelta {
+            label: "clean debug build".to_string(),
+            before: Duration::from_secs(100),
+            after: Duration::from_secs(75),
+        }];
+        let input = SummaryInput {
+            analysis: None,
+            report: Some(&report),
+            status: ChangeStatus::Applied,
+            build_times: &build_times,
+        };
+
+        let summary = pr_summary(&input);
+        assert!(summary.starts_with("## ⚡ cargo-optimize\n"));
+        assert!(summary.contains("### Applied changes"));
+        assert!(summary.contains("- ⏭️ `sccache`: low disk space on /"));
+        assert!(summary.contains("### Recommendations"));
+        assert!(summary.contains("| clean debug build | 100.0s | 75.0s | -25.0% |"));
+
+        // Stable output for identical input
+        assert_eq!(summary, pr_summary(&input));
+    }
+
+    #[test]
+    fn test_pr_summary_size_cap() {
+        let mut report = OptimizationReport::new();
+        for i in 0..500 {
+            report.applied(format!("setting.{}", i), "a reasonably long reason for this decision");
+        }
+        report.recommend("keep this section visible");
+        let input = SummaryInput {
+            analysis: None,
+            report: Some(&report),
+            status: ChangeStatus::Planned,
+            build_times: &[],
+        };
+
+        let summary = pr_summary_with_limit(&input, 2_000);
+        assert!(summary.len() <= 2_000);
+        assert!(summary.contains("### Planned changes"));
+        assert!(summary.contains("more lines omitted (size limit)"));
+        // Later sections still get their heading
+        assert!(summary.contains("### Recommendations"));
+    }
+
+    #[test]
+    fn test_empty_summary() {
+        let input = SummaryInput {
+            analysis: None,
+            report: None,
+            status: ChangeStatus::Planned,
+            build_times: &[],
+        };
+        assert!(pr_summary(&input).contains("No changes to report."));
+    }
+}
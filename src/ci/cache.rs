@@ -0,0 +1,240 @@
+//! Cache configuration snippets for CI services.
+//!
+//! Caching cargo on CI means getting several details right: which parts of
+//! `~/.cargo` are worth keeping (the registry index and crate archives, the
+//! git databases — not the extracted sources or installed binaries), a key
+//! that changes with `Cargo.lock` and the toolchain, and a fallback key so
+//! a lockfile change still starts from a warm cache. [`cache_snippet`]
+//! renders that for the service in its own syntax, using the project's
+//! lockfile, toolchain file and target directory ([`CacheLayout`]).
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+use super::CiEnvironment;
+use crate::config;
+
+/// Parts of the cargo home worth caching, relative to it
+const CARGO_HOME_PATHS: [&str; 3] = ["registry/index/", "registry/cache/", "git/db/"];
+
+/// Prefix of every cache key, bumped to throw all caches away
+const KEY_PREFIX: &str = "cargo-v1";
+
+/// Project files that decide what the cache holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheLayout {
+    /// Files whose content keys the cache, relative to the project root
+    pub key_files: Vec<PathBuf>,
+    /// Target directory: relative to the project root, or absolute outside it
+    pub target_dir: PathBuf,
+}
+
+impl Default for CacheLayout {
+    fn default() -> Self {
+        CacheLayout {
+            key_files: vec![PathBuf::from("Cargo.lock")],
+            target_dir: PathBuf::from("target"),
+        }
+    }
+}
+
+impl CacheLayout {
+    /// Layout of the project at `project_root`
+    ///
+    /// The key is `Cargo.lock` (the manifest when there is none) plus the
+    /// toolchain file, and the target directory is the one cargo uses,
+    /// honoring `CARGO_TARGET_DIR` and `build.target-dir`.
+    pub fn detect(project_root: &Path) -> Self {
+        let lockfile = if project_root.join("Cargo.lock").is_file() { "Cargo.lock" } else { "Cargo.toml" };
+        let mut key_files = vec![PathBuf::from(lockfile)];
+        key_files.extend(
+            ["rust-toolchain.toml", "rust-toolchain"]
+                .into_iter()
+                .find(|file| project_root.join(file).is_file())
+                .map(PathBuf::from),
+        );
+
+        let doc = fs::read_to_string(project_root.join(".cargo").join("config.toml"))
+            .ok()
+            .and_then(|content| content.parse::<DocumentMut>().ok())
+            .unwrap_or_default();
+        let env_dir = std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from);
+        let target_dir = config::effective_target_dir(project_root, env_dir.as_deref(), &doc).path;
+        let target_dir = match target_dir.strip_prefix(project_root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => target_dir,
+        };
+        CacheLayout { key_files, target_dir }
+    }
+
+    /// Whether the target directory is inside the project, which some services require
+    fn target_in_project(&self) -> bool {
+        self.target_dir.is_relative()
+    }
+
+    fn key_file_list(&self, separator: &str, quote: &str) -> String {
+        self.key_files
+            .iter()
+            .map(|file| format!("{}{}{}", quote, slashed(file), quote))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+}
+
+/// A snippet to paste into a CI service's configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheSnippet {
+    /// Service the snippet is for
+    pub environment: CiEnvironment,
+    /// Configuration file it goes into
+    pub file: &'static str,
+    /// The snippet
+    pub content: String,
+}
+
+/// Cache configuration for `environment`, for the project in the current directory
+///
+/// `None` for services without a cache feature of their own (Jenkins,
+/// Buildkite, Travis) and for [`CiEnvironment::Generic`].
+pub fn cache_snippet(environment: CiEnvironment) -> Option<CacheSnippet> {
+    let layout = std::env::current_dir().map(|dir| CacheLayout::detect(&dir)).unwrap_or_default();
+    cache_snippet_for(environment, &layout)
+}
+
+/// Cache configuration for `environment` and an explicit project layout
+pub fn cache_snippet_for(environment: CiEnvironment, layout: &CacheLayout) -> Option<CacheSnippet> {
+    let (file, content) = match environment {
+        CiEnvironment::GitHubActions => (".github/workflows/<workflow>.yml", github_actions(layout)),
+        CiEnvironment::GitLabCi => (".gitlab-ci.yml", gitlab_ci(layout)),
+        CiEnvironment::CircleCi => (".circleci/config.yml", circleci(layout)),
+        CiEnvironment::AzurePipelines => ("azure-pipelines.yml", azure_pipelines(layout)),
+        _ => return None,
+    };
+    Some(CacheSnippet { environment, file, content })
+}
+
+fn github_actions(layout: &CacheLayout) -> String {
+    let mut out = String::from("- name: Cache cargo\n  uses: actions/cache@v4\n  with:\n    path: |\n");
+    for path in CARGO_HOME_PATHS {
+        let _ = writeln!(out, "      ~/.cargo/{}", path);
+    }
+    let _ = writeln!(out, "      {}/", slashed(&layout.target_dir));
+    let _ = writeln!(
+        out,
+        "    key: ${{{{ runner.os }}}}-{}-${{{{ hashFiles({}) }}}}",
+        KEY_PREFIX,
+        layout.key_file_list(", ", "'")
+    );
+    let _ = writeln!(out, "    # A changed lockfile starts from the newest cache instead of a cold one");
+    let _ = writeln!(out, "    restore-keys: |\n      ${{{{ runner.os }}}}-{}-", KEY_PREFIX);
+    out
+}
+
+fn gitlab_ci(layout: &CacheLayout) -> String {
+    let mut out = String::from(
+        "# GitLab only caches paths inside the project, so cargo's home moves there\n\
+         variables:\n  CARGO_HOME: ${CI_PROJECT_DIR}/.cargo-home\n\n\
+         cache:\n  key:\n    files:\n",
+    );
+    // GitLab keys on at most two files
+    for file in layout.key_files.iter().take(2) {
+        let _ = writeln!(out, "      - {}", slashed(file));
+    }
+    // Keys made from files only match exactly: a changed lockfile starts cold
+    let _ = writeln!(out, "    prefix: {}-${{CI_JOB_NAME}}", KEY_PREFIX);
+    out.push_str("  paths:\n");
+    for path in CARGO_HOME_PATHS {
+        let _ = writeln!(out, "    - .cargo-home/{}", path);
+    }
+    if layout.target_in_project() {
+        let _ = writeln!(out, "    - {}/", slashed(&layout.target_dir));
+    } else {
+        let _ = writeln!(
+            out,
+            "    # {} is outside the project and cannot be cached; unset CARGO_TARGET_DIR on CI",
+            slashed(&layout.target_dir)
+        );
+    }
+    out.push_str("  # Use `policy: pull` in jobs that only read the cache\n  policy: pull-push\n");
+    out
+}
+
+fn circleci(layout: &CacheLayout) -> String {
+    let checksums: String = layout
+        .key_files
+        .iter()
+        .map(|file| format!("-{{{{ checksum \"{}\" }}}}", slashed(file)))
+        .collect();
+    let key = format!("{}-{{{{ arch }}}}{}", KEY_PREFIX, checksums);
+    let mut out = String::from("- restore_cache:\n    keys:\n");
+    let _ = writeln!(out, "      - {}", key);
+    let _ = writeln!(out, "      # Newest cache of any lockfile when this one has none yet");
+    let _ = writeln!(out, "      - {}-{{{{ arch }}}}-", KEY_PREFIX);
+    out.push_str("# ... build and test steps ...\n- save_cache:\n");
+    let _ = writeln!(out, "    key: {}", key);
+    out.push_str("    paths:\n");
+    for path in CARGO_HOME_PATHS {
+        let _ = writeln!(out, "      - ~/.cargo/{}", path.trim_end_matches('/'));
+    }
+    let _ = writeln!(out, "      - {}", slashed(&layout.target_dir));
+    out
+}
+
+fn azure_pipelines(layout: &CacheLayout) -> String {
+    let key_files = layout.key_file_list(" | ", "");
+    let mut out = String::from("variables:\n  CARGO_HOME: $(Pipeline.Workspace)/.cargo\n\nsteps:\n");
+    // Cache@2 restores and saves one path per task
+    let caches = [
+        ("registry", "$(CARGO_HOME)/registry".to_string()),
+        ("git", "$(CARGO_HOME)/git/db".to_string()),
+        ("target", slashed(&layout.target_dir)),
+    ];
+    for (name, path) in caches {
+        let _ = writeln!(out, "- task: Cache@2");
+        let _ = writeln!(out, "  displayName: Cache cargo {}", name);
+        let _ = writeln!(out, "  inputs:");
+        let _ = writeln!(out, "    key: '{} | {} | \"$(Agent.OS)\" | {}'", KEY_PREFIX, name, key_files);
+        let _ = writeln!(out, "    restoreKeys: |\n      {} | {} | \"$(Agent.OS)\"", KEY_PREFIX, name);
+        let _ = writeln!(out, "    path: {}", path);
+    }
+    out
+}
+
+/// Path with forward slashes, as every CI service expects
+fn slashed(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippets_follow_the_layout() {
+        let layout = CacheLayout {
+            key_files: vec![PathBuf::from("Cargo.lock"), PathBuf::from("rust-toolchain.toml")],
+            target_dir: PathBuf::from("build/target"),
+        };
+        let github = cache_snippet_for(CiEnvironment::GitHubActions, &layout).unwrap().content;
+        assert!(github.contains("key: ${{ runner.os }}-cargo-v1-${{ hashFiles('Cargo.lock', 'rust-toolchain.toml') }}"), "{}", github);
+        assert!(github.contains("      build/target/\n") && github.contains("restore-keys:"));
+
+        let gitlab = cache_snippet_for(CiEnvironment::GitLabCi, &layout).unwrap().content;
+        assert!(gitlab.contains("    - .cargo-home/git/db/\n    - build/target/\n"), "{}", gitlab);
+        let outside = CacheLayout { target_dir: PathBuf::from("/mnt/target"), ..layout.clone() };
+        let gitlab = cache_snippet_for(CiEnvironment::GitLabCi, &outside).unwrap().content;
+        assert!(gitlab.contains("/mnt/target is outside the project"), "{}", gitlab);
+
+        let circle = cache_snippet_for(CiEnvironment::CircleCi, &layout).unwrap().content;
+        assert!(circle.contains("cargo-v1-{{ arch }}-{{ checksum \"Cargo.lock\" }}-{{ checksum \"rust-toolchain.toml\" }}"));
+
+        let azure = cache_snippet_for(CiEnvironment::AzurePipelines, &layout).unwrap();
+        assert_eq!(azure.file, "azure-pipelines.yml");
+        assert_eq!(azure.content.matches("- task: Cache@2").count(), 3);
+        assert!(azure.content.contains("key: 'cargo-v1 | target | \"$(Agent.OS)\" | Cargo.lock | rust-toolchain.toml'"));
+
+        assert_eq!(cache_snippet_for(CiEnvironment::Jenkins, &layout), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! The planned config as cargo `--config` flags.
+//!
+//! One-off builds, such as CI steps that must not modify the checkout, can
+//! pass the planned settings on the command line instead of writing
+//! `.cargo/config.toml`. cargo layers `--config key=value` over the config
+//! files, so only the settings apply would add or change are needed. Two
+//! kinds of change have no flag: removing a key, and changing an array that
+//! is already set, since cargo appends arrays from different sources instead
+//! of replacing them. Those are listed as omitted.
+
+use anyhow::Result;
+use std::fmt::Write as _;
+use toml_edit::{DocumentMut, Key, Value};
+
+use crate::journal::ApplyJournal;
+
+/// `--config` arguments equivalent to an apply
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliFlags {
+    /// Dotted key and TOML value of every setting to pass
+    pub settings: Vec<(String, String)>,
+    /// Changes that cannot be passed as flags, with the reason
+    pub omitted: Vec<(String, String)>,
+}
+
+impl CliFlags {
+    /// Whether there is nothing to pass
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+
+    /// Arguments for cargo: `--config`, `key=value`, ...
+    pub fn args(&self) -> Vec<String> {
+        self.settings
+            .iter()
+            .flat_map(|(key, value)| ["--config".to_string(), format!("{}={}", key, value)])
+            .collect()
+    }
+
+    /// The arguments as one line for a shell, each quoted where needed
+    pub fn to_shell(&self) -> String {
+        let mut out = String::new();
+        for arg in self.args() {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            if arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_.=/:,+".contains(c)) {
+                out.push_str(&arg);
+            } else {
+                let _ = write!(out, "'{}'", arg.replace('\'', r"'\''"));
+            }
+        }
+        out
+    }
+}
+
+/// Flags that turn the `original` config into `planned` when passed to cargo
+///
+/// Values are written without spaces where TOML allows it, so the flags
+/// also survive unquoted word splitting as in `cargo build $(...)`. Relative
+/// paths in the flags resolve against the directory cargo runs in rather
+/// than the config file's, so run cargo from the project root.
+pub fn to_cli_flags(original: Option<&str>, planned: &DocumentMut) -> Result<CliFlags> {
+    let diff = ApplyJournal::record(original, &planned.to_string())?;
+    let mut flags = CliFlags::default();
+    for entry in diff.entries {
+        let key = entry
+            .key
+            .iter()
+            .map(|segment| Key::new(segment.as_str()).display_repr().into_owned())
+            .collect::<Vec<_>>()
+            .join(".");
+        let Some(written) = entry.written else {
+            flags.omitted.push((key, "removed by apply; flags can only add settings".to_string()));
+            continue;
+        };
+        let value = match written.parse::<Value>() {
+            Ok(value) => value,
+            Err(e) => {
+                flags.omitted.push((key, format!("unparsable value: {}", e)));
+                continue;
+            }
+        };
+        if value.is_array() && entry.previous.is_some() {
+            flags.omitted.push((key, "cargo appends arrays from --config to the configured ones".to_string()));
+            continue;
+        }
+        flags.settings.push((key, compact(&value)));
+    }
+    Ok(flags)
+}
+
+/// TOML rendering of a value without optional whitespace
+fn compact(value: &Value) -> String {
+    match value {
+        Value::Array(array) => format!("[{}]", array.iter().map(compact).collect::<Vec<_>>().join(",")),
+        Value::InlineTable(table) => format!(
+            "{{{}}}",
+            table
+                .iter()
+                .map(|(key, value)| format!("{}={}", Key::new(key).display_repr(), compact(value)))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        _ => {
+            let mut value = value.clone();
+            value.decor_mut().clear();
+            value.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_cover_additions_and_changes_only() {
+        let original = "[build]\njobs = 4\ntarget-dir = \"target\"\n\n[target.x86_64-unknown-linux-gnu]\nrustflags = [\"-C\", \"opt-level=1\"]\n";
+        let planned: DocumentMut = "\
+[build]
+jobs = 8 # cargo-optimize: CO-JOBS-001
+
+[target.x86_64-unknown-linux-gnu]
+rustflags = [\"-C\", \"link-arg=-fuse-ld=mold\"]
+
+[target.'cfg(unix)']
+linker = \"clang\"
+
+[env]
+SCCACHE_ENDPOINT = { value = \"https://cache\", force = true }
+"
+        .parse()
+        .unwrap();
+
+        let flags = to_cli_flags(Some(original), &planned).unwrap();
+
+        assert_eq!(
+            flags.args(),
+            vec![
+                "--config",
+                "build.jobs=8",
+                "--config",
+                "target.\"cfg(unix)\".linker=\"clang\"",
+                "--config",
+                "env.SCCACHE_ENDPOINT={value=\"https://cache\",force=true}",
+            ]
+        );
+        let omitted: Vec<&str> = flags.omitted.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(omitted, vec!["build.target-dir", "target.x86_64-unknown-linux-gnu.rustflags"]);
+        assert!(flags.to_shell().starts_with("--config build.jobs=8 --config 'target.\"cfg(unix)\".linker=\"clang\"' "));
+    }
+}
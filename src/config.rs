@@ -6,19 +6,76 @@
 //! - Automatic backup and restore capabilities
 //! - Profile support (dev/test/release/bench)
 //! - Percentage value parsing for flexible configuration
+//! - Introspection of the effective configuration ([`effective`])
+//! - Warnings for and migration of renamed keys ([`deprecation`])
 
 use anyhow::{Context, Result};
 use figment::providers::{Env, Format, Toml};
 use figment::{Figment};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
-use toml_edit::{DocumentMut, Item, Table};
-use tracing::{debug, info};
+use toml_edit::{DocumentMut, Item, Table, Value};
+use tracing::{debug, info, warn};
+
+use crate::advisor::{self, Advice, Advisor, Rejection};
+use crate::analysis::{self, build_weight, sources::AnalysisSettings};
+use crate::adoption;
+use crate::audit;
+use crate::build_lock;
+use crate::ci::CiEnvironment;
+use crate::cli_flags::{self, CliFlags};
+use crate::conflicts::{self, Adjustment, ToolEnvironment};
+use crate::container;
+use crate::exit::ExitStatus;
+use crate::explain;
+use crate::hooks;
+use crate::guardrail::LinkerFailure;
+use crate::ide::{self, Bypass, Ide, WrapperScope};
+use crate::hardware::{self, DiskInfo, HardwareInfo};
+use crate::hardware::dev_drive::{self, VolumeKind};
+use crate::hardware::disk_io::{self, DiskBenchmark, IoProfile};
+use crate::hardware::load::{self, SystemLoad};
+use crate::journal::{ApplyJournal, JournalEntry};
+use crate::phases::{self, ApplyPhase, PhaseProbe, PhaseStatus};
+use crate::linkers::{self, Invocation, LinkerSpec, Platform};
+use crate::manifest::ProfileEdit;
+use crate::migration::{self, UpgradePreview};
+use crate::mvp::DriverProbe;
+use crate::nix::{self, NixEnvironment, NixFormat, NixSnippet};
+use crate::nextest::{self, NextestSettings};
+use crate::output;
+use crate::policy::{DenyList, Policy};
+use crate::proposal::{self, Proposal, ProposedFile};
+use crate::providers::{self, OptimizationProvider, Provider, ProviderContext};
+use crate::sccache::health::{self as cache_health, CacheLocation, Repair};
+use crate::sccache::{self, ProjectSize, SccacheHistory, SccacheStats, SccacheVerdict};
+use crate::sccache_dist::{self, Reachability};
+use crate::resource::{ResourceExpr, ResourceKind};
+use crate::secrets;
+use crate::strict::{self, Requirement};
+use crate::relocation::{self, MigrationMode, Relocation};
+use crate::report::{CleanupCandidate, DecisionOutcome, DiskTradeoff, EffectiveTargetDir, OptimizationReport, SkipReason, TargetDirSource};
+use crate::metrics::{self, BranchActivity, MetricsHistory};
+use crate::state::{JobsAdjustment, LinkerIncompatibility, MemoryAdjustment, MemoryFingerprint, PendingApply, SharedRustflags, State};
+use crate::proxy::{Proxy, ProxySettings};
+use crate::purge::{self, Artifact, ArtifactKind, PurgeSummary};
+use crate::tools::{self, LinkerInstallMode, LinkerInstallSettings};
+use crate::transaction::{atomic_write, ConfigTransaction};
+use crate::capabilities::{Availability, Feature};
+use crate::toolchain::{self, Channel, RustVersion, ToolchainInfo, ToolchainSpec};
 use thiserror::Error;
 
+pub mod deprecation;
+pub mod effective;
+pub mod toolchain_overrides;
+pub use deprecation::{Deprecation, DeprecationWarning, DEPRECATIONS};
+pub use effective::{effective, effective_in, ConfigSource, EffectiveConfig, EffectiveSetting};
+pub use toolchain_overrides::ToolchainSelector;
+
 /// Configuration error types
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -61,14 +118,29 @@ pub struct Config {
     /// Metadata about the configuration
     #[serde(default)]
     pub metadata: ConfigMetadata,
+    
+    /// Flags, linkers and settings the team never wants generated
+    #[serde(default)]
+    pub deny: DenyList,
+    
+    /// Globs adjusting which sources project analysis counts
+    #[serde(default)]
+    pub analysis: AnalysisSettings,
 }
 
 /// Optimization profile for a specific build mode
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
-    /// Name of the profile (dev, test, release, bench)
+    /// Name of the profile (dev, test, release, bench or a custom profile);
+    /// taken from the key in `[profiles]` when omitted
+    #[serde(default)]
     pub name: String,
     
+    /// Profile a custom profile inherits from, written as `inherits` when
+    /// the profile is not defined in `Cargo.toml`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inherits: Option<String>,
+    
     /// Linker to use for this profile
     #[serde(skip_serializing_if = "Option::is_none")]
     pub linker: Option<String>,
@@ -92,6 +164,35 @@ pub struct Profile {
     /// Target directory override
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_dir: Option<PathBuf>,
+    
+    /// Cargo `opt-level` ("0"-"3", "s", "z")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opt_level: Option<String>,
+    
+    /// Cargo `lto` ("true", "thin", "fat", "off")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lto: Option<String>,
+    
+    /// Cargo `codegen-units`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codegen_units: Option<u32>,
+    
+    /// Cargo `panic` strategy ("unwind", "abort")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panic: Option<String>,
+    
+    /// Cargo `strip` ("true", "debuginfo", "symbols", "none")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip: Option<String>,
+    
+    /// Cargo `debug` level ("0"-"2", "true", "false", "line-tables-only")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug: Option<String>,
+    
+    /// `opt-level` of build scripts, proc macros and their dependencies
+    /// (`[profile.<name>.build-override]`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_override_opt_level: Option<String>,
 }
 
 /// Job count configuration with percentage support
@@ -101,6 +202,8 @@ pub enum JobCount {
     Fixed(usize),
     /// Percentage of available cores (e.g., "75%")
     Percentage(String),
+    /// Resource expression (e.g., "50% physical", "logical-2", "min(8, 75%)")
+    Expression(String),
 }
 
 // Custom Serialize implementation
@@ -111,7 +214,7 @@ impl Serialize for JobCount {
     {
         match self {
             JobCount::Fixed(n) => serializer.serialize_u64(*n as u64),
-            JobCount::Percentage(p) => serializer.serialize_str(p),
+            JobCount::Percentage(p) | JobCount::Expression(p) => serializer.serialize_str(p),
         }
     }
 }
@@ -130,7 +233,7 @@ impl<'de> Deserialize<'de> for JobCount {
             type Value = JobCount;
             
             fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                formatter.write_str("a number or a percentage string")
+                formatter.write_str("a number, a percentage or a resource expression")
             }
             
             fn visit_u64<E>(self, value: u64) -> Result<JobCount, E>
@@ -155,16 +258,7 @@ impl<'de> Deserialize<'de> for JobCount {
             where
                 E: de::Error,
             {
-                // Check if it's a percentage
-                if value.ends_with('%') {
-                    Ok(JobCount::Percentage(value.to_string()))
-                } else if let Ok(n) = value.parse::<usize>() {
-                    // Try to parse as a number
-                    Ok(JobCount::Fixed(n))
-                } else {
-                    // If it's not a valid format, assume it's meant to be a percentage
-                    Ok(JobCount::Percentage(value.to_string()))
-                }
+                JobCount::parse(value).map_err(|e| E::custom(format!("{:#}", e)))
             }
         }
         
@@ -188,6 +282,13 @@ impl JobCount {
                 // Default to all cores if parsing fails
                 cores
             }
+            JobCount::Expression(expression) => match ResourceExpr::parse(expression, ResourceKind::Cores) {
+                Ok(expression) => expression.eval_cores(num_cpus::get(), num_cpus::get_physical()),
+                Err(e) => {
+                    warn!("{}; using all cores", e);
+                    num_cpus::get()
+                }
+            },
         }
     }
 }
@@ -213,16 +314,20 @@ impl PartialOrd<usize> for JobCount {
 
 impl JobCount {
     /// Parse from a string value
+    ///
+    /// Accepts a number, a percentage of logical cores or a resource
+    /// expression such as `"50% physical"`, `"logical-2"` or `"min(8, 75%)"`.
     pub fn parse(s: &str) -> Result<Self> {
-        // Check if it ends with % to determine if it's a percentage
-        if s.ends_with('%') {
+        let trimmed = s.trim();
+        if trimmed.strip_suffix('%').is_some_and(|p| p.trim().parse::<f64>().is_ok()) {
             Ok(JobCount::Percentage(s.to_string()))
-        } else if let Ok(n) = s.parse::<usize>() {
+        } else if let Ok(n) = trimmed.parse::<usize>() {
             // If it's a valid number without %, treat it as fixed
             Ok(JobCount::Fixed(n))
         } else {
-            // If it's not a number and doesn't end with %, it's an error
-            anyhow::bail!("Invalid job count: {}", s)
+            ResourceExpr::parse(s, ResourceKind::Cores)
+                .with_context(|| format!("Invalid job count: {}", s))?;
+            Ok(JobCount::Expression(s.to_string()))
         }
     }
 }
@@ -249,182 +354,1711 @@ pub struct GlobalSettings {
     /// Default job count for all profiles (supports percentages)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_jobs: Option<JobCount>,
+    
+    /// Minimum free disk space (GB) on the project disk before disk-hungry
+    /// optimizations (sccache, incremental) are skipped
+    #[serde(default = "default_min_free_disk_gb")]
+    pub min_free_disk_gb: f64,
+    
+    /// Nightly-only `-Zbuild-std` settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_std: Option<BuildStdSettings>,
+    
+    /// Only write settings supported by the project's `rust-version` (MSRV)
+    #[serde(default)]
+    pub respect_msrv: bool,
+    
+    /// Project archetype whose optimization bundle is applied to the profiles
+    /// (overrides the template detected by project analysis)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<ProjectTemplate>,
+    
+    /// Run `rustup component add` for missing components instead of only
+    /// recommending the command
+    #[serde(default)]
+    pub allow_installs: bool,
+    
+    /// Rewrite deprecated keys of cargo-optimize.toml to their new names on load
+    #[serde(default)]
+    pub auto_migrate: bool,
+    
+    /// Queue the apply in the state file when a cargo build is running,
+    /// so the next run carries it out
+    #[serde(default)]
+    pub queue_when_busy: bool,
+    
+    /// Test-link an empty program with the linker settings before writing
+    /// them, rolling them back when the link fails
+    #[serde(default)]
+    pub probe_phases: bool,
+    
+    /// How build artifacts are split across target directories
+    #[serde(default)]
+    pub target_dir_layout: TargetDirLayout,
+    
+    /// Member groups for [`TargetDirLayout::PerGroup`]: group name to member
+    /// directories relative to the workspace root
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub member_groups: BTreeMap<String, Vec<PathBuf>>,
+    
+    /// How target-dir settings are reconciled with a `CARGO_TARGET_DIR` set by the user
+    #[serde(default)]
+    pub target_dir_env_policy: TargetDirEnvPolicy,
+    
+    /// Write `.config/nextest.toml` when cargo-nextest is installed
+    #[serde(default = "default_configure_nextest")]
+    pub configure_nextest: bool,
+    
+    /// Stage changes in `.cargo-optimize/proposed/` for review instead of
+    /// writing live configs (see [`ConfigManager::propose`])
+    #[serde(default)]
+    pub pull_request_mode: bool,
+    
+    /// sccache hit rate (0 to 1) below which a configured sccache wrapper is
+    /// removed once it has been measured on several applies
+    #[serde(default = "default_sccache_min_hit_rate")]
+    pub sccache_min_hit_rate: f64,
+    
+    /// Let [`ConfigManager::doctor`] delete corrupt sccache entries, or
+    /// rotate a cache directory that is over its cap or unreadable
+    #[serde(default)]
+    pub sccache_auto_repair: bool,
+    
+    /// URL of an sccache-dist scheduler to distribute compilations to;
+    /// read from the sccache client config when unset
+    #[serde(default)]
+    pub sccache_dist_scheduler: Option<String>,
+    
+    /// Variables configuring sccache's storage backend (e.g. `SCCACHE_BUCKET`),
+    /// written to `[env]`; secrets are referenced as `${ENV:NAME}` and never written
+    #[serde(default)]
+    pub sccache_backend: BTreeMap<String, String>,
+    
+    /// Whether settings are lowered when profiled builds come close to
+    /// running out of memory, or only recommended
+    #[serde(default)]
+    pub memory_pressure_policy: MemoryPressurePolicy,
+    
+    /// Whether rust-analyzer's checks run through a project-wide rustc
+    /// wrapper too, or only builds and tests (see [`crate::ide`])
+    #[serde(default)]
+    pub wrapper_scope: WrapperScope,
+    
+    /// Bounds of `build.jobs` adapted to the load of a shared machine;
+    /// the job count is fixed when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_jobs: Option<AdaptiveJobsSettings>,
+    
+    /// Whether a target directory off a Dev Drive is moved to an existing
+    /// one on Windows, or only recommended
+    #[serde(default)]
+    pub dev_drive_policy: DevDrivePolicy,
+    
+    /// Only use what the Rust toolchain and rustup components provide: no
+    /// external linkers, sccache or cargo-nextest, even when installed
+    #[serde(default)]
+    pub no_external_tools: bool,
+    
+    /// Fetch git dependencies with gitoxide (`-Zgitoxide`) on nightly at the
+    /// aggressive optimization level
+    #[serde(default)]
+    pub gitoxide: bool,
+    
+    /// Whether build artifacts are copied or moved into the target
+    /// directories a per-profile or per-group layout introduces
+    #[serde(default)]
+    pub target_dir_migration: MigrationMode,
+    
+    /// Whether a fast linker may be downloaded, or its package command
+    /// recommended, when none is installed (see [`crate::tools`])
+    #[serde(default)]
+    pub linker_install: LinkerInstallSettings,
+    
+    /// Proxies for linker downloads, component installs and the
+    /// sccache-dist probe; the `*_PROXY` variables fill in what is unset
+    #[serde(default)]
+    pub proxy: ProxySettings,
+    
+    /// Share of the project's own compile time above which the cost of
+    /// cargo-optimize as a build-dependency is reported (see
+    /// [`analysis::build_weight`])
+    #[serde(default = "default_build_weight_warn_ratio")]
+    pub build_weight_warn_ratio: f64,
+    
+    /// Optimizations the project cannot build without; an apply that cannot
+    /// put them in place fails instead of degrading (see [`crate::strict`])
+    #[serde(default)]
+    pub required: Vec<Requirement>,
 }
 
-/// Optimization level for build configuration
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum OptimizationLevel {
-    /// Conservative optimization - minimal changes, maximum compatibility
-    Conservative,
-    /// Balanced optimization - good performance with reasonable safety
-    Balanced,
-    /// Aggressive optimization - maximum performance, may affect stability
-    Aggressive,
+fn default_min_free_disk_gb() -> f64 {
+    5.0
 }
 
-/// Cache configuration settings
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CacheSettings {
-    /// Whether caching is enabled
-    pub enabled: bool,
-    
-    /// Cache directory location
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub cache_dir: Option<PathBuf>,
+fn default_configure_nextest() -> bool {
+    true
+}
+
+fn default_sccache_min_hit_rate() -> f64 {
+    0.2
+}
+
+fn default_build_weight_warn_ratio() -> f64 {
+    build_weight::DEFAULT_WARN_RATIO
+}
+
+/// Nightly-only `-Zbuild-std` settings
+///
+/// Written to the `[unstable]` table of `.cargo/config.toml` only when a
+/// nightly toolchain is active. Switching back to stable removes the keys
+/// cargo-optimize wrote on the next apply. Build pipelining needs no
+/// configuration: cargo has enabled it unconditionally since 1.38.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BuildStdSettings {
+    /// Standard library crates to rebuild (e.g. `["core", "alloc"]`)
+    #[serde(default = "default_build_std_crates")]
+    pub crates: Vec<String>,
     
-    /// Maximum cache size (supports percentages like "10%")
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub max_size: Option<CacheSize>,
+    /// Features to enable for the standard library build
+    #[serde(default)]
+    pub features: Vec<String>,
     
-    /// Cache type (sccache, ccache, etc.)
-    pub cache_type: CacheType,
+    /// Target triple or path to a target spec JSON file; falls back to an
+    /// existing `build.target` in the cargo config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
 }
 
-/// Cache size configuration with percentage support
+/// Bounds of `build.jobs` when it adapts to the load of a shared machine
+///
+/// Each apply leaves the cores other workloads keep busy to them, never
+/// going below `min` or above `max` (see [`hardware::load`]).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(untagged)]
-pub enum CacheSize {
-    /// Fixed size in MB
-    Megabytes(usize),
-    /// Percentage of available disk space
-    Percentage(String),
+pub struct AdaptiveJobsSettings {
+    /// Fewest jobs, however busy the machine
+    #[serde(default = "default_adaptive_min_jobs")]
+    pub min: JobCount,
+    
+    /// Most jobs, even on an idle machine; all cores when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<JobCount>,
 }
 
-impl CacheSize {
-    /// Convert to actual size in MB based on available disk space
-    pub fn to_megabytes(&self) -> usize {
+fn default_adaptive_min_jobs() -> JobCount {
+    JobCount::Fixed(1)
+}
+
+fn default_build_std_crates() -> Vec<String> {
+    vec!["core".to_string(), "alloc".to_string()]
+}
+
+/// Project archetype with a matching bundle of profile settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectTemplate {
+    /// Browser/WebAssembly application: small binaries
+    WasmApp,
+    /// Long-running server: fast code with usable backtraces
+    Server,
+    /// Bare-metal firmware: size-optimized, abort on panic
+    Embedded,
+    /// Command-line tool: fast, stripped release binaries
+    Cli,
+}
+
+impl ProjectTemplate {
+    /// Name used in configuration files (e.g. "wasm-app")
+    pub fn name(&self) -> &'static str {
         match self {
-            CacheSize::Megabytes(mb) => *mb,
-            CacheSize::Percentage(p) => {
-                // Use sysinfo to get disk space
-                use sysinfo::System;
-                let mut sys = System::new_all();
-                sys.refresh_all();
-                
-                // For now, default to 10GB as sysinfo API has changed
-                // TODO: Update to use new sysinfo disk API
-                let total_space_mb = 10240;
-                
-                if let Some(percentage_str) = p.strip_suffix('%') {
-                    if let Ok(percentage) = percentage_str.parse::<f64>() {
-                        let size = (total_space_mb as f64 * (percentage / 100.0)).round() as usize;
-                        return size.max(100); // Minimum 100MB
-                    }
-                }
-                // Default to 1GB if parsing fails
-                1024
+            ProjectTemplate::WasmApp => "wasm-app",
+            ProjectTemplate::Server => "server",
+            ProjectTemplate::Embedded => "embedded",
+            ProjectTemplate::Cli => "cli",
+        }
+    }
+    
+    /// Profile settings of the template as (profile, key, value)
+    pub fn settings(&self) -> &'static [(&'static str, &'static str, &'static str)] {
+        match self {
+            ProjectTemplate::WasmApp => &[
+                ("release", "opt-level", "s"),
+                ("release", "lto", "true"),
+                ("release", "codegen-units", "1"),
+                ("release", "panic", "abort"),
+                ("release", "strip", "true"),
+            ],
+            ProjectTemplate::Server => &[
+                ("release", "opt-level", "3"),
+                ("release", "lto", "thin"),
+                ("release", "debug", "1"),
+            ],
+            ProjectTemplate::Embedded => &[
+                ("dev", "opt-level", "1"),
+                ("dev", "panic", "abort"),
+                ("release", "opt-level", "z"),
+                ("release", "lto", "true"),
+                ("release", "codegen-units", "1"),
+                ("release", "panic", "abort"),
+                ("release", "debug", "true"),
+                ("dev", "build-override.opt-level", "0"),
+                ("release", "build-override.opt-level", "0"),
+            ],
+            ProjectTemplate::Cli => &[
+                ("release", "opt-level", "3"),
+                ("release", "lto", "thin"),
+                ("release", "codegen-units", "1"),
+                ("release", "strip", "true"),
+            ],
+        }
+    }
+    
+    /// Fill in the template's settings on profiles that do not set them already
+    pub fn apply_to(&self, config: &mut Config) {
+        for (profile_name, key, value) in self.settings() {
+            let profile = config
+                .profiles
+                .entry(profile_name.to_string())
+                .or_insert_with(|| Profile::default_for_name(profile_name.to_string()));
+            let value = value.to_string();
+            match *key {
+                "opt-level" => { profile.opt_level.get_or_insert(value); }
+                "lto" => { profile.lto.get_or_insert(value); }
+                "codegen-units" => { profile.codegen_units.get_or_insert(value.parse().unwrap_or(16)); }
+                "panic" => { profile.panic.get_or_insert(value); }
+                "strip" => { profile.strip.get_or_insert(value); }
+                "debug" => { profile.debug.get_or_insert(value); }
+                "build-override.opt-level" => { profile.build_override_opt_level.get_or_insert(value); }
+                _ => {}
             }
         }
     }
 }
 
-/// Type of build cache to use
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum CacheType {
-    /// No caching
-    None,
-    /// sccache
-    Sccache,
-    /// ccache
-    Ccache,
-    /// Custom cache command
-    Custom(String),
+impl std::fmt::Display for ProjectTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
-/// Backup configuration for rollback support
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BackupConfig {
-    /// Whether to create backups automatically
-    pub auto_backup: bool,
-    
-    /// Maximum number of backups to keep
-    pub max_backups: usize,
-    
-    /// Directory to store backups
-    pub backup_dir: PathBuf,
+/// Per-package profile override (`[profile.<profile>.package.<package>]`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageOverride {
+    /// Profile to override (e.g. "dev")
+    pub profile: String,
+    /// Package the override applies to
+    pub package: String,
+    /// Cargo profile keys and values (e.g. "incremental" = "false")
+    pub settings: Vec<(String, String)>,
 }
 
-/// Metadata about the configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConfigMetadata {
-    /// Version of cargo-optimize that created this config
-    pub version: String,
-    
-    /// Timestamp when config was created/modified
-    pub timestamp: u64,
+/// Keys cargo rejects inside `[profile.*.package.*]`
+const FORBIDDEN_OVERRIDE_KEYS: &[&str] = &["panic", "lto", "rpath"];
+
+/// Write a package override into a cargo config document
+///
+/// Only the keys in the override are touched; other keys already set for
+/// the package are preserved.
+pub fn apply_package_override(doc: &mut DocumentMut, override_: &PackageOverride) -> Result<()> {
+    if let Some((key, _)) = override_
+        .settings
+        .iter()
+        .find(|(key, _)| FORBIDDEN_OVERRIDE_KEYS.contains(&key.as_str()))
+    {
+        anyhow::bail!("`{}` cannot be set in a package override (package {})", key, override_.package);
+    }
     
-    /// Platform this config was created for
-    pub platform: String,
+    let implicit_table = || {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        Item::Table(table)
+    };
+    let profiles = doc
+        .entry("profile")
+        .or_insert_with(implicit_table)
+        .as_table_mut()
+        .context("`profile` is not a table")?;
+    let profile = profiles
+        .entry(&override_.profile)
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .with_context(|| format!("`profile.{}` is not a table", override_.profile))?;
+    let packages = profile
+        .entry("package")
+        .or_insert_with(implicit_table)
+        .as_table_mut()
+        .with_context(|| format!("`profile.{}.package` is not a table", override_.profile))?;
+    let package = packages
+        .entry(&override_.package)
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .with_context(|| format!("`profile.{}.package.{}` is not a table", override_.profile, override_.package))?;
     
-    /// Hash of the configuration for integrity checking
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub hash: Option<String>,
+    for (key, value) in &override_.settings {
+        package[key.as_str()] = explained(profile_value(value), "profile.*.package.*.*");
+    }
+    Ok(())
 }
 
-/// Configuration manager using Figment for layered config
-pub struct ConfigManager {
-    _figment: Figment,
-    config: Config,
-    config_path: PathBuf,
+/// Remove a package override entirely
+///
+/// Returns true if the override existed.
+pub fn remove_package_override(doc: &mut DocumentMut, profile: &str, package: &str) -> bool {
+    let Some(packages) = doc
+        .get_mut("profile")
+        .and_then(|p| p.get_mut(profile))
+        .and_then(|p| p.get_mut("package"))
+        .and_then(Item::as_table_like_mut)
+    else {
+        return false;
+    };
+    let removed = packages.remove(package).is_some();
+    if removed && packages.is_empty() {
+        if let Some(profile_table) = doc["profile"][profile].as_table_like_mut() {
+            profile_table.remove("package");
+        }
+    }
+    removed
 }
 
-impl ConfigManager {
-    /// Create a new configuration manager with layered sources
-    pub fn new() -> Result<Self> {
-        Self::new_with_env_prefix("CARGO_OPTIMIZE_")
+/// How build artifacts are split across target directories
+///
+/// Separate target directories stop builds with different profiles or
+/// feature sets from invalidating each other's artifacts, at the cost of
+/// compiling shared dependencies once per directory.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetDirLayout {
+    /// One target directory for everything (cargo's default)
+    #[default]
+    Shared,
+    /// A target directory per profile (`target/dev`, `target/release`, ...),
+    /// used through `cargo build-<profile>` aliases since cargo cannot select
+    /// `build.target-dir` by profile
+    PerProfile,
+    /// A target directory per member group, written as `build.target-dir` to
+    /// each member's own `.cargo/config.toml`
+    PerGroup,
+}
+
+/// What to do when profiled builds came close to running out of memory
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MemoryPressurePolicy {
+    /// Recommend fewer jobs and no parallel LTO in the report
+    #[default]
+    Recommend,
+    /// Write the lowered settings, and revert them once the machine or project changes
+    Apply,
+}
+
+/// What to do when the target directory is not on a Windows Dev Drive
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DevDrivePolicy {
+    /// Recommend creating a Dev Drive or moving the target directory to one
+    #[default]
+    Recommend,
+    /// Point `build.target-dir` at an existing Dev Drive
+    Relocate,
+}
+
+/// What to do with target-dir settings when `CARGO_TARGET_DIR` is set
+///
+/// The environment variable outranks `build.target-dir`, so a config value
+/// written while it is set is silently ignored by cargo.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetDirEnvPolicy {
+    /// Keep the user's directory: layouts are rooted in it and settings the
+    /// variable would override are skipped
+    #[default]
+    Respect,
+    /// Write no target-dir settings at all while the variable is set
+    Skip,
+    /// Write everything as configured and warn where the variable wins
+    Warn,
+}
+
+/// A report skipping `setting` while `CARGO_OPTIMIZE_DISABLE` is set
+///
+/// Entry points returning a report check it first and touch nothing when
+/// it is `Some`.
+fn disabled_report(setting: &str) -> Option<OptimizationReport> {
+    output::disabled().then(|| {
+        let mut report = OptimizationReport::new();
+        report.skipped(setting, SkipReason::UserOptOut, format!("disabled by {}", output::DISABLE_ENV_VAR));
+        report
+    })
+}
+
+/// Resolve the target directory cargo will use
+///
+/// `CARGO_TARGET_DIR` wins over `build.target-dir` in `doc`, which wins over
+/// `<project_root>/target`. A relative config value is resolved against the
+/// project root, as cargo does for the directory containing `.cargo/`.
+pub fn effective_target_dir(project_root: &Path, env_dir: Option<&Path>, doc: &DocumentMut) -> EffectiveTargetDir {
+    if let Some(dir) = env_dir {
+        return EffectiveTargetDir {
+            path: std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf()),
+            source: TargetDirSource::Environment,
+        };
     }
-    
-    /// Create a new configuration manager with custom environment prefix (for testing)
-    pub fn new_with_env_prefix(env_prefix: &str) -> Result<Self> {
-        // Use current directory as base
-        let base_dir = std::env::current_dir()?;
-        Self::new_with_base_dir(&base_dir, env_prefix)
+    let configured = doc
+        .get("build")
+        .and_then(|build| build.get("target-dir"))
+        .and_then(Item::as_str);
+    match configured {
+        Some(dir) => EffectiveTargetDir {
+            path: project_root.join(dir),
+            source: TargetDirSource::Config,
+        },
+        None => EffectiveTargetDir {
+            path: project_root.join("target"),
+            source: TargetDirSource::Default,
+        },
     }
-    
-    /// Create a new configuration manager with a specific base directory
-    /// This allows tests to work with isolated directories without changing the process's current directory
-    pub fn new_with_base_dir(base_dir: &Path, env_prefix: &str) -> Result<Self> {
-        // Construct absolute path to cargo-optimize.toml
-        let config_file = base_dir.join("cargo-optimize.toml");
-        
-        // Build layered configuration with Figment
-        let mut figment = Figment::new()
-            // 1. Start with defaults
-            .merge(Toml::string(&Self::default_config_toml()));
-        
-        // 2. Merge with cargo-optimize.toml if it exists
-        if config_file.exists() {
-            figment = figment.merge(Toml::file(&config_file));
-        }
-        
-        // 3. Override with environment variables
-        // Use double underscore for nested keys (e.g., PREFIX_GLOBAL__VERBOSE)
-        figment = figment.merge(Env::prefixed(env_prefix).split("__"));
-        
-        // Extract the configuration
-        let mut config: Config = figment.extract()
-            .map_err(|e| anyhow::anyhow!("Failed to extract config: {}", e))?;
-        
-        // Auto-detect hardware if enabled
-        if config.global.auto_detect_hardware {
-            config.apply_hardware_optimizations()?;
-        }
-        
-        // Use absolute path for .cargo/config.toml
-        let config_path = base_dir.join(".cargo").join("config.toml");
-        
-        Ok(ConfigManager {
-            _figment: figment,
-            config,
-            config_path,
+}
+
+/// Profiles cargo defines itself
+pub const BUILTIN_PROFILES: [&str; 5] = ["dev", "test", "release", "bench", "doc"];
+
+/// Custom profiles defined in a `Cargo.toml`, with the profile each inherits from
+///
+/// Built-in profiles are left out; an unparsable manifest has none.
+pub fn custom_profiles(manifest: &str) -> BTreeMap<String, Option<String>> {
+    let Ok(doc) = manifest.parse::<DocumentMut>() else {
+        return BTreeMap::new();
+    };
+    let Some(profiles) = doc.get("profile").and_then(Item::as_table_like) else {
+        return BTreeMap::new();
+    };
+    profiles
+        .iter()
+        .filter(|(name, item)| !BUILTIN_PROFILES.contains(name) && item.is_table_like())
+        .map(|(name, item)| {
+            let inherits = item.get("inherits").and_then(Item::as_str).map(String::from);
+            (name.to_string(), inherits)
         })
+        .collect()
+}
+
+/// Alias that builds with `profile` in its own target directory
+fn profile_alias(profile: &str) -> (String, &'static str) {
+    match profile {
+        "test" => ("test-isolated".to_string(), "test"),
+        "bench" => ("bench-isolated".to_string(), "bench"),
+        _ => (format!("build-{}", profile), "build"),
     }
-    
-    /// Load configuration from a specific profile
-    pub fn with_profile(profile: &str) -> Result<Self> {
-        Self::with_profile_and_env_prefix(profile, "CARGO_OPTIMIZE_")
+}
+
+/// Directory cargo uses for a profile's output inside a shared target dir
+fn profile_output_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "release" | "bench" => "release",
+        other => other,
+    }
+}
+
+/// Output directories to carry from `target_dir` into new target directories
+///
+/// `targets` pairs each new target directory with the profile output
+/// directory it receives, `None` for all of them. A source shared by several
+/// relocations is only moved by the last one; the others copy it.
+fn plan_relocations(target_dir: &Path, targets: &[(PathBuf, Option<&str>)], mode: MigrationMode) -> Vec<Relocation> {
+    if mode == MigrationMode::Off {
+        return Vec::new();
+    }
+    let target_dir = std::path::absolute(target_dir).unwrap_or_else(|_| target_dir.to_path_buf());
+    let nested: Vec<PathBuf> = targets
+        .iter()
+        .filter_map(|(dir, _)| dir.strip_prefix(&target_dir).ok()?.components().next())
+        .map(|component| PathBuf::from(component.as_os_str()))
+        .collect();
+    let outputs = relocation::output_dirs(&target_dir, &nested);
+    
+    let mut relocations: Vec<Relocation> = Vec::new();
+    for (dir, output) in targets {
+        for relative in &outputs {
+            if output.is_some_and(|output| relative.file_name() != Some(output.as_ref())) {
+                continue;
+            }
+            relocations.push(Relocation { from: target_dir.join(relative), to: dir.join(relative), mode });
+        }
+    }
+    for index in 0..relocations.len() {
+        if relocations[index + 1..].iter().any(|later| later.from == relocations[index].from) {
+            relocations[index].mode = MigrationMode::Copy;
+        }
+    }
+    relocations
+}
+
+/// `build.target-dir` for a group member, relative to the member directory
+///
+/// Cargo resolves relative config paths against the directory containing
+/// `.cargo/`, so the written config stays valid when the checkout moves.
+fn group_target_dir(member: &Path, group: &str) -> String {
+    let depth = member
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .count();
+    format!("{}target/{}", "../".repeat(depth), group)
+}
+
+/// Put back files written by an accept that failed part-way
+///
+/// Files that did not exist before are removed.
+fn restore_files(project_root: &Path, written: &[(PathBuf, Option<String>)]) {
+    for (path, original) in written.iter().rev() {
+        let accepted = fs::read_to_string(path).ok();
+        let restored = match original {
+            Some(content) => atomic_write(path, content),
+            None => fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path)),
+        };
+        match restored {
+            Ok(()) => audit::record(project_root, "revert-proposal", path, accepted.as_deref(), original.as_deref()),
+            Err(e) => warn!("Failed to restore {:?}: {:#}", path, e),
+        }
+    }
+}
+
+/// A member's nested config pointing at its group's target directory
+#[derive(Debug, Clone)]
+struct GroupTargetDir {
+    group: String,
+    member: PathBuf,
+    config_path: PathBuf,
+    target_dir: String,
+}
+
+/// Comment marking the `[unstable]` table as managed by cargo-optimize
+const MANAGED_UNSTABLE_MARKER: &str = "# cargo-optimize: managed (nightly only)";
+
+/// Trailing comment marking a `build.target` written for `-Zbuild-std`
+const MANAGED_TARGET_MARKER: &str = " # cargo-optimize: CO-BUILDSTD-001 (build-std target)";
+
+/// Part of [`MANAGED_TARGET_MARKER`] also present in markers written before
+/// explanation ids existed
+const MANAGED_TARGET_TAG: &str = "build-std target";
+
+/// Validate that a target can be used with `-Zbuild-std`
+///
+/// Target spec files (`*.json`) must exist relative to the project root;
+/// triples must be known to the active rustc when a target list is available.
+pub fn validate_build_std_target(
+    target: &str,
+    project_root: &Path,
+    known_targets: &[String],
+) -> std::result::Result<(), String> {
+    if target.ends_with(".json") {
+        let spec = project_root.join(target);
+        if !spec.exists() {
+            return Err(format!("target spec file {} does not exist", spec.display()));
+        }
+        return Ok(());
+    }
+    
+    if !known_targets.is_empty() && !known_targets.iter().any(|t| t == target) {
+        return Err(format!("target '{}' is not known to the active rustc", target));
+    }
+    
+    Ok(())
+}
+
+/// What a configuration key needs from the toolchain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolchainRequirement {
+    /// Available on stable from this version onwards
+    MinVersion(RustVersion),
+    /// Only available on nightly
+    Nightly,
+}
+
+impl ToolchainRequirement {
+    /// Whether the given toolchain satisfies this requirement
+    pub fn is_met_by(&self, toolchain: &ToolchainSpec) -> bool {
+        match self {
+            ToolchainRequirement::MinVersion(version) => toolchain.supports(*version),
+            ToolchainRequirement::Nightly => toolchain.channel == Channel::Nightly,
+        }
+    }
+}
+
+impl std::fmt::Display for ToolchainRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolchainRequirement::MinVersion(version) => write!(f, "requires Rust {} or newer", version),
+            ToolchainRequirement::Nightly => write!(f, "requires a nightly toolchain"),
+        }
+    }
+}
+
+/// Minimum toolchain requirements for cargo config keys.
+///
+/// `*` matches any single path segment (e.g. a profile or target name).
+const KEY_REQUIREMENTS: &[(&str, ToolchainRequirement)] = &[
+    ("profile", ToolchainRequirement::MinVersion(RustVersion::new(1, 43, 0))),
+    ("profile.*.split-debuginfo", ToolchainRequirement::MinVersion(RustVersion::new(1, 51, 0))),
+    ("profile.*.inherits", ToolchainRequirement::MinVersion(RustVersion::new(1, 57, 0))),
+    ("profile.*.strip", ToolchainRequirement::MinVersion(RustVersion::new(1, 59, 0))),
+    ("profile.*.rustflags", ToolchainRequirement::Nightly),
+    ("profile.*.trim-paths", ToolchainRequirement::Nightly),
+    ("profile.*.codegen-backend", ToolchainRequirement::Nightly),
+    ("env", ToolchainRequirement::MinVersion(RustVersion::new(1, 56, 0))),
+    ("registries.crates-io.protocol", ToolchainRequirement::MinVersion(RustVersion::new(1, 68, 0))),
+    ("unstable", ToolchainRequirement::Nightly),
+];
+
+/// A configuration key that a toolchain cannot handle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityIssue {
+    /// Dotted key path (e.g. "profile.release.strip")
+    pub key: String,
+    /// What the key needs from the toolchain
+    pub requirement: ToolchainRequirement,
+}
+
+/// Result of checking a configuration against one toolchain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolchainCompatibility {
+    /// The toolchain that was checked
+    pub toolchain: ToolchainSpec,
+    /// Keys the toolchain does not support
+    pub issues: Vec<CompatibilityIssue>,
+}
+
+impl ToolchainCompatibility {
+    /// Whether the configuration works on this toolchain
+    pub fn is_compatible(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check a cargo config document against a set of toolchains
+///
+/// Returns one result per toolchain listing the keys (and `-Z` rustflags)
+/// it does not support, so projects with an MSRV range can catch configs
+/// that would break contributors on older compilers.
+pub fn validate_against_toolchains(doc: &DocumentMut, toolchains: &[ToolchainSpec]) -> Vec<ToolchainCompatibility> {
+    let mut used = Vec::new();
+    collect_requirements(doc.as_table(), &mut Vec::new(), &mut used);
+    
+    toolchains
+        .iter()
+        .map(|toolchain| ToolchainCompatibility {
+            toolchain: *toolchain,
+            issues: used
+                .iter()
+                .filter(|issue| !issue.requirement.is_met_by(toolchain))
+                .cloned()
+                .collect(),
+        })
+        .collect()
+}
+
+fn collect_requirements(table: &dyn toml_edit::TableLike, path: &mut Vec<String>, used: &mut Vec<CompatibilityIssue>) {
+    for (key, item) in table.iter() {
+        path.push(key.to_string());
+        let dotted = path.join(".");
+        
+        if let Some((_, requirement)) = KEY_REQUIREMENTS.iter().find(|(pattern, _)| key_matches(pattern, path)) {
+            used.push(CompatibilityIssue { key: dotted.clone(), requirement: *requirement });
+        }
+        
+        if key == "rustflags" {
+            let nightly_flags = item
+                .as_array()
+                .map(|flags| flags.iter().filter_map(|f| f.as_str()).any(|f| f.starts_with("-Z")))
+                .unwrap_or(false);
+            if nightly_flags {
+                used.push(CompatibilityIssue { key: dotted.clone(), requirement: ToolchainRequirement::Nightly });
+            }
+        }
+        
+        if let Some(child) = item.as_table_like() {
+            collect_requirements(child, path, used);
+        }
+        path.pop();
+    }
+}
+
+/// Revert every setting written by this run that the MSRV toolchain cannot handle
+///
+/// Keys the user wrote themselves (identical in `original`) are left alone.
+fn enforce_msrv(original: &DocumentMut, doc: &mut DocumentMut, msrv: RustVersion, report: &mut OptimizationReport) {
+    let msrv_toolchain = ToolchainSpec::stable(msrv);
+    let mut used = Vec::new();
+    collect_requirements(doc.as_table(), &mut Vec::new(), &mut used);
+    
+    for issue in used.into_iter().filter(|issue| !issue.requirement.is_met_by(&msrv_toolchain)) {
+        let path: Vec<&str> = issue.key.split('.').collect();
+        let before = item_at(original.as_table(), &path).map(ToString::to_string);
+        let after = item_at(doc.as_table(), &path).map(ToString::to_string);
+        if before == after {
+            continue;
+        }
+        
+        restore_item(doc.as_table_mut(), original.as_table(), &path);
+        report.skipped(issue.key, SkipReason::UnsupportedToolchain, format!("not supported by MSRV {} ({})", msrv, issue.requirement));
+    }
+}
+
+/// Paths of every `rustflags` array in `build`, `target.*` and `profile.*`
+fn rustflags_paths(doc: &DocumentMut) -> Vec<Vec<String>> {
+    let mut paths = Vec::new();
+    if doc.get("build").and_then(|build| build.get("rustflags")).is_some() {
+        paths.push(vec!["build".to_string(), "rustflags".to_string()]);
+    }
+    for section in ["target", "profile"] {
+        let Some(tables) = doc.get(section).and_then(Item::as_table_like) else {
+            continue;
+        };
+        for (name, table) in tables.iter() {
+            if table.get("rustflags").is_some() {
+                paths.push(vec![section.to_string(), name.to_string(), "rustflags".to_string()]);
+            }
+        }
+    }
+    paths
+}
+
+/// Strings of an array item, `None` if it is not an array
+fn string_values(item: &Item) -> Option<Vec<String>> {
+    item.as_array()
+        .map(|array| array.iter().filter_map(|value| value.as_str()).map(String::from).collect())
+}
+
+/// Hex SHA-256 of `content`
+pub(crate) fn sha256_hex(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Path of the metadata sidecar for `backup`
+///
+/// Hidden and without the backup prefix, so it is never taken for a backup.
+fn backup_metadata_path(backup: &Path) -> PathBuf {
+    let name = backup.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    backup.with_file_name(format!(".{}.meta.json", name))
+}
+
+fn is_backup_file_name(name: &str) -> bool {
+    name.starts_with("config_backup_") && name.ends_with(".toml")
+}
+
+/// Metadata sidecar of `backup`, `None` if missing or unreadable
+fn read_backup_metadata(backup: &Path) -> Option<BackupMetadata> {
+    let content = fs::read_to_string(backup_metadata_path(backup)).ok()?;
+    serde_json::from_str(&content)
+        .map_err(|e| debug!("Ignoring invalid backup metadata for {:?}: {}", backup, e))
+        .ok()
+}
+
+fn item_at<'a>(table: &'a dyn toml_edit::TableLike, path: &[&str]) -> Option<&'a Item> {
+    let (first, rest) = path.split_first()?;
+    let item = table.get(first)?;
+    if rest.is_empty() {
+        Some(item)
+    } else {
+        item_at(item.as_table_like()?, rest)
+    }
+}
+
+/// Set `path` in `doc` back to its value in `original`, removing it if absent
+fn restore_item(doc: &mut dyn toml_edit::TableLike, original: &dyn toml_edit::TableLike, path: &[&str]) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        match original.get(first) {
+            Some(item) => {
+                doc.insert(first, item.clone());
+            }
+            None => {
+                doc.remove(first);
+            }
+        }
+        return;
+    }
+    
+    let original_child = original.get(first).and_then(Item::as_table_like);
+    if let Some(child) = doc.get_mut(first).and_then(Item::as_table_like_mut) {
+        let empty = Table::new();
+        restore_item(child, original_child.unwrap_or(&empty), rest);
+        if child.is_empty() && original_child.is_none() {
+            doc.remove(first);
+        }
+    }
+}
+
+/// Why a linker denied by the team is not configured
+const DENIED_LINKER_REASON: &str = "denied by [deny] linkers in cargo-optimize.toml";
+
+/// Take every setting the team's `[deny]` table forbids out of what this run writes
+///
+/// Values identical in `original` were not written by this run and are left
+/// alone, like the hand-set values they are.
+fn enforce_deny_list(
+    original: &DocumentMut,
+    doc: &mut DocumentMut,
+    deny: &DenyList,
+    report: &mut OptimizationReport,
+) -> Result<()> {
+    if deny.is_empty() {
+        return Ok(());
+    }
+    let written_by_run = |doc: &DocumentMut, keys: &[&str]| {
+        item_at(doc.as_table(), keys).map(ToString::to_string) != item_at(original.as_table(), keys).map(ToString::to_string)
+    };
+    
+    let mut paths = Vec::new();
+    collect_value_paths(doc.as_table(), &mut Vec::new(), &mut paths);
+    for path in paths {
+        let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+        if !written_by_run(doc, &keys) {
+            continue;
+        }
+        let id = item_at(doc.as_table(), &keys)
+            .and_then(Item::as_value)
+            .and_then(|value| value.decor().suffix())
+            .and_then(|suffix| suffix.as_str())
+            .and_then(explain::id_from_comment);
+        if let Some(feature) = deny.denied_feature(&path, id) {
+            let reason = format!("denied by [deny] features (`{}`) in cargo-optimize.toml", feature);
+            restore_item(doc.as_table_mut(), original.as_table(), &keys);
+            report.suppressed(path.join("."), reason);
+        }
+    }
+    
+    if deny.flags.is_empty() {
+        return Ok(());
+    }
+    for path in rustflags_paths(doc) {
+        let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+        let Some(flags) = item_at(doc.as_table(), &keys).and_then(string_values) else {
+            continue;
+        };
+        let (allowed, denied) = deny.split_flags(&flags);
+        if denied.is_empty() || !written_by_run(doc, &keys) {
+            continue;
+        }
+        let Some((key, parents)) = keys.split_last() else {
+            continue;
+        };
+        let table = parents
+            .iter()
+            .try_fold(doc.as_table_mut() as &mut dyn toml_edit::TableLike, |table, parent| {
+                table.get_mut(parent).and_then(Item::as_table_like_mut)
+            })
+            .context("Failed to access rustflags table")?;
+        if allowed.is_empty() {
+            table.remove(key);
+        } else if let Some(value) = table.get_mut(key).and_then(Item::as_value_mut) {
+            let decor = value.decor().clone();
+            *value = toml_edit::Value::Array(string_array(&allowed));
+            *value.decor_mut() = decor;
+        }
+        report.suppressed(path.join("."), format!("{} denied by [deny] flags in cargo-optimize.toml", denied.join(" ")));
+    }
+    Ok(())
+}
+
+/// Paths of every value (arrays included) below `table`
+fn collect_value_paths(table: &dyn toml_edit::TableLike, path: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+    for (key, item) in table.iter() {
+        path.push(key.to_string());
+        match item.as_table_like() {
+            Some(child) => collect_value_paths(child, path, paths),
+            None => paths.push(path.clone()),
+        }
+        path.pop();
+    }
+}
+
+/// Why settings needing tools outside the Rust toolchain are skipped
+const NO_EXTERNAL_TOOLS_REASON: &str = "no_external_tools is set: only the Rust toolchain and rustup components are used";
+
+/// Why host linker and CPU settings are left out of embedded projects
+const EMBEDDED_REASON: &str = "embedded project: it is cross-compiled, so target and link settings are left alone";
+
+/// Whether the plan is for firmware built for another target than the host
+fn is_embedded(config: &Config) -> bool {
+    config.global.template == Some(ProjectTemplate::Embedded)
+}
+
+/// Whether rustc links x86_64 Linux binaries with its bundled rust-lld by default
+pub(crate) fn links_with_rust_lld(toolchain: &ToolchainInfo) -> bool {
+    Feature::RustLldDefault.availability(&toolchain.into()) == Availability::Default
+}
+
+/// Drop settings that need tools outside the Rust toolchain and make up for the default linker
+///
+/// Less debug info in dev builds is the largest link-time saving left when
+/// the linker cannot be replaced.
+fn restrict_to_toolchain(config: &mut Config, report: &mut OptimizationReport) {
+    if config.global.use_sccache {
+        config.global.use_sccache = false;
+        report.skipped("sccache", SkipReason::UserOptOut, NO_EXTERNAL_TOOLS_REASON);
+    }
+    if let Some(dev) = config.profiles.get_mut("dev") {
+        if dev.debug.is_none() {
+            dev.debug = Some("line-tables-only".to_string());
+            report.applied("profile.dev.debug", "line-tables-only, so the default linker has less debug info to process");
+        }
+    }
+}
+
+/// Setting name of target dir migration decisions in the report
+const TARGET_DIR_MIGRATION_SETTING: &str = "target-dir migration";
+
+/// Setting name of memory adjustment decisions in the report
+const MEMORY_PRESSURE_SETTING: &str = "memory pressure";
+
+/// Explanation id of settings lowered for memory pressure
+const MEMORY_PRESSURE_ID: &str = "CO-MEMORY-001";
+
+/// Explanation id of a job count adapted to system load
+const ADAPTIVE_JOBS_ID: &str = "CO-JOBS-002";
+
+/// Age of the recorded builds whose background load still counts, in seconds
+const ADAPTIVE_LOAD_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// Share of the available memory lowered settings aim for
+const MEMORY_TARGET_RATIO: f64 = 0.7;
+
+/// Seconds a branch switch must add to the next build before it is reported
+const MIN_BRANCH_SWITCH_LOSS_SECS: f64 = 5.0;
+
+/// Days of reflog read to measure how often branches are switched
+const BRANCH_ACTIVITY_DAYS: u32 = 14;
+
+/// Explanation id of a target directory moved to a Dev Drive
+const DEV_DRIVE_ID: &str = "CO-DEVDRIVE-001";
+
+/// Value lowered for memory pressure, marked with its explanation id
+fn memory_value(value: toml_edit::Value) -> Item {
+    explained_with_note(value, MEMORY_PRESSURE_ID, "memory pressure")
+}
+
+/// Explanation id of the sparse registry protocol
+const SPARSE_REGISTRY_ID: &str = "CO-SPARSE-001";
+
+/// Explanation id of gitoxide fetches
+const GITOXIDE_ID: &str = "CO-GITOXIDE-001";
+
+/// Whether `build.rustc-wrapper` in `doc` runs sccache
+fn runs_sccache(doc: &DocumentMut) -> bool {
+    doc.get("build")
+        .and_then(|build| build.get("rustc-wrapper"))
+        .and_then(Item::as_str)
+        .is_some_and(|wrapper| Path::new(wrapper).file_stem().is_some_and(|stem| stem == "sccache"))
+}
+
+/// Whether `item` is a value cargo-optimize wrote with explanation `id`
+fn carries_id(item: Option<&Item>, id: &str) -> bool {
+    item.and_then(Item::as_value)
+        .and_then(|value| value.decor().suffix())
+        .and_then(|suffix| suffix.as_str())
+        .is_some_and(|suffix| suffix.contains(explain::COMMENT_PREFIX) && suffix.contains(id))
+}
+
+/// Remove the value at `keys` if cargo-optimize wrote it with explanation `id`
+///
+/// Tables left empty by the removal are dropped with it.
+fn remove_explained(table: &mut Table, keys: &[&str], id: &str) -> bool {
+    let Some((first, rest)) = keys.split_first() else {
+        return false;
+    };
+    if rest.is_empty() {
+        if !carries_id(table.get(first), id) {
+            return false;
+        }
+        table.remove(first);
+        return true;
+    }
+    let Some(child) = table.get_mut(first).and_then(Item::as_table_mut) else {
+        return false;
+    };
+    let removed = remove_explained(child, rest, id);
+    if removed && child.is_empty() {
+        table.remove(first);
+    }
+    removed
+}
+
+/// Set the value at `keys`, creating missing tables
+fn insert_at(table: &mut Table, keys: &[&str], item: Item) {
+    let Some((leaf, parents)) = keys.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let entry = table.entry(key).or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        match entry.as_table_mut() {
+            Some(next) => table = next,
+            None => return,
+        }
+    }
+    table.insert(leaf, item);
+}
+
+/// Machine policy a new manager starts with
+///
+/// Unit tests and fake environments never see the policy of the machine
+/// running them; they pass one with [`ConfigManager::with_policy`] or
+/// `FakeEnvironment::with_policy`.
+fn machine_policy() -> Result<Option<Policy>> {
+    if cfg!(test) {
+        return Ok(None);
+    }
+    #[cfg(feature = "test-support")]
+    if let Some(policy) = crate::test_support::policy() {
+        return Ok(policy);
+    }
+    Policy::discover()
+}
+
+/// Whether a journaled key is one of the linker settings (`target.<triple>.linker` or `rustflags`)
+fn is_linker_key(key: &[String]) -> bool {
+    key.len() == 3 && key[0] == "target" && (key[2] == "linker" || key[2] == "rustflags")
+}
+
+/// Journal entries of values written with the memory pressure explanation
+fn memory_changes(journal: Option<&ApplyJournal>, staged: Option<&str>) -> Vec<JournalEntry> {
+    let (Some(journal), Some(doc)) = (journal, staged.and_then(|staged| staged.parse::<DocumentMut>().ok())) else {
+        return Vec::new();
+    };
+    journal
+        .entries
+        .iter()
+        .filter(|entry| {
+            let keys: Vec<&str> = entry.key.iter().map(String::as_str).collect();
+            item_at(doc.as_table(), &keys)
+                .and_then(Item::as_value)
+                .and_then(|value| value.decor().suffix())
+                .and_then(|suffix| suffix.as_str())
+                .is_some_and(|suffix| suffix.contains(MEMORY_PRESSURE_ID))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Put back the hand-set values of a committed config that planning changed or removed
+fn keep_hand_set_values(original: &DocumentMut, doc: &mut DocumentMut, report: &mut OptimizationReport) {
+    let plain = |item: &Item| {
+        let value = item.as_value()?;
+        toml::from_str::<toml::Table>(&format!("v = {}", value)).ok()?.remove("v")
+    };
+    for (path, item) in conflicts::hand_set_values(original) {
+        let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+        if item_at(doc.as_table(), &keys).is_some_and(|planned| plain(planned) == plain(&item)) {
+            continue;
+        }
+        restore_item(doc.as_table_mut(), original.as_table(), &keys);
+        report.skipped(path.join("."), SkipReason::UserOptOut, "set by hand in the committed .cargo/config.toml");
+    }
+}
+
+/// Replace Nix store paths in the values this run wrote with the commands they name
+///
+/// Values the original config already had are left to the user, who is
+/// advised to change them.
+fn unpin_store_paths(original: &DocumentMut, doc: &mut DocumentMut, report: &mut OptimizationReport) {
+    fn walk(table: &mut dyn toml_edit::TableLike, original: &DocumentMut, path: &mut Vec<String>, report: &mut OptimizationReport) {
+        for (key, item) in table.iter_mut() {
+            path.push(key.get().to_string());
+            let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+            let unchanged = item_at(original.as_table(), &keys)
+                .zip(item.as_value())
+                .is_some_and(|(before, value)| before.as_value().map(ToString::to_string) == Some(value.to_string()));
+            match item {
+                Item::Table(_) | Item::Value(toml_edit::Value::InlineTable(_)) => {
+                    if let Some(child) = item.as_table_like_mut() {
+                        walk(child, original, path, report);
+                    }
+                }
+                Item::Value(value) if !unchanged => {
+                    let mut unpinned = false;
+                    let mut unpin = |value: &mut toml_edit::Value| {
+                        if let Some(command) = value.as_str().and_then(nix::unpin_store_paths) {
+                            let decor = value.decor().clone();
+                            *value = command.into();
+                            *value.decor_mut() = decor;
+                            unpinned = true;
+                        }
+                    };
+                    match value.as_array_mut() {
+                        Some(array) => array.iter_mut().for_each(&mut unpin),
+                        None => unpin(value),
+                    }
+                    if unpinned {
+                        report.applied(path.join("."), "Nix store path replaced by the command name");
+                    }
+                }
+                _ => {}
+            }
+            path.pop();
+        }
+    }
+    walk(doc.as_table_mut(), original, &mut Vec::new(), report);
+}
+
+fn key_matches(pattern: &str, path: &[String]) -> bool {
+    let segments: Vec<&str> = pattern.split('.').collect();
+    segments.len() == path.len()
+        && segments.iter().zip(path).all(|(pattern, key)| *pattern == "*" || pattern == key)
+}
+
+/// Optimization level for build configuration
+///
+/// What each level changes is defined in one place, its
+/// [`OptimizationPreset`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizationLevel {
+    /// Conservative optimization - minimal changes, maximum compatibility
+    Conservative,
+    /// Balanced optimization - good performance with reasonable safety
+    Balanced,
+    /// Aggressive optimization - maximum performance, may affect stability
+    Aggressive,
+}
+
+impl OptimizationLevel {
+    /// Name used in configuration files (e.g. "balanced")
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptimizationLevel::Conservative => "conservative",
+            OptimizationLevel::Balanced => "balanced",
+            OptimizationLevel::Aggressive => "aggressive",
+        }
+    }
+    
+    /// Concrete settings of the level
+    pub fn preset(&self) -> &'static OptimizationPreset {
+        match self {
+            OptimizationLevel::Conservative => &PRESETS[0],
+            OptimizationLevel::Balanced => &PRESETS[1],
+            OptimizationLevel::Aggressive => &PRESETS[2],
+        }
+    }
+}
+
+impl std::fmt::Display for OptimizationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Which linkers an optimization level may select
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkerSelection {
+    /// Only linkers with a long production record: rust-lld, lld-link, lld and gold
+    Proven,
+    /// The fastest installed linker, including mold, wild, sold and zld
+    Fastest,
+}
+
+impl LinkerSelection {
+    /// Whether the selection includes `spec`
+    pub fn allows(&self, spec: &LinkerSpec) -> bool {
+        match self {
+            LinkerSelection::Proven => ["rust-lld", "lld-link", "lld", "gold"].contains(&spec.name),
+            LinkerSelection::Fastest => true,
+        }
+    }
+}
+
+/// Concrete settings behind an [`OptimizationLevel`]
+///
+/// The config defaults take the job count and sccache setting from the
+/// preset; planning restricts linker detection to the preset's selection and
+/// fills in its profile settings where neither the user nor a template set
+/// them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizationPreset {
+    /// Level the preset belongs to
+    pub level: OptimizationLevel,
+    /// Linkers that may be configured
+    pub linkers: LinkerSelection,
+    /// Whether sccache is used
+    pub sccache: bool,
+    /// Default job count of every profile, as a share of the cores
+    pub jobs: &'static str,
+    /// `debug` of the dev profile; `None` keeps cargo's default
+    pub dev_debug: Option<&'static str>,
+    /// `lto` of the release profile; `None` keeps cargo's default
+    pub release_lto: Option<&'static str>,
+    /// Whether release builds target the CPU of the building machine
+    /// (`-C target-cpu=native`), making binaries unportable
+    pub native_cpu: bool,
+}
+
+const PRESETS: [OptimizationPreset; 3] = [
+    OptimizationPreset {
+        level: OptimizationLevel::Conservative,
+        linkers: LinkerSelection::Proven,
+        sccache: false,
+        jobs: "50%",
+        dev_debug: None,
+        release_lto: None,
+        native_cpu: false,
+    },
+    OptimizationPreset {
+        level: OptimizationLevel::Balanced,
+        linkers: LinkerSelection::Fastest,
+        sccache: true,
+        jobs: "75%",
+        dev_debug: None,
+        release_lto: None,
+        native_cpu: false,
+    },
+    OptimizationPreset {
+        level: OptimizationLevel::Aggressive,
+        linkers: LinkerSelection::Fastest,
+        sccache: true,
+        jobs: "100%",
+        dev_debug: Some("line-tables-only"),
+        release_lto: Some("fat"),
+        native_cpu: true,
+    },
+];
+
+/// Presets of every optimization level, from conservative to aggressive
+pub fn presets() -> &'static [OptimizationPreset] {
+    &PRESETS
+}
+
+impl OptimizationPreset {
+    /// Fill in the preset's profile settings where the profiles do not set them already
+    pub fn apply_to(&self, config: &mut Config) {
+        fn profile<'a>(config: &'a mut Config, name: &str) -> &'a mut Profile {
+            config
+                .profiles
+                .entry(name.to_string())
+                .or_insert_with(|| Profile::default_for_name(name.to_string()))
+        }
+        if let Some(debug) = self.dev_debug {
+            profile(config, "dev").debug.get_or_insert_with(|| debug.to_string());
+        }
+        if let Some(lto) = self.release_lto {
+            profile(config, "release").lto.get_or_insert_with(|| lto.to_string());
+        }
+        if self.native_cpu {
+            let release = profile(config, "release");
+            if !release.rustflags.iter().any(|flag| flag.contains("target-cpu")) {
+                release.rustflags.extend(["-C".to_string(), "target-cpu=native".to_string()]);
+            }
+        }
+    }
+}
+
+/// Cache configuration settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheSettings {
+    /// Whether caching is enabled
+    pub enabled: bool,
+    
+    /// Cache directory location
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+    
+    /// Maximum cache size (supports percentages like "10%")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<CacheSize>,
+    
+    /// Cache type (sccache, ccache, etc.)
+    pub cache_type: CacheType,
+}
+
+/// Cache size configuration with percentage support
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged, try_from = "RawCacheSize")]
+pub enum CacheSize {
+    /// Fixed size in MB
+    Megabytes(usize),
+    /// Percentage of available disk space, or a resource expression over it
+    /// (e.g. "min(4096, 10%)")
+    Percentage(String),
+}
+
+/// Unvalidated form of [`CacheSize`] as written in the config
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawCacheSize {
+    Megabytes(usize),
+    Expression(String),
+}
+
+impl TryFrom<RawCacheSize> for CacheSize {
+    type Error = String;
+
+    fn try_from(raw: RawCacheSize) -> std::result::Result<Self, Self::Error> {
+        match raw {
+            RawCacheSize::Megabytes(mb) => Ok(CacheSize::Megabytes(mb)),
+            RawCacheSize::Expression(expression) => CacheSize::parse(&expression).map_err(|e| format!("{:#}", e)),
+        }
+    }
+}
+
+impl CacheSize {
+    /// Parse a size in MB, a percentage of available disk space or an expression
+    pub fn parse(s: &str) -> Result<Self> {
+        if let Ok(mb) = s.trim().parse::<usize>() {
+            return Ok(CacheSize::Megabytes(mb));
+        }
+        ResourceExpr::parse(s, ResourceKind::Amount).with_context(|| format!("Invalid cache size: {}", s))?;
+        Ok(CacheSize::Percentage(s.to_string()))
+    }
+    
+    /// Convert to actual size in MB based on available disk space
+    pub fn to_megabytes(&self) -> usize {
+        match self {
+            CacheSize::Megabytes(mb) => *mb,
+            CacheSize::Percentage(p) => {
+                // Use the available space on the disk holding the current directory,
+                // defaulting to 10GB if it cannot be determined
+                let hardware = crate::hardware::HardwareInfo::detect();
+                let total_space_mb = std::env::current_dir()
+                    .ok()
+                    .and_then(|cwd| hardware.disk_for_path(&cwd).map(|d| d.available_space))
+                    .map(|bytes| (bytes / (1024 * 1024)) as usize)
+                    .unwrap_or(10240);
+                
+                match ResourceExpr::parse(p, ResourceKind::Amount) {
+                    Ok(expression) => {
+                        let size = expression.eval_amount(total_space_mb as f64).round() as usize;
+                        size.max(100) // Minimum 100MB
+                    }
+                    // Default to 1GB if parsing fails
+                    Err(_) => 1024,
+                }
+            }
+        }
+    }
+}
+
+/// Type of build cache to use
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheType {
+    /// No caching
+    None,
+    /// sccache
+    Sccache,
+    /// ccache
+    Ccache,
+    /// Custom cache command
+    Custom(String),
+}
+
+/// Backup configuration for rollback support
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConfig {
+    /// Whether to create backups automatically
+    pub auto_backup: bool,
+    
+    /// Maximum number of backups to keep
+    pub max_backups: usize,
+    
+    /// Directory to store backups
+    pub backup_dir: PathBuf,
+}
+
+/// Metadata sidecar written next to each backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupMetadata {
+    /// Config file that was backed up
+    pub origin: PathBuf,
+    /// When the backup was taken (seconds since the Unix epoch)
+    pub created_at: u64,
+    /// Version of cargo-optimize that took the backup
+    pub tool_version: String,
+    /// Hex SHA-256 of the backup content
+    pub sha256: String,
+    /// Why the backup was taken (e.g. "before apply")
+    pub reason: String,
+}
+
+/// A backup found in the backup directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupEntry {
+    /// Backup file
+    pub path: PathBuf,
+    /// Sidecar metadata; `None` for backups taken before sidecars existed
+    pub metadata: Option<BackupMetadata>,
+}
+
+impl BackupEntry {
+    /// Check the backup content against the recorded SHA-256
+    ///
+    /// Backups without metadata cannot be verified and pass.
+    pub fn verify(&self) -> Result<()> {
+        let Some(metadata) = &self.metadata else {
+            return Ok(());
+        };
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read backup file: {:?}", self.path))?;
+        let actual = sha256_hex(&content);
+        if actual != metadata.sha256 {
+            anyhow::bail!(
+                "Backup {:?} is corrupt: SHA-256 {} does not match recorded {}",
+                self.path,
+                actual,
+                metadata.sha256
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Result of [`ConfigManager::verify`]
+#[derive(Debug, Clone)]
+pub struct Verification {
+    /// Keys, dotted, whose value differs from what apply would write
+    pub drifted: Vec<String>,
+    /// Report of the plan the config was checked against
+    pub report: OptimizationReport,
+}
+
+impl Verification {
+    /// Whether the config differs from what apply would write
+    pub fn is_drifted(&self) -> bool {
+        !self.drifted.is_empty()
+    }
+    
+    /// Exit status for the check
+    ///
+    /// Policy conflicts and an unsupported platform outrank drift: re-running
+    /// apply would not resolve them.
+    pub fn exit_status(&self) -> ExitStatus {
+        match ExitStatus::of_report(&self.report) {
+            ExitStatus::Success if self.is_drifted() => ExitStatus::Drift,
+            status => status,
+        }
+    }
+}
+
+/// Metadata about the configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigMetadata {
+    /// Version of cargo-optimize that created this config
+    pub version: String,
+    
+    /// Timestamp when config was created/modified
+    pub timestamp: u64,
+    
+    /// Platform this config was created for
+    pub platform: String,
+    
+    /// Hash of the configuration for integrity checking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// Environment variable forcing the configuration context (`ci` or `local`)
+pub const CONTEXT_ENV_VAR: &str = "CARGO_OPTIMIZE_CONTEXT";
+
+/// Where a build runs, selecting a `[contexts.<name>]` section of `cargo-optimize.toml`
+///
+/// Settings in `[contexts.ci]` or `[contexts.local]` (profiles, global
+/// settings, ...) override the rest of the file in that context only, so one
+/// committed config serves developer machines and CI alike.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildContext {
+    /// Developer machine
+    #[default]
+    Local,
+    /// Continuous integration
+    Ci,
+}
+
+impl BuildContext {
+    /// Detect the context from the process environment
+    pub fn detect() -> Self {
+        Self::from_env_lookup(|key| std::env::var(key).ok())
+    }
+    
+    /// Detect the context using a custom environment lookup (for testing)
+    ///
+    /// `CARGO_OPTIMIZE_CONTEXT` wins; otherwise any detected CI service means
+    /// [`BuildContext::Ci`]. An unrecognized override is ignored.
+    pub fn from_env_lookup<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(context) = lookup(CONTEXT_ENV_VAR).and_then(|value| value.parse().ok()) {
+            return context;
+        }
+        match CiEnvironment::from_env_lookup(lookup) {
+            Some(_) => BuildContext::Ci,
+            None => BuildContext::Local,
+        }
+    }
+    
+    /// Name of the context's config section
+    pub fn name(self) -> &'static str {
+        match self {
+            BuildContext::Local => "local",
+            BuildContext::Ci => "ci",
+        }
+    }
+}
+
+impl std::str::FromStr for BuildContext {
+    type Err = String;
+    
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "local" => Ok(BuildContext::Local),
+            "ci" => Ok(BuildContext::Ci),
+            other => Err(format!("unknown context `{}` (expected `local` or `ci`)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for BuildContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Where the environment layer of the configuration comes from
+#[derive(Debug, Clone)]
+enum EnvSource {
+    /// Process environment variables under a prefix
+    Prefixed(String),
+    /// Variables keyed without the prefix, the process environment unread
+    Vars(BTreeMap<String, String>),
+}
+
+/// Configuration manager using Figment for layered config
+pub struct ConfigManager {
+    _figment: Figment,
+    config: Config,
+    context: BuildContext,
+    config_path: PathBuf,
+    env: EnvSource,
+    target_dir_env: Option<PathBuf>,
+    toolchain: Option<ToolchainInfo>,
+    msrv: Option<RustVersion>,
+    detected_template: Option<ProjectTemplate>,
+    installed_components: Option<Vec<String>>,
+    nextest_installed: Option<bool>,
+    sccache_stats: Option<SccacheStats>,
+    sccache_dist_reachability: Option<Reachability>,
+    project_size: Option<ProjectSize>,
+    platform: Option<Platform>,
+    arch: Option<String>,
+    installed_commands: Option<Vec<String>>,
+    tool_environment: Option<ToolEnvironment>,
+    branch_activity: Option<BranchActivity>,
+    volume_kinds: Option<BTreeMap<String, VolumeKind>>,
+    deprecations: Vec<DeprecationWarning>,
+    disk_benchmark: Option<DiskBenchmark>,
+    system_load: Option<SystemLoad>,
+    phase_probe: Option<PhaseProbe>,
+    sccache_cache: Option<CacheLocation>,
+    policy: Option<Policy>,
+    providers: Vec<Provider>,
+}
+
+impl ConfigManager {
+    /// Create a new configuration manager with layered sources
+    pub fn new() -> Result<Self> {
+        Self::new_with_env_prefix("CARGO_OPTIMIZE_")
+    }
+    
+    /// Create a new configuration manager with custom environment prefix (for testing)
+    pub fn new_with_env_prefix(env_prefix: &str) -> Result<Self> {
+        // Use current directory as base
+        let base_dir = std::env::current_dir()?;
+        Self::new_with_base_dir(&base_dir, env_prefix)
+    }
+    
+    /// Create a new configuration manager with a specific base directory
+    /// This allows tests to work with isolated directories without changing the process's current directory
+    pub fn new_with_base_dir(base_dir: &Path, env_prefix: &str) -> Result<Self> {
+        Self::new_with_context(base_dir, env_prefix, BuildContext::detect())
+    }
+    
+    /// Create a configuration manager for an explicit context instead of detecting it
+    pub fn new_with_context(base_dir: &Path, env_prefix: &str, context: BuildContext) -> Result<Self> {
+        Self::load(base_dir, EnvSource::Prefixed(env_prefix.to_string()), context)
+    }
+    
+    /// Create a configuration manager reading `vars` instead of the process environment
+    ///
+    /// `vars` are keyed without the `CARGO_OPTIMIZE_` prefix (e.g.
+    /// `GLOBAL__VERBOSE`), as replaying a capture needs.
+    pub(crate) fn new_with_env_vars(base_dir: &Path, vars: BTreeMap<String, String>, context: BuildContext) -> Result<Self> {
+        Self::load(base_dir, EnvSource::Vars(vars), context)
+    }
+    
+    fn load(base_dir: &Path, env: EnvSource, context: BuildContext) -> Result<Self> {
+        // Construct absolute path to cargo-optimize.toml
+        let config_file = base_dir.join("cargo-optimize.toml");
+        let toolchain = Self::toolchain_for_sections(&config_file)?;
+        let spec = toolchain.as_ref().map(ToolchainSpec::from);
+        let figment = Self::layered_figment(&config_file, &env, context, spec.as_ref());
+        
+        // Extract the configuration
+        let mut config: Config = figment.extract()
+            .map_err(|e| anyhow::anyhow!("Failed to extract config: {}", e))?;
+        config.deny.validate().context("Invalid [deny] in cargo-optimize.toml")?;
+        let deprecations = deprecation::warn_deprecated(&config_file, config.global.auto_migrate);
+        config.complete_profiles(base_dir);
+        
+        // Auto-detect hardware if enabled
+        if config.global.auto_detect_hardware {
+            config.apply_hardware_optimizations()?;
+        }
+        
+        // Use absolute path for .cargo/config.toml
+        let config_path = base_dir.join(".cargo").join("config.toml");
+        
+        Ok(ConfigManager {
+            _figment: figment,
+            config,
+            context,
+            config_path,
+            env,
+            target_dir_env: target_dir_from_env(),
+            toolchain,
+            msrv: None,
+            detected_template: None,
+            installed_components: None,
+            nextest_installed: None,
+            sccache_stats: None,
+            sccache_dist_reachability: None,
+            project_size: None,
+            platform: Platform::current(),
+            arch: None,
+            installed_commands: None,
+            tool_environment: None,
+            branch_activity: None,
+            volume_kinds: None,
+            deprecations,
+            disk_benchmark: None,
+            system_load: None,
+            phase_probe: None,
+            sccache_cache: None,
+            policy: machine_policy()?,
+            providers: providers::registered(),
+        })
+    }
+    
+    /// Load configuration from a specific profile
+    pub fn with_profile(profile: &str) -> Result<Self> {
+        Self::with_profile_and_env_prefix(profile, "CARGO_OPTIMIZE_")
     }
     
     /// Load configuration from a specific profile with custom environment prefix (for testing)
@@ -434,256 +2068,3197 @@ impl ConfigManager {
         Self::with_profile_and_base_dir(profile, &base_dir, env_prefix)
     }
     
-    /// Load configuration from a specific profile with a specific base directory
-    pub fn with_profile_and_base_dir(profile: &str, base_dir: &Path, env_prefix: &str) -> Result<Self> {
-        // Construct absolute path to cargo-optimize.toml
-        let config_file = base_dir.join("cargo-optimize.toml");
+    /// Load configuration from a specific profile with a specific base directory
+    pub fn with_profile_and_base_dir(profile: &str, base_dir: &Path, env_prefix: &str) -> Result<Self> {
+        // Construct absolute path to cargo-optimize.toml
+        let config_file = base_dir.join("cargo-optimize.toml");
+        let context = BuildContext::detect();
+        let toolchain = Self::toolchain_for_sections(&config_file)?;
+        let spec = toolchain.as_ref().map(ToolchainSpec::from);
+        let env = EnvSource::Prefixed(env_prefix.to_string());
+        let figment = Self::layered_figment(&config_file, &env, context, spec.as_ref());
+        
+        // Extract the configuration
+        let mut config: Config = figment.extract()
+            .map_err(|e| anyhow::anyhow!("Failed to extract config: {}", e))?;
+        config.deny.validate().context("Invalid [deny] in cargo-optimize.toml")?;
+        let deprecations = deprecation::warn_deprecated(&config_file, config.global.auto_migrate);
+        config.complete_profiles(base_dir);
+        
+        // Debug logging to understand what's being loaded
+        debug!("Loading configuration with profile: {}", profile);
+        debug!("Base directory: {:?}", base_dir);
+        debug!("Config file path: {:?}, exists: {}", config_file, config_file.exists());
+        debug!("Auto-detect hardware: {}", config.global.auto_detect_hardware);
+        debug!("Optimization level: {:?}", config.global.optimization_level);
+        
+        // Verify the profile exists
+        if !config.profiles.contains_key(profile) {
+            // If the profile doesn't exist, create a default one
+            let default_profile = Profile::default_for_name(profile.to_string());
+            config.profiles.insert(profile.to_string(), default_profile);
+        }
+        
+        // Auto-detect hardware if enabled
+        if config.global.auto_detect_hardware {
+            config.apply_hardware_optimizations()?;
+        }
+        
+        // Use absolute path for .cargo/config.toml
+        let config_path = base_dir.join(".cargo").join("config.toml");
+        
+        // Return the manager with the loaded configuration
+        Ok(ConfigManager {
+            _figment: figment,
+            config,
+            context,
+            config_path,
+            env,
+            target_dir_env: target_dir_from_env(),
+            toolchain,
+            msrv: None,
+            detected_template: None,
+            installed_components: None,
+            nextest_installed: None,
+            sccache_stats: None,
+            sccache_dist_reachability: None,
+            project_size: None,
+            platform: Platform::current(),
+            arch: None,
+            installed_commands: None,
+            tool_environment: None,
+            branch_activity: None,
+            volume_kinds: None,
+            deprecations,
+            disk_benchmark: None,
+            system_load: None,
+            phase_probe: None,
+            sccache_cache: None,
+            policy: machine_policy()?,
+            providers: providers::registered(),
+        })
+    }
+    
+    /// Use the given toolchain instead of detecting it with `rustc -vV`
+    ///
+    /// Reloads the configuration when `cargo-optimize.toml` has
+    /// `[toolchain."<selector>"]` sections, so the ones matching `toolchain` apply.
+    pub fn with_toolchain(mut self, toolchain: ToolchainInfo) -> Self {
+        let config_file = self.project_root().join("cargo-optimize.toml");
+        if toolchain_overrides::has_sections(&config_file) {
+            if let Err(e) = self.reload_for_toolchain(&config_file, &ToolchainSpec::from(&toolchain)) {
+                warn!("Keeping the configuration loaded for the detected toolchain: {:#}", e);
+            }
+        }
+        self.toolchain = Some(toolchain);
+        self
+    }
+    
+    /// Extract the configuration again with the sections matching `toolchain`
+    ///
+    /// Profiles the constructor added for a requested profile are kept.
+    fn reload_for_toolchain(&mut self, config_file: &Path, toolchain: &ToolchainSpec) -> Result<()> {
+        let figment = Self::layered_figment(config_file, &self.env, self.context, Some(toolchain));
+        let mut config: Config = figment.extract()
+            .map_err(|e| anyhow::anyhow!("Failed to extract config: {}", e))?;
+        config.deny.validate().context("Invalid [deny] in cargo-optimize.toml")?;
+        config.complete_profiles(&self.project_root());
+        for name in self.config.profiles.keys() {
+            if !config.profiles.contains_key(name) {
+                config.profiles.insert(name.clone(), Profile::default_for_name(name.clone()));
+            }
+        }
+        if config.global.auto_detect_hardware {
+            config.apply_hardware_optimizations()?;
+        }
+        self._figment = figment;
+        self.config = config;
+        Ok(())
+    }
+    
+    /// The active toolchain, detected only when `[toolchain."<selector>"]` sections need it
+    ///
+    /// Fails on a selector that does not parse. Without a detectable
+    /// toolchain, no section applies.
+    pub(crate) fn toolchain_for_sections(config_file: &Path) -> Result<Option<ToolchainInfo>> {
+        if toolchain_overrides::sections(config_file)?.is_empty() {
+            return Ok(None);
+        }
+        match ToolchainInfo::detect() {
+            Ok(toolchain) => Ok(Some(toolchain)),
+            Err(e) => {
+                warn!("Ignoring the [toolchain] sections of {}: {:#}", config_file.display(), e);
+                Ok(None)
+            }
+        }
+    }
+    
+    /// Use the given `CARGO_TARGET_DIR` value instead of reading the environment
+    pub fn with_target_dir_env(mut self, dir: Option<PathBuf>) -> Self {
+        self.target_dir_env = dir;
+        self
+    }
+    
+    /// Use the given MSRV instead of reading `rust-version` from `Cargo.toml`
+    pub fn with_msrv(mut self, msrv: RustVersion) -> Self {
+        self.msrv = Some(msrv);
+        self
+    }
+    
+    /// Use the given machine policy instead of loading the installed one
+    pub fn with_policy(mut self, policy: Option<Policy>) -> Self {
+        self.policy = policy;
+        self
+    }
+    
+    /// Use the project template detected by analysis when none is configured
+    pub fn with_detected_template(mut self, template: ProjectTemplate) -> Self {
+        self.detected_template = Some(template);
+        self
+    }
+    
+    /// Use the given installed components instead of asking rustup
+    pub fn with_installed_components(mut self, components: Vec<String>) -> Self {
+        self.installed_components = Some(components);
+        self
+    }
+    
+    /// Assume cargo-nextest is (or is not) installed instead of probing for it
+    pub fn with_nextest_installed(mut self, installed: bool) -> Self {
+        self.nextest_installed = Some(installed);
+        self
+    }
+    
+    /// Use the given sccache statistics instead of asking the sccache server
+    pub fn with_sccache_stats(mut self, stats: SccacheStats) -> Self {
+        self.sccache_stats = Some(stats);
+        self
+    }
+    
+    /// Check the given sccache cache instead of the one sccache uses
+    pub fn with_sccache_cache(mut self, location: CacheLocation) -> Self {
+        self.sccache_cache = Some(location);
+        self
+    }
+    
+    /// Use the given sccache-dist probe result instead of connecting to the scheduler
+    pub fn with_sccache_dist_reachability(mut self, reachability: Reachability) -> Self {
+        self.sccache_dist_reachability = Some(reachability);
+        self
+    }
+    
+    /// Use the project size from analysis to judge whether sccache pays off
+    pub fn with_project_size(mut self, size: ProjectSize) -> Self {
+        self.project_size = Some(size);
+        self
+    }
+    
+    /// Plan for the given host platform instead of the one cargo-optimize was built for
+    ///
+    /// `None` stands for a platform without a capability descriptor.
+    pub fn with_platform(mut self, platform: Option<Platform>) -> Self {
+        self.platform = platform;
+        self
+    }
+    
+    /// Write linker settings for `arch` (as in `std::env::consts::ARCH`) instead of the host's
+    pub fn with_arch(mut self, arch: impl Into<String>) -> Self {
+        self.arch = Some(arch.into());
+        self
+    }
+    
+    /// Target triple of `platform` on the configured architecture, if it has one
+    fn target_triple(&self, platform: Platform) -> Option<&'static str> {
+        match &self.arch {
+            Some(arch) => platform.target_triple_for(arch),
+            None => platform.target_triple(),
+        }
+    }
+    
+    /// Skip `setting` because `platform` has no triple on the configured architecture
+    fn skip_unknown_triple(&self, setting: impl Into<String>, platform: Platform, report: &mut OptimizationReport) {
+        let arch = self.arch.clone().unwrap_or_else(linkers::host_arch);
+        report.skipped(setting, SkipReason::UnsupportedPlatform, format!("no {} target triple is known for {}", platform.name(), arch));
+    }
+    
+    /// Treat exactly the given commands (linkers, C compiler drivers) as installed instead of probing PATH
+    pub fn with_installed_commands(mut self, commands: Vec<String>) -> Self {
+        self.installed_commands = Some(commands);
+        self
+    }
+    
+    /// Whether `command` is installed, as given by [`Self::with_installed_commands`] or on `PATH`
+    fn is_installed(&self, command: &str) -> bool {
+        match &self.installed_commands {
+            Some(installed) => installed.iter().any(|installed| installed == command),
+            None => crate::mvp::command_exists(command),
+        }
+    }
+    
+    /// Use the given view of other build tooling instead of inspecting the environment and project
+    pub fn with_tool_environment(mut self, tools: ToolEnvironment) -> Self {
+        self.tool_environment = Some(tools);
+        self
+    }
+    
+    /// Use the given branch switches instead of reading the git reflog
+    pub fn with_branch_activity(mut self, activity: BranchActivity) -> Self {
+        self.branch_activity = Some(activity);
+        self
+    }
+    
+    /// Use the given volume kinds by mount point instead of asking `fsutil`
+    ///
+    /// Volumes left out count as [`VolumeKind::Other`].
+    pub fn with_volume_kinds(mut self, kinds: BTreeMap<String, VolumeKind>) -> Self {
+        self.volume_kinds = Some(kinds);
+        self
+    }
+    
+    /// Use the given disk benchmark instead of the one cached in the state file
+    pub fn with_disk_benchmark(mut self, benchmark: DiskBenchmark) -> Self {
+        self.disk_benchmark = Some(benchmark);
+        self
+    }
+    
+    /// Use the given load instead of sampling the machine's (see [`hardware::load::sample`])
+    pub fn with_system_load(mut self, load: SystemLoad) -> Self {
+        self.system_load = Some(load);
+        self
+    }
+    
+    /// Verify each apply phase with `probe` instead of the built-in test link
+    pub fn with_phase_probe(mut self, probe: PhaseProbe) -> Self {
+        self.phase_probe = Some(probe);
+        self
+    }
+    
+    /// Plan `provider` too, in addition to the ones registered with [`providers::register`]
+    pub fn with_provider(mut self, provider: impl OptimizationProvider + 'static) -> Self {
+        let provider: Provider = std::sync::Arc::new(provider);
+        self.providers.retain(|existing| existing.name() != provider.name());
+        self.providers.push(provider);
+        self
+    }
+    
+    /// Layered configuration sources, lowest precedence first
+    fn layered_figment(
+        config_file: &Path,
+        env: &EnvSource,
+        context: BuildContext,
+        toolchain: Option<&ToolchainSpec>,
+    ) -> Figment {
+        // 1. Start with defaults
+        let mut figment = Figment::new().merge(Toml::string(&Self::default_config_toml()));
+        
+        // 2. Merge with cargo-optimize.toml if it exists
+        if config_file.exists() {
+            figment = figment.merge(Toml::file(config_file));
+            figment = figment.merge(deprecation::renamed_settings(config_file));
+            figment = figment.merge(context_settings(config_file, context));
+            // Selectors were validated when the toolchain was detected
+            if let Some(layer) = toolchain.and_then(|spec| toolchain_overrides::toolchain_settings(config_file, spec).ok()) {
+                figment = figment.merge(layer);
+            }
+        }
+        
+        // 3. Override with environment variables
+        // Use double underscore for nested keys (e.g., PREFIX_GLOBAL__VERBOSE)
+        match env {
+            EnvSource::Prefixed(prefix) => figment.merge(Env::prefixed(prefix).split("__")),
+            EnvSource::Vars(vars) => vars.iter().fold(figment, |figment, (key, value)| {
+                let path = key.to_ascii_lowercase().replace("__", ".");
+                let value: figment::value::Value = value.parse().unwrap_or_else(|never| match never {});
+                figment.merge((path, value))
+            }),
+        }
+    }
+    
+    /// Get the default configuration as TOML string
+    fn default_config_toml() -> String {
+        let config = Config::default();
+        toml::to_string_pretty(&config).unwrap_or_default()
+    }
+    
+    /// Apply configuration to .cargo/config.toml while preserving formatting
+    pub fn apply(&self) -> Result<()> {
+        self.apply_with_report().map(|_| ())
+    }
+    
+    /// Apply configuration and return a report of the decisions taken
+    pub fn apply_with_report(&self) -> Result<OptimizationReport> {
+        self.apply_with_hardware(&HardwareInfo::detect())
+    }
+    
+    /// Apply configuration using the given hardware information
+    ///
+    /// If the project disk is below `global.min_free_disk_gb`, sccache and
+    /// incremental compilation are skipped since both consume disk space, and
+    /// the report lists cleanup candidates with their measured sizes.
+    ///
+    /// With `global.pull_request_mode` set the changes are only proposed;
+    /// with `CARGO_OPTIMIZE_DISABLE` set nothing is touched at all.
+    pub fn apply_with_hardware(&self, hardware: &HardwareInfo) -> Result<OptimizationReport> {
+        if let Some(report) = disabled_report("apply") {
+            return Ok(report);
+        }
+        
+        // A rewritten config makes the IDE reload the workspace
+        if let Some(ide) = ide::running_in_ide() {
+            let mut report = OptimizationReport::new();
+            report.skipped("apply", SkipReason::Context, format!("cargo runs on behalf of {}", ide));
+            return Ok(report);
+        }
+        
+        if self.config.global.pull_request_mode {
+            return self.propose_with_hardware(hardware);
+        }
+        
+        // Never rewrite the config underneath a running cargo build
+        let active_locks = build_lock::active_build_locks(&self.project_root());
+        if let Some(lock) = active_locks.first() {
+            return self.defer_apply(lock);
+        }
+        
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        if self.config.global.auto_detect_hardware {
+            self.calibrate_disk(&transaction.document());
+        }
+        let (mut transaction, mut report) = self.prepare_in(transaction, hardware)?;
+        self.stage_phases(&mut transaction, &mut report)?;
+        
+        // Back up from the in-memory copy rather than re-reading the file
+        if self.config.backup.auto_backup && transaction.original().is_some() {
+            self.backup_transaction(&transaction, "before apply")?;
+        }
+        
+        let journal = self.journal_for(&transaction)?;
+        let memory_changes = memory_changes(journal.as_ref(), transaction.staged());
+        let linker_changed = journal
+            .as_ref()
+            .is_some_and(|journal| journal.entries.iter().any(|entry| is_linker_key(&entry.key)));
+        // What the steps below see; the file is not read again
+        let written = transaction.document();
+        self.commit_audited(transaction, "apply")?;
+        self.record_journal(journal);
+        self.record_memory_adjustment(memory_changes, hardware, &report);
+        self.record_jobs_adjustment(&report, &written);
+        self.record_shared_rustflags(&report);
+        if linker_changed {
+            self.record_unverified_linker(&report);
+        }
+        
+        for transaction in self.stage_extra_files(hardware, &written, &mut report)? {
+            if transaction.is_changed() {
+                self.commit_audited(transaction, "apply")?;
+            }
+        }
+        
+        if !report.providers.is_empty() {
+            let project_root = self.project_root();
+            let context = ProviderContext { project_root: &project_root, platform: self.platform, hardware: Some(hardware), config: &written };
+            providers::apply(&self.providers, &context, &mut report);
+        }
+        
+        self.migrate_target_dirs(&mut report);
+        self.resolve_components(&mut report);
+        self.check_build_weight(&mut report);
+        self.clear_pending_apply(&mut report);
+        self.record_sccache_stats(&report);
+        report.unmet_requirements = strict::check(&self.config_path, &self.config.global.required);
+        
+        info!("Configuration applied successfully to {:?}", self.config_path);
+        Ok(report)
+    }
+    
+    /// Keep the staged config to the phases that pass verification
+    ///
+    /// Settings of a rolled back phase, and of the phases after it, are
+    /// reported as skipped instead of applied.
+    fn stage_phases(&self, transaction: &mut ConfigTransaction, report: &mut OptimizationReport) -> Result<()> {
+        let Some(planned) = transaction.staged() else { return Ok(()) };
+        let target = self.platform.and_then(|platform| self.target_triple(platform));
+        let staged = phases::stage(transaction.original(), planned, |phase, content| match (self.phase_probe, target) {
+            (Some(probe), _) => probe(phase, content),
+            (None, Some(target)) if phase == ApplyPhase::Linker && self.config.global.probe_phases => {
+                phases::test_link(content, target)
+            }
+            _ => Ok(()),
+        })?;
+        
+        for outcome in &staged.outcomes {
+            let reason = match (outcome.status, &outcome.detail) {
+                (PhaseStatus::RolledBack, Some(detail)) => format!("rolled back with the {} phase: {}", outcome.phase, detail),
+                (PhaseStatus::NotAttempted, _) => format!("{} phase not attempted after an earlier phase was rolled back", outcome.phase),
+                _ => continue,
+            };
+            if outcome.phase == ApplyPhase::Profiles {
+                // The target-dir layout those moves prepare for is not written
+                report.relocations.clear();
+            }
+            for setting in &outcome.settings {
+                report.decisions.retain(|decision| decision.setting != *setting);
+                report.skipped(setting.clone(), SkipReason::RolledBack, reason.clone());
+            }
+        }
+        if staged.rolled_back() {
+            let content = if staged.content.trim().is_empty() {
+                "# Cargo configuration managed by cargo-optimize\n".to_string()
+            } else {
+                staged.content
+            };
+            transaction.stage(content);
+        }
+        report.phases = staged.outcomes;
+        Ok(())
+    }
+    
+    /// Skip an apply because a cargo build holds `lock`, queueing it if configured
+    fn defer_apply(&self, lock: &Path) -> Result<OptimizationReport> {
+        let mut report = OptimizationReport::new();
+        let reason = format!("cargo build in progress (lock held on {})", lock.display());
+        warn!("Deferring apply: {}", reason);
+        report.skipped("apply", SkipReason::Context, reason.clone());
+        report.deferred_by = Some(lock.to_path_buf());
+        
+        if self.config.global.queue_when_busy {
+            State::update(&self.state_path(), |state| state.queue_apply(reason))?;
+            report.recommend("The change was queued and will be applied on the next run");
+        } else {
+            report.recommend("Re-run cargo-optimize once the running build has finished");
+        }
+        Ok(report)
+    }
+    
+    /// Carry build artifacts over to the target directories the layout introduced
+    ///
+    /// A failed migration is rolled back and reported; the applied config stays.
+    fn migrate_target_dirs(&self, report: &mut OptimizationReport) {
+        if report.relocations.is_empty() {
+            return;
+        }
+        match relocation::migrate(&report.relocations) {
+            Ok(migration) if migration.migrated.is_empty() => {
+                debug!("No build artifacts to migrate: {:?}", migration.skipped);
+            }
+            Ok(migration) => {
+                let verb = match self.config.global.target_dir_migration {
+                    MigrationMode::Move => "moved",
+                    _ => "copied",
+                };
+                let size = migration.size();
+                report.applied(
+                    TARGET_DIR_MIGRATION_SETTING,
+                    format!(
+                        "{} {} output dir(s) ({} files, {:.1} MB) into the new target dirs",
+                        verb,
+                        migration.migrated.len(),
+                        size.files,
+                        size.bytes as f64 / (1024.0 * 1024.0)
+                    ),
+                );
+            }
+            Err(e) => {
+                warn!("Target dir migration failed: {:#}", e);
+                report.skipped(TARGET_DIR_MIGRATION_SETTING, SkipReason::RolledBack, format!("rolled back: {:#}", e));
+            }
+        }
+    }
+    
+    /// Clear an apply queued by an earlier run now that it has been carried out
+    fn clear_pending_apply(&self, report: &mut OptimizationReport) {
+        match State::update(&self.state_path(), |state| state.pending_apply.take()) {
+            Ok(Some(pending)) => {
+                report.applied("apply", format!("carried out change queued earlier ({})", pending.reason));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to update state file: {:#}", e),
+        }
+    }
+    
+    /// Remember settings this apply lowered for memory pressure, or forget a reverted adjustment
+    fn record_memory_adjustment(&self, changes: Vec<JournalEntry>, hardware: &HardwareInfo, report: &OptimizationReport) {
+        let reverted = report
+            .decision_for(MEMORY_PRESSURE_SETTING)
+            .is_some_and(|decision| decision.reason.starts_with("reverted"));
+        let memory = if !changes.is_empty() {
+            Some(MemoryAdjustment {
+                recorded_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+                fingerprint: self.memory_fingerprint(hardware),
+                changes: ApplyJournal { entries: changes, ..ApplyJournal::default() },
+            })
+        } else if reverted {
+            None
+        } else {
+            return;
+        };
+        if let Err(e) = State::update(&self.state_path(), |state| state.memory = memory) {
+            warn!("Failed to record the memory adjustment: {:#}", e);
+        }
+    }
+    
+    /// Remember a job count adapted to system load, if it made it into the written config
+    fn record_jobs_adjustment(&self, report: &OptimizationReport, written: &DocumentMut) {
+        let Some(adjustment) = &report.jobs_adjustment else {
+            return;
+        };
+        // A hand-set value, the deny list or memory pressure may have replaced it
+        if !carries_id(item_at(written.as_table(), &["build", "jobs"]), ADAPTIVE_JOBS_ID) {
+            return;
+        }
+        if let Err(e) = State::update(&self.state_path(), |state| state.record_jobs_adjustment(adjustment.clone())) {
+            warn!("Failed to record the job count adjustment: {:#}", e);
+        }
+    }
+    
+    /// Remember the flags the stable rustflags fallback added, or forget them once it adds none
+    fn record_shared_rustflags(&self, report: &OptimizationReport) {
+        let shared = report.shared_rustflags.clone();
+        if shared.is_none() && State::load(&self.state_path()).shared_rustflags.is_none() {
+            return;
+        }
+        if let Err(e) = State::update(&self.state_path(), |state| state.shared_rustflags = shared) {
+            warn!("Failed to record the shared rustflags: {:#}", e);
+        }
+    }
+    
+    /// Machine and project memory adjustments are made for
+    fn memory_fingerprint(&self, hardware: &HardwareInfo) -> MemoryFingerprint {
+        let root = self.project_root();
+        let project_sha256 = ["Cargo.lock", "Cargo.toml"]
+            .iter()
+            .find_map(|name| fs::read_to_string(root.join(name)).ok())
+            .map(|content| sha256_hex(&content));
+        MemoryFingerprint {
+            cpu_count: hardware.cpu_count,
+            total_memory: hardware.total_memory,
+            project_sha256,
+        }
+    }
+    
+    /// Add the current sccache statistics to the history in the state file
+    ///
+    /// The history starts over once the wrapper was removed for a low hit
+    /// rate, so a wrapper added back later is measured afresh.
+    fn record_sccache_stats(&self, report: &OptimizationReport) {
+        let disabled = report
+            .decision_for("build.rustc-wrapper")
+            .is_some_and(|decision| decision.reason.contains(sccache::LOW_HIT_RATE_ID));
+        let stats = if disabled { None } else { self.sccache_stats.or_else(sccache::current_stats) };
+        if !disabled && stats.is_none() {
+            return;
+        }
+        let recorded = State::update(&self.state_path(), |state| match stats {
+            Some(stats) => state.sccache.record(stats),
+            None => state.sccache = SccacheHistory::default(),
+        });
+        if let Err(e) = recorded {
+            warn!("Failed to record sccache statistics: {:#}", e);
+        }
+    }
+    
+    /// Recommend the slim MVP apply when cargo-optimize weighs on the project's builds
+    ///
+    /// Reads the report of the last `cargo build --timings`, if there is one,
+    /// and the metadata a full analysis cached; runs nothing.
+    fn check_build_weight(&self, report: &mut OptimizationReport) {
+        let target_dir = report
+            .effective_target_dir
+            .as_ref()
+            .map(|dir| dir.path.clone())
+            .unwrap_or_else(|| self.project_root().join("target"));
+        let Some(weight) = build_weight::from_last_timings(&target_dir) else { return };
+        debug!("cargo-optimize build weight: {}", weight);
+        if let Some(advice) = weight.advice(self.config.global.build_weight_warn_ratio) {
+            report.recommend(advice);
+        }
+    }
+    
+    /// Check the health of the caches builds rely on
+    ///
+    /// Looks at the local sccache cache directory: its size against the cap,
+    /// files that cannot be read, corrupt entries and the hit-rate trend of
+    /// the statistics recorded on past applies. Problems are recommendations;
+    /// with `global.sccache_auto_repair` corrupt entries are deleted, or the
+    /// directory rotated, and the change is reported as applied. The
+    /// cargo-optimize config is never changed.
+    pub fn doctor(&self) -> Result<OptimizationReport> {
+        const SETTING: &str = "sccache.cache";
+        let mut report = OptimizationReport::new();
+        let Some(location) = self.sccache_cache.clone().or_else(CacheLocation::detect) else {
+            report.skipped(SETTING, SkipReason::MissingTool, "no local sccache cache directory found");
+            return Ok(report);
+        };
+        let state = State::load(&self.state_path());
+        let health = cache_health::check(&location, &state.sccache);
+        let mb = health.size as f64 / (1024.0 * 1024.0);
+        if health.is_healthy() {
+            report.applied(SETTING, format!("{} is healthy ({:.1} MB)", location.dir.display(), mb));
+            return Ok(report);
+        }
+        
+        for issue in &health.issues {
+            report.recommend(format!("sccache cache {}: {}", location.dir.display(), issue.describe()));
+        }
+        if output::disabled() {
+            report.skipped(SETTING, SkipReason::UserOptOut, format!("not repaired: disabled by {}", output::DISABLE_ENV_VAR));
+            return Ok(report);
+        }
+        if !self.config.global.sccache_auto_repair {
+            report.skipped(SETTING, SkipReason::UserOptOut, "not repaired; set sccache_auto_repair = true under [global] to repair it");
+            return Ok(report);
+        }
+        match cache_health::repair(&health)? {
+            Some(Repair::RemovedCorrupt { removed }) => {
+                report.applied(SETTING, format!("removed {} corrupt entries, kept the rest", removed));
+            }
+            Some(Repair::Rotated { previous }) => {
+                // A fresh cache starts cold; its hit rate is no trend
+                State::update(&self.state_path(), |state| state.sccache = SccacheHistory::default())?;
+                report.applied(
+                    SETTING,
+                    format!("rotated the cache; the old one is in {} until the next rotation", previous.display()),
+                );
+            }
+            None => report.skipped(SETTING, SkipReason::NotNeeded, "nothing to repair; the hit rate recovers as the cache warms up"),
+        }
+        Ok(report)
+    }
+    
+    /// Mark the linker this apply wrote as unverified until a guarded build succeeds
+    fn record_unverified_linker(&self, report: &OptimizationReport) {
+        let linker = report
+            .decisions
+            .iter()
+            .filter(|decision| decision.outcome == DecisionOutcome::Applied)
+            .filter(|decision| decision.setting.starts_with("target.") && decision.setting.ends_with(".linker"))
+            .find_map(|decision| decision.reason.split_whitespace().next())
+            .filter(|linker| linkers::find(linker).is_some());
+        let Some(linker) = linker else { return };
+        if let Err(e) = State::update(&self.state_path(), |state| state.unverified_linker = Some(linker.to_string())) {
+            warn!("Failed to record the linker awaiting its first build: {:#}", e);
+        }
+    }
+    
+    /// Linker written by an apply that no guarded build has succeeded with yet
+    pub fn unverified_linker(&self) -> Option<String> {
+        State::load(&self.state_path()).unverified_linker
+    }
+    
+    /// Record that a build succeeded with the unverified linker
+    pub fn confirm_linker(&self) -> Result<()> {
+        State::update(&self.state_path(), |state| state.unverified_linker = None)
+    }
+    
+    /// Revert the linker settings cargo-optimize wrote after they broke a build
+    ///
+    /// Only the journaled `target.<triple>.linker` and `rustflags` keys are
+    /// restored; user edits made since are kept. The linker is recorded as
+    /// incompatible so later applies pick another one or none.
+    pub fn rollback_linker(&self, failure: &LinkerFailure) -> Result<OptimizationReport> {
+        if let Some(report) = disabled_report("rollback") {
+            return Ok(report);
+        }
+        let mut report = OptimizationReport::new();
+        let mut state = State::lock(&self.state_path())?;
+        let Some(linker) = state.unverified_linker.take() else {
+            report.skipped("rollback", SkipReason::NotNeeded, "no linker is awaiting its first build");
+            return Ok(report);
+        };
+        
+        let mut journal = state.journal.take().unwrap_or_default();
+        let (linker_entries, other_entries): (Vec<_>, Vec<_>) =
+            journal.entries.into_iter().partition(|entry| is_linker_key(&entry.key));
+        journal.entries = other_entries;
+        let linker_journal = ApplyJournal {
+            entries: linker_entries,
+            created_tables: journal.created_tables.iter().filter(|path| path.first().is_some_and(|key| key == "target")).cloned().collect(),
+            ..ApplyJournal::default()
+        };
+        
+        let mut transaction = ConfigTransaction::begin(&self.config_path)?;
+        if let Some(content) = transaction.original() {
+            let mut doc = content.parse::<DocumentMut>().context("Failed to parse existing config")?;
+            let kept = linker_journal.revert(&mut doc);
+            for entry in &linker_journal.entries {
+                if kept.contains(entry) {
+                    report.skipped(entry.dotted_key(), SkipReason::UserOptOut, "edited after cargo-optimize wrote it");
+                    journal.entries.push(entry.clone());
+                } else {
+                    report.applied(entry.dotted_key(), format!("rolled back: {}", failure.meaning));
+                }
+            }
+            transaction.stage(doc.to_string());
+            if transaction.is_changed() {
+                if self.config.backup.auto_backup {
+                    self.backup_transaction(&transaction, "before linker rollback")?;
+                }
+                self.commit_audited(transaction, "rollback-linker")?;
+            }
+        }
+        journal.created_tables.retain(|path| !linker_journal.created_tables.contains(path));
+        state.journal = (!journal.is_empty() || journal.created_file).then_some(journal);
+        
+        if !state.is_incompatible(&linker) {
+            state.incompatible_linkers.push(LinkerIncompatibility {
+                linker: linker.clone(),
+                reason: failure.line.clone(),
+                recorded_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            });
+        }
+        state.commit()?;
+        report.recommend(format!(
+            "{} broke the build ({}); it will not be configured again for this project. Remove it from \
+             `incompatible_linkers` in {} to try it again",
+            linker,
+            failure.line,
+            self.state_path().display()
+        ));
+        info!("Rolled back linker {} in {:?}", linker, self.config_path);
+        Ok(report)
+    }
+    
+    /// Path of the state file kept next to the config
+    pub fn state_path(&self) -> PathBuf {
+        State::path_in(self.config_path.parent().unwrap_or_else(|| Path::new(".cargo")))
+    }
+    
+    /// Advisor hiding the suggestions declined for this project
+    pub fn advisor(&self) -> Advisor {
+        Advisor::new().with_rejections(self.rejections())
+    }
+    
+    /// Suggestions declined for this project
+    pub fn rejections(&self) -> Vec<Rejection> {
+        State::load(&self.state_path()).rejections
+    }
+    
+    /// Stop repeating `advice`, recording why
+    ///
+    /// The advice comes back once the gain it quotes grows significantly
+    /// (see [`Rejection::covers`]). Rejecting it again replaces the earlier
+    /// record. Fails for advice without a key, which cannot be told apart
+    /// across runs.
+    pub fn reject_advice(&self, advice: &Advice, reason: impl Into<String>) -> Result<Rejection> {
+        let key = advice.key.clone().context("This advice cannot be rejected: it has no key")?;
+        let rejection = Rejection {
+            key,
+            reason: reason.into(),
+            measure: advice.measure,
+            rejected_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        };
+        State::update(&self.state_path(), |state| {
+            state.rejections.retain(|existing| existing.key != rejection.key);
+            state.rejections.push(rejection.clone());
+        })?;
+        Ok(rejection)
+    }
+    
+    /// Forget the rejection of `key`, or of every suggestion with `None`
+    ///
+    /// Returns the rejections removed.
+    pub fn clear_rejections(&self, key: Option<&str>) -> Result<Vec<Rejection>> {
+        State::update(&self.state_path(), |state| {
+            let (cleared, kept) = state
+                .rejections
+                .drain(..)
+                .partition(|rejection| key.is_none_or(|key| rejection.key == key));
+            state.rejections = kept;
+            cleared
+        })
+    }
+    
+    /// Apply queued by an earlier run that found a build in progress
+    pub fn pending_apply(&self) -> Option<PendingApply> {
+        State::load(&self.state_path()).pending_apply
+    }
+    
+    /// Check the components applied settings need, installing them if allowed
+    ///
+    /// Missing components are installed when `global.allow_installs` is set;
+    /// otherwise the exact `rustup component add` command is recommended.
+    fn resolve_components(&self, report: &mut OptimizationReport) {
+        if report.required_components.is_empty() {
+            return;
+        }
+        
+        let installed = match self.installed_components.clone() {
+            Some(installed) => installed,
+            None => match toolchain::installed_components() {
+                Ok(installed) => installed,
+                Err(e) => {
+                    debug!("Could not list rustup components: {:#}", e);
+                    Vec::new()
+                }
+            },
+        };
+        
+        let required = report.required_components.clone();
+        let mut advisor = self.advisor();
+        for requirement in advisor.check_components(&required, &installed) {
+            let setting = format!("component.{}", requirement.component);
+            if self.config.global.allow_installs {
+                match toolchain::install_component(&requirement.component, &Proxy::from_env(&self.config.global.proxy)) {
+                    Ok(()) => {
+                        report.applied(setting, format!("installed for {}", requirement.needed_for));
+                        continue;
+                    }
+                    Err(e) => warn!("Failed to install {}: {:#}", requirement.component, e),
+                }
+            }
+            report.recommend(advisor::component_advice(&requirement).to_string());
+        }
+    }
+    
+    /// Read the config once and stage the planned content without writing it
+    ///
+    /// Committing the returned transaction performs the single atomic write.
+    pub fn prepare_with_hardware(&self, hardware: &HardwareInfo) -> Result<(ConfigTransaction, OptimizationReport)> {
+        self.prepare_in(ConfigTransaction::begin(&self.config_path)?, hardware)
+    }
+    
+    /// Plan from the config `transaction` read and stage the result in it
+    fn prepare_in(
+        &self,
+        mut transaction: ConfigTransaction,
+        hardware: &HardwareInfo,
+    ) -> Result<(ConfigTransaction, OptimizationReport)> {
+        let (doc, report) = self.plan_from(transaction.original(), hardware)?;
+        
+        // Always write the config file, even if it's minimal
+        // This ensures the file exists after apply() is called
+        let content = doc.to_string();
+        // If document is empty, at least add a comment
+        let final_content = if content.trim().is_empty() {
+            "# Cargo configuration managed by cargo-optimize\n".to_string()
+        } else {
+            content
+        };
+        transaction.stage(final_content);
+        
+        Ok((transaction, report))
+    }
+    
+    /// Compute the document `apply()` would write, without touching disk
+    pub fn plan(&self) -> Result<(DocumentMut, OptimizationReport)> {
+        self.plan_with_hardware(&HardwareInfo::detect())
+    }
+    
+    /// Compute the planned document using the given hardware information
+    pub fn plan_with_hardware(&self, hardware: &HardwareInfo) -> Result<(DocumentMut, OptimizationReport)> {
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        self.plan_from(transaction.original(), hardware)
+    }
+    
+    /// Check whether `.cargo/config.toml` is what `apply()` would write
+    pub fn verify(&self) -> Result<Verification> {
+        self.verify_with_hardware(&HardwareInfo::detect())
+    }
+    
+    /// Check the config against the plan for the given hardware, without touching disk
+    ///
+    /// Only values count: comments and formatting may differ.
+    pub fn verify_with_hardware(&self, hardware: &HardwareInfo) -> Result<Verification> {
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        let (doc, report) = self.plan_from(transaction.original(), hardware)?;
+        let journal = ApplyJournal::record(transaction.original(), &doc.to_string())?;
+        Ok(Verification {
+            drifted: journal.entries.iter().map(JournalEntry::dotted_key).collect(),
+            report,
+        })
+    }
+    
+    /// Compute the planned document from the current config content
+    fn plan_from(&self, current: Option<&str>, hardware: &HardwareInfo) -> Result<(DocumentMut, OptimizationReport)> {
+        let mut report = OptimizationReport::new();
+        let project_root = self.project_root();
+        
+        // Without a capability descriptor nothing is known to be safe
+        let Some(platform) = self.platform else {
+            report.skipped(
+                "platform",
+                SkipReason::UnsupportedPlatform,
+                format!("no optimizations available for this platform ({})", std::env::consts::OS),
+            );
+            let doc = current.and_then(|content| content.parse::<DocumentMut>().ok()).unwrap_or_default();
+            return Ok((doc, report));
+        };
+        
+        // Load or create the document
+        let mut doc = current
+            .and_then(|content| content.parse::<DocumentMut>().ok())
+            .unwrap_or_default();
+        let original = doc.clone();
+        
+        // Other build tooling the plan has to work around
+        let tools = self
+            .tool_environment
+            .clone()
+            .unwrap_or_else(|| ToolEnvironment::detect(&project_root, &self.config_path));
+        let conflicts = conflicts::evaluate(&tools, &original);
+        for conflict in &conflicts {
+            report.tool_conflict(conflict.tool, conflict.message.clone());
+            if let Some(advice) = &conflict.advice {
+                report.recommend(advice.clone());
+            }
+        }
+        let adjusted = |adjustment| conflicts.iter().any(|conflict| conflict.adjustment == Some(adjustment));
+        
+        // Dockerfiles building the project without reusing cargo's caches
+        for analysis in container::find_dockerfiles(&project_root) {
+            if analysis.findings.is_empty() {
+                continue;
+            }
+            let mut advisor = self.advisor();
+            advisor.advise_dockerfile(&analysis);
+            report.note_rejected(&advisor);
+            if advisor.is_empty() {
+                continue;
+            }
+            report.advice.extend(advisor.advice().iter().cloned());
+            report.container_build(
+                analysis.path.clone(),
+                advisor.advice().iter().map(ToString::to_string).collect(),
+                container::recommended_snippet(&container::binary_name(&project_root), &analysis.workdir),
+            );
+        }
+        
+        let low_disk = hardware.low_disk(&project_root, self.config.global.min_free_disk_gb);
+        let mut effective = match &low_disk {
+            Some(condition) => {
+                warn!("{}", condition);
+                self.degrade_for_low_disk(condition, &mut report)
+            }
+            None => self.config.clone(),
+        };
+        
+        if adjusted(Adjustment::SharedTargetDir) && effective.global.target_dir_layout != TargetDirLayout::Shared {
+            effective.global.target_dir_layout = TargetDirLayout::Shared;
+            report.skipped("target-dir layout", SkipReason::Conflict, "cargo-chef caches dependencies in the shared target directory");
+        }
+        
+        // A configured template always wins over the detected one
+        let template = match (effective.global.template, self.detected_template) {
+            (Some(template), _) => Some((template, "configured".to_string())),
+            (None, Some(template)) => Some((template, "detected from project analysis".to_string())),
+            (None, None) => analysis::embedded_signal(&project_root)
+                .map(|signal| (ProjectTemplate::Embedded, format!("detected: {}", signal))),
+        };
+        if let Some((template, source)) = template {
+            template.apply_to(&mut effective);
+            effective.global.template = Some(template);
+            report.applied("template", format!("{} ({})", template, source));
+        }
+        
+        // The level's preset fills in whatever the user and the template left open
+        let mut preset = *effective.global.optimization_level.preset();
+        if is_embedded(&effective) && preset.native_cpu {
+            preset.native_cpu = false;
+            report.skipped("target-cpu=native", SkipReason::UnsupportedPlatform, EMBEDDED_REASON);
+        }
+        preset.apply_to(&mut effective);
+        if !preset.sccache && effective.global.use_sccache {
+            effective.global.use_sccache = false;
+            report.skipped("sccache", SkipReason::UserOptOut, format!("not used at the {} optimization level", preset.level));
+        }
+        if effective.global.no_external_tools {
+            restrict_to_toolchain(&mut effective, &mut report);
+        }
+        
+        // Apply our optimizations while preserving existing content
+        self.apply_to_document(&mut doc, &effective, platform, &mut report)?;
+        self.apply_unstable_to_document(&mut doc, &effective, &mut report)?;
+        self.apply_profile_rustflags_to_document(&mut doc, &effective, &mut report)?;
+        self.apply_cargo_features_to_document(&mut doc, &effective, &mut report)?;
+        self.apply_target_dir_layout_to_document(&mut doc, &effective, &mut report);
+        if platform == Platform::Windows {
+            self.apply_dev_drive(&mut doc, &effective, hardware, &mut report);
+        }
+        report.effective_target_dir = Some(effective_target_dir(&project_root, self.target_dir_env.as_deref(), &doc));
+        
+        if effective.global.respect_msrv {
+            let msrv = self.msrv.or_else(|| match toolchain::detect_msrv(&project_root) {
+                Ok(msrv) => msrv,
+                Err(e) => {
+                    warn!("Could not read rust-version: {:#}", e);
+                    None
+                }
+            });
+            match msrv {
+                Some(msrv) => enforce_msrv(&original, &mut doc, msrv, &mut report),
+                None => debug!("respect_msrv is set but the project declares no rust-version"),
+            }
+        }
+        
+        // A wrapper set in the environment is what cargo runs, whatever the config says
+        if !adjusted(Adjustment::KeepWrapper) {
+            self.apply_sccache_dist(&mut doc, &effective, &mut report);
+            self.apply_sccache_heuristics(&mut doc, &effective, &mut report);
+        }
+        self.apply_sccache_backend(&mut doc, &effective, &mut report);
+        
+        self.apply_adaptive_jobs(&mut doc, &effective, hardware, &mut report);
+        self.apply_memory_pressure(&mut doc, &effective, hardware, &mut report);
+        self.report_io_profile(&doc, &project_root, platform, hardware, &mut report);
+        self.report_branch_switches(&doc, &project_root, hardware, &mut report);
+        providers::plan(&self.providers, &project_root, Some(platform), Some(hardware), &original, &mut doc, &mut report);
+        
+        // Job counts and pinning for NUMA and hybrid CPUs, with Linux tools
+        if platform == Platform::Linux {
+            let mut advisor = self.advisor();
+            advisor.advise_topology(hardware);
+            report.advise(&advisor);
+        }
+        
+        // Store paths of a Nix shell are gone after the next update
+        if adjusted(Adjustment::ToolsByName) {
+            unpin_store_paths(&original, &mut doc, &mut report);
+        }
+        
+        // Values the team committed by hand win over everything but the machine policy
+        if adjusted(Adjustment::KeepHandSetValues) {
+            keep_hand_set_values(&original, &mut doc, &mut report);
+        }
+        enforce_deny_list(&original, &mut doc, &effective.deny, &mut report)?;
+        
+        // The machine policy has the last word
+        self.apply_policy_to_document(&original, &mut doc, &mut report)?;
+        
+        Ok((doc, report))
+    }
+    
+    /// Write per-package profile overrides in a single transaction
+    pub fn apply_package_overrides(&self, overrides: &[PackageOverride]) -> Result<OptimizationReport> {
+        if let Some(report) = disabled_report("package overrides") {
+            return Ok(report);
+        }
+        let mut report = OptimizationReport::new();
+        let mut transaction = ConfigTransaction::begin(&self.config_path)?;
+        let mut doc = match transaction.original() {
+            Some(content) => content.parse::<DocumentMut>()
+                .context("Failed to parse existing config")?,
+            None => DocumentMut::new(),
+        };
+        
+        for override_ in overrides {
+            apply_package_override(&mut doc, override_)?;
+            let settings: Vec<String> = override_
+                .settings
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, value))
+                .collect();
+            report.applied(
+                format!("profile.{}.package.{}", override_.profile, override_.package),
+                settings.join(", "),
+            );
+        }
+        
+        if self.config.backup.auto_backup && transaction.original().is_some() {
+            self.backup_transaction(&transaction, "before package overrides")?;
+        }
+        transaction.stage(doc.to_string());
+        let journal = self.journal_for(&transaction)?;
+        self.commit_audited(transaction, "package-overrides")?;
+        self.record_journal(journal);
+        Ok(report)
+    }
+    
+    /// Plan the providers into the config as it is and write it
+    ///
+    /// The build script setup configures the linker without the full
+    /// planner, so everything already in the config counts as built-in.
+    /// The deny list and the machine policy still constrain the providers.
+    pub fn apply_providers(&self) -> Result<OptimizationReport> {
+        if let Some(report) = disabled_report("providers") {
+            return Ok(report);
+        }
+        let mut report = OptimizationReport::new();
+        if self.providers.is_empty() {
+            return Ok(report);
+        }
+        let project_root = self.project_root();
+        let mut transaction = ConfigTransaction::begin(&self.config_path)?;
+        let original = match transaction.original() {
+            Some(content) => content.parse::<DocumentMut>()
+                .context("Failed to parse existing config")?,
+            None => DocumentMut::new(),
+        };
+        let mut doc = original.clone();
+        providers::plan(&self.providers, &project_root, self.platform, None, &original, &mut doc, &mut report);
+        enforce_deny_list(&original, &mut doc, &self.config.deny, &mut report)?;
+        self.apply_policy_to_document(&original, &mut doc, &mut report)?;
+        
+        transaction.stage(doc.to_string());
+        if transaction.is_changed() {
+            if let Some(dir) = self.config_path.parent() {
+                fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+            }
+            let journal = self.journal_for(&transaction)?;
+            self.commit_audited(transaction, "providers")?;
+            self.record_journal(journal);
+        }
+        
+        let context = ProviderContext { project_root: &project_root, platform: self.platform, hardware: None, config: &doc };
+        providers::apply(&self.providers, &context, &mut report);
+        Ok(report)
+    }
+    
+    /// Commit a transaction and record the change in the audit log
+    fn commit_audited(&self, transaction: ConfigTransaction, action: &str) -> Result<bool> {
+        let path = transaction.path().to_path_buf();
+        let original = transaction.original().map(str::to_string);
+        let staged = transaction.staged().map(str::to_string);
+        let committed = transaction.commit()?;
+        if committed {
+            audit::record(&self.project_root(), action, &path, original.as_deref(), staged.as_deref());
+        }
+        Ok(committed)
+    }
+    
+    /// Changes a transaction is about to make, for the uninstall journal
+    fn journal_for(&self, transaction: &ConfigTransaction) -> Result<Option<ApplyJournal>> {
+        match transaction.staged() {
+            Some(staged) => Ok(Some(ApplyJournal::record(transaction.original(), staged)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Add committed changes to the journal in the state file
+    fn record_journal(&self, journal: Option<ApplyJournal>) {
+        let Some(journal) = journal else { return };
+        let recorded = State::update(&self.state_path(), |state| {
+            state.record_apply(journal);
+            state.applied_version = Some(env!("CARGO_PKG_VERSION").to_string());
+        });
+        if let Err(e) = recorded {
+            warn!("Failed to record applied changes for uninstall: {:#}", e);
+        }
+    }
+    
+    /// Undo every change cargo-optimize made to the config
+    ///
+    /// Settings are restored to their values before the first apply and the
+    /// config file is removed if cargo-optimize created it and nothing else
+    /// was added since. Settings edited by the user after they were written
+    /// are kept and listed as skipped in the report.
+    pub fn uninstall(&self) -> Result<OptimizationReport> {
+        if let Some(report) = disabled_report("uninstall") {
+            return Ok(report);
+        }
+        let mut report = OptimizationReport::new();
+        let mut state = State::lock(&self.state_path())?;
+        let Some(journal) = state.journal.take() else {
+            report.skipped("uninstall", SkipReason::NotNeeded, "no changes recorded by cargo-optimize");
+            return Ok(report);
+        };
+        
+        let mut transaction = ConfigTransaction::begin(&self.config_path)?;
+        let mut written = DocumentMut::new();
+        if let Some(content) = transaction.original() {
+            let mut doc = content.parse::<DocumentMut>()
+                .context("Failed to parse existing config")?;
+            let kept = journal.revert(&mut doc);
+            if let Some(shared) = state.shared_rustflags.take() {
+                remove_shared_rustflags(&mut doc, &shared);
+            }
+            for entry in &kept {
+                report.skipped(entry.dotted_key(), SkipReason::UserOptOut, "edited after cargo-optimize wrote it");
+            }
+            report.applied(
+                "uninstall",
+                format!("restored {} setting(s)", journal.entries.len() - kept.len()),
+            );
+            
+            if journal.created_file && doc.as_table().is_empty() {
+                fs::remove_file(&self.config_path)
+                    .with_context(|| format!("Failed to remove {:?}", self.config_path))?;
+                audit::record(&self.project_root(), "uninstall", &self.config_path, transaction.original(), None);
+            } else {
+                transaction.stage(doc.to_string());
+                written = doc;
+                if transaction.is_changed() {
+                    self.commit_audited(transaction, "uninstall")?;
+                }
+            }
+        }
+        
+        state.commit()?;
+        if !self.providers.is_empty() {
+            let project_root = self.project_root();
+            let context = ProviderContext { project_root: &project_root, platform: self.platform, hardware: None, config: &written };
+            providers::revert(&self.providers, &context, &mut report);
+        }
+        info!("Uninstalled cargo-optimize changes from {:?}", self.config_path);
+        Ok(report)
+    }
+    
+    /// Remove every trace of cargo-optimize from the project, or list it (`dry_run`)
+    ///
+    /// Uninstalls (see [`Self::uninstall`]), removes the `cargo optimize`
+    /// aliases and the git hook drift check, then removes the files listed by
+    /// [`Self::purge_artifacts`]. A dry run changes nothing.
+    pub fn purge(&self, dry_run: bool) -> Result<PurgeSummary> {
+        output::ensure_enabled()?;
+        let mut artifacts = self.purge_artifacts();
+        if dry_run {
+            return Ok(PurgeSummary { dry_run, artifacts, ..PurgeSummary::default() });
+        }
+        
+        // Directories cargo-optimize created, removed once nothing else is in them
+        let project_root = self.project_root();
+        let mut prune = vec![self.backup_dir(), project_root.join(proposal::PROPOSAL_DIR)];
+        prune.extend(Path::new(audit::AUDIT_LOG).parent().map(|dir| project_root.join(dir)));
+        prune.extend(self.config_path.parent().map(Path::to_path_buf));
+        let linker_install = &self.config.global.linker_install;
+        if let Some(dir) = tools::tools_dir(linker_install) {
+            // A configured directory may be the user's own; only the default one's parent is ours
+            if linker_install.dir.is_none() {
+                prune.extend(dir.parent().map(Path::to_path_buf));
+            }
+            prune.insert(0, dir);
+        }
+        
+        let mut summary = PurgeSummary { uninstall: self.uninstall()?, ..PurgeSummary::default() };
+        if adoption::uninstall_alias(&project_root)? && !artifacts.iter().any(|artifact| artifact.kind == ArtifactKind::Settings) {
+            artifacts.extend(Artifact::at(ArtifactKind::Settings, &self.config_path));
+        }
+        if artifacts.iter().any(|artifact| artifact.kind == ArtifactKind::GitHook) {
+            hooks::uninstall_git_hooks(&project_root)?;
+        }
+        
+        // Uninstalling writes the audit log and state too; the target directory is the one listed first
+        for artifact in self.purge_artifacts() {
+            if !artifact.kind.is_edit() && !artifacts.iter().any(|listed| listed.path == artifact.path) {
+                artifacts.push(artifact);
+            }
+        }
+        purge::remove(artifacts, &prune, &mut summary);
+        info!("Purged cargo-optimize from {:?}", project_root);
+        Ok(summary)
+    }
+    
+    /// What [`Self::purge`] removes: the settings and hooks cargo-optimize wrote and the files it keeps
+    pub fn purge_artifacts(&self) -> Vec<Artifact> {
+        let project_root = self.project_root();
+        let cargo_dir = self.config_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut artifacts = Vec::new();
+        if State::load(&self.state_path()).journal.is_some() {
+            artifacts.extend(Artifact::at(ArtifactKind::Settings, &self.config_path));
+        }
+        if let Ok(dir) = hooks::hooks_dir(&project_root) {
+            for hook in hooks::HOOK_NAMES {
+                let path = dir.join(hook);
+                if fs::read_to_string(&path).is_ok_and(|content| content.contains(hooks::BLOCK_BEGIN)) {
+                    artifacts.extend(Artifact::at(ArtifactKind::GitHook, &path));
+                }
+            }
+        }
+        artifacts.extend(purge::stored(ArtifactKind::State, &self.state_path()));
+        artifacts.extend(purge::stored(ArtifactKind::Metrics, &MetricsHistory::path_in(&cargo_dir)));
+        for backup in self.list_backups().unwrap_or_default() {
+            artifacts.extend(Artifact::at(ArtifactKind::Backups, &backup.path));
+            artifacts.extend(Artifact::at(ArtifactKind::Backups, &backup_metadata_path(&backup.path)));
+        }
+        artifacts.extend(Artifact::at(ArtifactKind::AnalysisCache, &self.analysis_cache_dir()));
+        artifacts.extend(Artifact::at(ArtifactKind::Proposal, &project_root.join(proposal::PROPOSAL_DIR)));
+        artifacts.extend(Artifact::at(ArtifactKind::AuditLog, &project_root.join(audit::AUDIT_LOG)));
+        if let Some(dir) = tools::tools_dir(&self.config.global.linker_install) {
+            for release in tools::unpacked_releases(&dir) {
+                artifacts.extend(Artifact::at(ArtifactKind::ToolDownloads, &release));
+            }
+        }
+        artifacts
+    }
+    
+    /// Directory of the metadata cache and build-weight measurements in the target directory
+    fn analysis_cache_dir(&self) -> PathBuf {
+        let env_dir = std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from);
+        let doc = ConfigTransaction::begin(&self.config_path).map(|transaction| transaction.document()).unwrap_or_default();
+        let target_dir = effective_target_dir(&self.project_root(), env_dir.as_deref(), &doc).path;
+        let cache = target_dir.join(analysis::quick::METADATA_CACHE);
+        cache.parent().map(Path::to_path_buf).unwrap_or(target_dir)
+    }
+    
+    /// Check the planned configuration against a set of toolchains
+    ///
+    /// See [`validate_against_toolchains`] for what is checked.
+    pub fn validate_against_toolchains(&self, toolchains: &[ToolchainSpec]) -> Result<Vec<ToolchainCompatibility>> {
+        let (doc, _) = self.plan()?;
+        Ok(validate_against_toolchains(&doc, toolchains))
+    }
+    
+    /// The planned changes as cargo `--config` flags, for builds that must not write files
+    ///
+    /// See [`cli_flags::to_cli_flags`] for what cannot be expressed as a flag.
+    pub fn cli_flags(&self) -> Result<CliFlags> {
+        self.cli_flags_with_hardware(&HardwareInfo::detect())
+    }
+    
+    /// The planned changes as cargo `--config` flags, using the given hardware information
+    pub fn cli_flags_with_hardware(&self, hardware: &HardwareInfo) -> Result<CliFlags> {
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        let (planned, _) = self.plan_from(transaction.original(), hardware)?;
+        cli_flags::to_cli_flags(transaction.original(), &planned)
+    }
+    
+    /// The planned changes as settings of a Nix dev shell, for projects that keep their build setup in Nix
+    ///
+    /// Nothing is written. Without a `format`, devenv is used when the
+    /// project or shell uses it and a flake's `mkShell` otherwise. See
+    /// [`nix::to_nix_snippet`] for what cannot be expressed as a variable.
+    pub fn nix_snippet(&self, format: Option<NixFormat>) -> Result<NixSnippet> {
+        self.nix_snippet_with_hardware(format, &HardwareInfo::detect())
+    }
+    
+    /// The planned changes as settings of a Nix dev shell, using the given hardware information
+    pub fn nix_snippet_with_hardware(&self, format: Option<NixFormat>, hardware: &HardwareInfo) -> Result<NixSnippet> {
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        let (planned, _) = self.plan_from(transaction.original(), hardware)?;
+        let format = format.unwrap_or_else(|| {
+            let project_root = self.project_root();
+            match self.nix_environment() {
+                Some(environment) => environment.format_for(&project_root),
+                None if project_root.join("devenv.nix").is_file() => NixFormat::Devenv,
+                None => NixFormat::Flake,
+            }
+        });
+        nix::to_nix_snippet(transaction.original(), &planned, format)
+    }
+    
+    /// The Nix environment planning runs in, from the tool environment when one was given
+    fn nix_environment(&self) -> Option<NixEnvironment> {
+        match &self.tool_environment {
+            Some(tools) => tools.nix.clone(),
+            None => NixEnvironment::detect(),
+        }
+    }
+    
+    /// List what the next apply would change, for reviewing a cargo-optimize upgrade
+    ///
+    /// See [`migration::preview`]; nothing is written.
+    pub fn preview_upgrade(&self, hardware: &HardwareInfo) -> Result<UpgradePreview> {
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        let (planned, _) = self.plan_from(transaction.original(), hardware)?;
+        let state = State::load(&self.state_path());
+        migration::preview(transaction.original(), &planned, state.journal.as_ref(), state.applied_version)
+    }
+    
+    /// Plan the profile settings as `[profile.*]` edits of the project's `Cargo.toml`
+    ///
+    /// Nothing is written: show [`ProfileEdit::diff`] as the dry run and call
+    /// [`ProfileEdit::save`] to write it, which aborts if anything outside the
+    /// edited profile tables would change.
+    ///
+    /// Only the profile keys the plan changes are copied, without their
+    /// comments. `rustflags` is left out: it is nightly-only in a manifest.
+    pub fn plan_manifest_profiles(&self, hardware: &HardwareInfo) -> Result<ProfileEdit> {
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        let (doc, _) = self.plan_from(transaction.original(), hardware)?;
+        let journal = ApplyJournal::record(transaction.original(), &doc.to_string())?;
+        
+        let mut profiles = Table::new();
+        profiles.set_implicit(true);
+        for entry in &journal.entries {
+            let Some((first, path)) = entry.key.split_first() else { continue };
+            if first != "profile" || entry.written.is_none() || entry.key.iter().any(|key| key == "rustflags") {
+                continue;
+            }
+            let planned = path.iter().try_fold(&doc["profile"], |item, key| item.get(key));
+            let Some(value) = planned.and_then(Item::as_value) else { continue };
+            let mut value = value.clone();
+            value.decor_mut().clear();
+            
+            let (key, tables) = path.split_last().context("profile key without a name")?;
+            let mut table = &mut profiles;
+            for name in tables {
+                table = table
+                    .entry(name)
+                    .or_insert_with(|| Item::Table(Table::new()))
+                    .as_table_mut()
+                    .with_context(|| format!("`profile.{}` is not a table", name))?;
+            }
+            table.insert(key, Item::Value(value));
+        }
+        
+        let mut edit = ProfileEdit::open(self.project_root().join("Cargo.toml"))?;
+        if !profiles.is_empty() {
+            edit.merge_profiles(&profiles)?;
+        }
+        Ok(edit)
+    }
+    
+    /// Project root directory (the parent of `.cargo/`)
+    pub(crate) fn project_root(&self) -> PathBuf {
+        self.config_path
+            .parent()
+            .and_then(|cargo_dir| cargo_dir.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+    
+    /// Apply the configured target-dir layout and measure its disk overhead
+    ///
+    /// Per-profile dirs are written as aliases into `doc`; per-group dirs go
+    /// to the members' own configs, which are only written on apply.
+    fn apply_target_dir_layout_to_document(&self, doc: &mut DocumentMut, config: &Config, report: &mut OptimizationReport) {
+        let project_root = self.project_root();
+        let target_dir = effective_target_dir(&project_root, self.target_dir_env.as_deref(), doc).path;
+        
+        if let Some(env_dir) = &self.target_dir_env {
+            self.reconcile_target_dir_env(env_dir, doc, config, report);
+            if config.global.target_dir_layout != TargetDirLayout::Shared
+                && config.global.target_dir_env_policy == TargetDirEnvPolicy::Skip
+            {
+                report.skipped(
+                    "target-dir layout",
+                    SkipReason::Context,
+                    format!("CARGO_TARGET_DIR is set ({}); policy is skip", env_dir.display()),
+                );
+                return;
+            }
+        }
+        
+        match config.global.target_dir_layout {
+            TargetDirLayout::Shared => {}
+            TargetDirLayout::PerProfile => {
+                let mut names: Vec<&String> = config.profiles.keys().collect();
+                names.sort();
+                
+                let mut dirs = Vec::new();
+                let mut targets = Vec::new();
+                for name in &names {
+                    let dir = config.profiles[*name]
+                        .target_dir
+                        .clone()
+                        .unwrap_or_else(|| target_dir.join(name));
+                    // Aliases are resolved against the working directory
+                    let dir = std::path::absolute(&dir).unwrap_or(dir);
+                    let (alias, command) = profile_alias(name);
+                    let setting = format!("alias.{}", alias);
+                    
+                    let mut args = toml_edit::Array::new();
+                    for arg in [command, "--profile", name.as_str(), "--target-dir"] {
+                        args.push(arg);
+                    }
+                    args.push(dir.to_string_lossy().as_ref());
+                    
+                    let aliases = doc
+                        .entry("alias")
+                        .or_insert_with(|| Item::Table(Table::new()));
+                    let Some(aliases) = aliases.as_table_like_mut() else {
+                        report.skipped(setting, SkipReason::Conflict, "[alias] is not a table");
+                        continue;
+                    };
+                    let ours = aliases.get(&alias).is_none_or(|existing| {
+                        existing.as_array().and_then(|a| a.get(1)).and_then(|v| v.as_str()) == Some("--profile")
+                    });
+                    if !ours {
+                        report.skipped(setting, SkipReason::UserOptOut, "alias already defined by the user");
+                        continue;
+                    }
+                    aliases.insert(&alias, explained(args, "alias.*"));
+                    report.applied(setting, format!("cargo {} builds into {}", alias, dir.display()));
+                    targets.push((dir.clone(), Some(profile_output_dir(name))));
+                    dirs.push(dir);
+                }
+                report.relocations = plan_relocations(&target_dir, &targets, config.global.target_dir_migration);
+                
+                // Profiles that shared an output dir now each build their
+                // dependencies separately (e.g. dev and test in target/debug)
+                let mut sharing: BTreeMap<&str, usize> = BTreeMap::new();
+                for name in &names {
+                    *sharing.entry(profile_output_dir(name)).or_default() += 1;
+                }
+                let estimated_extra_bytes = sharing
+                    .iter()
+                    .map(|(dir, count)| hardware::directory_size(&target_dir.join(dir)) * (*count as u64 - 1))
+                    .sum();
+                report.disk_tradeoffs.push(DiskTradeoff {
+                    layout: "per-profile target dirs".to_string(),
+                    current_bytes: hardware::directory_size(&target_dir),
+                    estimated_extra_bytes,
+                    target_dirs: dirs,
+                });
+            }
+            TargetDirLayout::PerGroup => {
+                let entries = self.group_target_dirs(config);
+                if entries.is_empty() {
+                    report.skipped("target-dir layout", SkipReason::Error, "per-group layout configured without member_groups");
+                    return;
+                }
+                if !self.writes_group_target_dirs(config) {
+                    report.skipped("target-dir layout", SkipReason::Conflict, "CARGO_TARGET_DIR overrides build.target-dir in member configs");
+                    return;
+                }
+                
+                let mut dirs: Vec<PathBuf> = Vec::new();
+                for entry in &entries {
+                    report.applied(
+                        format!("{}: build.target-dir", entry.config_path.display()),
+                        format!("{} (group {})", entry.target_dir, entry.group),
+                    );
+                    let dir = target_dir.join(&entry.group);
+                    if !dirs.contains(&dir) {
+                        dirs.push(dir);
+                    }
+                }
+                let targets: Vec<(PathBuf, Option<&str>)> = dirs.iter().map(|dir| (dir.clone(), None)).collect();
+                report.relocations = plan_relocations(&target_dir, &targets, config.global.target_dir_migration);
+                
+                // Worst case every group compiles the full dependency graph again
+                let current_bytes = hardware::directory_size(&target_dir);
+                report.disk_tradeoffs.push(DiskTradeoff {
+                    layout: "per-group target dirs".to_string(),
+                    current_bytes,
+                    estimated_extra_bytes: current_bytes * dirs.len() as u64,
+                    target_dirs: dirs,
+                });
+            }
+        }
+    }
+    
+    /// Whether the per-group layout's member configs get written
+    ///
+    /// Unless the policy is to warn, they are not written while
+    /// `CARGO_TARGET_DIR` is set since cargo would ignore them.
+    fn writes_group_target_dirs(&self, config: &Config) -> bool {
+        config.global.target_dir_layout == TargetDirLayout::PerGroup
+            && (self.target_dir_env.is_none() || config.global.target_dir_env_policy == TargetDirEnvPolicy::Warn)
+    }
+    
+    /// Point out where `CARGO_TARGET_DIR` silently overrides the config
+    fn reconcile_target_dir_env(&self, env_dir: &Path, doc: &DocumentMut, config: &Config, report: &mut OptimizationReport) {
+        let env_dir = std::path::absolute(env_dir).unwrap_or_else(|_| env_dir.to_path_buf());
+        let configured = doc
+            .get("build")
+            .and_then(|build| build.get("target-dir"))
+            .and_then(Item::as_str)
+            .map(|dir| self.project_root().join(dir));
+        if let Some(configured) = configured.filter(|dir| *dir != env_dir) {
+            debug!("CARGO_TARGET_DIR {:?} overrides build.target-dir {:?}", env_dir, configured);
+            if config.global.target_dir_env_policy == TargetDirEnvPolicy::Warn {
+                report.recommend(format!(
+                    "CARGO_TARGET_DIR ({}) overrides build.target-dir ({}); unset one of them",
+                    env_dir.display(),
+                    configured.display()
+                ));
+            }
+        }
+        if config.global.target_dir_layout == TargetDirLayout::PerGroup
+            && config.global.target_dir_env_policy == TargetDirEnvPolicy::Warn
+        {
+            report.recommend(format!(
+                "CARGO_TARGET_DIR ({}) overrides the per-group build.target-dir; unset it to use group target dirs",
+                env_dir.display()
+            ));
+        }
+    }
+    
+    /// Member configs to write for the per-group layout
+    ///
+    /// Directories without a `Cargo.toml` are not workspace members and are
+    /// left out.
+    fn group_target_dirs(&self, config: &Config) -> Vec<GroupTargetDir> {
+        let project_root = self.project_root();
+        let mut entries = Vec::new();
+        for (group, members) in &config.global.member_groups {
+            for member in members {
+                let member_dir = project_root.join(member);
+                if !member_dir.join("Cargo.toml").is_file() {
+                    warn!("Member group {} lists {:?}, which has no Cargo.toml", group, member);
+                    continue;
+                }
+                entries.push(GroupTargetDir {
+                    group: group.clone(),
+                    member: member.clone(),
+                    config_path: member_dir.join(".cargo").join("config.toml"),
+                    target_dir: group_target_dir(member, group),
+                });
+            }
+        }
+        entries
+    }
+    
+    /// Stage every file an apply writes besides the main config
+    fn stage_extra_files(
+        &self,
+        hardware: &HardwareInfo,
+        written: &DocumentMut,
+        report: &mut OptimizationReport,
+    ) -> Result<Vec<ConfigTransaction>> {
+        let mut transactions = Vec::new();
+        if self.writes_group_target_dirs(&self.config) {
+            for entry in self.group_target_dirs(&self.config) {
+                transactions.push(self.stage_group_target_dir(&entry)?);
+            }
+        }
+        if self.config.global.configure_nextest {
+            if self.config.global.no_external_tools {
+                report.skipped(nextest::NEXTEST_CONFIG, SkipReason::UserOptOut, NO_EXTERNAL_TOOLS_REASON);
+            } else {
+                transactions.extend(self.stage_nextest_config(hardware, report)?);
+            }
+        }
+        transactions.extend(self.stage_ide_config(written, report)?);
+        Ok(transactions)
+    }
+    
+    /// Keep rust-analyzer's checks out of a project-wide sccache (see [`crate::ide`])
+    ///
+    /// Runs once the cargo config is staged, so the wrapper `written` ends up with decides.
+    fn stage_ide_config(&self, written: &DocumentMut, report: &mut OptimizationReport) -> Result<Option<ConfigTransaction>> {
+        let root = self.project_root();
+        let ides = ide::detect(&root);
+        let bypass = self.config.global.wrapper_scope == WrapperScope::Builds && runs_sccache(written);
+        if bypass && ides.contains(&Ide::RustRover) {
+            report.recommend(
+                "RustRover checks the project through sccache as well; add `RUSTC_WRAPPER=` (empty) to the \
+                 environment variables of its Cargo settings so editing does not churn the cache",
+            );
+        }
+        let path = ide::ra_config_path(&root);
+        if !ides.contains(&Ide::RustAnalyzer) && !path.is_file() {
+            return Ok(None);
+        }
+        let mut transaction = ConfigTransaction::begin(&path)?;
+        if !bypass && transaction.original().is_none() {
+            return Ok(None);
+        }
+        let (content, state) = ide::render_ra_config(transaction.original(), bypass)?;
+        const SETTING: &str = "rust-analyzer.toml: check.extraEnv.RUSTC_WRAPPER";
+        match state {
+            Bypass::Managed => report.applied(
+                SETTING,
+                format!("rust-analyzer checks bypass sccache; builds and tests keep it ({})", ide::IDE_ID),
+            ),
+            Bypass::User(value) => report.skipped(SETTING, SkipReason::UserOptOut, format!("set to `{}` by the user", value)),
+            Bypass::Absent if transaction.original() != Some(content.as_str()) => {
+                let reason = match self.config.global.wrapper_scope {
+                    WrapperScope::All => "removed: `wrapper_scope = \"all\"` runs checks through the wrapper",
+                    WrapperScope::Builds => "removed: sccache is no longer the rustc wrapper",
+                };
+                report.skipped(SETTING, SkipReason::UserOptOut, reason)
+            }
+            Bypass::Absent => {}
+        }
+        transaction.stage(content);
+        Ok(Some(transaction))
+    }
+    
+    /// Set `build.target-dir` in a group member's own config
+    fn stage_group_target_dir(&self, entry: &GroupTargetDir) -> Result<ConfigTransaction> {
+        let mut transaction = ConfigTransaction::begin(&entry.config_path)?;
+        let mut doc = match transaction.original() {
+            Some(content) => content
+                .parse::<DocumentMut>()
+                .with_context(|| format!("Failed to parse config of member {:?}", entry.member))?,
+            None => DocumentMut::new(),
+        };
+        let build = doc.entry("build").or_insert_with(|| Item::Table(Table::new()));
+        let Some(build) = build.as_table_like_mut() else {
+            anyhow::bail!("[build] in {:?} is not a table", entry.config_path);
+        };
+        build.insert("target-dir", explained(entry.target_dir.as_str(), "build.target-dir"));
+        transaction.stage(doc.to_string());
+        Ok(transaction)
+    }
+    
+    /// Stage the nextest settings for this machine and context, if nextest is installed
+    fn stage_nextest_config(&self, hardware: &HardwareInfo, report: &mut OptimizationReport) -> Result<Option<ConfigTransaction>> {
+        if !self.nextest_installed.unwrap_or_else(nextest::is_installed) {
+            debug!("cargo-nextest is not installed; leaving {} alone", nextest::NEXTEST_CONFIG);
+            return Ok(None);
+        }
+        
+        let root = self.project_root();
+        let settings = NextestSettings::for_environment(hardware, self.context);
+        let mut transaction = ConfigTransaction::begin(nextest::config_path(&root))?;
+        let content = nextest::render(transaction.original(), &settings)?;
+        transaction.stage(content);
+        report.applied(
+            format!("{}: profile.{}", nextest::NEXTEST_CONFIG, settings.profile),
+            format!("{} test threads, {} retries", settings.test_threads, settings.retries),
+        );
+        
+        let mut advisor = self.advisor();
+        advisor.advise_nextest(&settings, nextest::count_test_binaries(&root));
+        report.advise(&advisor);
+        Ok(Some(transaction))
+    }
+    
+    /// Stage the planned changes for review instead of applying them
+    ///
+    /// Every file an apply would write is rendered into
+    /// `.cargo-optimize/proposed/` under its path relative to the project
+    /// root, next to a `CHANGES.md` summary and a `proposal.json` manifest.
+    /// Live configs are not touched; an earlier proposal is replaced.
+    pub fn propose(&self) -> Result<OptimizationReport> {
+        self.propose_with_hardware(&HardwareInfo::detect())
+    }
+    
+    /// Stage the planned changes for review using the given hardware information
+    pub fn propose_with_hardware(&self, hardware: &HardwareInfo) -> Result<OptimizationReport> {
+        if let Some(report) = disabled_report("propose") {
+            return Ok(report);
+        }
+        let (transaction, mut report) = self.prepare_with_hardware(hardware)?;
+        let written = transaction.document();
+        let mut transactions = vec![transaction];
+        transactions.extend(self.stage_extra_files(hardware, &written, &mut report)?);
+        
+        let root = self.project_root();
+        let dir = Proposal::dir_in(&root);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove earlier proposal {:?}", dir))?;
+        }
+        
+        let mut proposal = Proposal::new();
+        for transaction in transactions.iter().filter(|transaction| transaction.is_changed()) {
+            let path = transaction.path();
+            let relative = path.strip_prefix(&root).unwrap_or(path).to_path_buf();
+            let staged = transaction.staged().unwrap_or_default();
+            let file = ProposedFile::new(relative, transaction.original(), staged)?;
+            atomic_write(&dir.join(&file.path), staged)?;
+            proposal.files.push(file);
+        }
+        proposal.save(&dir, &report)?;
+        
+        info!("Proposed changes to {} file(s) in {:?}", proposal.files.len(), dir);
+        report.recommend(format!(
+            "Review {} and accept the proposal to apply it",
+            dir.join(proposal::CHANGES_FILE).display()
+        ));
+        Ok(report)
+    }
+    
+    /// Apply the changes staged by [`propose`](Self::propose)
+    ///
+    /// Fails without writing anything if a live file changed since the
+    /// proposal was made. Files are written one by one with atomic renames;
+    /// if a write fails, the files already written are restored. The
+    /// proposal directory is removed afterwards.
+    pub fn accept(&self) -> Result<OptimizationReport> {
+        if let Some(report) = disabled_report("accept") {
+            return Ok(report);
+        }
+        let root = self.project_root();
+        if let Some(lock) = build_lock::active_build_locks(&root).first() {
+            anyhow::bail!("cargo build in progress (lock held on {}); accept once it has finished", lock.display());
+        }
+        
+        let dir = Proposal::dir_in(&root);
+        let proposal = Proposal::load(&dir)?;
+        let mut staged = Vec::new();
+        for file in &proposal.files {
+            let mut transaction = ConfigTransaction::begin(root.join(&file.path))?;
+            if !file.matches(transaction.original()) {
+                anyhow::bail!("{} changed since the proposal was made; propose again", file.path.display());
+            }
+            let content = fs::read_to_string(dir.join(&file.path))
+                .with_context(|| format!("Failed to read proposed {}", file.path.display()))?;
+            transaction.stage(content);
+            staged.push((file, transaction));
+        }
+        
+        let mut report = OptimizationReport::new();
+        let mut journal = None;
+        for (_, transaction) in &staged {
+            if transaction.path() == self.config_path {
+                if self.config.backup.auto_backup && transaction.original().is_some() {
+                    self.backup_transaction(transaction, "before accept")?;
+                }
+                journal = self.journal_for(transaction)?;
+            }
+        }
+        
+        let mut written: Vec<(PathBuf, Option<String>)> = Vec::new();
+        for (file, transaction) in staged {
+            let path = transaction.path().to_path_buf();
+            let original = transaction.original().map(str::to_string);
+            if let Err(e) = self.commit_audited(transaction, "accept-proposal") {
+                restore_files(&root, &written);
+                return Err(e.context(format!("Failed to accept proposed {}", file.path.display())));
+            }
+            written.push((path, original));
+            report.applied(
+                file.path.display().to_string(),
+                format!("accepted {} proposed change(s)", file.changes.len()),
+            );
+        }
+        self.record_journal(journal);
+        
+        fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove accepted proposal {:?}", dir))?;
+        info!("Accepted proposal from {:?}", dir);
+        Ok(report)
+    }
+    
+    /// Produce a copy of the configuration with disk-hungry settings disabled
+    fn degrade_for_low_disk(
+        &self,
+        condition: &hardware::LowDiskCondition,
+        report: &mut OptimizationReport,
+    ) -> Config {
+        let mut effective = self.config.clone();
+        let reason = condition.to_string();
+        
+        if effective.global.use_sccache {
+            effective.global.use_sccache = false;
+            report.skipped("sccache", SkipReason::Resources, reason.clone());
+        }
+        
+        if effective.global.target_dir_layout != TargetDirLayout::Shared {
+            effective.global.target_dir_layout = TargetDirLayout::Shared;
+            report.skipped("target-dir layout", SkipReason::Resources, reason.clone());
+        }
+        
+        let mut names: Vec<_> = effective.profiles.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            let profile = effective.profiles.get_mut(&name).expect("profile exists");
+            profile.cache.enabled = false;
+            if profile.incremental == Some(true) {
+                profile.incremental = Some(false);
+                report.skipped(format!("profile.{}.incremental", name), SkipReason::Resources, reason.clone());
+            }
+        }
+        
+        let project_root = self.project_root();
+        let sccache_dir = std::env::var_os("SCCACHE_DIR")
+            .map(PathBuf::from)
+            .or_else(default_sccache_dir);
+        let mut candidates = vec![
+            ("target directory", Some(project_root.join("target"))),
+            ("sccache directory", sccache_dir),
+            ("cargo-optimize backups", Some(self.backup_dir())),
+        ];
+        for (label, path) in candidates.drain(..) {
+            let Some(path) = path else { continue };
+            let size_bytes = hardware::directory_size(&path);
+            if size_bytes > 0 {
+                report.cleanup_candidates.push(CleanupCandidate {
+                    label: label.to_string(),
+                    path,
+                    size_bytes,
+                });
+            }
+        }
+        
+        report.recommend(format!(
+            "Free up disk space ({}); consider `cargo clean` or clearing the sccache directory, \
+             then re-run cargo-optimize to enable sccache and incremental compilation",
+            reason
+        ));
+        
+        effective
+    }
+    
+    /// Apply optimizations to a TOML document while preserving formatting
+    fn apply_to_document(
+        &self,
+        doc: &mut DocumentMut,
+        config: &Config,
+        platform: Platform,
+        report: &mut OptimizationReport,
+    ) -> Result<()> {
+        // Add header comment if document is empty
+        if doc.as_table().is_empty() {
+            doc.decor_mut().set_prefix(
+                "# Cargo configuration - optimized by cargo-optimize\n\
+                 # This file has been automatically optimized for better build performance\n\n"
+            );
+        }
+        
+        // Apply linker configuration where the platform has fast linkers
+        let capabilities = platform.capabilities();
+        let linkers = config.global.optimization_level.preset().linkers;
+        if is_embedded(config) {
+            report.skipped("linker", SkipReason::UnsupportedPlatform, EMBEDDED_REASON);
+        } else if config.global.no_external_tools {
+            if let Some(linker) = self.toolchain_linker(platform, report) {
+                self.apply_linker_to_document(doc, linker, platform, report)?;
+            }
+        } else {
+            let linker = capabilities
+                .linker_config
+                .then(|| self.detect_best_linker(platform, linkers, report))
+                .flatten();
+            match (linker, capabilities.note) {
+                (Some(linker), _) => self.apply_linker_to_document(doc, &linker, platform, report)?,
+                (None, Some(note)) => report.skipped("linker", SkipReason::UnsupportedPlatform, note),
+                (None, None) => {
+                    if capabilities.linker_config && report.decision_for("linker").is_none() {
+                        report.skipped("linker", SkipReason::MissingTool, "no fast linker is installed");
+                    }
+                    self.recommend_linker_install(platform, config, report)
+                }
+            }
+        }
+        
+        // Apply build configuration
+        self.apply_build_config_to_document(doc, config)?;
+        
+        // Apply profile configurations
+        self.apply_profiles_to_document(doc, config, report)?;
+        
+        Ok(())
+    }
+    
+    /// Recommend installing the preferred fast linker, when the config opts in
+    ///
+    /// In a Nix environment the linker is added to the dev shell instead.
+    fn recommend_linker_install(&self, platform: Platform, config: &Config, report: &mut OptimizationReport) {
+        if config.global.linker_install.mode == LinkerInstallMode::Off {
+            return;
+        }
+        if self.nix_environment().is_some() {
+            let packaged = linkers::for_platform(platform).find_map(|spec| nix::package_for(spec.name).map(|package| (spec.name, package)));
+            if let Some((linker, package)) = packaged {
+                report.recommend(format!(
+                    "No fast linker is installed; add pkgs.{} to the packages of the Nix dev shell to use {}",
+                    package, linker
+                ));
+            }
+            return;
+        }
+        let os_release = fs::read_to_string("/etc/os-release").unwrap_or_default();
+        let installable = linkers::for_platform(platform).find_map(|spec| {
+            tools::package_command(spec.name, platform, &os_release).map(|command| (spec.name, command))
+        });
+        if let Some((linker, command)) = installable {
+            report.recommend(format!("No fast linker is installed; install {} with: {}", linker, command));
+        }
+    }
+    
+    /// Apply linker configuration to document
+    fn apply_linker_to_document(
+        &self,
+        doc: &mut DocumentMut,
+        linker: &str,
+        platform: Platform,
+        report: &mut OptimizationReport,
+    ) -> Result<()> {
+        let Some(target) = self.target_triple(platform) else {
+            self.skip_unknown_triple(format!("linker {}", linker), platform, report);
+            return Ok(());
+        };
+        let Some(spec) = linkers::find(linker).filter(|spec| spec.supports(platform) && spec.supports_triple(target)) else {
+            report.skipped(format!("linker {}", linker), SkipReason::UnsupportedPlatform, format!("not a known linker for {}", target));
+            return Ok(());
+        };
+        let setting = format!("target.{}.linker", target);
+        
+        // Most fast linkers are invoked through a C compiler driver
+        let linker_value = match spec.invocation {
+            Invocation::Direct(executable) => {
+                report.applied(&setting, linker);
+                executable.to_string()
+            }
+            Invocation::Driver => {
+                let selection = match &self.installed_commands {
+                    Some(installed) => crate::mvp::select_linker_driver_with(linker, |driver, _| {
+                        if installed.iter().any(|command| command == driver) {
+                            DriverProbe::Supported
+                        } else {
+                            DriverProbe::NotInstalled
+                        }
+                    }),
+                    None => crate::mvp::select_linker_driver(linker),
+                };
+                match &selection.driver {
+                    Some(driver) => {
+                        report.applied(&setting, format!("{} via {}", linker, selection.describe()));
+                        driver.clone()
+                    }
+                    None => {
+                        report.skipped(&setting, SkipReason::MissingTool, selection.describe());
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        
+        // Ensure target table exists
+        let _target_key = format!("target.{}", target);
+        if !doc.contains_key("target") {
+            doc["target"] = Item::Table(Table::new());
+        }
+        
+        let target_table = doc["target"].as_table_mut()
+            .context("Failed to access target table")?;
+        
+        if !target_table.contains_key(target) {
+            target_table[target] = Item::Table(Table::new());
+        }
+        
+        let platform_table = target_table[target].as_table_mut()
+            .context("Failed to access platform table")?;
+        
+        platform_table["linker"] = explained(linker_value, "target.*.linker");
+        let rustflags = spec.rustflags();
+        if !rustflags.is_empty() {
+            platform_table["rustflags"] = explained(string_array(&rustflags), "target.*.rustflags");
+        }
+        
+        debug!("Applied linker configuration: {}", linker);
+        Ok(())
+    }
+    
+    /// Apply build configuration to document
+    fn apply_build_config_to_document(&self, doc: &mut DocumentMut, config: &Config) -> Result<()> {
+        // Ensure build table exists
+        if !doc.contains_key("build") {
+            doc["build"] = Item::Table(Table::new());
+        }
+        
+        let build_table = doc["build"].as_table_mut()
+            .context("Failed to access build table")?;
+        
+        // Apply default job count if specified
+        if let Some(jobs) = &config.global.default_jobs {
+            build_table["jobs"] = explained(jobs.to_count() as i64, "build.jobs");
+        }
+        
+        Ok(())
+    }
+    
+    /// Apply profile configurations to document
+    fn apply_profiles_to_document(&self, doc: &mut DocumentMut, config: &Config, report: &mut OptimizationReport) -> Result<()> {
+        let manifest_profiles = fs::read_to_string(self.project_root().join("Cargo.toml"))
+            .map(|manifest| custom_profiles(&manifest))
+            .unwrap_or_default();
+        
+        // Sorted, so the same inputs always render the same document
+        let mut profiles: Vec<_> = config.profiles.iter().collect();
+        profiles.sort_by_key(|(name, _)| *name);
+        for (name, profile) in profiles {
+            let cargo_keys = [
+                ("opt-level", profile.opt_level.clone()),
+                ("lto", profile.lto.clone()),
+                ("codegen-units", profile.codegen_units.map(|units| units.to_string())),
+                ("panic", profile.panic.clone()),
+                ("strip", profile.strip.clone()),
+                ("debug", profile.debug.clone()),
+            ];
+            
+            // Skip if no customizations for this profile
+            if profile.rustflags.is_empty()
+                && profile.incremental.is_none()
+                && profile.build_override_opt_level.is_none()
+                && cargo_keys.iter().all(|(_, value)| value.is_none())
+            {
+                continue;
+            }
+            
+            // Cargo rejects a custom profile that inherits from nothing
+            let custom = !BUILTIN_PROFILES.contains(&name.as_str());
+            if custom && profile.inherits.is_none() && !manifest_profiles.contains_key(name) {
+                report.skipped(
+                    format!("profile.{}", name),
+                    SkipReason::Error,
+                    "custom profile is not defined in Cargo.toml; set `inherits` under \
+                     [profiles.<name>] in cargo-optimize.toml",
+                );
+                continue;
+            }
+            
+            // Ensure profile table exists
+            let _profile_key = format!("profile.{}", name);
+            if !doc.contains_key("profile") {
+                doc["profile"] = Item::Table(Table::new());
+            }
+            
+            let profile_table = doc["profile"].as_table_mut()
+                .context("Failed to access profile table")?;
+            
+            if !profile_table.contains_key(name) {
+                profile_table[name] = Item::Table(Table::new());
+            }
+            
+            let specific_profile = profile_table[name].as_table_mut()
+                .context("Failed to access specific profile")?;
+            
+            if let Some(inherits) = profile.inherits.as_deref().filter(|_| custom && !manifest_profiles.contains_key(name)) {
+                specific_profile["inherits"] = explained(inherits, "profile.*.inherits");
+            }
+            
+            // Apply incremental setting
+            if let Some(incremental) = profile.incremental {
+                specific_profile["incremental"] = explained(incremental, "profile.*.incremental");
+            }
+            
+            for (key, value) in cargo_keys {
+                if let Some(value) = value {
+                    specific_profile[key] = explained(profile_value(&value), &format!("profile.*.{}", key));
+                }
+            }
+            
+            if let Some(opt_level) = &profile.build_override_opt_level {
+                let build_override = specific_profile
+                    .entry("build-override")
+                    .or_insert_with(|| Item::Table(Table::new()))
+                    .as_table_mut()
+                    .with_context(|| format!("`profile.{}.build-override` is not a table", name))?;
+                build_override["opt-level"] = explained(profile_value(opt_level), "profile.*.build-override.opt-level");
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Resolve the backup directory
+    ///
+    /// Relative backup directories are resolved against the project root
+    /// (the parent of `.cargo/`) so backups land in the right place even when
+    /// the current directory is different.
+    pub fn backup_dir(&self) -> PathBuf {
+        let backup_dir = if self.config.backup.backup_dir.is_absolute() {
+            self.config.backup.backup_dir.clone()
+        } else {
+            self.project_root().join(&self.config.backup.backup_dir)
+        };
         
-        // Build layered configuration with Figment
-        let mut figment = Figment::new()
-            // 1. Start with defaults
-            .merge(Toml::string(&Self::default_config_toml()));
+        // Rebuild from components so separators are the platform's
+        backup_dir.components().collect()
+    }
+    
+    /// Apply nightly-only `[unstable]` settings, or clean them up on stable
+    fn apply_unstable_to_document(
+        &self,
+        doc: &mut DocumentMut,
+        config: &Config,
+        report: &mut OptimizationReport,
+    ) -> Result<()> {
+        const SETTING: &str = "unstable.build-std";
         
-        // 2. Merge with cargo-optimize.toml if it exists
-        if config_file.exists() {
-            figment = figment.merge(Toml::file(&config_file));
+        let Some(settings) = &config.global.build_std else {
+            if remove_managed_unstable(doc) {
+                report.skipped(SETTING, SkipReason::UserOptOut, "build_std is no longer configured; removed previously written keys");
+            }
+            return Ok(());
+        };
+        
+        let toolchain = self.toolchain.clone().or_else(|| ToolchainInfo::detect().ok());
+        if !toolchain.as_ref().is_some_and(|toolchain| Feature::BuildStd.is_available(&toolchain.into())) {
+            let reason = if remove_managed_unstable(doc) {
+                "requires a nightly toolchain; removed previously written [unstable] keys"
+            } else {
+                "requires a nightly toolchain"
+            };
+            report.skipped(SETTING, SkipReason::UnsupportedToolchain, reason);
+            return Ok(());
         }
         
-        // 3. Override with environment variables
-        figment = figment.merge(Env::prefixed(env_prefix).split("__"));
+        let existing_target = doc
+            .get("build")
+            .and_then(|build| build.get("target"))
+            .and_then(|target| target.as_str())
+            .map(String::from);
+        let Some(target) = settings.target.clone().or(existing_target.clone()) else {
+            report.skipped(SETTING, SkipReason::Error, "-Zbuild-std requires an explicit target (set global.build_std.target or build.target)");
+            return Ok(());
+        };
         
-        // Extract the configuration
-        let mut config: Config = figment.extract()
-            .map_err(|e| anyhow::anyhow!("Failed to extract config: {}", e))?;
+        let known_targets = if target.ends_with(".json") {
+            Vec::new()
+        } else {
+            toolchain::target_list().unwrap_or_default()
+        };
+        if let Err(reason) = validate_build_std_target(&target, &self.project_root(), &known_targets) {
+            report.skipped(SETTING, SkipReason::UnsupportedPlatform, reason);
+            return Ok(());
+        }
         
-        // Debug logging to understand what's being loaded
-        debug!("Loading configuration with profile: {}", profile);
-        debug!("Base directory: {:?}", base_dir);
-        debug!("Config file path: {:?}, exists: {}", config_file, config_file.exists());
-        debug!("Auto-detect hardware: {}", config.global.auto_detect_hardware);
-        debug!("Optimization level: {:?}", config.global.optimization_level);
+        if existing_target.is_none() {
+            if !doc.contains_key("build") {
+                doc["build"] = Item::Table(Table::new());
+            }
+            let mut value = toml_edit::Value::from(target.as_str());
+            value.decor_mut().set_suffix(MANAGED_TARGET_MARKER);
+            doc["build"]["target"] = Item::Value(value);
+        }
         
-        // Verify the profile exists
-        if !config.profiles.contains_key(profile) {
-            // If the profile doesn't exist, create a default one
-            let default_profile = Profile::default_for_name(profile.to_string());
-            config.profiles.insert(profile.to_string(), default_profile);
+        if !doc.contains_key("unstable") {
+            doc["unstable"] = Item::Table(Table::new());
+        }
+        let unstable = doc["unstable"].as_table_mut()
+            .context("Failed to access unstable table")?;
+        unstable.decor_mut().set_prefix(format!("\n{}\n", MANAGED_UNSTABLE_MARKER));
+        unstable["build-std"] = explained(string_array(&settings.crates), SETTING);
+        if settings.features.is_empty() {
+            unstable.remove("build-std-features");
+        } else {
+            unstable["build-std-features"] = explained(string_array(&settings.features), "unstable.build-std-features");
         }
         
-        // Auto-detect hardware if enabled
-        if config.global.auto_detect_hardware {
-            config.apply_hardware_optimizations()?;
+        report.applied(SETTING, format!("nightly toolchain, target {}", target));
+        report.require_component("rust-src", "-Zbuild-std");
+        debug!("Applied build-std configuration for {}", target);
+        Ok(())
+    }
+    
+    /// Write per-profile rustflags
+    ///
+    /// Cargo only supports rustflags per profile with the nightly
+    /// `profile-rustflags` feature, so on nightly they are written to
+    /// `profile.<name>.rustflags` together with `[unstable] profile-rustflags`.
+    /// On stable, flags shared by every profile go to `target.<triple>.rustflags`
+    /// (which applies to all profiles); anything profile-specific is skipped.
+    fn apply_profile_rustflags_to_document(
+        &self,
+        doc: &mut DocumentMut,
+        config: &Config,
+        report: &mut OptimizationReport,
+    ) -> Result<()> {
+        let mut wanted: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        let mut profiles: Vec<_> = config.profiles.iter().collect();
+        profiles.sort_by_key(|(name, _)| *name);
+        for (name, profile) in profiles {
+            let (flags, redundant) = split_profile_rustflags(&profile.rustflags);
+            for flag in redundant {
+                report.skipped(
+                    format!("profile.{}.rustflags", name),
+                    SkipReason::Conflict,
+                    format!("`{}` is controlled by the profile's own settings", flag),
+                );
+            }
+            if !flags.is_empty() {
+                wanted.insert(name, flags);
+            }
         }
         
-        // Use absolute path for .cargo/config.toml
-        let config_path = base_dir.join(".cargo").join("config.toml");
+        let nightly = !wanted.is_empty() && self
+            .toolchain
+            .clone()
+            .or_else(|| ToolchainInfo::detect().ok())
+            .as_ref()
+            .is_some_and(|toolchain| Feature::ProfileRustflags.is_available(&toolchain.into()));
+        let keep: Vec<&str> = if nightly { wanted.keys().copied().collect() } else { Vec::new() };
+        remove_managed_profile_rustflags(doc, &keep);
+        // What the stable fallback added last time; the user's flags stay
+        if let Some(shared) = State::load(&self.state_path()).shared_rustflags {
+            remove_shared_rustflags(doc, &shared);
+        }
+        if wanted.is_empty() {
+            return Ok(());
+        }
         
-        // Return the manager with the loaded configuration
-        Ok(ConfigManager {
-            _figment: figment,
-            config,
-            config_path,
-        })
+        if nightly {
+            for (name, flags) in &wanted {
+                let profiles = doc
+                    .entry("profile")
+                    .or_insert_with(|| Item::Table(Table::new()))
+                    .as_table_mut()
+                    .context("Failed to access profile table")?;
+                let profile = profiles
+                    .entry(name)
+                    .or_insert_with(|| Item::Table(Table::new()))
+                    .as_table_mut()
+                    .with_context(|| format!("`profile.{}` is not a table", name))?;
+                profile["rustflags"] = explained_with_note(string_array(flags), PROFILE_RUSTFLAGS_ID, PROFILE_RUSTFLAGS_NOTE);
+                report.applied(
+                    format!("profile.{}.rustflags", name),
+                    format!("{} via [unstable] profile-rustflags (nightly)", flags.join(" ")),
+                );
+            }
+            
+            if !doc.contains_key("unstable") {
+                let mut unstable = Table::new();
+                unstable.decor_mut().set_prefix(format!("\n{}\n", MANAGED_UNSTABLE_MARKER));
+                doc["unstable"] = Item::Table(unstable);
+            }
+            let unstable = doc["unstable"].as_table_mut()
+                .context("Failed to access unstable table")?;
+            unstable["profile-rustflags"] = explained_with_note(true, PROFILE_RUSTFLAGS_ID, PROFILE_RUSTFLAGS_NOTE);
+            return Ok(());
+        }
+        
+        // The host's target table is not what firmware is built with
+        if is_embedded(config) {
+            for name in wanted.keys() {
+                report.skipped(format!("profile.{}.rustflags", name), SkipReason::UnsupportedPlatform, EMBEDDED_REASON);
+            }
+            return Ok(());
+        }
+        
+        // Stable: only flags every profile agrees on can be written
+        let shared = wanted.values().next().filter(|first| {
+            wanted.len() == config.profiles.len() && wanted.values().all(|flags| flags == *first)
+        });
+        let (Some(flags), Some(platform)) = (shared, self.platform) else {
+            for name in wanted.keys() {
+                report.skipped(
+                    format!("profile.{}.rustflags", name),
+                    SkipReason::UnsupportedToolchain,
+                    "per-profile rustflags need the nightly profile-rustflags feature; \
+                     stable cargo only has rustflags for all profiles",
+                );
+            }
+            return Ok(());
+        };
+        
+        let Some(triple) = self.target_triple(platform) else {
+            for name in wanted.keys() {
+                self.skip_unknown_triple(format!("profile.{}.rustflags", name), platform, report);
+            }
+            return Ok(());
+        };
+        let setting = format!("target.{}.rustflags", triple);
+        // Every stable build gets these, and stable rustc rejects -Z
+        let (flags, unstable) = split_unstable_flags(flags);
+        for flag in unstable {
+            report.skipped(&setting, SkipReason::UnsupportedToolchain, format!("`{}` needs a nightly toolchain", flag));
+        }
+        if flags.is_empty() {
+            return Ok(());
+        }
+        
+        let targets = doc
+            .entry("target")
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_mut()
+            .context("Failed to access target table")?;
+        let target = targets
+            .entry(triple)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_mut()
+            .with_context(|| format!("`target.{}` is not a table", triple))?;
+        let current = target.get("rustflags").and_then(Item::as_value);
+        let mut existing: Vec<String> = current
+            .and_then(Value::as_array)
+            .map(|array| array.iter().filter_map(|v| v.as_str()).map(String::from).collect())
+            .unwrap_or_default();
+        if find_run(&existing, &flags).is_some() {
+            report.skipped(&setting, SkipReason::NotNeeded, format!("{} is already set", flags.join(" ")));
+            return Ok(());
+        }
+        existing.extend(flags.iter().cloned());
+        // Only an array cargo-optimize created is marked as managed
+        target["rustflags"] = match current {
+            Some(current) => {
+                let mut value = Value::from(string_array(&existing));
+                *value.decor_mut() = current.decor().clone();
+                Item::Value(value)
+            }
+            None => explained_with_note(string_array(&existing), SHARED_RUSTFLAGS_ID, SHARED_RUSTFLAGS_NOTE),
+        };
+        report.applied(
+            setting,
+            format!("{} (shared by every profile; per-profile rustflags need nightly)", flags.join(" ")),
+        );
+        report.shared_rustflags = Some(SharedRustflags { target: triple.to_string(), flags });
+        Ok(())
     }
     
-    /// Get the default configuration as TOML string
-    fn default_config_toml() -> String {
-        let config = Config::default();
-        toml::to_string_pretty(&config).unwrap_or_default()
+    /// Enable version-gated cargo features the toolchain supports but does not use by default
+    ///
+    /// Availability comes from [`crate::capabilities::MATRIX`]. Keys written
+    /// for an older toolchain are removed once the feature is on by default,
+    /// no longer supported or no longer wanted.
+    fn apply_cargo_features_to_document(
+        &self,
+        doc: &mut DocumentMut,
+        config: &Config,
+        report: &mut OptimizationReport,
+    ) -> Result<()> {
+        let Some(toolchain) = self.toolchain.clone().or_else(|| ToolchainInfo::detect().ok()) else {
+            debug!("rustc version unknown; leaving version-gated cargo features alone");
+            return Ok(());
+        };
+        let toolchain = ToolchainSpec::from(&toolchain);
+        
+        const PROTOCOL: [&str; 3] = ["registries", "crates-io", "protocol"];
+        let protocol = item_at(doc.as_table(), &PROTOCOL);
+        if protocol.is_some() && !carries_id(protocol, SPARSE_REGISTRY_ID) {
+            debug!("registries.crates-io.protocol is set by the user");
+        } else if Feature::SparseRegistry.availability(&toolchain) == Availability::Configurable {
+            insert_at(doc.as_table_mut(), &PROTOCOL, explained("sparse", "registries.crates-io.protocol"));
+            report.applied(
+                "registries.crates-io.protocol",
+                format!("sparse (cargo {} still defaults to the git index)", toolchain.version),
+            );
+        } else {
+            remove_explained(doc.as_table_mut(), &PROTOCOL, SPARSE_REGISTRY_ID);
+        }
+        
+        const GITOXIDE: [&str; 2] = ["unstable", "gitoxide"];
+        const SETTING: &str = "unstable.gitoxide";
+        let gitoxide = item_at(doc.as_table(), &GITOXIDE);
+        if config.global.gitoxide {
+            let level = config.global.optimization_level;
+            let skip = if gitoxide.is_some() && !carries_id(gitoxide, GITOXIDE_ID) {
+                Some((SkipReason::UserOptOut, "set by the user".to_string()))
+            } else if level != OptimizationLevel::Aggressive {
+                Some((SkipReason::UserOptOut, format!("only used at the aggressive optimization level, not {}", level)))
+            } else if !Feature::Gitoxide.is_available(&toolchain) {
+                Some((SkipReason::UnsupportedToolchain, Feature::Gitoxide.requirement()))
+            } else {
+                None
+            };
+            if let Some((why, reason)) = skip {
+                report.skipped(SETTING, why, reason);
+            } else {
+                if !doc.contains_key("unstable") {
+                    let mut unstable = Table::new();
+                    unstable.decor_mut().set_prefix(format!("\n{}\n", MANAGED_UNSTABLE_MARKER));
+                    doc["unstable"] = Item::Table(unstable);
+                }
+                let unstable = doc["unstable"].as_table_mut()
+                    .context("Failed to access unstable table")?;
+                unstable["gitoxide"] = explained(string_array(&["fetch".to_string()]), SETTING);
+                report.applied(SETTING, "fetch (nightly, aggressive optimization level)");
+                return Ok(());
+            }
+        }
+        if carries_id(gitoxide, GITOXIDE_ID) {
+            remove_explained(doc.as_table_mut(), &GITOXIDE, GITOXIDE_ID);
+        }
+        Ok(())
     }
     
-    /// Apply configuration to .cargo/config.toml while preserving formatting
-    pub fn apply(&self) -> Result<()> {
-        // Always ensure .cargo directory exists first
-        if let Some(parent) = self.config_path.parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create parent directory: {:?}", parent))?;
+    /// Route rustc through sccache when a reachable sccache-dist scheduler is configured
+    ///
+    /// A wrapper written earlier stays while the farm is unreachable, since
+    /// sccache then compiles locally; it is removed once no scheduler is
+    /// configured any more.
+    fn apply_sccache_dist(&self, doc: &mut DocumentMut, config: &Config, report: &mut OptimizationReport) {
+        const WRAPPER: [&str; 2] = ["build", "rustc-wrapper"];
+        const SETTING: &str = "sccache-dist";
+        let client_config = sccache_dist::client_config_path(|key| std::env::var(key).ok());
+        // A URL with secret references is probed resolved, but only shown and written as configured
+        let configured = config.global.sccache_dist_scheduler.as_deref();
+        let resolved = match configured.filter(|url| secrets::has_references(url)) {
+            Some(url) => match secrets::resolve(url, |key| std::env::var(key).ok()) {
+                Ok(resolved) => Some(resolved),
+                Err(e) => {
+                    report.skipped(SETTING, SkipReason::Error, format!("sccache_dist_scheduler: {}", e));
+                    return;
+                }
+            },
+            None => None,
+        };
+        let scheduler = sccache_dist::detect(resolved.as_deref().or(configured), client_config.as_deref());
+        let Some(scheduler) = scheduler else {
+            remove_explained(doc.as_table_mut(), &WRAPPER, sccache_dist::DIST_ID);
+            return;
+        };
+        if config.global.no_external_tools {
+            report.skipped(SETTING, SkipReason::UserOptOut, "no_external_tools is set");
+            return;
         }
         
-        // Create backup if enabled AND if the config file exists
-        if self.config.backup.auto_backup && self.config_path.exists() {
-            self.create_backup()?;
-        }
+        let installed = self.is_installed("sccache");
+        let reachability = match &self.sccache_dist_reachability {
+            Some(reachability) => reachability.clone(),
+            None if installed => sccache_dist::probe_with(&scheduler.url, &Proxy::from_env(&config.global.proxy)),
+            None => Reachability::Unreachable { reason: "sccache is not installed".to_string() },
+        };
+        let shown = match (configured, &resolved) {
+            (Some(configured), Some(_)) => sccache_dist::DistScheduler { url: configured.to_string(), ..scheduler.clone() },
+            _ => scheduler.clone(),
+        };
+        let mut advisor = self.advisor();
+        advisor.advise_sccache_dist(&shown, &reachability, installed, client_config.as_deref());
+        report.advise(&advisor);
         
-        // Load or create the document
-        let mut doc = if self.config_path.exists() {
-            // Read with retry for Windows file locking issues
-            let content = self.read_config_with_retry()?;
-            content.parse::<DocumentMut>()
-                .unwrap_or_else(|_| DocumentMut::new())
-        } else {
-            DocumentMut::new()
+        let wrapper = item_at(doc.as_table(), &WRAPPER);
+        let ours = carries_id(wrapper, sccache_dist::DIST_ID);
+        let latency_ms = match reachability {
+            _ if !installed => {
+                report.skipped(SETTING, SkipReason::MissingTool, format!("scheduler {} is configured, but sccache is not installed", scheduler.url));
+                return;
+            }
+            Reachability::Unreachable { reason } => {
+                report.skipped(SETTING, SkipReason::MissingTool, format!("scheduler {} is unreachable: {}", scheduler.url, reason));
+                return;
+            }
+            Reachability::Reachable { latency_ms } => latency_ms,
         };
+        if let Some(current) = wrapper.and_then(Item::as_str).filter(|_| !ours) {
+            if Path::new(current).file_stem().is_some_and(|stem| stem == "sccache") {
+                report.applied(SETTING, format!("build.rustc-wrapper already runs sccache; compilations go to {}", scheduler.url));
+            } else {
+                report.skipped(SETTING, SkipReason::UserOptOut, format!("build.rustc-wrapper is set to `{}` by the user", current));
+            }
+            return;
+        }
+        insert_at(
+            doc.as_table_mut(),
+            &WRAPPER,
+            explained_with_note("sccache", sccache_dist::DIST_ID, &format!("distributed via {}", shown.url)),
+        );
+        report.applied(
+            "build.rustc-wrapper",
+            format!("sccache, distributing compilations to {} ({} ms away)", scheduler.url, latency_ms),
+        );
+    }
+    
+    /// Write `[global.sccache_backend]` to `[env]`, keeping secrets out of the file
+    ///
+    /// Values referencing `${ENV:NAME}` are resolved to check the variables
+    /// are set, and left to the build environment; literal values that look
+    /// like secrets are not written either. Variables written earlier and no
+    /// longer configured are removed.
+    fn apply_sccache_backend(&self, doc: &mut DocumentMut, config: &Config, report: &mut OptimizationReport) {
+        let backend = &config.global.sccache_backend;
+        let stale: Vec<String> = doc
+            .get("env")
+            .and_then(Item::as_table_like)
+            .map(|env| {
+                env.iter()
+                    .filter(|(key, item)| !backend.contains_key(*key) && carries_id(Some(item), sccache::BACKEND_ID))
+                    .map(|(key, _)| key.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        for key in stale {
+            remove_explained(doc.as_table_mut(), &["env", key.as_str()], sccache::BACKEND_ID);
+        }
+        if backend.is_empty() {
+            return;
+        }
+        if !config.global.use_sccache && !runs_sccache(doc) {
+            report.skipped("sccache backend", SkipReason::NotNeeded, "sccache is not used");
+            return;
+        }
         
-        // Apply our optimizations while preserving existing content
-        self.apply_to_document(&mut doc)?;
+        for (key, value) in backend {
+            let setting = format!("env.{}", key);
+            let keys = ["env", key.as_str()];
+            let existing = item_at(doc.as_table(), &keys);
+            if existing.is_some() && !carries_id(existing, sccache::BACKEND_ID) {
+                report.skipped(setting, SkipReason::UserOptOut, "set in .cargo/config.toml by the user");
+                continue;
+            }
+            if secrets::has_references(value) {
+                // Whatever was written for a literal before must not linger
+                remove_explained(doc.as_table_mut(), &keys, sccache::BACKEND_ID);
+                match secrets::resolve(value, |name| std::env::var(name).ok()) {
+                    Err(e) => report.skipped(setting, SkipReason::Error, format!("{}; builds will not reach the sccache backend", e)),
+                    Ok(resolved) if std::env::var(key).ok().as_deref() == Some(resolved.as_str()) => {
+                        report.applied(setting, "references a secret; read from the build environment, not written")
+                    }
+                    Ok(_) => {
+                        report.skipped(setting, SkipReason::Policy, "references a secret, which is never written to .cargo/config.toml");
+                        report.recommend(format!(
+                            "Export {} in the build environment: {}=\"{}\"",
+                            key,
+                            key,
+                            value.replace("${ENV:", "${")
+                        ));
+                    }
+                }
+                continue;
+            }
+            if secrets::is_sensitive_key(key) || secrets::redact_url_credentials(value) != *value {
+                report.skipped(setting, SkipReason::Policy, "looks like a secret; reference it as ${ENV:NAME} in [global.sccache_backend]");
+                continue;
+            }
+            insert_at(doc.as_table_mut(), &keys, explained_by(value.as_str(), sccache::BACKEND_ID));
+            report.applied(setting, format!("sccache backend: {}", value));
+        }
+    }
+    
+    /// Remove an sccache wrapper that does not pay off, or recommend removing it
+    fn apply_sccache_heuristics(&self, doc: &mut DocumentMut, config: &Config, report: &mut OptimizationReport) {
+        if !runs_sccache(doc) {
+            return;
+        }
         
-        // Always write the config file, even if it's minimal
-        // This ensures the file exists after apply() is called
-        let content = doc.to_string();
-        // If document is empty, at least add a comment
-        let final_content = if content.trim().is_empty() {
-            "# Cargo configuration managed by cargo-optimize\n".to_string()
-        } else {
-            content
+        let mut history = State::load(&self.state_path()).sccache;
+        if let Some(stats) = self.sccache_stats.or_else(sccache::current_stats) {
+            history.record(stats);
+        }
+        let floor = config.global.sccache_min_hit_rate;
+        match sccache::assess(self.project_size, &history, floor, self.context) {
+            SccacheVerdict::Keep => {}
+            SccacheVerdict::RecommendDisable { dependencies } => report.recommend(format!(
+                "sccache rarely pays off for a single package with {} dependencies built locally; \
+                 consider removing build.rustc-wrapper",
+                dependencies
+            )),
+            SccacheVerdict::Disable { .. } if self.policy.as_ref().is_some_and(|p| p.sccache_endpoint.is_some()) => {
+                debug!("sccache hit rate is below the floor, but the machine policy requires sccache");
+            }
+            // Farm compilations are not cache hits; the hit rate says nothing about them
+            SccacheVerdict::Disable { .. } if carries_id(doc.get("build").and_then(|build| build.get("rustc-wrapper")), sccache_dist::DIST_ID) => {
+                debug!("sccache hit rate is below the floor, but sccache distributes to a build farm");
+            }
+            SccacheVerdict::Disable { hit_rate } => {
+                if let Some(build) = doc.get_mut("build").and_then(Item::as_table_like_mut) {
+                    build.remove("rustc-wrapper");
+                }
+                report.skipped(
+                    "build.rustc-wrapper",
+                    SkipReason::NotNeeded,
+                    format!(
+                        "sccache hit rate stayed below {:.0}% on the last {} readings (latest {:.0}%), \
+                         so the wrapper only added overhead ({})",
+                        floor * 100.0,
+                        sccache::MIN_SAMPLES,
+                        hit_rate * 100.0,
+                        sccache::LOW_HIT_RATE_ID
+                    ),
+                );
+            }
+        }
+    }
+    
+    /// Leave the cores other workloads keep busy to them, within `[global.adaptive_jobs]`
+    ///
+    /// The load sampled now is combined with that of the recent profiled
+    /// builds (median), so one busy moment does not decide alone. The effect
+    /// of the last adjustment on build times is reported along. A value
+    /// written earlier is removed once the mode is turned off.
+    fn apply_adaptive_jobs(
+        &self,
+        doc: &mut DocumentMut,
+        config: &Config,
+        hardware: &HardwareInfo,
+        report: &mut OptimizationReport,
+    ) {
+        let Some(bounds) = &config.global.adaptive_jobs else {
+            if remove_explained(doc.as_table_mut(), &["build", "jobs"], ADAPTIVE_JOBS_ID) {
+                report.skipped("build.jobs", SkipReason::UserOptOut, format!("no longer adapted to system load ({})", ADAPTIVE_JOBS_ID));
+            }
+            return;
         };
+        let load = self.system_load.or_else(load::sample);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let cargo_dir = self.config_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let history = MetricsHistory::load(&MetricsHistory::path_in(&cargo_dir));
+        let mut loads = history.background_loads(now.saturating_sub(ADAPTIVE_LOAD_MAX_AGE_SECS));
+        let builds = loads.len();
+        loads.extend(load.map(|load| load.busy_cores));
+        if loads.is_empty() {
+            // Leave a value written earlier to the next reading
+            report.skipped(
+                "build.jobs",
+                SkipReason::UnsupportedPlatform,
+                format!("no load average here and no profiled build measured the load yet ({})", ADAPTIVE_JOBS_ID),
+            );
+            return;
+        }
+        let busy = metrics::median(loads);
         
-        // Write back the modified document with retry for Windows
-        self.write_config_with_retry(&final_content)?;
+        let cores = hardware.cpu_count;
+        let base = config.global.default_jobs.as_ref().map_or(cores, JobCount::to_count);
+        let min = bounds.min.to_count();
+        let max = bounds.max.as_ref().map_or(cores, JobCount::to_count);
+        let jobs = load::adaptive_jobs(base, cores, busy, min, max);
+        insert_at(doc.as_table_mut(), &["build", "jobs"], explained_by(jobs as i64, ADAPTIVE_JOBS_ID));
         
-        info!("Configuration applied successfully to {:?}", self.config_path);
-        Ok(())
+        let mut reason = format!("{:.1} of {} cores busy with other work", busy, cores);
+        match (load.is_some(), builds) {
+            (_, 0) => {}
+            (true, builds) => reason.push_str(&format!(" (median of now and {} recent builds)", builds)),
+            (false, builds) => reason.push_str(&format!(" (median of {} recent builds)", builds)),
+        }
+        if let Some(pressure) = load.and_then(|load| load.cpu_pressure) {
+            reason.push_str(&format!(", tasks waiting for a CPU {:.0}% of the time", pressure));
+        }
+        reason.push_str(&format!("; {} jobs within {}..={}", jobs, min, max));
+        let adjustments = State::load(&self.state_path()).jobs_adjustments;
+        if let Some((last, earlier)) = adjustments.split_last() {
+            let from = earlier.last().map_or(0, |previous| previous.recorded_at);
+            if let Some(change) = history.duration_change("dev", from, last.recorded_at) {
+                reason.push_str(&format!("; since the last adjustment to {} jobs, {}", last.jobs, change));
+            }
+        }
+        report.applied("build.jobs", format!("{} ({})", reason, ADAPTIVE_JOBS_ID));
+        report.jobs_adjustment =
+            Some(JobsAdjustment { recorded_at: now, busy_cores: busy.round() as usize, base_jobs: base, jobs });
     }
     
-    /// Apply optimizations to a TOML document while preserving formatting
-    fn apply_to_document(&self, doc: &mut DocumentMut) -> Result<()> {
-        // Add header comment if document is empty
-        if doc.as_table().is_empty() {
-            doc.decor_mut().set_prefix(
-                "# Cargo configuration - optimized by cargo-optimize\n\
-                 # This file has been automatically optimized for better build performance\n\n"
+    /// Lower `build.jobs` and parallel LTO when profiled builds came close to running out of memory
+    ///
+    /// A previously applied adjustment is kept while the machine and the
+    /// project stay the same: builds since then ran with the lowered settings
+    /// and say nothing about whether they are still needed. Once either
+    /// changes, it is reverted and only builds measured afterwards count.
+    fn apply_memory_pressure(
+        &self,
+        doc: &mut DocumentMut,
+        config: &Config,
+        hardware: &HardwareInfo,
+        report: &mut OptimizationReport,
+    ) {
+        let state = State::load(&self.state_path());
+        let mut since = 0;
+        if let Some(adjustment) = &state.memory {
+            if adjustment.fingerprint == self.memory_fingerprint(hardware) {
+                for entry in &adjustment.changes.entries {
+                    let Some(value) = entry.written.as_deref().and_then(|written| written.parse::<toml_edit::Value>().ok()) else {
+                        continue;
+                    };
+                    let keys: Vec<&str> = entry.key.iter().map(String::as_str).collect();
+                    insert_at(doc.as_table_mut(), &keys, memory_value(value));
+                    report.applied(entry.dotted_key(), format!("kept from an earlier apply ({})", MEMORY_PRESSURE_ID));
+                }
+                return;
+            }
+            adjustment.changes.revert(doc);
+            report.skipped(
+                MEMORY_PRESSURE_SETTING,
+                SkipReason::RolledBack,
+                format!("reverted: the machine or the project changed since the adjustment ({})", MEMORY_PRESSURE_ID),
             );
+            since = adjustment.recorded_at;
         }
         
-        // Apply linker configuration
-        if let Some(linker) = self.detect_best_linker() {
-            self.apply_linker_to_document(doc, &linker)?;
+        let cargo_dir = self.config_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let history = MetricsHistory::load(&MetricsHistory::path_in(&cargo_dir));
+        let Some(pressure) = history.memory_pressure(hardware.cpu_count, since) else {
+            return;
+        };
+        
+        let jobs = item_at(doc.as_table(), &["build", "jobs"])
+            .and_then(Item::as_integer)
+            .filter(|jobs| *jobs > 0)
+            .map_or(hardware.cpu_count, |jobs| jobs as usize);
+        let lowered_jobs = ((jobs as f64 * MEMORY_TARGET_RATIO / pressure.ratio()).floor() as usize).max(1);
+        let mut lowered: Vec<(Vec<&str>, toml_edit::Value)> = Vec::new();
+        if lowered_jobs < jobs {
+            lowered.push((vec!["build", "jobs"], (lowered_jobs as i64).into()));
+        }
+        let lto = item_at(doc.as_table(), &["profile", &pressure.profile, "lto"]).and_then(Item::as_str);
+        if lto == Some("thin") {
+            lowered.push((vec!["profile", &pressure.profile, "lto"], false.into()));
+        }
+        if lowered.is_empty() {
+            return;
         }
         
-        // Apply build configuration
-        self.apply_build_config_to_document(doc)?;
+        let gib = |bytes: u64| bytes as f64 / (1u64 << 30) as f64;
+        let measured = format!(
+            "{} builds peaked at {:.1} of {:.1} GiB available ({:.0}%)",
+            pressure.profile,
+            gib(pressure.peak_bytes),
+            gib(pressure.budget_bytes),
+            pressure.ratio() * 100.0
+        );
+        match config.global.memory_pressure_policy {
+            MemoryPressurePolicy::Recommend => {
+                let settings: Vec<String> = lowered
+                    .iter()
+                    .map(|(keys, value)| format!("`{} = {}`", keys.join("."), value))
+                    .collect();
+                report.recommend(format!(
+                    "{}; set {} to avoid running out of memory, or `memory_pressure_policy = \"apply\"` \
+                     under [global] to let cargo-optimize do it",
+                    measured,
+                    settings.join(" and ")
+                ));
+            }
+            MemoryPressurePolicy::Apply => {
+                for (keys, value) in lowered {
+                    insert_at(doc.as_table_mut(), &keys, memory_value(value));
+                    report.applied(keys.join("."), format!("lowered: {}", measured));
+                }
+            }
+        }
+    }
+    
+    /// Benchmark the disk of the target directory unless a recent result is cached
+    ///
+    /// Runs before staging so the report of the same apply already uses it;
+    /// a directory that cannot be written only costs the calibration. `doc`
+    /// is the config as the apply's transaction read it.
+    fn calibrate_disk(&self, doc: &DocumentMut) {
+        if self.disk_benchmark.is_some() {
+            return;
+        }
+        let project_root = self.project_root();
+        let target_dir = effective_target_dir(&project_root, self.target_dir_env.as_deref(), doc).path;
+        let dir = if target_dir.is_dir() { target_dir } else { project_root };
+        if State::load(&self.state_path()).disk_benchmark.as_ref().is_some_and(|benchmark| benchmark.is_current_for(&dir)) {
+            return;
+        }
+        let Some(benchmark) = DiskBenchmark::run(&dir, disk_io::BUDGET) else {
+            warn!("Failed to benchmark the disk of {:?}", dir);
+            return;
+        };
+        debug!("Disk benchmark: {}", benchmark);
+        if let Err(e) = State::update(&self.state_path(), |state| state.disk_benchmark = Some(benchmark)) {
+            warn!("Failed to record the disk benchmark: {:#}", e);
+        }
+    }
+    
+    /// Recommend what matters most for whether builds wait on the disk or the CPU
+    ///
+    /// Uses the benchmark cached by an earlier apply; plans never run one.
+    /// On an IO-bound machine less debug info and a target directory in
+    /// memory or on the fastest disk cut what is written; on a CPU-bound one
+    /// the disk keeps up, so parallelism is what is left to tune.
+    fn report_io_profile(
+        &self,
+        doc: &DocumentMut,
+        project_root: &Path,
+        platform: Platform,
+        hardware: &HardwareInfo,
+        report: &mut OptimizationReport,
+    ) {
+        let Some(benchmark) = self
+            .disk_benchmark
+            .clone()
+            .or_else(|| State::load(&self.state_path()).disk_benchmark)
+        else {
+            return;
+        };
+        let profile = benchmark.profile(hardware.cpu_count);
+        let measured = format!("builds here are {} ({} for {} cores)", profile, benchmark, hardware.cpu_count);
+        match profile {
+            IoProfile::IoBound => {
+                let debug = item_at(doc.as_table(), &["profile", "dev", "debug"]);
+                let full_debug = match debug.and_then(Item::as_value) {
+                    None => true,
+                    Some(toml_edit::Value::Boolean(full)) => *full.value(),
+                    Some(toml_edit::Value::Integer(level)) => *level.value() >= 2,
+                    Some(toml_edit::Value::String(level)) => matches!(level.value().as_str(), "full" | "2" | "true"),
+                    Some(_) => false,
+                };
+                if full_debug {
+                    report.recommend(format!(
+                        "{}; set `debug = \"line-tables-only\"` for the dev profile to write far less debug info",
+                        measured
+                    ));
+                }
+                let target_dir = effective_target_dir(project_root, self.target_dir_env.as_deref(), doc).path;
+                let target_bytes = hardware::directory_size(&target_dir);
+                if platform == Platform::Linux && target_bytes.saturating_mul(4) < hardware.total_memory {
+                    report.recommend(format!(
+                        "{}; build in memory with `CARGO_TARGET_DIR=/dev/shm/{}-target`, \
+                         which needs about {:.1} GiB of RAM and is emptied on reboot",
+                        measured,
+                        project_root.file_name().map_or("cargo".into(), |name| name.to_string_lossy()),
+                        target_bytes as f64 / (1u64 << 30) as f64
+                    ));
+                } else {
+                    report.recommend(format!(
+                        "{}; put the target directory and SCCACHE_DIR on the fastest local disk",
+                        measured
+                    ));
+                }
+            }
+            IoProfile::CpuBound => {
+                let jobs = item_at(doc.as_table(), &["build", "jobs"]).and_then(Item::as_integer);
+                if let Some(jobs) = jobs.filter(|jobs| *jobs > 0 && (*jobs as usize) < hardware.cpu_count) {
+                    report.recommend(format!(
+                        "{}; `build.jobs = {}` leaves cores idle, raise it to {} unless memory is short",
+                        measured, jobs, hardware.cpu_count
+                    ));
+                }
+                let units = item_at(doc.as_table(), &["profile", "release", "codegen-units"]).and_then(Item::as_integer);
+                if units == Some(1) {
+                    report.recommend(format!(
+                        "{}; release `codegen-units = 1` compiles each crate on one core, \
+                         raise it when release build time matters more than the last bit of runtime speed",
+                        measured
+                    ));
+                }
+            }
+            IoProfile::Balanced => debug!("Disk benchmark: {}", measured),
+        }
+    }
+    
+    /// Recommend a mitigation when builds after a branch switch cost measurably more
+    ///
+    /// The loss per switch comes from builds tagged with their branch in the
+    /// metrics history, the switch rate from the last
+    /// [`BRANCH_ACTIVITY_DAYS`] days of the reflog. A target directory per
+    /// branch keeps every branch's incremental cache but needs a copy of the
+    /// target directory for each, so it is only recommended when the disk
+    /// holds them with `min_free_disk_gb` to spare; otherwise sccache.
+    fn report_branch_switches(&self, doc: &DocumentMut, project_root: &Path, hardware: &HardwareInfo, report: &mut OptimizationReport) {
+        let cargo_dir = self.config_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let history = MetricsHistory::load(&MetricsHistory::path_in(&cargo_dir));
+        let Some(cost) = history.branch_switch_cost() else {
+            return;
+        };
+        let lost = cost.lost_per_switch_secs();
+        if lost < MIN_BRANCH_SWITCH_LOSS_SECS {
+            return;
+        }
+        let Some(activity) = self
+            .branch_activity
+            .clone()
+            .or_else(|| metrics::branch_activity(project_root, BRANCH_ACTIVITY_DAYS))
+            .filter(|activity| activity.switches > 0)
+        else {
+            return;
+        };
         
-        // Apply profile configurations
-        self.apply_profiles_to_document(doc)?;
+        let measured = format!(
+            "{} builds after a branch switch take {:.0}s against {:.0}s on the same branch; at {:.1} switches a week \
+             over {} branches that is about {:.0} minutes a week",
+            cost.profile,
+            cost.median_after_switch_secs,
+            cost.median_same_branch_secs,
+            activity.switches_per_week(),
+            activity.branches.len(),
+            lost * activity.switches_per_week() / 60.0
+        );
         
-        Ok(())
+        let gib = |bytes: u64| bytes as f64 / (1u64 << 30) as f64;
+        let target_dir = effective_target_dir(project_root, self.target_dir_env.as_deref(), doc).path;
+        let extra_bytes = hardware::directory_size(&target_dir) * (activity.branches.len().max(2) as u64 - 1);
+        let reserve_bytes = (self.config.global.min_free_disk_gb * (1u64 << 30) as f64) as u64;
+        let available = hardware.disk_for_path(project_root).map(|disk| disk.available_space);
+        if available.is_some_and(|available| available >= extra_bytes.saturating_add(reserve_bytes)) {
+            report.recommend(format!(
+                "{}; build each branch in its own target directory, e.g. \
+                 `CARGO_TARGET_DIR=target/branch-$(git branch --show-current)`, which needs about {:.1} GiB more disk",
+                measured,
+                gib(extra_bytes)
+            ));
+            return;
+        }
+        
+        let wrapper = item_at(doc.as_table(), &["build", "rustc-wrapper"]).and_then(Item::as_str);
+        if wrapper.is_some_and(|wrapper| Path::new(wrapper).file_stem().is_some_and(|stem| stem == "sccache")) {
+            return;
+        }
+        report.recommend(format!(
+            "{}; there is not enough free disk for a target directory per branch (about {:.1} GiB), \
+             so enable sccache (`use_sccache = true` under [global]) to reuse compiled crates across branches",
+            measured,
+            gib(extra_bytes)
+        ));
     }
     
-    /// Apply linker configuration to document
-    fn apply_linker_to_document(&self, doc: &mut DocumentMut, linker: &str) -> Result<()> {
-        let target = if cfg!(target_os = "windows") {
-            "x86_64-pc-windows-msvc"
-        } else {
-            "x86_64-unknown-linux-gnu"
-        };
+    /// Recommend a Dev Drive for a target directory on an ordinary volume
+    ///
+    /// The recommendation carries the small-file rate measured in the target
+    /// directory and the improvement projected from it. With
+    /// `dev_drive_policy = "relocate"` and a Dev Drive present,
+    /// `build.target-dir` is pointed at it instead, unless something else
+    /// decides where the target directory is.
+    fn apply_dev_drive(&self, doc: &mut DocumentMut, config: &Config, hardware: &HardwareInfo, report: &mut OptimizationReport) {
+        let project_root = self.project_root();
+        let ours = item_at(doc.as_table(), &["build", "target-dir"])
+            .and_then(Item::as_value)
+            .and_then(|value| value.decor().suffix())
+            .and_then(|suffix| suffix.as_str())
+            .is_some_and(|suffix| suffix.contains(DEV_DRIVE_ID));
+        if ours && config.global.dev_drive_policy != DevDrivePolicy::Relocate {
+            if let Some(build) = doc.get_mut("build").and_then(Item::as_table_like_mut) {
+                build.remove("target-dir");
+            }
+            report.skipped("build.target-dir", SkipReason::UserOptOut, "removed: dev_drive_policy is no longer relocate");
+        }
         
-        // Ensure target table exists
-        let _target_key = format!("target.{}", target);
-        if !doc.contains_key("target") {
-            doc["target"] = Item::Table(Table::new());
+        let kind_of = |disk: &DiskInfo| match &self.volume_kinds {
+            Some(kinds) => kinds.get(&disk.mount_point).copied().unwrap_or(VolumeKind::Other),
+            None => dev_drive::volume_kind(disk),
+        };
+        let target_dir = effective_target_dir(&project_root, self.target_dir_env.as_deref(), doc).path;
+        let Some(disk) = hardware.disk_for_path(&target_dir).or_else(|| hardware.disk_for_path(&project_root)) else {
+            return;
+        };
+        match kind_of(disk) {
+            VolumeKind::DevDrive { trusted: true } => {
+                debug!("Target directory {:?} is on the Dev Drive {}", target_dir, disk.mount_point);
+                return;
+            }
+            VolumeKind::DevDrive { trusted: false } => {
+                report.recommend(format!(
+                    "The target directory is on the untrusted Dev Drive {}; run `fsutil devdrv trust {}` \
+                     as administrator so Microsoft Defender scans it in performance mode",
+                    disk.mount_point,
+                    disk.mount_point.trim_end_matches(['\\', '/'])
+                ));
+                return;
+            }
+            VolumeKind::ReFs | VolumeKind::Other => {}
         }
         
-        let target_table = doc["target"].as_table_mut()
-            .context("Failed to access target table")?;
+        let probe_dir = if target_dir.is_dir() { target_dir.as_path() } else { project_root.as_path() };
+        let rate = dev_drive::measure_small_files(probe_dir);
+        let (low, high) = dev_drive::projected_improvement(rate);
+        let measured = match rate {
+            Some(rate) => format!(
+                "the target directory is on {} ({}), which handles {:.0} small files/s; builds on a Dev Drive \
+                 are projected to be {}-{}% faster",
+                disk.mount_point, disk.file_system, rate, low, high
+            ),
+            None => format!(
+                "the target directory is on {} ({}); builds on a Dev Drive are typically {}-{}% faster",
+                disk.mount_point, disk.file_system, low, high
+            ),
+        };
+        let Some(drive) = hardware.disks.iter().find(|other| {
+            other.mount_point != disk.mount_point && matches!(kind_of(other), VolumeKind::DevDrive { .. })
+        }) else {
+            report.recommend(format!(
+                "{}; create one under Settings > System > Storage > Advanced storage settings > Disks & volumes \
+                 (or `Format-Volume -DriveLetter <letter> -DevDrive` on free space), then set \
+                 `dev_drive_policy = \"relocate\"` under [global] to move the target directory there",
+                measured
+            ));
+            return;
+        };
+        let name = project_root
+            .canonicalize()
+            .ok()
+            .and_then(|root| root.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "project".to_string());
+        let relocated = Path::new(&drive.mount_point).join("cargo-target").join(&name);
         
-        if !target_table.contains_key(target) {
-            target_table[target] = Item::Table(Table::new());
+        if config.global.dev_drive_policy == DevDrivePolicy::Recommend {
+            report.recommend(format!(
+                "{}; set `build.target-dir = {}` to build on the Dev Drive {}, or `dev_drive_policy = \"relocate\"` \
+                 under [global] to let cargo-optimize do it",
+                measured,
+                toml_edit::Value::from(relocated.display().to_string()),
+                drive.mount_point
+            ));
+            return;
         }
+        if let Some(env_dir) = &self.target_dir_env {
+            report.skipped("build.target-dir", SkipReason::Context, format!("CARGO_TARGET_DIR is set ({})", env_dir.display()));
+            return;
+        }
+        if config.global.target_dir_layout != TargetDirLayout::Shared {
+            report.skipped("build.target-dir", SkipReason::Conflict, "the target-dir layout places target directories itself");
+            return;
+        }
+        if !ours && item_at(doc.as_table(), &["build", "target-dir"]).is_some() {
+            report.skipped("build.target-dir", SkipReason::UserOptOut, "already set by the user");
+            return;
+        }
+        let Some(build) = doc.entry("build").or_insert_with(|| Item::Table(Table::new())).as_table_like_mut() else {
+            report.skipped("build.target-dir", SkipReason::Conflict, "[build] is not a table");
+            return;
+        };
+        let value = relocated.to_string_lossy().into_owned();
+        build.insert("target-dir", explained_with_note(value, DEV_DRIVE_ID, "Dev Drive"));
+        report.applied("build.target-dir", format!("moved to the Dev Drive {}: {}", drive.mount_point, measured));
+        report.relocations = plan_relocations(&target_dir, &[(relocated, None)], config.global.target_dir_migration);
+    }
+    
+    /// Make the planned document comply with the machine policy
+    ///
+    /// Settings written by this run are brought in line with the policy;
+    /// rustflags the user wrote themselves (identical in `original`) are left
+    /// alone. Both are reported as conflicts.
+    fn apply_policy_to_document(&self, original: &DocumentMut, doc: &mut DocumentMut, report: &mut OptimizationReport) -> Result<()> {
+        let Some(policy) = &self.policy else {
+            return Ok(());
+        };
         
-        let platform_table = target_table[target].as_table_mut()
-            .context("Failed to access platform table")?;
-        
-        // Set linker
-        if cfg!(target_os = "windows") {
-            platform_table["linker"] = toml_edit::value(linker);
-        } else {
-            // For Linux, use clang with appropriate flags
-            platform_table["linker"] = toml_edit::value("clang");
-            let rustflags = match linker {
-                "mold" => vec!["-C", "link-arg=-fuse-ld=mold"],
-                "lld" => vec!["-C", "link-arg=-fuse-ld=lld"],
-                "gold" => vec!["-C", "link-arg=-fuse-ld=gold"],
-                _ => vec![],
-            };
+        if let (Some(endpoint), Some(bucket)) = (&policy.sccache_endpoint, &policy.sccache_bucket) {
+            if !self.is_installed("sccache") {
+                report.skipped(
+                    "build.rustc-wrapper",
+                    SkipReason::MissingTool,
+                    "the machine policy requires sccache, but it is not installed; builds would fail to start rustc",
+                );
+                report.recommend("Install sccache (`cargo install sccache`) to build through the shared cache the machine policy requires");
+                return self.apply_forbidden_flags(policy, original, doc, report);
+            }
+            if !self.config.global.use_sccache {
+                report.conflict("sccache", "disabled in cargo-optimize.toml but required by the machine policy");
+            }
+            let build = doc
+                .entry("build")
+                .or_insert_with(|| Item::Table(Table::new()))
+                .as_table_like_mut()
+                .context("Failed to access build table")?;
+            if let Some(wrapper) = build.get("rustc-wrapper").and_then(Item::as_str) {
+                if Path::new(wrapper).file_stem().is_none_or(|stem| stem != "sccache") {
+                    report.conflict(
+                        "build.rustc-wrapper",
+                        format!("`{}` replaced by sccache as required by the machine policy", wrapper),
+                    );
+                }
+            }
+            build.insert("rustc-wrapper", explained("sccache", "build.rustc-wrapper"));
             
-            if !rustflags.is_empty() {
-                let mut array = toml_edit::Array::new();
-                for flag in rustflags {
-                    array.push(flag);
+            let env = doc
+                .entry("env")
+                .or_insert_with(|| Item::Table(Table::new()))
+                .as_table_like_mut()
+                .context("Failed to access env table")?;
+            for (key, value) in [("SCCACHE_ENDPOINT", endpoint), ("SCCACHE_BUCKET", bucket)] {
+                let setting = format!("env.{}", key);
+                if let Some(current) = env.get(key).and_then(Item::as_str) {
+                    if current != value {
+                        report.conflict(&setting, format!("`{}` replaced by the machine policy value", current));
+                    }
                 }
-                platform_table["rustflags"] = toml_edit::value(array);
+                env.insert(key, explained(value.as_str(), &setting));
             }
+            report.applied("build.rustc-wrapper", format!("sccache with endpoint {} (machine policy)", endpoint));
         }
-        
-        debug!("Applied linker configuration: {}", linker);
-        Ok(())
+        self.apply_forbidden_flags(policy, original, doc, report)
     }
     
-    /// Apply build configuration to document
-    fn apply_build_config_to_document(&self, doc: &mut DocumentMut) -> Result<()> {
-        // Ensure build table exists
-        if !doc.contains_key("build") {
-            doc["build"] = Item::Table(Table::new());
-        }
-        
-        let build_table = doc["build"].as_table_mut()
-            .context("Failed to access build table")?;
-        
-        // Apply default job count if specified
-        if let Some(jobs) = &self.config.global.default_jobs {
-            build_table["jobs"] = toml_edit::value(jobs.to_count() as i64);
+    /// Remove the rustflags the machine policy forbids
+    fn apply_forbidden_flags(
+        &self,
+        policy: &Policy,
+        original: &DocumentMut,
+        doc: &mut DocumentMut,
+        report: &mut OptimizationReport,
+    ) -> Result<()> {
+        if policy.forbidden_flags.is_empty() {
+            return Ok(());
         }
-        
-        Ok(())
-    }
-    
-    /// Apply profile configurations to document
-    fn apply_profiles_to_document(&self, doc: &mut DocumentMut) -> Result<()> {
-        for (name, profile) in &self.config.profiles {
-            // Skip if no customizations for this profile
-            if profile.rustflags.is_empty() && profile.incremental.is_none() {
+        for path in rustflags_paths(doc) {
+            let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+            let Some(flags) = item_at(doc.as_table(), &keys).and_then(string_values) else {
+                continue;
+            };
+            let (allowed, forbidden) = policy.split_flags(&flags);
+            if forbidden.is_empty() {
                 continue;
             }
-            
-            // Ensure profile table exists
-            let _profile_key = format!("profile.{}", name);
-            if !doc.contains_key("profile") {
-                doc["profile"] = Item::Table(Table::new());
-            }
-            
-            let profile_table = doc["profile"].as_table_mut()
-                .context("Failed to access profile table")?;
-            
-            if !profile_table.contains_key(name) {
-                profile_table[name] = Item::Table(Table::new());
+            let setting = path.join(".");
+            let user_written = item_at(original.as_table(), &keys).and_then(string_values).as_ref() == Some(&flags);
+            if user_written {
+                report.conflict(
+                    setting,
+                    format!(
+                        "{} forbidden by the machine policy; written outside cargo-optimize, remove it manually",
+                        forbidden.join(" ")
+                    ),
+                );
+                continue;
             }
             
-            let specific_profile = profile_table[name].as_table_mut()
-                .context("Failed to access specific profile")?;
-            
-            // Apply incremental setting
-            if let Some(incremental) = profile.incremental {
-                specific_profile["incremental"] = toml_edit::value(incremental);
+            let Some((key, parents)) = keys.split_last() else {
+                continue;
+            };
+            let table = parents
+                .iter()
+                .try_fold(doc.as_table_mut() as &mut dyn toml_edit::TableLike, |table, parent| {
+                    table.get_mut(parent).and_then(Item::as_table_like_mut)
+                })
+                .context("Failed to access rustflags table")?;
+            if allowed.is_empty() {
+                table.remove(key);
+            } else if let Some(value) = table.get_mut(key).and_then(Item::as_value_mut) {
+                let decor = value.decor().clone();
+                *value = toml_edit::Value::Array(string_array(&allowed));
+                *value.decor_mut() = decor;
             }
+            report.conflict(setting, format!("{} forbidden by the machine policy; removed", forbidden.join(" ")));
         }
-        
         Ok(())
     }
     
     /// Create a backup of the current configuration
     pub fn create_backup(&self) -> Result<PathBuf> {
+        let transaction = ConfigTransaction::begin(&self.config_path)?;
+        self.backup_transaction(&transaction, "manual backup")
+    }
+    
+    /// Back up the content a transaction was opened with
+    ///
+    /// A metadata sidecar records the origin, tool version, SHA-256 of the
+    /// content and `reason`.
+    fn backup_transaction(&self, transaction: &ConfigTransaction, reason: &str) -> Result<PathBuf> {
         // Ensure backup directory exists - handle both absolute and relative paths
-        let backup_dir = if self.config.backup.backup_dir.is_absolute() {
-            self.config.backup.backup_dir.clone()
-        } else {
-            // If relative, it's relative to the config file's parent directory
-            // This ensures backups are created in the right place even when
-            // the current directory is different
-            if let Some(parent) = self.config_path.parent() {
-                // Go up one level from .cargo/config.toml to get the project root
-                if let Some(project_root) = parent.parent() {
-                    project_root.join(&self.config.backup.backup_dir)
-                } else {
-                    parent.join(&self.config.backup.backup_dir)
-                }
-            } else {
-                // Fallback to current directory if we can't determine parent
-                std::env::current_dir()?.join(&self.config.backup.backup_dir)
-            }
-        };
-        
-        // Normalize the path to use proper separators for the platform
-        let backup_dir = PathBuf::from(backup_dir.to_string_lossy().replace('/', std::path::MAIN_SEPARATOR_STR));
+        let backup_dir = self.backup_dir();
         
         // Create all parent directories if they don't exist
         fs::create_dir_all(&backup_dir)
@@ -697,22 +5272,24 @@ impl ConfigManager {
         let backup_name = format!("config_backup_{}.toml", timestamp);
         let backup_path = backup_dir.join(&backup_name);
         
-        // Debug: Check current directory and config path
-        debug!("Current dir: {:?}", std::env::current_dir());
-        debug!("Config path: {:?}, exists: {}", self.config_path, self.config_path.exists());
-        debug!("Backup dir: {:?}, exists: {}", backup_dir, backup_dir.exists());
-        
-        // Copy current config if it exists
-        if self.config_path.exists() {
-            let content = fs::read_to_string(&self.config_path)
-                .context("Failed to read config file for backup")?;
-            fs::write(&backup_path, &content)
-                .with_context(|| format!("Failed to write backup file: {:?}", backup_path))?;
+        // An empty marker backup is written when there is no config yet
+        transaction.write_backup(&backup_path)?;
+        let content = fs::read_to_string(&backup_path)
+            .with_context(|| format!("Failed to read backup file: {:?}", backup_path))?;
+        let metadata = BackupMetadata {
+            origin: self.config_path.clone(),
+            created_at: timestamp,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            sha256: sha256_hex(&content),
+            reason: reason.to_string(),
+        };
+        let metadata_path = backup_metadata_path(&backup_path);
+        let metadata_json = serde_json::to_string_pretty(&metadata).context("Failed to serialize backup metadata")?;
+        fs::write(&metadata_path, metadata_json)
+            .with_context(|| format!("Failed to write backup metadata: {:?}", metadata_path))?;
+        if transaction.original().is_some() {
             info!("Created backup at {:?}", backup_path);
         } else {
-            // Create empty backup as marker
-            fs::write(&backup_path, "# No previous configuration\n")
-                .with_context(|| format!("Failed to create empty backup: {:?}", backup_path))?;
             debug!("Config file does not exist at {:?}, created empty backup", self.config_path);
         }
         
@@ -722,12 +5299,48 @@ impl ConfigManager {
         Ok(backup_path)
     }
     
+    /// List backups with their metadata, newest first
+    pub fn list_backups(&self) -> Result<Vec<BackupEntry>> {
+        let backup_dir = self.backup_dir();
+        if !backup_dir.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let mut backups: Vec<BackupEntry> = fs::read_dir(&backup_dir)
+            .with_context(|| format!("Failed to read backup directory: {:?}", backup_dir))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_str().is_some_and(is_backup_file_name))
+            .map(|entry| {
+                let path = entry.path();
+                let metadata = read_backup_metadata(&path);
+                BackupEntry { path, metadata }
+            })
+            .collect();
+        backups.sort_by(|a, b| {
+            let created = |entry: &BackupEntry| entry.metadata.as_ref().map(|m| m.created_at);
+            created(b).cmp(&created(a)).then_with(|| b.path.cmp(&a.path))
+        });
+        Ok(backups)
+    }
+    
     /// Restore configuration from a backup
+    ///
+    /// Backups with a metadata sidecar are verified against the recorded
+    /// SHA-256 first; a corrupt backup is never restored.
     pub fn restore_from_backup(&self, backup_path: &Path) -> Result<()> {
         if !backup_path.exists() {
             anyhow::bail!("Backup file does not exist: {:?}", backup_path);
         }
         
+        let entry = BackupEntry {
+            path: backup_path.to_path_buf(),
+            metadata: read_backup_metadata(backup_path),
+        };
+        entry.verify()?;
+        if let Some(metadata) = entry.metadata.as_ref().filter(|m| m.origin != self.config_path) {
+            warn!("Backup {:?} was taken from {:?}, restoring to {:?}", backup_path, metadata.origin, self.config_path);
+        }
+        
         // Read the backup content
         let backup_content = fs::read_to_string(backup_path)
             .context("Failed to read backup file")?;
@@ -739,8 +5352,10 @@ impl ConfigManager {
         }
         
         // Write the backup content to the config file
-        fs::write(&self.config_path, backup_content)
+        let replaced = fs::read_to_string(&self.config_path).ok();
+        atomic_write(&self.config_path, &backup_content)
             .context("Failed to restore from backup")?;
+        audit::record(&self.project_root(), "restore-backup", &self.config_path, replaced.as_deref(), Some(&backup_content));
         
         info!("Restored configuration from {:?}", backup_path);
         Ok(())
@@ -749,25 +5364,7 @@ impl ConfigManager {
     /// Clean up old backups, keeping only the most recent ones
     fn cleanup_old_backups(&self) -> Result<()> {
         // Handle both absolute and relative backup paths
-        let backup_dir = if self.config.backup.backup_dir.is_absolute() {
-            self.config.backup.backup_dir.clone()
-        } else {
-            // If relative, it's relative to the config file's parent directory
-            if let Some(parent) = self.config_path.parent() {
-                // Go up one level from .cargo/config.toml to get the project root
-                if let Some(project_root) = parent.parent() {
-                    project_root.join(&self.config.backup.backup_dir)
-                } else {
-                    parent.join(&self.config.backup.backup_dir)
-                }
-            } else {
-                // Fallback to current directory if we can't determine parent
-                std::env::current_dir()?.join(&self.config.backup.backup_dir)
-            }
-        };
-        
-        // Normalize the path to use proper separators for the platform
-        let backup_dir = PathBuf::from(backup_dir.to_string_lossy().replace('/', std::path::MAIN_SEPARATOR_STR));
+        let backup_dir = self.backup_dir();
         
         // Return early if backup dir doesn't exist
         if !backup_dir.exists() {
@@ -777,12 +5374,7 @@ impl ConfigManager {
         let mut backups: Vec<_> = fs::read_dir(&backup_dir)
             .with_context(|| format!("Failed to read backup directory: {:?}", backup_dir))?
             .filter_map(|entry| entry.ok())
-            .filter(|entry| {
-                entry.file_name()
-                    .to_str()
-                    .map(|s| s.starts_with("config_backup_") && s.ends_with(".toml"))
-                    .unwrap_or(false)
-            })
+            .filter(|entry| entry.file_name().to_str().is_some_and(is_backup_file_name))
             .collect();
         
         if backups.len() <= self.config.backup.max_backups {
@@ -801,91 +5393,494 @@ impl ConfigManager {
         for entry in backups.into_iter().take(to_remove) {
             fs::remove_file(entry.path())
                 .context("Failed to remove old backup")?;
+            let _ = fs::remove_file(backup_metadata_path(&entry.path()));
             debug!("Removed old backup: {:?}", entry.path());
         }
         
         Ok(())
     }
     
-    /// Detect the best available linker
-    fn detect_best_linker(&self) -> Option<String> {
-        match crate::mvp::detect_best_linker() {
-            Ok(linker) if linker != "default" => Some(linker),
-            _ => None,
+    /// Detect the best available linker the optimization level and machine policy allow
+    fn detect_best_linker(
+        &self,
+        platform: Platform,
+        linkers: LinkerSelection,
+        report: &mut OptimizationReport,
+    ) -> Option<String> {
+        let rustc = self.toolchain.as_ref().map(|toolchain| toolchain.version);
+        let state = State::load(&self.state_path());
+        let candidates = match &self.installed_commands {
+            Some(installed) => crate::mvp::detect_linkers_on(
+                platform,
+                rustc,
+                |command| installed.iter().any(|installed| installed == command),
+                |_| (None, None),
+            ),
+            None => crate::mvp::detect_linkers_on(platform, rustc, crate::mvp::command_exists, crate::mvp::locate_command),
+        };
+        for candidate in &candidates {
+            debug!(
+                "Linker {}{}: {}",
+                candidate.name,
+                candidate.version.as_deref().map(|version| format!(" ({})", version)).unwrap_or_default(),
+                candidate.reason
+            );
+        }
+        let deny = &self.config.deny;
+        let detect = |allowed: &dyn Fn(&LinkerSpec) -> bool| {
+            // Linkers rolled back after breaking a build are never picked again
+            candidates
+                .iter()
+                .filter(|candidate| candidate.supported)
+                .filter_map(|candidate| linkers::find(&candidate.name))
+                .find(|spec| allowed(spec) && !state.is_incompatible(spec.name) && !deny.denies_linker(spec.name))
+                .map(|spec| spec.name.to_string())
+        };
+        let best = detect(&|spec| linkers.allows(spec));
+        for candidate in candidates.iter().filter(|candidate| candidate.supported && deny.denies_linker(&candidate.name)) {
+            report.suppressed(format!("linker {}", candidate.name), DENIED_LINKER_REASON);
+        }
+        for incompatibility in &state.incompatible_linkers {
+            if linkers::find(&incompatibility.linker).is_some_and(|spec| spec.supports(platform)) {
+                report.skipped(
+                    format!("linker {}", incompatibility.linker),
+                    SkipReason::RolledBack,
+                    format!("rolled back after it broke a build: {}", incompatibility.reason),
+                );
+            }
+        }
+        if linkers != LinkerSelection::Fastest {
+            if let Some(fastest) = detect(&|_| true).filter(|fastest| Some(fastest) != best.as_ref()) {
+                debug!("{} is installed but not selected at this optimization level", fastest);
+            }
+        }
+        let Some(policy) = &self.policy else {
+            return best;
+        };
+        
+        let allowed = detect(&|spec| linkers.allows(spec) && policy.allows_linker(spec.name));
+        if let Some(best) = best.filter(|best| Some(best) != allowed.as_ref()) {
+            let fallback = match &allowed {
+                Some(linker) => format!("using {}", linker),
+                None => "keeping the default linker".to_string(),
+            };
+            report.conflict("linker", format!("{} is not allowed by the machine policy; {}", best, fallback));
+        }
+        allowed
+    }
+    
+    /// The linker shipped with the Rust toolchain, if it links faster than the system one
+    ///
+    /// rust-lld replaces link.exe on Windows, x64 and ARM64. On x86_64 Linux rustc already
+    /// links with it by default from 1.90 (nightly from 1.80), so the best
+    /// move there is a toolchain update, which rustup can do without
+    /// installing anything else.
+    fn toolchain_linker(&self, platform: Platform, report: &mut OptimizationReport) -> Option<&'static str> {
+        match platform {
+            Platform::Windows => {
+                let Some(triple) = self.target_triple(platform) else {
+                    self.skip_unknown_triple("linker rust-lld", platform, report);
+                    return None;
+                };
+                if !linkers::find("rust-lld").is_some_and(|spec| spec.supports_triple(triple)) {
+                    report.skipped("linker rust-lld", SkipReason::UnsupportedToolchain, format!("no rust-lld ships with the {} toolchain", triple));
+                    return None;
+                }
+                if let Some(incompatibility) = State::load(&self.state_path())
+                    .incompatible_linkers
+                    .into_iter()
+                    .find(|incompatibility| incompatibility.linker == "rust-lld")
+                {
+                    report.skipped("linker rust-lld", SkipReason::RolledBack, format!("rolled back after it broke a build: {}", incompatibility.reason));
+                    return None;
+                }
+                if self.policy.as_ref().is_some_and(|policy| !policy.allows_linker("rust-lld")) {
+                    report.conflict("linker", "rust-lld is not allowed by the machine policy; keeping the default linker");
+                    return None;
+                }
+                if self.config.deny.denies_linker("rust-lld") {
+                    report.suppressed("linker rust-lld", DENIED_LINKER_REASON);
+                    return None;
+                }
+                Some("rust-lld")
+            }
+            Platform::Linux => {
+                let toolchain = self.toolchain.clone().or_else(|| ToolchainInfo::detect().ok());
+                let (why, reason) = match toolchain {
+                    Some(toolchain) if links_with_rust_lld(&toolchain) => (
+                        SkipReason::NotNeeded,
+                        format!("rustc {} already links with the bundled rust-lld", toolchain.version),
+                    ),
+                    Some(toolchain) if self.nix_environment().is_some_and(|nix| nix.store_toolchain.is_some()) => {
+                        report.recommend(
+                            "Update the Rust toolchain of the Nix dev shell: rustc 1.90 and later link with the \
+                             bundled rust-lld on x86_64 Linux, with no linker to install",
+                        );
+                        (
+                            SkipReason::UnsupportedToolchain,
+                            format!("rustc {} links with the system linker; no faster one ships with it", toolchain.version),
+                        )
+                    }
+                    Some(toolchain) => {
+                        report.recommend(
+                            "Run `rustup update stable`: rustc 1.90 and later link with the bundled rust-lld \
+                             on x86_64 Linux, with no linker to install",
+                        );
+                        (
+                            SkipReason::UnsupportedToolchain,
+                            format!("rustc {} links with the system linker; no faster one ships with it", toolchain.version),
+                        )
+                    }
+                    None => (SkipReason::UnsupportedToolchain, "rustc version unknown; keeping the default linker".to_string()),
+                };
+                report.skipped("linker", why, reason);
+                None
+            }
+            _ => {
+                report.skipped(
+                    "linker",
+                    SkipReason::UserOptOut,
+                    format!("no linker ships with the Rust toolchain for {}; {}", platform.name(), NO_EXTERNAL_TOOLS_REASON),
+                );
+                None
+            }
         }
     }
     
+    /// Machine policy the applies comply with, if one is installed
+    pub fn policy(&self) -> Option<&Policy> {
+        self.policy.as_ref()
+    }
+    
     /// Get the current configuration
     pub fn config(&self) -> &Config {
         &self.config
     }
     
+    /// Deprecated keys found in cargo-optimize.toml while loading
+    pub fn deprecations(&self) -> &[DeprecationWarning] {
+        &self.deprecations
+    }
+    
+    /// Context whose `[contexts.<name>]` settings were applied
+    pub fn context(&self) -> BuildContext {
+        self.context
+    }
+    
     /// Get a mutable reference to the configuration
     pub fn config_mut(&mut self) -> &mut Config {
         &mut self.config
     }
-    
-    /// Read config file with retry for Windows file locking
-    fn read_config_with_retry(&self) -> Result<String> {
-        let mut retries = 3;
-        let mut last_error = None;
-        
-        while retries > 0 {
-            match fs::read_to_string(&self.config_path) {
-                Ok(content) => return Ok(content),
-                Err(e) => {
-                    last_error = Some(e);
-                    retries -= 1;
-                    if retries > 0 {
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
+}
+
+/// `CARGO_TARGET_DIR` from the environment, if set and not empty
+fn target_dir_from_env() -> Option<PathBuf> {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The `[contexts.<name>]` section of `cargo-optimize.toml` as a config layer
+fn context_settings(config_file: &Path, context: BuildContext) -> Figment {
+    Figment::from(Toml::file(config_file))
+        .merge(deprecation::renamed_settings(config_file))
+        .focus(&format!("contexts.{}", context.name()))
+}
+
+/// Convert a profile setting to the TOML type cargo expects
+///
+/// Cargo rejects `opt-level = "3"` and `lto = "true"`, so numbers and booleans
+/// are written unquoted; everything else ("s", "thin", "abort") stays a string.
+pub(crate) fn profile_value(value: &str) -> toml_edit::Value {
+    if let Ok(number) = value.parse::<i64>() {
+        number.into()
+    } else if let Ok(flag) = value.parse::<bool>() {
+        flag.into()
+    } else {
+        value.into()
+    }
+}
+
+/// Value carrying the explanation id of `setting` as a trailing comment
+///
+/// The id lets users look up any managed line with [`explain::explain`].
+fn explained(value: impl Into<toml_edit::Value>, setting: &str) -> Item {
+    let mut value = value.into();
+    if let Some(explanation) = explain::for_setting(setting) {
+        value.decor_mut().set_suffix(explanation.comment());
+    }
+    Item::Value(value)
+}
+
+/// Value carrying explanation `id`, for settings several explanations cover
+fn explained_by(value: impl Into<toml_edit::Value>, id: &str) -> Item {
+    let mut value = value.into();
+    if let Some(explanation) = explain::find(id) {
+        value.decor_mut().set_suffix(explanation.comment());
+    }
+    Item::Value(value)
+}
+
+/// Value carrying an explanation id and a note on how the setting works
+fn explained_with_note(value: impl Into<toml_edit::Value>, id: &str, note: &str) -> Item {
+    let mut value = value.into();
+    if let Some(explanation) = explain::find(id) {
+        value.decor_mut().set_suffix(explanation.comment_with(note));
+    }
+    Item::Value(value)
+}
+
+/// Build a TOML array of strings
+fn string_array(values: &[String]) -> toml_edit::Array {
+    let mut array = toml_edit::Array::new();
+    for value in values {
+        array.push(value.as_str());
+    }
+    array
+}
+
+/// Explanation id marking `profile.<name>.rustflags` written by cargo-optimize
+const PROFILE_RUSTFLAGS_ID: &str = "CO-RUSTFLAGS-001";
+
+/// Mechanism note for per-profile rustflags
+const PROFILE_RUSTFLAGS_NOTE: &str = "via [unstable] profile-rustflags, nightly only";
+
+/// Explanation id of profile rustflags written to the target table on stable
+const SHARED_RUSTFLAGS_ID: &str = "CO-RUSTFLAGS-002";
+
+/// Mechanism note for the stable fallback
+const SHARED_RUSTFLAGS_NOTE: &str = "stable fallback: rustflags shared by all profiles";
+
+/// `-C` options cargo already derives from profile settings
+const PROFILE_CODEGEN_OPTIONS: &[&str] = &[
+    "opt-level",
+    "lto",
+    "codegen-units",
+    "debuginfo",
+    "panic",
+    "strip",
+    "incremental",
+    "debug-assertions",
+    "overflow-checks",
+];
+
+/// Split profile rustflags into flags to write and `-C` options a profile key controls
+///
+/// Passing e.g. `-C lto` through rustflags conflicts with the flags cargo
+/// itself derives from the profile, so those are dropped.
+fn split_profile_rustflags(flags: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut passthrough = Vec::new();
+    let mut redundant = Vec::new();
+    let mut iter = flags.iter();
+    while let Some(flag) = iter.next() {
+        let (option, tokens) = if flag == "-C" {
+            match iter.next() {
+                Some(value) => (value.as_str(), vec![flag.clone(), value.clone()]),
+                None => {
+                    passthrough.push(flag.clone());
+                    continue;
                 }
             }
+        } else if let Some(option) = flag.strip_prefix("-C") {
+            (option, vec![flag.clone()])
+        } else {
+            passthrough.push(flag.clone());
+            continue;
+        };
+        let key = option.split('=').next().unwrap_or(option);
+        if PROFILE_CODEGEN_OPTIONS.contains(&key) {
+            redundant.push(tokens.join(" "));
+        } else {
+            passthrough.extend(tokens);
+        }
+    }
+    (passthrough, redundant)
+}
+
+/// Split flags into those stable rustc accepts and `-Z` flags, which need nightly
+fn split_unstable_flags(flags: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut stable = Vec::new();
+    let mut unstable = Vec::new();
+    let mut iter = flags.iter();
+    while let Some(flag) = iter.next() {
+        if flag == "-Z" {
+            unstable.push(iter.next().map(|value| format!("-Z {}", value)).unwrap_or_else(|| flag.clone()));
+        } else if flag.starts_with("-Z") {
+            unstable.push(flag.clone());
+        } else {
+            stable.push(flag.clone());
+        }
+    }
+    (stable, unstable)
+}
+
+/// Position of `run` as consecutive elements of `values`, compared one by one
+fn find_run(values: &[String], run: &[String]) -> Option<usize> {
+    if run.is_empty() {
+        return None;
+    }
+    values.windows(run.len()).position(|window| window == run)
+}
+
+/// Remove the flags the stable fallback added to `target.<triple>.rustflags`
+///
+/// Only the recorded flags, in the recorded order, are taken out; what the
+/// user or the linker configuration put in the array stays. The array and
+/// target table go once they are empty.
+fn remove_shared_rustflags(doc: &mut DocumentMut, shared: &SharedRustflags) {
+    let Some(targets) = doc.get_mut("target").and_then(Item::as_table_like_mut) else {
+        return;
+    };
+    let Some(target) = targets.get_mut(&shared.target).and_then(Item::as_table_like_mut) else {
+        return;
+    };
+    let Some(array) = target.get_mut("rustflags").and_then(Item::as_array_mut) else {
+        return;
+    };
+    let values: Vec<String> = array.iter().map(|value| value.as_str().unwrap_or_default().to_string()).collect();
+    let Some(start) = find_run(&values, &shared.flags) else {
+        return;
+    };
+    for _ in 0..shared.flags.len() {
+        array.remove(start);
+    }
+    if array.is_empty() {
+        target.remove("rustflags");
+    } else if let Some(value) = target.get_mut("rustflags").and_then(Item::as_value_mut) {
+        // What is left is not ours to explain
+        if value.decor().suffix().and_then(|suffix| suffix.as_str()).is_some_and(|suffix| suffix.contains(SHARED_RUSTFLAGS_ID)) {
+            value.decor_mut().set_suffix("");
         }
-        
-        Err(anyhow::anyhow!("Failed to read config after retries: {:?}", last_error))
     }
+    if target.is_empty() {
+        targets.remove(&shared.target);
+    }
+    if targets.is_empty() {
+        doc.remove("target");
+    }
+}
+
+/// Remove per-profile rustflags written by cargo-optimize, except for `keep`
+///
+/// `[unstable] profile-rustflags` goes too once no profile keeps its flags.
+fn remove_managed_profile_rustflags(doc: &mut DocumentMut, keep: &[&str]) {
+    let is_managed = |item: Option<&Item>| {
+        item.and_then(Item::as_value)
+            .and_then(|value| value.decor().suffix())
+            .and_then(|suffix| suffix.as_str())
+            .is_some_and(|suffix| suffix.contains(PROFILE_RUSTFLAGS_ID))
+    };
     
-    /// Write config file with retry for Windows file locking
-    fn write_config_with_retry(&self, content: &str) -> Result<()> {
-        let mut retries = 3;
-        let mut last_error = None;
-        
-        while retries > 0 {
-            match fs::write(&self.config_path, content) {
-                Ok(_) => return Ok(()),
-                Err(e) => {
-                    last_error = Some(e);
-                    retries -= 1;
-                    if retries > 0 {
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
+    if let Some(profiles) = doc.get_mut("profile").and_then(Item::as_table_like_mut) {
+        for (name, profile) in profiles.iter_mut() {
+            if keep.contains(&name.get()) {
+                continue;
+            }
+            if let Some(profile) = profile.as_table_like_mut() {
+                if is_managed(profile.get("rustflags")) {
+                    profile.remove("rustflags");
                 }
             }
         }
-        
-        Err(anyhow::anyhow!("Failed to write config after retries: {:?}", last_error))
+    }
+    
+    if keep.is_empty() {
+        let managed_table = doc
+            .get("unstable")
+            .and_then(Item::as_table)
+            .and_then(|table| table.decor().prefix())
+            .and_then(|prefix| prefix.as_str())
+            .is_some_and(|prefix| prefix.contains(MANAGED_UNSTABLE_MARKER));
+        if let Some(unstable) = doc.get_mut("unstable").and_then(Item::as_table_mut) {
+            if is_managed(unstable.get("profile-rustflags")) {
+                unstable.remove("profile-rustflags");
+            }
+            if managed_table && unstable.is_empty() {
+                doc.remove("unstable");
+            }
+        }
+    }
+}
+
+/// Remove the `[unstable]` keys (and `build.target`) written by cargo-optimize
+///
+/// Returns true if anything was removed. Tables or keys without our marker
+/// are left untouched.
+fn remove_managed_unstable(doc: &mut DocumentMut) -> bool {
+    let mut removed = false;
+    
+    let managed_table = doc
+        .get("unstable")
+        .and_then(Item::as_table)
+        .map(|table| {
+            table.decor().prefix()
+                .and_then(|prefix| prefix.as_str())
+                .map(|prefix| prefix.contains(MANAGED_UNSTABLE_MARKER))
+                .unwrap_or(false)
+        })
+        .unwrap_or(false);
+    
+    if managed_table {
+        if let Some(unstable) = doc["unstable"].as_table_mut() {
+            removed |= unstable.remove("build-std").is_some();
+            removed |= unstable.remove("build-std-features").is_some();
+            if unstable.is_empty() {
+                doc.remove("unstable");
+            }
+        }
+    }
+    
+    let managed_target = doc
+        .get("build")
+        .and_then(|build| build.get("target"))
+        .and_then(Item::as_value)
+        .and_then(|value| value.decor().suffix())
+        .and_then(|suffix| suffix.as_str())
+        .map(|suffix| suffix.contains(explain::COMMENT_PREFIX) && suffix.contains(MANAGED_TARGET_TAG))
+        .unwrap_or(false);
+    
+    if managed_target {
+        if let Some(build) = doc["build"].as_table_mut() {
+            build.remove("target");
+            removed = true;
+        }
+    }
+    
+    removed
+}
+
+/// Default sccache cache directory for the current platform
+fn default_sccache_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("Mozilla").join("sccache"))
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join("Library").join("Caches").join("Mozilla.sccache"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".cache")))
+            .map(|dir| dir.join("sccache"))
     }
 }
 
 impl Config {
     /// Apply hardware-based optimizations
     pub fn apply_hardware_optimizations(&mut self) -> Result<()> {
-        use sysinfo::System;
-        
-        let mut sys = System::new_all();
-        sys.refresh_all();
-        
-        let cpu_cores = num_cpus::get();
-        let total_memory_mb = (sys.total_memory() / 1024) as usize;
+        self.apply_hardware_optimizations_for(num_cpus::get(), hardware::system::total_memory());
+        Ok(())
+    }
+    
+    /// Apply hardware-based defaults for the given core count and total memory
+    pub fn apply_hardware_optimizations_for(&mut self, cpu_cores: usize, total_memory: u64) {
+        let total_memory_mb = (total_memory / 1024) as usize;
         
         info!("Detected {} CPU cores, {}MB RAM", cpu_cores, total_memory_mb);
         
-        // Apply to all profiles that don't have explicit job counts
+        // Apply the level's share of the cores to profiles without explicit job counts
+        let jobs = self.global.optimization_level.preset().jobs;
         for profile in self.profiles.values_mut() {
             if profile.jobs.is_none() {
-                // Use 75% of cores by default
-                profile.jobs = Some(JobCount::Percentage("75%".to_string()));
+                profile.jobs = Some(JobCount::Percentage(jobs.to_string()));
             }
             
             // Adjust cache size based on available memory
@@ -895,8 +5890,6 @@ impl Config {
                 profile.cache.max_size = Some(CacheSize::Megabytes(cache_size));
             }
         }
-        
-        Ok(())
     }
     
     /// Get a profile by name
@@ -910,68 +5903,30 @@ impl Config {
     }
     
     /// Generate linker configuration for Cargo
+    ///
+    /// Known fast linkers come from the [`linkers`] registry; the platform's
+    /// system linkers are accepted as well and written without rustflags.
     pub fn generate_linker_config(&self, linker: &str) -> Result<String, ConfigError> {
-        // Validate linker is supported
-        let supported_linkers = if cfg!(target_os = "windows") {
-            vec!["rust-lld", "lld-link.exe", "lld", "link.exe"]
-        } else if cfg!(target_os = "linux") {
-            vec!["mold", "lld", "gold", "ld"]
-        } else if cfg!(target_os = "macos") {
-            vec!["zld", "lld", "ld64"]
-        } else {
-            vec!["lld"]
+        let unsupported = || {
+            ConfigError::Other(anyhow::anyhow!("Unsupported linker '{}' for current platform", linker))
         };
+        let platform = Platform::current().ok_or_else(unsupported)?;
+        let system_linkers = platform.capabilities().system_linkers;
         
-        if !supported_linkers.contains(&linker) {
-            return Err(ConfigError::Other(anyhow::anyhow!(
-                "Unsupported linker '{}' for current platform", linker
-            )));
-        }
-        
-        let mut config = String::new();
-        
-        // Add target-specific configuration
-        if cfg!(target_os = "windows") {
-            config.push_str("[target.x86_64-pc-windows-msvc]\n");
-            config.push_str(&format!("linker = \"{}\"\n", linker));
-            
-            // Add rustflags for specific linkers
-            match linker {
-                "rust-lld" => {
-                    config.push_str("rustflags = [\"-C\", \"link-arg=-fuse-ld=lld\"]\n");
-                }
-                "lld-link.exe" => {
-                    config.push_str("rustflags = [\"-C\", \"linker=lld-link.exe\"]\n");
-                }
-                _ => {}
-            }
-        } else if cfg!(target_os = "linux") {
-            config.push_str("[target.x86_64-unknown-linux-gnu]\n");
-            config.push_str("linker = \"clang\"\n");
-            
-            // Add rustflags for specific linkers
-            match linker {
-                "mold" => {
-                    config.push_str("rustflags = [\"-C\", \"link-arg=-fuse-ld=mold\"]\n");
-                }
-                "lld" => {
-                    config.push_str("rustflags = [\"-C\", \"link-arg=-fuse-ld=lld\"]\n");
-                }
-                "gold" => {
-                    config.push_str("rustflags = [\"-C\", \"link-arg=-fuse-ld=gold\"]\n");
-                }
-                _ => {}
-            }
-        } else if cfg!(target_os = "macos") {
-            config.push_str("[target.x86_64-apple-darwin]\n");
-            config.push_str(&format!("linker = \"{}\"\n", linker));
-            
-            if linker == "zld" {
-                config.push_str("rustflags = [\"-C\", \"link-arg=-fuse-ld=zld\"]\n");
-            }
-        }
+        let spec = linkers::find(linker).filter(|spec| spec.supports(platform));
+        let linker_value = match spec.map(|spec| spec.invocation) {
+            Some(Invocation::Direct(executable)) => executable.to_string(),
+            // Fall back to the system `cc` when no driver can be confirmed
+            Some(Invocation::Driver) => crate::mvp::select_linker_driver(linker)
+                .driver
+                .unwrap_or_else(|| "cc".to_string()),
+            None if system_linkers.contains(&linker) => linker.to_string(),
+            None => return Err(unsupported()),
+        };
         
-        Ok(config)
+        let rustflags = spec.map(|spec| spec.rustflags()).unwrap_or_default();
+        let triple = platform.target_triple().ok_or_else(unsupported)?;
+        Ok(linkers::target_table(triple, &linker_value, &rustflags, ""))
     }
 }
 
@@ -982,6 +5937,8 @@ impl Default for Config {
             global: GlobalSettings::default(),
             backup: BackupConfig::default(),
             metadata: ConfigMetadata::default(),
+            deny: DenyList::default(),
+            analysis: AnalysisSettings::default(),
         }
     }
 }
@@ -991,6 +5948,7 @@ impl Profile {
     pub fn default_for_name(name: String) -> Self {
         Profile {
             name: name.clone(),
+            inherits: None,
             linker: None,
             jobs: None,
             incremental: if name == "dev" || name == "test" {
@@ -1001,11 +5959,40 @@ impl Profile {
             rustflags: Vec::new(),
             cache: CacheSettings::default(),
             target_dir: None,
+            opt_level: None,
+            lto: None,
+            codegen_units: None,
+            panic: None,
+            strip: None,
+            debug: None,
+            build_override_opt_level: None,
         }
     }
 }
 
 impl Config {
+    /// Name profiles after their key and add the custom profiles of the project's `Cargo.toml`
+    ///
+    /// Custom profiles found in the manifest carry no settings, so nothing is
+    /// written for them unless `cargo-optimize.toml` sets some under
+    /// `[profiles.<name>]`.
+    pub fn complete_profiles(&mut self, project_root: &Path) {
+        for (name, profile) in &mut self.profiles {
+            if profile.name.is_empty() {
+                profile.name = name.clone();
+            }
+        }
+        let Ok(manifest) = fs::read_to_string(project_root.join("Cargo.toml")) else {
+            return;
+        };
+        for name in custom_profiles(&manifest).into_keys() {
+            self.profiles.entry(name.clone()).or_insert_with(|| Profile {
+                incremental: None,
+                ..Profile::default_for_name(name)
+            });
+        }
+    }
+    
     /// Create default profiles for dev, test, release, and bench
     fn default_profiles() -> HashMap<String, Profile> {
         let mut profiles = HashMap::new();
@@ -1024,6 +6011,7 @@ impl Config {
                 cache_type: CacheType::Sccache,
             },
             target_dir: None,
+            ..Profile::default_for_name(String::new())
         });
         
         // Test profile - balanced for test execution
@@ -1040,6 +6028,7 @@ impl Config {
                 cache_type: CacheType::Sccache,
             },
             target_dir: None,
+            ..Profile::default_for_name(String::new())
         });
         
         // Release profile - maximum optimization
@@ -1061,6 +6050,7 @@ impl Config {
                 cache_type: CacheType::Sccache,
             },
             target_dir: None,
+            ..Profile::default_for_name(String::new())
         });
         
         // Bench profile - optimized for benchmarking
@@ -1077,6 +6067,7 @@ impl Config {
                 cache_type: CacheType::None,
             },
             target_dir: None,
+            ..Profile::default_for_name(String::new())
         });
         
         profiles
@@ -1089,9 +6080,37 @@ impl Default for GlobalSettings {
             optimization_level: OptimizationLevel::Balanced,
             auto_detect_hardware: true,
             verbose: false,
-            use_sccache: true,
+            use_sccache: OptimizationLevel::Balanced.preset().sccache,
             env_vars: HashMap::new(),
             default_jobs: None,
+            min_free_disk_gb: default_min_free_disk_gb(),
+            build_std: None,
+            respect_msrv: false,
+            template: None,
+            allow_installs: false,
+            auto_migrate: false,
+            queue_when_busy: false,
+            probe_phases: false,
+            target_dir_layout: TargetDirLayout::Shared,
+            member_groups: BTreeMap::new(),
+            target_dir_env_policy: TargetDirEnvPolicy::Respect,
+            memory_pressure_policy: MemoryPressurePolicy::Recommend,
+            adaptive_jobs: None,
+            wrapper_scope: WrapperScope::default(),
+            dev_drive_policy: DevDrivePolicy::Recommend,
+            no_external_tools: false,
+            gitoxide: false,
+            target_dir_migration: MigrationMode::default(),
+            linker_install: LinkerInstallSettings::default(),
+            proxy: ProxySettings::default(),
+            build_weight_warn_ratio: default_build_weight_warn_ratio(),
+            required: Vec::new(),
+            configure_nextest: default_configure_nextest(),
+            pull_request_mode: false,
+            sccache_min_hit_rate: default_sccache_min_hit_rate(),
+            sccache_auto_repair: false,
+            sccache_dist_scheduler: None,
+            sccache_backend: BTreeMap::new(),
         }
     }
 }
@@ -1126,15 +6145,9 @@ impl Default for ConfigMetadata {
                 .duration_since(UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
-            platform: if cfg!(target_os = "windows") {
-                "windows".to_string()
-            } else if cfg!(target_os = "linux") {
-                "linux".to_string()
-            } else if cfg!(target_os = "macos") {
-                "macos".to_string()
-            } else {
-                "unknown".to_string()
-            },
+            platform: Platform::current()
+                .map(|platform| platform.name().to_lowercase())
+                .unwrap_or_else(|| "unknown".to_string()),
             hash: None,
         }
     }
@@ -1182,6 +6195,42 @@ mod tests {
         );
     }
     
+    #[test]
+    fn test_presets_cover_every_level() {
+        let levels: Vec<_> = presets().iter().map(|preset| preset.level).collect();
+        assert_eq!(
+            levels,
+            [OptimizationLevel::Conservative, OptimizationLevel::Balanced, OptimizationLevel::Aggressive]
+        );
+        for preset in presets() {
+            assert_eq!(preset.level.preset(), preset);
+        }
+        assert_eq!(GlobalSettings::default().use_sccache, OptimizationLevel::Balanced.preset().sccache);
+        
+        let mold = linkers::find("mold").unwrap();
+        assert!(!OptimizationLevel::Conservative.preset().linkers.allows(mold));
+        assert!(OptimizationLevel::Balanced.preset().linkers.allows(mold));
+        assert!(LinkerSelection::Proven.allows(linkers::find("lld").unwrap()));
+    }
+    
+    #[test]
+    fn test_preset_defaults_and_fill_in() {
+        let mut config = Config::default();
+        config.global.optimization_level = OptimizationLevel::Conservative;
+        config.apply_hardware_optimizations_for(8, 16 << 30);
+        assert_eq!(config.profiles["dev"].jobs, Some(JobCount::Percentage("50%".to_string())));
+        
+        let mut config = Config::default();
+        config.profiles.get_mut("release").unwrap().lto = Some("thin".to_string());
+        OptimizationLevel::Aggressive.preset().apply_to(&mut config);
+        OptimizationLevel::Aggressive.preset().apply_to(&mut config);
+        assert_eq!(config.profiles["dev"].debug.as_deref(), Some("line-tables-only"));
+        assert_eq!(config.profiles["release"].lto.as_deref(), Some("thin"));
+        let rustflags = &config.profiles["release"].rustflags;
+        assert!(rustflags.ends_with(&["-C".to_string(), "target-cpu=native".to_string()]));
+        assert_eq!(rustflags.iter().filter(|flag| flag.contains("target-cpu")).count(), 1);
+    }
+    
     #[test]
     fn test_profile_defaults() {
         let config = Config::default();
@@ -0,0 +1,262 @@
+//! Renamed keys of `cargo-optimize.toml`.
+//!
+//! When a key is renamed, its old name stays in [`DEPRECATIONS`] until the
+//! version it is removed in. Loading reads a deprecated key as if it had its
+//! new name (unless the new one is set as well) and warns with the removal
+//! version. With `auto_migrate = true` under `[global]`, [`check`] rewrites
+//! the file to the new names instead, keeping comments and formatting. Keys
+//! inside `[contexts.<name>]` sections are handled the same way.
+
+use anyhow::{Context, Result};
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Key, Table};
+use tracing::warn;
+
+use super::{insert_at, item_at};
+use crate::audit;
+use crate::transaction::atomic_write;
+
+/// A key of `cargo-optimize.toml` that was renamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Old dotted key
+    pub old: &'static str,
+    /// Dotted key replacing it
+    pub new: &'static str,
+    /// Version the old key was deprecated in
+    pub since: &'static str,
+    /// Version that stops reading the old key
+    pub removal: &'static str,
+}
+
+/// Every renamed key
+pub const DEPRECATIONS: &[Deprecation] = &[];
+
+/// A deprecated key found in a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecationWarning {
+    /// Key as found, including a `contexts.<name>` prefix
+    pub key: String,
+    /// Key to use instead, with the same prefix
+    pub replacement: String,
+    /// The renaming
+    pub deprecation: Deprecation,
+    /// Whether the file was rewritten to the new key
+    pub migrated: bool,
+}
+
+impl fmt::Display for DeprecationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.migrated {
+            return write!(f, "`{}` in cargo-optimize.toml was renamed to `{}`", self.key, self.replacement);
+        }
+        write!(
+            f,
+            "`{}` in cargo-optimize.toml is deprecated since {} and will stop working in {}; rename it to `{}` \
+             or set `auto_migrate = true` under [global]",
+            self.key, self.deprecation.since, self.deprecation.removal, self.replacement
+        )
+    }
+}
+
+/// Keys of `doc` renamed by one of `deprecations`
+pub fn find_deprecated(doc: &DocumentMut, deprecations: &[Deprecation]) -> Vec<DeprecationWarning> {
+    let mut warnings = Vec::new();
+    for (prefix, table) in sections(doc) {
+        for deprecation in deprecations {
+            if item_at(table, &keys(deprecation.old)).is_some() {
+                warnings.push(DeprecationWarning {
+                    key: format!("{}{}", prefix, deprecation.old),
+                    replacement: format!("{}{}", prefix, deprecation.new),
+                    deprecation: *deprecation,
+                    migrated: false,
+                });
+            }
+        }
+    }
+    warnings
+}
+
+/// Rename keys of `doc` renamed by one of `deprecations`, keeping their values and comments
+///
+/// Where the new key is set too, it wins and the old one is dropped.
+pub fn migrate(doc: &mut DocumentMut, deprecations: &[Deprecation]) -> Vec<DeprecationWarning> {
+    let mut warnings = find_deprecated(doc, deprecations);
+    for warning in &mut warnings {
+        let old = keys(&warning.key);
+        let new = keys(&warning.replacement);
+        if item_at(doc.as_table(), &new).is_some() {
+            take_at(doc.as_table_mut(), &old);
+        } else {
+            move_at(doc.as_table_mut(), &old, &new);
+        }
+        warning.migrated = true;
+    }
+    warnings
+}
+
+/// Deprecated keys of `config_file`, renamed in place when `auto_migrate` is set
+pub fn check(config_file: &Path, auto_migrate: bool) -> Result<Vec<DeprecationWarning>> {
+    let Some(content) = fs::read_to_string(config_file).ok() else {
+        return Ok(Vec::new());
+    };
+    let mut doc = content
+        .parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {:?}", config_file))?;
+    if !auto_migrate {
+        return Ok(find_deprecated(&doc, DEPRECATIONS));
+    }
+    let warnings = migrate(&mut doc, DEPRECATIONS);
+    if !warnings.is_empty() {
+        let migrated = doc.to_string();
+        atomic_write(config_file, &migrated)?;
+        let project_root = config_file.parent().unwrap_or(Path::new("."));
+        audit::record(project_root, "auto-migrate", config_file, Some(&content), Some(&migrated));
+    }
+    Ok(warnings)
+}
+
+/// Check `config_file` for deprecated keys while loading, warning about each
+///
+/// A file that cannot be rewritten only costs the migration.
+pub(crate) fn warn_deprecated(config_file: &Path, auto_migrate: bool) -> Vec<DeprecationWarning> {
+    let warnings = check(config_file, auto_migrate).unwrap_or_else(|e| {
+        warn!("Failed to migrate deprecated keys of {:?}: {:#}", config_file, e);
+        Vec::new()
+    });
+    for warning in &warnings {
+        warn!(
+            key = %warning.key,
+            replacement = %warning.replacement,
+            removal = warning.deprecation.removal,
+            migrated = warning.migrated,
+            "{}",
+            warning
+        );
+    }
+    warnings
+}
+
+/// Values of deprecated keys in `config_file` under their new names, as a layer
+///
+/// Merged right above the file, so the old key applies wherever the new one
+/// is not set.
+pub(crate) fn renamed_settings(config_file: &Path) -> Figment {
+    let Some(doc) = fs::read_to_string(config_file).ok().and_then(|content| content.parse::<DocumentMut>().ok()) else {
+        return Figment::new();
+    };
+    let mut renamed = DocumentMut::new();
+    for warning in find_deprecated(&doc, DEPRECATIONS) {
+        let new = keys(&warning.replacement);
+        if item_at(doc.as_table(), &new).is_some() {
+            continue;
+        }
+        if let Some(item) = item_at(doc.as_table(), &keys(&warning.key)) {
+            insert_at(renamed.as_table_mut(), &new, item.clone());
+        }
+    }
+    Figment::from(Toml::string(&renamed.to_string()))
+}
+
+/// The top level and every `[contexts.<name>]` table, with their key prefix
+fn sections(doc: &DocumentMut) -> Vec<(String, &dyn toml_edit::TableLike)> {
+    let mut sections: Vec<(String, &dyn toml_edit::TableLike)> = vec![(String::new(), doc.as_table())];
+    if let Some(contexts) = doc.get("contexts").and_then(Item::as_table_like) {
+        for (name, section) in contexts.iter() {
+            if let Some(section) = section.as_table_like() {
+                sections.push((format!("contexts.{}.", name), section));
+            }
+        }
+    }
+    sections
+}
+
+fn keys(dotted: &str) -> Vec<&str> {
+    dotted.split('.').collect()
+}
+
+/// The table at `path`
+fn table_at_mut<'a>(table: &'a mut Table, path: &[&str]) -> Option<&'a mut Table> {
+    path.iter().try_fold(table, |table, key| table.get_mut(key)?.as_table_mut())
+}
+
+/// Remove the item at `path`
+fn take_at(table: &mut Table, path: &[&str]) -> Option<Item> {
+    let (leaf, parents) = path.split_last()?;
+    table_at_mut(table, parents)?.remove(leaf)
+}
+
+/// Move the item at `old` to `new`, renaming it in place when both share a table
+fn move_at(table: &mut Table, old: &[&str], new: &[&str]) {
+    let (Some((old_leaf, old_parents)), Some((new_leaf, new_parents))) = (old.split_last(), new.split_last()) else {
+        return;
+    };
+    if old_parents != new_parents {
+        if let Some(item) = take_at(table, old) {
+            insert_at(table, new, item);
+        }
+        return;
+    }
+    let Some(parent) = table_at_mut(table, old_parents) else {
+        return;
+    };
+    // Tables keep keys in insertion order, so re-insert all of them
+    let names: Vec<String> = parent.iter().map(|(key, _)| key.to_string()).collect();
+    let entries: Vec<(Key, Item)> = names.iter().filter_map(|name| parent.remove_entry(name)).collect();
+    for (key, item) in entries {
+        if key.get() == *old_leaf {
+            parent.insert_formatted(&Key::new(*new_leaf).with_leaf_decor(key.leaf_decor().clone()), item);
+        } else {
+            parent.insert_formatted(&key, item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENAMED: &[Deprecation] = &[Deprecation {
+        old: "global.old_flag",
+        new: "global.new_flag",
+        since: "0.2.0",
+        removal: "0.4.0",
+    }];
+
+    #[test]
+    fn test_find_deprecated() {
+        let doc = "[global]\nold_flag = true\n\n[contexts.ci.global]\nold_flag = false\n\n[contexts.local.global]\nnew_flag = true\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        let warnings = find_deprecated(&doc, RENAMED);
+        let found: Vec<(&str, &str)> =
+            warnings.iter().map(|warning| (warning.key.as_str(), warning.replacement.as_str())).collect();
+        assert_eq!(
+            found,
+            [("global.old_flag", "global.new_flag"), ("contexts.ci.global.old_flag", "contexts.ci.global.new_flag")]
+        );
+        assert!(warnings.iter().all(|warning| !warning.migrated));
+        assert!(warnings[0].to_string().contains("deprecated since 0.2.0 and will stop working in 0.4.0"), "{}", warnings[0]);
+        assert!(find_deprecated(&doc, DEPRECATIONS).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_keeps_formatting() {
+        let mut doc = "[global]\n# set for CI\nold_flag = true # needed\nverbose = false\n\n\
+                       [contexts.ci.global]\nold_flag = false\nnew_flag = true\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        let warnings = migrate(&mut doc, RENAMED);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().all(|warning| warning.migrated));
+        let migrated = doc.to_string();
+        assert!(migrated.contains("# set for CI\nnew_flag = true # needed\nverbose = false"), "{}", migrated);
+        assert!(!migrated.contains("old_flag"), "{}", migrated);
+        // The new key set in the context section wins over the old one
+        assert_eq!(doc["contexts"]["ci"]["global"]["new_flag"].as_bool(), Some(true));
+    }
+}
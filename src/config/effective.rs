@@ -0,0 +1,214 @@
+//! Effective configuration with the source of every setting.
+//!
+//! Settings come from five layers: built-in defaults, `cargo-optimize.toml`,
+//! its `[contexts.<name>]` section for the active context, its
+//! `[toolchain."<selector>"]` sections matching the active toolchain, and
+//! environment variables such as `CARGO_OPTIMIZE_GLOBAL__DEFAULT_JOBS`. Loading then
+//! completes the profiles (names, custom profiles of `Cargo.toml`) and fills
+//! in hardware-based defaults. [`effective`] returns the result together
+//! with where each value came from, to find out why a setting is not doing
+//! what one expected.
+
+use anyhow::{anyhow, Result};
+use figment::providers::{Env, Format, Toml};
+use figment::Figment;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use super::toolchain_overrides::{self, ToolchainSelector};
+use super::{context_settings, deprecation, BuildContext, Config, ConfigManager, EnvSource};
+use crate::toolchain::ToolchainSpec;
+
+/// Where an effective setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// `cargo-optimize.toml`
+    File(PathBuf),
+    /// The `[contexts.<name>]` section of `cargo-optimize.toml`
+    Context {
+        /// The config file
+        file: PathBuf,
+        /// Context whose section applied
+        context: BuildContext,
+    },
+    /// A `[toolchain."<selector>"]` section of `cargo-optimize.toml`
+    Toolchain {
+        /// The config file
+        file: PathBuf,
+        /// Selector of the last matching section setting the value
+        selector: ToolchainSelector,
+    },
+    /// An environment variable, by name
+    Env(String),
+    /// Filled in while loading (e.g. "hardware detection")
+    Derived(&'static str),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => f.write_str("default"),
+            ConfigSource::File(file) => write!(f, "file {}", file.display()),
+            ConfigSource::Context { file, context } => write!(f, "[contexts.{}] in {}", context, file.display()),
+            ConfigSource::Toolchain { file, selector } => {
+                write!(f, "[toolchain.\"{}\"] in {}", selector, file.display())
+            }
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+            ConfigSource::Derived(step) => f.write_str(step),
+        }
+    }
+}
+
+/// One setting of the effective configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectiveSetting {
+    /// Dotted key (e.g. "global.default_jobs")
+    pub key: String,
+    /// Value as TOML (strings quoted)
+    pub value: String,
+    /// Layer the value came from
+    pub source: ConfigSource,
+}
+
+impl fmt::Display for EffectiveSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={} from {}", self.key, self.value, self.source)
+    }
+}
+
+/// The merged configuration and the source of each setting
+#[derive(Debug, Clone)]
+pub struct EffectiveConfig {
+    /// The configuration a [`ConfigManager`] would use
+    pub config: Config,
+    /// Context whose `[contexts.<name>]` section applied
+    pub context: BuildContext,
+    /// Every setting, sorted by key
+    pub settings: Vec<EffectiveSetting>,
+}
+
+impl EffectiveConfig {
+    /// The setting at `key`
+    pub fn get(&self, key: &str) -> Option<&EffectiveSetting> {
+        self.settings.iter().find(|setting| setting.key == key)
+    }
+
+    /// One line per setting
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for setting in &self.settings {
+            let _ = writeln!(out, "{}", setting);
+        }
+        out
+    }
+}
+
+/// Effective configuration of the project in the current directory
+pub fn effective() -> Result<EffectiveConfig> {
+    let base_dir = std::env::current_dir()?;
+    effective_in(&base_dir, "CARGO_OPTIMIZE_", BuildContext::detect())
+}
+
+/// Effective configuration for `base_dir`, reading variables with `env_prefix`
+///
+/// Loads exactly as [`ConfigManager::new_with_context`] does.
+pub fn effective_in(base_dir: &Path, env_prefix: &str, context: BuildContext) -> Result<EffectiveConfig> {
+    let config_file = base_dir.join("cargo-optimize.toml");
+    let toolchain = ConfigManager::toolchain_for_sections(&config_file)?.map(|info| ToolchainSpec::from(&info));
+    let mut config: Config = ConfigManager::layered_figment(&config_file, &EnvSource::Prefixed(env_prefix.to_string()), context, toolchain.as_ref())
+        .extract()
+        .map_err(|e| anyhow!("Failed to extract config: {}", e))?;
+    let merged = flatten_config(&config)?;
+    config.complete_profiles(base_dir);
+    let completed = flatten_config(&config)?;
+    if config.global.auto_detect_hardware {
+        config.apply_hardware_optimizations()?;
+    }
+    let loaded = flatten_config(&config)?;
+
+    let env_keys = layer_keys(Figment::from(Env::prefixed(env_prefix).split("__")));
+    let (file_keys, context_keys) = if config_file.exists() {
+        let file = Figment::from(Toml::file(&config_file)).merge(deprecation::renamed_settings(&config_file));
+        (layer_keys(file), layer_keys(context_settings(&config_file, context)))
+    } else {
+        (BTreeSet::new(), BTreeSet::new())
+    };
+    let toolchain_keys: Vec<(ToolchainSelector, BTreeSet<String>)> = match &toolchain {
+        Some(spec) => toolchain_overrides::sections(&config_file)?
+            .into_iter()
+            .filter(|(selector, _)| selector.matches(spec))
+            .map(|(selector, settings)| (selector, layer_keys(Figment::from(Toml::string(&settings)))))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let settings = loaded
+        .into_iter()
+        .map(|(key, value)| {
+            let source = if completed.get(&key) != Some(&value) {
+                ConfigSource::Derived("hardware detection")
+            } else if merged.get(&key) != Some(&value) {
+                ConfigSource::Derived("profile completion")
+            } else if env_keys.contains(&key) {
+                ConfigSource::Env(env_var(env_prefix, &key))
+            } else if let Some((selector, _)) = toolchain_keys.iter().rev().find(|(_, keys)| keys.contains(&key)) {
+                ConfigSource::Toolchain { file: config_file.clone(), selector: selector.clone() }
+            } else if context_keys.contains(&key) {
+                ConfigSource::Context { file: config_file.clone(), context }
+            } else if file_keys.contains(&key) {
+                ConfigSource::File(config_file.clone())
+            } else {
+                ConfigSource::Default
+            };
+            EffectiveSetting { key, value: value.to_string(), source }
+        })
+        .collect();
+    Ok(EffectiveConfig { config, context, settings })
+}
+
+fn flatten_config(config: &Config) -> Result<BTreeMap<String, toml::Value>> {
+    let value = toml::Value::try_from(config).map_err(|e| anyhow!("Failed to serialize config: {}", e))?;
+    let mut settings = BTreeMap::new();
+    flatten("", value, &mut settings);
+    Ok(settings)
+}
+
+/// Leaf values of `value` by dotted key; arrays count as leaves
+fn flatten(prefix: &str, value: toml::Value, out: &mut BTreeMap<String, toml::Value>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+                flatten(&key, value, out);
+            }
+        }
+        value => {
+            out.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+/// Dotted keys a single layer sets
+fn layer_keys(layer: Figment) -> BTreeSet<String> {
+    let mut keys = BTreeMap::new();
+    if let Ok(value) = layer.extract::<toml::Value>() {
+        flatten("", value, &mut keys);
+    }
+    keys.into_keys().collect()
+}
+
+/// Name of the environment variable setting `key`, as it is spelled in the environment
+fn env_var(prefix: &str, key: &str) -> String {
+    let upper_prefix = prefix.to_ascii_uppercase();
+    std::env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .find(|name| {
+            name.to_ascii_uppercase()
+                .strip_prefix(&upper_prefix)
+                .is_some_and(|rest| rest.to_ascii_lowercase().replace("__", ".") == key)
+        })
+        .unwrap_or_else(|| format!("{}{}", prefix, key.replace('.', "__").to_ascii_uppercase()))
+}
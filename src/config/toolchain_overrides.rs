@@ -0,0 +1,243 @@
+//! `[toolchain."<selector>"]` sections of `cargo-optimize.toml`.
+//!
+//! A section holds settings in the same layout as the file and overrides it
+//! when the active toolchain matches the selector, so one committed config
+//! serves a team on mixed toolchains:
+//!
+//! ```toml
+//! [toolchain."nightly".global]
+//! gitoxide = true
+//!
+//! [toolchain.">=1.78".profiles.release]
+//! strip = "symbols"
+//! ```
+//!
+//! A selector is a comma-separated list of clauses that must all match: a
+//! channel (`stable`, `beta`, `nightly`) or a version with an optional
+//! comparison (`>=1.78`, `<1.80.0`, `=1.85`). Missing components count as
+//! zero, except that a version without `patch` and without a comparison, or
+//! with `=`, matches every patch release. Matching sections apply in file
+//! order, later ones winning, above `[contexts.<name>]` and below
+//! environment variables. Keys that are not sections of the file, such as a
+//! flat `strip = "symbols"` under a selector, are ignored with a warning.
+
+use anyhow::{bail, Context, Result};
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use toml_edit::{DocumentMut, Item};
+use tracing::warn;
+
+use super::Config;
+use crate::toolchain::{Channel, RustVersion, ToolchainSpec};
+
+/// Table of `cargo-optimize.toml` holding the toolchain sections
+pub const TOOLCHAIN_TABLE: &str = "toolchain";
+
+/// Comparison of a version clause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+}
+
+/// One condition of a selector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clause {
+    Channel(Channel),
+    Version {
+        comparison: Comparison,
+        version: RustVersion,
+        /// Whether the patch component was given
+        exact: bool,
+    },
+}
+
+/// Which toolchains a `[toolchain."<selector>"]` section applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolchainSelector {
+    source: String,
+    clauses: Vec<Clause>,
+}
+
+impl ToolchainSelector {
+    /// Whether the section applies to `toolchain`
+    pub fn matches(&self, toolchain: &ToolchainSpec) -> bool {
+        self.clauses.iter().all(|clause| match *clause {
+            Clause::Channel(channel) => toolchain.channel == channel,
+            Clause::Version { comparison, version, exact } => {
+                let actual = toolchain.version;
+                match comparison {
+                    Comparison::Greater => actual > version,
+                    Comparison::GreaterOrEqual => actual >= version,
+                    Comparison::Less => actual < version,
+                    Comparison::LessOrEqual => actual <= version,
+                    Comparison::Equal if exact => actual == version,
+                    Comparison::Equal => (actual.major, actual.minor) == (version.major, version.minor),
+                }
+            }
+        })
+    }
+}
+
+impl FromStr for ToolchainSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut clauses = Vec::new();
+        for clause in s.split(',').map(str::trim) {
+            if clause.is_empty() {
+                bail!("empty clause in toolchain selector `{}`", s);
+            }
+            let channel = match clause {
+                "stable" => Some(Channel::Stable),
+                "beta" => Some(Channel::Beta),
+                "nightly" => Some(Channel::Nightly),
+                _ => None,
+            };
+            if let Some(channel) = channel {
+                clauses.push(Clause::Channel(channel));
+                continue;
+            }
+            let (comparison, version) = [
+                (">=", Comparison::GreaterOrEqual),
+                ("<=", Comparison::LessOrEqual),
+                (">", Comparison::Greater),
+                ("<", Comparison::Less),
+                ("=", Comparison::Equal),
+            ]
+            .into_iter()
+            .find_map(|(prefix, comparison)| clause.strip_prefix(prefix).map(|rest| (comparison, rest.trim())))
+            .unwrap_or((Comparison::Equal, clause));
+            let parsed: RustVersion = version
+                .parse()
+                .with_context(|| format!("invalid toolchain selector `{}`: expected a channel or a version", s))?;
+            clauses.push(Clause::Version { comparison, version: parsed, exact: version.split('.').count() > 2 });
+        }
+        Ok(ToolchainSelector { source: s.to_string(), clauses })
+    }
+}
+
+impl fmt::Display for ToolchainSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The toolchain sections of `config_file` with their selectors, in file order
+///
+/// Fails on a selector that does not parse; no sections without the file.
+pub fn sections(config_file: &Path) -> Result<Vec<(ToolchainSelector, String)>> {
+    let Ok(content) = fs::read_to_string(config_file) else {
+        return Ok(Vec::new());
+    };
+    let Ok(doc) = content.parse::<DocumentMut>() else {
+        // Loading the file reports the parse error
+        return Ok(Vec::new());
+    };
+    let Some(table) = doc.get(TOOLCHAIN_TABLE).and_then(Item::as_table_like) else {
+        return Ok(Vec::new());
+    };
+    let mut sections = Vec::new();
+    for (selector, item) in table.iter() {
+        let Some(section) = item.as_table_like() else {
+            bail!("[toolchain.\"{}\"] in {} is not a table", selector, config_file.display());
+        };
+        let selector = selector.parse().with_context(|| format!("Invalid [toolchain] section in {}", config_file.display()))?;
+        let mut settings = DocumentMut::new();
+        for (key, value) in section.iter() {
+            settings.insert(key, value.clone());
+        }
+        sections.push((selector, settings.to_string()));
+    }
+    Ok(sections)
+}
+
+/// Whether `config_file` has toolchain sections, which makes loading detect the toolchain
+pub fn has_sections(config_file: &Path) -> bool {
+    sections(config_file).is_ok_and(|sections| !sections.is_empty())
+}
+
+/// The sections of `config_file` matching `toolchain`, merged in file order, as a layer
+///
+/// Warns about keys of any section that are not sections of the file.
+pub(crate) fn toolchain_settings(config_file: &Path, toolchain: &ToolchainSpec) -> Result<Figment> {
+    let sections = sections(config_file)?;
+    for (selector, settings) in &sections {
+        for key in unknown_keys(settings) {
+            warn!(
+                "Ignoring `{}` in [toolchain.\"{}\"] of {}: settings go in the file's layout, e.g. [toolchain.\"{}\".profiles.release]",
+                key,
+                selector,
+                config_file.display(),
+                selector
+            );
+        }
+    }
+    Ok(sections
+        .into_iter()
+        .filter(|(selector, _)| selector.matches(toolchain))
+        .fold(Figment::new(), |figment, (_, settings)| figment.merge(Toml::string(&settings))))
+}
+
+/// Top-level keys of a section's `settings` that are not sections of `cargo-optimize.toml`
+fn unknown_keys(settings: &str) -> Vec<String> {
+    let known = match toml::Value::try_from(Config::default()) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => return Vec::new(),
+    };
+    let Ok(doc) = settings.parse::<DocumentMut>() else {
+        return Vec::new();
+    };
+    doc.iter().map(|(key, _)| key.to_string()).filter(|key| !known.contains_key(key)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(s: &str) -> ToolchainSpec {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_selectors() {
+        let selector = |s: &str| s.parse::<ToolchainSelector>().unwrap();
+        assert!(selector("nightly").matches(&spec("1.80.0-nightly")));
+        assert!(!selector("nightly").matches(&spec("1.80.0")));
+        assert!(selector(">=1.78").matches(&spec("1.78.0")));
+        assert!(!selector(">=1.78").matches(&spec("1.77.2")));
+        assert!(selector("<1.80.0").matches(&spec("1.79.9")));
+        assert!(selector("1.85").matches(&spec("1.85.1")));
+        assert!(!selector("1.85.0").matches(&spec("1.85.1")));
+        assert!(selector("=1.85").matches(&spec("1.85.3-beta")));
+        assert!(selector("stable, >=1.78, <1.90").matches(&spec("1.85.0")));
+        assert!(!selector("stable, >=1.78, <1.90").matches(&spec("1.85.0-nightly")));
+        assert_eq!(selector(" >= 1.78 ").to_string(), " >= 1.78 ");
+
+        for invalid in ["", "nightly,", "~1.78", "latest", ">=1"] {
+            assert!(invalid.parse::<ToolchainSelector>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config_file = temp.path().join("cargo-optimize.toml");
+        fs::write(
+            &config_file,
+            "[toolchain.\">=1.78\"]\nstrip = \"symbols\"\n\n[toolchain.\">=1.78\".profiles.release]\nstrip = \"symbols\"\n",
+        )
+        .unwrap();
+        let sections = sections(&config_file).unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(unknown_keys(&sections[0].1), ["strip"]);
+        assert!(unknown_keys("[global]\ngitoxide = true\n[profiles.dev]\ndebug = 1\n").is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+//! Other build tooling cargo-optimize has to work around.
+//!
+//! Some projects already tune their builds with tools that interact with the
+//! settings cargo-optimize writes: a wrapper set through `RUSTC_WRAPPER`,
+//! Docker images caching dependencies with cargo-chef, a Nix dev shell
+//! providing the tools, or a `.cargo/config.toml` the team tuned by hand
+//! and committed. Each rule of
+//! [`REGISTRY`] detects one of them from a [`ToolEnvironment`] and describes
+//! the interaction; planning reports every [`Conflict`] found and applies its
+//! [`Adjustment`].
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::container;
+use crate::nix::{self, NixEnvironment};
+
+/// Variables that set the rustc wrapper, outranking `build.rustc-wrapper`
+const WRAPPER_VARS: &[&str] = &["RUSTC_WRAPPER", "CARGO_BUILD_RUSTC_WRAPPER"];
+
+/// Comment fragment marking values and tables written by cargo-optimize
+const MANAGED_FRAGMENT: &str = "cargo-optimize:";
+
+/// A Dockerfile building dependencies with `cargo chef cook`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChefDockerfile {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// Whether `.cargo/config.toml` is copied into the image before every `cargo chef cook`
+    pub copies_cargo_config: bool,
+}
+
+/// What planning needs to know about other build tooling
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolEnvironment {
+    /// Wrapper set in the environment, as (variable, value)
+    #[serde(default)]
+    pub rustc_wrapper_env: Option<(String, String)>,
+    /// Dockerfiles using cargo-chef
+    #[serde(default)]
+    pub chef_dockerfiles: Vec<ChefDockerfile>,
+    /// Whether `.cargo/config.toml` is tracked by git
+    #[serde(default)]
+    pub config_committed: bool,
+    /// The Nix environment cargo-optimize runs in, if any
+    #[serde(default)]
+    pub nix: Option<NixEnvironment>,
+}
+
+impl ToolEnvironment {
+    /// Inspect the environment, the project at `project_root` and its config at `config_path`
+    pub fn detect(project_root: &Path, config_path: &Path) -> Self {
+        ToolEnvironment {
+            rustc_wrapper_env: WRAPPER_VARS.iter().find_map(|var| {
+                std::env::var(var)
+                    .ok()
+                    .filter(|value| !value.trim().is_empty())
+                    .map(|value| (var.to_string(), value))
+            }),
+            chef_dockerfiles: find_chef_dockerfiles(project_root),
+            config_committed: is_tracked(project_root, config_path),
+            nix: NixEnvironment::detect(),
+        }
+    }
+}
+
+/// How planning adapts to a detected tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjustment {
+    /// Leave `build.rustc-wrapper` and its sccache statistics alone
+    KeepWrapper,
+    /// Keep the single shared target directory
+    SharedTargetDir,
+    /// Keep every value the committed config sets by hand
+    KeepHandSetValues,
+    /// Refer to tools by command name instead of Nix store path
+    ToolsByName,
+}
+
+/// An interaction with another tool, found during planning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Tool the conflict is about (e.g. "cargo-chef")
+    pub tool: &'static str,
+    /// What interacts and how cargo-optimize deals with it
+    pub message: String,
+    /// What the user should change, if anything
+    pub advice: Option<String>,
+    /// How planning adapts
+    pub adjustment: Option<Adjustment>,
+}
+
+/// One known tool
+#[derive(Debug, Clone, Copy)]
+pub struct ConflictRule {
+    /// Tool the rule detects
+    pub tool: &'static str,
+    /// Detect the tool from the environment and the config before planning
+    pub detect: fn(&'static str, &ToolEnvironment, &DocumentMut) -> Option<Conflict>,
+}
+
+/// Known tools, evaluated in order
+pub const REGISTRY: &[ConflictRule] = &[
+    ConflictRule { tool: "RUSTC_WRAPPER", detect: wrapper_env },
+    ConflictRule { tool: "cargo-chef", detect: cargo_chef },
+    ConflictRule { tool: "nix", detect: nix_shell },
+    ConflictRule { tool: "hand-tuned config", detect: hand_tuned_config },
+];
+
+/// Conflicts of every rule in [`REGISTRY`] that applies
+///
+/// `config` is the project's `.cargo/config.toml` before planning.
+pub fn evaluate(tools: &ToolEnvironment, config: &DocumentMut) -> Vec<Conflict> {
+    REGISTRY.iter().filter_map(|rule| (rule.detect)(rule.tool, tools, config)).collect()
+}
+
+fn wrapper_env(tool: &'static str, tools: &ToolEnvironment, _: &DocumentMut) -> Option<Conflict> {
+    let (var, value) = tools.rustc_wrapper_env.as_ref()?;
+    Some(Conflict {
+        tool,
+        message: format!(
+            "`{}={}` is set in the environment and takes precedence over `build.rustc-wrapper`; \
+             the wrapper is left alone",
+            var, value
+        ),
+        advice: None,
+        adjustment: Some(Adjustment::KeepWrapper),
+    })
+}
+
+fn cargo_chef(tool: &'static str, tools: &ToolEnvironment, _: &DocumentMut) -> Option<Conflict> {
+    let first = tools.chef_dockerfiles.first()?;
+    let missing: Vec<String> = tools
+        .chef_dockerfiles
+        .iter()
+        .filter(|dockerfile| !dockerfile.copies_cargo_config)
+        .map(|dockerfile| format!("`{}`", dockerfile.path.display()))
+        .collect();
+    Some(Conflict {
+        tool,
+        message: format!(
+            "`{}` caches dependencies with `cargo chef cook` in the image's target directory, \
+             which therefore stays shared",
+            first.path.display()
+        ),
+        advice: (!missing.is_empty()).then(|| {
+            format!(
+                "Copy `.cargo/config.toml` into the image before `cargo chef cook` in {} \
+                 (e.g. `COPY .cargo .cargo`); otherwise dependencies are cooked without the \
+                 optimized settings and compiled again by the final build",
+                missing.join(", ")
+            )
+        }),
+        adjustment: Some(Adjustment::SharedTargetDir),
+    })
+}
+
+fn nix_shell(tool: &'static str, tools: &ToolEnvironment, config: &DocumentMut) -> Option<Conflict> {
+    let environment = tools.nix.as_ref()?;
+    let pinned: Vec<String> = store_path_values(config).into_iter().map(|key| format!("`{}`", key)).collect();
+    Some(Conflict {
+        tool,
+        message: format!(
+            "running in {}: tools are referred to by command name, not by Nix store path, and \
+             nothing is installed outside the store; `cargo optimize nix` prints the settings for \
+             devenv.nix or a flake instead",
+            environment.describe()
+        ),
+        advice: (!pinned.is_empty()).then(|| {
+            format!(
+                "Replace the Nix store paths of {} in `.cargo/config.toml` with command names from the \
+                 dev shell's packages; store paths change with every nixpkgs update and disappear on \
+                 garbage collection",
+                pinned.join(", ")
+            )
+        }),
+        adjustment: Some(Adjustment::ToolsByName),
+    })
+}
+
+/// Dotted keys of the string values and array items of `doc` holding a Nix store path
+fn store_path_values(doc: &DocumentMut) -> Vec<String> {
+    hand_set_values(doc)
+        .into_iter()
+        .filter(|(_, item)| {
+            let value = item.as_value();
+            let strings: Vec<&str> = match value.and_then(|value| value.as_array()) {
+                Some(array) => array.iter().filter_map(|item| item.as_str()).collect(),
+                None => value.and_then(|value| value.as_str()).into_iter().collect(),
+            };
+            strings.iter().any(|s| nix::unpin_store_paths(s).is_some())
+        })
+        .map(|(key, _)| key.join("."))
+        .collect()
+}
+
+fn hand_tuned_config(tool: &'static str, tools: &ToolEnvironment, config: &DocumentMut) -> Option<Conflict> {
+    if !tools.config_committed {
+        return None;
+    }
+    let count = hand_set_values(config).len();
+    (count > 0).then(|| Conflict {
+        tool,
+        message: format!(
+            "`.cargo/config.toml` is committed and sets {} value(s) by hand; they are kept \
+             and only missing settings are added",
+            count
+        ),
+        advice: None,
+        adjustment: Some(Adjustment::KeepHandSetValues),
+    })
+}
+
+/// Values of `doc` not written by cargo-optimize, with their key paths
+///
+/// Values and tables carrying a `cargo-optimize:` comment are managed; inline
+/// tables and arrays count as single values.
+pub fn hand_set_values(doc: &DocumentMut) -> Vec<(Vec<String>, Item)> {
+    let mut found = Vec::new();
+    collect_hand_set(doc.as_table(), &mut Vec::new(), &mut found);
+    found
+}
+
+fn collect_hand_set(table: &Table, path: &mut Vec<String>, found: &mut Vec<(Vec<String>, Item)>) {
+    for (key, item) in table.iter() {
+        path.push(key.to_string());
+        match item {
+            Item::Table(child) => {
+                let managed = child
+                    .decor()
+                    .prefix()
+                    .and_then(|prefix| prefix.as_str())
+                    .is_some_and(|prefix| prefix.contains(MANAGED_FRAGMENT));
+                if !managed {
+                    collect_hand_set(child, path, found);
+                }
+            }
+            Item::Value(value) => {
+                let managed = value
+                    .decor()
+                    .suffix()
+                    .and_then(|suffix| suffix.as_str())
+                    .is_some_and(|suffix| suffix.contains(MANAGED_FRAGMENT));
+                if !managed {
+                    found.push((path.clone(), item.clone()));
+                }
+            }
+            Item::None | Item::ArrayOfTables(_) => {}
+        }
+        path.pop();
+    }
+}
+
+fn find_chef_dockerfiles(project_root: &Path) -> Vec<ChefDockerfile> {
+    let mut found = Vec::new();
+    for path in container::dockerfile_paths(project_root) {
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+        if let Some(copies_cargo_config) = chef_copies_cargo_config(&content) {
+            let path = path.strip_prefix(project_root).map(Path::to_path_buf).unwrap_or(path);
+            found.push(ChefDockerfile { path, copies_cargo_config });
+        }
+    }
+    found
+}
+
+/// Whether every `cargo chef cook` of a Dockerfile runs after `.cargo` was
+/// copied into its stage; `None` if the Dockerfile does not use cargo-chef
+pub fn chef_copies_cargo_config(dockerfile: &str) -> Option<bool> {
+    let mut copied = false;
+    let mut result = None;
+    for line in dockerfile.lines().map(str::trim) {
+        let mut words = line.split_whitespace();
+        let instruction = words.next().unwrap_or_default().to_ascii_uppercase();
+        match instruction.as_str() {
+            "FROM" => copied = false,
+            "COPY" | "ADD" => {
+                let args: Vec<&str> = words.collect();
+                // Files from other stages are not the project's
+                if args.iter().any(|arg| arg.starts_with("--from")) {
+                    continue;
+                }
+                let paths: Vec<&str> = args.into_iter().filter(|arg| !arg.starts_with("--")).collect();
+                let sources = &paths[..paths.len().saturating_sub(1)];
+                copied |= sources.iter().any(|source| {
+                    let source = source.trim_start_matches("./").trim_end_matches('/');
+                    source == "." || source.is_empty() || source.starts_with(".cargo")
+                });
+            }
+            _ if line.contains("cargo chef cook") => {
+                result = Some(result.unwrap_or(true) && copied);
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Whether git tracks `path` in the repository containing `project_root`
+fn is_tracked(project_root: &Path, path: &Path) -> bool {
+    if !path.exists() {
+        return false;
+    }
+    Command::new("git")
+        .arg("-C")
+        .arg(project_root)
+        .args(["ls-files", "--error-unmatch", "--"])
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chef_dockerfile_parsing() {
+        let cooked_blind = "\
+FROM rust AS planner
+COPY . .
+RUN cargo chef prepare --recipe-path recipe.json
+
+FROM rust AS builder
+COPY --from=planner /app/recipe.json recipe.json
+RUN cargo chef cook --release --recipe-path recipe.json
+COPY . .
+RUN cargo build --release
+";
+        assert_eq!(chef_copies_cargo_config(cooked_blind), Some(false));
+        let with_config = cooked_blind.replace("COPY --from", "COPY .cargo .cargo\nCOPY --from");
+        assert_eq!(chef_copies_cargo_config(&with_config), Some(true));
+        assert_eq!(chef_copies_cargo_config("FROM rust\nCOPY . .\nRUN cargo build\n"), None);
+    }
+
+    #[test]
+    fn test_registry_rules() {
+        let config: DocumentMut = "[build]\njobs = 4 # team setting\n\n\
+            [profile.dev]\nincremental = true # cargo-optimize: CO-INCR-001\n"
+            .parse()
+            .unwrap();
+        assert!(evaluate(&ToolEnvironment::default(), &config).is_empty());
+
+        let tools = ToolEnvironment {
+            rustc_wrapper_env: Some(("RUSTC_WRAPPER".to_string(), "sccache".to_string())),
+            chef_dockerfiles: vec![ChefDockerfile { path: PathBuf::from("Dockerfile"), copies_cargo_config: false }],
+            config_committed: true,
+            nix: None,
+        };
+        let conflicts = evaluate(&tools, &config);
+        let tools: Vec<&str> = conflicts.iter().map(|conflict| conflict.tool).collect();
+        assert_eq!(tools, ["RUSTC_WRAPPER", "cargo-chef", "hand-tuned config"]);
+        assert_eq!(conflicts[1].adjustment, Some(Adjustment::SharedTargetDir));
+        assert!(conflicts[1].advice.as_deref().is_some_and(|advice| advice.contains("`Dockerfile`")));
+        assert!(conflicts[2].message.contains("1 value(s)"));
+
+        let hand_set = hand_set_values(&config);
+        assert_eq!(hand_set.len(), 1);
+        assert_eq!(hand_set[0].0, ["build", "jobs"]);
+    }
+}
@@ -0,0 +1,298 @@
+//! Build cache guidance for Docker and Podman images.
+//!
+//! Container builds start from an empty `target/` and `CARGO_HOME` unless
+//! the Dockerfile keeps them between builds. [`find_dockerfiles`] reads the
+//! Dockerfiles (and Containerfiles) of a project that run cargo and flags
+//! the patterns that throw the cache away: copying the whole project before
+//! dependencies are built, so every source change invalidates the dependency
+//! layer, and cargo steps without cache mounts for the registry or the
+//! target directory. [`recommended_snippet`] shows a layout that avoids
+//! them; BuildKit and Podman (buildah) both support the cache mounts it uses.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories searched for Dockerfiles, relative to the project root
+const DOCKERFILE_DIRS: &[&str] = &["", "docker", ".devcontainer"];
+
+/// Cargo registry inside the official Rust images
+const REGISTRY_DIR: &str = "/usr/local/cargo/registry";
+
+/// A pattern that keeps a container build from reusing its cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheIssue {
+    /// The whole project is copied before dependencies are built
+    CopyBeforeDependencies,
+    /// cargo downloads crates without a cache mount for the registry
+    NoRegistryCacheMount,
+    /// cargo compiles dependencies without a cache mount for `target/`
+    NoTargetCacheMount,
+}
+
+/// One issue and the line of the instruction causing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DockerfileFinding {
+    /// What is wrong
+    pub issue: CacheIssue,
+    /// Line of the instruction (1-based)
+    pub line: usize,
+}
+
+/// A Dockerfile that runs cargo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DockerfileAnalysis {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// Working directory of the stage running cargo
+    pub workdir: String,
+    /// Cache issues, in file order
+    pub findings: Vec<DockerfileFinding>,
+}
+
+/// Paths of the Dockerfiles of a project, in a stable order
+pub fn dockerfile_paths(project_root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for dir in DOCKERFILE_DIRS {
+        let Ok(entries) = fs::read_dir(project_root.join(dir)) else { continue };
+        let mut paths: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && is_dockerfile(path))
+            .collect();
+        paths.sort();
+        found.extend(paths);
+    }
+    found
+}
+
+fn is_dockerfile(path: &Path) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    name.starts_with("dockerfile") || name.starts_with("containerfile") || name.ends_with(".dockerfile")
+}
+
+/// Analyze every Dockerfile of the project that runs cargo
+pub fn find_dockerfiles(project_root: &Path) -> Vec<DockerfileAnalysis> {
+    dockerfile_paths(project_root)
+        .into_iter()
+        .filter_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            let (workdir, findings) = analyze(&content)?;
+            let path = path.strip_prefix(project_root).map(Path::to_path_buf).unwrap_or(path);
+            Some(DockerfileAnalysis { path, workdir, findings })
+        })
+        .collect()
+}
+
+/// Cache issues of a Dockerfile, with the working directory cargo runs in
+///
+/// Returns `None` if the Dockerfile does not run cargo. Dependencies count
+/// as built in their own layer when a stage runs `cargo chef cook`,
+/// `cargo fetch` or any cargo build before the whole project is copied (the
+/// dummy `main.rs` technique); a stage doing so needs no further caching.
+pub fn analyze(dockerfile: &str) -> Option<(String, Vec<DockerfileFinding>)> {
+    let mut findings = Vec::new();
+    let mut runs_cargo = false;
+    let mut cargo_workdir = None;
+    let mut workdir = "/".to_string();
+    let mut copied_all = None;
+    let mut dependencies_built = false;
+
+    for (line, instruction) in instructions(dockerfile) {
+        let mut words = instruction.split_whitespace();
+        let keyword = words.next().unwrap_or_default().to_ascii_uppercase();
+        let args: Vec<&str> = words.collect();
+        match keyword.as_str() {
+            "FROM" => {
+                workdir = "/".to_string();
+                copied_all = None;
+                dependencies_built = false;
+            }
+            "WORKDIR" => {
+                if let Some(dir) = args.first() {
+                    workdir = if dir.starts_with('/') {
+                        dir.to_string()
+                    } else {
+                        format!("{}/{}", workdir.trim_end_matches('/'), dir)
+                    };
+                }
+            }
+            "COPY" | "ADD" if !args.iter().any(|arg| arg.starts_with("--from")) => {
+                let paths: Vec<&str> = args.into_iter().filter(|arg| !arg.starts_with("--")).collect();
+                let sources = &paths[..paths.len().saturating_sub(1)];
+                let whole_project = sources.iter().any(|source| {
+                    let source = source.trim_start_matches("./").trim_end_matches('/');
+                    source == "." || source.is_empty() || source == "src"
+                });
+                if whole_project && copied_all.is_none() {
+                    copied_all = Some(line);
+                }
+            }
+            "RUN" => {
+                let fetches = ["cargo chef cook", "cargo fetch"].iter().any(|step| instruction.contains(step));
+                let compiles = ["cargo build", "cargo install", "cargo test"].iter().any(|step| instruction.contains(step));
+                if !fetches && !compiles {
+                    continue;
+                }
+                runs_cargo = true;
+                cargo_workdir.get_or_insert_with(|| workdir.clone());
+                if dependencies_built {
+                    continue;
+                }
+                let cache_mount = |target: &str| {
+                    instruction.split_whitespace().any(|word| {
+                        word.starts_with("--mount=") && word.contains("type=cache") && word.contains(target)
+                    })
+                };
+                if !cache_mount("cargo/registry") && !cache_mount("CARGO_HOME") {
+                    findings.push(DockerfileFinding { issue: CacheIssue::NoRegistryCacheMount, line });
+                }
+                match copied_all {
+                    // Built before the project was copied: dependencies have their own layer
+                    _ if fetches => dependencies_built = true,
+                    None => dependencies_built = true,
+                    Some(copy_line) => {
+                        if !cache_mount("target") {
+                            findings.push(DockerfileFinding { issue: CacheIssue::CopyBeforeDependencies, line: copy_line });
+                            findings.push(DockerfileFinding { issue: CacheIssue::NoTargetCacheMount, line });
+                        }
+                        dependencies_built = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !runs_cargo {
+        return None;
+    }
+    findings.sort_by_key(|finding| finding.line);
+    Some((cargo_workdir.unwrap_or(workdir), findings))
+}
+
+/// Instructions with their starting line, continuation lines joined and comments dropped
+fn instructions(dockerfile: &str) -> Vec<(usize, String)> {
+    let mut instructions = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, raw) in dockerfile.lines().enumerate() {
+        let line = raw.trim();
+        if line.starts_with('#') || (line.is_empty() && current.is_none()) {
+            continue;
+        }
+        let (text, continues) = match line.strip_suffix('\\') {
+            Some(text) => (text.trim_end(), true),
+            None => (line, false),
+        };
+        let (start, mut instruction) = current.take().unwrap_or((index + 1, String::new()));
+        if !instruction.is_empty() {
+            instruction.push(' ');
+        }
+        instruction.push_str(text);
+        if continues {
+            current = Some((start, instruction));
+        } else {
+            instructions.push((start, instruction));
+        }
+    }
+    instructions.extend(current);
+    instructions
+}
+
+/// Binary the recommended build stage copies out: the package name of the project
+///
+/// Falls back to `app` for virtual workspaces and unreadable manifests.
+pub fn binary_name(project_root: &Path) -> String {
+    fs::read_to_string(project_root.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml_edit::DocumentMut>().ok())
+        .and_then(|doc| doc.get("package")?.get("name")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| "app".to_string())
+}
+
+/// A build stage that keeps the dependency layer and cargo's caches between builds
+///
+/// `binary` is the executable to copy out of the cached target directory;
+/// `workdir` the working directory of the build stage.
+pub fn recommended_snippet(binary: &str, workdir: &str) -> String {
+    let workdir = if workdir == "/" { "/app" } else { workdir.trim_end_matches('/') };
+    format!(
+        "# syntax=docker/dockerfile:1\n\
+         FROM rust:1 AS builder\n\
+         WORKDIR {workdir}\n\
+         # Dependencies first: this layer is reused until the manifests change\n\
+         # (copy the Cargo.toml of every workspace member as well)\n\
+         COPY Cargo.toml Cargo.lock ./\n\
+         COPY .cargo .cargo\n\
+         RUN --mount=type=cache,target={registry} \\\n    \
+         mkdir -p src && touch src/main.rs && cargo fetch --locked\n\
+         # The registry and target/ persist across builds; only changed crates recompile\n\
+         COPY . .\n\
+         RUN --mount=type=cache,target={registry} \\\n    \
+         --mount=type=cache,target={workdir}/target \\\n    \
+         cargo build --release --locked && \\\n    \
+         cp target/release/{binary} /usr/local/bin/{binary}\n",
+        workdir = workdir,
+        registry = REGISTRY_DIR,
+        binary = binary,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(dockerfile: &str) -> Vec<(CacheIssue, usize)> {
+        analyze(dockerfile).unwrap().1.into_iter().map(|finding| (finding.issue, finding.line)).collect()
+    }
+
+    #[test]
+    fn test_cache_busting_patterns() {
+        let naive = "\
+FROM rust:1.85
+WORKDIR /src
+COPY . .
+RUN cargo build --release
+";
+        assert_eq!(analyze(naive).unwrap().0, "/src");
+        assert_eq!(
+            issues(naive),
+            vec![
+                (CacheIssue::CopyBeforeDependencies, 3),
+                (CacheIssue::NoRegistryCacheMount, 4),
+                (CacheIssue::NoTargetCacheMount, 4),
+            ]
+        );
+
+        // Dependencies in their own layer, downloads without a cache mount
+        let layered = "\
+FROM rust:1.85
+COPY Cargo.toml Cargo.lock ./
+RUN mkdir src && echo 'fn main() {}' > src/main.rs \\
+    && cargo build --release
+COPY . .
+RUN cargo build --release
+";
+        assert_eq!(issues(layered), vec![(CacheIssue::NoRegistryCacheMount, 3)]);
+
+        let cached = "\
+FROM rust:1.85
+WORKDIR /app
+COPY . .
+RUN --mount=type=cache,target=/usr/local/cargo/registry \\
+    --mount=type=cache,target=/app/target \\
+    cargo build --release
+";
+        assert_eq!(issues(cached), vec![]);
+        assert_eq!(analyze("FROM nginx\nCOPY . /usr/share/nginx/html\n"), None);
+    }
+
+    #[test]
+    fn test_recommended_snippet_passes_its_own_analysis() {
+        let snippet = recommended_snippet("demo", "/");
+        assert!(snippet.contains("WORKDIR /app\n"));
+        assert!(snippet.contains("cp target/release/demo /usr/local/bin/demo"));
+        assert_eq!(analyze(&snippet), Some(("/app".to_string(), Vec::new())));
+    }
+}
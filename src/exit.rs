@@ -0,0 +1,202 @@
+//! Stable exit codes for scripts and CI pipelines.
+//!
+//! The `cargo-optimize` binary exits with one of the [`ExitStatus`] codes so
+//! pipeline authors can branch on the outcome without parsing its output:
+//!
+//! | Code | Status | Meaning |
+//! |------|--------|---------|
+//! | 0 | `success` | Done; `verify` found the config up to date |
+//! | 1 | `failure` | An error not covered by a more specific code |
+//! | 2 | `drift` | `verify`: the config differs from what apply would write |
+//! | 3 | `policy-violation` | The project asks for settings the machine policy forbids |
+//! | 4 | `unsupported-platform` | No optimizations are known for this platform |
+//! | 5 | `rolled-back` | Apply failed verification and was rolled back, or left out a rolled back linker |
+//! | 6 | `invalid-config` | A config or policy file could not be parsed |
+//! | 7 | `busy` | A cargo build holds the project; apply was deferred |
+//! | 8 | `requirement-unmet` | An optimization `global.required` asks for is missing |
+//! | 64 | `usage` | Unknown command or missing arguments |
+//!
+//! Codes are never reused for another meaning; new outcomes get new codes.
+
+use std::fmt;
+use std::process::ExitCode;
+
+use crate::config::ConfigError;
+use crate::phases::PhaseStatus;
+use crate::report::{DecisionOutcome, OptimizationReport};
+
+/// Outcome of a run, as an exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExitStatus {
+    /// Done; nothing needs attention
+    Success,
+    /// An error not covered by a more specific status
+    Failure,
+    /// The config differs from what apply would write
+    Drift,
+    /// The project asks for settings the machine policy forbids
+    PolicyViolation,
+    /// No optimizations are known for this platform
+    UnsupportedPlatform,
+    /// Apply failed verification and was rolled back, in whole or in part
+    RolledBack,
+    /// A config or policy file could not be parsed
+    InvalidConfig,
+    /// A cargo build holds the project, so apply was deferred
+    Busy,
+    /// An optimization `global.required` asks for is missing
+    RequirementUnmet,
+    /// Unknown command or missing arguments
+    Usage,
+}
+
+impl ExitStatus {
+    /// Every status, in code order
+    pub const ALL: [ExitStatus; 10] = [
+        ExitStatus::Success,
+        ExitStatus::Failure,
+        ExitStatus::Drift,
+        ExitStatus::PolicyViolation,
+        ExitStatus::UnsupportedPlatform,
+        ExitStatus::RolledBack,
+        ExitStatus::InvalidConfig,
+        ExitStatus::Busy,
+        ExitStatus::RequirementUnmet,
+        ExitStatus::Usage,
+    ];
+
+    /// The process exit code
+    pub fn code(self) -> u8 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Failure => 1,
+            ExitStatus::Drift => 2,
+            ExitStatus::PolicyViolation => 3,
+            ExitStatus::UnsupportedPlatform => 4,
+            ExitStatus::RolledBack => 5,
+            ExitStatus::InvalidConfig => 6,
+            ExitStatus::Busy => 7,
+            ExitStatus::RequirementUnmet => 8,
+            // EX_USAGE of sysexits.h
+            ExitStatus::Usage => 64,
+        }
+    }
+
+    /// Stable name (e.g. "policy-violation")
+    pub fn name(self) -> &'static str {
+        match self {
+            ExitStatus::Success => "success",
+            ExitStatus::Failure => "failure",
+            ExitStatus::Drift => "drift",
+            ExitStatus::PolicyViolation => "policy-violation",
+            ExitStatus::UnsupportedPlatform => "unsupported-platform",
+            ExitStatus::RolledBack => "rolled-back",
+            ExitStatus::InvalidConfig => "invalid-config",
+            ExitStatus::Busy => "busy",
+            ExitStatus::RequirementUnmet => "requirement-unmet",
+            ExitStatus::Usage => "usage",
+        }
+    }
+
+    /// The status a code stands for, if any
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.code() == code)
+    }
+
+    /// Whether the run succeeded
+    pub fn is_success(self) -> bool {
+        self == ExitStatus::Success
+    }
+
+    /// Status of a run that produced `report`
+    ///
+    /// An unmet requirement outranks a rollback, which outranks a policy
+    /// conflict, which outranks an unsupported platform and a deferred apply.
+    pub fn of_report(report: &OptimizationReport) -> Self {
+        if !report.unmet_requirements.is_empty() {
+            ExitStatus::RequirementUnmet
+        } else if report.phases.iter().any(|phase| phase.status == PhaseStatus::RolledBack) {
+            ExitStatus::RolledBack
+        } else if !report.policy_conflicts.is_empty() {
+            ExitStatus::PolicyViolation
+        } else if is_skipped(report, "platform") {
+            ExitStatus::UnsupportedPlatform
+        } else if report.deferred_by.is_some() {
+            ExitStatus::Busy
+        } else {
+            ExitStatus::Success
+        }
+    }
+
+    /// Status of a run that failed with `error`
+    ///
+    /// The error and its causes are searched for parse errors of a config or
+    /// policy file; anything else is a [`ExitStatus::Failure`].
+    pub fn of_error(error: &anyhow::Error) -> Self {
+        let invalid = error.chain().any(|cause| {
+            matches!(cause.downcast_ref::<ConfigError>(), Some(ConfigError::ParseError(_)))
+                || cause.is::<toml::de::Error>()
+                || cause.is::<toml_edit::TomlError>()
+        });
+        if invalid {
+            ExitStatus::InvalidConfig
+        } else {
+            ExitStatus::Failure
+        }
+    }
+}
+
+fn is_skipped(report: &OptimizationReport, setting: &str) -> bool {
+    report.decision_for(setting).is_some_and(|decision| decision.outcome == DecisionOutcome::Skipped)
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name(), self.code())
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::SkipReason;
+
+    #[test]
+    fn test_codes_are_stable_and_distinct() {
+        let codes: Vec<u8> = ExitStatus::ALL.iter().map(|status| status.code()).collect();
+        assert_eq!(codes, [0, 1, 2, 3, 4, 5, 6, 7, 8, 64]);
+        for status in ExitStatus::ALL {
+            assert_eq!(ExitStatus::from_code(status.code()), Some(status));
+        }
+        assert_eq!(ExitStatus::from_code(9), None);
+        assert_eq!(ExitStatus::PolicyViolation.to_string(), "policy-violation (3)");
+    }
+
+    #[test]
+    fn test_status_of_report_and_error() {
+        let mut report = OptimizationReport::new();
+        report.applied("linker", "mold");
+        assert_eq!(ExitStatus::of_report(&report), ExitStatus::Success);
+        report.skipped("platform", SkipReason::UnsupportedPlatform, "no optimizations available for this platform (plan9)");
+        assert_eq!(ExitStatus::of_report(&report), ExitStatus::UnsupportedPlatform);
+        report.conflict("linker", "gold is not allowed by the machine policy");
+        assert_eq!(ExitStatus::of_report(&report), ExitStatus::PolicyViolation);
+        report.unmet_requirements.push(crate::strict::UnmetRequirement {
+            requirement: crate::strict::Requirement::Sccache,
+            reason: "build.rustc-wrapper is not set".to_string(),
+            remediation: Vec::new(),
+        });
+        assert_eq!(ExitStatus::of_report(&report), ExitStatus::RequirementUnmet);
+
+        let parse = "[build".parse::<toml_edit::DocumentMut>().unwrap_err();
+        let error = anyhow::Error::new(parse).context("Failed to parse .cargo/config.toml");
+        assert_eq!(ExitStatus::of_error(&error), ExitStatus::InvalidConfig);
+        assert_eq!(ExitStatus::of_error(&anyhow::anyhow!("disk full")), ExitStatus::Failure);
+    }
+}
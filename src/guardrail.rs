@@ -0,0 +1,176 @@
+//! Rolling back a linker that breaks the build.
+//!
+//! A fast linker can pass every check at apply time and still fail on the
+//! first real link: a compiler driver without `-fuse-ld` support, a mold
+//! build too old for the object files, an lld that crashes on the target.
+//! Each apply that writes a linker marks it as unverified in the state file.
+//! [`guarded_build`] is an optional wrapper around cargo that watches the
+//! build's stderr for linker failure signatures. When the first build after
+//! such an apply fails with one, the linker settings cargo-optimize wrote
+//! are reverted, the linker is recorded as incompatible so later applies do
+//! not configure it again, and the user is told. A successful build clears
+//! the mark. Undefined and duplicate symbols point at the code rather than
+//! the linker and never trigger a rollback.
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader};
+use std::process::{Command, ExitStatus, Stdio};
+
+use crate::config::ConfigManager;
+use crate::i18n::{Lang, Message};
+use crate::output;
+use crate::report::OptimizationReport;
+
+/// A line of build output that implicates a linker
+struct Signature {
+    /// Text the line contains
+    pattern: &'static str,
+    /// Registry names of the linkers it comes from; empty for any configured linker
+    linkers: &'static [&'static str],
+    /// What went wrong
+    meaning: &'static str,
+}
+
+const SIGNATURES: &[Signature] = &[
+    Signature { pattern: "mold: fatal:", linkers: &["mold", "sold"], meaning: "mold aborted" },
+    Signature { pattern: "mold: error:", linkers: &["mold", "sold"], meaning: "mold failed" },
+    Signature { pattern: "ld.lld: error:", linkers: &["lld"], meaning: "lld failed" },
+    Signature { pattern: "ld64.lld: error:", linkers: &["lld"], meaning: "lld failed" },
+    Signature { pattern: "rust-lld: error:", linkers: &["rust-lld", "lld-link"], meaning: "rust-lld failed" },
+    Signature { pattern: "lld-link: error:", linkers: &["lld-link", "rust-lld"], meaning: "lld-link failed" },
+    Signature { pattern: "wild: error:", linkers: &["wild"], meaning: "wild failed" },
+    Signature { pattern: "ld.gold: error:", linkers: &["gold"], meaning: "gold failed" },
+    Signature { pattern: "ld.gold: fatal error:", linkers: &["gold"], meaning: "gold aborted" },
+    Signature {
+        pattern: "invalid linker name in argument '-fuse-ld=",
+        linkers: &[],
+        meaning: "the C compiler driver does not know the linker",
+    },
+    Signature {
+        pattern: "unrecognized command-line option '-fuse-ld=",
+        linkers: &[],
+        meaning: "the C compiler driver does not support -fuse-ld",
+    },
+    Signature {
+        pattern: "unrecognized command line option '-fuse-ld=",
+        linkers: &[],
+        meaning: "the C compiler driver does not support -fuse-ld",
+    },
+    Signature { pattern: "cannot find 'ld'", linkers: &[], meaning: "the C compiler driver cannot find the linker" },
+];
+
+/// Lines that report problems in the code being linked, whatever the linker
+const CODE_PROBLEMS: &[&str] = &["undefined symbol", "undefined reference", "duplicate symbol", "multiple definition"];
+
+/// A build failure attributed to the configured linker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkerFailure {
+    /// What went wrong
+    pub meaning: String,
+    /// The output line it was recognized from
+    pub line: String,
+}
+
+/// Outcome of a guarded build
+#[derive(Debug)]
+pub struct GuardedBuild {
+    /// Exit status of cargo
+    pub status: ExitStatus,
+    /// Linker failure found in the output, if the build failed with one
+    pub failure: Option<LinkerFailure>,
+    /// Report of the rollback, if the linker settings were reverted
+    pub rollback: Option<OptimizationReport>,
+}
+
+/// Whether one line of build output shows `linker` failing
+pub fn line_failure(line: &str, linker: &str) -> Option<LinkerFailure> {
+    if CODE_PROBLEMS.iter().any(|problem| line.contains(problem)) {
+        return None;
+    }
+    let not_found = line.contains("error: linker `") && line.contains("` not found");
+    let signature = SIGNATURES.iter().find(|signature| {
+        line.contains(signature.pattern) && (signature.linkers.is_empty() || signature.linkers.contains(&linker))
+    });
+    let meaning = match signature {
+        Some(signature) => signature.meaning,
+        None if not_found => "the configured linker executable was not found",
+        None => return None,
+    };
+    Some(LinkerFailure { meaning: meaning.to_string(), line: line.trim().to_string() })
+}
+
+/// The first line of `stderr` showing `linker` failing
+pub fn detect_failure(stderr: &str, linker: &str) -> Option<LinkerFailure> {
+    stderr.lines().find_map(|line| line_failure(line, linker))
+}
+
+/// Run `cargo <args>` in the project and roll back a linker that breaks it
+///
+/// Uses the `CARGO` environment variable when set, so it also works from a
+/// cargo subcommand. See [`guard`].
+pub fn guarded_build(manager: &ConfigManager, args: &[&str]) -> Result<GuardedBuild> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = Command::new(cargo);
+    command.args(args);
+    let root = manager.project_root();
+    if !root.as_os_str().is_empty() {
+        command.current_dir(root);
+    }
+    guard(manager, command)
+}
+
+/// Run a build command, passing its stderr through, and act on the outcome
+///
+/// Only builds after an apply that wrote a linker are checked: a success
+/// confirms the linker, and a failure with a signature of it rolls it back.
+pub fn guard(manager: &ConfigManager, mut command: Command) -> Result<GuardedBuild> {
+    let linker = manager.unverified_linker();
+    let mut child = command.stderr(Stdio::piped()).spawn().context("Failed to start the build")?;
+    let mut failure = None;
+    if let Some(stderr) = child.stderr.take() {
+        for line in BufReader::new(stderr).lines() {
+            let line = line.context("Failed to read the build output")?;
+            eprintln!("{}", line);
+            if failure.is_none() {
+                failure = linker.as_deref().and_then(|linker| line_failure(&line, linker));
+            }
+        }
+    }
+    let status = child.wait().context("Failed to wait for the build")?;
+
+    let mut rollback = None;
+    match (&linker, &failure) {
+        (Some(_), _) if status.success() => manager.confirm_linker()?,
+        (Some(linker), Some(failure)) => {
+            rollback = Some(manager.rollback_linker(failure)?);
+            output::warn(&Lang::detect().format(Message::LinkerRolledBack, &[linker, &failure.line]));
+        }
+        _ => {}
+    }
+    Ok(GuardedBuild { status, failure: if status.success() { None } else { failure }, rollback })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_signatures() {
+        let stderr = "   Compiling demo v0.1.0\n\
+                      error: linking with `clang` failed: exit status: 1\n  \
+                      = note: mold: fatal: unknown command line option: --no-undefined-version\n";
+        let failure = detect_failure(stderr, "mold").unwrap();
+        assert_eq!(failure.meaning, "mold aborted");
+        assert!(failure.line.starts_with("= note: mold: fatal:"));
+
+        // Signatures of other linkers, and problems in the code, are not ours
+        assert_eq!(detect_failure(stderr, "lld"), None);
+        assert_eq!(detect_failure("  = note: mold: error: undefined symbol: foo\n", "mold"), None);
+
+        // Driver and lookup failures apply to whichever linker was written
+        let driver = "clang: error: invalid linker name in argument '-fuse-ld=mold'";
+        assert!(line_failure(driver, "mold").is_some());
+        assert!(line_failure("error: linker `clang` not found", "lld").is_some());
+        assert!(line_failure("error[E0425]: cannot find value `x` in this scope", "mold").is_none());
+    }
+}
@@ -1,15 +1,36 @@
 //! Hardware detection and system information module.
 //!
 //! Provides platform-aware hardware detection with percentage-based
-//! calculations and graceful fallbacks.
+//! calculations and graceful fallbacks. The CPU [`topology`] (sockets, NUMA
+//! nodes, core types) sizes job counts on large and hybrid machines,
+//! [`dev_drive`] tells Windows Dev Drives from ordinary volumes, and
+//! [`disk_io`] measures whether builds wait on the disk, and [`load`] how
+//! many cores other workloads keep busy. Memory and disks are read through
+//! the process-wide [`system`] handle.
 
 use anyhow::{Context, Result};
 use num_cpus;
-use sysinfo::{Disks, System};
+use serde::{Deserialize, Serialize};
+use sysinfo::System;
 use std::fmt;
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+use crate::resource::{ResourceExpr, ResourceKind};
+
+pub mod dev_drive;
+pub mod disk_io;
+pub mod load;
+pub mod system;
+pub mod topology;
+
+pub use topology::CpuTopology;
+
+use system::{Memory, Refresh};
 
 /// System hardware information with support for percentage calculations.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HardwareInfo {
     /// Total number of logical CPU cores
     pub cpu_count: usize,
@@ -27,10 +48,13 @@ pub struct HardwareInfo {
     pub os_version: String,
     /// CPU architecture (e.g., x86_64, aarch64)
     pub arch: String,
+    /// Sockets, NUMA nodes and core types
+    #[serde(default)]
+    pub topology: CpuTopology,
 }
 
 /// Information about a disk/storage device.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiskInfo {
     /// Mount point or drive letter
     pub mount_point: String,
@@ -49,29 +73,37 @@ impl HardwareInfo {
     ///
     /// Returns hardware info with fallback values if detection fails.
     pub fn detect() -> Self {
-        let mut sys = System::new_all();
-        sys.refresh_all();
-
+        #[cfg(feature = "test-support")]
+        if let Some(hardware) = crate::test_support::hardware() {
+            return hardware;
+        }
         // CPU detection with fallbacks
         let cpu_count = num_cpus::get();
         let physical_cpu_count = num_cpus::get_physical();
 
-        // Memory detection
-        let total_memory = sys.total_memory();
-        let available_memory = sys.available_memory();
+        // Memory detection; sysinfo reports nothing on platforms it does not
+        // support (NetBSD, illumos), where the conservative defaults apply
+        let (total_memory, available_memory) = match system::memory(Refresh::default()) {
+            Memory { total: 0, .. } => {
+                let fallback = get_fallback_hardware();
+                debug!("Memory detection unavailable; assuming {} bytes", fallback.total_memory);
+                (fallback.total_memory, fallback.available_memory)
+            }
+            Memory { total, available } => (total, available),
+        };
 
-        // Disk detection - use separate Disks struct in sysinfo 0.30+
-        let disks_info = Disks::new_with_refreshed_list();
-        let disks = disks_info
-            .iter()
-            .map(|disk| DiskInfo {
-                mount_point: disk.mount_point().to_string_lossy().to_string(),
-                total_space: disk.total_space(),
-                available_space: disk.available_space(),
-                file_system: disk.file_system().to_string_lossy().to_string(),
-                is_ssd: detect_ssd_type(disk.name()),
-            })
-            .collect();
+        let disks = system::with_disks(Refresh::default(), |disks| {
+            disks
+                .iter()
+                .map(|disk| DiskInfo {
+                    mount_point: disk.mount_point().to_string_lossy().to_string(),
+                    total_space: disk.total_space(),
+                    available_space: disk.available_space(),
+                    file_system: disk.file_system().to_string_lossy().to_string(),
+                    is_ssd: detect_ssd_type(disk.name()),
+                })
+                .collect()
+        });
 
         // OS information
         let os_name = System::name()
@@ -91,6 +123,7 @@ impl HardwareInfo {
             os_name,
             os_version,
             arch,
+            topology: CpuTopology::detect(),
         }
     }
 
@@ -139,12 +172,49 @@ impl HardwareInfo {
             .map(|d| ((d.available_space as f64 * percentage) / 100.0).round() as u64)
     }
 
+    /// Find the disk that holds the given path.
+    ///
+    /// Uses the longest mount point that prefixes the (canonicalized) path.
+    pub fn disk_for_path(&self, path: &Path) -> Option<&DiskInfo> {
+        let resolved = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let path_str = resolved.to_string_lossy();
+
+        self.disks
+            .iter()
+            .filter(|d| path_str.starts_with(&d.mount_point))
+            .max_by_key(|d| d.mount_point.len())
+    }
+
+    /// Check whether the disk holding `path` has less than `min_free_gb` available.
+    ///
+    /// # Returns
+    /// The low-disk details, or None if there is enough space or the disk is unknown
+    pub fn low_disk(&self, path: &Path, min_free_gb: f64) -> Option<LowDiskCondition> {
+        let disk = self.disk_for_path(path)?;
+        let threshold_bytes = (min_free_gb * 1024.0 * 1024.0 * 1024.0) as u64;
+
+        if disk.available_space < threshold_bytes {
+            Some(LowDiskCondition {
+                mount_point: disk.mount_point.clone(),
+                available_bytes: disk.available_space,
+                threshold_bytes,
+            })
+        } else {
+            None
+        }
+    }
+
     /// Get recommended parallelism level for builds.
     ///
-    /// Returns a conservative estimate based on available resources.
+    /// Returns a conservative estimate based on available resources. On
+    /// NUMA machines it is sized for one node and on hybrid CPUs efficiency
+    /// cores count half; see [`CpuTopology::effective_cpus`].
     pub fn recommended_parallelism(&self) -> usize {
         // Use 75% of logical cores by default, but consider memory
-        let cpu_based = self.cpu_percentage(75.0, false);
+        let cpu_based = match self.topology.effective_cpus() {
+            Some(cpus) => ((cpus * 0.75).round() as usize).max(1),
+            None => self.cpu_percentage(75.0, false),
+        };
         
         // Assume each build job needs ~500MB
         let memory_based = (self.available_memory / (500 * 1024 * 1024)) as usize;
@@ -202,6 +272,7 @@ impl HardwareInfo {
         format!(
             "System: {} {} ({})\n\
              CPU: {} logical cores ({} physical)\n\
+             Topology: {}\n\
              Memory: {:.1} GB total, {:.1} GB available\n\
              Disks: {} mounted",
             self.os_name,
@@ -209,6 +280,7 @@ impl HardwareInfo {
             self.arch,
             self.cpu_count,
             self.physical_cpu_count,
+            self.topology.summary(),
             self.total_memory as f64 / (1024.0 * 1024.0 * 1024.0),
             self.available_memory as f64 / (1024.0 * 1024.0 * 1024.0),
             self.disks.len()
@@ -216,6 +288,29 @@ impl HardwareInfo {
     }
 }
 
+/// A disk that is below the configured free-space threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LowDiskCondition {
+    /// Mount point of the affected disk
+    pub mount_point: String,
+    /// Available space in bytes
+    pub available_bytes: u64,
+    /// Configured minimum free space in bytes
+    pub threshold_bytes: u64,
+}
+
+impl fmt::Display for LowDiskCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "low disk space on {}: {:.1} GB available, {:.1} GB required",
+            self.mount_point,
+            self.available_bytes as f64 / (1024.0 * 1024.0 * 1024.0),
+            self.threshold_bytes as f64 / (1024.0 * 1024.0 * 1024.0)
+        )
+    }
+}
+
 impl fmt::Display for HardwareInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.summary())
@@ -241,6 +336,9 @@ pub fn parse_percentage(input: &str) -> Option<f64> {
 
 /// Calculate the actual value from a percentage string or absolute value.
 ///
+/// Resource expressions such as `"min(8, 50%)"` are accepted as well; see
+/// [`crate::resource`].
+///
 /// # Arguments
 /// * `value` - Either a percentage ("75%") or absolute value ("4")
 /// * `total` - Total value for percentage calculation
@@ -248,11 +346,42 @@ pub fn parse_percentage(input: &str) -> Option<f64> {
 /// # Returns
 /// Calculated value, or None if invalid input
 pub fn calculate_from_percentage_or_value(value: &str, total: usize) -> Option<usize> {
-    if value.contains('%') {
-        parse_percentage(value).map(|p| ((total as f64 * p) / 100.0).round() as usize)
-    } else {
-        value.parse::<usize>().ok()
+    if let Ok(absolute) = value.trim().parse::<usize>() {
+        return Some(absolute);
+    }
+    if value.trim().ends_with('%') {
+        return parse_percentage(value).map(|p| ((total as f64 * p) / 100.0).round() as usize);
+    }
+    ResourceExpr::parse(value, ResourceKind::Amount)
+        .ok()
+        .map(|expression| expression.eval_amount(total as f64).round() as usize)
+}
+
+/// Measure the total size of a directory tree in bytes.
+///
+/// Symlinks are not followed and unreadable entries are skipped, so the
+/// result is a lower bound. Returns 0 if the path does not exist.
+pub fn directory_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+
+    if metadata.is_file() {
+        return metadata.len();
     }
+    if !metadata.is_dir() {
+        return 0;
+    }
+
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| directory_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
 }
 
 /// Get default hardware info for fallback scenarios.
@@ -274,6 +403,7 @@ pub fn get_fallback_hardware() -> HardwareInfo {
         os_name: detect_os_fallback().to_string(),
         os_version: "unknown".to_string(),
         arch: std::env::consts::ARCH.to_string(),
+        topology: CpuTopology::default(),
     }
 }
 
@@ -285,6 +415,12 @@ fn detect_os_fallback() -> &'static str {
         "macOS"
     } else if cfg!(target_os = "linux") {
         "Linux"
+    } else if cfg!(target_os = "freebsd") {
+        "FreeBSD"
+    } else if cfg!(target_os = "netbsd") {
+        "NetBSD"
+    } else if cfg!(target_os = "illumos") {
+        "illumos"
     } else {
         "Unknown"
     }
@@ -407,6 +543,38 @@ mod tests {
         assert!(parallelism <= 8);
     }
 
+    #[test]
+    fn test_low_disk_detection() {
+        let mount = if cfg!(windows) { "C:\\".to_string() } else { "/".to_string() };
+        let hw = HardwareInfo {
+            disks: vec![DiskInfo {
+                mount_point: mount.clone(),
+                total_space: 100 * 1024 * 1024 * 1024,
+                available_space: 2 * 1024 * 1024 * 1024, // 2 GB
+                file_system: "ext4".to_string(),
+                is_ssd: None,
+            }],
+            ..get_fallback_hardware()
+        };
+        let cwd = std::env::current_dir().unwrap();
+
+        let low = hw.low_disk(&cwd, 5.0).expect("2 GB free should be below a 5 GB threshold");
+        assert_eq!(low.mount_point, mount);
+        assert!(low.to_string().contains("low disk space"));
+        assert!(hw.low_disk(&cwd, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_directory_size() {
+        let temp = tempfile::TempDir::new().unwrap();
+        fs::write(temp.path().join("a"), vec![0u8; 100]).unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        fs::write(temp.path().join("sub").join("b"), vec![0u8; 50]).unwrap();
+
+        assert_eq!(directory_size(temp.path()), 150);
+        assert_eq!(directory_size(&temp.path().join("missing")), 0);
+    }
+
     #[test]
     fn test_check_resources() {
         // Create a test mount point that matches the platform
@@ -427,6 +595,7 @@ mod tests {
             os_name: detect_os_fallback().to_string(),
             os_version: "test".to_string(),
             arch: "x86_64".to_string(),
+            topology: CpuTopology::default(),
         };
 
         // Should pass with reasonable requirements
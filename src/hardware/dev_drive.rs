@@ -0,0 +1,133 @@
+//! Windows Dev Drive detection.
+//!
+//! A Dev Drive is a ReFS volume that Microsoft Defender scans asynchronously
+//! (performance mode) when it is trusted. Builds create and read many small
+//! files, which pay for synchronous real-time scanning on NTFS, so a target
+//! directory on a Dev Drive typically builds 10-30% faster. [`volume_kind`]
+//! asks `fsutil devdrv query` whether a ReFS volume is one, and
+//! [`measure_small_files`] times the small-file workload of a directory to
+//! project what moving there would gain.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use super::DiskInfo;
+
+/// Files written, read and removed by [`measure_small_files`]
+const SMALL_FILE_COUNT: usize = 200;
+
+/// Size of each probe file, about that of a small rlib or fingerprint
+const SMALL_FILE_BYTES: usize = 4096;
+
+/// Small files per second below which real-time scanning dominates
+const SLOW_SMALL_FILE_RATE: f64 = 2000.0;
+
+/// What a volume is, as far as builds are concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VolumeKind {
+    /// A Dev Drive; only trusted ones get Defender's performance mode
+    DevDrive {
+        /// Whether the volume is trusted (`fsutil devdrv trust`)
+        trusted: bool,
+    },
+    /// A ReFS volume that is not a Dev Drive
+    ReFs,
+    /// Anything else (NTFS, FAT, network shares, other systems)
+    Other,
+}
+
+/// Kind of the volume mounted at `disk`
+///
+/// Only ReFS volumes on Windows are asked about, since every Dev Drive is one.
+pub fn volume_kind(disk: &DiskInfo) -> VolumeKind {
+    if !disk.file_system.eq_ignore_ascii_case("refs") {
+        return VolumeKind::Other;
+    }
+    if !cfg!(windows) {
+        return VolumeKind::ReFs;
+    }
+    let volume = disk.mount_point.trim_end_matches(['\\', '/']);
+    std::process::Command::new("fsutil")
+        .args(["devdrv", "query", volume])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| parse_devdrv_query(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or(VolumeKind::ReFs)
+}
+
+/// Kind of volume reported by `fsutil devdrv query <volume>`
+pub fn parse_devdrv_query(output: &str) -> Option<VolumeKind> {
+    let status = output.lines().map(str::trim).find(|line| line.starts_with("This is"))?.to_ascii_lowercase();
+    if status.contains("not a developer volume") {
+        Some(VolumeKind::ReFs)
+    } else if status.contains("untrusted developer volume") {
+        Some(VolumeKind::DevDrive { trusted: false })
+    } else if status.contains("developer volume") {
+        Some(VolumeKind::DevDrive { trusted: true })
+    } else {
+        None
+    }
+}
+
+/// Small files per second written, read back and removed in `dir`
+///
+/// The probe files go to a scratch directory inside `dir` that is removed
+/// afterwards. `None` when `dir` cannot be written.
+pub fn measure_small_files(dir: &Path) -> Option<f64> {
+    let scratch = dir.join(format!(".cargo-optimize-probe-{}", std::process::id()));
+    fs::create_dir_all(&scratch).ok()?;
+    let content = vec![0u8; SMALL_FILE_BYTES];
+    let started = Instant::now();
+    let completed = (0..SMALL_FILE_COUNT).all(|index| {
+        let path = scratch.join(format!("{}.o", index));
+        fs::write(&path, &content).is_ok() && fs::read(&path).is_ok() && fs::remove_file(&path).is_ok()
+    });
+    let elapsed = started.elapsed();
+    let _ = fs::remove_dir_all(&scratch);
+    completed.then(|| SMALL_FILE_COUNT as f64 / elapsed.as_secs_f64().max(f64::EPSILON))
+}
+
+/// Build time saved on a Dev Drive, as a range in percent
+///
+/// Slow small-file I/O means scanning dominates and the upper end of the
+/// range applies; without a measurement the whole range does.
+pub fn projected_improvement(files_per_sec: Option<f64>) -> (u32, u32) {
+    match files_per_sec {
+        Some(rate) if rate < SLOW_SMALL_FILE_RATE => (20, 30),
+        Some(_) => (10, 20),
+        None => (10, 30),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_devdrv_query() {
+        let trusted = "This is a trusted developer volume.\n\
+                       Developer volumes are protected by antivirus filter, with the following filters attached:\n    WdFilter\n";
+        assert_eq!(parse_devdrv_query(trusted), Some(VolumeKind::DevDrive { trusted: true }));
+        assert_eq!(
+            parse_devdrv_query("This is an untrusted developer volume.\n"),
+            Some(VolumeKind::DevDrive { trusted: false })
+        );
+        assert_eq!(parse_devdrv_query("This is not a developer volume.\n"), Some(VolumeKind::ReFs));
+        assert_eq!(parse_devdrv_query("Error: The parameter is incorrect.\n"), None);
+
+        let ntfs = DiskInfo {
+            mount_point: "C:\\".to_string(),
+            total_space: 0,
+            available_space: 0,
+            file_system: "NTFS".to_string(),
+            is_ssd: None,
+        };
+        assert_eq!(volume_kind(&ntfs), VolumeKind::Other);
+        assert_eq!(projected_improvement(Some(500.0)), (20, 30));
+        assert_eq!(projected_improvement(Some(20000.0)), (10, 20));
+    }
+}
@@ -0,0 +1,198 @@
+//! Disk IO micro-benchmark of the filesystem builds write to.
+//!
+//! Cloud VMs often pair many cores with a network disk of a few thousand
+//! IOPS, and builds there wait on the disk rather than the compiler; a
+//! workstation with an NVMe drive is the opposite. [`DiskBenchmark::run`]
+//! writes a sequential and a random 4 KiB pattern into the target directory
+//! within a time budget, each flushed to the disk, and [`IoProfile`] weighs
+//! the result against the number of cores. Results are cached in the state
+//! file for [`MAX_AGE_SECS`].
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Time a benchmark may take, split between its two passes
+pub const BUDGET: Duration = Duration::from_secs(1);
+
+/// Age after which a cached benchmark is run again
+pub const MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Chunk written by the sequential pass
+const SEQUENTIAL_CHUNK: usize = 1 << 20;
+
+/// Most the sequential pass writes
+const SEQUENTIAL_MAX_BYTES: u64 = 64 << 20;
+
+/// Block written by the random pass, the size of a small object file write
+const RANDOM_BLOCK: usize = 4096;
+
+/// File the random pass writes into
+const RANDOM_FILE_BYTES: u64 = 16 << 20;
+
+/// Most writes of the random pass
+const RANDOM_MAX_WRITES: u64 = 4096;
+
+/// Random writes per second each core needs for the disk to keep up
+const IOPS_PER_CORE: u64 = 250;
+
+/// Sequential MB/s each core needs for the disk to keep up
+const MB_PER_SEC_PER_CORE: u64 = 25;
+
+/// How much faster than needed a disk must be for the build to be CPU-bound
+const HEADROOM: u64 = 4;
+
+/// Measured throughput of one filesystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskBenchmark {
+    /// Directory the benchmark wrote to
+    pub dir: PathBuf,
+    /// Sequential writes, flushed, in MB/s
+    pub sequential_mb_per_sec: u64,
+    /// Random 4 KiB writes, flushed, per second
+    pub random_write_iops: u64,
+    /// When it ran (seconds since the Unix epoch)
+    pub measured_at: u64,
+}
+
+/// Whether builds on a machine wait on the disk or on the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoProfile {
+    /// The disk cannot keep up with the cores
+    IoBound,
+    /// Neither clearly dominates
+    Balanced,
+    /// The disk keeps up with room to spare
+    CpuBound,
+}
+
+impl fmt::Display for IoProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IoProfile::IoBound => "IO-bound",
+            IoProfile::Balanced => "balanced",
+            IoProfile::CpuBound => "CPU-bound",
+        })
+    }
+}
+
+impl DiskBenchmark {
+    /// Benchmark the filesystem of `dir` within `budget`
+    ///
+    /// Writes to a scratch file in `dir` that is removed afterwards. `None`
+    /// when `dir` cannot be written.
+    pub fn run(dir: &Path, budget: Duration) -> Option<DiskBenchmark> {
+        let scratch = dir.join(format!(".cargo-optimize-io-{}", std::process::id()));
+        let result = measure(&scratch, budget / 2);
+        let _ = fs::remove_file(&scratch);
+        let (sequential_mb_per_sec, random_write_iops) = result.ok()?;
+        Some(DiskBenchmark {
+            dir: dir.to_path_buf(),
+            sequential_mb_per_sec,
+            random_write_iops,
+            measured_at: now(),
+        })
+    }
+
+    /// Whether the result still describes the disk of `dir`
+    pub fn is_current_for(&self, dir: &Path) -> bool {
+        self.dir == dir && now().saturating_sub(self.measured_at) < MAX_AGE_SECS
+    }
+
+    /// Whether a build with `cpu_count` jobs waits on this disk
+    pub fn profile(&self, cpu_count: usize) -> IoProfile {
+        let cores = cpu_count.max(1) as u64;
+        let (iops, mb_per_sec) = (cores * IOPS_PER_CORE, cores * MB_PER_SEC_PER_CORE);
+        if self.random_write_iops < iops || self.sequential_mb_per_sec < mb_per_sec {
+            IoProfile::IoBound
+        } else if self.random_write_iops >= iops * HEADROOM && self.sequential_mb_per_sec >= mb_per_sec * HEADROOM {
+            IoProfile::CpuBound
+        } else {
+            IoProfile::Balanced
+        }
+    }
+}
+
+impl fmt::Display for DiskBenchmark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} MB/s sequential, {} random 4 KiB writes/s in {}",
+            self.sequential_mb_per_sec,
+            self.random_write_iops,
+            self.dir.display()
+        )
+    }
+}
+
+/// Sequential MB/s and random writes per second, each pass within `budget`
+fn measure(scratch: &Path, budget: Duration) -> std::io::Result<(u64, u64)> {
+    let chunk = vec![0x5au8; SEQUENTIAL_CHUNK];
+    let mut file = File::create(scratch)?;
+    let started = Instant::now();
+    let mut written = 0u64;
+    while written < SEQUENTIAL_MAX_BYTES && started.elapsed() < budget {
+        file.write_all(&chunk)?;
+        written += chunk.len() as u64;
+    }
+    file.sync_all()?;
+    let sequential = written as f64 / 1_000_000.0 / started.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    let mut file = OpenOptions::new().write(true).truncate(true).open(scratch)?;
+    file.set_len(RANDOM_FILE_BYTES)?;
+    file.sync_all()?;
+    let block = vec![0xa5u8; RANDOM_BLOCK];
+    let blocks = RANDOM_FILE_BYTES / RANDOM_BLOCK as u64;
+    // xorshift: spread over the file without a rand dependency
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let started = Instant::now();
+    let mut writes = 0u64;
+    while writes < RANDOM_MAX_WRITES && started.elapsed() < budget {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        file.seek(SeekFrom::Start((seed % blocks) * RANDOM_BLOCK as u64))?;
+        file.write_all(&block)?;
+        writes += 1;
+    }
+    file.sync_all()?;
+    let random = writes as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON);
+    Ok((sequential.round() as u64, random.round() as u64))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_profile() {
+        let disk = |sequential_mb_per_sec, random_write_iops| DiskBenchmark {
+            dir: PathBuf::from("target"),
+            sequential_mb_per_sec,
+            random_write_iops,
+            measured_at: now(),
+        };
+        // A network disk on a 16-core VM
+        assert_eq!(disk(125, 3000).profile(16), IoProfile::IoBound);
+        assert_eq!(disk(125, 3000).profile(2), IoProfile::Balanced);
+        // NVMe on a 16-core workstation
+        assert_eq!(disk(3000, 200_000).profile(16), IoProfile::CpuBound);
+        assert!(disk(3000, 200_000).is_current_for(Path::new("target")));
+        assert!(!disk(3000, 200_000).is_current_for(Path::new("elsewhere")));
+
+        let dir = std::env::temp_dir();
+        let benchmark = DiskBenchmark::run(&dir, Duration::from_millis(50)).unwrap();
+        assert!(benchmark.sequential_mb_per_sec > 0 && benchmark.random_write_iops > 0);
+        assert!(!fs::read_dir(&dir).unwrap().flatten().any(|entry| {
+            entry.file_name().to_string_lossy() == format!(".cargo-optimize-io-{}", std::process::id())
+        }));
+    }
+}
@@ -0,0 +1,106 @@
+//! Load from other workloads on a shared machine.
+//!
+//! A fixed job count fights whatever else runs on a shared dev machine:
+//! another user's build, a test suite, an IDE indexing. [`sample`] reads how
+//! many cores such workloads keep busy. It takes the 1-minute load average,
+//! minus the rustc and linker processes running right now (configuring runs
+//! inside a build), or the CPU usage where there is no load average
+//! (Windows). On Linux the CPU pressure stall information (PSI) adds how
+//! often runnable tasks waited for a core. [`adaptive_jobs`] leaves those
+//! cores to the other workloads, within configured bounds. The machine is
+//! read through the shared handle of [`crate::hardware::system`].
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use sysinfo::System;
+
+use crate::hardware::system::{self, Refresh};
+use crate::profiler;
+
+/// Share of time runnable tasks waited for a core, from `/proc/pressure/cpu`
+const PSI_CPU_PATH: &str = "/proc/pressure/cpu";
+
+/// Load of the machine from workloads other than the build
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SystemLoad {
+    /// Cores kept busy by other workloads
+    pub busy_cores: f64,
+    /// Percent of the last 10 seconds some runnable task waited for a core (Linux PSI)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_pressure: Option<f64>,
+}
+
+impl fmt::Display for SystemLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1} cores busy with other work", self.busy_cores)?;
+        if let Some(pressure) = self.cpu_pressure {
+            write!(f, ", tasks waiting for a CPU {:.0}% of the time", pressure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sample the load of this machine, without waiting
+///
+/// Where there is no load average, CPU usage is measured since the previous
+/// reading of the shared handle (see [`system::with_cpu_usage`]); `None`
+/// when there was none yet.
+pub fn sample() -> Option<SystemLoad> {
+    let load_average = System::load_average().one;
+    let busy_cores = if load_average > 0.0 {
+        let build_processes = system::with_processes(Refresh::default(), |system| {
+            system.processes().values().filter(|process| profiler::classify(process.name()).is_some()).count()
+        });
+        (load_average - build_processes as f64).max(0.0)
+    } else {
+        system::with_cpu_usage(|system| {
+            let total = f64::from(system.global_cpu_info().cpu_usage()) * system.cpus().len() as f64 / 100.0;
+            let build: f64 = system
+                .processes()
+                .values()
+                .filter(|process| profiler::classify(process.name()).is_some())
+                .map(|process| f64::from(process.cpu_usage()) / 100.0)
+                .sum();
+            (total - build).max(0.0)
+        })?
+    };
+    let cpu_pressure = fs::read_to_string(PSI_CPU_PATH).ok().and_then(|text| parse_cpu_pressure(&text));
+    Some(SystemLoad { busy_cores, cpu_pressure })
+}
+
+/// `avg10` of the `some` line of a PSI file
+pub fn parse_cpu_pressure(text: &str) -> Option<f64> {
+    let line = text.lines().find(|line| line.starts_with("some "))?;
+    line.split_whitespace().find_map(|field| field.strip_prefix("avg10="))?.parse().ok()
+}
+
+/// Jobs to run with `busy_cores` taken by other workloads
+///
+/// Never more than `base` (the job count without adapting), the cores left
+/// over, or `max`; never fewer than `min`.
+pub fn adaptive_jobs(base: usize, cpu_count: usize, busy_cores: f64, min: usize, max: usize) -> usize {
+    let free = cpu_count.saturating_sub(busy_cores.round() as usize);
+    base.min(free).min(max).max(min).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_jobs() {
+        let psi = "some avg10=12.50 avg60=3.10 avg300=1.00 total=123\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=0\n";
+        assert_eq!(parse_cpu_pressure(psi), Some(12.5));
+        assert_eq!(parse_cpu_pressure(""), None);
+
+        // 6 of 16 cores taken: 10 left, within 2..=12
+        assert_eq!(adaptive_jobs(16, 16, 6.0, 2, 12), 10);
+        // An idle machine still stays within the maximum and the configured jobs
+        assert_eq!(adaptive_jobs(16, 16, 0.0, 2, 12), 12);
+        assert_eq!(adaptive_jobs(8, 16, 0.0, 2, 12), 8);
+        // An overloaded one keeps the minimum
+        assert_eq!(adaptive_jobs(16, 16, 40.0, 2, 12), 2);
+        assert_eq!(adaptive_jobs(16, 16, 40.0, 0, 12), 1);
+    }
+}
@@ -0,0 +1,207 @@
+//! One sysinfo handle for the whole process, refreshed selectively.
+//!
+//! `System::new_all()` followed by `refresh_all()` enumerates every process,
+//! CPU and component of the machine, which takes tens of milliseconds and
+//! was repeated by each hardware query of an apply. Hardware detection only
+//! needs memory and disks, so the shared handle refreshes just those, and
+//! only when the cached values are older than the caller's [`Refresh`]
+//! policy allows. Total memory never changes and is read once. Processes
+//! and CPU usage are read only by the queries that need them.
+
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use sysinfo::{Disks, System, MINIMUM_CPU_UPDATE_INTERVAL};
+
+/// How long [`Refresh::default`] trusts cached values
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(1);
+
+/// When a query refreshes the shared handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refresh {
+    /// Only the first query of the process reads the system
+    Cached,
+    /// Values older than this are read again
+    MaxAge(Duration),
+    /// Every query reads the system
+    Always,
+}
+
+impl Default for Refresh {
+    fn default() -> Self {
+        Refresh::MaxAge(DEFAULT_MAX_AGE)
+    }
+}
+
+impl Refresh {
+    /// Whether values read at `at` (`None`: never) must be read again
+    fn due(self, at: Option<Instant>) -> bool {
+        match (self, at) {
+            (_, None) | (Refresh::Always, _) => true,
+            (Refresh::Cached, Some(_)) => false,
+            (Refresh::MaxAge(age), Some(at)) => at.elapsed() >= age,
+        }
+    }
+}
+
+/// Memory of the machine, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Memory {
+    /// Installed memory; 0 where sysinfo cannot read it (NetBSD, illumos)
+    pub total: u64,
+    /// Memory available to new processes
+    pub available: u64,
+}
+
+/// How often the shared handle actually read the system
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefreshCounts {
+    /// Memory reads
+    pub memory: u64,
+    /// Disk list reads
+    pub disks: u64,
+    /// Process list reads
+    pub processes: u64,
+    /// CPU usage reads
+    pub cpu: u64,
+}
+
+struct Shared {
+    system: System,
+    memory_at: Option<Instant>,
+    disks: Disks,
+    disks_at: Option<Instant>,
+    processes_at: Option<Instant>,
+    cpu_at: Option<Instant>,
+    /// CPU usage was read twice far enough apart to be measured
+    cpu_measured: bool,
+    counts: RefreshCounts,
+}
+
+fn shared() -> MutexGuard<'static, Shared> {
+    static SHARED: OnceLock<Mutex<Shared>> = OnceLock::new();
+    SHARED
+        .get_or_init(|| {
+            Mutex::new(Shared {
+                system: System::new(),
+                memory_at: None,
+                disks: Disks::new(),
+                disks_at: None,
+                processes_at: None,
+                cpu_at: None,
+                cpu_measured: false,
+                counts: RefreshCounts::default(),
+            })
+        })
+        .lock()
+        // The cached values stay usable after a panic elsewhere
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Memory of the machine, refreshed according to `refresh`
+pub fn memory(refresh: Refresh) -> Memory {
+    let mut shared = shared();
+    if refresh.due(shared.memory_at) {
+        shared.system.refresh_memory();
+        shared.memory_at = Some(Instant::now());
+        shared.counts.memory += 1;
+    }
+    Memory { total: shared.system.total_memory(), available: shared.system.available_memory() }
+}
+
+/// Installed memory in bytes, read once per process
+pub fn total_memory() -> u64 {
+    memory(Refresh::Cached).total
+}
+
+/// Run `f` on the mounted disks, refreshed according to `refresh`
+///
+/// A refresh lists the disks again, so drives mounted since the last one
+/// show up, and re-reads their free space.
+pub fn with_disks<T>(refresh: Refresh, f: impl FnOnce(&Disks) -> T) -> T {
+    let mut shared = shared();
+    if refresh.due(shared.disks_at) {
+        shared.disks.refresh_list();
+        shared.disks_at = Some(Instant::now());
+        shared.counts.disks += 1;
+    }
+    f(&shared.disks)
+}
+
+/// Run `f` on the running processes, refreshed according to `refresh`
+pub fn with_processes<T>(refresh: Refresh, f: impl FnOnce(&System) -> T) -> T {
+    let mut shared = shared();
+    if refresh.due(shared.processes_at) {
+        shared.system.refresh_processes();
+        shared.processes_at = Some(Instant::now());
+        shared.counts.processes += 1;
+    }
+    f(&shared.system)
+}
+
+/// Run `f` on the CPU usage of the machine and its processes, if it was measured
+///
+/// sysinfo measures CPU usage between two reads at least
+/// [`MINIMUM_CPU_UPDATE_INTERVAL`] apart. Instead of waiting that long, a
+/// read reuses the previous one of the process: the first call only takes a
+/// reading and returns `None`, later ones measure since the one before.
+pub fn with_cpu_usage<T>(f: impl FnOnce(&System) -> T) -> Option<T> {
+    let mut shared = shared();
+    if shared.cpu_at.is_none_or(|at| at.elapsed() >= MINIMUM_CPU_UPDATE_INTERVAL) {
+        let measured = shared.cpu_at.is_some();
+        shared.system.refresh_cpu_usage();
+        shared.system.refresh_processes();
+        let now = Some(Instant::now());
+        shared.cpu_at = now;
+        shared.processes_at = now;
+        shared.cpu_measured |= measured;
+        shared.counts.cpu += 1;
+        shared.counts.processes += 1;
+    }
+    shared.cpu_measured.then(|| f(&shared.system))
+}
+
+/// Reads of the system so far, for measuring what caching saves
+pub fn refresh_counts() -> RefreshCounts {
+    shared().counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_policies() {
+        let just_now = Some(Instant::now());
+        let long_ago = Instant::now().checked_sub(Duration::from_secs(60));
+        assert!(Refresh::Cached.due(None));
+        assert!(!Refresh::Cached.due(long_ago));
+        assert!(Refresh::Always.due(just_now));
+        assert!(!Refresh::default().due(just_now));
+        assert!(Refresh::default().due(long_ago));
+    }
+
+    #[test]
+    fn test_shared_handle_counts_reads() {
+        let before = refresh_counts();
+        let fresh = memory(Refresh::Always);
+        assert!(refresh_counts().memory > before.memory);
+        assert_eq!(total_memory(), fresh.total);
+        
+        let before = refresh_counts();
+        let count = with_processes(Refresh::Always, |system| system.processes().len());
+        assert!(count > 0);
+        with_processes(Refresh::Cached, |_| ());
+        assert_eq!(refresh_counts().processes, before.processes + 1);
+    }
+
+    #[test]
+    fn test_cpu_usage_reuses_the_previous_reading() {
+        with_cpu_usage(|_| ());
+        std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+        assert!(with_cpu_usage(|system| system.cpus().len()).is_some_and(|cpus| cpus > 0));
+        // Right after a reading the measurement stands without another one
+        let reads = refresh_counts().cpu;
+        assert!(with_cpu_usage(|_| ()).is_some());
+        assert_eq!(refresh_counts().cpu, reads);
+    }
+}
@@ -0,0 +1,272 @@
+//! CPU topology: sockets, NUMA nodes and core types.
+//!
+//! On multi-socket build servers memory is attached to a socket, and a build
+//! spread over every core keeps pulling data across the interconnect. Hybrid
+//! CPUs (Intel P/E cores, ARM big.LITTLE) mix fast and slow cores, and a
+//! long single-threaded link step lands on a slow one as often as not.
+//! [`CpuTopology::detect`] reads the topology from sysfs on Linux and from
+//! `sysctl` on macOS; elsewhere it reports one socket and one node, which
+//! leaves job recommendations unchanged.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// A NUMA node: CPUs sharing local memory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NumaNode {
+    /// Node number
+    pub id: usize,
+    /// Logical CPUs of the node
+    pub cpus: Vec<usize>,
+    /// Memory attached to the node in bytes, when known
+    pub memory_bytes: Option<u64>,
+}
+
+/// Logical CPUs of each kind on a hybrid CPU
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreTypes {
+    /// Logical CPUs on performance cores
+    pub performance: Vec<usize>,
+    /// Logical CPUs on efficiency cores
+    pub efficiency: Vec<usize>,
+}
+
+/// How the logical CPUs are laid out
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuTopology {
+    /// Physical packages; 0 when unknown
+    pub sockets: usize,
+    /// NUMA nodes with CPUs; empty when the system does not report any
+    pub numa_nodes: Vec<NumaNode>,
+    /// Performance and efficiency cores; `None` when all cores are alike
+    pub core_types: Option<CoreTypes>,
+}
+
+impl CpuTopology {
+    /// Detect the topology of this machine
+    pub fn detect() -> Self {
+        if cfg!(target_os = "linux") {
+            Self::from_sysfs(Path::new("/sys"))
+        } else if cfg!(target_os = "macos") {
+            Self::from_sysctl()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Read the topology from a sysfs tree mounted at `sys`
+    pub fn from_sysfs(sys: &Path) -> Self {
+        let cpu_dir = sys.join("devices/system/cpu");
+        let cpus = read(&cpu_dir.join("online")).map(|list| parse_cpu_list(&list)).unwrap_or_default();
+        let sockets: BTreeSet<String> = cpus
+            .iter()
+            .filter_map(|cpu| read(&cpu_dir.join(format!("cpu{}/topology/physical_package_id", cpu))))
+            .collect();
+
+        let mut numa_nodes: Vec<NumaNode> = fs::read_dir(sys.join("devices/system/node"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let id = entry.file_name().to_str()?.strip_prefix("node")?.parse().ok()?;
+                let cpus = parse_cpu_list(&read(&entry.path().join("cpulist"))?);
+                let memory_bytes = read(&entry.path().join("meminfo")).and_then(|meminfo| node_memory(&meminfo));
+                Some(NumaNode { id, cpus, memory_bytes })
+            })
+            .filter(|node| !node.cpus.is_empty())
+            .collect();
+        numa_nodes.sort_by_key(|node| node.id);
+
+        // Intel hybrid CPUs register a PMU per core type; elsewhere cores differ in capacity
+        let pmu = |name: &str| read(&sys.join("devices").join(name).join("cpus")).map(|list| parse_cpu_list(&list));
+        let core_types = match (pmu("cpu_core"), pmu("cpu_atom")) {
+            (Some(performance), Some(efficiency)) if !performance.is_empty() && !efficiency.is_empty() => {
+                Some(CoreTypes { performance, efficiency })
+            }
+            _ => {
+                let capacities: Vec<(usize, u64)> = cpus
+                    .iter()
+                    .filter_map(|cpu| {
+                        let capacity = read(&cpu_dir.join(format!("cpu{}/cpu_capacity", cpu)))?;
+                        Some((*cpu, capacity.parse().ok()?))
+                    })
+                    .collect();
+                let fastest = capacities.iter().map(|(_, capacity)| *capacity).max();
+                let (performance, efficiency): (Vec<_>, Vec<_>) =
+                    capacities.iter().partition(|(_, capacity)| Some(*capacity) == fastest);
+                (!efficiency.is_empty()).then(|| CoreTypes {
+                    performance: performance.into_iter().map(|(cpu, _)| cpu).collect(),
+                    efficiency: efficiency.into_iter().map(|(cpu, _)| cpu).collect(),
+                })
+            }
+        };
+
+        CpuTopology { sockets: sockets.len(), numa_nodes, core_types }
+    }
+
+    /// Read the core types of Apple silicon (`hw.perflevel*`)
+    fn from_sysctl() -> Self {
+        let sysctl = |name: &str| {
+            let output = std::process::Command::new("sysctl").args(["-n", name]).output().ok()?;
+            String::from_utf8_lossy(&output.stdout).trim().parse::<usize>().ok()
+        };
+        let performance = sysctl("hw.perflevel0.logicalcpu").unwrap_or(0);
+        let efficiency = sysctl("hw.perflevel1.logicalcpu").unwrap_or(0);
+        // CPU numbers are not exposed; number them performance cores first
+        let core_types = (performance > 0 && efficiency > 0).then(|| CoreTypes {
+            performance: (0..performance).collect(),
+            efficiency: (performance..performance + efficiency).collect(),
+        });
+        CpuTopology { sockets: sysctl("hw.packages").unwrap_or(1), numa_nodes: Vec::new(), core_types }
+    }
+
+    /// Whether memory is split across NUMA nodes
+    pub fn is_numa(&self) -> bool {
+        self.numa_nodes.len() > 1
+    }
+
+    /// The NUMA node with the most CPUs, on a NUMA machine
+    pub fn largest_node(&self) -> Option<&NumaNode> {
+        self.numa_nodes
+            .iter()
+            .filter(|_| self.is_numa())
+            .max_by_key(|node| (node.cpus.len(), std::cmp::Reverse(node.id)))
+    }
+
+    /// Logical CPUs a build should be sized for
+    ///
+    /// On a NUMA machine that is the largest node, so jobs stay on local
+    /// memory. Efficiency cores count as half a core each. `None` when the
+    /// topology gives no reason to deviate from the logical CPU count.
+    pub fn effective_cpus(&self) -> Option<f64> {
+        let cpus: Vec<usize> = match self.largest_node() {
+            Some(node) => node.cpus.clone(),
+            None if self.core_types.is_some() => Vec::new(),
+            None => return None,
+        };
+        let Some(types) = &self.core_types else {
+            return Some(cpus.len() as f64);
+        };
+        let in_scope = |cpu: &&usize| cpus.is_empty() || cpus.contains(cpu);
+        let performance = types.performance.iter().filter(in_scope).count();
+        let efficiency = types.efficiency.iter().filter(in_scope).count();
+        Some(performance as f64 + efficiency as f64 / 2.0)
+    }
+
+    /// One-line description (e.g. "2 sockets, 2 NUMA nodes, 16 performance + 8 efficiency CPUs")
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.sockets > 0 {
+            parts.push(format!("{} socket{}", self.sockets, if self.sockets == 1 { "" } else { "s" }));
+        }
+        if self.is_numa() {
+            parts.push(format!("{} NUMA nodes", self.numa_nodes.len()));
+        }
+        if let Some(types) = &self.core_types {
+            parts.push(format!("{} performance + {} efficiency CPUs", types.performance.len(), types.efficiency.len()));
+        }
+        if parts.is_empty() {
+            "unknown".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Parse a kernel CPU list such as `0-3,8,10-11`
+pub fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let bounds = match range.split_once('-') {
+            Some((first, last)) => first.trim().parse().ok().zip(last.trim().parse().ok()),
+            None => range.trim().parse::<usize>().ok().map(|cpu| (cpu, cpu)),
+        };
+        if let Some((first, last)) = bounds {
+            cpus.extend(first..=last);
+        }
+    }
+    cpus
+}
+
+/// Render CPUs as a kernel CPU list, the form `taskset -c` accepts
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut sorted = cpus.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut ranges: Vec<String> = Vec::new();
+    let mut iter = sorted.into_iter().peekable();
+    while let Some(first) = iter.next() {
+        let mut last = first;
+        while iter.peek() == Some(&(last + 1)) {
+            last = iter.next().unwrap_or(last);
+        }
+        ranges.push(if first == last { first.to_string() } else { format!("{}-{}", first, last) });
+    }
+    ranges.join(",")
+}
+
+fn read(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|content| content.trim().to_string())
+}
+
+/// `MemTotal` of a node's `meminfo` (`Node 0 MemTotal:  32823520 kB`)
+fn node_memory(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|line| line.contains("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().rev().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_cpu_lists() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), [0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list(""), Vec::<usize>::new());
+        assert_eq!(format_cpu_list(&[11, 0, 1, 2, 3, 8, 10]), "0-3,8,10-11");
+        assert_eq!(format_cpu_list(&[5]), "5");
+    }
+
+    #[test]
+    fn test_sysfs_topology() {
+        let sys = tempfile::TempDir::new().unwrap();
+        let root = sys.path();
+        write(root, "devices/system/cpu/online", "0-7\n");
+        for cpu in 0..8 {
+            write(root, &format!("devices/system/cpu/cpu{}/topology/physical_package_id", cpu), &(cpu / 4).to_string());
+        }
+        write(root, "devices/system/node/node0/cpulist", "0-3\n");
+        write(root, "devices/system/node/node0/meminfo", "Node 0 MemTotal:       16384 kB\nNode 0 MemFree: 1 kB\n");
+        write(root, "devices/system/node/node1/cpulist", "4-7\n");
+        write(root, "devices/system/node/node2/cpulist", "\n");
+
+        let topology = CpuTopology::from_sysfs(root);
+        assert_eq!(topology.sockets, 2);
+        assert_eq!(topology.numa_nodes.len(), 2);
+        assert_eq!(topology.numa_nodes[0].memory_bytes, Some(16 << 20));
+        assert_eq!(topology.largest_node().unwrap().id, 0);
+        assert_eq!(topology.effective_cpus(), Some(4.0));
+        assert_eq!(topology.summary(), "2 sockets, 2 NUMA nodes");
+
+        // Cores of differing capacity, one node
+        fs::remove_dir_all(root.join("devices/system/node/node1")).unwrap();
+        for cpu in 0..8 {
+            write(root, &format!("devices/system/cpu/cpu{}/cpu_capacity", cpu), if cpu < 2 { "1024" } else { "446" });
+        }
+        let topology = CpuTopology::from_sysfs(root);
+        assert!(!topology.is_numa());
+        assert_eq!(topology.core_types.as_ref().unwrap().performance, [0, 1]);
+        assert_eq!(topology.effective_cpus(), Some(2.0 + 3.0));
+
+        assert_eq!(CpuTopology::default().effective_cpus(), None);
+    }
+}
@@ -0,0 +1,301 @@
+//! Git hooks that keep a team's cargo config in sync.
+//!
+//! [`install_git_hooks`] adds a block to the repository's `post-checkout` and
+//! `post-merge` hooks. After a checkout or pull it runs a fast drift check —
+//! a `git diff` of the files that drive cargo-optimize's decisions — and
+//! prints one line recommending a re-run when any of them changed. Existing
+//! hook content is kept; the block is delimited by marker lines so installing
+//! twice replaces it and [`uninstall_git_hooks`] removes exactly what was added.
+//! In a hook that ends early with a top-level `exit`, the block goes before
+//! it so it still runs.
+//!
+//! Projects that adopted cargo-optimize through the `cargo optimize` alias
+//! instead of a build script have nothing that re-runs it, so
+//! [`install_git_hooks_with`] can install a block that runs `cargo optimize`
+//! on drift instead of only saying so.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use crate::audit;
+use crate::output;
+
+/// Hooks the drift check is installed into
+pub const HOOK_NAMES: &[&str] = &["post-checkout", "post-merge"];
+
+/// First line of the block managed by cargo-optimize
+pub const BLOCK_BEGIN: &str = "# >>> cargo-optimize drift check >>>";
+
+/// Last line of the block managed by cargo-optimize
+pub const BLOCK_END: &str = "# <<< cargo-optimize drift check <<<";
+
+/// Files whose changes make a re-run worthwhile, relative to the project root
+pub const WATCHED_FILES: &[&str] = &["cargo-optimize.toml", "rust-toolchain.toml", "rust-toolchain"];
+
+const SHEBANG: &str = "#!/bin/sh";
+
+/// What a hook does when the watched files changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HookAction {
+    /// Print a line recommending a re-run
+    #[default]
+    Notify,
+    /// Run `cargo optimize` in the project directory
+    Apply,
+}
+
+/// Directory git runs hooks from for the repository containing `project_root`
+///
+/// Honors `core.hooksPath` and linked worktrees.
+pub fn hooks_dir(project_root: &Path) -> Result<PathBuf> {
+    let dir = git(project_root, &["rev-parse", "--git-path", "hooks"])?;
+    Ok(project_root.join(dir))
+}
+
+/// Install the drift check into the `post-checkout` and `post-merge` hooks
+///
+/// Idempotent: an existing cargo-optimize block is replaced, any other hook
+/// content is preserved. Returns the hook files written.
+pub fn install_git_hooks(project_root: &Path) -> Result<Vec<PathBuf>> {
+    install_git_hooks_with(project_root, HookAction::Notify)
+}
+
+/// Install the drift check with `action`, replacing one installed with another
+pub fn install_git_hooks_with(project_root: &Path, action: HookAction) -> Result<Vec<PathBuf>> {
+    output::ensure_enabled()?;
+    let dir = hooks_dir(project_root)?;
+    // Hooks run from the repository root; the project may be a subdirectory
+    let prefix = git(project_root, &["rev-parse", "--show-prefix"])?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+
+    let mut written = Vec::new();
+    for hook in HOOK_NAMES {
+        let path = dir.join(hook);
+        let existing = match fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        let content = insert_block(existing.as_deref(), &hook_block_with(hook, &prefix, action));
+        if existing.as_deref() != Some(content.as_str()) {
+            fs::write(&path, &content).with_context(|| format!("Failed to write {:?}", path))?;
+            audit::record(project_root, "install-hooks", &path, existing.as_deref(), Some(&content));
+        }
+        make_executable(&path)?;
+        debug!("Installed drift check into {:?}", path);
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Remove the drift check from the hooks
+///
+/// Hook files that only contained the block are deleted. Returns the hook
+/// files changed or removed.
+pub fn uninstall_git_hooks(project_root: &Path) -> Result<Vec<PathBuf>> {
+    let dir = hooks_dir(project_root)?;
+    let mut changed = Vec::new();
+    for hook in HOOK_NAMES {
+        let path = dir.join(hook);
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+        match remove_block(&content) {
+            Some(rest) if rest.trim().is_empty() || rest.trim() == SHEBANG => {
+                fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+                audit::record(project_root, "uninstall-hooks", &path, Some(&content), None);
+            }
+            Some(rest) => {
+                fs::write(&path, &rest).with_context(|| format!("Failed to write {:?}", path))?;
+                audit::record(project_root, "uninstall-hooks", &path, Some(&content), Some(&rest));
+            }
+            None => continue,
+        }
+        changed.push(path);
+    }
+    Ok(changed)
+}
+
+/// Shell block running the drift check for `hook`
+///
+/// `prefix` is the project directory relative to the repository root
+/// (`git rev-parse --show-prefix`, empty or ending in `/`).
+pub fn hook_block(hook: &str, prefix: &str) -> String {
+    hook_block_with(hook, prefix, HookAction::Notify)
+}
+
+/// Shell block running the drift check for `hook` with `action`
+pub fn hook_block_with(hook: &str, prefix: &str, action: HookAction) -> String {
+    let files: Vec<String> = WATCHED_FILES
+        .iter()
+        .map(|file| shell_quote(&format!("{}{}", prefix, file)))
+        .collect();
+    // post-checkout gets <old> <new> <branch-flag>; file checkouts pass flag 0
+    let range = match hook {
+        "post-checkout" => "[ \"$3\" = 1 ] && co_range=\"$1 $2\"",
+        _ => "co_range=\"ORIG_HEAD HEAD\"",
+    };
+    let run = match action {
+        HookAction::Notify => {
+            "echo \"cargo-optimize: build settings changed upstream; re-run cargo-optimize to update .cargo/config.toml\"".to_string()
+        }
+        HookAction::Apply => format!(
+            "(cd {} && cargo optimize) || echo \"cargo-optimize: updating .cargo/config.toml failed; run cargo optimize\"",
+            shell_quote(&format!("./{}", prefix))
+        ),
+    };
+    format!(
+        "{begin}\n\
+         # Installed by cargo-optimize; remove with cargo_optimize::hooks::uninstall_git_hooks()\n\
+         co_range=\n\
+         {range}\n\
+         if [ -n \"$co_range\" ] && ! git diff --quiet $co_range -- {files} 2>/dev/null; then\n\
+         \x20   {run}\n\
+         fi\n\
+         {end}\n",
+        begin = BLOCK_BEGIN,
+        range = range,
+        files = files.join(" "),
+        run = run,
+        end = BLOCK_END,
+    )
+}
+
+/// `value` as a single-quoted shell word
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Hook content with `block` added, replacing a previously installed one
+///
+/// The block is appended, or put before the first top-level `exit` so the
+/// hook does not end before reaching it.
+fn insert_block(existing: Option<&str>, block: &str) -> String {
+    match existing {
+        None => format!("{}\n\n{}", SHEBANG, block),
+        Some(content) => {
+            let content = remove_block(content).unwrap_or_else(|| content.to_string());
+            let (head, tail) = content.split_at(top_level_exit(&content).unwrap_or(content.len()));
+            let mut head = head.to_string();
+            if !head.is_empty() && !head.ends_with('\n') {
+                head.push('\n');
+            }
+            if !head.is_empty() && !head.ends_with("\n\n") {
+                head.push('\n');
+            }
+            head.push_str(block);
+            if !tail.is_empty() {
+                head.push('\n');
+                head.push_str(tail);
+            }
+            head
+        }
+    }
+}
+
+/// Byte offset of the first unindented `exit` command in `content`
+fn top_level_exit(content: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let command = line.trim_end();
+        if command == "exit" || command.starts_with("exit ") || command.starts_with("exit;") {
+            return Some(offset);
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Hook content without the cargo-optimize block, `None` if it has none
+fn remove_block(content: &str) -> Option<String> {
+    let start = content.find(BLOCK_BEGIN)?;
+    let end = content[start..].find(BLOCK_END)? + start + BLOCK_END.len();
+    let before = content[..start].trim_end_matches('\n');
+    let after = content[end..].trim_start_matches('\n');
+    let mut rest = before.to_string();
+    if !rest.is_empty() {
+        rest.push('\n');
+    }
+    if !after.is_empty() {
+        rest.push('\n');
+        rest.push_str(after);
+    }
+    Some(rest)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    fs::set_permissions(path, permissions).with_context(|| format!("Failed to make {:?} executable", path))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_insertion_is_idempotent_and_reversible() {
+        let block = hook_block("post-merge", "");
+        let fresh = insert_block(None, &block);
+        assert!(fresh.starts_with(SHEBANG));
+        assert_eq!(insert_block(Some(&fresh), &block), fresh);
+
+        let user_hook = "#!/bin/sh\necho user hook\n";
+        let combined = insert_block(Some(user_hook), &block);
+        assert!(combined.starts_with(user_hook));
+        assert_eq!(insert_block(Some(&combined), &block), combined);
+        assert_eq!(remove_block(&combined).as_deref(), Some(user_hook));
+        assert_eq!(remove_block(user_hook), None);
+    }
+
+    #[test]
+    fn test_block_goes_before_a_top_level_exit() {
+        let block = hook_block("post-merge", "");
+        let user_hook = "#!/bin/sh\nif [ -n \"$SKIP\" ]; then\n    exit 0\nfi\necho user hook\nexit 0\n";
+        let combined = insert_block(Some(user_hook), &block);
+        let block_at = combined.find(BLOCK_BEGIN).unwrap();
+        assert!(combined[..block_at].contains("    exit 0\nfi\necho user hook\n"), "{}", combined);
+        assert!(combined.ends_with(&format!("{}\n\nexit 0\n", BLOCK_END)), "{}", combined);
+        assert_eq!(insert_block(Some(&combined), &block), combined);
+        assert!(remove_block(&combined).unwrap().ends_with("echo user hook\n\nexit 0\n"));
+    }
+
+    #[test]
+    fn test_hook_block_watches_project_files() {
+        let block = hook_block("post-checkout", "crates/app/");
+        assert!(block.contains("'crates/app/cargo-optimize.toml'"));
+        assert!(block.contains("[ \"$3\" = 1 ]"));
+        assert!(hook_block("post-merge", "").contains("ORIG_HEAD HEAD"));
+        let apply = hook_block_with("post-merge", "crates/app/", HookAction::Apply);
+        assert!(apply.contains("(cd './crates/app/' && cargo optimize)"));
+        let quoted = hook_block_with("post-merge", "it's/", HookAction::Apply);
+        assert!(quoted.contains("'it'\\''s/cargo-optimize.toml'"), "{}", quoted);
+        assert!(quoted.contains("(cd './it'\\''s/' && cargo optimize)"), "{}", quoted);
+        assert!(apply.starts_with(BLOCK_BEGIN) && apply.ends_with(&format!("{}\n", BLOCK_END)));
+    }
+}
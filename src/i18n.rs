@@ -0,0 +1,848 @@
+//! Localized user-facing messages.
+//!
+//! Every message shown to users has a [`Message`] id and a translation in
+//! each supported [`Lang`]. The catalogs are plain `match` expressions, so a
+//! message without a translation in some language does not compile. The
+//! language comes from `CARGO_OPTIMIZE_LANG` or, failing that, the POSIX
+//! locale variables (`LC_ALL`, `LC_MESSAGES`, `LANG`), with English as the
+//! fallback. Placeholders are positional: `{0}`, `{1}`, ...
+
+use std::env;
+use std::fmt;
+
+/// Environment variable selecting the message language (e.g. `ja`, `de_DE`)
+pub const LANG_ENV_VAR: &str = "CARGO_OPTIMIZE_LANG";
+
+/// Locale variables consulted after [`LANG_ENV_VAR`], in POSIX precedence
+const LOCALE_ENV_VARS: &[&str] = &["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Supported message languages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    /// English
+    #[default]
+    En,
+    /// Japanese
+    Ja,
+    /// Chinese (Simplified)
+    Zh,
+    /// German
+    De,
+}
+
+impl Lang {
+    /// All supported languages
+    pub const ALL: [Lang; 4] = [Lang::En, Lang::Ja, Lang::Zh, Lang::De];
+
+    /// Detect the language from the process environment
+    pub fn detect() -> Self {
+        Self::from_env_lookup(|key| env::var(key).ok())
+    }
+
+    /// Detect the language using a custom environment lookup (for testing)
+    ///
+    /// An unsupported `CARGO_OPTIMIZE_LANG` is ignored. For the locale
+    /// variables the first one set decides, as in POSIX, and unsupported
+    /// locales fall back to English.
+    pub fn from_env_lookup<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(lang) = lookup(LANG_ENV_VAR).and_then(|value| Lang::from_locale(&value)) {
+            return lang;
+        }
+        LOCALE_ENV_VARS
+            .iter()
+            .filter_map(|key| lookup(key))
+            .find(|value| !value.is_empty())
+            .and_then(|value| Lang::from_locale(&value))
+            .unwrap_or_default()
+    }
+
+    /// Parse a language code or locale name (`ja`, `zh-CN`, `de_DE.UTF-8`)
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Some(Lang::En),
+            "ja" => Some(Lang::Ja),
+            "zh" => Some(Lang::Zh),
+            "de" => Some(Lang::De),
+            _ => None,
+        }
+    }
+
+    /// ISO 639-1 code
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Ja => "ja",
+            Lang::Zh => "zh",
+            Lang::De => "de",
+        }
+    }
+
+    /// Message template in this language
+    pub fn text(self, message: Message) -> &'static str {
+        match self {
+            Lang::En => en(message),
+            Lang::Ja => ja(message),
+            Lang::Zh => zh(message),
+            Lang::De => de(message),
+        }
+    }
+
+    /// Message in this language with placeholders filled in
+    pub fn format(self, message: Message, args: &[&dyn fmt::Display]) -> String {
+        let mut text = self.text(message).to_string();
+        for (index, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", index), &arg.to_string());
+        }
+        text
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Message in the detected language with placeholders filled in
+pub fn tr(message: Message, args: &[&dyn fmt::Display]) -> String {
+    Lang::detect().format(message, args)
+}
+
+/// Identifier of a user-facing message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// `{0}`: linker
+    ConfigCreated,
+    /// `{0}`: linker
+    ConfigUpdated,
+    /// Config already uses a fast linker
+    AlreadyOptimized,
+    /// `{0}`: linker
+    DryRunConfigure,
+    /// `{0}`: lock file path
+    BuildInProgress,
+    /// `{0}`: error
+    ConfigureFailed,
+    /// No fast linker installed
+    NoFastLinker,
+    /// `{0}`: error
+    DetectionFailed,
+    /// `{0}`: kill switch environment variable
+    Disabled,
+    /// Dry run: config already has a fast linker
+    DryRunAlreadyOptimized,
+    /// Dry run: existing linker config would be updated
+    DryRunUpdate,
+    /// Dry run: linker config would be appended
+    DryRunAppend,
+    /// `{0}`: linker
+    DryRunCreate,
+    /// `{0}`: backup path
+    MalformedConfigBackedUp,
+    /// Error: existing config is malformed
+    MalformedConfig,
+    /// Error: linker name not recognized
+    UnknownLinker,
+    /// `{0}`: backup path
+    BackedUp,
+    /// `{0}`: feature, `{1}`: component
+    ComponentNeeded,
+    /// `{0}`: package, `{1}`: commits, `{2}`: days
+    StableMember,
+    /// Report heading for decisions
+    ReportDecisions,
+    /// Report marker for applied settings
+    ReportApplied,
+    /// Report marker for skipped settings
+    ReportSkipped,
+    /// Report marker for settings denied by the team policy
+    ReportSuppressed,
+    /// `{0}`: skipped decisions left out of the report
+    ReportMoreSkipped,
+    /// Heading of the per-phase apply outcomes
+    ReportPhases,
+    /// Marker of a phase rolled back after failing verification
+    ReportRolledBack,
+    /// Marker of a phase left out after an earlier one was rolled back
+    ReportNotAttempted,
+    /// Report heading for cleanup candidates
+    ReportCleanupCandidates,
+    /// Report heading for disk tradeoffs
+    ReportDiskTradeoffs,
+    /// `{0}`: layout, `{1}`: extra MB, `{2}`: current MB
+    ReportDiskTradeoff,
+    /// Report heading for recommendations
+    ReportRecommendations,
+    /// `{0}`: effective target directory, `{1}`: where it comes from
+    ReportTargetDir,
+    /// Target directory source: the `CARGO_TARGET_DIR` environment variable
+    TargetDirFromEnv,
+    /// Target directory source: `build.target-dir` in the config
+    TargetDirFromConfig,
+    /// Target directory source: cargo's default
+    TargetDirDefault,
+    /// Report heading for conflicts with the machine policy
+    ReportPolicyConflicts,
+    /// Report heading for interactions with other build tools
+    ReportToolConflicts,
+    /// Report heading for required optimizations that are missing
+    ReportUnmetRequirements,
+    /// `{0}`: speedup factor, `{1}`: test binaries, `{2}`: test threads
+    NextestSpeedup,
+    /// `{0}`: crate, `{1}`: followed branch, `{2}`: locked commit, `{3}`: dependents
+    PinGitDependency,
+    /// `{0}`: pinned git crates
+    VendorGitDependencies,
+    /// `{0}`: crate, `{1}`: dependents
+    LocalPatch,
+    /// Report heading for Dockerfiles running cargo
+    ReportContainerBuilds,
+    /// `{0}`: Dockerfile
+    ReportContainerSnippet,
+    /// `{0}`: Dockerfile and line of the COPY
+    DockerCopyBeforeDependencies,
+    /// `{0}`: Dockerfile and line of the RUN
+    DockerNoRegistryCache,
+    /// `{0}`: Dockerfile and line of the RUN, `{1}`: target directory in the image
+    DockerNoTargetCache,
+    /// `{0}`: dependencies with differing features, `{1}`: members, `{2}`: extra build cost in percent
+    WorkspaceHack,
+    /// `{0}`: scheduler URL
+    SccacheDistNotInstalled,
+    /// `{0}`: scheduler URL
+    SccacheDistSpeedup,
+    /// `{0}`: scheduler URL, `{1}`: probe error
+    SccacheDistUnreachable,
+    /// `{0}`: environment variable skipping the farm
+    SccacheDistFallback,
+    /// `{0}`: scheduler URL, `{1}`: sccache client config path
+    SccacheDistClientConfig,
+    /// `{0}`: package, `{1}`: examples, `{2}`: share of the build in percent
+    ExamplesRequiredFeatures,
+    /// `{0}`: package, `{1}`: benches, `{2}`: share of the build in percent
+    BenchesCiJob,
+    /// `{0}`: package, `{1}`: targets, `{2}`: their directory, `{3}`: share of the build in percent
+    TargetsSeparateCrate,
+    /// `{0}`: linker, `{1}`: failing output line
+    LinkerRolledBack,
+    /// `{0}`: NUMA nodes, `{1}`: logical CPUs, `{2}`: jobs, `{3}`: node
+    NumaJobs,
+    /// `{0}`: efficiency CPUs, `{1}`: logical CPUs, `{2}`: performance CPUs
+    HybridLinkAffinity,
+    /// `{0}`: suggestions left out because the user rejected them
+    ReportRejected,
+    /// `{0}`: member, `{1}`: dependency, `{2}`: features, `{3}`: members enabling them, `{4}`: share of the dependency build in percent
+    FeatureReplumbing,
+}
+
+fn en(message: Message) -> &'static str {
+    match message {
+        Message::ConfigCreated => "✅ Created .cargo/config.toml with {0} linker",
+        Message::ConfigUpdated => "✅ Updated .cargo/config.toml to use {0} linker",
+        Message::AlreadyOptimized => "ℹ️  Config already optimized with fast linker",
+        Message::DryRunConfigure => "🔍 Would configure {0} linker (dry run)",
+        Message::BuildInProgress => {
+            "⏳ cargo build in progress (lock held on {0}); not changing .cargo/config.toml, re-run once it finishes"
+        }
+        Message::ConfigureFailed => "❌ Failed to configure linker: {0}",
+        Message::NoFastLinker => "ℹ️  No fast linker found - using default",
+        Message::DetectionFailed => "❌ Error detecting linker: {0}",
+        Message::Disabled => "⏸️  Disabled by {0}; nothing was changed",
+        Message::DryRunAlreadyOptimized => "Config already has fast linker (dry run)",
+        Message::DryRunUpdate => "Would update existing linker config (dry run)",
+        Message::DryRunAppend => "Would append linker config to existing .cargo/config.toml (dry run)",
+        Message::DryRunCreate => "Would create .cargo/config.toml with {0} linker (dry run)",
+        Message::MalformedConfigBackedUp => "⚠️  Existing config appears to be malformed. Backed up to {0}",
+        Message::MalformedConfig => {
+            "Existing config.toml appears to be malformed. Please fix it manually or use --force to overwrite."
+        }
+        Message::UnknownLinker => "Unknown linker",
+        Message::BackedUp => "📋 Backed up existing config to {0}",
+        Message::ComponentNeeded => "{0} needs the {1} component",
+        Message::StableMember => {
+            "{0} changed in {1} commit(s) over the last {2} days; build it with \
+             incremental = false and opt-level = 1 in the dev profile"
+        }
+        Message::ReportDecisions => "Decisions:",
+        Message::ReportApplied => "applied",
+        Message::ReportSkipped => "skipped",
+        Message::ReportSuppressed => "suppressed by policy",
+        Message::ReportMoreSkipped => "{0} more skipped",
+        Message::ReportPhases => "Apply phases:",
+        Message::ReportRolledBack => "rolled back",
+        Message::ReportNotAttempted => "not attempted",
+        Message::ReportCleanupCandidates => "Cleanup candidates:",
+        Message::ReportDiskTradeoffs => "Disk tradeoffs:",
+        Message::ReportDiskTradeoff => "{0}: about {1} MB more (target dir currently {2} MB)",
+        Message::ReportRecommendations => "Recommendations:",
+        Message::ReportTargetDir => "Target directory: {0} ({1})",
+        Message::TargetDirFromEnv => "set by CARGO_TARGET_DIR",
+        Message::TargetDirFromConfig => "set by build.target-dir",
+        Message::TargetDirDefault => "cargo default",
+        Message::ReportPolicyConflicts => "Machine policy conflicts:",
+        Message::ReportToolConflicts => "Other build tools:",
+        Message::ReportUnmetRequirements => "Required optimizations missing:",
+        Message::NextestSpeedup => {
+            "cargo-nextest runs the {1} test binaries on {2} threads; expect the test phase to \
+             run about {0}x faster than with cargo test"
+        }
+        Message::PinGitDependency => {
+            "{0} follows {1} of its git repository, so every cargo update can rebuild it and {3} \
+             crate(s) depending on it; pin rev = \"{2}\" to keep it cached"
+        }
+        Message::VendorGitDependencies => {
+            "{0} come from git and bypass the crates.io cache; vendor them to build from a stable local source"
+        }
+        Message::LocalPatch => {
+            "{0} is patched with a local checkout; every edit to it rebuilds {1} crate(s) depending on it, \
+             so drop the [patch] entry once the fix is released"
+        }
+        Message::ReportContainerBuilds => "Container builds:",
+        Message::ReportContainerSnippet => "Recommended build stage for {0}:",
+        Message::DockerCopyBeforeDependencies => {
+            "{0}: the whole project is copied before dependencies are built, so every source change \
+             rebuilds all of them; copy Cargo.toml and Cargo.lock and fetch dependencies first"
+        }
+        Message::DockerNoRegistryCache => {
+            "{0}: cargo downloads crates without a cache mount for the registry; add \
+             --mount=type=cache,target=/usr/local/cargo/registry"
+        }
+        Message::DockerNoTargetCache => {
+            "{0}: cargo compiles dependencies without a cache mount for the target directory; add \
+             --mount=type=cache,target={1}"
+        }
+        Message::WorkspaceHack => {
+            "{0} dependencies are compiled with other features depending on which of the {1} members \
+             is built, adding about {2}% to the dependency build when switching between them; a \
+             workspace-hack crate makes every build use the same features"
+        }
+        Message::SccacheDistNotInstalled => {
+            "An sccache-dist scheduler is configured at {0}, but sccache is not installed"
+        }
+        Message::SccacheDistSpeedup => {
+            "Compilations are distributed to the build farm at {0}: clean builds speed up roughly with \
+             the number of build servers, while linking, build scripts and proc macros still run locally"
+        }
+        Message::SccacheDistUnreachable => {
+            "The sccache-dist scheduler at {0} does not answer ({1}); builds stay local until it does"
+        }
+        Message::SccacheDistFallback => {
+            "When the farm is unreachable sccache compiles locally after a short timeout; set {0}=1 to \
+             skip it right away"
+        }
+        Message::SccacheDistClientConfig => {
+            "No sccache client config names the scheduler {0}; add this to {1} and fill in the client token:"
+        }
+        Message::ExamplesRequiredFeatures => {
+            "{0} has {1} examples that every `cargo test` and `--all-targets` build compiles, about {2}% of \
+             the workspace's own build; declare `required-features` on them so they are only built on request"
+        }
+        Message::BenchesCiJob => {
+            "{0} has {1} benches that every `--all-targets` build compiles, about {2}% of the workspace's own \
+             build; build them in a CI job of their own and use `--lib --bins --tests` in the main one"
+        }
+        Message::TargetsSeparateCrate => {
+            "{0} has {1} targets in {2}/, about {3}% of the workspace's own build; move them into a separate \
+             crate left out of `workspace.default-members`"
+        }
+        Message::LinkerRolledBack => {
+            "↩️  The build failed in {0} ({1}); the linker settings cargo-optimize wrote were rolled back \
+             and {0} will not be configured again for this project"
+        }
+        Message::NumaJobs => {
+            "This machine has {0} NUMA nodes; jobs spread over all {1} CPUs fetch memory across nodes, so \
+             build with {2} jobs bound to node {3}"
+        }
+        Message::HybridLinkAffinity => {
+            "{0} of the {1} CPUs are efficiency cores; run release builds, whose final link is the heaviest \
+             single-threaded step, on the {2} performance CPUs"
+        }
+        Message::ReportRejected => "{0} suggestion(s) you rejected earlier are not shown again",
+        Message::FeatureReplumbing => {
+            "Building {0} on its own compiles {1} without {2}, which {3} enable, so switching between them \
+             rebuilds {1} (about {4}% of the dependency build); enable {2} of {1} in {0}'s Cargo.toml"
+        }
+    }
+}
+
+fn ja(message: Message) -> &'static str {
+    match message {
+        Message::ConfigCreated => "✅ {0} リンカーを使用する .cargo/config.toml を作成しました",
+        Message::ConfigUpdated => "✅ {0} リンカーを使用するよう .cargo/config.toml を更新しました",
+        Message::AlreadyOptimized => "ℹ️  設定はすでに高速リンカーで最適化されています",
+        Message::DryRunConfigure => "🔍 {0} リンカーを設定します（ドライラン）",
+        Message::BuildInProgress => {
+            "⏳ cargo build を実行中です（{0} がロックされています）。.cargo/config.toml は変更しません。ビルド完了後に再実行してください"
+        }
+        Message::ConfigureFailed => "❌ リンカーの設定に失敗しました: {0}",
+        Message::NoFastLinker => "ℹ️  高速リンカーが見つかりません。デフォルトを使用します",
+        Message::DetectionFailed => "❌ リンカーの検出中にエラーが発生しました: {0}",
+        Message::Disabled => "⏸️  {0} により無効化されています。何も変更していません",
+        Message::DryRunAlreadyOptimized => "設定にはすでに高速リンカーがあります（ドライラン）",
+        Message::DryRunUpdate => "既存のリンカー設定を更新します（ドライラン）",
+        Message::DryRunAppend => "既存の .cargo/config.toml にリンカー設定を追記します（ドライラン）",
+        Message::DryRunCreate => "{0} リンカーを使用する .cargo/config.toml を作成します（ドライラン）",
+        Message::MalformedConfigBackedUp => "⚠️  既存の設定が不正な形式のようです。{0} にバックアップしました",
+        Message::MalformedConfig => {
+            "既存の config.toml が不正な形式のようです。手動で修正するか、--force で上書きしてください。"
+        }
+        Message::UnknownLinker => "不明なリンカーです",
+        Message::BackedUp => "📋 既存の設定を {0} にバックアップしました",
+        Message::ComponentNeeded => "{0} には {1} コンポーネントが必要です",
+        Message::StableMember => {
+            "{0} の過去 {2} 日間の変更は {1} コミットです。dev プロファイルでは \
+             incremental = false と opt-level = 1 でビルドしてください"
+        }
+        Message::ReportDecisions => "決定事項:",
+        Message::ReportApplied => "適用",
+        Message::ReportSkipped => "スキップ",
+        Message::ReportSuppressed => "ポリシーにより抑止",
+        Message::ReportMoreSkipped => "ほかに {0} 件をスキップ",
+        Message::ReportPhases => "適用フェーズ:",
+        Message::ReportRolledBack => "ロールバック",
+        Message::ReportNotAttempted => "未実行",
+        Message::ReportCleanupCandidates => "削除候補:",
+        Message::ReportDiskTradeoffs => "ディスク使用量のトレードオフ:",
+        Message::ReportDiskTradeoff => "{0}: 約 {1} MB 増加（現在の target ディレクトリ {2} MB）",
+        Message::ReportRecommendations => "推奨事項:",
+        Message::ReportTargetDir => "target ディレクトリ: {0}（{1}）",
+        Message::TargetDirFromEnv => "CARGO_TARGET_DIR で指定",
+        Message::TargetDirFromConfig => "build.target-dir で指定",
+        Message::TargetDirDefault => "cargo の既定値",
+        Message::ReportPolicyConflicts => "マシンポリシーとの競合:",
+        Message::ReportToolConflicts => "他のビルドツール:",
+        Message::ReportUnmetRequirements => "必須の最適化が欠けています:",
+        Message::NextestSpeedup => {
+            "cargo-nextest は {1} 個のテストバイナリを {2} スレッドで実行します。テストフェーズは \
+             cargo test より約 {0} 倍速くなる見込みです"
+        }
+        Message::PinGitDependency => {
+            "{0} は git リポジトリの {1} を追跡しているため、cargo update のたびに {0} と依存する \
+             {3} 個のクレートが再ビルドされる可能性があります。rev = \"{2}\" に固定してキャッシュを保ってください"
+        }
+        Message::VendorGitDependencies => {
+            "{0} は git から取得され crates.io のキャッシュを経由しません。vendor して安定したローカルソースからビルドしてください"
+        }
+        Message::LocalPatch => {
+            "{0} はローカルのチェックアウトでパッチされています。編集のたびに依存する {1} 個のクレートが \
+             再ビルドされるため、修正がリリースされたら [patch] エントリを削除してください"
+        }
+        Message::ReportContainerBuilds => "コンテナビルド:",
+        Message::ReportContainerSnippet => "{0} の推奨ビルドステージ:",
+        Message::DockerCopyBeforeDependencies => {
+            "{0}: 依存関係のビルド前にプロジェクト全体をコピーしているため、ソースを変更するたびに \
+             すべての依存関係が再ビルドされます。先に Cargo.toml と Cargo.lock をコピーして依存関係を取得してください"
+        }
+        Message::DockerNoRegistryCache => {
+            "{0}: cargo がレジストリのキャッシュマウントなしでクレートをダウンロードしています。\
+             --mount=type=cache,target=/usr/local/cargo/registry を追加してください"
+        }
+        Message::DockerNoTargetCache => {
+            "{0}: cargo がターゲットディレクトリのキャッシュマウントなしで依存関係をコンパイルしています。\
+             --mount=type=cache,target={1} を追加してください"
+        }
+        Message::WorkspaceHack => {
+            "{1} 個のメンバーのどれをビルドするかによって {0} 個の依存関係が異なるフィーチャーで \
+             コンパイルされ、切り替えるたびに依存関係のビルドが約 {2}% 増えます。workspace-hack \
+             クレートを使うとすべてのビルドで同じフィーチャーになります"
+        }
+        Message::SccacheDistNotInstalled => {
+            "{0} に sccache-dist スケジューラーが設定されていますが、sccache がインストールされていません"
+        }
+        Message::SccacheDistSpeedup => {
+            "コンパイルは {0} のビルドファームに分散されます。クリーンビルドはビルドサーバーの数に応じて \
+             速くなりますが、リンク、ビルドスクリプト、proc macro はローカルで実行されます"
+        }
+        Message::SccacheDistUnreachable => {
+            "{0} の sccache-dist スケジューラーが応答しません（{1}）。応答するまでビルドはローカルで行われます"
+        }
+        Message::SccacheDistFallback => {
+            "ファームに到達できない場合、sccache は短いタイムアウトの後にローカルでコンパイルします。\
+             すぐにスキップするには {0}=1 を設定してください"
+        }
+        Message::SccacheDistClientConfig => {
+            "スケジューラー {0} を指定した sccache クライアント設定がありません。{1} に次の内容を追加し、\
+             クライアントトークンを記入してください:"
+        }
+        Message::ExamplesRequiredFeatures => {
+            "{0} には `cargo test` と `--all-targets` のビルドのたびにコンパイルされる example が {1} 個あり、\
+             ワークスペース自体のビルドの約 {2}% を占めます。`required-features` を宣言して必要なときだけ\
+             ビルドされるようにしてください"
+        }
+        Message::BenchesCiJob => {
+            "{0} には `--all-targets` のビルドのたびにコンパイルされるベンチマークが {1} 個あり、ワークスペース\
+             自体のビルドの約 {2}% を占めます。専用の CI ジョブでビルドし、メインのジョブでは \
+             `--lib --bins --tests` を使ってください"
+        }
+        Message::TargetsSeparateCrate => {
+            "{0} の {2}/ には {1} 個のターゲットがあり、ワークスペース自体のビルドの約 {3}% を占めます。\
+             `workspace.default-members` に含まれない別のクレートに移してください"
+        }
+        Message::LinkerRolledBack => {
+            "↩️  {0} でビルドが失敗しました ({1})。cargo-optimize が書き込んだリンカー設定を元に戻しました。\
+             このプロジェクトでは今後 {0} を設定しません"
+        }
+        Message::NumaJobs => {
+            "このマシンには NUMA ノードが {0} 個あります。{1} 個すべての CPU にジョブを分散するとノード間で\
+             メモリを取得するため、ノード {3} にバインドして {2} ジョブでビルドしてください"
+        }
+        Message::HybridLinkAffinity => {
+            "{1} 個の CPU のうち {0} 個は高効率コアです。最後のリンクが最も重いシングルスレッド処理となる\
+             リリースビルドは、{2} 個の高性能 CPU で実行してください"
+        }
+        Message::ReportRejected => "以前に却下された {0} 件の提案は表示されません",
+        Message::FeatureReplumbing => {
+            "{0} を単独でビルドすると {1} が {2} なしでコンパイルされますが、{3} はそれを有効にするため、\
+             切り替えのたびに {1} が再ビルドされます（依存関係のビルドの約 {4}%）。{0} の Cargo.toml で \
+             {1} の {2} を有効にしてください"
+        }
+    }
+}
+
+fn zh(message: Message) -> &'static str {
+    match message {
+        Message::ConfigCreated => "✅ 已创建使用 {0} 链接器的 .cargo/config.toml",
+        Message::ConfigUpdated => "✅ 已更新 .cargo/config.toml 以使用 {0} 链接器",
+        Message::AlreadyOptimized => "ℹ️  配置已使用快速链接器优化",
+        Message::DryRunConfigure => "🔍 将配置 {0} 链接器（试运行）",
+        Message::BuildInProgress => {
+            "⏳ cargo build 正在进行（{0} 已被锁定）；不修改 .cargo/config.toml，请在构建完成后重新运行"
+        }
+        Message::ConfigureFailed => "❌ 配置链接器失败：{0}",
+        Message::NoFastLinker => "ℹ️  未找到快速链接器，使用默认链接器",
+        Message::DetectionFailed => "❌ 检测链接器时出错：{0}",
+        Message::Disabled => "⏸️  已被 {0} 禁用，未做任何更改",
+        Message::DryRunAlreadyOptimized => "配置已包含快速链接器（试运行）",
+        Message::DryRunUpdate => "将更新现有链接器配置（试运行）",
+        Message::DryRunAppend => "将向现有 .cargo/config.toml 追加链接器配置（试运行）",
+        Message::DryRunCreate => "将创建使用 {0} 链接器的 .cargo/config.toml（试运行）",
+        Message::MalformedConfigBackedUp => "⚠️  现有配置格式似乎有误，已备份到 {0}",
+        Message::MalformedConfig => "现有 config.toml 格式似乎有误。请手动修复，或使用 --force 覆盖。",
+        Message::UnknownLinker => "未知的链接器",
+        Message::BackedUp => "📋 已将现有配置备份到 {0}",
+        Message::ComponentNeeded => "{0} 需要 {1} 组件",
+        Message::StableMember => {
+            "{0} 在过去 {2} 天内仅有 {1} 次提交变更；请在 dev 配置中以 \
+             incremental = false 和 opt-level = 1 构建它"
+        }
+        Message::ReportDecisions => "决策：",
+        Message::ReportApplied => "已应用",
+        Message::ReportSkipped => "已跳过",
+        Message::ReportSuppressed => "已被策略禁止",
+        Message::ReportMoreSkipped => "另有 {0} 项已跳过",
+        Message::ReportPhases => "应用阶段：",
+        Message::ReportRolledBack => "已回滚",
+        Message::ReportNotAttempted => "未执行",
+        Message::ReportCleanupCandidates => "可清理项：",
+        Message::ReportDiskTradeoffs => "磁盘占用权衡：",
+        Message::ReportDiskTradeoff => "{0}：约增加 {1} MB（当前 target 目录 {2} MB）",
+        Message::ReportRecommendations => "建议：",
+        Message::ReportTargetDir => "target 目录：{0}（{1}）",
+        Message::TargetDirFromEnv => "由 CARGO_TARGET_DIR 指定",
+        Message::TargetDirFromConfig => "由 build.target-dir 指定",
+        Message::TargetDirDefault => "cargo 默认值",
+        Message::ReportPolicyConflicts => "与机器策略的冲突：",
+        Message::ReportToolConflicts => "其他构建工具：",
+        Message::ReportUnmetRequirements => "缺少必需的优化：",
+        Message::NextestSpeedup => {
+            "cargo-nextest 以 {2} 个线程运行 {1} 个测试二进制文件；测试阶段预计比 \
+             cargo test 快约 {0} 倍"
+        }
+        Message::PinGitDependency => {
+            "{0} 跟踪其 git 仓库的 {1}，每次 cargo update 都可能重新构建它及依赖它的 {3} 个 crate；\
+             请固定 rev = \"{2}\" 以保持缓存"
+        }
+        Message::VendorGitDependencies => {
+            "{0} 来自 git，绕过了 crates.io 缓存；请 vendor 它们，从稳定的本地源构建"
+        }
+        Message::LocalPatch => {
+            "{0} 被本地检出通过 patch 替换；每次编辑都会重新构建依赖它的 {1} 个 crate，\
+             修复发布后请删除该 [patch] 条目"
+        }
+        Message::ReportContainerBuilds => "容器构建：",
+        Message::ReportContainerSnippet => "{0} 的推荐构建阶段：",
+        Message::DockerCopyBeforeDependencies => {
+            "{0}：在构建依赖之前复制了整个项目，每次源码修改都会重新构建所有依赖；\
+             请先复制 Cargo.toml 和 Cargo.lock 并获取依赖"
+        }
+        Message::DockerNoRegistryCache => {
+            "{0}：cargo 下载 crate 时没有注册表的缓存挂载；\
+             请添加 --mount=type=cache,target=/usr/local/cargo/registry"
+        }
+        Message::DockerNoTargetCache => {
+            "{0}：cargo 编译依赖时没有目标目录的缓存挂载；请添加 --mount=type=cache,target={1}"
+        }
+        Message::WorkspaceHack => {
+            "根据构建 {1} 个成员中的哪一个，{0} 个依赖会以不同的 feature 编译，在成员之间切换时依赖构建 \
+             增加约 {2}%；workspace-hack crate 可让所有构建使用相同的 feature"
+        }
+        Message::SccacheDistNotInstalled => {
+            "已在 {0} 配置 sccache-dist 调度器，但未安装 sccache"
+        }
+        Message::SccacheDistSpeedup => {
+            "编译将分发到 {0} 的构建集群：全新构建的速度大致随构建服务器数量提升，链接、构建脚本和 \
+             过程宏仍在本地运行"
+        }
+        Message::SccacheDistUnreachable => {
+            "{0} 的 sccache-dist 调度器无响应（{1}）；在其恢复之前构建将在本地进行"
+        }
+        Message::SccacheDistFallback => {
+            "集群不可达时，sccache 会在短暂超时后在本地编译；设置 {0}=1 可立即跳过集群"
+        }
+        Message::SccacheDistClientConfig => {
+            "没有 sccache 客户端配置指向调度器 {0}；请将以下内容添加到 {1} 并填写客户端令牌："
+        }
+        Message::ExamplesRequiredFeatures => {
+            "{0} 有 {1} 个 example，每次 `cargo test` 和 `--all-targets` 构建都会编译，约占工作区自身构建的 \
+             {2}%；为它们声明 `required-features`，使其仅在需要时构建"
+        }
+        Message::BenchesCiJob => {
+            "{0} 有 {1} 个基准测试，每次 `--all-targets` 构建都会编译，约占工作区自身构建的 {2}%；请在单独的 \
+             CI 作业中构建它们，并在主作业中使用 `--lib --bins --tests`"
+        }
+        Message::TargetsSeparateCrate => {
+            "{0} 的 {2}/ 中有 {1} 个目标，约占工作区自身构建的 {3}%；请将它们移到不在 \
+             `workspace.default-members` 中的独立 crate"
+        }
+        Message::LinkerRolledBack => {
+            "↩️  构建在 {0} 中失败（{1}）；已回滚 cargo-optimize 写入的链接器设置，此项目今后不会再配置 {0}"
+        }
+        Message::NumaJobs => {
+            "此机器有 {0} 个 NUMA 节点；将作业分散到全部 {1} 个 CPU 会跨节点访问内存，请绑定到节点 {3} 并使用 \
+             {2} 个作业构建"
+        }
+        Message::HybridLinkAffinity => {
+            "{1} 个 CPU 中有 {0} 个是能效核心；发布构建的最终链接是最重的单线程步骤，请在 {2} 个性能 CPU 上运行"
+        }
+        Message::ReportRejected => "此前已拒绝的 {0} 条建议不再显示",
+        Message::FeatureReplumbing => {
+            "单独构建 {0} 时 {1} 不带 {2} 编译，而 {3} 会启用它们，因此切换时会重新构建 {1}（约占依赖构建的 \
+             {4}%）；请在 {0} 的 Cargo.toml 中为 {1} 启用 {2}"
+        }
+    }
+}
+
+fn de(message: Message) -> &'static str {
+    match message {
+        Message::ConfigCreated => "✅ .cargo/config.toml mit {0}-Linker erstellt",
+        Message::ConfigUpdated => "✅ .cargo/config.toml für den {0}-Linker aktualisiert",
+        Message::AlreadyOptimized => "ℹ️  Konfiguration verwendet bereits einen schnellen Linker",
+        Message::DryRunConfigure => "🔍 Würde den {0}-Linker konfigurieren (Probelauf)",
+        Message::BuildInProgress => {
+            "⏳ cargo build läuft (Sperre auf {0}); .cargo/config.toml bleibt unverändert, bitte nach Abschluss erneut ausführen"
+        }
+        Message::ConfigureFailed => "❌ Linker konnte nicht konfiguriert werden: {0}",
+        Message::NoFastLinker => "ℹ️  Kein schneller Linker gefunden – Standard wird verwendet",
+        Message::DetectionFailed => "❌ Fehler bei der Linker-Erkennung: {0}",
+        Message::Disabled => "⏸️  Durch {0} deaktiviert; nichts wurde geändert",
+        Message::DryRunAlreadyOptimized => "Konfiguration enthält bereits einen schnellen Linker (Probelauf)",
+        Message::DryRunUpdate => "Würde die vorhandene Linker-Konfiguration aktualisieren (Probelauf)",
+        Message::DryRunAppend => {
+            "Würde die Linker-Konfiguration an die vorhandene .cargo/config.toml anhängen (Probelauf)"
+        }
+        Message::DryRunCreate => "Würde .cargo/config.toml mit {0}-Linker erstellen (Probelauf)",
+        Message::MalformedConfigBackedUp => {
+            "⚠️  Die vorhandene Konfiguration scheint fehlerhaft zu sein. Sicherung unter {0}"
+        }
+        Message::MalformedConfig => {
+            "Die vorhandene config.toml scheint fehlerhaft zu sein. Bitte manuell korrigieren oder mit --force überschreiben."
+        }
+        Message::UnknownLinker => "Unbekannter Linker",
+        Message::BackedUp => "📋 Vorhandene Konfiguration gesichert unter {0}",
+        Message::ComponentNeeded => "{0} benötigt die Komponente {1}",
+        Message::StableMember => {
+            "{0} wurde in den letzten {2} Tagen in {1} Commit(s) geändert; im dev-Profil \
+             mit incremental = false und opt-level = 1 bauen"
+        }
+        Message::ReportDecisions => "Entscheidungen:",
+        Message::ReportApplied => "angewendet",
+        Message::ReportSkipped => "übersprungen",
+        Message::ReportSuppressed => "durch Richtlinie unterdrückt",
+        Message::ReportMoreSkipped => "{0} weitere übersprungen",
+        Message::ReportPhases => "Anwendungsphasen:",
+        Message::ReportRolledBack => "zurückgenommen",
+        Message::ReportNotAttempted => "nicht versucht",
+        Message::ReportCleanupCandidates => "Aufräumkandidaten:",
+        Message::ReportDiskTradeoffs => "Speicherplatz-Abwägungen:",
+        Message::ReportDiskTradeoff => "{0}: etwa {1} MB mehr (target-Verzeichnis derzeit {2} MB)",
+        Message::ReportRecommendations => "Empfehlungen:",
+        Message::ReportTargetDir => "target-Verzeichnis: {0} ({1})",
+        Message::TargetDirFromEnv => "durch CARGO_TARGET_DIR festgelegt",
+        Message::TargetDirFromConfig => "durch build.target-dir festgelegt",
+        Message::TargetDirDefault => "Standard von cargo",
+        Message::ReportPolicyConflicts => "Konflikte mit der Maschinenrichtlinie:",
+        Message::ReportToolConflicts => "Andere Build-Werkzeuge:",
+        Message::ReportUnmetRequirements => "Fehlende erforderliche Optimierungen:",
+        Message::NextestSpeedup => {
+            "cargo-nextest führt die {1} Test-Binaries mit {2} Threads aus; die Testphase \
+             läuft voraussichtlich etwa {0}-mal so schnell wie mit cargo test"
+        }
+        Message::PinGitDependency => {
+            "{0} folgt {1} seines Git-Repositorys, daher kann jedes cargo update es und {3} davon \
+             abhängige Crate(s) neu bauen; rev = \"{2}\" festlegen, damit es im Cache bleibt"
+        }
+        Message::VendorGitDependencies => {
+            "{0} stammen aus Git und umgehen den crates.io-Cache; per vendor aus einer stabilen lokalen Quelle bauen"
+        }
+        Message::LocalPatch => {
+            "{0} wird per [patch] durch einen lokalen Checkout ersetzt; jede Änderung daran baut {1} davon \
+             abhängige Crate(s) neu, daher den [patch]-Eintrag entfernen, sobald der Fix veröffentlicht ist"
+        }
+        Message::ReportContainerBuilds => "Container-Builds:",
+        Message::ReportContainerSnippet => "Empfohlene Build-Stage für {0}:",
+        Message::DockerCopyBeforeDependencies => {
+            "{0}: das ganze Projekt wird vor den Abhängigkeiten kopiert, daher baut jede Quelländerung \
+             alle neu; zuerst Cargo.toml und Cargo.lock kopieren und die Abhängigkeiten holen"
+        }
+        Message::DockerNoRegistryCache => {
+            "{0}: cargo lädt Crates ohne Cache-Mount für die Registry herunter; \
+             --mount=type=cache,target=/usr/local/cargo/registry ergänzen"
+        }
+        Message::DockerNoTargetCache => {
+            "{0}: cargo kompiliert Abhängigkeiten ohne Cache-Mount für das Zielverzeichnis; \
+             --mount=type=cache,target={1} ergänzen"
+        }
+        Message::WorkspaceHack => {
+            "{0} Abhängigkeiten werden je nachdem, welches der {1} Mitglieder gebaut wird, mit anderen \
+             Features kompiliert, was beim Wechseln etwa {2}% zum Bau der Abhängigkeiten hinzufügt; ein \
+             workspace-hack-Crate sorgt dafür, dass jeder Build dieselben Features nutzt"
+        }
+        Message::SccacheDistNotInstalled => {
+            "Unter {0} ist ein sccache-dist-Scheduler konfiguriert, aber sccache ist nicht installiert"
+        }
+        Message::SccacheDistSpeedup => {
+            "Kompilierungen werden an die Build-Farm unter {0} verteilt: saubere Builds werden etwa mit \
+             der Zahl der Build-Server schneller, Linken, Build-Skripte und Proc-Macros laufen weiter lokal"
+        }
+        Message::SccacheDistUnreachable => {
+            "Der sccache-dist-Scheduler unter {0} antwortet nicht ({1}); bis dahin wird lokal gebaut"
+        }
+        Message::SccacheDistFallback => {
+            "Ist die Farm nicht erreichbar, kompiliert sccache nach einem kurzen Timeout lokal; mit {0}=1 \
+             wird sie sofort übersprungen"
+        }
+        Message::SccacheDistClientConfig => {
+            "Keine sccache-Client-Konfiguration nennt den Scheduler {0}; Folgendes in {1} ergänzen und das \
+             Client-Token eintragen:"
+        }
+        Message::ExamplesRequiredFeatures => {
+            "{0} hat {1} Beispiele, die jeder `cargo test`- und `--all-targets`-Build kompiliert, etwa {2}% des \
+             Builds des Workspaces selbst; `required-features` für sie deklarieren, damit sie nur auf Anfrage \
+             gebaut werden"
+        }
+        Message::BenchesCiJob => {
+            "{0} hat {1} Benchmarks, die jeder `--all-targets`-Build kompiliert, etwa {2}% des Builds des \
+             Workspaces selbst; sie in einem eigenen CI-Job bauen und im Haupt-Job `--lib --bins --tests` verwenden"
+        }
+        Message::TargetsSeparateCrate => {
+            "{0} hat {1} Targets in {2}/, etwa {3}% des Builds des Workspaces selbst; sie in ein eigenes Crate \
+             außerhalb von `workspace.default-members` verschieben"
+        }
+        Message::LinkerRolledBack => {
+            "↩️  Der Build ist in {0} fehlgeschlagen ({1}); die von cargo-optimize geschriebenen \
+             Linker-Einstellungen wurden zurückgenommen und {0} wird für dieses Projekt nicht erneut konfiguriert"
+        }
+        Message::NumaJobs => {
+            "Dieser Rechner hat {0} NUMA-Knoten; Jobs auf allen {1} CPUs holen Speicher über Knotengrenzen, \
+             daher mit {2} Jobs gebunden an Knoten {3} bauen"
+        }
+        Message::HybridLinkAffinity => {
+            "{0} der {1} CPUs sind Effizienzkerne; Release-Builds, deren finaler Link der schwerste \
+             Single-Thread-Schritt ist, auf den {2} Performance-CPUs ausführen"
+        }
+        Message::ReportRejected => "{0} früher abgelehnte Vorschläge werden nicht mehr angezeigt",
+        Message::FeatureReplumbing => {
+            "Allein gebaut kompiliert {0} {1} ohne {2}, was {3} aktivieren, daher wird {1} beim Wechseln neu \
+             gebaut (etwa {4}% des Abhängigkeits-Builds); {2} von {1} in der Cargo.toml von {0} aktivieren"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn test_language_selection() {
+        assert_eq!(Lang::from_env_lookup(lookup(&[])), Lang::En);
+        assert_eq!(Lang::from_env_lookup(lookup(&[("LANG", "ja_JP.UTF-8")])), Lang::Ja);
+        assert_eq!(Lang::from_env_lookup(lookup(&[("LC_ALL", "zh_CN"), ("LANG", "de_DE")])), Lang::Zh);
+        // The explicit setting wins; an unsupported one is ignored
+        assert_eq!(Lang::from_env_lookup(lookup(&[(LANG_ENV_VAR, "de"), ("LANG", "ja_JP")])), Lang::De);
+        assert_eq!(Lang::from_env_lookup(lookup(&[(LANG_ENV_VAR, "fr"), ("LANG", "ja_JP")])), Lang::Ja);
+        // The first locale variable set decides, even if unsupported
+        assert_eq!(Lang::from_env_lookup(lookup(&[("LC_ALL", "fr_FR"), ("LANG", "ja_JP")])), Lang::En);
+        assert_eq!(Lang::from_locale("de_DE@euro"), Some(Lang::De));
+        assert_eq!(Lang::from_locale("C"), Some(Lang::En));
+    }
+
+    #[test]
+    fn test_catalogs_keep_placeholders() {
+        let messages = [
+            (Message::ConfigCreated, 1),
+            (Message::BuildInProgress, 1),
+            (Message::Disabled, 1),
+            (Message::ComponentNeeded, 2),
+            (Message::StableMember, 3),
+            (Message::ReportDiskTradeoff, 3),
+            (Message::ReportTargetDir, 2),
+            (Message::NextestSpeedup, 3),
+            (Message::PinGitDependency, 4),
+            (Message::VendorGitDependencies, 1),
+            (Message::LocalPatch, 2),
+            (Message::ReportContainerSnippet, 1),
+            (Message::DockerCopyBeforeDependencies, 1),
+            (Message::DockerNoRegistryCache, 1),
+            (Message::DockerNoTargetCache, 2),
+            (Message::WorkspaceHack, 3),
+            (Message::SccacheDistNotInstalled, 1),
+            (Message::SccacheDistSpeedup, 1),
+            (Message::SccacheDistUnreachable, 2),
+            (Message::SccacheDistFallback, 1),
+            (Message::SccacheDistClientConfig, 2),
+            (Message::ExamplesRequiredFeatures, 3),
+            (Message::BenchesCiJob, 3),
+            (Message::TargetsSeparateCrate, 4),
+            (Message::LinkerRolledBack, 2),
+            (Message::NumaJobs, 4),
+            (Message::HybridLinkAffinity, 3),
+            (Message::ReportRejected, 1),
+            (Message::ReportMoreSkipped, 1),
+            (Message::FeatureReplumbing, 5),
+        ];
+        for lang in Lang::ALL {
+            for (message, count) in messages {
+                for index in 0..count {
+                    assert!(
+                        lang.text(message).contains(&format!("{{{}}}", index)),
+                        "{} translation of {:?} lacks {{{}}}",
+                        lang,
+                        message,
+                        index
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            Lang::De.format(Message::ComponentNeeded, &[&"PGO", &"llvm-tools"]),
+            "PGO benötigt die Komponente llvm-tools"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Rust IDEs and a project-wide rustc wrapper.
+//!
+//! rust-analyzer runs `cargo check` on every save. With sccache set as
+//! `build.rustc-wrapper`, each of those checks goes through the cache as
+//! well: the members being edited change all the time, so their entries
+//! evict useful ones, and every rustc invocation pays the round-trip to the
+//! sccache server while the developer waits for diagnostics.
+//!
+//! Cargo cannot scope the wrapper to some profiles or commands, and
+//! `build.rustc-workspace-wrapper` runs inside `build.rustc-wrapper` rather
+//! than instead of it. What does work is the IDE's own environment:
+//! `RUSTC_WRAPPER=""` resets cargo to no wrapper. With the default
+//! [`WrapperScope::Builds`], apply writes it to `check.extraEnv` in the
+//! project's `rust-analyzer.toml` when rust-analyzer is in use, and
+//! recommends the same for RustRover, while builds and tests from the
+//! terminal keep the wrapper.
+//!
+//! Configuring from a build script rust-analyzer runs is skipped altogether
+//! (see [`running_in_ide`]): a rewritten `.cargo/config.toml` would make it
+//! reload the workspace.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::explain;
+
+/// rust-analyzer's project config, relative to the workspace root
+pub const RA_CONFIG: &str = "rust-analyzer.toml";
+
+/// Explanation id written next to the wrapper bypass
+pub const IDE_ID: &str = "CO-IDE-001";
+
+/// Set by IDEs without a marker of their own to say cargo runs on their behalf
+pub const IDE_ENV_VAR: &str = "CARGO_OPTIMIZE_IDE";
+
+/// Key of the bypass in [`RA_CONFIG`]
+const BYPASS_KEY: [&str; 3] = ["check", "extraEnv", "RUSTC_WRAPPER"];
+
+/// Set by rust-analyzer while it runs build scripts through its own rustc wrapper
+const RA_WRAPPER_VAR: &str = "RA_RUSTC_WRAPPER";
+
+/// Directories of editors that talk to rust-analyzer
+const RA_EDITOR_DIRS: &[&str] = &[".vscode", ".zed", ".helix"];
+
+/// Which compilations a project-wide rustc wrapper such as sccache covers
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WrapperScope {
+    /// Every compilation, the IDE's checks included
+    All,
+    /// Builds and tests; IDE checks bypass the wrapper
+    #[default]
+    Builds,
+}
+
+/// An IDE a project is set up for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Ide {
+    /// rust-analyzer, in VS Code, Zed, Helix or any other client
+    RustAnalyzer,
+    /// RustRover or IntelliJ with the Rust plugin
+    RustRover,
+}
+
+/// IDEs the project at `root` is set up for, from their project files
+pub fn detect(root: &Path) -> Vec<Ide> {
+    let mut ides = Vec::new();
+    if root.join(RA_CONFIG).is_file() || RA_EDITOR_DIRS.iter().any(|dir| root.join(dir).is_dir()) {
+        ides.push(Ide::RustAnalyzer);
+    }
+    if root.join(".idea").is_dir() {
+        ides.push(Ide::RustRover);
+    }
+    ides
+}
+
+/// Path of the rust-analyzer config of the workspace at `root`
+pub fn ra_config_path(root: &Path) -> PathBuf {
+    root.join(RA_CONFIG)
+}
+
+/// The IDE this process runs on behalf of, if any
+pub fn running_in_ide() -> Option<&'static str> {
+    running_in_ide_from_env_lookup(crate::output::env_var)
+}
+
+/// Check for an IDE using a custom environment lookup (for testing)
+pub fn running_in_ide_from_env_lookup<F>(lookup: F) -> Option<&'static str>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let wraps_with_ra = ["RUSTC_WRAPPER", "RUSTC_WORKSPACE_WRAPPER"].iter().filter_map(|var| lookup(var)).any(|wrapper| {
+        Path::new(&wrapper).file_stem().is_some_and(|stem| stem.eq_ignore_ascii_case("rust-analyzer"))
+    });
+    if lookup(RA_WRAPPER_VAR).is_some() || wraps_with_ra {
+        Some("rust-analyzer")
+    } else if lookup(IDE_ENV_VAR).is_some_and(|value| crate::output::is_truthy(&value)) {
+        Some("an IDE")
+    } else {
+        None
+    }
+}
+
+/// How the bypass in a rust-analyzer config stands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bypass {
+    /// Written by cargo-optimize
+    Managed,
+    /// Set by the user, to this value
+    User(String),
+    /// Not set
+    Absent,
+}
+
+/// Render `rust-analyzer.toml` with IDE checks bypassing the wrapper (`bypass`) or without the managed bypass
+///
+/// A `RUSTC_WRAPPER` the user set is left alone. Returns the content and how the bypass stands in it.
+pub fn render_ra_config(original: Option<&str>, bypass: bool) -> Result<(String, Bypass)> {
+    let mut doc: DocumentMut =
+        original.unwrap_or_default().parse().with_context(|| format!("Failed to parse {}", RA_CONFIG))?;
+    let current = doc
+        .get(BYPASS_KEY[0])
+        .and_then(|check| check.get(BYPASS_KEY[1]))
+        .and_then(|extra_env| extra_env.get(BYPASS_KEY[2]));
+    let managed = current
+        .and_then(Item::as_value)
+        .and_then(|value| value.decor().suffix())
+        .and_then(|suffix| suffix.as_str())
+        .is_some_and(|suffix| suffix.contains(explain::COMMENT_PREFIX) && suffix.contains(IDE_ID));
+    if let Some(value) = current.filter(|_| !managed) {
+        let value = value.as_str().unwrap_or_default().to_string();
+        return Ok((doc.to_string(), Bypass::User(value)));
+    }
+
+    let check = doc.entry(BYPASS_KEY[0]).or_insert_with(implicit_table);
+    let extra_env = check
+        .as_table_like_mut()
+        .with_context(|| format!("`{}` in {} is not a table", BYPASS_KEY[0], RA_CONFIG))?
+        .entry(BYPASS_KEY[1])
+        .or_insert_with(|| Item::Table(Table::new()));
+    let extra_env = extra_env
+        .as_table_like_mut()
+        .with_context(|| format!("`{}.{}` in {} is not a table", BYPASS_KEY[0], BYPASS_KEY[1], RA_CONFIG))?;
+    if bypass {
+        let mut value = toml_edit::Value::from("");
+        if let Some(explanation) = explain::find(IDE_ID) {
+            value.decor_mut().set_suffix(explanation.comment());
+        }
+        extra_env.insert(BYPASS_KEY[2], Item::Value(value));
+    } else {
+        extra_env.remove(BYPASS_KEY[2]);
+    }
+
+    // Drop the tables the bypass needed once nothing else is in them
+    if extra_env.is_empty() {
+        if let Some(check) = doc.get_mut(BYPASS_KEY[0]).and_then(Item::as_table_like_mut) {
+            check.remove(BYPASS_KEY[1]);
+            if check.is_empty() {
+                doc.remove(BYPASS_KEY[0]);
+            }
+        }
+    }
+    Ok((doc.to_string(), if bypass { Bypass::Managed } else { Bypass::Absent }))
+}
+
+fn implicit_table() -> Item {
+    let mut table = Table::new();
+    table.set_implicit(true);
+    Item::Table(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_in_ide() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        assert_eq!(running_in_ide_from_env_lookup(env(&[("RA_RUSTC_WRAPPER", "1")])), Some("rust-analyzer"));
+        assert_eq!(
+            running_in_ide_from_env_lookup(env(&[("RUSTC_WRAPPER", "/home/u/.vscode/rust-analyzer.exe")])),
+            Some("rust-analyzer")
+        );
+        assert_eq!(running_in_ide_from_env_lookup(env(&[("CARGO_OPTIMIZE_IDE", "1")])), Some("an IDE"));
+        assert_eq!(running_in_ide_from_env_lookup(env(&[("RUSTC_WRAPPER", "sccache")])), None);
+    }
+
+    #[test]
+    fn test_render_ra_config() {
+        let (written, bypass) = render_ra_config(None, true).unwrap();
+        assert_eq!(bypass, Bypass::Managed);
+        assert!(written.starts_with("[check.extraEnv]\nRUSTC_WRAPPER = \"\" # cargo-optimize: CO-IDE-001"), "{}", written);
+        assert_eq!(render_ra_config(Some(&written), true).unwrap().0, written);
+        assert_eq!(render_ra_config(Some(&written), false).unwrap(), (String::new(), Bypass::Absent));
+
+        // Other settings stay, a wrapper the user chose wins
+        let original = "[check]\ncommand = \"clippy\"\n";
+        let (written, _) = render_ra_config(Some(original), true).unwrap();
+        assert!(written.starts_with(original));
+        assert_eq!(render_ra_config(Some(&written), false).unwrap().0, original);
+        let user = "[check.extraEnv]\nRUSTC_WRAPPER = \"sccache\"\n";
+        assert_eq!(render_ra_config(Some(user), true).unwrap(), (user.to_string(), Bypass::User("sccache".to_string())));
+    }
+}
@@ -0,0 +1,258 @@
+//! Journal of the config changes made by cargo-optimize, used to uninstall them.
+//!
+//! Every apply records, per key, the value it had before cargo-optimize first
+//! touched it and the value that was written. Uninstalling replays the journal:
+//! keys that did not exist are removed, changed keys get their old value back
+//! and tables that only exist because of cargo-optimize are dropped once empty.
+//! Keys the user has edited since they were written are left alone.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item, Table, Value};
+use tracing::debug;
+
+/// One key changed by cargo-optimize
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Key path (e.g. `["profile", "dev", "incremental"]`)
+    pub key: Vec<String>,
+    /// Value before cargo-optimize changed it (TOML syntax), `None` if absent
+    pub previous: Option<String>,
+    /// Value cargo-optimize wrote (TOML syntax), `None` if it removed the key
+    pub written: Option<String>,
+}
+
+impl JournalEntry {
+    /// Dotted key path for display
+    pub fn dotted_key(&self) -> String {
+        self.key.join(".")
+    }
+}
+
+/// Changes made to one config file, relative to its content before the first apply
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyJournal {
+    /// Whether the config file did not exist before cargo-optimize wrote it
+    #[serde(default)]
+    pub created_file: bool,
+    /// Keys changed, in the order they were first changed
+    #[serde(default)]
+    pub entries: Vec<JournalEntry>,
+    /// Tables that did not exist before
+    #[serde(default)]
+    pub created_tables: Vec<Vec<String>>,
+}
+
+impl ApplyJournal {
+    /// Record the differences between the original content and what was written
+    ///
+    /// An unparsable original is treated as empty, the same way planning does.
+    pub fn record(original: Option<&str>, written: &str) -> Result<Self> {
+        let before = original
+            .and_then(|content| content.parse::<DocumentMut>().ok())
+            .unwrap_or_default();
+        let after = written
+            .parse::<DocumentMut>()
+            .context("Failed to parse written config")?;
+
+        let mut journal = ApplyJournal {
+            created_file: original.is_none(),
+            ..ApplyJournal::default()
+        };
+        journal.diff(&mut Vec::new(), Some(before.as_item()), Some(after.as_item()));
+        Ok(journal)
+    }
+
+    /// Whether nothing was changed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.created_tables.is_empty()
+    }
+
+    /// Fold a later apply into this journal
+    ///
+    /// Keys keep the value from before the first change, so uninstalling goes
+    /// all the way back. Keys that ended up at their original value are dropped.
+    pub fn merge(&mut self, later: ApplyJournal) {
+        for entry in later.entries {
+            match self.entries.iter_mut().find(|e| e.key == entry.key) {
+                Some(existing) => existing.written = entry.written,
+                None => self.entries.push(entry),
+            }
+        }
+        self.entries.retain(|e| e.previous != e.written);
+
+        for table in later.created_tables {
+            if !self.created_tables.contains(&table) {
+                self.created_tables.push(table);
+            }
+        }
+    }
+
+    /// Undo the recorded changes in `doc`
+    ///
+    /// Returns the entries that were left in place because their current
+    /// value is no longer the one cargo-optimize wrote.
+    pub fn revert(&self, doc: &mut DocumentMut) -> Vec<JournalEntry> {
+        let mut kept = Vec::new();
+        for entry in self.entries.iter().rev() {
+            if !revert_entry(doc.as_table_mut(), entry) {
+                kept.push(entry.clone());
+            }
+        }
+
+        // Deepest tables first so parents are empty when they are checked
+        let mut tables = self.created_tables.clone();
+        tables.sort_by_key(|path| std::cmp::Reverse(path.len()));
+        for path in tables {
+            remove_table_if_empty(doc.as_table_mut(), &path);
+        }
+        kept
+    }
+
+    fn diff(&mut self, path: &mut Vec<String>, before: Option<&Item>, after: Option<&Item>) {
+        match (before, after) {
+            (Some(Item::Table(before)), Some(Item::Table(after))) => {
+                self.diff_tables(path, Some(before), Some(after));
+            }
+            (None, Some(Item::Table(after))) => {
+                self.created_tables.push(path.clone());
+                self.diff_tables(path, None, Some(after));
+            }
+            (Some(Item::Table(before)), None) => self.diff_tables(path, Some(before), None),
+            (before, after) => {
+                let previous = before.and_then(Item::as_value).map(normalized);
+                let written = after.and_then(Item::as_value).map(normalized);
+                let is_value = |item: Option<&Item>| item.is_none_or(Item::is_value);
+                if !is_value(before) || !is_value(after) {
+                    debug!("Not journaling structural change at {}", path.join("."));
+                } else if previous != written {
+                    self.entries.push(JournalEntry { key: path.clone(), previous, written });
+                }
+            }
+        }
+    }
+
+    fn diff_tables(&mut self, path: &mut Vec<String>, before: Option<&Table>, after: Option<&Table>) {
+        let mut keys: Vec<&str> = after.into_iter().flat_map(|t| t.iter().map(|(k, _)| k)).collect();
+        for (key, _) in before.into_iter().flat_map(|t| t.iter()) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        for key in keys {
+            path.push(key.to_string());
+            self.diff(path, before.and_then(|t| t.get(key)), after.and_then(|t| t.get(key)));
+            path.pop();
+        }
+    }
+}
+
+/// Value in TOML syntax with formatting normalized for comparison
+fn normalized(value: &Value) -> String {
+    let mut value = value.clone();
+    match &mut value {
+        Value::Array(array) => array.fmt(),
+        Value::InlineTable(table) => table.fmt(),
+        _ => {}
+    }
+    value.decor_mut().clear();
+    value.to_string()
+}
+
+/// Restore one entry; false if the key was edited since it was written
+fn revert_entry(root: &mut Table, entry: &JournalEntry) -> bool {
+    let Some((leaf, parents)) = entry.key.split_last() else {
+        return false;
+    };
+
+    let mut table = root;
+    for segment in parents {
+        if !table.contains_key(segment) {
+            if entry.previous.is_none() {
+                // Parent gone, so the key we wrote is gone as well
+                return entry.written.is_none();
+            }
+            let mut created = Table::new();
+            created.set_implicit(true);
+            table.insert(segment, Item::Table(created));
+        }
+        match table.get_mut(segment).and_then(Item::as_table_mut) {
+            Some(next) => table = next,
+            None => return false,
+        }
+    }
+
+    let current = table.get(leaf).and_then(Item::as_value).map(normalized);
+    if current != entry.written {
+        return false;
+    }
+
+    match entry.previous.as_deref().map(str::parse::<Value>) {
+        Some(Ok(previous)) => {
+            table.insert(leaf, toml_edit::value(previous));
+        }
+        Some(Err(e)) => {
+            debug!("Cannot restore {}: {}", entry.dotted_key(), e);
+            return false;
+        }
+        None => {
+            table.remove(leaf);
+        }
+    }
+    true
+}
+
+fn remove_table_if_empty(root: &mut Table, path: &[String]) {
+    let Some((leaf, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = root;
+    for segment in parents {
+        match table.get_mut(segment).and_then(Item::as_table_mut) {
+            Some(next) => table = next,
+            None => return,
+        }
+    }
+    if table.get(leaf).and_then(Item::as_table).is_some_and(Table::is_empty) {
+        table.remove(leaf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_revert() {
+        let original = "# keep me\n[build]\njobs = 2\ntarget-dir = \"out\"\n";
+        let written = "# keep me\n[build]\njobs = 8\ntarget-dir = \"out\"\n\n[profile.dev]\nincremental = true\n";
+
+        let journal = ApplyJournal::record(Some(original), written).unwrap();
+        assert!(!journal.created_file);
+        assert_eq!(journal.entries.len(), 2);
+        assert!(journal.created_tables.contains(&vec!["profile".to_string()]));
+
+        let mut doc: DocumentMut = written.parse().unwrap();
+        assert!(journal.revert(&mut doc).is_empty());
+        assert_eq!(doc.to_string(), original);
+    }
+
+    #[test]
+    fn test_revert_keeps_user_edits_and_merge_keeps_first_value() {
+        let mut journal = ApplyJournal::record(Some("[build]\njobs = 2\n"), "[build]\njobs = 4\n").unwrap();
+        journal.merge(ApplyJournal::record(Some("[build]\njobs = 4\n"), "[build]\njobs = 6\n").unwrap());
+        assert_eq!(journal.entries[0].previous.as_deref(), Some("2"));
+        assert_eq!(journal.entries[0].written.as_deref(), Some("6"));
+
+        // The user changed the value afterwards; it is theirs now
+        let mut doc: DocumentMut = "[build]\njobs = 12\n".parse().unwrap();
+        let kept = journal.revert(&mut doc);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(doc["build"]["jobs"].as_integer(), Some(12));
+
+        // Changing a key back to its original value drops it from the journal
+        journal.merge(ApplyJournal::record(Some("[build]\njobs = 6\n"), "[build]\njobs = 2\n").unwrap());
+        assert!(journal.is_empty());
+    }
+}
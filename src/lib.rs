@@ -29,6 +29,18 @@ pub mod mvp;
 // Re-export MVP function as the main interface for now
 pub use mvp::auto_configure_mvp as auto_configure;
 
+/// Output routing for user-facing messages.
+/// 
+/// Detects the build-script context and emits namespaced `cargo:warning=`
+/// lines, honoring `CARGO_OPTIMIZE_QUIET`.
+pub mod output;
+
+/// Localized user-facing messages.
+/// 
+/// Message catalogs for English, Japanese, Chinese and German, selected via
+/// `CARGO_OPTIMIZE_LANG` or the system locale.
+pub mod i18n;
+
 /// Configuration module for build optimization settings.
 /// 
 /// Provides enhanced configuration management with support for:
@@ -38,6 +50,126 @@ pub use mvp::auto_configure_mvp as auto_configure;
 /// - Backup and rollback capabilities
 pub mod config;
 
+/// Pull-request mode.
+/// 
+/// Stages proposed config files and a `CHANGES.md` summary in
+/// `.cargo-optimize/proposed/` for review; accepting applies them.
+pub mod proposal;
+
+/// Atomic config file transactions.
+/// 
+/// Reads `.cargo/config.toml` once, stages the final content in memory and
+/// commits it with a single fsynced temp-file write and atomic rename.
+pub mod transaction;
+
+/// Windows-safe path handling.
+/// 
+/// Extended-length (`\\?\`) forms for paths beyond `MAX_PATH`, including
+/// UNC shares, and actionable errors when the filesystem refuses a path.
+pub mod paths;
+
+/// Format-preserving profile edits of `Cargo.toml`.
+/// 
+/// Shows planned `[profile.*]` changes as a diff and refuses to write when
+/// anything outside the targeted tables would change.
+pub mod manifest;
+
+/// Persistent state between runs.
+/// 
+/// Stores information such as deferred applies in
+/// `.cargo/cargo-optimize-state.json`.
+pub mod state;
+
+/// Concurrent access to the state and metrics files.
+/// 
+/// Locks writers against each other, keeps the previous snapshot and moves
+/// corrupt files aside, so parallel build scripts never lose an update.
+pub mod store;
+
+/// Phased apply.
+/// 
+/// Adds the planned changes phase by phase (linker, cache, profiles, env),
+/// verifying each one and rolling back the phase that fails.
+pub mod phases;
+
+/// Journal of applied changes.
+/// 
+/// Records what each apply changed so [`config::ConfigManager::uninstall`]
+/// can restore the previous configuration.
+pub mod journal;
+
+/// Audit log of changed files.
+/// 
+/// Appends one line per file cargo-optimize writes to `.cargo-optimize/audit.log`,
+/// with content hashes before and after, and renders the history as a changelog.
+pub mod audit;
+
+/// Removing everything cargo-optimize left behind.
+/// 
+/// Lists the state, metrics, backups, caches, proposals, audit log and
+/// downloaded linkers cargo-optimize keeps, and removes them after uninstalling.
+pub mod purge;
+
+/// Upgrade previews.
+/// 
+/// Lists the settings the next apply would add, change or remove, separating
+/// values an earlier version wrote from new ones.
+pub mod migration;
+
+/// The planned config as cargo `--config` flags.
+/// 
+/// Lets one-off builds use the optimizations without writing any file, by
+/// passing `--config key=value` arguments to cargo instead.
+pub mod cli_flags;
+
+/// Concurrent build detection.
+/// 
+/// Detects cargo builds holding the target directory lock so the config is
+/// never rewritten underneath them.
+pub mod build_lock;
+
+/// Target directory relocation.
+/// 
+/// Copies or moves profile output directories, fingerprints included, into
+/// the target directories a new layout introduces, rolling back on failure.
+pub mod relocation;
+
+/// Rust toolchain detection.
+/// 
+/// Parses `rustc -vV` to determine the compiler version and release channel
+/// so version- and channel-gated settings are only written when supported.
+pub mod toolchain;
+
+/// Toolchain capability matrix.
+/// 
+/// Versions and channels from which version-gated cargo features can be
+/// configured or are on by default, consulted by every generator.
+pub mod capabilities;
+
+/// Resource amount expressions.
+/// 
+/// Tiny parser for job counts and sizes such as `"50% physical"`,
+/// `"logical-2"` and `"min(8, 75%)"`, with precise error messages.
+pub mod resource;
+
+/// Fast linker registry.
+/// 
+/// Data table of known linkers with platform support, detection commands,
+/// invocation, rustflags and minimum rustc versions.
+pub mod linkers;
+
+/// Fast linker installation.
+/// 
+/// Opt-in download of checksum-verified linker releases into a managed tools
+/// directory, or the distribution's package command when downloads are off.
+pub mod tools;
+
+/// Network proxies.
+/// 
+/// `[global.proxy]` and the `*_PROXY` variables, applied to linker downloads,
+/// component installs and the sccache-dist probe, with failure diagnosis.
+pub mod proxy;
+
 /// Hardware detection and system information module.
 /// 
 /// Provides platform-aware hardware detection with:
@@ -47,6 +179,12 @@ pub mod config;
 /// - Fallback values for failure scenarios
 pub mod hardware;
 
+/// Build process profiling.
+/// 
+/// Samples rustc and linker processes during a build and attributes their
+/// time to compilation, linking or IO wait to show which optimization helps most.
+pub mod profiler;
+
 /// Project analysis module using cargo_metadata and guppy.
 /// 
 /// Provides comprehensive project analysis including:
@@ -55,8 +193,181 @@ pub mod hardware;
 /// - Feature analysis and optimization suggestions
 /// - Build target detection
 /// - Build metrics collection
+/// - Per-dependency build reports exported as CSV or JSON
 pub mod analysis;
 
+/// Local build metrics.
+/// 
+/// History of build durations on this machine and badge generation
+/// (`metrics::render_badge`) for READMEs; nothing leaves the machine.
+pub mod metrics;
+
+/// Optimization scoring.
+/// 
+/// Grades how optimized a project already is, from 0 to 100 across linker,
+/// caching, profile, dependency and CI categories, with the top next action.
+pub mod score;
+
+/// Optimization report module.
+/// 
+/// Records the decisions taken during apply (applied or skipped, with the
+/// reason), recommendations, and cleanup candidates, and writes read-only
+/// CI report artifacts.
+pub mod report;
+
+/// Explanations for managed settings.
+/// 
+/// Catalog of explanation ids (e.g. `CO-LINKER-001`) written next to each
+/// managed config value, with rationale, impact, risks and how to disable.
+pub mod explain;
+
+// Look up the explanation id found in a config comment
+pub use explain::explain;
+
+/// Workspace-hack crates.
+/// 
+/// Simulates per-member builds to find dependencies compiled with different
+/// features, quantifies the rebuilds and generates a workspace-hack crate.
+pub mod workspace_hack;
+
+/// sccache payoff heuristics.
+/// 
+/// Records sccache hit statistics across applies and removes the wrapper
+/// when it does not pay off, e.g. for small local projects.
+pub mod sccache;
+
+/// Linker rollback guardrails.
+/// 
+/// Optional build wrapper that recognizes linker failures after an apply,
+/// reverts the linker settings and keeps the linker from being configured again.
+pub mod guardrail;
+
+/// Distributed compilation with sccache-dist.
+/// 
+/// Detects a configured build farm scheduler, probes it and routes rustc
+/// through sccache when it answers.
+pub mod sccache_dist;
+
+/// Competing build tooling.
+/// 
+/// Registry of tools whose settings interact with cargo-optimize's (a
+/// wrapper in `RUSTC_WRAPPER`, cargo-chef, a committed hand-tuned config),
+/// evaluated during planning.
+pub mod conflicts;
+
+/// Container build caching.
+/// 
+/// Finds Dockerfiles running cargo, flags cache-busting layer orders and
+/// missing cache mounts, and proposes a cache-friendly build stage.
+pub mod container;
+
+/// Nix and devenv environments.
+/// 
+/// Detects Nix dev shells and store-path toolchains, keeps store paths out
+/// of the config, and renders the planned settings for devenv or a flake.
+pub mod nix;
+
+/// Strict mode.
+/// 
+/// Verifies the optimizations `cargo-optimize.toml` declares as required
+/// and fails the build with remediation steps when one is missing.
+pub mod strict;
+
+/// Secret references and redaction.
+/// 
+/// Resolves `${ENV:NAME}` references in cache backend settings at apply
+/// time and masks secrets in reports, output and captures.
+pub mod secrets;
+
+/// Third-party optimization providers.
+/// 
+/// The [`OptimizationProvider`](providers::OptimizationProvider) trait and
+/// the registry build scripts add external optimizations to, planned and
+/// reported alongside the built-in ones.
+pub mod providers;
+
+/// Machine-wide policy.
+/// 
+/// Loads the admin-managed policy file (allowed linkers, mandatory sccache
+/// endpoint, forbidden flags) that project-level applies comply with.
+pub mod policy;
+
+/// Advisor module.
+/// 
+/// Turns decisions that need user action (missing toolchain components,
+/// tools to install) into advice with the exact command to run.
+pub mod advisor;
+
+/// cargo-nextest integration.
+/// 
+/// Writes a managed `.config/nextest.toml` with parallelism and retries
+/// derived from the hardware and build context when nextest is installed.
+pub mod nextest;
+
+/// IDE integration.
+/// 
+/// Keeps rust-analyzer's checks out of a project-wide rustc wrapper such as
+/// sccache, and skips configuring from build scripts an IDE runs.
+pub mod ide;
+
+/// CI integration module.
+/// 
+/// Composes stable, size-capped markdown summaries of analysis and changes
+/// for bots that comment on pull requests.
+pub mod ci;
+
+/// Exit codes.
+/// 
+/// Stable exit codes the binary and CI pipelines branch on: drift, policy
+/// violations, unsupported platforms, rolled back applies and more.
+pub mod exit;
+
+/// Git hooks integration.
+/// 
+/// Installs an idempotent, removable drift check into `post-checkout` and
+/// `post-merge` that recommends re-running cargo-optimize when its inputs change.
+pub mod hooks;
+
+/// Adoption without a build script.
+/// 
+/// Defines a `cargo optimize` alias in `.cargo/config.toml` for the
+/// `cargo-optimize` binary, optionally with git hooks that re-run it.
+pub mod adoption;
+
+/// New project generation.
+/// 
+/// Renders a package or workspace with cargo-optimize wired in, profiles for
+/// a project template, a CI pipeline and `.gitignore` entries for its state.
+pub mod scaffold;
+
+/// Troubleshooting captures.
+/// 
+/// Captures the redacted configs, environment, plan and log behind a run, and
+/// replays the planning against a capture to reproduce decisions elsewhere.
+pub mod debug;
+
+/// Managed TOML sections (feature `toml-manage`).
+/// 
+/// Stable API for other build tools to open a cargo config, upsert or remove
+/// the tables they manage, diff the result and save it atomically.
+#[cfg(feature = "toml-manage")]
+pub mod toml_manage;
+
+/// Build script testing support (feature `test-support`).
+/// 
+/// Fake linkers, platform, cores and working directory for the current
+/// thread, and scaffolding of throwaway projects, so downstream build scripts
+/// calling cargo-optimize can be unit tested deterministically.
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+/// Property-testing support (feature `property-tests`).
+/// 
+/// Proptest strategies generating arbitrary valid cargo configs for testing
+/// the merge engine.
+#[cfg(feature = "property-tests")]
+pub mod testing;
+
 /// Utility functions for output and common operations.
 /// 
 /// Provides helper functions for displaying messages to users.
@@ -80,9 +391,42 @@ pub mod utils {
 
 /// Main optimizer struct for managing build optimizations.
 /// 
-/// Currently a placeholder that will be expanded with full
-/// optimization logic in future versions.
-pub struct Optimizer;
+/// Applies the layered configuration of one project. With the `async`
+/// feature, [`Optimizer::apply_async`] runs the apply on tokio's blocking
+/// thread pool so dev servers and watchers do not stall their runtime.
+#[derive(Debug, Clone)]
+pub struct Optimizer {
+    project_root: std::path::PathBuf,
+}
+
+impl Optimizer {
+    /// Create an optimizer for the project at `project_root`
+    pub fn new(project_root: impl Into<std::path::PathBuf>) -> Self {
+        Self { project_root: project_root.into() }
+    }
+    
+    /// Project root whose `.cargo/config.toml` is optimized
+    pub fn project_root(&self) -> &std::path::Path {
+        &self.project_root
+    }
+    
+    /// Apply optimizations and report the decisions taken
+    pub fn apply(&self) -> anyhow::Result<report::OptimizationReport> {
+        config::ConfigManager::new_with_base_dir(&self.project_root, "CARGO_OPTIMIZE_")?
+            .apply_with_report()
+    }
+    
+    /// Apply optimizations without blocking the async runtime (feature `async`)
+    ///
+    /// Must be called from within a tokio runtime.
+    #[cfg(feature = "async")]
+    pub async fn apply_async(&self) -> anyhow::Result<report::OptimizationReport> {
+        let optimizer = self.clone();
+        tokio::task::spawn_blocking(move || optimizer.apply())
+            .await
+            .map_err(|e| anyhow::anyhow!("apply task failed: {}", e))?
+    }
+}
 
 /// Build cache configuration and management.
 /// 
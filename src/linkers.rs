@@ -0,0 +1,521 @@
+//! Registry of known fast linkers.
+//!
+//! Each [`LinkerSpec`] describes one linker: the platforms it runs on, the
+//! commands that reveal it is installed, how cargo invokes it and the oldest
+//! rustc it works with. Detection and config generation read this table, so
+//! supporting a new linker means adding an entry here plus tests.
+//!
+//! [`PlatformCapabilities`] describe what is safe to configure on each host
+//! platform, so platforms beyond the tier 1 hosts get either correct settings
+//! or a clear reason why an optimization is unavailable.
+
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+
+use crate::toolchain::RustVersion;
+
+/// Host platform a linker can run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Linux (GNU toolchain)
+    Linux,
+    /// Windows (MSVC toolchain)
+    Windows,
+    /// macOS
+    MacOs,
+    /// FreeBSD
+    FreeBsd,
+    /// NetBSD
+    NetBsd,
+    /// illumos
+    Illumos,
+}
+
+impl Platform {
+    /// Every platform with a capability descriptor
+    pub const ALL: [Platform; 6] = [
+        Platform::Linux,
+        Platform::Windows,
+        Platform::MacOs,
+        Platform::FreeBsd,
+        Platform::NetBsd,
+        Platform::Illumos,
+    ];
+
+    /// The platform cargo-optimize was built for, if supported
+    ///
+    /// A known OS on a CPU architecture without a target triple here counts
+    /// as unsupported, so no linker settings are guessed for it.
+    pub fn current() -> Option<Self> {
+        Self::current_os().filter(|platform| platform.target_triple().is_some())
+    }
+
+    fn current_os() -> Option<Self> {
+        #[cfg(feature = "test-support")]
+        if let Some(platform) = crate::test_support::platform() {
+            return platform;
+        }
+        if cfg!(target_os = "linux") {
+            Some(Platform::Linux)
+        } else if cfg!(target_os = "windows") {
+            Some(Platform::Windows)
+        } else if cfg!(target_os = "macos") {
+            Some(Platform::MacOs)
+        } else if cfg!(target_os = "freebsd") {
+            Some(Platform::FreeBsd)
+        } else if cfg!(target_os = "netbsd") {
+            Some(Platform::NetBsd)
+        } else if cfg!(target_os = "illumos") {
+            Some(Platform::Illumos)
+        } else {
+            None
+        }
+    }
+
+    /// Display name of the platform
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Linux => "Linux",
+            Platform::Windows => "Windows",
+            Platform::MacOs => "macOS",
+            Platform::FreeBsd => "FreeBSD",
+            Platform::NetBsd => "NetBSD",
+            Platform::Illumos => "illumos",
+        }
+    }
+
+    /// What can safely be configured on the platform
+    pub fn capabilities(self) -> PlatformCapabilities {
+        let full = PlatformCapabilities {
+            platform: self,
+            tier1: true,
+            linker_config: true,
+            memory_detection: true,
+            system_linkers: &[],
+            note: None,
+        };
+        match self {
+            Platform::Linux => PlatformCapabilities { system_linkers: &["ld"], ..full },
+            Platform::Windows => PlatformCapabilities { system_linkers: &["link.exe", "lld-link.exe"], ..full },
+            Platform::MacOs => PlatformCapabilities { system_linkers: &["ld64"], ..full },
+            Platform::FreeBsd => PlatformCapabilities {
+                tier1: false,
+                system_linkers: &["ld", "ld.lld"],
+                note: Some("ld.lld is already the system linker on FreeBSD; only mold links faster"),
+                ..full
+            },
+            Platform::NetBsd => PlatformCapabilities {
+                tier1: false,
+                linker_config: false,
+                memory_detection: false,
+                system_linkers: &["ld"],
+                note: Some("no supported fast linker runs on NetBSD, which links with GNU ld"),
+                ..full
+            },
+            Platform::Illumos => PlatformCapabilities {
+                tier1: false,
+                linker_config: false,
+                memory_detection: false,
+                system_linkers: &["ld"],
+                note: Some("illumos links with its native ld, which no supported fast linker replaces"),
+                ..full
+            },
+        }
+    }
+
+    /// Target triple whose `[target.<triple>]` table receives the linker config
+    ///
+    /// For the CPU architecture cargo-optimize was built for, which is the
+    /// host's when it runs as a build dependency.
+    /// `None` when the platform has no triple for that architecture.
+    pub fn target_triple(self) -> Option<&'static str> {
+        self.target_triple_for(&host_arch())
+    }
+
+    /// Target triple of the platform on `arch` (as in `std::env::consts::ARCH`)
+    ///
+    /// `None` for an architecture the platform has no host triple for.
+    pub fn target_triple_for(self, arch: &str) -> Option<&'static str> {
+        let triple = match (self, arch) {
+            (Platform::Linux, "x86_64") => "x86_64-unknown-linux-gnu",
+            (Platform::Linux, "aarch64") => "aarch64-unknown-linux-gnu",
+            (Platform::Linux, "x86") => "i686-unknown-linux-gnu",
+            (Platform::Linux, "riscv64") => "riscv64gc-unknown-linux-gnu",
+            (Platform::Linux, "loongarch64") => "loongarch64-unknown-linux-gnu",
+            (Platform::Linux, "s390x") => "s390x-unknown-linux-gnu",
+            (Platform::Windows, "x86_64") => "x86_64-pc-windows-msvc",
+            (Platform::Windows, "aarch64") => "aarch64-pc-windows-msvc",
+            (Platform::Windows, "x86") => "i686-pc-windows-msvc",
+            (Platform::MacOs, "x86_64") => "x86_64-apple-darwin",
+            (Platform::MacOs, "aarch64") => "aarch64-apple-darwin",
+            (Platform::FreeBsd, "x86_64") => "x86_64-unknown-freebsd",
+            (Platform::FreeBsd, "aarch64") => "aarch64-unknown-freebsd",
+            (Platform::FreeBsd, "x86") => "i686-unknown-freebsd",
+            (Platform::NetBsd, "x86_64") => "x86_64-unknown-netbsd",
+            (Platform::NetBsd, "aarch64") => "aarch64-unknown-netbsd",
+            (Platform::Illumos, "x86_64") => "x86_64-unknown-illumos",
+            _ => return None,
+        };
+        Some(triple)
+    }
+}
+
+/// CPU architecture the linker settings are written for
+///
+/// The one cargo-optimize was built for, or the pretended one under a fake
+/// environment.
+pub fn host_arch() -> String {
+    #[cfg(feature = "test-support")]
+    if let Some(arch) = crate::test_support::arch() {
+        return arch;
+    }
+    std::env::consts::ARCH.to_string()
+}
+
+/// What cargo-optimize can safely configure on a host platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlatformCapabilities {
+    /// Platform described
+    pub platform: Platform,
+    /// Whether the platform is a tier 1 Rust host
+    pub tier1: bool,
+    /// Whether fast-linker configuration is written; when set, the registry
+    /// has at least one linker for the platform
+    pub linker_config: bool,
+    /// Whether total and available memory can be detected
+    pub memory_detection: bool,
+    /// Linkers of the platform's own toolchain, accepted without rustflags
+    pub system_linkers: &'static [&'static str],
+    /// Why optimizations are limited on the platform, for the report
+    pub note: Option<&'static str>,
+}
+
+/// How cargo invokes a linker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invocation {
+    /// `linker = "<value>"` names the linker executable itself
+    Direct(&'static str),
+    /// `linker` names a C compiler driver that is told to use the linker
+    /// through [`LinkerSpec::link_arg`]
+    Driver,
+}
+
+/// One known linker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkerSpec {
+    /// Name used in configuration and output (e.g. "mold")
+    pub name: &'static str,
+    /// Platforms the linker supports
+    pub platforms: &'static [Platform],
+    /// CPU architectures it links for (as in `std::env::consts::ARCH`); empty for every one
+    pub arches: &'static [&'static str],
+    /// Commands whose presence means the linker is installed (any of them)
+    pub detect: &'static [&'static str],
+    /// How cargo invokes the linker
+    pub invocation: Invocation,
+    /// Driver argument selecting the linker (passed via `-C link-arg=`)
+    pub link_arg: Option<&'static str>,
+    /// C compiler drivers able to pass `link_arg`, in order of preference
+    pub drivers: &'static [&'static str],
+    /// Oldest rustc the configuration works with
+    pub min_rustc: Option<RustVersion>,
+}
+
+impl LinkerSpec {
+    /// Whether the linker runs on `platform`
+    pub fn supports(&self, platform: Platform) -> bool {
+        self.platforms.contains(&platform)
+    }
+
+    /// Whether the linker links for the architecture of `triple`
+    pub fn supports_triple(&self, triple: &str) -> bool {
+        let arch = triple.split('-').next().unwrap_or_default();
+        self.arches.is_empty() || self.arches.contains(&arch)
+    }
+
+    /// Whether the linker works with the given rustc (unknown versions pass)
+    pub fn supports_rustc(&self, rustc: Option<&RustVersion>) -> bool {
+        match (&self.min_rustc, rustc) {
+            (Some(min), Some(rustc)) => rustc >= min,
+            _ => true,
+        }
+    }
+
+    /// Whether any detection command exists according to `exists`
+    pub fn is_installed_with<F>(&self, exists: F) -> bool
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.detect.iter().any(|command| exists(command))
+    }
+
+    /// `rustflags` needed to select the linker
+    pub fn rustflags(&self) -> Vec<String> {
+        match self.link_arg {
+            Some(arg) => vec!["-C".to_string(), format!("link-arg={}", arg)],
+            None => Vec::new(),
+        }
+    }
+}
+
+const GCC_COMPATIBLE_DRIVERS: &[&str] = &["clang", "gcc", "cc"];
+
+/// Known linkers in order of preference
+pub const LINKERS: &[LinkerSpec] = &[
+    LinkerSpec {
+        name: "rust-lld",
+        platforms: &[Platform::Windows],
+        // Shipped with the x64 and ARM64 MSVC toolchains
+        arches: &["x86_64", "aarch64"],
+        // Shipped with every Rust toolchain
+        detect: &["rustc"],
+        invocation: Invocation::Direct("rust-lld"),
+        link_arg: None,
+        drivers: &[],
+        min_rustc: None,
+    },
+    LinkerSpec {
+        name: "lld-link",
+        platforms: &[Platform::Windows],
+        arches: &["x86_64", "aarch64"],
+        detect: &["lld-link.exe", "lld-link"],
+        invocation: Invocation::Direct("lld-link.exe"),
+        link_arg: None,
+        drivers: &[],
+        min_rustc: None,
+    },
+    LinkerSpec {
+        name: "mold",
+        platforms: &[Platform::Linux, Platform::FreeBsd],
+        arches: &[],
+        detect: &["mold"],
+        invocation: Invocation::Driver,
+        link_arg: Some("-fuse-ld=mold"),
+        drivers: GCC_COMPATIBLE_DRIVERS,
+        min_rustc: None,
+    },
+    LinkerSpec {
+        name: "wild",
+        platforms: &[Platform::Linux],
+        arches: &[],
+        detect: &["wild"],
+        invocation: Invocation::Driver,
+        // GCC's -fuse-ld only accepts a fixed set of names
+        link_arg: Some("--ld-path=wild"),
+        drivers: &["clang"],
+        min_rustc: None,
+    },
+    LinkerSpec {
+        name: "sold",
+        platforms: &[Platform::MacOs],
+        arches: &[],
+        detect: &["ld64.sold"],
+        invocation: Invocation::Driver,
+        link_arg: Some("--ld-path=ld64.sold"),
+        drivers: &["clang"],
+        min_rustc: None,
+    },
+    LinkerSpec {
+        name: "lld",
+        platforms: &[Platform::Linux, Platform::MacOs],
+        arches: &[],
+        detect: &["ld.lld", "lld"],
+        invocation: Invocation::Driver,
+        link_arg: Some("-fuse-ld=lld"),
+        drivers: GCC_COMPATIBLE_DRIVERS,
+        min_rustc: None,
+    },
+    LinkerSpec {
+        name: "gold",
+        platforms: &[Platform::Linux],
+        arches: &[],
+        detect: &["gold"],
+        invocation: Invocation::Driver,
+        link_arg: Some("-fuse-ld=gold"),
+        drivers: GCC_COMPATIBLE_DRIVERS,
+        min_rustc: None,
+    },
+    LinkerSpec {
+        name: "zld",
+        platforms: &[Platform::MacOs],
+        arches: &[],
+        detect: &["zld"],
+        invocation: Invocation::Driver,
+        link_arg: Some("-fuse-ld=zld"),
+        drivers: &["clang"],
+        min_rustc: None,
+    },
+];
+
+/// Look up a known linker by name
+pub fn find(name: &str) -> Option<&'static LinkerSpec> {
+    LINKERS.iter().find(|spec| spec.name == name)
+}
+
+/// Known linkers for a platform, in order of preference
+pub fn for_platform(platform: Platform) -> impl Iterator<Item = &'static LinkerSpec> {
+    LINKERS.iter().filter(move |spec| spec.supports(platform))
+}
+
+/// Pick the preferred installed linker from `registry`
+///
+/// Linkers needing a newer rustc than `rustc` are passed over.
+pub fn detect_in<'a, F>(
+    registry: &'a [LinkerSpec],
+    platform: Platform,
+    rustc: Option<&RustVersion>,
+    exists: F,
+) -> Option<&'a LinkerSpec>
+where
+    F: Fn(&str) -> bool,
+{
+    registry.iter().find(|spec| {
+        spec.supports(platform)
+            && platform.target_triple().is_some_and(|triple| spec.supports_triple(triple))
+            && spec.supports_rustc(rustc)
+            && spec.is_installed_with(&exists)
+    })
+}
+
+/// `[target.<triple>]` table running `linker` with `rustflags`, `comment` trailing each value
+///
+/// Values are built with toml_edit rather than pasted into a template, so
+/// linker paths with spaces, backslashes (`C:\Program Files\LLVM\bin`) or
+/// non-ASCII characters come out as valid TOML strings.
+pub fn target_table(triple: &str, linker: &str, rustflags: &[String], comment: &str) -> String {
+    let trailing = |mut value: Value| {
+        value.decor_mut().set_suffix(comment);
+        Item::Value(value)
+    };
+    let mut table = Table::new();
+    table.insert("linker", trailing(Value::from(linker)));
+    if !rustflags.is_empty() {
+        table.insert("rustflags", trailing(Value::Array(Array::from_iter(rustflags))));
+    }
+    let mut target = Table::new();
+    target.set_implicit(true);
+    target.insert(triple, Item::Table(table));
+    let mut doc = DocumentMut::new();
+    doc.insert("target", Item::Table(target));
+    doc.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_entries_are_consistent() {
+        for spec in LINKERS {
+            assert!(!spec.platforms.is_empty(), "{} has no platform", spec.name);
+            assert!(!spec.detect.is_empty(), "{} has no detection command", spec.name);
+            if spec.invocation == Invocation::Driver {
+                assert!(spec.link_arg.is_some(), "{} needs a link arg", spec.name);
+                assert!(!spec.drivers.is_empty(), "{} needs a driver", spec.name);
+            }
+            assert_eq!(find(spec.name), Some(spec));
+        }
+    }
+
+    #[test]
+    fn test_capabilities_match_registry() {
+        for platform in Platform::ALL {
+            let capabilities = platform.capabilities();
+            assert_eq!(capabilities.platform, platform);
+            assert_eq!(
+                capabilities.linker_config,
+                for_platform(platform).next().is_some(),
+                "{} linker capability disagrees with the registry",
+                platform.name()
+            );
+            assert!(!capabilities.system_linkers.is_empty());
+            assert!(capabilities.tier1 || capabilities.note.is_some(), "{} needs a note", platform.name());
+        }
+        assert_eq!(for_platform(Platform::FreeBsd).map(|s| s.name).collect::<Vec<_>>(), vec!["mold"]);
+    }
+
+    #[test]
+    fn test_detection_order_and_filters() {
+        let installed = |commands: &'static [&'static str]| move |cmd: &str| commands.contains(&cmd);
+
+        let linker = detect_in(LINKERS, Platform::Linux, None, installed(&["gold", "wild", "ld.lld"]));
+        assert_eq!(linker.map(|s| s.name), Some("wild"));
+        let linker = detect_in(LINKERS, Platform::Linux, None, installed(&["gold", "mold"]));
+        assert_eq!(linker.map(|s| s.name), Some("mold"));
+        // Platform mismatch
+        assert!(detect_in(LINKERS, Platform::MacOs, None, installed(&["mold", "gold"])).is_none());
+
+        // A linker needing a newer rustc is skipped
+        let registry = [
+            LinkerSpec { min_rustc: Some(RustVersion::new(1, 90, 0)), ..*find("wild").unwrap() },
+            *find("gold").unwrap(),
+        ];
+        let old = RustVersion::new(1, 80, 0);
+        let linker = detect_in(&registry, Platform::Linux, Some(&old), installed(&["wild", "gold"]));
+        assert_eq!(linker.map(|s| s.name), Some("gold"));
+        let linker = detect_in(&registry, Platform::Linux, None, installed(&["wild", "gold"]));
+        assert_eq!(linker.map(|s| s.name), Some("wild"));
+
+        assert_eq!(find("wild").unwrap().rustflags(), vec!["-C", "link-arg=--ld-path=wild"]);
+        assert!(find("rust-lld").unwrap().rustflags().is_empty());
+    }
+
+    #[test]
+    fn test_target_table_quoting() {
+        let flags = find("mold").unwrap().rustflags();
+        assert_eq!(
+            target_table("x86_64-unknown-linux-gnu", "clang", &flags, " # note"),
+            "[target.x86_64-unknown-linux-gnu]\nlinker = \"clang\" # note\nrustflags = [\"-C\", \"link-arg=-fuse-ld=mold\"] # note\n"
+        );
+
+        let gnarly = [
+            r"C:\Program Files\LLVM\bin\lld-link.exe",
+            r"C:\Users\Zoë Ångström\.cargo\bin\rust-lld.exe",
+            "/home/jürgen/it's \"quoted\"/bin/clang",
+            "/opt/tab\there/用户/clang",
+            r#"C:\it's "both"\ld.exe"#,
+        ];
+        for linker in gnarly {
+            let rendered = target_table("x86_64-pc-windows-msvc", linker, &[linker.to_string()], "");
+            let doc: DocumentMut = rendered.parse().unwrap_or_else(|e| panic!("{}\n{}", rendered, e));
+            let table = &doc["target"]["x86_64-pc-windows-msvc"];
+            assert_eq!(table["linker"].as_str(), Some(linker), "{}", rendered);
+            assert_eq!(table["rustflags"][0].as_str(), Some(linker), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn test_windows_arm64() {
+        assert_eq!(Platform::Windows.target_triple_for("aarch64"), Some("aarch64-pc-windows-msvc"));
+        assert_eq!(Platform::Windows.target_triple_for("x86_64"), Some("x86_64-pc-windows-msvc"));
+        assert_eq!(Platform::MacOs.target_triple_for("aarch64"), Some("aarch64-apple-darwin"));
+
+        // Both Windows linkers link ARM64, rust-lld first
+        let arm64: Vec<_> = for_platform(Platform::Windows)
+            .filter(|spec| spec.supports_triple("aarch64-pc-windows-msvc"))
+            .map(|spec| spec.name)
+            .collect();
+        assert_eq!(arm64, ["rust-lld", "lld-link"]);
+        let x64_only = LinkerSpec { arches: &["x86_64"], ..*find("lld-link").unwrap() };
+        assert!(!x64_only.supports_triple("aarch64-pc-windows-msvc"));
+        assert!(find("mold").unwrap().supports_triple("aarch64-unknown-linux-gnu"));
+
+        let rendered = target_table("aarch64-pc-windows-msvc", "rust-lld", &[], "");
+        assert_eq!(rendered, "[target.aarch64-pc-windows-msvc]\nlinker = \"rust-lld\"\n");
+    }
+
+    #[test]
+    fn test_target_triples_follow_the_arch() {
+        for platform in Platform::ALL {
+            let triple = platform.target_triple_for("x86_64").unwrap();
+            assert!(triple.starts_with("x86_64-"), "{}", triple);
+        }
+        assert_eq!(Platform::Linux.target_triple_for("aarch64"), Some("aarch64-unknown-linux-gnu"));
+        assert_eq!(Platform::Linux.target_triple_for("riscv64"), Some("riscv64gc-unknown-linux-gnu"));
+        assert_eq!(Platform::FreeBsd.target_triple_for("aarch64"), Some("aarch64-unknown-freebsd"));
+        assert_eq!(Platform::Windows.target_triple_for("x86"), Some("i686-pc-windows-msvc"));
+        // No guessed x86_64 triple for an architecture the platform has no host for
+        assert_eq!(Platform::MacOs.target_triple_for("riscv64"), None);
+        assert_eq!(Platform::Illumos.target_triple_for("aarch64"), None);
+        assert_eq!(Platform::Linux.target_triple_for("sparc64"), None);
+    }
+}
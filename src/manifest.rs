@@ -0,0 +1,296 @@
+//! Format-preserving `[profile.*]` edits of `Cargo.toml`.
+//!
+//! Profiles set in `Cargo.toml` apply to everyone building the project,
+//! including builds that never see `.cargo/config.toml`. Manifests are often
+//! formatted with care, so a [`ProfileEdit`] only ever touches the
+//! `[profile.<name>]` tables it targets. [`ProfileEdit::diff`] shows the
+//! change as a unified diff before anything is written, and
+//! [`ProfileEdit::save`] compares the edited document with the original
+//! outside the targeted tables and refuses to write if any other node, its
+//! comments or its whitespace changed.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::audit;
+use crate::transaction::ConfigTransaction;
+
+/// Lines of unchanged context around each hunk of a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Pending profile edits of a `Cargo.toml`
+#[derive(Debug, Clone)]
+pub struct ProfileEdit {
+    transaction: ConfigTransaction,
+    doc: DocumentMut,
+    targets: Vec<String>,
+}
+
+impl ProfileEdit {
+    /// Open the manifest at `path`, which must exist
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let transaction = ConfigTransaction::begin(path)?;
+        let doc = transaction
+            .original()
+            .with_context(|| format!("{} does not exist", transaction.path().display()))?
+            .parse::<DocumentMut>()
+            .with_context(|| format!("Failed to parse {}", transaction.path().display()))?;
+        Ok(ProfileEdit { transaction, doc, targets: Vec::new() })
+    }
+
+    /// Path of the manifest
+    pub fn path(&self) -> &Path {
+        self.transaction.path()
+    }
+
+    /// Edited document
+    pub fn document(&self) -> &DocumentMut {
+        &self.doc
+    }
+
+    /// Profiles whose tables are edited
+    pub fn targets(&self) -> &[String] {
+        &self.targets
+    }
+
+    /// Merge `profiles` (the content of a `[profile]` table) into the manifest
+    ///
+    /// Keys are set one by one, so keys the manifest already has and
+    /// `profiles` does not mention keep their value and formatting. Missing
+    /// profile tables are appended at the end of the manifest.
+    pub fn merge_profiles(&mut self, profiles: &Table) -> Result<()> {
+        let manifest_profiles = self
+            .doc
+            .entry("profile")
+            .or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_mut()
+            .context("profile in Cargo.toml is not a table")?;
+        for (name, settings) in profiles.iter() {
+            let Some(settings) = settings.as_table() else {
+                bail!("profile.{} is not a table", name);
+            };
+            let table = manifest_profiles
+                .entry(name)
+                .or_insert_with(|| {
+                    let mut table = Table::new();
+                    table.decor_mut().set_prefix("\n");
+                    Item::Table(table)
+                })
+                .as_table_mut()
+                .with_context(|| format!("profile.{} in Cargo.toml is not a table", name))?;
+            merge_table(table, settings);
+            if !self.targets.iter().any(|target| target == name) {
+                self.targets.push(name.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether saving would change the manifest
+    pub fn is_changed(&self) -> bool {
+        self.transaction.original() != Some(self.doc.to_string().as_str())
+    }
+
+    /// Unified diff of the manifest before and after the edit; empty without changes
+    pub fn diff(&self) -> String {
+        let name = self.path().display().to_string();
+        unified_diff(&name, self.transaction.original().unwrap_or_default(), &self.doc.to_string())
+    }
+
+    /// Check that nothing but the targeted profile tables changed
+    ///
+    /// Both documents are rendered without the targeted tables and must then
+    /// be identical, byte for byte.
+    pub fn verify(&self) -> Result<()> {
+        let original = self.transaction.original().unwrap_or_default();
+        let before = without_targets(original.parse().context("Failed to parse the original manifest")?, &self.targets);
+        let after = without_targets(self.doc.clone(), &self.targets);
+        if before != after {
+            let changed = unified_diff("outside the targeted profiles", &before, &after);
+            bail!(
+                "editing {} would change more than [profile.{}]:\n{}",
+                self.path().display(),
+                self.targets.join("], [profile."),
+                changed
+            );
+        }
+        Ok(())
+    }
+
+    /// Verify the edit and write the manifest atomically
+    ///
+    /// Returns false without touching disk when nothing changed.
+    pub fn save(mut self) -> Result<bool> {
+        if !self.is_changed() {
+            return Ok(false);
+        }
+        self.verify()?;
+        let content = self.doc.to_string();
+        self.transaction.stage(content.as_str());
+        let path = self.transaction.path().to_path_buf();
+        let original = self.transaction.original().map(str::to_string);
+        let written = self.transaction.commit()?;
+        let root = path.parent().unwrap_or(Path::new(""));
+        audit::record(root, "manifest-profiles", &path, original.as_deref(), Some(&content));
+        Ok(written)
+    }
+}
+
+/// Set every key of `source` in `target`, recursing into subtables
+fn merge_table(target: &mut Table, source: &Table) {
+    for (key, item) in source.iter() {
+        match (item, target.get_mut(key)) {
+            (Item::Table(nested), Some(Item::Table(existing))) => merge_table(existing, nested),
+            (Item::Table(nested), _) => {
+                let mut table = Table::new();
+                table.set_implicit(nested.is_implicit());
+                merge_table(&mut table, nested);
+                target.insert(key, Item::Table(table));
+            }
+            (item, _) => {
+                target.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// Render `doc` with the targeted profile tables removed
+fn without_targets(mut doc: DocumentMut, targets: &[String]) -> String {
+    if let Some(profiles) = doc.get_mut("profile").and_then(Item::as_table_mut) {
+        for target in targets {
+            profiles.remove(target);
+        }
+        if profiles.is_empty() && profiles.is_implicit() {
+            doc.remove("profile");
+        }
+    }
+    doc.to_string()
+}
+
+/// Line-based unified diff with [`DIFF_CONTEXT`] lines of context
+pub fn unified_diff(name: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    // (marker, old line index, new line index)
+    let mut ops: Vec<(char, usize, usize)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', i, j));
+            i += 1;
+        } else {
+            ops.push(('+', i, j));
+            j += 1;
+        }
+    }
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|&index| ops[index].0 != ' ').collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("--- {}\n+++ {}\n", name, name);
+    let mut index = 0;
+    while index < changes.len() {
+        let start = changes[index].saturating_sub(DIFF_CONTEXT);
+        let mut end = changes[index];
+        while index < changes.len() && changes[index] <= end + 2 * DIFF_CONTEXT {
+            end = changes[index];
+            index += 1;
+        }
+        let end = (end + DIFF_CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+        let old_count = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_count = hunk.iter().filter(|op| op.0 != '-').count();
+        let (_, old_start, new_start) = hunk[0];
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_count > 0),
+            old_count,
+            new_start + usize::from(new_count > 0),
+            new_count
+        ));
+        for &(marker, old_index, new_index) in hunk {
+            let line = if marker == '+' { new[new_index] } else { old[old_index] };
+            out.push(marker);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "\
+[package]
+name    = \"demo\"     # aligned on purpose
+version = \"0.1.0\"
+
+[dependencies]
+serde = { version = \"1\", features = [ \"derive\" ] }
+
+[profile.release]
+debug = true   # keep symbols
+";
+
+    fn edit(dir: &Path) -> ProfileEdit {
+        std::fs::write(dir.join("Cargo.toml"), MANIFEST).unwrap();
+        let mut edit = ProfileEdit::open(dir.join("Cargo.toml")).unwrap();
+        let planned: DocumentMut = "[profile.release]\nlto = \"thin\"\n[profile.dev]\nincremental = true\n".parse().unwrap();
+        edit.merge_profiles(planned["profile"].as_table().unwrap()).unwrap();
+        edit
+    }
+
+    #[test]
+    fn test_merge_keeps_formatting_and_diffs() {
+        let temp = tempfile::tempdir().unwrap();
+        let edit = edit(temp.path());
+
+        assert_eq!(edit.targets(), ["release", "dev"]);
+        let diff = edit.diff();
+        assert!(diff.contains("@@ -7,3 +7,7 @@\n \n [profile.release]\n debug = true   # keep symbols\n+lto = \"thin\"\n"), "{}", diff);
+        assert!(diff.contains("+[profile.dev]\n+incremental = true\n"), "{}", diff);
+        assert!(!diff.lines().skip(2).any(|line| line.starts_with('-')), "{}", diff);
+
+        assert!(edit.save().unwrap());
+        let written = std::fs::read_to_string(temp.path().join("Cargo.toml")).unwrap();
+        assert!(written.starts_with(&MANIFEST[..MANIFEST.len() - 1]));
+    }
+
+    #[test]
+    fn test_unrelated_change_aborts_the_write() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut edit = edit(temp.path());
+        edit.doc["dependencies"]["serde"] = toml_edit::value("1");
+
+        let error = edit.save().unwrap_err().to_string();
+        assert!(error.contains("would change more than [profile.release], [profile.dev]"), "{}", error);
+        assert_eq!(std::fs::read_to_string(temp.path().join("Cargo.toml")).unwrap(), MANIFEST);
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        assert_eq!(unified_diff("a", "x\ny\n", "x\ny\n"), "");
+        assert_eq!(unified_diff("a", "x\ny\n", "x\nz\n"), "--- a\n+++ a\n@@ -1,2 +1,2 @@\n x\n-y\n+z\n");
+        assert_eq!(unified_diff("a", "", "x\n"), "--- a\n+++ a\n@@ -0,0 +1,1 @@\n+x\n");
+    }
+}
@@ -0,0 +1,728 @@
+//! Local build metrics history and README badges.
+//!
+//! Build durations, and for profiled builds their peak memory, are kept as
+//! JSON in `.cargo/cargo-optimize-metrics.json`. [`MetricsHistory::memory_pressure`]
+//! flags builds that came close to running out of memory, and optimization
+//! scores recorded with [`MetricsHistory::record_score`] show progress over time.
+//! Builds tagged with their git branch let [`MetricsHistory::branch_switch_cost`]
+//! measure what switching branches costs, and [`branch_activity`] reads from
+//! the reflog how often that happens. Profiled builds also record how many
+//! cores other workloads kept busy, and [`MetricsHistory::duration_change`]
+//! compares builds before and after a setting changed.
+//! [`render_badge`] turns the recent history into a small SVG or markdown
+//! badge (e.g. "build: 42s on 16-core") that projects can commit to their
+//! README by hand. Everything stays local; nothing is sent anywhere.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::profiler::BuildProfile;
+use crate::score::Score;
+use crate::store;
+
+/// File name of the metrics history inside `.cargo/`
+pub const METRICS_FILE_NAME: &str = "cargo-optimize-metrics.json";
+
+/// Most records kept; older ones are dropped
+pub const MAX_RECORDS: usize = 100;
+
+/// Share of the memory available to a build above which it risks being killed
+pub const MEMORY_RISK_RATIO: f64 = 0.85;
+
+/// Recent records checked for memory pressure
+const MEMORY_WINDOW: usize = 5;
+
+/// Builds needed on each side before the cost of branch switches is estimated
+const MIN_BRANCH_SAMPLES: usize = 2;
+
+/// Recent records the background load is taken from
+const LOAD_WINDOW: usize = 5;
+
+/// Builds needed on each side before a change in duration is reported
+const MIN_CHANGE_SAMPLES: usize = 2;
+
+/// One measured build
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildRecord {
+    /// When the build finished (seconds since the Unix epoch)
+    pub finished_at: u64,
+    /// Wall-clock duration in seconds
+    pub duration_secs: f64,
+    /// Cargo profile that was built (e.g. "dev")
+    pub profile: String,
+    /// Logical CPU cores of the machine
+    pub cpu_count: usize,
+    /// Highest resident memory of the build's compile and link processes, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_memory_bytes: Option<u64>,
+    /// Memory the build could use at its peak (its usage plus what was still available), in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_budget_bytes: Option<u64>,
+    /// Git branch checked out during the build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Average cores other workloads kept busy during the build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background_load: Option<f64>,
+}
+
+impl BuildRecord {
+    /// Record a build that just finished on this machine
+    pub fn new(duration_secs: f64, profile: impl Into<String>) -> Self {
+        Self {
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration_secs,
+            profile: profile.into(),
+            cpu_count: num_cpus::get(),
+            peak_memory_bytes: None,
+            memory_budget_bytes: None,
+            branch: None,
+            background_load: None,
+        }
+    }
+    
+    /// Tag the build with the git branch it was made on (see [`current_branch`])
+    pub fn with_branch(mut self, branch: Option<String>) -> Self {
+        self.branch = branch;
+        self
+    }
+    
+    /// Add the memory measured by the build's profiler
+    pub fn with_memory(mut self, profile: &BuildProfile) -> Self {
+        if profile.peak_memory_bytes > 0 {
+            self.peak_memory_bytes = Some(profile.peak_memory_bytes);
+            self.memory_budget_bytes = Some(profile.memory_budget_bytes());
+        }
+        self
+    }
+    
+    /// Add the load of other workloads measured by the build's profiler
+    pub fn with_load(mut self, profile: &BuildProfile) -> Self {
+        if profile.background_samples > 0 {
+            self.background_load = Some(profile.background_load);
+        }
+        self
+    }
+    
+    /// Share of the available memory the build used at its peak, if measured
+    pub fn memory_ratio(&self) -> Option<f64> {
+        match (self.peak_memory_bytes, self.memory_budget_bytes) {
+            (Some(peak), Some(budget)) if budget > 0 => Some(peak as f64 / budget as f64),
+            _ => None,
+        }
+    }
+}
+
+/// A build that came close to running out of memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryPressure {
+    /// Profile of the build
+    pub profile: String,
+    /// Peak memory of the build, in bytes
+    pub peak_bytes: u64,
+    /// Memory the build could use, in bytes
+    pub budget_bytes: u64,
+}
+
+impl MemoryPressure {
+    /// Share of the available memory used at the peak
+    pub fn ratio(&self) -> f64 {
+        self.peak_bytes as f64 / self.budget_bytes.max(1) as f64
+    }
+}
+
+/// How build durations changed at a point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationChange {
+    /// Builds before the change
+    pub builds_before: usize,
+    /// Builds since the change
+    pub builds_after: usize,
+    /// Median duration before the change, in seconds
+    pub median_before_secs: f64,
+    /// Median duration since the change, in seconds
+    pub median_after_secs: f64,
+}
+
+impl DurationChange {
+    /// Change of the median duration in percent; negative when builds got faster
+    pub fn percent(&self) -> f64 {
+        (self.median_after_secs / self.median_before_secs.max(f64::EPSILON) - 1.0) * 100.0
+    }
+}
+
+impl std::fmt::Display for DurationChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = self.percent();
+        write!(
+            f,
+            "builds took {:.0}% {} ({:.0}s over {} builds, {:.0}s over {} before)",
+            percent.abs(),
+            if percent <= 0.0 { "less" } else { "more" },
+            self.median_after_secs,
+            self.builds_after,
+            self.median_before_secs,
+            self.builds_before
+        )
+    }
+}
+
+/// What builds right after a branch switch cost compared to builds on the same branch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchSwitchCost {
+    /// Profile of the builds compared
+    pub profile: String,
+    /// Builds made right after switching branches
+    pub switch_builds: usize,
+    /// Builds made on the same branch as the build before
+    pub same_branch_builds: usize,
+    /// Median duration of builds after a switch, in seconds
+    pub median_after_switch_secs: f64,
+    /// Median duration of builds on the same branch, in seconds
+    pub median_same_branch_secs: f64,
+}
+
+impl BranchSwitchCost {
+    /// Seconds a switch adds to the next build
+    pub fn lost_per_switch_secs(&self) -> f64 {
+        (self.median_after_switch_secs - self.median_same_branch_secs).max(0.0)
+    }
+}
+
+/// Branch switches found in the reflog
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BranchActivity {
+    /// Days covered
+    pub days: u32,
+    /// Checkouts that changed the branch
+    pub switches: usize,
+    /// Branches checked out, switched from or to
+    pub branches: BTreeSet<String>,
+}
+
+impl BranchActivity {
+    /// Average switches per week
+    pub fn switches_per_week(&self) -> f64 {
+        self.switches as f64 * 7.0 / f64::from(self.days.max(1))
+    }
+}
+
+/// An optimization score at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreRecord {
+    /// When the score was computed (seconds since the Unix epoch)
+    pub recorded_at: u64,
+    /// Total score from 0 to 100
+    pub total: u8,
+    /// Score of each applicable category by name
+    pub categories: BTreeMap<String, u8>,
+}
+
+/// Build durations measured on this machine
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsHistory {
+    /// Records, oldest first
+    #[serde(default)]
+    pub records: Vec<BuildRecord>,
+    /// Optimization scores, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scores: Vec<ScoreRecord>,
+}
+
+impl MetricsHistory {
+    /// Path of the history file for a `.cargo` directory
+    pub fn path_in(cargo_dir: &Path) -> PathBuf {
+        cargo_dir.join(METRICS_FILE_NAME)
+    }
+
+    /// Load the history, falling back to an empty one if the file is missing or invalid
+    pub fn load(path: &Path) -> Self {
+        store::read(path).value
+    }
+
+    /// Replace the saved history atomically
+    ///
+    /// Overwrites records other processes added since this history was
+    /// loaded; prefer [`MetricsHistory::update`].
+    pub fn save(&self, path: &Path) -> Result<()> {
+        store::write(path, self)
+    }
+
+    /// Change the saved history under its lock, writing only if `change` modified it
+    ///
+    /// Safe to call from build scripts running in parallel (see [`crate::store`]).
+    pub fn update<R>(path: &Path, change: impl FnOnce(&mut MetricsHistory) -> R) -> Result<R> {
+        store::update(path, change)
+    }
+
+    /// Add a record, dropping the oldest beyond [`MAX_RECORDS`]
+    pub fn record(&mut self, record: BuildRecord) {
+        self.records.push(record);
+        let excess = self.records.len().saturating_sub(MAX_RECORDS);
+        self.records.drain(..excess);
+    }
+
+    /// Add an optimization score, dropping the oldest beyond [`MAX_RECORDS`]
+    ///
+    /// A score equal to the latest one is not recorded again.
+    pub fn record_score(&mut self, score: &Score) {
+        let categories: BTreeMap<String, u8> = score
+            .categories
+            .iter()
+            .map(|category| (category.category.name().to_string(), category.score))
+            .collect();
+        if self.scores.last().is_some_and(|last| last.total == score.total && last.categories == categories) {
+            return;
+        }
+        self.scores.push(ScoreRecord {
+            recorded_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            total: score.total,
+            categories,
+        });
+        let excess = self.scores.len().saturating_sub(MAX_RECORDS);
+        self.scores.drain(..excess);
+    }
+    
+    /// Change of the total score between the first and the latest recorded score
+    pub fn score_trend(&self) -> Option<i16> {
+        let (first, last) = (self.scores.first()?, self.scores.last()?);
+        Some(i16::from(last.total) - i16::from(first.total))
+    }
+    
+    /// Most recent records for `profile` (any profile when `None`), newest first
+    pub fn recent<'a>(&'a self, profile: Option<&'a str>, count: usize) -> impl Iterator<Item = &'a BuildRecord> + 'a {
+        self.records
+            .iter()
+            .rev()
+            .filter(move |record| profile.is_none_or(|profile| record.profile == profile))
+            .take(count)
+    }
+    
+    /// Cost of branch switches for the profile where they cost the most
+    ///
+    /// Compares builds whose branch differs from that of the previous build
+    /// of the same profile with builds on the same branch. Needs at least
+    /// [`MIN_BRANCH_SAMPLES`] of each; builds without a branch are ignored.
+    pub fn branch_switch_cost(&self) -> Option<BranchSwitchCost> {
+        let mut samples: BTreeMap<&str, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+        let mut last_branch: BTreeMap<&str, &str> = BTreeMap::new();
+        for record in &self.records {
+            let Some(branch) = record.branch.as_deref() else { continue };
+            if let Some(previous) = last_branch.insert(&record.profile, branch) {
+                let (switched, same) = samples.entry(&record.profile).or_default();
+                if previous == branch { same } else { switched }.push(record.duration_secs);
+            }
+        }
+        samples
+            .into_iter()
+            .filter(|(_, (switched, same))| switched.len() >= MIN_BRANCH_SAMPLES && same.len() >= MIN_BRANCH_SAMPLES)
+            .map(|(profile, (switched, same))| BranchSwitchCost {
+                profile: profile.to_string(),
+                switch_builds: switched.len(),
+                same_branch_builds: same.len(),
+                median_after_switch_secs: median(switched),
+                median_same_branch_secs: median(same),
+            })
+            .max_by(|a, b| a.lost_per_switch_secs().total_cmp(&b.lost_per_switch_secs()))
+    }
+    
+    /// Background load of the recent measured builds finished at or after `since`, newest first
+    pub fn background_loads(&self, since: u64) -> Vec<f64> {
+        self.records
+            .iter()
+            .rev()
+            .filter(|record| record.finished_at >= since)
+            .filter_map(|record| record.background_load)
+            .take(LOAD_WINDOW)
+            .collect()
+    }
+    
+    /// Durations of `profile` builds since `at` compared with those between `from` and `at`
+    ///
+    /// Times are seconds since the Unix epoch. Needs at least
+    /// [`MIN_CHANGE_SAMPLES`] builds on each side.
+    pub fn duration_change(&self, profile: &str, from: u64, at: u64) -> Option<DurationChange> {
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        for record in self.records.iter().filter(|record| record.profile == profile && record.finished_at >= from) {
+            if record.finished_at < at { &mut before } else { &mut after }.push(record.duration_secs);
+        }
+        if before.len() < MIN_CHANGE_SAMPLES || after.len() < MIN_CHANGE_SAMPLES {
+            return None;
+        }
+        Some(DurationChange {
+            builds_before: before.len(),
+            builds_after: after.len(),
+            median_before_secs: median(before),
+            median_after_secs: median(after),
+        })
+    }
+    
+    /// The recent build closest to running out of memory, if any used more than [`MEMORY_RISK_RATIO`]
+    ///
+    /// Only measured builds on a machine with `cpu_count` cores finished at or
+    /// after `since` (seconds since the Unix epoch) count.
+    pub fn memory_pressure(&self, cpu_count: usize, since: u64) -> Option<MemoryPressure> {
+        self.records
+            .iter()
+            .rev()
+            .filter(|record| record.cpu_count == cpu_count && record.finished_at >= since)
+            .filter(|record| record.memory_ratio().is_some())
+            .take(MEMORY_WINDOW)
+            .filter(|record| record.memory_ratio().is_some_and(|ratio| ratio > MEMORY_RISK_RATIO))
+            .max_by(|a, b| a.memory_ratio().partial_cmp(&b.memory_ratio()).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|record| MemoryPressure {
+                profile: record.profile.clone(),
+                peak_bytes: record.peak_memory_bytes.unwrap_or_default(),
+                budget_bytes: record.memory_budget_bytes.unwrap_or_default(),
+            })
+    }
+}
+
+pub(crate) fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    match values.len() {
+        0 => 0.0,
+        len if len % 2 == 0 => (values[middle - 1] + values[middle]) / 2.0,
+        _ => values[middle],
+    }
+}
+
+/// Branch checked out in the repository at `root`, `None` when detached or not a git repository
+pub fn current_branch(root: &Path) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["symbolic-ref", "--short", "-q", "HEAD"])
+        .output()
+        .ok()?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !branch.is_empty()).then_some(branch)
+}
+
+/// Branch switches in the last `days` days of the reflog of the repository at `root`
+pub fn branch_activity(root: &Path, days: u32) -> Option<BranchActivity> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["reflog", "--date=unix", "--format=%gd%x09%gs"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let since = now.saturating_sub(u64::from(days) * 24 * 60 * 60);
+    Some(activity_from_reflog(&String::from_utf8_lossy(&output.stdout), since, days))
+}
+
+/// Parse `git reflog --date=unix --format=%gd%x09%gs` output, keeping entries at or after `since`
+pub fn activity_from_reflog(reflog: &str, since: u64, days: u32) -> BranchActivity {
+    let mut activity = BranchActivity { days, ..BranchActivity::default() };
+    for line in reflog.lines() {
+        let Some((selector, subject)) = line.split_once('\t') else { continue };
+        let time = selector
+            .split_once("@{")
+            .and_then(|(_, rest)| rest.strip_suffix('}'))
+            .and_then(|time| time.parse::<u64>().ok());
+        if time.is_none_or(|time| time < since) {
+            continue;
+        }
+        let Some((from, to)) = subject.strip_prefix("checkout: moving from ").and_then(|rest| rest.split_once(" to ")) else {
+            continue;
+        };
+        if from != to {
+            activity.switches += 1;
+            activity.branches.insert(from.to_string());
+            activity.branches.insert(to.to_string());
+        }
+    }
+    activity
+}
+
+/// Output format of a badge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BadgeFormat {
+    /// Standalone SVG image
+    #[default]
+    Svg,
+    /// Markdown image referencing [`BadgeOptions::image_path`] with the text as alt text
+    Markdown,
+}
+
+/// How a badge is rendered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadgeOptions {
+    /// Left-hand label (default "build")
+    pub label: String,
+    /// Only use builds of this profile
+    pub profile: Option<String>,
+    /// Number of recent builds whose median is shown
+    pub window: usize,
+    /// Append the core count ("on 16-core")
+    pub show_cores: bool,
+    /// Color of the right-hand side; picked from the duration when `None`
+    pub color: Option<String>,
+    /// Output format
+    pub format: BadgeFormat,
+    /// SVG file the markdown badge links to
+    pub image_path: String,
+}
+
+impl Default for BadgeOptions {
+    fn default() -> Self {
+        Self {
+            label: "build".to_string(),
+            profile: None,
+            window: 5,
+            show_cores: true,
+            color: None,
+            format: BadgeFormat::Svg,
+            image_path: "build-badge.svg".to_string(),
+        }
+    }
+}
+
+/// Render a badge from the recent builds in `history`
+///
+/// Returns `None` when there are no matching builds.
+pub fn render_badge(history: &MetricsHistory, options: &BadgeOptions) -> Option<String> {
+    let recent: Vec<&BuildRecord> = history.recent(options.profile.as_deref(), options.window.max(1)).collect();
+    let mut durations: Vec<f64> = recent.iter().map(|record| record.duration_secs).collect();
+    durations.sort_by(f64::total_cmp);
+    let median = *durations.get(durations.len() / 2)?;
+
+    let mut message = format_duration(median);
+    if options.show_cores {
+        let _ = write!(message, " on {}-core", recent[0].cpu_count);
+    }
+    let color = options.color.clone().unwrap_or_else(|| duration_color(median).to_string());
+
+    Some(match options.format {
+        BadgeFormat::Svg => badge_svg(&options.label, &message, &color),
+        BadgeFormat::Markdown => format!("![{}: {}]({})", options.label, message, options.image_path),
+    })
+}
+
+/// Short human-readable duration ("42s", "3m 05s")
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+fn duration_color(secs: f64) -> &'static str {
+    match secs {
+        s if s < 30.0 => "#4c1",
+        s if s < 120.0 => "#97ca00",
+        s if s < 300.0 => "#dfb317",
+        _ => "#fe7d37",
+    }
+}
+
+/// Flat two-part badge; text widths are estimated since no font is available
+fn badge_svg(label: &str, message: &str, color: &str) -> String {
+    let width = |text: &str| text.chars().count() as u32 * 7 + 10;
+    let (left, right) = (width(label), width(message));
+    let total = left + right;
+    let (label, message, color) = (xml_escape(label), xml_escape(message), xml_escape(color));
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{total}\" height=\"20\" role=\"img\" aria-label=\"{label}: {message}\">\n\
+         <title>{label}: {message}</title>\n\
+         <rect width=\"{left}\" height=\"20\" fill=\"#555\"/>\n\
+         <rect x=\"{left}\" width=\"{right}\" height=\"20\" fill=\"{color}\"/>\n\
+         <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\n\
+         <text x=\"{label_x}\" y=\"14\">{label}</text>\n\
+         <text x=\"{message_x}\" y=\"14\">{message}</text>\n\
+         </g>\n\
+         </svg>\n",
+        label_x = left / 2,
+        message_x = left + right / 2,
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(duration_secs: f64, profile: &str) -> BuildRecord {
+        BuildRecord {
+            finished_at: 0,
+            duration_secs,
+            profile: profile.to_string(),
+            cpu_count: 16,
+            peak_memory_bytes: None,
+            memory_budget_bytes: None,
+            branch: None,
+            background_load: None,
+        }
+    }
+
+    #[test]
+    fn test_history_roundtrip_and_cap() {
+        let temp = TempDir::new().unwrap();
+        let path = MetricsHistory::path_in(temp.path());
+        assert_eq!(MetricsHistory::load(&path), MetricsHistory::default());
+
+        let mut history = MetricsHistory::default();
+        for i in 0..MAX_RECORDS + 5 {
+            history.record(record(i as f64, "dev"));
+        }
+        assert_eq!(history.records.len(), MAX_RECORDS);
+        assert_eq!(history.records[0].duration_secs, 5.0);
+        history.save(&path).unwrap();
+        assert_eq!(MetricsHistory::load(&path), history);
+    }
+
+    #[test]
+    fn test_memory_pressure() {
+        let measured = |finished_at: u64, profile: &str, peak_gib: u64, budget_gib: u64| BuildRecord {
+            finished_at,
+            peak_memory_bytes: Some(peak_gib << 30),
+            memory_budget_bytes: Some(budget_gib << 30),
+            ..record(60.0, profile)
+        };
+        let mut history = MetricsHistory::default();
+        history.record(record(60.0, "dev"));
+        history.record(measured(10, "dev", 6, 16));
+        assert_eq!(history.memory_pressure(16, 0), None);
+
+        history.record(measured(20, "release", 15, 16));
+        history.record(measured(30, "dev", 14, 16));
+        let pressure = history.memory_pressure(16, 0).unwrap();
+        assert_eq!(pressure.profile, "release");
+        assert!((pressure.ratio() - 15.0 / 16.0).abs() < 1e-9);
+
+        // Other machines and builds before `since` do not count
+        assert_eq!(history.memory_pressure(8, 0), None);
+        assert_eq!(history.memory_pressure(16, 30).unwrap().profile, "dev");
+        assert_eq!(history.memory_pressure(16, 31), None);
+    }
+
+    #[test]
+    fn test_branch_switch_cost() {
+        let on = |branch: &str, secs: f64, profile: &str| record(secs, profile).with_branch(Some(branch.to_string()));
+        let mut history = MetricsHistory::default();
+        history.record(on("main", 300.0, "dev"));
+        history.record(on("main", 20.0, "dev"));
+        history.record(on("feature", 180.0, "dev"));
+        history.record(record(999.0, "dev"));
+        history.record(on("feature", 25.0, "dev"));
+        assert_eq!(history.branch_switch_cost(), None);
+
+        history.record(on("main", 200.0, "dev"));
+        history.record(on("main", 15.0, "dev"));
+        history.record(on("main", 90.0, "release"));
+        let cost = history.branch_switch_cost().unwrap();
+        assert_eq!((cost.profile.as_str(), cost.switch_builds, cost.same_branch_builds), ("dev", 2, 3));
+        assert_eq!(cost.lost_per_switch_secs(), 190.0 - 20.0);
+
+        let now = 1_700_000_000;
+        let reflog = format!(
+            "HEAD@{{{}}}\tcheckout: moving from feature to main\n\
+             HEAD@{{{}}}\tcommit: wip\n\
+             HEAD@{{{}}}\tcheckout: moving from main to feature\n\
+             HEAD@{{{}}}\tcheckout: moving from old to main\n",
+            now,
+            now - 60,
+            now - 3600,
+            now - 30 * 86400
+        );
+        let activity = activity_from_reflog(&reflog, now - 14 * 86400, 14);
+        assert_eq!(activity.switches, 2);
+        assert_eq!(activity.branches.iter().collect::<Vec<_>>(), ["feature", "main"]);
+        assert_eq!(activity.switches_per_week(), 1.0);
+    }
+
+    #[test]
+    fn test_duration_change_and_background_load() {
+        let at = |finished_at: u64, secs: f64, load: Option<f64>| BuildRecord {
+            finished_at,
+            background_load: load,
+            ..record(secs, "dev")
+        };
+        let mut history = MetricsHistory::default();
+        for (finished_at, secs, load) in [(5, 90.0, None), (10, 80.0, Some(6.0)), (20, 100.0, Some(8.0)), (30, 60.0, Some(1.0))] {
+            history.record(at(finished_at, secs, load));
+        }
+        assert_eq!(history.duration_change("dev", 0, 30), None);
+        history.record(at(40, 40.0, None));
+        history.record(record(10.0, "release"));
+
+        let change = history.duration_change("dev", 10, 30).unwrap();
+        assert_eq!((change.builds_before, change.builds_after), (2, 2));
+        assert_eq!((change.median_before_secs, change.median_after_secs), (90.0, 50.0));
+        assert_eq!(change.to_string(), "builds took 44% less (50s over 2 builds, 90s over 2 before)");
+        assert_eq!(history.background_loads(15), [1.0, 8.0]);
+    }
+
+    #[test]
+    fn test_score_history() {
+        use crate::score::{compute, ScoreInput};
+        use toml_edit::DocumentMut;
+
+        let empty = DocumentMut::new();
+        let tuned: DocumentMut = "[build]\nrustc-wrapper = \"sccache\"\n".parse().unwrap();
+        let mut history = MetricsHistory::default();
+        assert_eq!(history.score_trend(), None);
+        history.record_score(&compute(&ScoreInput { config: Some(&empty), ..ScoreInput::default() }));
+        history.record_score(&compute(&ScoreInput { config: Some(&empty), ..ScoreInput::default() }));
+        assert_eq!(history.scores.len(), 1);
+        history.record_score(&compute(&ScoreInput { config: Some(&tuned), ..ScoreInput::default() }));
+        assert_eq!(history.scores[1].categories["caching"], 100);
+        assert!(history.score_trend().unwrap() > 0);
+
+        let json = serde_json::to_string(&history).unwrap();
+        assert_eq!(serde_json::from_str::<MetricsHistory>(&json).unwrap(), history);
+    }
+
+    #[test]
+    fn test_render_badge() {
+        let mut history = MetricsHistory::default();
+        assert_eq!(render_badge(&history, &BadgeOptions::default()), None);
+
+        for secs in [100.0, 40.0, 44.0, 42.0, 400.0] {
+            history.record(record(secs, "dev"));
+        }
+        history.record(record(900.0, "release"));
+
+        let options = BadgeOptions {
+            profile: Some("dev".to_string()),
+            format: BadgeFormat::Markdown,
+            ..BadgeOptions::default()
+        };
+        assert_eq!(
+            render_badge(&history, &options).unwrap(),
+            "![build: 44s on 16-core](build-badge.svg)"
+        );
+
+        let options = BadgeOptions {
+            label: "dev <build>".to_string(),
+            window: 1,
+            show_cores: false,
+            ..BadgeOptions::default()
+        };
+        let svg = render_badge(&history, &options).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("dev &lt;build&gt;: 15m 00s"));
+        assert!(svg.contains("#fe7d37"));
+    }
+}
@@ -0,0 +1,192 @@
+//! What upgrading cargo-optimize would change in a project's config.
+//!
+//! cargo-optimize is usually a build-dependency, so a version bump changes
+//! the written config the next time CI builds. [`preview`] compares the
+//! current `.cargo/config.toml` with what this version plans for it and
+//! lists every setting apply would add, change or remove, marking the ones
+//! an earlier version wrote (from the uninstall journal) apart from new ones.
+//! Run it after bumping the dependency, before the change reaches CI.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use toml_edit::DocumentMut;
+
+use crate::journal::ApplyJournal;
+
+/// How a setting would change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    /// Not set yet, would be written
+    Added,
+    /// Set, would get another value
+    Changed,
+    /// Set, would be removed
+    Removed,
+}
+
+/// One setting apply would change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingChange {
+    /// Key path (e.g. `["profile", "dev", "incremental"]`)
+    pub key: Vec<String>,
+    /// What would happen to it
+    pub kind: ChangeKind,
+    /// Current value (TOML syntax)
+    pub current: Option<String>,
+    /// Value after the next apply (TOML syntax)
+    pub upcoming: Option<String>,
+    /// Whether the current value is the one an earlier apply wrote
+    pub previously_written: bool,
+}
+
+impl SettingChange {
+    /// Dotted key path for display
+    pub fn dotted_key(&self) -> String {
+        self.key.join(".")
+    }
+}
+
+/// Settings the next apply would change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpgradePreview {
+    /// Version that last applied, if recorded
+    pub applied_version: Option<String>,
+    /// Version computing the preview (this crate)
+    pub version: String,
+    /// Changes in config order
+    pub changes: Vec<SettingChange>,
+}
+
+impl UpgradePreview {
+    /// Whether the next apply would leave the config as it is
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Changes to settings an earlier version wrote: the behavior changes of the upgrade
+    pub fn behavior_changes(&self) -> impl Iterator<Item = &SettingChange> {
+        self.changes.iter().filter(|change| change.previously_written)
+    }
+
+    /// Human-readable listing, one change per line
+    pub fn render(&self) -> String {
+        let mut out = match &self.applied_version {
+            Some(applied) if *applied != self.version => {
+                format!("cargo-optimize {} (config last applied by {}):\n", self.version, applied)
+            }
+            _ => format!("cargo-optimize {}:\n", self.version),
+        };
+        if self.changes.is_empty() {
+            out.push_str("  no changes\n");
+            return out;
+        }
+        for change in &self.changes {
+            let origin = if change.previously_written { "" } else { " (new)" };
+            let _ = match change.kind {
+                ChangeKind::Added => writeln!(
+                    out,
+                    "  + {} = {}{}",
+                    change.dotted_key(),
+                    change.upcoming.as_deref().unwrap_or_default(),
+                    origin
+                ),
+                ChangeKind::Changed => writeln!(
+                    out,
+                    "  ~ {}: {} -> {}{}",
+                    change.dotted_key(),
+                    change.current.as_deref().unwrap_or_default(),
+                    change.upcoming.as_deref().unwrap_or_default(),
+                    origin
+                ),
+                ChangeKind::Removed => writeln!(
+                    out,
+                    "  - {} = {}{}",
+                    change.dotted_key(),
+                    change.current.as_deref().unwrap_or_default(),
+                    origin
+                ),
+            };
+        }
+        out
+    }
+}
+
+/// Compare the current config with the planned one
+///
+/// `journal` is the uninstall journal of earlier applies: a key counts as
+/// previously written when its current value is the one recorded there.
+/// Settings that depend on the machine (jobs, memory) show up as well when
+/// the hardware changed since the last apply.
+pub fn preview(
+    current: Option<&str>,
+    planned: &DocumentMut,
+    journal: Option<&ApplyJournal>,
+    applied_version: Option<String>,
+) -> Result<UpgradePreview> {
+    let diff = ApplyJournal::record(current, &planned.to_string())?;
+    let changes = diff
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let kind = match (&entry.previous, &entry.written) {
+                (None, _) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(_), Some(_)) => ChangeKind::Changed,
+            };
+            let previously_written = entry.previous.is_some()
+                && journal.is_some_and(|journal| {
+                    journal.entries.iter().any(|e| e.key == entry.key && e.written == entry.previous)
+                });
+            SettingChange {
+                key: entry.key,
+                kind,
+                current: entry.previous,
+                upcoming: entry.written,
+                previously_written,
+            }
+        })
+        .collect();
+    Ok(UpgradePreview {
+        applied_version,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_marks_previously_written_settings() {
+        let current = "[build]\njobs = 4\n\n[profile.dev]\nincremental = true\ndebug = 1\n";
+        let journal = ApplyJournal::record(None, current).unwrap();
+        // The user raised debug by hand; the new version changes jobs and adds split-debuginfo
+        let current = current.replace("debug = 1", "debug = 2");
+        let planned: DocumentMut =
+            "[build]\njobs = 8\n\n[profile.dev]\nincremental = true\nsplit-debuginfo = \"unpacked\"\n".parse().unwrap();
+
+        let preview = preview(Some(&current), &planned, Some(&journal), Some("0.0.9".to_string())).unwrap();
+
+        let summary: Vec<(String, ChangeKind, bool)> = preview
+            .changes
+            .iter()
+            .map(|change| (change.dotted_key(), change.kind, change.previously_written))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("build.jobs".to_string(), ChangeKind::Changed, true),
+                ("profile.dev.split-debuginfo".to_string(), ChangeKind::Added, false),
+                ("profile.dev.debug".to_string(), ChangeKind::Removed, false),
+            ]
+        );
+        assert_eq!(preview.behavior_changes().count(), 1);
+        let rendered = preview.render();
+        assert!(rendered.contains("(config last applied by 0.0.9)"), "{}", rendered);
+        assert!(rendered.contains("  ~ build.jobs: 4 -> 8\n"), "{}", rendered);
+        assert!(rendered.contains("  + profile.dev.split-debuginfo = \"unpacked\" (new)\n"), "{}", rendered);
+    }
+}
@@ -0,0 +1,245 @@
+//! cargo-nextest integration.
+//!
+//! When `cargo nextest` is installed, apply writes a managed
+//! `.config/nextest.toml` whose parallelism and retry settings follow the
+//! detected hardware and build context: developer machines fail fast
+//! without retries, CI gets a `ci` profile that retries flaky tests and
+//! terminates hung ones. Other nextest settings in the file are preserved.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
+
+use crate::config::BuildContext;
+use crate::explain;
+use crate::hardware::HardwareInfo;
+
+/// Location of the nextest config relative to the workspace root
+pub const NEXTEST_CONFIG: &str = ".config/nextest.toml";
+
+/// Explanation id written next to managed nextest settings
+pub const NEXTEST_ID: &str = "CO-NEXTEST-001";
+
+/// Memory a test thread is budgeted, so memory-hungry suites do not swap
+const MEMORY_PER_THREAD: u64 = 512 * 1024 * 1024;
+
+/// Upper bound of the speedup estimate, in line with nextest's published benchmarks
+const MAX_SPEEDUP: f64 = 3.0;
+
+/// Whether `cargo nextest` can be run
+pub fn is_installed() -> bool {
+    Command::new("cargo")
+        .args(["nextest", "--version"])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Path of the nextest config of the workspace at `root`
+pub fn config_path(root: &Path) -> PathBuf {
+    root.join(NEXTEST_CONFIG)
+}
+
+/// Managed settings of one nextest profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextestSettings {
+    /// Profile the settings are written to ("default" or "ci")
+    pub profile: String,
+    /// Tests run at once
+    pub test_threads: usize,
+    /// Times a failing test is retried
+    pub retries: u32,
+    /// Stop at the first failure
+    pub fail_fast: bool,
+    /// Seconds after which a test is reported as slow
+    pub slow_timeout_secs: u64,
+    /// Slow periods after which a test is terminated; never when `None`
+    pub terminate_after: Option<u32>,
+}
+
+impl NextestSettings {
+    /// Settings for the given hardware and context
+    ///
+    /// Threads follow the logical cores, capped so each gets 512 MiB of the
+    /// available memory.
+    pub fn for_environment(hardware: &HardwareInfo, context: BuildContext) -> Self {
+        let by_memory = (hardware.available_memory / MEMORY_PER_THREAD).max(1) as usize;
+        let test_threads = hardware.cpu_count.max(1).min(by_memory);
+        match context {
+            BuildContext::Local => NextestSettings {
+                profile: "default".to_string(),
+                test_threads,
+                retries: 0,
+                fail_fast: true,
+                slow_timeout_secs: 60,
+                terminate_after: None,
+            },
+            BuildContext::Ci => NextestSettings {
+                profile: "ci".to_string(),
+                test_threads,
+                retries: 2,
+                fail_fast: false,
+                slow_timeout_secs: 120,
+                terminate_after: Some(3),
+            },
+        }
+    }
+
+    /// Command running the tests with these settings
+    pub fn command(&self) -> String {
+        if self.profile == "default" {
+            "cargo nextest run".to_string()
+        } else {
+            format!("cargo nextest run --profile {}", self.profile)
+        }
+    }
+
+    /// Write the settings into a nextest config, keeping everything else
+    pub fn write_to(&self, doc: &mut DocumentMut) -> Result<()> {
+        let profiles = doc.entry("profile").or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        let profiles = profiles.as_table_like_mut().context("[profile] in nextest.toml is not a table")?;
+        let profile = profiles
+            .entry(&self.profile)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+            .with_context(|| format!("[profile.{}] in nextest.toml is not a table", self.profile))?;
+
+        let slow_timeout: toml_edit::Value = match self.terminate_after {
+            Some(terminate_after) => {
+                let mut table = InlineTable::new();
+                table.insert("period", format!("{}s", self.slow_timeout_secs).into());
+                table.insert("terminate-after", i64::from(terminate_after).into());
+                table.into()
+            }
+            None => format!("{}s", self.slow_timeout_secs).into(),
+        };
+        profile.insert("test-threads", managed(self.test_threads as i64));
+        profile.insert("retries", managed(i64::from(self.retries)));
+        profile.insert("fail-fast", managed(self.fail_fast));
+        profile.insert("slow-timeout", managed(slow_timeout));
+        Ok(())
+    }
+}
+
+/// Render the nextest config, starting from `original` when the file exists
+pub fn render(original: Option<&str>, settings: &NextestSettings) -> Result<String> {
+    let mut doc = match original {
+        Some(content) => content.parse::<DocumentMut>().context("Failed to parse nextest.toml")?,
+        None => {
+            let mut doc = DocumentMut::new();
+            doc.decor_mut()
+                .set_prefix("# Test runner settings managed by cargo-optimize; values marked with\n# `cargo-optimize:` are rewritten on apply.\n\n");
+            doc
+        }
+    };
+    settings.write_to(&mut doc)?;
+    Ok(doc.to_string())
+}
+
+/// Expected speedup of the test phase over `cargo test`
+///
+/// `cargo test` runs test binaries one after another and waits for the
+/// slowest test of each before starting the next; nextest schedules every
+/// test on its own, so the gain grows with the number of test binaries.
+/// The estimate adds 20% per binary beyond the first, bounded by the thread
+/// count and the 3x nextest reports for large workspaces.
+pub fn expected_speedup(test_binaries: usize, test_threads: usize) -> f64 {
+    if test_binaries <= 1 || test_threads <= 1 {
+        return 1.0;
+    }
+    (1.0 + 0.2 * (test_binaries - 1) as f64)
+        .min(test_threads as f64)
+        .min(MAX_SPEEDUP)
+}
+
+/// Test binaries `cargo test` builds for the package at `root`
+///
+/// Counts the unit tests of `src/` and every integration test in `tests/`.
+pub fn count_test_binaries(root: &Path) -> usize {
+    let unit = usize::from(root.join("src").is_dir());
+    let integration = std::fs::read_dir(root.join("tests"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    let path = entry.path();
+                    path.extension().is_some_and(|ext| ext == "rs") || path.join("main.rs").is_file()
+                })
+                .count()
+        })
+        .unwrap_or(0);
+    unit + integration
+}
+
+fn managed(value: impl Into<toml_edit::Value>) -> Item {
+    let mut value = value.into();
+    if let Some(explanation) = explain::find(NEXTEST_ID) {
+        value.decor_mut().set_suffix(explanation.comment());
+    }
+    Item::Value(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hardware(cpu_count: usize, available_gib: u64) -> HardwareInfo {
+        HardwareInfo {
+            cpu_count,
+            physical_cpu_count: cpu_count / 2,
+            total_memory: available_gib << 31,
+            available_memory: available_gib << 30,
+            disks: Vec::new(),
+            os_name: "Linux".to_string(),
+            os_version: "6.0".to_string(),
+            arch: "x86_64".to_string(),
+            topology: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_settings_follow_hardware_and_context() {
+        let local = NextestSettings::for_environment(&hardware(16, 32), BuildContext::Local);
+        assert_eq!(local.test_threads, 16);
+        assert_eq!((local.retries, local.fail_fast), (0, true));
+        assert_eq!(local.command(), "cargo nextest run");
+
+        let ci = NextestSettings::for_environment(&hardware(16, 2), BuildContext::Ci);
+        assert_eq!(ci.test_threads, 4);
+        assert_eq!((ci.retries, ci.fail_fast, ci.terminate_after), (2, false, Some(3)));
+        assert_eq!(ci.command(), "cargo nextest run --profile ci");
+
+        assert_eq!(NextestSettings::for_environment(&hardware(4, 0), BuildContext::Local).test_threads, 1);
+    }
+
+    #[test]
+    fn test_render_preserves_user_settings() {
+        let original = "[profile.default]\nstatus-level = \"all\"\n\n[test-groups]\nserial = { max-threads = 1 }\n";
+        let settings = NextestSettings::for_environment(&hardware(8, 16), BuildContext::Ci);
+        let rendered = render(Some(original), &settings).unwrap();
+        let doc: DocumentMut = rendered.parse().unwrap();
+        assert_eq!(doc["profile"]["default"]["status-level"].as_str(), Some("all"));
+        assert_eq!(doc["test-groups"]["serial"]["max-threads"].as_integer(), Some(1));
+        assert_eq!(doc["profile"]["ci"]["test-threads"].as_integer(), Some(8));
+        assert_eq!(doc["profile"]["ci"]["slow-timeout"]["period"].as_str(), Some("120s"));
+        assert!(rendered.contains(NEXTEST_ID));
+
+        let fresh = render(None, &NextestSettings::for_environment(&hardware(8, 16), BuildContext::Local)).unwrap();
+        assert!(fresh.starts_with("# Test runner settings"));
+        assert!(fresh.contains("slow-timeout = \"60s\""));
+    }
+
+    #[test]
+    fn test_expected_speedup() {
+        assert_eq!(expected_speedup(1, 16), 1.0);
+        assert_eq!(expected_speedup(6, 1), 1.0);
+        assert!((expected_speedup(6, 16) - 2.0).abs() < 1e-9);
+        assert_eq!(expected_speedup(50, 16), MAX_SPEEDUP);
+        assert_eq!(expected_speedup(50, 2), 2.0);
+    }
+}
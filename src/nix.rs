@@ -0,0 +1,376 @@
+//! Nix and devenv environments.
+//!
+//! In a Nix dev shell (`nix develop`, `nix-shell`, devenv or direnv's `use
+//! flake`), the toolchain, linkers and wrappers come from the Nix store and
+//! are on `PATH` only inside the shell. Their store paths change with every
+//! nixpkgs update and disappear on garbage collection, so the config refers
+//! to tools by command name, and nothing is installed outside the store.
+//! [`NixEnvironment::detect`] recognizes such a shell, and [`to_nix_snippet`]
+//! turns the planned config into the environment variables and packages of
+//! a `devenv.nix` or a flake's `mkShell`, for projects that keep their
+//! build setup in Nix instead of `.cargo/config.toml`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Value};
+
+use crate::journal::ApplyJournal;
+
+/// Directory of the Nix store
+pub const STORE_DIR: &str = "/nix/store";
+
+/// Nixpkgs attribute providing each command the config refers to
+const PACKAGES: &[(&str, &str)] = &[
+    ("clang", "clang"),
+    ("gcc", "gcc"),
+    ("mold", "mold"),
+    ("wild", "wild"),
+    ("lld", "lld"),
+    ("ld.lld", "lld"),
+    ("gold", "binutils"),
+    ("sccache", "sccache"),
+];
+
+/// Kind of Nix shell, from `IN_NIX_SHELL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NixShell {
+    /// Only the shell's packages are on `PATH`
+    Pure,
+    /// The shell's packages come before the rest of `PATH`
+    Impure,
+}
+
+/// A Nix environment cargo-optimize runs in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NixEnvironment {
+    /// Kind of `nix develop`/`nix-shell` shell, if in one
+    #[serde(default)]
+    pub shell: Option<NixShell>,
+    /// Whether the shell is a devenv shell (`DEVENV_ROOT` is set)
+    #[serde(default)]
+    pub devenv: bool,
+    /// `rustc` on `PATH`, when it lives in the Nix store
+    #[serde(default)]
+    pub store_toolchain: Option<PathBuf>,
+}
+
+impl NixEnvironment {
+    /// Detect a Nix environment from the process environment and the `rustc` on `PATH`
+    pub fn detect() -> Option<Self> {
+        Self::from_lookup(|var| std::env::var(var).ok(), which::which("rustc").ok())
+    }
+
+    /// Detect a Nix environment from variables looked up with `lookup` and the path of `rustc`
+    ///
+    /// `None` when neither a Nix shell nor a store toolchain is found.
+    pub fn from_lookup<F>(lookup: F, rustc: Option<PathBuf>) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let shell = lookup("IN_NIX_SHELL").and_then(|value| match value.trim() {
+            "pure" => Some(NixShell::Pure),
+            "" => None,
+            _ => Some(NixShell::Impure),
+        });
+        let devenv = lookup("DEVENV_ROOT").is_some_and(|root| !root.trim().is_empty());
+        let store_toolchain = rustc.filter(|path| is_store_path(path));
+        (shell.is_some() || devenv || store_toolchain.is_some()).then_some(NixEnvironment {
+            shell,
+            devenv,
+            store_toolchain,
+        })
+    }
+
+    /// Snippet format for the project at `project_root`: devenv when it uses devenv, a flake otherwise
+    pub fn format_for(&self, project_root: &Path) -> NixFormat {
+        if self.devenv || project_root.join("devenv.nix").is_file() {
+            NixFormat::Devenv
+        } else {
+            NixFormat::Flake
+        }
+    }
+
+    /// One-line description for reports
+    pub fn describe(&self) -> String {
+        let shell = match (self.devenv, self.shell) {
+            (true, _) => "a devenv shell".to_string(),
+            (false, Some(NixShell::Pure)) => "a pure Nix shell".to_string(),
+            (false, Some(NixShell::Impure)) => "a Nix shell".to_string(),
+            (false, None) => "a Nix environment".to_string(),
+        };
+        match &self.store_toolchain {
+            Some(rustc) => format!("{} (rustc from {})", shell, rustc.display()),
+            None => shell,
+        }
+    }
+}
+
+/// Whether `path` is in the Nix store
+pub fn is_store_path(path: &Path) -> bool {
+    path.starts_with(STORE_DIR)
+}
+
+/// `value` with every Nix store path replaced by the command it names
+///
+/// `/nix/store/<hash>-mold-2.34/bin/mold` becomes `mold`, also inside a
+/// link argument such as `-fuse-ld=/nix/store/.../bin/mold`. `None` when the
+/// value has no store path.
+pub fn unpin_store_paths(value: &str) -> Option<String> {
+    let start = value.find(STORE_DIR)?;
+    let end = value[start..]
+        .find(|c: char| c.is_whitespace() || c == '"' || c == '\'')
+        .map_or(value.len(), |end| start + end);
+    let command = Path::new(&value[start..end]).file_name()?.to_string_lossy().into_owned();
+    let rest = &value[end..];
+    let rest = unpin_store_paths(rest).unwrap_or_else(|| rest.to_string());
+    Some(format!("{}{}{}", &value[..start], command, rest))
+}
+
+/// The nixpkgs attribute providing `command`, if known
+pub fn package_for(command: &str) -> Option<&'static str> {
+    let command = unpin_store_paths(command).unwrap_or_else(|| command.to_string());
+    PACKAGES.iter().find(|(known, _)| *known == command).map(|(_, package)| *package)
+}
+
+/// Where the snippet goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NixFormat {
+    /// `devenv.nix`: `packages` and `env.*`
+    Devenv,
+    /// A flake's dev shell (or `shell.nix`): attributes of `pkgs.mkShell`
+    Flake,
+}
+
+impl NixFormat {
+    /// Parse a format name (`devenv`, `flake`)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "devenv" => Some(NixFormat::Devenv),
+            "flake" | "shell" => Some(NixFormat::Flake),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NixFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NixFormat::Devenv => "devenv",
+            NixFormat::Flake => "flake",
+        })
+    }
+}
+
+/// The planned config as Nix dev shell settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NixSnippet {
+    /// Where the snippet goes
+    pub format: NixFormat,
+    /// Nixpkgs attributes of the tools the settings use
+    pub packages: Vec<String>,
+    /// Environment variables cargo reads the settings from, in plan order
+    pub env: Vec<(String, String)>,
+    /// Changes that cannot be expressed as variables, with the reason
+    pub omitted: Vec<(String, String)>,
+}
+
+impl NixSnippet {
+    /// Whether there is nothing to set
+    pub fn is_empty(&self) -> bool {
+        self.env.is_empty() && self.packages.is_empty()
+    }
+
+    /// The snippet as Nix code, with the omitted changes as comments
+    pub fn render(&self) -> String {
+        let mut out = String::from(match self.format {
+            NixFormat::Devenv => "# devenv.nix\n{ pkgs, ... }:\n{\n",
+            NixFormat::Flake => "# flake.nix: devShells.<system>.default, or shell.nix\npkgs.mkShell {\n",
+        });
+        let indent = "  ";
+        if !self.packages.is_empty() {
+            let packages: Vec<String> = self.packages.iter().map(|package| format!("pkgs.{}", package)).collect();
+            let _ = writeln!(out, "{}packages = [ {} ];", indent, packages.join(" "));
+        }
+        let prefix = match self.format {
+            NixFormat::Devenv => "env.",
+            NixFormat::Flake => "",
+        };
+        for (var, value) in &self.env {
+            let _ = writeln!(out, "{}{}{} = {};", indent, prefix, var, nix_string(value));
+        }
+        for (key, reason) in &self.omitted {
+            let _ = writeln!(out, "{}# not set: {} ({})", indent, key, reason);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// `value` as a Nix string literal
+fn nix_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace("${", "\\${"))
+}
+
+/// Dev shell settings that turn the `original` config into `planned`
+///
+/// Cargo reads every config key from a `CARGO_<KEY>` variable as well, so
+/// the changes an apply would write become variables of the shell, with
+/// the tools they name as packages. As with [`crate::cli_flags`], removals
+/// and arrays already set cannot be expressed; neither can keys such as
+/// `target.'cfg(unix)'` that have no variable name, or profile overrides of
+/// single packages. Nix store paths are replaced by command names.
+pub fn to_nix_snippet(original: Option<&str>, planned: &DocumentMut, format: NixFormat) -> Result<NixSnippet> {
+    let diff = ApplyJournal::record(original, &planned.to_string())?;
+    let mut snippet = NixSnippet { format, packages: Vec::new(), env: Vec::new(), omitted: Vec::new() };
+    for entry in diff.entries {
+        let key = entry.dotted_key();
+        let Some(written) = entry.written else {
+            snippet.omitted.push((key, "removed by apply; variables can only add settings".to_string()));
+            continue;
+        };
+        let Ok(value) = written.parse::<Value>() else {
+            snippet.omitted.push((key, "unparsable value".to_string()));
+            continue;
+        };
+        if value.is_array() && entry.previous.is_some() {
+            snippet.omitted.push((key, "cargo appends arrays from variables to the configured ones".to_string()));
+            continue;
+        }
+        let Some(var) = env_var(&entry.key) else {
+            snippet.omitted.push((key, "no environment variable for this key".to_string()));
+            continue;
+        };
+        let Some(value) = env_value(&value) else {
+            snippet.omitted.push((key, "value cannot be written as a variable".to_string()));
+            continue;
+        };
+        let commands: Vec<&str> = match entry.key.last().map(String::as_str) {
+            Some("linker" | "rustc-wrapper") => vec![value.as_str()],
+            Some("rustflags") => value
+                .split_whitespace()
+                .filter_map(|flag| ["-fuse-ld=", "--ld-path="].iter().find_map(|prefix| flag.split_once(prefix)))
+                .map(|(_, linker)| linker)
+                .collect(),
+            _ => Vec::new(),
+        };
+        for package in commands.into_iter().filter_map(package_for) {
+            if !snippet.packages.iter().any(|known| known == package) {
+                snippet.packages.push(package.to_string());
+            }
+        }
+        snippet.env.push((var, value));
+    }
+    Ok(snippet)
+}
+
+/// The variable cargo reads `key` from; `[env]` entries are plain variables
+fn env_var(key: &[String]) -> Option<String> {
+    let valid = |segment: &String| {
+        !segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    match key {
+        [env, var] if env == "env" => Some(var.clone()),
+        [profile, _, package, ..] if profile == "profile" && package == "package" => None,
+        _ if key.iter().all(valid) => {
+            Some(format!("CARGO_{}", key.join("_").replace(['-', '.'], "_").to_ascii_uppercase()))
+        }
+        _ => None,
+    }
+}
+
+/// The variable's value: strings as they are, arrays joined with spaces
+fn env_value(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.value().clone(),
+        Value::Integer(i) => i.value().to_string(),
+        Value::Float(f) => f.value().to_string(),
+        Value::Boolean(b) => b.value().to_string(),
+        Value::Array(array) => {
+            let items: Option<Vec<String>> = array
+                .iter()
+                .map(|item| {
+                    item.as_str().filter(|s| !s.is_empty() && !s.contains(char::is_whitespace)).map(str::to_string)
+                })
+                .collect();
+            items?.join(" ")
+        }
+        Value::Datetime(_) | Value::InlineTable(_) => return None,
+    };
+    Some(unpin_store_paths(&text).unwrap_or(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detection() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+        assert_eq!(NixEnvironment::from_lookup(env(&[]), Some(PathBuf::from("/usr/bin/rustc"))), None);
+
+        let shell = NixEnvironment::from_lookup(env(&[("IN_NIX_SHELL", "pure")]), None).unwrap();
+        assert_eq!(shell.shell, Some(NixShell::Pure));
+        assert_eq!(shell.describe(), "a pure Nix shell");
+
+        let rustc = PathBuf::from("/nix/store/abc-rust-1.85.0/bin/rustc");
+        let devenv = NixEnvironment::from_lookup(env(&[("DEVENV_ROOT", "/p")]), Some(rustc.clone())).unwrap();
+        assert!(devenv.devenv);
+        assert_eq!(devenv.store_toolchain, Some(rustc));
+        assert_eq!(devenv.format_for(Path::new("/nonexistent")), NixFormat::Devenv);
+    }
+
+    #[test]
+    fn test_store_paths_become_commands() {
+        assert_eq!(unpin_store_paths("/nix/store/abc-clang-17/bin/clang").as_deref(), Some("clang"));
+        assert_eq!(
+            unpin_store_paths("link-arg=-fuse-ld=/nix/store/x-mold-2.3/bin/mold").as_deref(),
+            Some("link-arg=-fuse-ld=mold")
+        );
+        assert_eq!(unpin_store_paths("clang"), None);
+        assert_eq!(package_for("/nix/store/x-sccache/bin/sccache"), Some("sccache"));
+        assert_eq!(package_for("cc"), None);
+    }
+
+    #[test]
+    fn test_snippet() {
+        let original = "[build]\njobs = 4\n";
+        let planned: DocumentMut = "[build]\njobs = 8\nrustc-wrapper = \"sccache\"\n\n\
+                                    [target.x86_64-unknown-linux-gnu]\nlinker = \"/nix/store/a-clang/bin/clang\"\n\
+                                    rustflags = [\"-C\", \"link-arg=-fuse-ld=mold\"]\n\n\
+                                    [target.'cfg(unix)']\nrunner = \"x\"\n\n\
+                                    [profile.dev.package.\"*\"]\nopt-level = 1\n\n\
+                                    [env]\nSCCACHE_DIR = \"/tmp/${x}\"\n"
+            .parse()
+            .unwrap();
+        let snippet = to_nix_snippet(Some(original), &planned, NixFormat::Devenv).unwrap();
+        assert_eq!(snippet.packages, ["sccache", "clang", "mold"]);
+        assert_eq!(
+            snippet.env,
+            [
+                ("CARGO_BUILD_JOBS".to_string(), "8".to_string()),
+                ("CARGO_BUILD_RUSTC_WRAPPER".to_string(), "sccache".to_string()),
+                ("CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_LINKER".to_string(), "clang".to_string()),
+                (
+                    "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUSTFLAGS".to_string(),
+                    "-C link-arg=-fuse-ld=mold".to_string()
+                ),
+                ("SCCACHE_DIR".to_string(), "/tmp/${x}".to_string()),
+            ]
+        );
+        let omitted: Vec<&str> = snippet.omitted.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(omitted, ["target.cfg(unix).runner", "profile.dev.package.*.opt-level"]);
+
+        let rendered = snippet.render();
+        assert!(rendered.contains("  packages = [ pkgs.sccache pkgs.clang pkgs.mold ];\n"), "{}", rendered);
+        assert!(rendered.contains("  env.CARGO_BUILD_JOBS = \"8\";\n"), "{}", rendered);
+        assert!(rendered.contains("  env.SCCACHE_DIR = \"/tmp/\\${x}\";\n"), "{}", rendered);
+        let flake = NixSnippet { format: NixFormat::Flake, ..snippet }.render();
+        assert!(flake.contains("pkgs.mkShell {\n") && flake.contains("  CARGO_BUILD_JOBS = \"8\";\n"), "{}", flake);
+    }
+}
@@ -0,0 +1,260 @@
+//! Output routing for messages emitted by cargo-optimize.
+//!
+//! When called from a build script, plain `println!` output is swallowed by
+//! cargo and `eprintln!` only shows up on failure or with `-vv`. This module
+//! detects the build-script context and routes every message through
+//! `cargo:warning=` so it is visible. `CARGO_OPTIMIZE_QUIET` suppresses
+//! informational messages; outside a build script it suppresses warnings
+//! too. All messages are namespaced with a `cargo-optimize:` prefix.
+//!
+//! `CARGO_OPTIMIZE_DISABLE` is the kill switch: every public entry point
+//! checks [`disabled`] first and does nothing else while it is set, which
+//! helps when bisecting a broken build and lets packagers opt out entirely.
+
+use std::env;
+use std::sync::Once;
+
+use crate::i18n::{tr, Message};
+use crate::secrets;
+
+/// Prefix applied to every message emitted by cargo-optimize
+pub const MESSAGE_PREFIX: &str = "cargo-optimize:";
+
+/// Environment variable that suppresses informational output, and warnings outside build scripts
+pub const QUIET_ENV_VAR: &str = "CARGO_OPTIMIZE_QUIET";
+
+/// Environment variable that turns every entry point into a no-op
+pub const DISABLE_ENV_VAR: &str = "CARGO_OPTIMIZE_DISABLE";
+
+/// How messages should be emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Regular terminal output (stdout for info, stderr for warnings/errors)
+    Terminal,
+    /// Running inside a build script - route through `cargo:warning=`
+    BuildScript,
+    /// Suppress everything except errors
+    Quiet,
+    /// Running inside a build script with `CARGO_OPTIMIZE_QUIET` set - only
+    /// warnings and errors, still through `cargo:warning=`
+    QuietBuildScript,
+}
+
+/// Severity of an emitted message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageLevel {
+    /// Informational message
+    Info,
+    /// Something the user should look at
+    Warning,
+    /// An operation failed
+    Error,
+}
+
+impl OutputMode {
+    /// Detect the output mode from the current process environment.
+    ///
+    /// `CARGO_OPTIMIZE_QUIET` is any value other than `0`/`false`. The
+    /// build-script context is recognized by the variables cargo only sets
+    /// when running a `build.rs` (`OUT_DIR` and `TARGET`, alongside
+    /// `CARGO_MANIFEST_DIR`); there quiet keeps warnings, because cargo hides
+    /// the stderr errors would otherwise go to.
+    pub fn detect() -> Self {
+        Self::from_env_lookup(env_var)
+    }
+
+    /// Detect the output mode using a custom environment lookup (for testing)
+    pub fn from_env_lookup<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let quiet = lookup(QUIET_ENV_VAR).is_some_and(|value| is_truthy(&value));
+        let in_build_script = lookup("OUT_DIR").is_some()
+            && lookup("TARGET").is_some()
+            && lookup("CARGO_MANIFEST_DIR").is_some();
+
+        match (in_build_script, quiet) {
+            (true, true) => OutputMode::QuietBuildScript,
+            (true, false) => OutputMode::BuildScript,
+            (false, true) => OutputMode::Quiet,
+            (false, false) => OutputMode::Terminal,
+        }
+    }
+
+    /// Render a message for this mode, or `None` if it should be suppressed.
+    ///
+    /// Multi-line messages are split so that every line in build-script mode
+    /// carries its own `cargo:warning=` directive.
+    pub fn render(&self, level: MessageLevel, msg: &str) -> Option<String> {
+        match self {
+            OutputMode::Quiet if level < MessageLevel::Error => None,
+            OutputMode::QuietBuildScript if level < MessageLevel::Warning => None,
+            OutputMode::BuildScript | OutputMode::QuietBuildScript => Some(
+                msg.lines()
+                    .map(|line| format!("cargo:warning={} {}", MESSAGE_PREFIX, line))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            _ => Some(format!("{} {}", MESSAGE_PREFIX, msg)),
+        }
+    }
+
+    /// Emit a message according to this mode
+    ///
+    /// Secrets resolved earlier and URL credentials are masked.
+    pub fn emit(&self, level: MessageLevel, msg: &str) {
+        if let Some(rendered) = self.render(level, &secrets::redact(msg)) {
+            match (self, level) {
+                // Build scripts communicate with cargo over stdout
+                (OutputMode::BuildScript | OutputMode::QuietBuildScript, _) => println!("{}", rendered),
+                (_, MessageLevel::Info) => println!("{}", rendered),
+                _ => eprintln!("{}", rendered),
+            }
+        }
+    }
+}
+
+pub(crate) fn is_truthy(value: &str) -> bool {
+    !matches!(value.trim().to_ascii_lowercase().as_str(), "" | "0" | "false" | "no" | "off")
+}
+
+/// Whether `CARGO_OPTIMIZE_DISABLE` switches cargo-optimize off for this invocation
+///
+/// Any value other than `0`/`false`/`no`/`off` counts. The first call that
+/// finds it set emits a single notice; later calls stay silent.
+pub fn disabled() -> bool {
+    static ANNOUNCED: Once = Once::new();
+    let disabled = disabled_from_env_lookup(env_var);
+    if disabled {
+        ANNOUNCED.call_once(|| info(&tr(Message::Disabled, &[&DISABLE_ENV_VAR])));
+    }
+    disabled
+}
+
+/// Fail while `CARGO_OPTIMIZE_DISABLE` is set
+///
+/// The first check of every entry point that writes files and returns a
+/// `Result`; see [`disabled`].
+pub fn ensure_enabled() -> anyhow::Result<()> {
+    if disabled() {
+        anyhow::bail!("cargo-optimize is disabled by {}", DISABLE_ENV_VAR);
+    }
+    Ok(())
+}
+
+/// Check the kill switch using a custom environment lookup (for testing)
+pub fn disabled_from_env_lookup<F>(lookup: F) -> bool
+where
+    F: Fn(&str) -> Option<String>,
+{
+    lookup(DISABLE_ENV_VAR).is_some_and(|value| is_truthy(&value))
+}
+
+/// Value of environment variable `key`, as pretended by an active fake environment if any
+pub(crate) fn env_var(key: &str) -> Option<String> {
+    #[cfg(feature = "test-support")]
+    if let Some(value) = crate::test_support::env_var(key) {
+        return value;
+    }
+    env::var(key).ok()
+}
+
+/// Emit an informational message using the detected output mode
+pub fn info(msg: &str) {
+    OutputMode::detect().emit(MessageLevel::Info, msg);
+}
+
+/// Emit a warning using the detected output mode
+pub fn warn(msg: &str) {
+    OutputMode::detect().emit(MessageLevel::Warning, msg);
+}
+
+/// Emit an error using the detected output mode
+pub fn error(msg: &str) {
+    OutputMode::detect().emit(MessageLevel::Error, msg);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn test_detect_terminal() {
+        assert_eq!(OutputMode::from_env_lookup(lookup(&[])), OutputMode::Terminal);
+        // CARGO_MANIFEST_DIR alone is also set for `cargo run`/`cargo test`
+        assert_eq!(
+            OutputMode::from_env_lookup(lookup(&[("CARGO_MANIFEST_DIR", "/p")])),
+            OutputMode::Terminal
+        );
+    }
+
+    #[test]
+    fn test_detect_build_script() {
+        let env = lookup(&[
+            ("OUT_DIR", "/p/target/debug/build/x/out"),
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("CARGO_MANIFEST_DIR", "/p"),
+        ]);
+        assert_eq!(OutputMode::from_env_lookup(env), OutputMode::BuildScript);
+    }
+
+    #[test]
+    fn test_quiet_build_script_keeps_its_channel() {
+        let env = lookup(&[
+            ("OUT_DIR", "/out"),
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("CARGO_MANIFEST_DIR", "/p"),
+            (QUIET_ENV_VAR, "1"),
+        ]);
+        assert_eq!(OutputMode::from_env_lookup(env), OutputMode::QuietBuildScript);
+
+        let env = lookup(&[(QUIET_ENV_VAR, "1")]);
+        assert_eq!(OutputMode::from_env_lookup(env), OutputMode::Quiet);
+
+        let env = lookup(&[(QUIET_ENV_VAR, "false")]);
+        assert_eq!(OutputMode::from_env_lookup(env), OutputMode::Terminal);
+    }
+
+    #[test]
+    fn test_kill_switch() {
+        assert!(!disabled_from_env_lookup(lookup(&[])));
+        assert!(!disabled_from_env_lookup(lookup(&[(DISABLE_ENV_VAR, "0")])));
+        assert!(!disabled_from_env_lookup(lookup(&[(DISABLE_ENV_VAR, "off")])));
+        assert!(disabled_from_env_lookup(lookup(&[(DISABLE_ENV_VAR, "1")])));
+        assert!(disabled_from_env_lookup(lookup(&[(DISABLE_ENV_VAR, "yes")])));
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            OutputMode::BuildScript.render(MessageLevel::Info, "line one\nline two").unwrap(),
+            "cargo:warning=cargo-optimize: line one\ncargo:warning=cargo-optimize: line two"
+        );
+        assert_eq!(
+            OutputMode::Terminal.render(MessageLevel::Warning, "careful").unwrap(),
+            "cargo-optimize: careful"
+        );
+        assert!(OutputMode::Quiet.render(MessageLevel::Info, "hidden").is_none());
+        assert!(OutputMode::Quiet.render(MessageLevel::Warning, "hidden").is_none());
+        assert!(OutputMode::Quiet.render(MessageLevel::Error, "shown").is_some());
+        // Cargo hides a build script's stderr, so quiet build scripts still warn through cargo
+        assert!(OutputMode::QuietBuildScript.render(MessageLevel::Info, "hidden").is_none());
+        assert_eq!(
+            OutputMode::QuietBuildScript.render(MessageLevel::Warning, "careful").unwrap(),
+            "cargo:warning=cargo-optimize: careful"
+        );
+        assert_eq!(
+            OutputMode::QuietBuildScript.render(MessageLevel::Error, "failed").unwrap(),
+            "cargo:warning=cargo-optimize: failed"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Path handling that holds up on Windows.
+//!
+//! Win32 APIs reject paths longer than `MAX_PATH` (260 characters) unless
+//! long paths are enabled machine-wide or the path carries the `\\?\`
+//! extended-length prefix. Deeply nested workspaces and relocated target
+//! directories reach that limit quickly. [`long_path`] adds the prefix
+//! where needed, including the `\\?\UNC\` form for network shares, and
+//! [`io_context`] turns the errors Windows gives for long paths and
+//! unreachable shares into advice. Paths written into TOML go through
+//! [`to_slash`], which works on components instead of replacing characters.
+
+use anyhow::Result;
+use std::borrow::Cow;
+use std::io;
+use std::path::{Component, Path};
+
+/// Longest path the Win32 APIs accept without the extended-length prefix
+pub const MAX_PATH: usize = 260;
+
+/// Longest directory path: room must be left for an 8.3 file name
+const MAX_DIR_PATH: usize = 248;
+
+/// Prefix of extended-length paths
+const VERBATIM: &str = r"\\?\";
+
+/// Prefix of extended-length paths on network shares
+const VERBATIM_UNC: &str = r"\\?\UNC\";
+
+/// `ERROR_PATH_NOT_FOUND`
+const ERROR_PATH_NOT_FOUND: i32 = 3;
+/// `ERROR_BAD_NETPATH`
+const ERROR_BAD_NETPATH: i32 = 53;
+/// `ERROR_BAD_NET_NAME`
+const ERROR_BAD_NET_NAME: i32 = 67;
+/// `ERROR_FILENAME_EXCED_RANGE`
+const ERROR_FILENAME_EXCED_RANGE: i32 = 206;
+
+/// `path` in a form the filesystem APIs accept whatever its length
+///
+/// On Windows, absolute paths that may exceed the directory limit get the
+/// extended-length prefix; elsewhere, and for short paths, `path` is
+/// returned as it is.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    if !cfg!(windows) || path.as_os_str().len() < MAX_DIR_PATH {
+        return Cow::Borrowed(path);
+    }
+    let Ok(absolute) = std::path::absolute(path) else {
+        return Cow::Borrowed(path);
+    };
+    match absolute.to_str().and_then(verbatim_form) {
+        Some(verbatim) => Cow::Owned(verbatim.into()),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// Extended-length form of an absolute Windows path
+///
+/// Returns `None` for short, relative, device and already extended paths.
+/// Windows does not normalize extended-length paths, so separators are
+/// unified and `.` and `..` resolved here.
+pub fn verbatim_form(path: &str) -> Option<String> {
+    if path.len() < MAX_DIR_PATH || path.starts_with(VERBATIM) || path.starts_with(r"\\.\") {
+        return None;
+    }
+    let path = path.replace('/', "\\");
+    let (mut out, rest, fixed) = if let Some(unc) = path.strip_prefix(r"\\") {
+        // Server and share are not subject to `..`
+        (VERBATIM_UNC.to_string(), unc, 2)
+    } else if path.as_bytes().get(1) == Some(&b':') && path.as_bytes().get(2) == Some(&b'\\') {
+        (format!("{}{}", VERBATIM, &path[..3]), &path[3..], 0)
+    } else {
+        return None;
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." if parts.len() > fixed => {
+                parts.pop();
+            }
+            ".." => {}
+            part => parts.push(part),
+        }
+    }
+    out.push_str(&parts.join("\\"));
+    Some(out)
+}
+
+/// A path for messages, without the extended-length prefix
+pub fn display_form(path: &str) -> Cow<'_, str> {
+    if let Some(unc) = path.strip_prefix(VERBATIM_UNC) {
+        Cow::Owned(format!(r"\\{}", unc))
+    } else if let Some(local) = path.strip_prefix(VERBATIM) {
+        Cow::Borrowed(local)
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+/// A path with `/` separators, as cargo expects in TOML on every platform
+pub fn to_slash(path: &Path) -> String {
+    let mut out = String::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => out.push_str(&prefix.as_os_str().to_string_lossy()),
+            Component::RootDir => out.push('/'),
+            Component::CurDir => {}
+            Component::ParentDir | Component::Normal(_) => {
+                if !out.is_empty() && !out.ends_with('/') {
+                    out.push('/');
+                }
+                out.push_str(&component.as_os_str().to_string_lossy());
+            }
+        }
+    }
+    out
+}
+
+/// Advice for an I/O error on `path`, when Windows refused it for its length or share
+pub fn io_error_hint(path: &Path, error: &io::Error) -> Option<String> {
+    if !cfg!(windows) {
+        return None;
+    }
+    hint_for(&path.to_string_lossy(), error.raw_os_error())
+}
+
+/// [`io_error_hint`] on a path string and raw OS error code
+pub fn hint_for(path: &str, os_error: Option<i32>) -> Option<String> {
+    let display = display_form(path);
+    match os_error? {
+        ERROR_FILENAME_EXCED_RANGE | ERROR_PATH_NOT_FOUND if display.len() >= MAX_DIR_PATH => Some(format!(
+            "the path is {} characters, over the {}-character Windows limit; enable \"Win32 long paths\" \
+             (group policy, or LongPathsEnabled in the registry) or move the project closer to the drive root",
+            display.len(),
+            MAX_PATH
+        )),
+        ERROR_BAD_NETPATH | ERROR_BAD_NET_NAME => {
+            let share: Vec<&str> = display.trim_start_matches('\\').splitn(3, '\\').take(2).collect();
+            Some(format!(
+                r"the network share \\{} cannot be reached; check that it is online and accessible, or work from a local clone",
+                share.join("\\")
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Add context naming the action and path, with advice where Windows refused the path
+pub fn io_context<T>(result: io::Result<T>, action: &str, path: &Path) -> Result<T> {
+    result.map_err(|e| {
+        let hint = io_error_hint(path, &e);
+        let message = format!("Failed to {} {}", action, display_form(&path.to_string_lossy()));
+        match hint {
+            Some(hint) => anyhow::Error::new(e).context(format!("{}: {}", message, hint)),
+            None => anyhow::Error::new(e).context(message),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deep(root: &str, separator: &str) -> String {
+        let mut path = root.to_string();
+        for i in 0..30 {
+            path.push_str(&format!("{}directory-{:02}", separator, i));
+        }
+        path
+    }
+
+    #[test]
+    fn test_verbatim_form() {
+        assert_eq!(verbatim_form(r"C:\project\.cargo\config.toml"), None);
+
+        let local = deep(r"C:\work", "\\");
+        assert!(local.len() > MAX_PATH);
+        assert_eq!(verbatim_form(&local), Some(format!(r"\\?\{}", local)));
+        assert_eq!(verbatim_form(&format!(r"\\?\{}", local)), None);
+        assert_eq!(verbatim_form(&deep("relative", "\\")), None);
+
+        // Mixed separators, `.` and `..` are resolved; `..` cannot climb above the share
+        let unc = deep(r"\\server\share", "/");
+        let verbatim = verbatim_form(&format!(r"{}\.\target\..\..\..\x", unc)).unwrap();
+        assert!(verbatim.starts_with(r"\\?\UNC\server\share\directory-00\"), "{}", verbatim);
+        assert!(verbatim.ends_with(r"\directory-27\x"), "{}", verbatim);
+        assert_eq!(verbatim_form(&format!(r"\\server\share{}", r"\..".repeat(90))), Some(r"\\?\UNC\server\share".to_string()));
+    }
+
+    #[test]
+    fn test_display_form_and_to_slash() {
+        assert_eq!(display_form(r"\\?\UNC\server\share\dir"), r"\\server\share\dir");
+        assert_eq!(display_form(r"\\?\C:\dir"), r"C:\dir");
+        assert_eq!(display_form("/home/dev"), "/home/dev");
+
+        assert_eq!(to_slash(&Path::new("crates").join("core").join("..").join("app")), "crates/core/../app");
+        assert_eq!(to_slash(Path::new("./crates/app/")), "crates/app");
+        assert_eq!(to_slash(Path::new("")), "");
+        #[cfg(unix)]
+        assert_eq!(to_slash(Path::new("/srv/project")), "/srv/project");
+        #[cfg(windows)]
+        assert_eq!(to_slash(Path::new(r"C:\project\crates")), "C:/project/crates");
+    }
+
+    #[test]
+    fn test_hints() {
+        let long = deep(r"C:\work", "\\");
+        let hint = hint_for(&long, Some(ERROR_FILENAME_EXCED_RANGE)).unwrap();
+        assert!(hint.starts_with(&format!("the path is {} characters", long.len())), "{}", hint);
+        assert_eq!(hint_for(r"C:\short", Some(ERROR_PATH_NOT_FOUND)), None);
+
+        let hint = hint_for(r"\\?\UNC\fileserver\builds\project\.cargo", Some(ERROR_BAD_NET_NAME)).unwrap();
+        assert!(hint.starts_with(r"the network share \\fileserver\builds cannot be reached"), "{}", hint);
+        assert_eq!(hint_for(r"C:\x", None), None);
+    }
+}
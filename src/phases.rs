@@ -0,0 +1,268 @@
+//! Applying a planned config in phases with rollback points.
+//!
+//! Writing the whole plan at once means a setting that breaks the build
+//! takes every other change down with it, or stays in place next to them.
+//! [`stage`] splits the changes of a plan into [`ApplyPhase`]s — linker,
+//! cache, profiles, env — and adds them one phase at a time. Each phase is
+//! verified before the next one starts: the rendered config must parse back
+//! with the phase's values in place, and an optional probe (such as
+//! [`test_link`]) must pass. A phase failing verification is rolled back to
+//! the content verified before it and the later phases are not attempted, so
+//! what gets written is always a fully verified prefix of the plan.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::process::Command;
+use toml_edit::{DocumentMut, Item};
+use tracing::{debug, warn};
+
+use crate::journal::{ApplyJournal, JournalEntry};
+
+/// A group of settings applied and verified together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApplyPhase {
+    /// `target.<triple>.linker` and the rustflags selecting it
+    Linker,
+    /// The compiler cache wrapper and its environment
+    Cache,
+    /// Profiles, jobs, target dirs and everything else
+    Profiles,
+    /// The `[env]` table
+    Env,
+}
+
+impl ApplyPhase {
+    /// Phases in the order they are applied
+    pub const ALL: [ApplyPhase; 4] = [ApplyPhase::Linker, ApplyPhase::Cache, ApplyPhase::Profiles, ApplyPhase::Env];
+
+    /// Phase a config key (e.g. `["profile", "dev", "incremental"]`) belongs to
+    pub fn of(key: &[String]) -> ApplyPhase {
+        let keys: Vec<&str> = key.iter().map(String::as_str).collect();
+        match keys.as_slice() {
+            ["target", _, "linker" | "rustflags"] => ApplyPhase::Linker,
+            ["build", "rustc-wrapper"] => ApplyPhase::Cache,
+            ["env", var, ..] if var.starts_with("SCCACHE_") || var.starts_with("CCACHE_") => ApplyPhase::Cache,
+            ["env", ..] => ApplyPhase::Env,
+            _ => ApplyPhase::Profiles,
+        }
+    }
+
+    /// Lowercase name for reports
+    pub fn name(self) -> &'static str {
+        match self {
+            ApplyPhase::Linker => "linker",
+            ApplyPhase::Cache => "cache",
+            ApplyPhase::Profiles => "profiles",
+            ApplyPhase::Env => "env",
+        }
+    }
+}
+
+impl fmt::Display for ApplyPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Verification run on the config content after a phase was added
+pub type PhaseProbe = fn(ApplyPhase, &str) -> Result<()>;
+
+/// What happened to one phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PhaseStatus {
+    /// The phase's settings were verified and are written
+    Applied,
+    /// The plan changes nothing in this phase
+    Unchanged,
+    /// Verification failed; the phase's settings were taken out again
+    RolledBack,
+    /// Not attempted because an earlier phase was rolled back
+    NotAttempted,
+}
+
+/// Outcome of one phase of an apply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PhaseOutcome {
+    /// The phase
+    pub phase: ApplyPhase,
+    /// What happened to it
+    pub status: PhaseStatus,
+    /// Keys the phase changes, dotted (e.g. `profile.dev.incremental`)
+    pub settings: Vec<String>,
+    /// Why verification failed, for rolled back phases
+    pub detail: Option<String>,
+}
+
+/// Result of [`stage`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagedApply {
+    /// Config content to write: the plan up to the last verified phase
+    pub content: String,
+    /// Outcome of every phase, in order
+    pub outcomes: Vec<PhaseOutcome>,
+}
+
+impl StagedApply {
+    /// Whether a phase was rolled back
+    pub fn rolled_back(&self) -> bool {
+        self.outcomes.iter().any(|outcome| outcome.status == PhaseStatus::RolledBack)
+    }
+}
+
+/// Add the changes from `original` to `planned` phase by phase
+///
+/// After a phase's changes are added, the rendered config is parsed back
+/// and `probe` runs with the phase and the content; when either fails, the
+/// content verified before the phase is kept and later phases are marked
+/// [`PhaseStatus::NotAttempted`]. Changes the journal cannot attribute to a
+/// key (structural changes) are part of every rollback point.
+pub fn stage<F>(original: Option<&str>, planned: &str, mut probe: F) -> Result<StagedApply>
+where
+    F: FnMut(ApplyPhase, &str) -> Result<()>,
+{
+    let journal = ApplyJournal::record(original, planned)?;
+    let planned_doc: DocumentMut = planned.parse().context("Failed to parse planned config")?;
+
+    let mut content = up_to(&planned_doc, &journal, None);
+    let mut outcomes = Vec::new();
+    let mut failed = false;
+    for phase in ApplyPhase::ALL {
+        let entries: Vec<&JournalEntry> = journal.entries.iter().filter(|e| ApplyPhase::of(&e.key) == phase).collect();
+        let settings = entries.iter().map(|entry| entry.dotted_key()).collect();
+        let (status, detail) = if entries.is_empty() {
+            (PhaseStatus::Unchanged, None)
+        } else if failed {
+            (PhaseStatus::NotAttempted, None)
+        } else {
+            let candidate = up_to(&planned_doc, &journal, Some(phase));
+            match verify(original, &candidate, &entries).and_then(|()| probe(phase, &candidate)) {
+                Ok(()) => {
+                    debug!("Apply phase {} verified", phase);
+                    content = candidate;
+                    (PhaseStatus::Applied, None)
+                }
+                Err(e) => {
+                    warn!("Apply phase {} failed verification, rolling it back: {:#}", phase, e);
+                    failed = true;
+                    (PhaseStatus::RolledBack, Some(format!("{:#}", e)))
+                }
+            }
+        };
+        outcomes.push(PhaseOutcome { phase, status, settings, detail });
+    }
+    Ok(StagedApply { content, outcomes })
+}
+
+/// The plan with the changes of phases after `last` taken out (all of them for `None`)
+fn up_to(planned: &DocumentMut, journal: &ApplyJournal, last: Option<ApplyPhase>) -> String {
+    let later = ApplyJournal {
+        entries: journal
+            .entries
+            .iter()
+            .filter(|entry| last.is_none_or(|last| ApplyPhase::of(&entry.key) > last))
+            .cloned()
+            .collect(),
+        ..journal.clone()
+    };
+    let mut doc = planned.clone();
+    later.revert(&mut doc);
+    doc.to_string()
+}
+
+/// Parse `candidate` back and check the phase's values are in it
+fn verify(original: Option<&str>, candidate: &str, entries: &[&JournalEntry]) -> Result<()> {
+    let written = ApplyJournal::record(original, candidate).context("config does not parse back")?;
+    for entry in entries {
+        let found = written.entries.iter().find(|e| e.key == entry.key);
+        if found.map(|e| &e.written) != Some(&entry.written) {
+            anyhow::bail!("{} does not read back as written", entry.dotted_key());
+        }
+    }
+    Ok(())
+}
+
+/// Link an empty program for `target` with the linker settings in `content`
+///
+/// Succeeds without doing anything when `content` configures no linker for
+/// `target`. The program is built in a scratch directory removed afterwards.
+pub fn test_link(content: &str, target: &str) -> Result<()> {
+    let doc: DocumentMut = content.parse().context("Failed to parse config")?;
+    let Some(table) = doc.get("target").and_then(|targets| targets.get(target)) else {
+        return Ok(());
+    };
+    let linker = table.get("linker").and_then(Item::as_str);
+    let rustflags: Vec<&str> = table
+        .get("rustflags")
+        .and_then(Item::as_array)
+        .map(|flags| flags.iter().filter_map(|flag| flag.as_str()).collect())
+        .unwrap_or_default();
+    if linker.is_none() && rustflags.is_empty() {
+        return Ok(());
+    }
+
+    let dir = std::env::temp_dir().join(format!("cargo-optimize-link-probe-{}", std::process::id()));
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let result = (|| {
+        let source = dir.join("main.rs");
+        fs::write(&source, "fn main() {}\n")?;
+        let mut command = Command::new("rustc");
+        command.arg("--target").arg(target).arg(&source).arg("-o").arg(dir.join("probe"));
+        if let Some(linker) = linker {
+            command.arg("-C").arg(format!("linker={}", linker));
+        }
+        command.args(&rustflags);
+        let output = command.output().context("Failed to run rustc")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let line = stderr.lines().find(|line| line.starts_with("error")).unwrap_or("link failed");
+            anyhow::bail!("test link failed: {}", line.trim());
+        }
+        Ok(())
+    })();
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLANNED: &str = "[target.x86_64-unknown-linux-gnu]\nlinker = \"clang\"\n\n\
+                           [build]\nrustc-wrapper = \"sccache\"\njobs = 8\n\n\
+                           [profile.dev]\nincremental = true\n";
+
+    #[test]
+    fn test_failed_phase_keeps_earlier_phases_only() {
+        let original = "[build]\njobs = 2\n";
+        let staged = stage(Some(original), PLANNED, |phase, _| match phase {
+            ApplyPhase::Cache => anyhow::bail!("sccache missing"),
+            _ => Ok(()),
+        })
+        .unwrap();
+
+        let statuses: Vec<PhaseStatus> = staged.outcomes.iter().map(|outcome| outcome.status).collect();
+        assert_eq!(
+            statuses,
+            [PhaseStatus::Applied, PhaseStatus::RolledBack, PhaseStatus::NotAttempted, PhaseStatus::Unchanged]
+        );
+        assert_eq!(staged.outcomes[1].detail.as_deref(), Some("sccache missing"));
+        let doc: DocumentMut = staged.content.parse().unwrap();
+        assert_eq!(doc["target"]["x86_64-unknown-linux-gnu"]["linker"].as_str(), Some("clang"));
+        assert!(doc["build"].get("rustc-wrapper").is_none());
+        assert_eq!(doc["build"]["jobs"].as_integer(), Some(2));
+        assert!(doc.get("profile").is_none());
+    }
+
+    #[test]
+    fn test_verified_phases_write_the_plan_unchanged() {
+        let staged = stage(None, PLANNED, |_, _| Ok(())).unwrap();
+        assert!(!staged.rolled_back());
+        assert_eq!(staged.content, PLANNED);
+        assert_eq!(staged.outcomes[2].settings, ["build.jobs", "profile.dev.incremental"]);
+        assert_eq!(ApplyPhase::of(&["env".to_string(), "SCCACHE_ENDPOINT".to_string()]), ApplyPhase::Cache);
+    }
+}
@@ -0,0 +1,335 @@
+//! Machine-wide policy for org-managed fleets.
+//!
+//! Admins can place a policy file at `/etc/cargo-optimize/policy.toml`
+//! (`%ProgramData%\cargo-optimize\policy.toml` on Windows, or the path in
+//! `CARGO_OPTIMIZE_POLICY`) to centralize decisions across machines:
+//!
+//! ```toml
+//! # Only these linkers may be configured
+//! allowed_linkers = ["lld", "mold"]
+//! # Every project builds through this shared sccache
+//! sccache_endpoint = "https://sccache.example.com"
+//! sccache_bucket = "build-cache"
+//! # Flags that must never end up in rustflags
+//! forbidden_flags = ["-C target-cpu=native", "-Z"]
+//! ```
+//!
+//! Project-level applies comply with the policy and report every setting
+//! where the project asked for something else.
+//!
+//! Teams can deny settings for their project in the `[deny]` table of the
+//! committed `cargo-optimize.toml` ([`DenyList`]). The planner never
+//! generates what it lists and reports each such setting as suppressed.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::linkers;
+
+/// Environment variable naming a policy file to use instead of the default
+pub const POLICY_ENV_VAR: &str = "CARGO_OPTIMIZE_POLICY";
+
+/// Machine-level policy managed by admins
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Linkers that may be configured; any linker when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_linkers: Option<Vec<String>>,
+
+    /// sccache endpoint every build must use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sccache_endpoint: Option<String>,
+
+    /// Bucket on the sccache endpoint; sccache ignores the endpoint without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sccache_bucket: Option<String>,
+
+    /// Flags that must not appear in rustflags
+    ///
+    /// `-C target-cpu=native` forbids exactly that flag, `-C target-cpu`
+    /// forbids it with any value and `-Z` forbids every `-Z` flag.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden_flags: Vec<String>,
+
+    /// File the policy was loaded from
+    #[serde(skip)]
+    pub source: PathBuf,
+}
+
+impl Policy {
+    /// Default location of the machine policy file
+    pub fn default_path() -> PathBuf {
+        if cfg!(windows) {
+            std::env::var_os("ProgramData")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+                .join("cargo-optimize")
+                .join("policy.toml")
+        } else {
+            PathBuf::from("/etc/cargo-optimize/policy.toml")
+        }
+    }
+
+    /// Load and validate the policy at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read policy {:?}", path))?;
+        let mut policy: Policy =
+            toml::from_str(&content).with_context(|| format!("Failed to parse policy {:?}", path))?;
+        policy.source = path.to_path_buf();
+        policy.validate().with_context(|| format!("Invalid policy {:?}", path))?;
+        Ok(policy)
+    }
+
+    /// Load the machine policy, if one is installed
+    ///
+    /// A path set in `CARGO_OPTIMIZE_POLICY` must exist; the default path is
+    /// optional.
+    pub fn discover() -> Result<Option<Self>> {
+        if let Some(path) = std::env::var_os(POLICY_ENV_VAR).filter(|path| !path.is_empty()) {
+            return Self::load(Path::new(&path)).map(Some);
+        }
+        let path = Self::default_path();
+        if !path.exists() {
+            debug!("No machine policy at {:?}", path);
+            return Ok(None);
+        }
+        Self::load(&path).map(Some)
+    }
+
+    /// Check that the policy is usable
+    pub fn validate(&self) -> Result<()> {
+        for linker in self.allowed_linkers.iter().flatten() {
+            if linkers::find(linker).is_none() {
+                anyhow::bail!("allowed_linkers names unknown linker `{}`", linker);
+            }
+        }
+        if self.sccache_endpoint.as_ref().is_some_and(|endpoint| endpoint.trim().is_empty()) {
+            anyhow::bail!("sccache_endpoint is empty");
+        }
+        if self.sccache_bucket.as_ref().is_some_and(|bucket| bucket.trim().is_empty()) {
+            anyhow::bail!("sccache_bucket is empty");
+        }
+        match (&self.sccache_endpoint, &self.sccache_bucket) {
+            (Some(_), None) => anyhow::bail!("sccache_endpoint needs sccache_bucket; sccache ignores an endpoint without a bucket"),
+            (None, Some(_)) => anyhow::bail!("sccache_bucket needs sccache_endpoint"),
+            _ => {}
+        }
+        if let Some(flag) = self.forbidden_flags.iter().find(|flag| flag.split_whitespace().next().is_none()) {
+            anyhow::bail!("forbidden_flags contains an empty flag {:?}", flag);
+        }
+        Ok(())
+    }
+
+    /// Whether `linker` may be configured
+    pub fn allows_linker(&self, linker: &str) -> bool {
+        self.allowed_linkers
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|name| name == linker))
+    }
+
+    /// Split rustflags into allowed flags and forbidden ones
+    ///
+    /// `-C <option>` pairs are treated as one flag; forbidden flags are
+    /// returned in their normalized `-C<option>` form.
+    pub fn split_flags(&self, flags: &[String]) -> (Vec<String>, Vec<String>) {
+        split_flags(&self.forbidden_flags, flags)
+    }
+}
+
+/// Settings a team denies for its project (`[deny]` in `cargo-optimize.toml`)
+///
+/// ```toml
+/// [deny]
+/// # Builds must be reproducible across machines
+/// flags = ["-C target-cpu=native"]
+/// linkers = ["mold"]
+/// # Explanation ids or config keys, `*` matching any one key
+/// features = ["CO-SCCACHE-002", "profile.*.lto"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DenyList {
+    /// Flags that must not be generated, matched like [`Policy::forbidden_flags`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+
+    /// Linkers that must not be configured
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub linkers: Vec<String>,
+
+    /// Settings that must not be generated: explanation ids (e.g.
+    /// "CO-INCR-001") or dotted config keys (e.g. "profile.*.lto")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+}
+
+impl DenyList {
+    /// Whether nothing is denied
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.linkers.is_empty() && self.features.is_empty()
+    }
+
+    /// Check that every entry names something cargo-optimize generates
+    pub fn validate(&self) -> Result<()> {
+        if let Some(linker) = self.linkers.iter().find(|linker| linkers::find(linker).is_none()) {
+            anyhow::bail!("deny.linkers names unknown linker `{}`", linker);
+        }
+        if let Some(flag) = self.flags.iter().find(|flag| flag.split_whitespace().next().is_none()) {
+            anyhow::bail!("deny.flags contains an empty flag {:?}", flag);
+        }
+        let unknown = |feature: &&String| !feature.contains('.') && crate::explain::find(feature).is_none();
+        if let Some(feature) = self.features.iter().find(unknown) {
+            anyhow::bail!("deny.features entry `{}` is neither an explanation id nor a config key", feature);
+        }
+        Ok(())
+    }
+
+    /// Whether `linker` must not be configured
+    pub fn denies_linker(&self, linker: &str) -> bool {
+        self.linkers.iter().any(|name| name == linker)
+    }
+
+    /// Split rustflags into allowed flags and denied ones, like [`Policy::split_flags`]
+    pub fn split_flags(&self, flags: &[String]) -> (Vec<String>, Vec<String>) {
+        split_flags(&self.flags, flags)
+    }
+
+    /// The `features` entry denying the value at `key`, written with explanation `id`
+    pub fn denied_feature(&self, key: &[String], id: Option<&str>) -> Option<&str> {
+        self.features
+            .iter()
+            .find(|feature| {
+                if feature.contains('.') {
+                    let segments: Vec<&str> = feature.split('.').collect();
+                    segments.len() == key.len()
+                        && segments.iter().zip(key).all(|(segment, key)| *segment == "*" || segment == key)
+                } else {
+                    id == Some(feature.as_str())
+                }
+            })
+            .map(String::as_str)
+    }
+}
+
+/// Split `flags` into those matching none of `patterns` and the normalized matching ones
+fn split_flags(patterns: &[String], flags: &[String]) -> (Vec<String>, Vec<String>) {
+    let patterns: Vec<String> = patterns
+        .iter()
+        .flat_map(|pattern| normalize_flags(&pattern.split_whitespace().collect::<Vec<_>>()))
+        .collect();
+    let mut allowed = Vec::new();
+    let mut forbidden = Vec::new();
+    let mut iter = flags.iter();
+    while let Some(flag) = iter.next() {
+        let mut tokens = vec![flag.clone()];
+        if matches!(flag.as_str(), "-C" | "-Z") {
+            tokens.extend(iter.next().cloned());
+        }
+        let refs: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        let normalized = normalize_flags(&refs).concat();
+        if patterns.iter().any(|pattern| flag_matches(&normalized, pattern)) {
+            forbidden.push(normalized);
+        } else {
+            allowed.extend(tokens);
+        }
+    }
+    (allowed, forbidden)
+}
+
+/// Join `-C <option>` and `-Z <option>` pairs into single `-C<option>` flags
+fn normalize_flags(tokens: &[&str]) -> Vec<String> {
+    let mut flags = Vec::new();
+    let mut iter = tokens.iter().filter(|token| !token.is_empty());
+    while let Some(token) = iter.next() {
+        match *token {
+            "-C" | "-Z" => flags.push(format!("{}{}", token, iter.next().unwrap_or(&""))),
+            _ => flags.push(token.to_string()),
+        }
+    }
+    flags
+}
+
+/// Whether a normalized flag matches a normalized pattern
+fn flag_matches(flag: &str, pattern: &str) -> bool {
+    if matches!(pattern, "-C" | "-Z") {
+        return flag.starts_with(pattern);
+    }
+    flag == pattern || flag.strip_prefix(pattern).is_some_and(|rest| rest.starts_with('='))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|flag| flag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_forbidden_flag_matching() {
+        let policy = Policy {
+            forbidden_flags: flags(&["-C target-cpu", "-Z", "-Clink-arg=-fuse-ld=gold"]),
+            ..Policy::default()
+        };
+        let (allowed, forbidden) = policy.split_flags(&flags(&[
+            "-C",
+            "target-cpu=native",
+            "-Zshare-generics=y",
+            "-C",
+            "link-arg=-fuse-ld=gold",
+            "-C",
+            "link-arg=-fuse-ld=mold",
+            "-Ctarget-feature=+avx2",
+        ]));
+        assert_eq!(allowed, flags(&["-C", "link-arg=-fuse-ld=mold", "-Ctarget-feature=+avx2"]));
+        assert_eq!(
+            forbidden,
+            flags(&["-Ctarget-cpu=native", "-Zshare-generics=y", "-Clink-arg=-fuse-ld=gold"])
+        );
+    }
+
+    #[test]
+    fn test_policy_parsing_and_validation() {
+        let policy: Policy = toml::from_str(
+            "allowed_linkers = [\"lld\"]\nsccache_endpoint = \"https://cache.example.com\"\nsccache_bucket = \"builds\"\n",
+        )
+        .unwrap();
+        assert!(policy.validate().is_ok());
+        let endpoint_only = Policy { sccache_bucket: None, ..policy.clone() };
+        assert!(endpoint_only.validate().unwrap_err().to_string().contains("sccache_bucket"));
+        let bucket_only = Policy { sccache_endpoint: None, ..policy.clone() };
+        assert!(bucket_only.validate().is_err());
+        assert!(policy.allows_linker("lld"));
+        assert!(!policy.allows_linker("mold"));
+        assert!(Policy::default().allows_linker("mold"));
+
+        let unknown = Policy { allowed_linkers: Some(flags(&["fastld"])), ..Policy::default() };
+        assert!(unknown.validate().is_err());
+        assert!(toml::from_str::<Policy>("allowed_linker = [\"lld\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_deny_list() {
+        let deny: DenyList = toml::from_str(
+            "flags = [\"-C target-cpu=native\"]\nlinkers = [\"mold\"]\nfeatures = [\"CO-INCR-001\", \"profile.*.lto\"]\n",
+        )
+        .unwrap();
+        assert!(deny.validate().is_ok());
+        assert!(deny.denies_linker("mold") && !deny.denies_linker("lld"));
+        let (allowed, denied) = deny.split_flags(&flags(&["-C", "target-cpu=native", "-C", "opt-level=3"]));
+        assert_eq!((allowed, denied), (flags(&["-C", "opt-level=3"]), flags(&["-Ctarget-cpu=native"])));
+
+        let key = |key: &str| flags(&key.split('.').collect::<Vec<_>>());
+        assert_eq!(deny.denied_feature(&key("profile.release.lto"), None), Some("profile.*.lto"));
+        assert_eq!(deny.denied_feature(&key("profile.dev.incremental"), Some("CO-INCR-001")), Some("CO-INCR-001"));
+        assert_eq!(deny.denied_feature(&key("profile.release.lto.x"), None), None);
+        assert_eq!(deny.denied_feature(&key("build.jobs"), Some("CO-JOBS-001")), None);
+
+        assert!(DenyList { linkers: flags(&["fastld"]), ..DenyList::default() }.validate().is_err());
+        assert!(DenyList { features: flags(&["sccache"]), ..DenyList::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,361 @@
+//! Sampling profiler for build processes.
+//!
+//! [`BuildProfiler`] runs a watcher thread that polls the process table with
+//! sysinfo while a build runs. Every sample of a rustc, linker or cache
+//! process is attributed to compile time, link time or IO wait (the process
+//! was barely using the CPU while reading or writing the disk). The resulting
+//! [`BuildProfile`] shows whether a faster linker or a compilation cache
+//! would help most on this machine, how close the build came to running
+//! out of memory, and how many cores other workloads kept busy meanwhile.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, System};
+
+/// Default time between samples
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// CPU usage (percent of one core) below which a process doing disk IO counts as waiting on it
+const IO_WAIT_CPU_PERCENT: f32 = 10.0;
+
+/// Processes doing compilation work
+const COMPILE_PROCESSES: &[&str] = &["rustc", "clippy-driver", "rustdoc", "sccache"];
+
+/// Processes doing link work; rustc links through a C compiler driver, so
+/// drivers count as linking (C code compiled by build scripts included)
+const LINK_PROCESSES: &[&str] = &[
+    "ld", "ld.bfd", "ld.gold", "gold", "ld.lld", "lld", "lld-link", "rust-lld", "mold", "ld.mold", "wild",
+    "ld64", "ld64.lld", "ld64.sold", "zld", "link", "cc", "gcc", "clang",
+];
+
+/// Build phase a process belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// rustc and compilation caches
+    Compile,
+    /// Linkers and the C compiler drivers invoking them
+    Link,
+}
+
+/// Classify a process by its executable name
+pub fn classify(process_name: &str) -> Option<Phase> {
+    let name = process_name.to_ascii_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    if COMPILE_PROCESSES.contains(&name) {
+        Some(Phase::Compile)
+    } else if LINK_PROCESSES.contains(&name) {
+        Some(Phase::Link)
+    } else {
+        None
+    }
+}
+
+/// One process observed in a sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessSample {
+    /// Executable name
+    pub name: String,
+    /// CPU usage since the previous sample, in percent of one core
+    pub cpu_percent: f32,
+    /// Bytes read and written since the previous sample
+    pub disk_bytes: u64,
+    /// Resident memory in bytes
+    pub memory_bytes: u64,
+}
+
+/// What the build spent most of its process time on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bottleneck {
+    /// Running rustc
+    Compile,
+    /// Running the linker
+    Link,
+    /// Waiting on disk IO
+    IoWait,
+}
+
+/// Time attribution of a profiled build
+///
+/// Times are process-seconds: two rustc processes running for one second
+/// count as two seconds of compile time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildProfile {
+    /// Seconds compile processes were busy on the CPU
+    pub compile_secs: f64,
+    /// Seconds link processes were busy on the CPU
+    pub link_secs: f64,
+    /// Seconds build processes waited on disk IO
+    pub io_wait_secs: f64,
+    /// Bytes read and written by build processes
+    pub disk_bytes: u64,
+    /// Wall-clock duration of the profile
+    pub wall_secs: f64,
+    /// Number of samples taken
+    pub samples: usize,
+    /// Most build processes seen in one sample
+    pub peak_processes: usize,
+    /// Highest resident memory of all compile and link processes together, in bytes
+    pub peak_memory_bytes: u64,
+    /// Memory still available on the machine when the peak was sampled, in bytes
+    pub available_at_peak_bytes: u64,
+    /// Average cores kept busy by processes other than the build
+    pub background_load: f64,
+    /// Samples the background load is averaged over
+    pub background_samples: usize,
+}
+
+impl BuildProfile {
+    /// Attribute one sample covering `elapsed`
+    pub fn record(&mut self, processes: &[ProcessSample], elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let mut active = 0;
+        let mut memory_bytes = 0;
+        for process in processes {
+            let Some(phase) = classify(&process.name) else {
+                continue;
+            };
+            active += 1;
+            memory_bytes += process.memory_bytes;
+            self.disk_bytes += process.disk_bytes;
+            if process.cpu_percent < IO_WAIT_CPU_PERCENT {
+                // Idle processes without IO wait on other processes (jobserver, pipes)
+                if process.disk_bytes > 0 {
+                    self.io_wait_secs += secs;
+                }
+                continue;
+            }
+            match phase {
+                Phase::Compile => self.compile_secs += secs,
+                Phase::Link => self.link_secs += secs,
+            }
+        }
+        self.samples += 1;
+        self.peak_processes = self.peak_processes.max(active);
+        self.peak_memory_bytes = self.peak_memory_bytes.max(memory_bytes);
+    }
+    
+    /// Add a sample of the machine's CPU usage, `total_cores` busy in all
+    ///
+    /// What the build's own processes used is subtracted.
+    pub fn record_background(&mut self, processes: &[ProcessSample], total_cores: f64) {
+        let build: f64 = processes
+            .iter()
+            .filter(|process| classify(&process.name).is_some())
+            .map(|process| f64::from(process.cpu_percent) / 100.0)
+            .sum();
+        let busy = (total_cores - build).max(0.0);
+        self.background_samples += 1;
+        self.background_load += (busy - self.background_load) / self.background_samples as f64;
+    }
+    
+    /// Memory the build could use at its peak: its own usage plus what was still available
+    pub fn memory_budget_bytes(&self) -> u64 {
+        self.peak_memory_bytes + self.available_at_peak_bytes
+    }
+
+    /// Attributed process time in seconds
+    pub fn total_secs(&self) -> f64 {
+        self.compile_secs + self.link_secs + self.io_wait_secs
+    }
+
+    /// Share of the attributed time spent on `bottleneck`, from 0 to 1
+    pub fn share(&self, bottleneck: Bottleneck) -> f64 {
+        let total = self.total_secs();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let secs = match bottleneck {
+            Bottleneck::Compile => self.compile_secs,
+            Bottleneck::Link => self.link_secs,
+            Bottleneck::IoWait => self.io_wait_secs,
+        };
+        secs / total
+    }
+
+    /// Phase taking the largest share of time, if anything was attributed
+    pub fn bottleneck(&self) -> Option<Bottleneck> {
+        if self.total_secs() <= 0.0 {
+            return None;
+        }
+        [Bottleneck::Compile, Bottleneck::Link, Bottleneck::IoWait]
+            .into_iter()
+            .max_by(|a, b| self.share(*a).total_cmp(&self.share(*b)))
+    }
+
+    /// Which optimization is likely to help most
+    pub fn recommendation(&self) -> Option<&'static str> {
+        self.bottleneck().map(|bottleneck| match bottleneck {
+            Bottleneck::Compile => {
+                "Compilation dominates; a compilation cache (sccache) and profile tuning will help most"
+            }
+            Bottleneck::Link => "Linking dominates; a faster linker (mold, lld) will help most",
+            Bottleneck::IoWait => {
+                "Build processes mostly wait on disk IO; a faster disk for the target directory will help most"
+            }
+        })
+    }
+}
+
+impl fmt::Display for BuildProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Build profile ({:.1}s wall, {} samples):", self.wall_secs, self.samples)?;
+        let rows = [
+            ("compile", self.compile_secs, Bottleneck::Compile),
+            ("link", self.link_secs, Bottleneck::Link),
+            ("io wait", self.io_wait_secs, Bottleneck::IoWait),
+        ];
+        for (label, secs, bottleneck) in rows {
+            writeln!(f, "  {:<8} {:>8.1}s ({:.0}%)", label, secs, self.share(bottleneck) * 100.0)?;
+        }
+        match self.recommendation() {
+            Some(recommendation) => write!(f, "{}", recommendation),
+            None => write!(f, "No rustc or linker activity was observed"),
+        }
+    }
+}
+
+/// Watcher thread sampling build processes
+pub struct BuildProfiler {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<BuildProfile>,
+}
+
+impl BuildProfiler {
+    /// Start sampling every `interval`
+    ///
+    /// With `root`, only descendants of that process are sampled; otherwise
+    /// every rustc and linker on the machine is.
+    pub fn start(root: Option<u32>, interval: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let root = root.map(Pid::from_u32);
+            let mut system = System::new();
+            let mut profile = BuildProfile::default();
+            let started = Instant::now();
+            let mut last = started;
+            loop {
+                system.refresh_processes();
+                system.refresh_memory();
+                system.refresh_cpu_usage();
+                let now = Instant::now();
+                let peak = profile.peak_memory_bytes;
+                let processes = build_processes(&system, root);
+                profile.record(&processes, now - last);
+                if profile.peak_memory_bytes > peak {
+                    profile.available_at_peak_bytes = system.available_memory();
+                }
+                // CPU usage is measured between two refreshes
+                if profile.samples > 1 {
+                    let total = f64::from(system.global_cpu_info().cpu_usage()) * system.cpus().len() as f64 / 100.0;
+                    profile.record_background(&processes, total);
+                }
+                last = now;
+                if stop_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                thread::sleep(interval);
+            }
+            profile.wall_secs = started.elapsed().as_secs_f64();
+            profile
+        });
+        Self { stop, handle }
+    }
+
+    /// Stop sampling and return the profile
+    pub fn finish(self) -> BuildProfile {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+/// Run `command` (e.g. `cargo build`) while sampling its process tree
+pub fn profile_command(command: &mut Command, interval: Duration) -> Result<(ExitStatus, BuildProfile)> {
+    let mut child = command.spawn().context("Failed to start the build command")?;
+    let profiler = BuildProfiler::start(Some(child.id()), interval);
+    let status = child.wait().context("Failed to wait for the build command")?;
+    Ok((status, profiler.finish()))
+}
+
+/// Snapshot of the processes belonging to the build
+fn build_processes(system: &System, root: Option<Pid>) -> Vec<ProcessSample> {
+    system
+        .processes()
+        .iter()
+        .filter(|(pid, _)| root.is_none_or(|root| is_descendant(system, **pid, root)))
+        .map(|(_, process)| {
+            let disk = process.disk_usage();
+            ProcessSample {
+                name: process.name().to_string(),
+                cpu_percent: process.cpu_usage(),
+                disk_bytes: disk.read_bytes + disk.written_bytes,
+                memory_bytes: process.memory(),
+            }
+        })
+        .collect()
+}
+
+fn is_descendant(system: &System, pid: Pid, root: Pid) -> bool {
+    let mut current = Some(pid);
+    // Bounded in case the process table changes mid-walk
+    for _ in 0..64 {
+        match current {
+            Some(pid) if pid == root => return true,
+            Some(pid) => current = system.process(pid).and_then(|process| process.parent()),
+            None => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str, cpu_percent: f32, disk_bytes: u64) -> ProcessSample {
+        ProcessSample { name: name.to_string(), cpu_percent, disk_bytes, memory_bytes: 1 << 30 }
+    }
+
+    #[test]
+    fn test_classify_processes() {
+        assert_eq!(classify("rustc"), Some(Phase::Compile));
+        assert_eq!(classify("sccache.exe"), Some(Phase::Compile));
+        assert_eq!(classify("ld.lld"), Some(Phase::Link));
+        assert_eq!(classify("RUST-LLD.EXE"), Some(Phase::Link));
+        assert_eq!(classify("cargo"), None);
+    }
+
+    #[test]
+    fn test_time_attribution() {
+        let mut profile = BuildProfile::default();
+        let tick = Duration::from_millis(500);
+        profile.record(&[sample("rustc", 95.0, 0), sample("rustc", 80.0, 4096), sample("cargo", 50.0, 0)], tick);
+        profile.record(&[sample("mold", 400.0, 1 << 20), sample("rustc", 2.0, 8192)], tick);
+        profile.record(&[sample("cc", 1.0, 0)], tick);
+
+        assert_eq!(profile.samples, 3);
+        assert_eq!(profile.peak_processes, 2);
+        assert_eq!(profile.peak_memory_bytes, 2 << 30);
+        assert_eq!(profile.compile_secs, 1.0);
+        assert_eq!(profile.link_secs, 0.5);
+        assert_eq!(profile.io_wait_secs, 0.5);
+        assert_eq!(profile.disk_bytes, 4096 + (1 << 20) + 8192);
+        assert_eq!(profile.bottleneck(), Some(Bottleneck::Compile));
+        assert!(profile.to_string().contains("sccache"));
+
+        profile.record(&[sample("ld", 100.0, 0), sample("ld", 100.0, 0), sample("ld", 100.0, 0)], tick);
+        assert_eq!(profile.bottleneck(), Some(Bottleneck::Link));
+        assert_eq!(BuildProfile::default().bottleneck(), None);
+
+        // Other work is what the machine used beyond the build's processes
+        profile.record_background(&[sample("rustc", 100.0, 0), sample("cargo", 20.0, 0)], 5.0);
+        profile.record_background(&[sample("rustc", 300.0, 0)], 3.0);
+        assert_eq!(profile.background_samples, 2);
+        assert_eq!(profile.background_load, 2.0);
+    }
+}
@@ -0,0 +1,193 @@
+//! Pull-request mode: stage changes for review instead of applying them.
+//!
+//! [`ConfigManager::propose`](crate::config::ConfigManager::propose) renders
+//! every file an apply would write into `.cargo-optimize/proposed/`, under the
+//! same path relative to the project root, together with a `CHANGES.md`
+//! summary for reviewers and a `proposal.json` manifest. Live configs are
+//! never touched. [`ConfigManager::accept`](crate::config::ConfigManager::accept)
+//! later writes the reviewed files, refusing if a live file changed since the
+//! proposal was made.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::sha256_hex;
+use crate::i18n::Lang;
+use crate::journal::{ApplyJournal, JournalEntry};
+use crate::report::OptimizationReport;
+use crate::transaction::atomic_write;
+
+/// Directory of the staged proposal, relative to the project root
+pub const PROPOSAL_DIR: &str = ".cargo-optimize/proposed";
+
+/// Human-readable summary of the proposal
+pub const CHANGES_FILE: &str = "CHANGES.md";
+
+/// Machine-readable manifest of the proposal
+pub const MANIFEST_FILE: &str = "proposal.json";
+
+/// One file the proposal would write
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposedFile {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// SHA-256 of the live file when the proposal was made; `None` if it did not exist
+    pub original_sha256: Option<String>,
+    /// Keys the proposal changes
+    #[serde(default)]
+    pub changes: Vec<JournalEntry>,
+}
+
+impl ProposedFile {
+    /// Describe the change from `original` to `proposed` at the relative `path`
+    pub fn new(path: PathBuf, original: Option<&str>, proposed: &str) -> Result<Self> {
+        check_relative(&path)?;
+        Ok(ProposedFile {
+            original_sha256: original.map(sha256_hex),
+            changes: ApplyJournal::record(original, proposed)?.entries,
+            path,
+        })
+    }
+
+    /// Whether the live file still has the content the proposal was made from
+    pub fn matches(&self, live: Option<&str>) -> bool {
+        live.map(sha256_hex) == self.original_sha256
+    }
+}
+
+/// Changes staged for review
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposal {
+    /// When the proposal was made (seconds since the Unix epoch)
+    pub created_at: u64,
+    /// cargo-optimize version that made the proposal
+    pub tool_version: String,
+    /// Files the proposal writes
+    #[serde(default)]
+    pub files: Vec<ProposedFile>,
+}
+
+impl Proposal {
+    /// Empty proposal made now
+    pub fn new() -> Self {
+        Proposal {
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Proposal directory of the project at `root`
+    pub fn dir_in(root: &Path) -> PathBuf {
+        root.join(PROPOSAL_DIR)
+    }
+
+    /// Load the proposal staged in `dir`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("No staged proposal in {:?}; run propose first", dir))?;
+        let proposal: Proposal =
+            serde_json::from_str(&content).with_context(|| format!("Invalid proposal manifest {:?}", path))?;
+        for file in &proposal.files {
+            check_relative(&file.path)?;
+        }
+        Ok(proposal)
+    }
+
+    /// Write the manifest and `CHANGES.md` into `dir`
+    pub fn save(&self, dir: &Path, report: &OptimizationReport) -> Result<()> {
+        let manifest = serde_json::to_string_pretty(self).context("Failed to serialize proposal manifest")?;
+        atomic_write(&dir.join(MANIFEST_FILE), &manifest)?;
+        atomic_write(&dir.join(CHANGES_FILE), &self.changes_markdown(report))
+    }
+
+    /// Summary for reviewers: every changed key per file, then the decisions taken
+    pub fn changes_markdown(&self, report: &OptimizationReport) -> String {
+        let mut out = String::from("# Proposed cargo-optimize changes\n\n");
+        if self.files.is_empty() {
+            out.push_str("No changes proposed; the live configuration is up to date.\n");
+        } else {
+            out.push_str(
+                "The rendered files next to this summary replace the live ones once the \
+                 proposal is accepted. Nothing has been changed yet.\n",
+            );
+        }
+        for file in &self.files {
+            let status = if file.original_sha256.is_none() { " (new file)" } else { "" };
+            let _ = writeln!(out, "\n## `{}`{}\n", file.path.display(), status);
+            out.push_str("| Setting | Current | Proposed |\n|---|---|---|\n");
+            for change in &file.changes {
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | {} |",
+                    change.dotted_key(),
+                    table_cell(change.previous.as_deref()),
+                    table_cell(change.written.as_deref())
+                );
+            }
+        }
+        let summary = report.localized(Lang::En);
+        if !summary.trim().is_empty() {
+            let _ = write!(out, "\n## Report\n\n```text\n{}\n```\n", summary.trim_end());
+        }
+        out
+    }
+}
+
+fn table_cell(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("`{}`", value.replace('|', "\\|")),
+        None => "—".to_string(),
+    }
+}
+
+/// Reject paths that would escape the project root
+fn check_relative(path: &Path) -> Result<()> {
+    if path.as_os_str().is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        anyhow::bail!("Proposed file {:?} is outside the project", path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_manifest_roundtrip_and_changes() {
+        let temp = TempDir::new().unwrap();
+        let original = "[build]\njobs = 4\n";
+        let mut proposal = Proposal::new();
+        proposal.files.push(
+            ProposedFile::new(PathBuf::from(".cargo/config.toml"), Some(original), "[build]\njobs = 8\n").unwrap(),
+        );
+        let mut report = OptimizationReport::new();
+        report.applied("build.jobs", "8 cores");
+        proposal.save(temp.path(), &report).unwrap();
+
+        let loaded = Proposal::load(temp.path()).unwrap();
+        assert_eq!(loaded, proposal);
+        assert!(loaded.files[0].matches(Some(original)));
+        assert!(!loaded.files[0].matches(None));
+
+        let changes = fs::read_to_string(temp.path().join(CHANGES_FILE)).unwrap();
+        assert!(changes.contains("| `build.jobs` | `4` | `8` |"));
+        assert!(changes.contains("8 cores"));
+    }
+
+    #[test]
+    fn test_paths_must_stay_in_project() {
+        assert!(ProposedFile::new(PathBuf::from("../config.toml"), None, "").is_err());
+        assert!(ProposedFile::new(PathBuf::from("/etc/config.toml"), None, "").is_err());
+        assert!(ProposedFile::new(PathBuf::from(".config/nextest.toml"), None, "").is_ok());
+    }
+}
@@ -0,0 +1,414 @@
+//! Third-party optimization providers.
+//!
+//! Crates outside cargo-optimize add optimizations, a company-internal
+//! build cache for instance, by implementing [`OptimizationProvider`] and
+//! registering it in the build script before configuring:
+//!
+//! ```no_run
+//! use cargo_optimize::providers::{self, OptimizationProvider, ProviderAction, ProviderContext};
+//!
+//! struct AcmeCache;
+//!
+//! impl OptimizationProvider for AcmeCache {
+//!     fn name(&self) -> &str {
+//!         "acme-cache"
+//!     }
+//!
+//!     fn detect(&self, _context: &ProviderContext<'_>) -> Result<(), String> {
+//!         std::env::var("ACME_CACHE_URL").map(|_| ()).map_err(|_| "ACME_CACHE_URL is not set".to_string())
+//!     }
+//!
+//!     fn plan(&self, _context: &ProviderContext<'_>) -> Vec<ProviderAction> {
+//!         vec![ProviderAction::set(&["build", "rustc-wrapper"], "acme-cache", "shared company cache")]
+//!     }
+//! }
+//!
+//! providers::register(AcmeCache);
+//! cargo_optimize::auto_configure();
+//! ```
+//!
+//! The planner runs the providers after the built-in optimizations, highest
+//! [`priority`](OptimizationProvider::priority) first. A setting the
+//! built-ins changed, or an earlier provider claimed with another value, is
+//! not overwritten: the losing action is reported as skipped, naming the
+//! winner. Neither is a value already in the config without the provider's
+//! comment, which was set by hand. The deny list and the machine policy constrain provider settings
+//! like any other. Written values carry a `# provider: <name>` comment, are
+//! recorded in the apply journal, and are restored by an uninstall, which
+//! also calls [`revert`](OptimizationProvider::revert).
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::config::ConfigManager;
+use crate::hardware::HardwareInfo;
+use crate::linkers::Platform;
+use crate::report::{DecisionOutcome, OptimizationReport, SkipReason};
+
+/// What a provider sees of the project it optimizes
+#[derive(Debug, Clone, Copy)]
+pub struct ProviderContext<'a> {
+    /// Directory containing `.cargo/config.toml`
+    pub project_root: &'a Path,
+    /// Host platform, when cargo-optimize knows it
+    pub platform: Option<Platform>,
+    /// Detected hardware; `None` in the linker-only build script setup
+    pub hardware: Option<&'a HardwareInfo>,
+    /// The planned config, built-in optimizations included
+    pub config: &'a DocumentMut,
+}
+
+/// A config value a provider wants written
+#[derive(Debug, Clone)]
+pub struct ProviderAction {
+    /// Key path in `.cargo/config.toml` (e.g. `["env", "ACME_CACHE_URL"]`)
+    pub key: Vec<String>,
+    /// Value to write
+    pub value: Value,
+    /// Why, shown in the report
+    pub reason: String,
+}
+
+impl ProviderAction {
+    /// Set `key` to `value` for `reason`
+    pub fn set(key: &[&str], value: impl Into<Value>, reason: impl Into<String>) -> Self {
+        Self { key: key.iter().map(|k| k.to_string()).collect(), value: value.into(), reason: reason.into() }
+    }
+
+    /// Dotted form of the key, as used in reports
+    pub fn setting(&self) -> String {
+        self.key.join(".")
+    }
+}
+
+/// Actions of one provider that the planner applied
+#[derive(Debug, Clone)]
+pub struct ProviderOutcome {
+    /// Name of the provider
+    pub provider: String,
+    /// Its actions that were written, in planning order
+    pub actions: Vec<ProviderAction>,
+}
+
+/// An optimization contributed by a crate outside cargo-optimize
+pub trait OptimizationProvider: Send + Sync {
+    /// Unique name, shown in reports and config comments
+    fn name(&self) -> &str;
+
+    /// Whether the optimization can run here; `Err` says why not
+    fn detect(&self, context: &ProviderContext<'_>) -> Result<(), String>;
+
+    /// Config values the optimization needs
+    fn plan(&self, context: &ProviderContext<'_>) -> Vec<ProviderAction>;
+
+    /// Work beyond the config, run after it is written with the actions that were applied
+    fn apply(&self, _context: &ProviderContext<'_>, _applied: &[ProviderAction]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Undo the work of [`apply`](Self::apply); the config values are restored by the journal
+    fn revert(&self, _context: &ProviderContext<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Providers with a higher priority plan first and win conflicts between providers
+    fn priority(&self) -> i32 {
+        0
+    }
+}
+
+/// Shared handle to a registered provider
+pub type Provider = Arc<dyn OptimizationProvider>;
+
+fn registry() -> MutexGuard<'static, Vec<Provider>> {
+    static REGISTRY: OnceLock<Mutex<Vec<Provider>>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Register `provider` for every configuration run of this process
+///
+/// A provider registered again under the same name replaces the earlier one.
+pub fn register(provider: impl OptimizationProvider + 'static) {
+    register_shared(Arc::new(provider));
+}
+
+/// Register an already shared provider, see [`register`]
+pub fn register_shared(provider: Provider) {
+    let mut providers = registry();
+    providers.retain(|existing| existing.name() != provider.name());
+    providers.push(provider);
+}
+
+/// Remove the provider registered as `name`, returning whether there was one
+pub fn unregister(name: &str) -> bool {
+    let mut providers = registry();
+    let before = providers.len();
+    providers.retain(|provider| provider.name() != name);
+    providers.len() != before
+}
+
+/// Providers registered so far, in registration order
+pub fn registered() -> Vec<Provider> {
+    registry().clone()
+}
+
+/// Providers in planning order: highest priority first, then registration order
+fn ordered(providers: &[Provider]) -> Vec<Provider> {
+    let mut ordered = providers.to_vec();
+    ordered.sort_by_key(|provider| std::cmp::Reverse(provider.priority()));
+    ordered
+}
+
+/// Plan `providers` into `doc`, whose built-in changes over `original` take precedence
+///
+/// Applied and skipped actions are recorded in `report` as decisions, and
+/// the applied ones of each available provider in `report.providers`.
+pub fn plan(
+    providers: &[Provider],
+    project_root: &Path,
+    platform: Option<Platform>,
+    hardware: Option<&HardwareInfo>,
+    original: &DocumentMut,
+    doc: &mut DocumentMut,
+    report: &mut OptimizationReport,
+) {
+    let mut claimed: HashMap<String, (String, String)> = HashMap::new();
+    for provider in ordered(providers) {
+        let name = provider.name().to_string();
+        let planned = {
+            let context = ProviderContext { project_root, platform, hardware, config: doc };
+            if let Err(reason) = provider.detect(&context) {
+                report.skipped(format!("provider {}", name), SkipReason::NotNeeded, reason);
+                continue;
+            }
+            provider.plan(&context)
+        };
+
+        let mut kept = Vec::new();
+        for action in planned {
+            let setting = action.setting();
+            let value = action.value.to_string().trim().to_string();
+            let key: Vec<&str> = action.key.iter().map(String::as_str).collect();
+            let current = rendered(doc, &key);
+            let conflict = match claimed.get(&setting) {
+                Some((_, claimed_value)) if *claimed_value == value => None,
+                Some((owner, _)) => Some((SkipReason::Conflict, format!("conflicts with provider {}, which set it first", owner))),
+                // Built-in changes win; values from earlier runs are ours to replace
+                None if current.is_some() && current != rendered(original, &key) && current.as_deref() != Some(&value) => {
+                    Some((SkipReason::Conflict, "conflicts with a built-in optimization".to_string()))
+                }
+                None if current.is_some() && !written_by(original, &key, &name) => {
+                    Some((SkipReason::UserOptOut, "already set by hand in .cargo/config.toml".to_string()))
+                }
+                None => None,
+            };
+            if let Some((why, conflict)) = conflict {
+                report.skipped(setting, why, format!("{} ({})", conflict, name));
+                continue;
+            }
+            if !set(doc, &key, &action.value, &name) {
+                report.skipped(setting, SkipReason::Conflict, format!("a parent key is not a table ({})", name));
+                continue;
+            }
+            report.applied(setting.clone(), format!("{} ({})", action.reason, name));
+            claimed.insert(setting, (name.clone(), value));
+            kept.push(action);
+        }
+        report.providers.push(ProviderOutcome { provider: name, actions: kept });
+    }
+}
+
+/// Run [`apply`](OptimizationProvider::apply) of each provider planned in `report`
+///
+/// Actions skipped after planning, by a rolled back phase for instance, are
+/// left out. Failures are reported as skipped.
+pub fn apply(providers: &[Provider], context: &ProviderContext<'_>, report: &mut OptimizationReport) {
+    let mut failures = Vec::new();
+    for outcome in &report.providers {
+        let Some(provider) = providers.iter().find(|provider| provider.name() == outcome.provider) else {
+            continue;
+        };
+        let applied: Vec<ProviderAction> = outcome
+            .actions
+            .iter()
+            .filter(|action| {
+                report.decision_for(&action.setting()).is_some_and(|d| d.outcome == DecisionOutcome::Applied)
+            })
+            .cloned()
+            .collect();
+        if let Err(e) = provider.apply(context, &applied) {
+            failures.push((format!("provider {}", provider.name()), format!("apply failed: {:#}", e)));
+        }
+    }
+    for (setting, reason) in failures {
+        report.skipped(setting, SkipReason::Error, reason);
+    }
+}
+
+/// Run [`revert`](OptimizationProvider::revert) of every provider, reporting failures
+pub fn revert(providers: &[Provider], context: &ProviderContext<'_>, report: &mut OptimizationReport) {
+    for provider in ordered(providers) {
+        match provider.revert(context) {
+            Ok(()) => report.applied(format!("provider {}", provider.name()), "reverted"),
+            Err(e) => report.skipped(format!("provider {}", provider.name()), SkipReason::Error, format!("revert failed: {:#}", e)),
+        }
+    }
+}
+
+/// Plan the registered providers into the config at `config_path` and write it
+///
+/// Used by the build script setup, which configures the linker without the
+/// full planner; see [`ConfigManager::apply_providers`].
+pub fn apply_registered(config_path: &Path, platform: Option<Platform>) -> Result<OptimizationReport> {
+    if registered().is_empty() {
+        return Ok(OptimizationReport::new());
+    }
+    let project_root = config_path.parent().and_then(Path::parent).unwrap_or(Path::new("."));
+    ConfigManager::new_with_base_dir(project_root, "CARGO_OPTIMIZE_")?
+        .with_platform(platform)
+        .apply_providers()
+}
+
+/// Value at `key` as written, without comments
+fn rendered(doc: &DocumentMut, key: &[&str]) -> Option<String> {
+    let (first, rest) = key.split_first()?;
+    let mut item = doc.get(first)?;
+    for key in rest {
+        item = item.as_table_like()?.get(key)?;
+    }
+    item.as_value().map(|value| value.clone().decorated("", "").to_string())
+}
+
+/// Whether the value at `key` carries the `# provider: <provider>` comment
+fn written_by(doc: &DocumentMut, key: &[&str], provider: &str) -> bool {
+    let Some((first, rest)) = key.split_first() else {
+        return false;
+    };
+    let mut item = doc.get(first);
+    for key in rest {
+        item = item.and_then(Item::as_table_like).and_then(|table| table.get(key));
+    }
+    item.and_then(Item::as_value)
+        .and_then(|value| value.decor().suffix())
+        .and_then(|suffix| suffix.as_str())
+        .is_some_and(|suffix| suffix.trim() == format!("# provider: {}", provider))
+}
+
+/// Write `value` at `key`, noting the provider; `false` when a parent is not a table
+fn set(doc: &mut DocumentMut, key: &[&str], value: &Value, provider: &str) -> bool {
+    let Some((leaf, parents)) = key.split_last() else {
+        return false;
+    };
+    let mut table = doc.as_table_mut();
+    for key in parents {
+        let entry = table.entry(key).or_insert_with(|| {
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        match entry.as_table_mut() {
+            Some(next) => table = next,
+            None => return false,
+        }
+    }
+    let value = value.clone().decorated(" ", format!(" # provider: {}", provider));
+    table.insert(leaf, Item::Value(value));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed {
+        name: &'static str,
+        priority: i32,
+        actions: Vec<(&'static str, &'static str)>,
+        available: bool,
+    }
+
+    impl OptimizationProvider for Fixed {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn detect(&self, _context: &ProviderContext<'_>) -> Result<(), String> {
+            if self.available {
+                Ok(())
+            } else {
+                Err("not reachable".to_string())
+            }
+        }
+
+        fn plan(&self, _context: &ProviderContext<'_>) -> Vec<ProviderAction> {
+            self.actions
+                .iter()
+                .map(|(key, value)| {
+                    let key: Vec<&str> = key.split('.').collect();
+                    ProviderAction::set(&key, *value, "test")
+                })
+                .collect()
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+    }
+
+    fn provider(name: &'static str, priority: i32, actions: Vec<(&'static str, &'static str)>) -> Provider {
+        Arc::new(Fixed { name, priority, actions, available: true })
+    }
+
+    #[test]
+    fn test_plan_orders_and_deconflicts() {
+        let original: DocumentMut = "[env]\nACME = \"old\" # provider: high\nMINE = \"1\"\n".parse().unwrap();
+        let mut doc = original.clone();
+        // A built-in optimization chose the wrapper in this plan
+        doc["build"]["rustc-wrapper"] = toml_edit::value("sccache");
+        let providers = vec![
+            provider("low", 0, vec![("env.ACME", "low"), ("env.SHARED", "x"), ("env.MINE", "2")]),
+            provider("high", 10, vec![("env.ACME", "high"), ("build.rustc-wrapper", "acme"), ("env.SHARED", "x")]),
+            Arc::new(Fixed { name: "offline", priority: 20, actions: vec![("env.OFF", "1")], available: false }),
+        ];
+        let mut report = OptimizationReport::new();
+        plan(&providers, Path::new("."), None, None, &original, &mut doc, &mut report);
+
+        let applied = &report.providers;
+        assert_eq!(applied.iter().map(|o| o.provider.as_str()).collect::<Vec<_>>(), ["high", "low"]);
+        assert_eq!(doc["env"]["ACME"].as_str(), Some("high"));
+        assert_eq!(doc["build"]["rustc-wrapper"].as_str(), Some("sccache"));
+        assert!(doc.to_string().contains("ACME = \"high\" # provider: high"));
+        assert!(doc["env"].get("OFF").is_none());
+
+        let skipped: Vec<_> = report.skipped_decisions().map(|d| (d.setting.as_str(), d.reason.as_str())).collect();
+        assert_eq!(
+            skipped,
+            [
+                ("provider offline", "not reachable"),
+                ("build.rustc-wrapper", "conflicts with a built-in optimization (high)"),
+                ("env.ACME", "conflicts with provider high, which set it first (low)"),
+                ("env.MINE", "already set by hand in .cargo/config.toml (low)"),
+            ]
+        );
+        // Agreeing providers both count as applied
+        assert_eq!(applied[1].actions.len(), 1);
+        assert_eq!(applied[1].actions[0].setting(), "env.SHARED");
+    }
+
+    #[test]
+    fn test_registry_replaces_by_name() {
+        register_shared(provider("registry-test", 0, vec![]));
+        register_shared(provider("registry-test", 5, vec![]));
+        let found: Vec<_> = registered().into_iter().filter(|p| p.name() == "registry-test").collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].priority(), 5);
+        assert!(unregister("registry-test"));
+        assert!(!unregister("registry-test"));
+    }
+}
@@ -0,0 +1,224 @@
+//! Removing everything cargo-optimize left behind.
+//!
+//! [`ConfigManager::uninstall`](crate::config::ConfigManager::uninstall)
+//! restores the settings cargo-optimize wrote, but the files it keeps for
+//! itself stay: the state and metrics files next to the config with their
+//! lock, previous and quarantined snapshots, config backups, the metadata
+//! cache and build-weight measurements in the target directory, a staged
+//! proposal, the audit log and downloaded linkers.
+//! [`ConfigManager::purge`](crate::config::ConfigManager::purge) lists them
+//! as [`Artifact`]s and, unless it is a dry run, uninstalls and removes them.
+//! Backups are removed file by file, so a backup directory shared with other
+//! files is left in place. Downloaded linkers are shared by every project of
+//! the user; only the release directories cargo-optimize unpacked are
+//! removed, and the tools directory only once it is empty.
+
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::hardware::directory_size;
+use crate::report::OptimizationReport;
+use crate::store::{self, CORRUPT_EXTENSION, LOCK_EXTENSION, PREVIOUS_EXTENSION};
+
+/// What an artifact is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ArtifactKind {
+    /// Settings cargo-optimize wrote into the cargo config
+    Settings,
+    /// The drift check in a git hook
+    GitHook,
+    /// The state file and its snapshots
+    State,
+    /// The metrics history and its snapshots
+    Metrics,
+    /// Backups of the cargo config
+    Backups,
+    /// Cached metadata and build-weight measurements in the target directory
+    AnalysisCache,
+    /// A proposal staged for review
+    Proposal,
+    /// The audit log
+    AuditLog,
+    /// Linkers downloaded into the tools directory
+    ToolDownloads,
+}
+
+impl ArtifactKind {
+    /// Short description for listings
+    pub fn label(&self) -> &'static str {
+        match self {
+            ArtifactKind::Settings => "managed settings",
+            ArtifactKind::GitHook => "git hook drift check",
+            ArtifactKind::State => "state",
+            ArtifactKind::Metrics => "metrics history",
+            ArtifactKind::Backups => "config backup",
+            ArtifactKind::AnalysisCache => "analysis cache",
+            ArtifactKind::Proposal => "staged proposal",
+            ArtifactKind::AuditLog => "audit log",
+            ArtifactKind::ToolDownloads => "downloaded linkers (shared by all projects)",
+        }
+    }
+
+    /// Whether purging edits the file instead of removing it
+    pub fn is_edit(&self) -> bool {
+        matches!(self, ArtifactKind::Settings | ArtifactKind::GitHook)
+    }
+}
+
+/// A file or directory cargo-optimize created, or one it wrote into
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Artifact {
+    /// What it is
+    pub kind: ArtifactKind,
+    /// Where it is
+    pub path: PathBuf,
+    /// Size on disk in bytes; 0 for edits
+    pub bytes: u64,
+}
+
+impl Artifact {
+    /// The file or directory at `path`, if there is one
+    pub fn at(kind: ArtifactKind, path: &Path) -> Option<Self> {
+        fs::symlink_metadata(path).ok()?;
+        let bytes = if kind.is_edit() { 0 } else { directory_size(path) };
+        Some(Artifact { kind, path: path.to_path_buf(), bytes })
+    }
+}
+
+/// A stored file (see [`crate::store`]) with its lock, previous and quarantined snapshots
+pub fn stored(kind: ArtifactKind, path: &Path) -> Vec<Artifact> {
+    let mut paths =
+        vec![path.to_path_buf(), store::sidecar(path, LOCK_EXTENSION), store::sidecar(path, PREVIOUS_EXTENSION)];
+    let corrupt = store::sidecar(path, CORRUPT_EXTENSION);
+    let prefix = corrupt.file_name().map(|name| format!("{}-", name.to_string_lossy())).unwrap_or_default();
+    if let (Some(dir), false) = (path.parent(), prefix.is_empty()) {
+        let mut quarantined: Vec<PathBuf> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+            .map(|entry| entry.path())
+            .collect();
+        quarantined.sort();
+        paths.extend(quarantined);
+    }
+    paths.iter().filter_map(|path| Artifact::at(kind, path)).collect()
+}
+
+/// What a purge removed, or would remove
+#[derive(Debug, Clone, Default)]
+pub struct PurgeSummary {
+    /// Nothing was changed; `artifacts` is what would be
+    pub dry_run: bool,
+    /// Artifacts removed or edited
+    pub artifacts: Vec<Artifact>,
+    /// Artifacts that could not be removed, with the error
+    pub failed: Vec<(Artifact, String)>,
+    /// Outcome of restoring the settings; empty on a dry run
+    pub uninstall: OptimizationReport,
+}
+
+impl PurgeSummary {
+    /// Bytes freed, or that would be
+    pub fn bytes(&self) -> u64 {
+        self.artifacts.iter().map(|artifact| artifact.bytes).sum()
+    }
+}
+
+impl fmt::Display for PurgeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.artifacts.is_empty() && self.failed.is_empty() {
+            return writeln!(f, "Nothing of cargo-optimize is left");
+        }
+        writeln!(f, "{}:", if self.dry_run { "cargo-optimize would remove" } else { "Removed" })?;
+        for artifact in &self.artifacts {
+            if artifact.kind.is_edit() {
+                writeln!(f, "  {}: its part of {}", artifact.kind.label(), artifact.path.display())?;
+            } else {
+                writeln!(
+                    f,
+                    "  {}: {} ({})",
+                    artifact.kind.label(),
+                    artifact.path.display(),
+                    megabytes(artifact.bytes)
+                )?;
+            }
+        }
+        writeln!(f, "{} item(s), {}", self.artifacts.len(), megabytes(self.bytes()))?;
+        for (artifact, error) in &self.failed {
+            writeln!(f, "failed to remove {}: {}", artifact.path.display(), error)?;
+        }
+        Ok(())
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Remove the artifacts that are not edits, then the directories in `prune` that are left empty
+///
+/// Artifacts already gone count as removed.
+pub fn remove(artifacts: Vec<Artifact>, prune: &[PathBuf], summary: &mut PurgeSummary) {
+    for artifact in artifacts {
+        let removed = if artifact.kind.is_edit() {
+            Ok(())
+        } else if artifact.path.is_dir() {
+            fs::remove_dir_all(&artifact.path)
+        } else {
+            fs::remove_file(&artifact.path)
+        };
+        match removed {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => summary.failed.push((artifact, e.to_string())),
+            _ => summary.artifacts.push(artifact),
+        }
+    }
+    for dir in prune {
+        // Only succeeds for an empty directory
+        if fs::remove_dir(dir).is_ok() {
+            debug!("Removed empty directory {:?}", dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("cargo-optimize-state.json");
+        for name in [
+            "cargo-optimize-state.json",
+            "cargo-optimize-state.json.lock",
+            "cargo-optimize-state.json.corrupt-1700000000",
+            "cargo-optimize-metrics.json",
+        ] {
+            fs::write(dir.path().join(name), "{}").unwrap();
+        }
+        let artifacts = stored(ArtifactKind::State, &state);
+        let names: Vec<_> = artifacts.iter().map(|artifact| artifact.path.file_name().unwrap().to_owned()).collect();
+        assert_eq!(
+            names,
+            [
+                "cargo-optimize-state.json",
+                "cargo-optimize-state.json.lock",
+                "cargo-optimize-state.json.corrupt-1700000000"
+            ]
+        );
+
+        let mut summary = PurgeSummary::default();
+        remove(artifacts, &[dir.path().to_path_buf()], &mut summary);
+        assert_eq!(summary.artifacts.len(), 3);
+        assert_eq!(summary.bytes(), 6);
+        assert!(summary.to_string().starts_with("Removed:\n  state: "), "{}", summary);
+        // The metrics file keeps the directory
+        assert!(dir.path().join("cargo-optimize-metrics.json").is_file());
+        assert!(!state.exists());
+    }
+}
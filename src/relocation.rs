@@ -0,0 +1,310 @@
+//! Carrying build artifacts over to a relocated target directory.
+//!
+//! Switching to per-profile or per-group target directories points cargo at
+//! empty directories, so the first build after an apply compiles everything
+//! again. A cargo target directory holds one output directory per profile
+//! (`debug`, `release`, a custom profile, or `<triple>/<profile>` when
+//! cross-compiling), each with its `.fingerprint`, `build` and `deps`
+//! directories. Fingerprints compare modification times, so a
+//! [`Relocation`] carries an output directory over as a whole with the
+//! times preserved. [`migrate`] validates every carried directory and rolls
+//! all of them back if one fails.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::paths::{io_context, long_path};
+
+/// Directory cargo keeps unit fingerprints in, inside every profile output directory
+const FINGERPRINT_DIR: &str = ".fingerprint";
+
+/// How build artifacts reach a relocated target directory
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MigrationMode {
+    /// Leave build artifacts where they are
+    Off,
+    /// Copy them, so builds without the new layout keep theirs
+    #[default]
+    Copy,
+    /// Move them; falls back to copying across filesystems
+    Move,
+}
+
+/// One profile output directory to carry over
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relocation {
+    /// Output directory in the old target directory (e.g. `target/debug`)
+    pub from: PathBuf,
+    /// Output directory in the new one (e.g. `target/dev/debug`)
+    pub to: PathBuf,
+    /// Whether the old directory is copied or moved
+    pub mode: MigrationMode,
+}
+
+/// Files and bytes below a directory, compared before and after migrating
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeSize {
+    /// Regular files
+    pub files: u64,
+    /// Total size of the files
+    pub bytes: u64,
+}
+
+impl TreeSize {
+    /// Measure the regular files below `path`; symlinks are not followed
+    pub fn measure(path: &Path) -> TreeSize {
+        let mut size = TreeSize::default();
+        let Ok(entries) = fs::read_dir(path) else { return size };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_dir() {
+                let nested = TreeSize::measure(&entry.path());
+                size.files += nested.files;
+                size.bytes += nested.bytes;
+            } else if file_type.is_file() {
+                size.files += 1;
+                size.bytes += entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            }
+        }
+        size
+    }
+}
+
+/// Outcome of a migration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migration {
+    /// Directories carried over, with what they contain
+    pub migrated: Vec<(Relocation, TreeSize)>,
+    /// Directories left alone, with the reason
+    pub skipped: Vec<(Relocation, String)>,
+}
+
+impl Migration {
+    /// Total size of the carried directories
+    pub fn size(&self) -> TreeSize {
+        self.migrated.iter().fold(TreeSize::default(), |total, (_, size)| TreeSize {
+            files: total.files + size.files,
+            bytes: total.bytes + size.bytes,
+        })
+    }
+}
+
+/// Profile output directories of a target directory, relative to it
+///
+/// Host outputs (`debug`) come first, then cross-compiled ones
+/// (`<triple>/debug`). Top-level directories in `exclude` are not searched
+/// for cross-compiled outputs, so new target directories nested in the old
+/// one are not mistaken for target triples.
+pub fn output_dirs(target_dir: &Path, exclude: &[PathBuf]) -> Vec<PathBuf> {
+    let children = |dir: &Path| -> Vec<PathBuf> {
+        let mut children: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| entries.flatten().filter(|entry| entry.path().is_dir()).map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        children.sort();
+        children
+    };
+
+    let mut host = Vec::new();
+    let mut cross = Vec::new();
+    for child in children(target_dir) {
+        let Ok(relative) = child.strip_prefix(target_dir) else { continue };
+        if child.join(FINGERPRINT_DIR).is_dir() {
+            host.push(relative.to_path_buf());
+        } else if !exclude.iter().any(|excluded| excluded == relative) {
+            cross.extend(
+                children(&child)
+                    .into_iter()
+                    .filter(|nested| nested.join(FINGERPRINT_DIR).is_dir())
+                    .filter_map(|nested| nested.strip_prefix(target_dir).ok().map(Path::to_path_buf)),
+            );
+        }
+    }
+    host.extend(cross);
+    host
+}
+
+/// Carry every relocation over, validating each and rolling all back on failure
+///
+/// A relocation is skipped when its source holds no fingerprints, its
+/// destination lies inside it, or the destination exists already, since
+/// cargo has then built into it. After
+/// copying or moving, the destination must hold the same files and bytes
+/// the source held; otherwise, or if anything fails midway, every directory
+/// carried so far is restored and the error returned.
+pub fn migrate(relocations: &[Relocation]) -> Result<Migration> {
+    let mut migration = Migration::default();
+    for relocation in relocations {
+        let skip = if relocation.mode == MigrationMode::Off {
+            Some("migration is off")
+        } else if relocation.to.starts_with(&relocation.from) {
+            Some("destination is inside the source")
+        } else if !relocation.from.join(FINGERPRINT_DIR).is_dir() {
+            Some("no build artifacts")
+        } else if relocation.to.exists() {
+            Some("destination already has build artifacts")
+        } else {
+            None
+        };
+        if let Some(reason) = skip {
+            migration.skipped.push((relocation.clone(), reason.to_string()));
+            continue;
+        }
+
+        match carry(relocation) {
+            Ok(size) => migration.migrated.push((relocation.clone(), size)),
+            Err(e) => {
+                for (done, _) in migration.migrated.iter().rev() {
+                    if let Err(undo) = restore(done) {
+                        warn!("Failed to roll back {}: {:#}", done.to.display(), undo);
+                    }
+                }
+                return Err(e).with_context(|| {
+                    format!("Failed to migrate {} to {}", relocation.from.display(), relocation.to.display())
+                });
+            }
+        }
+    }
+    Ok(migration)
+}
+
+/// Copy or move one output directory and validate the result
+fn carry(relocation: &Relocation) -> Result<TreeSize> {
+    // Output directories nest deep enough to pass MAX_PATH on Windows
+    let (from, to) = (long_path(&relocation.from), long_path(&relocation.to));
+    let expected = TreeSize::measure(&from);
+    let parent = relocation.to.parent().context("destination has no parent directory")?;
+    io_context(fs::create_dir_all(long_path(parent)), "create", parent)?;
+
+    let moved = relocation.mode == MigrationMode::Move && fs::rename(&from, &to).is_ok();
+    if !moved {
+        if let Err(e) = copy_tree(&from, &to) {
+            let _ = fs::remove_dir_all(&to);
+            return io_context(Err(e), "copy", &relocation.from);
+        }
+    }
+
+    let actual = TreeSize::measure(&to);
+    if actual != expected {
+        restore(relocation)?;
+        bail!(
+            "{} holds {} files ({} bytes) after migrating, expected {} ({} bytes)",
+            relocation.to.display(),
+            actual.files,
+            actual.bytes,
+            expected.files,
+            expected.bytes
+        );
+    }
+    if relocation.mode == MigrationMode::Move && !moved {
+        io_context(fs::remove_dir_all(&from), "remove the copied", &relocation.from)?;
+    }
+    debug!("Migrated {} to {} ({} files)", relocation.from.display(), relocation.to.display(), actual.files);
+    Ok(actual)
+}
+
+/// Undo one carried relocation: drop the copy, or move the directory back
+fn restore(relocation: &Relocation) -> Result<()> {
+    let (from, to) = (long_path(&relocation.from), long_path(&relocation.to));
+    if relocation.mode == MigrationMode::Move && !from.exists() {
+        if fs::rename(&to, &from).is_err() {
+            io_context(copy_tree(&to, &from), "move back", &relocation.to)?;
+        } else {
+            return Ok(());
+        }
+    }
+    io_context(fs::remove_dir_all(&to), "remove", &relocation.to)
+}
+
+/// Copy a directory tree, keeping modification times so fingerprints stay fresh
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let destination = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &destination)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &destination)?;
+            let modified = entry.metadata()?.modified()?;
+            File::options().write(true).open(&destination)?.set_modified(modified)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn build_output(dir: &Path) {
+        fs::create_dir_all(dir.join(".fingerprint/demo-1a2b")).unwrap();
+        fs::create_dir_all(dir.join("deps")).unwrap();
+        fs::write(dir.join(".fingerprint/demo-1a2b/lib-demo"), "0123abcd").unwrap();
+        fs::write(dir.join("deps/libdemo-1a2b.rlib"), vec![0u8; 512]).unwrap();
+    }
+
+    #[test]
+    fn test_output_dirs_and_copy_keeps_mtimes() {
+        let temp = tempfile::tempdir().unwrap();
+        let target = temp.path().join("target");
+        build_output(&target.join("debug"));
+        build_output(&target.join("wasm32-unknown-unknown/release"));
+        build_output(&target.join("dev/debug"));
+        fs::create_dir_all(target.join("doc")).unwrap();
+
+        assert_eq!(
+            output_dirs(&target, &[PathBuf::from("dev")]),
+            vec![PathBuf::from("debug"), PathBuf::from("wasm32-unknown-unknown/release")]
+        );
+
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(target.join("debug/deps/libdemo-1a2b.rlib"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let relocation = |from: &str, to: &str| Relocation {
+            from: target.join(from),
+            to: target.join(to),
+            mode: MigrationMode::Copy,
+        };
+        let migration = migrate(&[relocation("debug", "test/debug"), relocation("release", "bench/release")]).unwrap();
+
+        assert_eq!(migration.migrated.len(), 1);
+        assert_eq!(migration.size(), TreeSize { files: 2, bytes: 520 });
+        assert_eq!(migration.skipped[0].1, "no build artifacts");
+        assert!(target.join("debug/.fingerprint").is_dir());
+        let copied = fs::metadata(target.join("test/debug/deps/libdemo-1a2b.rlib")).unwrap();
+        assert_eq!(copied.modified().unwrap(), old);
+
+        // A second run finds the destination built and leaves it alone
+        let migration = migrate(&[relocation("debug", "test/debug")]).unwrap();
+        assert_eq!(migration.skipped[0].1, "destination already has build artifacts");
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        let temp = tempfile::tempdir().unwrap();
+        build_output(&temp.path().join("a/debug"));
+        build_output(&temp.path().join("b/debug"));
+        // The second destination's parent is a file, so it cannot be created
+        fs::write(temp.path().join("blocked"), "").unwrap();
+
+        let result = migrate(&[
+            Relocation { from: temp.path().join("a/debug"), to: temp.path().join("new/debug"), mode: MigrationMode::Move },
+            Relocation { from: temp.path().join("b/debug"), to: temp.path().join("blocked/debug"), mode: MigrationMode::Copy },
+        ]);
+
+        assert!(result.is_err());
+        assert!(!temp.path().join("new/debug").exists());
+        assert_eq!(TreeSize::measure(&temp.path().join("a/debug")), TreeSize { files: 2, bytes: 520 });
+    }
+}
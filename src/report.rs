@@ -0,0 +1,515 @@
+//! Optimization report describing what cargo-optimize decided and why.
+//!
+//! Every apply run produces an [`OptimizationReport`] listing the decisions
+//! taken for each setting along with any recommendations for the user.
+//! [`ci_artifact`] produces the same findings without applying anything, as
+//! JSON and markdown files for CI to upload. [`snapshot`] and [`compare`]
+//! explain why the same project builds faster on one machine than another.
+
+use serde::Serialize;
+use std::fmt::{self, Write as _};
+use std::path::PathBuf;
+
+use crate::advisor::{Advice, Advisor};
+use crate::explain;
+use crate::i18n::{Lang, Message};
+use crate::phases::{PhaseOutcome, PhaseStatus};
+use crate::providers::ProviderOutcome;
+use crate::relocation::Relocation;
+use crate::secrets;
+use crate::state::{JobsAdjustment, SharedRustflags};
+use crate::strict::UnmetRequirement;
+
+pub mod ci;
+pub mod snapshot;
+
+pub use ci::{ci_artifact, CiArtifact};
+pub use snapshot::{compare, snapshot, Snapshot, SnapshotComparison};
+
+/// Most skipped decisions a rendered report lists; the rest are only counted
+pub const MAX_SKIPPED_DECISIONS: usize = 100;
+
+/// Outcome of a single optimization decision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DecisionOutcome {
+    /// The setting was written/enabled
+    Applied,
+    /// The setting was deliberately not enabled
+    Skipped,
+    /// The setting was not generated because the team policy denies it
+    Suppressed,
+}
+
+/// Why a setting was considered but not written
+///
+/// Every skipped and suppressed [`Decision`] carries one, so a report can
+/// answer "why didn't it do X?" without parsing the reason text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// The toolchain is too old, or not nightly
+    UnsupportedToolchain,
+    /// The platform or target cannot use it
+    UnsupportedPlatform,
+    /// The machine policy, the team policy or the rules for secrets forbid it
+    Policy,
+    /// The user's config or settings turned it off, or set it by hand
+    UserOptOut,
+    /// A tool it needs is not installed or not reachable
+    MissingTool,
+    /// Where cargo-optimize runs: an IDE, a running build or the environment
+    Context,
+    /// Nothing needed doing
+    NotNeeded,
+    /// Another setting or tool already controls it
+    Conflict,
+    /// It was written, then rolled back
+    RolledBack,
+    /// Too little disk space or memory
+    Resources,
+    /// An error or an invalid setting stopped it
+    Error,
+}
+
+impl SkipReason {
+    /// Stable identifier used in reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            SkipReason::UnsupportedToolchain => "unsupported-toolchain",
+            SkipReason::UnsupportedPlatform => "unsupported-platform",
+            SkipReason::Policy => "policy",
+            SkipReason::UserOptOut => "user-opt-out",
+            SkipReason::MissingTool => "missing-tool",
+            SkipReason::Context => "context",
+            SkipReason::NotNeeded => "not-needed",
+            SkipReason::Conflict => "conflict",
+            SkipReason::RolledBack => "rolled-back",
+            SkipReason::Resources => "resources",
+            SkipReason::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// A decision taken for one setting during apply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Decision {
+    /// The setting the decision refers to (e.g. "sccache", "profile.dev.incremental")
+    pub setting: String,
+    /// What happened to the setting
+    pub outcome: DecisionOutcome,
+    /// Human-readable reason for the decision
+    pub reason: String,
+    /// Why the setting was not written; `None` for applied settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<SkipReason>,
+}
+
+/// A directory that could be cleaned up to free disk space
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanupCandidate {
+    /// Short label (e.g. "target directory")
+    pub label: String,
+    /// Location on disk
+    pub path: PathBuf,
+    /// Measured size in bytes
+    pub size_bytes: u64,
+}
+
+/// A rustup component needed by a setting that was applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentRequirement {
+    /// Component name (e.g. "rust-src")
+    pub component: String,
+    /// The setting or feature that needs it
+    pub needed_for: String,
+}
+
+/// Disk cost of a target-dir layout that trades space for fewer rebuilds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskTradeoff {
+    /// Layout the estimate refers to (e.g. "per-profile target dirs")
+    pub layout: String,
+    /// Measured size of the current target directory in bytes
+    pub current_bytes: u64,
+    /// Estimated additional bytes once every separate dir is populated
+    pub estimated_extra_bytes: u64,
+    /// Target directories the layout builds into
+    pub target_dirs: Vec<PathBuf>,
+}
+
+/// Where the effective target directory is configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetDirSource {
+    /// The `CARGO_TARGET_DIR` environment variable, which outranks the config
+    Environment,
+    /// `build.target-dir` in `.cargo/config.toml`
+    Config,
+    /// Cargo's default `<project>/target`
+    Default,
+}
+
+/// Target directory cargo will build into
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveTargetDir {
+    /// Absolute path of the target directory
+    pub path: PathBuf,
+    /// Where it is configured
+    pub source: TargetDirSource,
+}
+
+/// A setting where the project asked for something the machine policy forbids
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyConflict {
+    /// The setting in conflict (e.g. "linker", "target.x86_64-unknown-linux-gnu.rustflags")
+    pub setting: String,
+    /// What was requested and how the policy resolved it
+    pub reason: String,
+}
+
+/// An interaction with other build tooling detected while planning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolConflict {
+    /// The tool (e.g. "cargo-chef", "RUSTC_WRAPPER")
+    pub tool: String,
+    /// What interacts and how planning adapted
+    pub reason: String,
+}
+
+/// A Dockerfile whose build throws cargo's caches away
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerBuild {
+    /// Path relative to the project root
+    pub dockerfile: PathBuf,
+    /// What to change, one item per finding
+    pub advice: Vec<String>,
+    /// Build stage keeping the dependency layer and cargo's caches
+    pub snippet: String,
+}
+
+/// Report of an optimization run
+///
+/// Reasons and recommendations are recorded with secrets redacted (see [`secrets::redact`]).
+#[derive(Debug, Clone, Default)]
+pub struct OptimizationReport {
+    /// Decisions taken for individual settings
+    pub decisions: Vec<Decision>,
+    /// Recommendations for the user that were not acted upon automatically
+    pub recommendations: Vec<String>,
+    /// Directories that could be cleaned to reclaim disk space
+    pub cleanup_candidates: Vec<CleanupCandidate>,
+    /// Toolchain components the applied settings depend on
+    pub required_components: Vec<ComponentRequirement>,
+    /// Disk overhead of separate target directories
+    pub disk_tradeoffs: Vec<DiskTradeoff>,
+    /// Target directory builds use with the planned config
+    pub effective_target_dir: Option<EffectiveTargetDir>,
+    /// Settings overridden or flagged by the machine policy
+    pub policy_conflicts: Vec<PolicyConflict>,
+    /// Other build tooling the plan works around
+    pub tool_conflicts: Vec<ToolConflict>,
+    /// Dockerfiles running cargo without reusing its caches
+    pub container_builds: Vec<ContainerBuild>,
+    /// Build outputs to carry into the target directories of a new layout
+    pub relocations: Vec<Relocation>,
+    /// Advisor suggestions in the report, for rejecting them
+    pub advice: Vec<Advice>,
+    /// Advice left out because the user declined it earlier
+    pub rejected_advice: Vec<Advice>,
+    /// Outcome of each apply phase, empty when nothing was applied
+    pub phases: Vec<PhaseOutcome>,
+    /// Lock of the running cargo build the apply was deferred for, if any
+    pub deferred_by: Option<PathBuf>,
+    /// Optimizations `global.required` asks for that the applied config lacks
+    pub unmet_requirements: Vec<UnmetRequirement>,
+    /// Actions of third-party providers written alongside the built-ins
+    pub providers: Vec<ProviderOutcome>,
+    /// Job count adapted to system load, recorded once it is applied
+    pub jobs_adjustment: Option<JobsAdjustment>,
+    /// Flags the stable fallback added to a target's rustflags, recorded once applied
+    pub shared_rustflags: Option<SharedRustflags>,
+}
+
+impl OptimizationReport {
+    /// Create an empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a setting was applied
+    pub fn applied(&mut self, setting: impl Into<String>, reason: impl Into<String>) {
+        self.decisions.push(Decision {
+            setting: setting.into(),
+            outcome: DecisionOutcome::Applied,
+            reason: secrets::redact(&reason.into()),
+            skip_reason: None,
+        });
+    }
+
+    /// Record that a setting was skipped, and why
+    pub fn skipped(&mut self, setting: impl Into<String>, why: SkipReason, reason: impl Into<String>) {
+        self.decisions.push(Decision {
+            setting: setting.into(),
+            outcome: DecisionOutcome::Skipped,
+            reason: secrets::redact(&reason.into()),
+            skip_reason: Some(why),
+        });
+    }
+
+    /// Record that a setting denied by the team policy was not generated
+    ///
+    /// Replaces earlier decisions for the setting, which no longer hold.
+    pub fn suppressed(&mut self, setting: impl Into<String>, reason: impl Into<String>) {
+        let setting = setting.into();
+        self.decisions.retain(|decision| decision.setting != setting);
+        self.decisions.push(Decision {
+            setting,
+            outcome: DecisionOutcome::Suppressed,
+            reason: secrets::redact(&reason.into()),
+            skip_reason: Some(SkipReason::Policy),
+        });
+    }
+
+    /// Record a conflict with the machine policy
+    pub fn conflict(&mut self, setting: impl Into<String>, reason: impl Into<String>) {
+        self.policy_conflicts.push(PolicyConflict {
+            setting: setting.into(),
+            reason: reason.into(),
+        });
+    }
+
+    /// Record an interaction with another build tool
+    pub fn tool_conflict(&mut self, tool: impl Into<String>, reason: impl Into<String>) {
+        self.tool_conflicts.push(ToolConflict {
+            tool: tool.into(),
+            reason: reason.into(),
+        });
+    }
+
+    /// Record guidance for a Dockerfile running cargo
+    pub fn container_build(&mut self, dockerfile: impl Into<PathBuf>, advice: Vec<String>, snippet: impl Into<String>) {
+        self.container_builds.push(ContainerBuild {
+            dockerfile: dockerfile.into(),
+            advice,
+            snippet: snippet.into(),
+        });
+    }
+
+    /// Add a recommendation for the user
+    pub fn recommend(&mut self, recommendation: impl Into<String>) {
+        self.recommendations.push(secrets::redact(&recommendation.into()));
+    }
+
+    /// Recommend the advice of `advisor`, noting what it left out as declined
+    pub fn advise(&mut self, advisor: &Advisor) {
+        for advice in advisor.advice() {
+            self.recommend(advice.to_string());
+            self.advice.push(advice.clone());
+        }
+        self.note_rejected(advisor);
+    }
+
+    /// Note the advice `advisor` left out because the user declined it earlier
+    pub fn note_rejected(&mut self, advisor: &Advisor) {
+        self.rejected_advice.extend(advisor.rejected().iter().cloned());
+    }
+
+    /// Record that an applied setting needs a rustup component
+    pub fn require_component(&mut self, component: impl Into<String>, needed_for: impl Into<String>) {
+        self.required_components.push(ComponentRequirement {
+            component: component.into(),
+            needed_for: needed_for.into(),
+        });
+    }
+
+    /// Get all skipped decisions
+    pub fn skipped_decisions(&self) -> impl Iterator<Item = &Decision> {
+        self.decisions
+            .iter()
+            .filter(|d| d.outcome == DecisionOutcome::Skipped)
+    }
+
+    /// Get the skipped and suppressed decisions with the given reason
+    pub fn skipped_because(&self, why: SkipReason) -> impl Iterator<Item = &Decision> {
+        self.decisions.iter().filter(move |d| d.skip_reason == Some(why))
+    }
+
+    /// Find the decision recorded for a setting, if any
+    pub fn decision_for(&self, setting: &str) -> Option<&Decision> {
+        self.decisions.iter().find(|d| d.setting == setting)
+    }
+}
+
+/// The decisions to list, and how many skipped ones are left out
+///
+/// Past [`MAX_SKIPPED_DECISIONS`] skipped decisions are only counted, except
+/// rollbacks and policy skips, which are always listed like applied ones.
+pub fn bounded(decisions: &[Decision]) -> (Vec<&Decision>, usize) {
+    let mut listed = Vec::new();
+    let mut skipped = 0;
+    let mut more_skipped = 0;
+    for decision in decisions {
+        let capped = decision.outcome == DecisionOutcome::Skipped
+            && !matches!(decision.skip_reason, Some(SkipReason::RolledBack) | Some(SkipReason::Policy));
+        if capped {
+            if skipped == MAX_SKIPPED_DECISIONS {
+                more_skipped += 1;
+                continue;
+            }
+            skipped += 1;
+        }
+        listed.push(decision);
+    }
+    (listed, more_skipped)
+}
+
+impl OptimizationReport {
+    /// Render the report with headings in `lang`
+    pub fn localized(&self, lang: Lang) -> String {
+        let mut out = String::new();
+        if !self.decisions.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportDecisions));
+            let (decisions, more_skipped) = bounded(&self.decisions);
+            for decision in decisions {
+                let marker = match decision.outcome {
+                    DecisionOutcome::Applied => lang.text(Message::ReportApplied),
+                    DecisionOutcome::Skipped => lang.text(Message::ReportSkipped),
+                    DecisionOutcome::Suppressed => lang.text(Message::ReportSuppressed),
+                };
+                match decision.skip_reason {
+                    Some(why) if decision.outcome == DecisionOutcome::Skipped => {
+                        let _ = write!(out, "  - {} [{}: {}]: {}", decision.setting, marker, why, decision.reason);
+                    }
+                    _ => {
+                        let _ = write!(out, "  - {} [{}]: {}", decision.setting, marker, decision.reason);
+                    }
+                }
+                match explain::for_setting(&decision.setting) {
+                    Some(explanation) if decision.outcome == DecisionOutcome::Applied => {
+                        let _ = writeln!(out, " ({})", explanation.id);
+                    }
+                    _ => out.push('\n'),
+                }
+            }
+            if more_skipped > 0 {
+                let _ = writeln!(out, "  - {}", lang.format(Message::ReportMoreSkipped, &[&more_skipped]));
+            }
+        }
+
+        if self.phases.iter().any(|phase| phase.status != PhaseStatus::Unchanged) {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportPhases));
+            for phase in &self.phases {
+                let marker = match phase.status {
+                    PhaseStatus::Unchanged => continue,
+                    PhaseStatus::Applied => lang.text(Message::ReportApplied),
+                    PhaseStatus::RolledBack => lang.text(Message::ReportRolledBack),
+                    PhaseStatus::NotAttempted => lang.text(Message::ReportNotAttempted),
+                };
+                let _ = write!(out, "  - {} [{}]: {}", phase.phase, marker, phase.settings.join(", "));
+                match &phase.detail {
+                    Some(detail) => {
+                        let _ = writeln!(out, " ({})", detail);
+                    }
+                    None => out.push('\n'),
+                }
+            }
+        }
+
+        if let Some(target_dir) = &self.effective_target_dir {
+            let source = lang.text(match target_dir.source {
+                TargetDirSource::Environment => Message::TargetDirFromEnv,
+                TargetDirSource::Config => Message::TargetDirFromConfig,
+                TargetDirSource::Default => Message::TargetDirDefault,
+            });
+            let path = target_dir.path.display();
+            let _ = writeln!(out, "{}", lang.format(Message::ReportTargetDir, &[&path, &source]));
+        }
+
+        if !self.policy_conflicts.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportPolicyConflicts));
+            for conflict in &self.policy_conflicts {
+                let _ = writeln!(out, "  - {}: {}", conflict.setting, conflict.reason);
+            }
+        }
+
+        if !self.unmet_requirements.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportUnmetRequirements));
+            for unmet in &self.unmet_requirements {
+                let _ = writeln!(out, "  - {}: {}", unmet.requirement, unmet.reason);
+                for remedy in &unmet.remediation {
+                    let _ = writeln!(out, "      {}", remedy);
+                }
+            }
+        }
+
+        if !self.tool_conflicts.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportToolConflicts));
+            for conflict in &self.tool_conflicts {
+                let _ = writeln!(out, "  - {}: {}", conflict.tool, conflict.reason);
+            }
+        }
+
+        if !self.container_builds.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportContainerBuilds));
+            for build in &self.container_builds {
+                for advice in &build.advice {
+                    let _ = writeln!(out, "  - {}", advice);
+                }
+                let dockerfile = build.dockerfile.display();
+                let _ = writeln!(out, "  {}", lang.format(Message::ReportContainerSnippet, &[&dockerfile]));
+                for line in build.snippet.lines() {
+                    let _ = writeln!(out, "      {}", line);
+                }
+            }
+        }
+
+        if !self.cleanup_candidates.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportCleanupCandidates));
+            for candidate in &self.cleanup_candidates {
+                let _ = writeln!(
+                    out,
+                    "  - {} ({}): {:.1} MB",
+                    candidate.label,
+                    candidate.path.display(),
+                    candidate.size_bytes as f64 / (1024.0 * 1024.0)
+                );
+            }
+        }
+
+        if !self.disk_tradeoffs.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportDiskTradeoffs));
+            for tradeoff in &self.disk_tradeoffs {
+                let extra = format!("{:.1}", tradeoff.estimated_extra_bytes as f64 / (1024.0 * 1024.0));
+                let current = format!("{:.1}", tradeoff.current_bytes as f64 / (1024.0 * 1024.0));
+                let _ = writeln!(
+                    out,
+                    "  - {}",
+                    lang.format(Message::ReportDiskTradeoff, &[&tradeoff.layout, &extra, &current])
+                );
+            }
+        }
+
+        if !self.recommendations.is_empty() {
+            let _ = writeln!(out, "{}", lang.text(Message::ReportRecommendations));
+            for recommendation in &self.recommendations {
+                let _ = writeln!(out, "  - {}", recommendation);
+            }
+        }
+        if !self.rejected_advice.is_empty() {
+            let _ = writeln!(out, "{}", lang.format(Message::ReportRejected, &[&self.rejected_advice.len()]));
+        }
+        out
+    }
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.localized(Lang::detect()))
+    }
+}
@@ -0,0 +1,284 @@
+//! Read-only reports for CI artifacts.
+//!
+//! [`ci_artifact`] runs hardware and toolchain detection, project analysis,
+//! planning and scoring, and writes the findings as a JSON file for tooling
+//! and a markdown file for reviewers, ready to upload on every pull request.
+//! Nothing is applied: the config is planned, not written, `cargo metadata`
+//! runs with `--locked` so it cannot create or update `Cargo.lock`, and no
+//! state, metrics or backups are recorded. The project tree is compared
+//! before and after the run, outside `.git` and the target directory, and
+//! the artifact is refused if anything else changed.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+use super::{bounded, Decision, DecisionOutcome};
+use crate::analysis::{self, ProjectAnalysis};
+use crate::config::ConfigManager;
+use crate::hardware::HardwareInfo;
+use crate::linkers::Platform;
+use crate::sccache::ProjectSize;
+use crate::score::{self, Score};
+use crate::toolchain::{ToolchainInfo, ToolchainSpec};
+use crate::transaction::atomic_write;
+
+/// Version of the artifact's JSON layout, raised on incompatible changes
+pub const CI_ARTIFACT_VERSION: u32 = 1;
+
+/// Changed paths listed when the project tree did not stay untouched
+const MAX_LISTED_CHANGES: usize = 5;
+
+/// The machine the report was produced on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CiEnvironment {
+    /// Host platform, if it has a capability descriptor
+    pub platform: Option<String>,
+    /// Active toolchain (e.g. "1.85.0 stable")
+    pub toolchain: Option<String>,
+    /// Logical CPUs
+    pub cpu_count: usize,
+    /// Total memory in bytes
+    pub total_memory_bytes: u64,
+}
+
+/// Summary of the project analysis
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CiAnalysis {
+    /// Workspace member names
+    pub workspace_members: Vec<String>,
+    /// Dependencies, transitive ones included
+    pub total_dependencies: usize,
+    /// Direct dependencies
+    pub direct_dependencies: usize,
+}
+
+/// Everything a read-only run found
+#[derive(Debug, Clone, Serialize)]
+pub struct CiArtifact {
+    /// [`CI_ARTIFACT_VERSION`]
+    pub version: u32,
+    /// When the report was produced (seconds since the Unix epoch)
+    pub generated_at: u64,
+    /// Machine the report was produced on
+    pub environment: CiEnvironment,
+    /// Optimization score of the project as committed
+    pub score: Score,
+    /// Analysis summary, unless it could not run
+    pub analysis: Option<CiAnalysis>,
+    /// Why the analysis did not run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis_skipped: Option<String>,
+    /// What an apply would do with each setting
+    pub planned: Vec<Decision>,
+    /// Recommendations an apply would make
+    pub recommendations: Vec<String>,
+    /// `.cargo/config.toml` as an apply would write it
+    pub planned_config: String,
+}
+
+impl CiArtifact {
+    /// Render the artifact as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize CI artifact")
+    }
+
+    /// Render the artifact as markdown for reviewers
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# cargo-optimize report\n");
+        let _ = writeln!(out, "**Optimization score: {}/100**\n", self.score.total);
+        let _ = writeln!(out, "| Category | Score |\n| --- | ---: |");
+        for category in &self.score.categories {
+            let _ = writeln!(out, "| {} | {} |", category.category.name(), category.score);
+        }
+        if let Some(action) = &self.score.next_action {
+            let _ = writeln!(out, "\nNext: {}", action);
+        }
+
+        let _ = writeln!(out, "\n## Project\n");
+        match (&self.analysis, &self.analysis_skipped) {
+            (Some(analysis), _) => {
+                let _ = writeln!(
+                    out,
+                    "{} workspace member(s), {} dependencies ({} direct).",
+                    analysis.workspace_members.len(),
+                    analysis.total_dependencies,
+                    analysis.direct_dependencies
+                );
+            }
+            (None, reason) => {
+                let _ = writeln!(out, "Analysis skipped: {}.", reason.as_deref().unwrap_or("unknown reason"));
+            }
+        }
+        let environment = &self.environment;
+        let _ = writeln!(
+            out,
+            "Measured on {} with {} CPUs and {:.1} GiB of memory, toolchain {}.",
+            environment.platform.as_deref().unwrap_or("an unsupported platform"),
+            environment.cpu_count,
+            environment.total_memory_bytes as f64 / (1u64 << 30) as f64,
+            environment.toolchain.as_deref().unwrap_or("unknown")
+        );
+
+        if !self.planned.is_empty() {
+            let _ = writeln!(out, "\n## Planned changes\n");
+            let (planned, more_skipped) = bounded(&self.planned);
+            for decision in planned {
+                let outcome = match decision.outcome {
+                    DecisionOutcome::Applied => "apply",
+                    DecisionOutcome::Skipped => "skip",
+                    DecisionOutcome::Suppressed => "suppressed by policy",
+                };
+                match decision.skip_reason {
+                    Some(why) => {
+                        let _ = writeln!(out, "- `{}` ({}, {}): {}", decision.setting, outcome, why, decision.reason);
+                    }
+                    None => {
+                        let _ = writeln!(out, "- `{}` ({}): {}", decision.setting, outcome, decision.reason);
+                    }
+                }
+            }
+            if more_skipped > 0 {
+                let _ = writeln!(out, "- {} more skipped", more_skipped);
+            }
+        }
+        if !self.recommendations.is_empty() {
+            let _ = writeln!(out, "\n## Recommendations\n");
+            for recommendation in &self.recommendations {
+                let _ = writeln!(out, "- {}", recommendation);
+            }
+        }
+        out
+    }
+
+    /// Paths of the JSON and markdown files written for `path`
+    pub fn paths(path: &Path) -> (PathBuf, PathBuf) {
+        (path.with_extension("json"), path.with_extension("md"))
+    }
+}
+
+/// Produce the CI artifact for the project in the current directory
+///
+/// Writes `path` with a `.json` and an `.md` extension (see
+/// [`CiArtifact::paths`]) and nothing else.
+pub fn ci_artifact(path: &Path) -> Result<CiArtifact> {
+    let root = std::env::current_dir().context("Failed to determine the current directory")?;
+    ci_artifact_in(&root, path)
+}
+
+/// Produce the CI artifact for the project at `project_root`
+pub fn ci_artifact_in(project_root: &Path, path: &Path) -> Result<CiArtifact> {
+    crate::output::ensure_enabled()?;
+    let resolve = |path: &Path| std::path::absolute(path).with_context(|| format!("Failed to resolve {}", path.display()));
+    let project_root = &resolve(project_root)?;
+    let outputs = CiArtifact::paths(&resolve(path)?);
+    let excluded = [project_root.join(".git"), project_root.join("target"), outputs.0.clone(), outputs.1.clone()];
+    let before = snapshot(project_root, &excluded);
+
+    let artifact = build(project_root)?;
+
+    let after = snapshot(project_root, &excluded);
+    let changed: BTreeSet<String> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|entry| before.get(*entry) != after.get(*entry))
+        .map(|entry| entry.display().to_string())
+        .collect();
+    if !changed.is_empty() {
+        bail!(
+            "The project changed while the report was produced ({}{}); no artifact was written",
+            changed.iter().take(MAX_LISTED_CHANGES).map(String::as_str).collect::<Vec<_>>().join(", "),
+            if changed.len() > MAX_LISTED_CHANGES { ", ..." } else { "" }
+        );
+    }
+
+    atomic_write(&outputs.0, &artifact.to_json()?)?;
+    atomic_write(&outputs.1, &artifact.to_markdown())?;
+    Ok(artifact)
+}
+
+fn build(project_root: &Path) -> Result<CiArtifact> {
+    let hardware = HardwareInfo::detect();
+    let platform = Platform::current();
+    let toolchain = match ToolchainInfo::detect() {
+        Ok(toolchain) => Some(toolchain),
+        Err(e) => {
+            debug!("Could not detect the toolchain: {:#}", e);
+            None
+        }
+    };
+
+    let (analysis, analysis_skipped) = match analysis::analyze_project_locked(Some(&project_root.join("Cargo.toml"))) {
+        Ok(analysis) => (Some(analysis), None),
+        Err(e) => (None, Some(format!("{:#}", e))),
+    };
+
+    let mut manager = ConfigManager::new_with_base_dir(project_root, "CARGO_OPTIMIZE_")?;
+    if let Some(toolchain) = &toolchain {
+        manager = manager.with_toolchain(toolchain.clone());
+    }
+    if let Some(analysis) = &analysis {
+        if let Some(size) = ProjectSize::from_analysis(analysis) {
+            manager = manager.with_project_size(size);
+        }
+        if let Some(template) = analysis.template {
+            manager = manager.with_detected_template(template);
+        }
+    }
+    let (doc, report) = manager.plan_with_hardware(&hardware)?;
+
+    let spec = toolchain.as_ref().map(ToolchainSpec::from);
+    Ok(CiArtifact {
+        version: CI_ARTIFACT_VERSION,
+        generated_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        environment: CiEnvironment {
+            platform: platform.map(|platform| platform.name().to_string()),
+            toolchain: spec.map(|spec| spec.to_string()),
+            cpu_count: hardware.cpu_count,
+            total_memory_bytes: hardware.total_memory,
+        },
+        score: score::for_project(project_root, platform, spec, analysis.as_ref()),
+        analysis: analysis.as_ref().map(summarize),
+        analysis_skipped,
+        planned: report.decisions,
+        recommendations: report.recommendations,
+        planned_config: doc.to_string(),
+    })
+}
+
+fn summarize(analysis: &ProjectAnalysis) -> CiAnalysis {
+    CiAnalysis {
+        workspace_members: analysis.workspace_members.iter().map(|member| member.name.clone()).collect(),
+        total_dependencies: analysis.total_dependencies,
+        direct_dependencies: analysis.direct_dependencies,
+    }
+}
+
+/// Size and modification time of every file under `root`, outside `excluded`
+fn snapshot(root: &Path, excluded: &[PathBuf]) -> BTreeMap<PathBuf, (u64, Option<SystemTime>)> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if excluded.contains(&path) {
+                continue;
+            }
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => pending.push(path),
+                Ok(metadata) => {
+                    let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                    files.insert(relative, (metadata.len(), metadata.modified().ok()));
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    files
+}
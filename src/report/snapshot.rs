@@ -0,0 +1,599 @@
+//! Snapshots of a machine's build setup, for comparing two machines.
+//!
+//! "Why is the build fast on your machine?" [`snapshot`] records what
+//! decides the answer on one machine: the hardware, the toolchain, the
+//! settings of `.cargo/config.toml`, the build times measured on past
+//! applies and the sccache hit rate. Snapshots are plain JSON, so one can be
+//! taken on each machine and [`compare`] run wherever both files are.
+//! The comparison lists every difference, the ones most likely responsible
+//! for a gap in build times first: the linker, the number of cores, the
+//! cache hit rate and the kind of disk.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml_edit::{DocumentMut, Item, TableLike};
+use tracing::debug;
+
+use crate::hardware::HardwareInfo;
+use crate::linkers::Platform;
+use crate::metrics::{self, MetricsHistory};
+use crate::state::State;
+use crate::toolchain::{ToolchainInfo, ToolchainSpec};
+use crate::transaction::atomic_write;
+
+/// Version of the snapshot's JSON layout, raised on incompatible changes
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Builds per profile the median build time is taken over
+const RECENT_BUILDS: usize = 10;
+
+/// Ratio of core counts from which the difference counts as a likely cause
+const CORES_RATIO: f64 = 1.5;
+
+/// Difference in hit rate from which the cache counts as a likely cause
+const HIT_RATE_GAP: f64 = 0.25;
+
+/// Linkers much faster than the platform default
+const FAST_LINKERS: &[&str] = &["rust-lld", "lld-link", "ld.lld", "lld", "mold", "wild", "ld64.sold", "sold", "zld"];
+
+/// The machine a snapshot was taken on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotEnvironment {
+    /// Host platform, if it has a capability descriptor
+    pub platform: Option<String>,
+    /// CPU architecture
+    pub arch: String,
+    /// Operating system name and version
+    pub os: String,
+    /// Active toolchain (e.g. "1.85.0" or "1.86.0-nightly")
+    pub toolchain: Option<String>,
+    /// Logical CPUs
+    pub cpu_count: usize,
+    /// Physical cores
+    pub physical_cpu_count: usize,
+    /// Total memory in bytes
+    pub total_memory_bytes: u64,
+    /// File system of the disk holding the project
+    pub file_system: Option<String>,
+    /// Whether that disk is a solid-state drive, if known
+    pub is_ssd: Option<bool>,
+    /// Sequential write speed measured by the disk benchmark, in MB/s
+    pub disk_mb_per_sec: Option<u64>,
+}
+
+/// The project's cargo configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Linker selected by the config, `None` for the platform default
+    pub linker: Option<String>,
+    /// `build.rustc-wrapper` (e.g. "sccache")
+    pub rustc_wrapper: Option<String>,
+    /// `build.jobs`
+    pub jobs: Option<i64>,
+    /// Every setting of `.cargo/config.toml` by dotted key
+    pub settings: BTreeMap<String, String>,
+}
+
+/// Build measurements recorded on past applies
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotMetrics {
+    /// Median duration of the recent builds of each profile, in seconds
+    pub median_build_secs: BTreeMap<String, f64>,
+    /// Builds the medians were taken over
+    pub builds: usize,
+    /// Latest sccache hit rate, from 0 to 1
+    pub sccache_hit_rate: Option<f64>,
+}
+
+/// Environment, config and metrics of one machine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// [`SNAPSHOT_VERSION`]
+    pub version: u32,
+    /// Name of the machine (its host name, if known)
+    pub machine: String,
+    /// When the snapshot was taken (seconds since the Unix epoch)
+    pub generated_at: u64,
+    /// The machine
+    pub environment: SnapshotEnvironment,
+    /// The cargo configuration
+    pub config: SnapshotConfig,
+    /// Build measurements
+    pub metrics: SnapshotMetrics,
+}
+
+impl Snapshot {
+    /// The snapshot as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize the snapshot")
+    }
+
+    /// Save the snapshot atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        atomic_write(path, &self.to_json()?)
+    }
+
+    /// Load a snapshot saved by [`Snapshot::save`], possibly on another machine
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read snapshot {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid snapshot {:?}", path))
+    }
+}
+
+/// Take a snapshot of this machine for the project in the current directory
+pub fn snapshot() -> Result<Snapshot> {
+    snapshot_in(Path::new("."))
+}
+
+/// Take a snapshot of this machine for the project at `project_root`
+///
+/// Nothing is measured: build times, the hit rate and the disk speed are
+/// those recorded by past applies, and are missing if there were none.
+pub fn snapshot_in(project_root: &Path) -> Result<Snapshot> {
+    let cargo_dir = project_root.join(".cargo");
+    let config = match fs::read_to_string(cargo_dir.join("config.toml")) {
+        Ok(content) => {
+            let doc: DocumentMut = content.parse().context("Failed to parse .cargo/config.toml")?;
+            config_of(&doc)
+        }
+        Err(_) => SnapshotConfig::default(),
+    };
+
+    let hardware = HardwareInfo::detect();
+    let disk = hardware.disk_for_path(project_root);
+    let state = State::load(&State::path_in(&cargo_dir));
+    let toolchain = match ToolchainInfo::detect() {
+        Ok(toolchain) => Some(ToolchainSpec::from(&toolchain).to_string()),
+        Err(e) => {
+            debug!("Could not detect the toolchain: {:#}", e);
+            None
+        }
+    };
+    let environment = SnapshotEnvironment {
+        platform: Platform::current().map(|platform| platform.name().to_string()),
+        arch: hardware.arch.clone(),
+        os: format!("{} {}", hardware.os_name, hardware.os_version).trim().to_string(),
+        toolchain,
+        cpu_count: hardware.cpu_count,
+        physical_cpu_count: hardware.physical_cpu_count,
+        total_memory_bytes: hardware.total_memory,
+        file_system: disk.map(|disk| disk.file_system.clone()),
+        is_ssd: disk.and_then(|disk| disk.is_ssd),
+        disk_mb_per_sec: state.disk_benchmark.as_ref().map(|benchmark| benchmark.sequential_mb_per_sec),
+    };
+
+    let history = MetricsHistory::load(&MetricsHistory::path_in(&cargo_dir));
+    let mut durations: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for record in history.records.iter().rev() {
+        let profile = durations.entry(&record.profile).or_default();
+        if profile.len() < RECENT_BUILDS {
+            profile.push(record.duration_secs);
+        }
+    }
+    let metrics = SnapshotMetrics {
+        builds: durations.values().map(Vec::len).sum(),
+        median_build_secs: durations
+            .into_iter()
+            .map(|(profile, durations)| (profile.to_string(), metrics::median(durations)))
+            .collect(),
+        sccache_hit_rate: state
+            .sccache
+            .samples
+            .iter()
+            .rev()
+            .find(|sample| sample.stats.requests() > 0)
+            .map(|sample| sample.stats.hit_rate()),
+    };
+
+    Ok(Snapshot {
+        version: SNAPSHOT_VERSION,
+        machine: machine_name(),
+        generated_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        environment,
+        config,
+        metrics,
+    })
+}
+
+fn machine_name() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .chain(fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn config_of(doc: &DocumentMut) -> SnapshotConfig {
+    let mut settings = BTreeMap::new();
+    flatten(doc.as_table(), "", &mut settings);
+    let build = doc.get("build");
+    SnapshotConfig {
+        linker: linker_of(doc),
+        rustc_wrapper: build.and_then(|build| build.get("rustc-wrapper")).and_then(Item::as_str).map(str::to_string),
+        jobs: build.and_then(|build| build.get("jobs")).and_then(Item::as_integer),
+        settings,
+    }
+}
+
+fn flatten(table: &dyn TableLike, prefix: &str, settings: &mut BTreeMap<String, String>) {
+    for (key, item) in table.iter() {
+        let key = if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+        if let Some(table) = item.as_table_like() {
+            flatten(table, &key, settings);
+        } else if let Some(value) = item.as_value() {
+            settings.insert(key, value.clone().decorated("", "").to_string());
+        }
+    }
+}
+
+/// Linker selected by any target of the config, by `linker` or by a
+/// `-fuse-ld=`/`--ld-path=` link argument
+fn linker_of(doc: &DocumentMut) -> Option<String> {
+    let stem = |path: &str| Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path).to_string();
+    let targets = doc.get("target").and_then(Item::as_table_like);
+    let target_items: Vec<&Item> =
+        targets.map(|targets| targets.iter().map(|(_, item)| item).collect()).unwrap_or_default();
+    let mut flag_lists: Vec<&Item> = target_items.iter().filter_map(|target| target.get("rustflags")).collect();
+    flag_lists.extend(doc.get("build").and_then(|build| build.get("rustflags")));
+    let from_flags =
+        flag_lists.into_iter().filter_map(Item::as_array).flatten().filter_map(|flag| flag.as_str()).find_map(|flag| {
+            ["-fuse-ld=", "--ld-path="].iter().find_map(|prefix| flag.split_once(prefix).map(|(_, name)| stem(name)))
+        });
+    from_flags.or_else(|| {
+        target_items
+            .iter()
+            .filter_map(|target| target.get("linker").and_then(Item::as_str))
+            .map(|linker| stem(linker).trim_end_matches(".exe").to_string())
+            .next()
+    })
+}
+
+/// How likely a difference is to explain a gap in build times
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Impact {
+    /// Known to change build times severalfold
+    High,
+    /// Can change build times noticeably
+    Medium,
+    /// Rarely matters for build times
+    Low,
+}
+
+impl fmt::Display for Impact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Impact::High => "high",
+            Impact::Medium => "medium",
+            Impact::Low => "low",
+        })
+    }
+}
+
+/// One way two snapshots differ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Difference {
+    /// What differs (e.g. "linker", "cores", "build.jobs")
+    pub factor: String,
+    /// Value in the first snapshot
+    pub a: String,
+    /// Value in the second snapshot
+    pub b: String,
+    /// How likely it explains a gap in build times
+    pub impact: Impact,
+    /// Why it matters
+    pub explanation: String,
+}
+
+/// Median build times of a profile both snapshots measured
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildTimeGap {
+    /// Build profile
+    pub profile: String,
+    /// Median in the first snapshot, in seconds
+    pub a_secs: f64,
+    /// Median in the second snapshot, in seconds
+    pub b_secs: f64,
+}
+
+impl BuildTimeGap {
+    /// How many times slower the slower machine builds
+    pub fn ratio(&self) -> f64 {
+        let (fast, slow) =
+            if self.a_secs <= self.b_secs { (self.a_secs, self.b_secs) } else { (self.b_secs, self.a_secs) };
+        if fast > 0.0 {
+            slow / fast
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Differences between two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnapshotComparison {
+    /// Machine of the first snapshot
+    pub a: String,
+    /// Machine of the second snapshot
+    pub b: String,
+    /// Build times of the profiles both snapshots measured
+    pub build_times: Vec<BuildTimeGap>,
+    /// Differences, the most likely causes of a gap first
+    pub differences: Vec<Difference>,
+}
+
+impl SnapshotComparison {
+    /// Differences of the given impact
+    pub fn with_impact(&self, impact: Impact) -> impl Iterator<Item = &Difference> {
+        self.differences.iter().filter(move |difference| difference.impact == impact)
+    }
+}
+
+/// Compare two snapshots
+///
+/// Differences known to change build times severalfold rank first: a fast
+/// linker on one machine only, [`CORES_RATIO`] times the cores, a hit rate
+/// [`HIT_RATE_GAP`] apart and a solid-state drive against a spinning disk.
+/// Settings that differ otherwise follow. Order within an impact is stable.
+pub fn compare(a: &Snapshot, b: &Snapshot) -> SnapshotComparison {
+    let mut differences = Vec::new();
+    let mut differ = |factor: &str, values: (String, String), impact: Impact, explanation: &str| {
+        if values.0 != values.1 {
+            differences.push(Difference {
+                factor: factor.to_string(),
+                a: values.0,
+                b: values.1,
+                impact,
+                explanation: explanation.to_string(),
+            });
+        }
+    };
+    let (ea, eb) = (&a.environment, &b.environment);
+    let (ca, cb) = (&a.config, &b.config);
+
+    let linker = |config: &SnapshotConfig| config.linker.clone().unwrap_or_else(|| "default".to_string());
+    let is_fast =
+        |config: &SnapshotConfig| config.linker.as_deref().is_some_and(|linker| FAST_LINKERS.contains(&linker));
+    differ(
+        "linker",
+        (linker(ca), linker(cb)),
+        if is_fast(ca) != is_fast(cb) { Impact::High } else { Impact::Medium },
+        "linking dominates incremental builds, and fast linkers take a fraction of the time of the default one",
+    );
+
+    let ratio = ea.cpu_count.max(eb.cpu_count) as f64 / ea.cpu_count.min(eb.cpu_count).max(1) as f64;
+    differ(
+        "cores",
+        (ea.cpu_count.to_string(), eb.cpu_count.to_string()),
+        if ratio >= CORES_RATIO { Impact::High } else { Impact::Medium },
+        "clean builds compile independent crates in parallel, one per core",
+    );
+
+    let hit_rate = |metrics: &SnapshotMetrics| {
+        metrics.sccache_hit_rate.map_or_else(|| "none".to_string(), |rate| format!("{:.0}%", rate * 100.0))
+    };
+    let hit_gap = match (a.metrics.sccache_hit_rate, b.metrics.sccache_hit_rate) {
+        (Some(ra), Some(rb)) => (ra - rb).abs() >= HIT_RATE_GAP,
+        (Some(rate), None) | (None, Some(rate)) => rate >= HIT_RATE_GAP,
+        (None, None) => false,
+    };
+    differ(
+        "cache hit rate",
+        (hit_rate(&a.metrics), hit_rate(&b.metrics)),
+        if hit_gap { Impact::High } else { Impact::Low },
+        "every sccache hit skips compiling a crate",
+    );
+
+    let disk = |environment: &SnapshotEnvironment| match environment.is_ssd {
+        Some(true) => "SSD".to_string(),
+        Some(false) => "HDD".to_string(),
+        None => "unknown".to_string(),
+    };
+    differ(
+        "disk type",
+        (disk(ea), disk(eb)),
+        if ea.is_ssd.is_some() && eb.is_ssd.is_some() { Impact::High } else { Impact::Low },
+        "builds write thousands of small files that spinning disks serve slowly",
+    );
+
+    let speed = |environment: &SnapshotEnvironment| {
+        environment.disk_mb_per_sec.map_or_else(|| "unknown".to_string(), |speed| format!("{} MB/s", speed))
+    };
+    let speed_gap = match (ea.disk_mb_per_sec, eb.disk_mb_per_sec) {
+        (Some(sa), Some(sb)) => sa.max(sb) as f64 >= 2.0 * sa.min(sb).max(1) as f64,
+        _ => false,
+    };
+    differ(
+        "disk speed",
+        (speed(ea), speed(eb)),
+        if speed_gap { Impact::Medium } else { Impact::Low },
+        "target directories on slow disks make builds wait on writes",
+    );
+
+    let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+    differ(
+        "toolchain",
+        (unknown(&ea.toolchain), unknown(&eb.toolchain)),
+        Impact::Medium,
+        "compiler releases differ in speed, and nightly enables faster settings",
+    );
+    differ(
+        "rustc wrapper",
+        (
+            ca.rustc_wrapper.clone().unwrap_or_else(|| "none".to_string()),
+            cb.rustc_wrapper.clone().unwrap_or_else(|| "none".to_string()),
+        ),
+        Impact::Medium,
+        "a compiler cache speeds up clean builds but adds overhead to every invocation",
+    );
+    differ(
+        "memory",
+        (format_gib(ea.total_memory_bytes), format_gib(eb.total_memory_bytes)),
+        if ea.total_memory_bytes.max(eb.total_memory_bytes) >= 2 * ea.total_memory_bytes.min(eb.total_memory_bytes) {
+            Impact::Medium
+        } else {
+            Impact::Low
+        },
+        "parallel rustc and linker processes swap when memory runs short",
+    );
+    differ(
+        "build.jobs",
+        (
+            ca.jobs.map_or_else(|| "default".to_string(), |jobs| jobs.to_string()),
+            cb.jobs.map_or_else(|| "default".to_string(), |jobs| jobs.to_string()),
+        ),
+        Impact::Medium,
+        "caps how many crates compile at once",
+    );
+    differ(
+        "file system",
+        (unknown(&ea.file_system), unknown(&eb.file_system)),
+        Impact::Low,
+        "file systems differ in small-file performance",
+    );
+    differ(
+        "os",
+        (ea.os.clone(), eb.os.clone()),
+        Impact::Low,
+        "process start-up and file access costs differ between systems",
+    );
+    differ("architecture", (ea.arch.clone(), eb.arch.clone()), Impact::Low, "code generation differs between targets");
+
+    let summarized = ["build.rustc-wrapper", "build.jobs"];
+    let keys: std::collections::BTreeSet<&String> = ca.settings.keys().chain(cb.settings.keys()).collect();
+    for key in keys {
+        let is_linker = key.starts_with("target.") && (key.ends_with(".linker") || key.ends_with(".rustflags"));
+        if summarized.contains(&key.as_str()) || is_linker || key.starts_with("alias.") {
+            continue;
+        }
+        let setting =
+            |config: &SnapshotConfig| config.settings.get(key).cloned().unwrap_or_else(|| "unset".to_string());
+        differ(key, (setting(ca), setting(cb)), Impact::Low, "set differently in .cargo/config.toml");
+    }
+
+    differences.sort_by_key(|difference| difference.impact);
+
+    let build_times = a
+        .metrics
+        .median_build_secs
+        .iter()
+        .filter_map(|(profile, a_secs)| {
+            let b_secs = *b.metrics.median_build_secs.get(profile)?;
+            Some(BuildTimeGap { profile: profile.clone(), a_secs: *a_secs, b_secs })
+        })
+        .collect();
+
+    SnapshotComparison { a: a.machine.clone(), b: b.machine.clone(), build_times, differences }
+}
+
+fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+impl fmt::Display for SnapshotComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Comparing {} (A) with {} (B)", self.a, self.b)?;
+        for gap in &self.build_times {
+            writeln!(
+                f,
+                "  {} builds: {:.1}s on A, {:.1}s on B ({:.1}x)",
+                gap.profile,
+                gap.a_secs,
+                gap.b_secs,
+                gap.ratio()
+            )?;
+        }
+        if self.differences.is_empty() {
+            return writeln!(f, "No differences found.");
+        }
+        for impact in [Impact::High, Impact::Medium, Impact::Low] {
+            let mut differences = self.with_impact(impact).peekable();
+            if differences.peek().is_none() {
+                continue;
+            }
+            writeln!(f, "\n{} impact:", impact)?;
+            for difference in differences {
+                writeln!(f, "  {}: {} (A) vs {} (B)", difference.factor, difference.a, difference.b)?;
+                writeln!(f, "    {}", difference.explanation)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(machine: &str, cpu_count: usize, is_ssd: bool, config: &str, hit_rate: Option<f64>) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            machine: machine.to_string(),
+            generated_at: 0,
+            environment: SnapshotEnvironment {
+                arch: "x86_64".to_string(),
+                os: "Linux 6.8".to_string(),
+                toolchain: Some("1.85.0".to_string()),
+                cpu_count,
+                physical_cpu_count: cpu_count / 2,
+                total_memory_bytes: 32 << 30,
+                is_ssd: Some(is_ssd),
+                ..SnapshotEnvironment::default()
+            },
+            config: config_of(&config.parse().unwrap()),
+            metrics: SnapshotMetrics {
+                median_build_secs: BTreeMap::from([("dev".to_string(), 40.0)]),
+                builds: 5,
+                sccache_hit_rate: hit_rate,
+            },
+        }
+    }
+
+    #[test]
+    fn test_config_of_finds_linker_and_settings() {
+        let config = config_of(
+            &"[build]\njobs = 8 # fewer\nrustc-wrapper = \"sccache\"\n\
+              [target.x86_64-unknown-linux-gnu]\nlinker = \"clang\"\nrustflags = [\"-C\", \"link-arg=-fuse-ld=/usr/bin/mold\"]\n"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(config.linker.as_deref(), Some("mold"));
+        assert_eq!(config.rustc_wrapper.as_deref(), Some("sccache"));
+        assert_eq!(config.jobs, Some(8));
+        assert_eq!(config.settings.get("build.jobs").map(String::as_str), Some("8"));
+        assert_eq!(
+            config_of(&"[target.x86_64-pc-windows-msvc]\nlinker = \"rust-lld.exe\"\n".parse().unwrap())
+                .linker
+                .as_deref(),
+            Some("rust-lld")
+        );
+    }
+
+    #[test]
+    fn test_compare_ranks_likely_causes_first() {
+        let mut fast = snapshot("fast", 16, true, "[target.x86_64-unknown-linux-gnu]\nrustflags = [\"-C\", \"link-arg=-fuse-ld=mold\"]\n[profile.dev]\ndebug = 1\n", Some(0.9));
+        fast.metrics.median_build_secs.insert("dev".to_string(), 12.0);
+        let slow = snapshot("slow", 8, false, "", Some(0.3));
+
+        let comparison = compare(&slow, &fast);
+        let high: Vec<&str> = comparison.with_impact(Impact::High).map(|d| d.factor.as_str()).collect();
+        assert_eq!(high, ["linker", "cores", "cache hit rate", "disk type"]);
+        assert_eq!(comparison.differences[0].a, "default");
+        assert_eq!(comparison.differences[0].b, "mold");
+        let last = comparison.differences.last().unwrap();
+        assert_eq!((last.factor.as_str(), last.a.as_str(), last.impact), ("profile.dev.debug", "unset", Impact::Low));
+        assert!((comparison.build_times[0].ratio() - 40.0 / 12.0).abs() < 1e-9);
+        assert!(comparison.to_string().contains("linker: default (A) vs mold (B)"));
+
+        // Equal setups have nothing to explain, and snapshots survive a round trip
+        let copy: Snapshot = serde_json::from_str(&slow.to_json().unwrap()).unwrap();
+        assert!(compare(&slow, &copy).differences.is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+//! Resource amount expressions.
+//!
+//! Job counts and cache sizes accept more than plain numbers and
+//! percentages. A tiny expression language covers the common cases:
+//!
+//! | Expression      | Meaning                                    |
+//! |-----------------|--------------------------------------------|
+//! | `8`             | exactly 8                                  |
+//! | `75%`           | 75% of logical cores (or of the total)     |
+//! | `50% physical`  | half the physical cores                    |
+//! | `logical-2`     | all logical cores but two                  |
+//! | `min(8, 75%)`   | 75% of the cores, at most 8                |
+//!
+//! Terms can be combined with `+`, `-`, `min(...)`, `max(...)` and
+//! parentheses. `logical` and `physical` are only meaningful for core counts.
+
+use std::fmt;
+use thiserror::Error;
+
+/// What an expression measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// CPU cores; percentages refer to logical cores unless `physical` follows
+    Cores,
+    /// Any other amount (e.g. megabytes); percentages refer to the total
+    Amount,
+}
+
+/// Quantity a percentage or name refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    /// Logical CPU cores
+    Logical,
+    /// Physical CPU cores
+    Physical,
+    /// The total an amount is measured against
+    Total,
+}
+
+/// Error describing why an expression could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid resource amount {input:?}: {message} at position {position}")]
+pub struct ResourceParseError {
+    /// The expression that failed to parse
+    pub input: String,
+    /// Character offset of the problem
+    pub position: usize,
+    /// What was found and what was expected instead
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Percent(f64, Base),
+    Base(Base),
+    Add(Box<Node>, Box<Node>),
+    Sub(Box<Node>, Box<Node>),
+    Min(Vec<Node>),
+    Max(Vec<Node>),
+}
+
+/// A parsed resource amount
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceExpr {
+    root: Node,
+}
+
+impl ResourceExpr {
+    /// Parse an expression for `kind`
+    pub fn parse(input: &str, kind: ResourceKind) -> Result<Self, ResourceParseError> {
+        let mut parser = Parser { input, chars: input.char_indices().collect(), pos: 0, kind };
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            return Err(parser.error("empty expression; expected a number, a percentage or an expression"));
+        }
+        let root = parser.expr()?;
+        parser.skip_whitespace();
+        if let Some(c) = parser.peek() {
+            return Err(parser.error(format!("unexpected `{}`; expected `+`, `-` or the end of the expression", c)));
+        }
+        Ok(Self { root })
+    }
+
+    /// Evaluate with `resolve` supplying the value of each base
+    pub fn eval(&self, resolve: &dyn Fn(Base) -> f64) -> f64 {
+        eval_node(&self.root, resolve)
+    }
+
+    /// Evaluate a core count (at least 1)
+    pub fn eval_cores(&self, logical: usize, physical: usize) -> usize {
+        let value = self.eval(&|base| match base {
+            Base::Physical => physical as f64,
+            Base::Logical | Base::Total => logical as f64,
+        });
+        (value.round().max(1.0)) as usize
+    }
+
+    /// Evaluate an amount measured against `total` (at least 0)
+    pub fn eval_amount(&self, total: f64) -> f64 {
+        self.eval(&|_| total).max(0.0)
+    }
+}
+
+impl fmt::Display for ResourceExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_node(&self.root, f)
+    }
+}
+
+fn eval_node(node: &Node, resolve: &dyn Fn(Base) -> f64) -> f64 {
+    match node {
+        Node::Number(value) => *value,
+        Node::Percent(percent, base) => resolve(*base) * percent / 100.0,
+        Node::Base(base) => resolve(*base),
+        Node::Add(a, b) => eval_node(a, resolve) + eval_node(b, resolve),
+        Node::Sub(a, b) => eval_node(a, resolve) - eval_node(b, resolve),
+        Node::Min(args) => args.iter().map(|arg| eval_node(arg, resolve)).fold(f64::INFINITY, f64::min),
+        Node::Max(args) => args.iter().map(|arg| eval_node(arg, resolve)).fold(f64::NEG_INFINITY, f64::max),
+    }
+}
+
+fn write_node(node: &Node, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let base_name = |base: Base| match base {
+        Base::Logical => "logical",
+        Base::Physical => "physical",
+        Base::Total => "total",
+    };
+    match node {
+        Node::Number(value) => write!(f, "{}", value),
+        Node::Percent(percent, Base::Physical) => write!(f, "{}% physical", percent),
+        Node::Percent(percent, _) => write!(f, "{}%", percent),
+        Node::Base(base) => f.write_str(base_name(*base)),
+        Node::Add(a, b) | Node::Sub(a, b) => {
+            write_node(a, f)?;
+            f.write_str(if matches!(node, Node::Add(..)) { " + " } else { " - " })?;
+            match **b {
+                Node::Add(..) | Node::Sub(..) => {
+                    f.write_str("(")?;
+                    write_node(b, f)?;
+                    f.write_str(")")
+                }
+                _ => write_node(b, f),
+            }
+        }
+        Node::Min(args) | Node::Max(args) => {
+            f.write_str(if matches!(node, Node::Min(_)) { "min(" } else { "max(" })?;
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write_node(arg, f)?;
+            }
+            f.write_str(")")
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    kind: ResourceKind,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ResourceParseError {
+        ResourceParseError { input: self.input.to_string(), position: self.pos, message: message.into() }
+    }
+
+    fn term_expected(&self) -> &'static str {
+        match self.kind {
+            ResourceKind::Cores => "a number, a percentage, `logical`, `physical`, `min(...)`, `max(...)` or `(`",
+            ResourceKind::Amount => "a number, a percentage, `min(...)`, `max(...)` or `(`",
+        }
+    }
+
+    fn expr(&mut self) -> Result<Node, ResourceParseError> {
+        let mut node = self.term()?;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('+') => {
+                    self.pos += 1;
+                    node = Node::Add(Box::new(node), Box::new(self.term()?));
+                }
+                Some('-') => {
+                    self.pos += 1;
+                    node = Node::Sub(Box::new(node), Box::new(self.term()?));
+                }
+                _ => return Ok(node),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, ResourceParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number_or_percent(),
+            Some(c) if c.is_ascii_alphabetic() => self.name(),
+            Some('(') => {
+                self.pos += 1;
+                let node = self.expr()?;
+                self.expect(')', "`+`, `-` or `)`")?;
+                Ok(node)
+            }
+            Some(c) => Err(self.error(format!("unexpected `{}`; expected {}", c, self.term_expected()))),
+            None => Err(self.error(format!("unexpected end; expected {}", self.term_expected()))),
+        }
+    }
+
+    fn number_or_percent(&mut self) -> Result<Node, ResourceParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().map(|(_, c)| c).collect();
+        let value: f64 = text.parse().map_err(|_| {
+            ResourceParseError {
+                input: self.input.to_string(),
+                position: start,
+                message: format!("`{}` is not a number", text),
+            }
+        })?;
+
+        self.skip_whitespace();
+        if self.peek() != Some('%') {
+            return Ok(Node::Number(value));
+        }
+        self.pos += 1;
+
+        // An optional base name may follow a percentage ("50% physical")
+        let checkpoint = self.pos;
+        self.skip_whitespace();
+        let word = self.word();
+        let base = match (word.as_str(), self.kind) {
+            ("physical", ResourceKind::Cores) => Base::Physical,
+            ("logical", ResourceKind::Cores) => Base::Logical,
+            ("", ResourceKind::Cores) => {
+                self.pos = checkpoint;
+                Base::Logical
+            }
+            (_, ResourceKind::Cores) => {
+                self.pos = checkpoint;
+                self.skip_whitespace();
+                return Err(self.error(format!(
+                    "unknown core kind `{}`; expected `logical` or `physical` after a percentage",
+                    word
+                )));
+            }
+            _ => {
+                self.pos = checkpoint;
+                Base::Total
+            }
+        };
+        Ok(Node::Percent(value, base))
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().map(|(_, c)| c).collect()
+    }
+
+    fn name(&mut self) -> Result<Node, ResourceParseError> {
+        let start = self.pos;
+        let word = self.word();
+        match (word.as_str(), self.kind) {
+            ("logical", ResourceKind::Cores) => Ok(Node::Base(Base::Logical)),
+            ("physical", ResourceKind::Cores) => Ok(Node::Base(Base::Physical)),
+            ("min" | "max", _) => {
+                self.skip_whitespace();
+                self.expect('(', "`(` after the function name")?;
+                let mut args = vec![self.expr()?];
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => {
+                            self.pos += 1;
+                            args.push(self.expr()?);
+                        }
+                        Some(')') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(c) => return Err(self.error(format!("unexpected `{}`; expected `,`, `+`, `-` or `)`", c))),
+                        None => return Err(self.error("unexpected end; expected `,` or `)`")),
+                    }
+                }
+                Ok(if word == "min" { Node::Min(args) } else { Node::Max(args) })
+            }
+            ("logical" | "physical", ResourceKind::Amount) => {
+                self.pos = start;
+                Err(self.error(format!("`{}` is only valid for core counts", word)))
+            }
+            _ => {
+                self.pos = start;
+                let expected = match self.kind {
+                    ResourceKind::Cores => "`logical`, `physical`, `min` or `max`",
+                    ResourceKind::Amount => "`min` or `max`",
+                };
+                Err(self.error(format!("unknown name `{}`; expected {}", word, expected)))
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char, description: &str) -> Result<(), ResourceParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("unexpected `{}`; expected {}", c, description))),
+            None => Err(self.error(format!("unexpected end; expected {}", description))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cores(input: &str) -> usize {
+        ResourceExpr::parse(input, ResourceKind::Cores).unwrap().eval_cores(16, 8)
+    }
+
+    fn error(input: &str, kind: ResourceKind) -> ResourceParseError {
+        ResourceExpr::parse(input, kind).unwrap_err()
+    }
+
+    #[test]
+    fn test_core_expressions() {
+        assert_eq!(cores("8"), 8);
+        assert_eq!(cores("75%"), 12);
+        assert_eq!(cores("50% physical"), 4);
+        assert_eq!(cores("50 % logical"), 8);
+        assert_eq!(cores("logical-2"), 14);
+        assert_eq!(cores("physical - 1"), 7);
+        assert_eq!(cores("min(8, 75%)"), 8);
+        assert_eq!(cores("max(2, 10% physical)"), 2);
+        assert_eq!(cores("min(logical - 2, (physical + 4))"), 12);
+        // Never below one job
+        assert_eq!(cores("logical-32"), 1);
+
+        let amount = ResourceExpr::parse("min(4096, 10%)", ResourceKind::Amount).unwrap();
+        assert_eq!(amount.eval_amount(100_000.0), 4096.0);
+        assert_eq!(amount.eval_amount(1000.0), 100.0);
+        assert_eq!(amount.to_string(), "min(4096, 10%)");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let e = error("", ResourceKind::Cores);
+        assert!(e.message.contains("empty"));
+        let e = error("min(8, 75%", ResourceKind::Cores);
+        assert_eq!(e.position, 10);
+        assert!(e.message.contains("expected `,` or `)`"));
+        let e = error("50% virtual", ResourceKind::Cores);
+        assert!(e.message.contains("`logical` or `physical`"));
+        let e = error("cores-2", ResourceKind::Cores);
+        assert!(e.message.contains("unknown name `cores`"));
+        let e = error("physical", ResourceKind::Amount);
+        assert!(e.message.contains("only valid for core counts"));
+        let e = error("8 8", ResourceKind::Cores);
+        assert_eq!(e.position, 2);
+        let e = error("1.2.3", ResourceKind::Cores);
+        assert!(e.message.contains("not a number"));
+        let e = error("*2", ResourceKind::Amount);
+        assert!(e.to_string().starts_with("invalid resource amount \"*2\": unexpected `*`"));
+    }
+}
@@ -0,0 +1,449 @@
+//! New projects with cargo-optimize configured from the start.
+//!
+//! [`new_project`] renders a package or workspace for organization project
+//! generators that embed this crate: a manifest with profiles for the
+//! chosen [`ProjectTemplate`], cargo-optimize wired in as a build script or
+//! as the installed `cargo optimize` binary, a `cargo-optimize.toml` pinning
+//! the template, a CI pipeline with cargo caching and `.gitignore` entries
+//! for the files cargo-optimize keeps per machine. The files are returned in
+//! a [`Scaffold`] so a generator can add its own before
+//! [`Scaffold::write_to`] puts them on disk.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, Array, DocumentMut, Item, Table};
+
+use crate::adoption::AliasTarget;
+use crate::audit::AUDIT_LOG;
+use crate::ci::{cache_snippet_for, CacheLayout, CiEnvironment};
+use crate::config::{profile_value, ProjectTemplate};
+use crate::metrics::METRICS_FILE_NAME;
+use crate::output;
+use crate::state::STATE_FILE_NAME;
+
+/// Directory holding the members of a generated workspace
+pub const MEMBERS_DIR: &str = "crates";
+
+/// Profile settings of every generated project, as (profile, key, value); templates override them
+pub const DEFAULT_PROFILE_SETTINGS: &[(&str, &str, &str)] = &[
+    // Line tables keep backtraces usable with a fraction of the debug info to link
+    ("dev", "debug", "line-tables-only"),
+    ("release", "lto", "thin"),
+];
+
+/// Kind of crate a package is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrateKind {
+    /// An executable (`src/main.rs`)
+    #[default]
+    Binary,
+    /// A library (`src/lib.rs`)
+    Library,
+}
+
+/// How builds get the cargo-optimize configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integration {
+    /// A `build.rs` calling [`crate::auto_configure`], with cargo-optimize as a build-dependency
+    #[default]
+    BuildScript,
+    /// The `cargo optimize` binary, installed with the `cargo optimize-install` alias
+    InstalledBinary,
+}
+
+/// What [`new_project`] generates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    /// Kind of the package, or of the first member of a workspace
+    pub kind: CrateKind,
+    /// Profile bundle; [`DEFAULT_PROFILE_SETTINGS`] only when `None`
+    pub archetype: Option<ProjectTemplate>,
+    /// Members of a workspace under [`MEMBERS_DIR`]; a single package when empty
+    pub members: Vec<String>,
+    /// How builds get the configuration
+    pub integration: Integration,
+    /// CI service to generate a pipeline for, if any
+    pub ci: Option<CiEnvironment>,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template {
+            kind: CrateKind::default(),
+            archetype: None,
+            members: Vec::new(),
+            integration: Integration::default(),
+            ci: Some(CiEnvironment::GitHubActions),
+        }
+    }
+}
+
+impl Template {
+    /// A single package of `kind`
+    pub fn package(kind: CrateKind) -> Self {
+        Template { kind, ..Template::default() }
+    }
+
+    /// A workspace of `members`; the first is of `kind`, the others libraries
+    pub fn workspace<S: Into<String>>(kind: CrateKind, members: impl IntoIterator<Item = S>) -> Self {
+        Template { kind, members: members.into_iter().map(Into::into).collect(), ..Template::default() }
+    }
+
+    /// Use the profile bundle of `archetype`
+    pub fn with_archetype(mut self, archetype: ProjectTemplate) -> Self {
+        self.archetype = Some(archetype);
+        self
+    }
+
+    /// Wire cargo-optimize in with `integration`
+    pub fn with_integration(mut self, integration: Integration) -> Self {
+        self.integration = integration;
+        self
+    }
+
+    /// Generate a pipeline for `ci`, or none
+    pub fn with_ci(mut self, ci: Option<CiEnvironment>) -> Self {
+        self.ci = ci;
+        self
+    }
+
+    /// Profile settings of the manifest, as (profile, key, value)
+    pub fn profile_settings(&self) -> Vec<(&'static str, &'static str, &'static str)> {
+        let mut settings: Vec<_> = DEFAULT_PROFILE_SETTINGS.to_vec();
+        for &(profile, key, value) in self.archetype.map(|archetype| archetype.settings()).unwrap_or_default() {
+            settings.retain(|&(p, k, _)| (p, k) != (profile, key));
+            settings.push((profile, key, value));
+        }
+        settings
+    }
+}
+
+/// Files of a generated project, by path relative to its root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scaffold {
+    /// Name of the project and of its root directory
+    pub name: String,
+    /// File contents by relative path
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl Scaffold {
+    /// Content of the file at `path`, relative to the project root
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<&str> {
+        self.files.get(path.as_ref()).map(String::as_str)
+    }
+
+    /// Write the project to `<parent>/<name>`, returning its root
+    ///
+    /// Fails without writing anything when the directory exists and is not empty.
+    pub fn write_to(&self, parent: &Path) -> Result<PathBuf> {
+        output::ensure_enabled()?;
+        let root = parent.join(&self.name);
+        if fs::read_dir(&root).is_ok_and(|mut entries| entries.next().is_some()) {
+            bail!("{} already exists and is not empty", root.display());
+        }
+        for (path, content) in &self.files {
+            let path = root.join(path);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(root)
+    }
+}
+
+/// Render a project called `name` from `template`
+///
+/// Fails for names cargo rejects and for CI services without a pipeline
+/// template (Jenkins, Buildkite, Travis, CircleCI and generic CI); their
+/// cache configuration is still available from [`cache_snippet_for`].
+pub fn new_project(name: &str, template: &Template) -> Result<Scaffold> {
+    output::ensure_enabled()?;
+    for package in std::iter::once(name).chain(template.members.iter().map(String::as_str)) {
+        validate_name(package)?;
+    }
+    let mut files = BTreeMap::new();
+    if template.members.is_empty() {
+        let build_script = template.integration == Integration::BuildScript;
+        let mut manifest = package_manifest(name, build_script, true);
+        add_profiles(&mut manifest, template);
+        files.insert(PathBuf::from("Cargo.toml"), manifest.to_string());
+        add_sources(&mut files, Path::new(""), name, template.kind, build_script);
+    } else {
+        files.insert(PathBuf::from("Cargo.toml"), workspace_manifest(template).to_string());
+        for (index, member) in template.members.iter().enumerate() {
+            let dir = Path::new(MEMBERS_DIR).join(member);
+            // One build script configures the whole workspace
+            let build_script = index == 0 && template.integration == Integration::BuildScript;
+            let kind = if index == 0 { template.kind } else { CrateKind::Library };
+            files.insert(dir.join("Cargo.toml"), package_manifest(member, build_script, false).to_string());
+            add_sources(&mut files, &dir, member, kind, build_script);
+        }
+    }
+    files.insert(PathBuf::from("cargo-optimize.toml"), optimize_config(template));
+    files.insert(PathBuf::from(".gitignore"), gitignore());
+    if template.integration == Integration::InstalledBinary {
+        files.insert(PathBuf::from(".cargo").join("config.toml"), alias_config());
+    }
+    if let Some(ci) = template.ci {
+        let (path, content) = pipeline(ci, template.integration)?;
+        files.insert(PathBuf::from(path), content);
+    }
+    Ok(Scaffold { name: name.to_string(), files })
+}
+
+/// Fail for names cargo does not accept as package names
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.chars().next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!("`{}` is not a valid package name: use ASCII letters, digits, `-` and `_`, starting with a letter", name);
+    }
+    Ok(())
+}
+
+/// Version requirement on this crate
+fn requirement() -> String {
+    format!("{}.{}", env!("CARGO_PKG_VERSION_MAJOR"), env!("CARGO_PKG_VERSION_MINOR"))
+}
+
+fn package_manifest(name: &str, build_script: bool, standalone: bool) -> DocumentMut {
+    let mut doc = DocumentMut::new();
+    let mut package = Table::new();
+    package.insert("name", value(name));
+    if standalone {
+        package.insert("version", value("0.1.0"));
+        package.insert("edition", value("2021"));
+    } else {
+        let mut inherited = toml_edit::InlineTable::new();
+        inherited.insert("workspace", true.into());
+        package.insert("version", value(inherited.clone()));
+        package.insert("edition", value(inherited));
+    }
+    doc.insert("package", Item::Table(package));
+    if build_script {
+        let mut build_dependencies = Table::new();
+        build_dependencies.insert("cargo-optimize", value(requirement()));
+        doc.insert("build-dependencies", Item::Table(build_dependencies));
+    }
+    doc
+}
+
+fn workspace_manifest(template: &Template) -> DocumentMut {
+    let mut doc = DocumentMut::new();
+    let mut workspace = Table::new();
+    workspace.insert("resolver", value("2"));
+    let members: Array = template.members.iter().map(|member| format!("{}/{}", MEMBERS_DIR, member)).collect();
+    workspace.insert("members", value(members));
+    let mut package = Table::new();
+    package.insert("version", value("0.1.0"));
+    package.insert("edition", value("2021"));
+    workspace.insert("package", Item::Table(package));
+    doc.insert("workspace", Item::Table(workspace));
+    // Cargo only reads profiles from the workspace root
+    add_profiles(&mut doc, template);
+    doc
+}
+
+/// Add the template's `[profile.*]` tables
+fn add_profiles(doc: &mut DocumentMut, template: &Template) {
+    let mut profiles = Table::new();
+    profiles.set_implicit(true);
+    for (profile, key, setting) in template.profile_settings() {
+        let table = profiles.entry(profile).or_insert_with(|| Item::Table(Table::new()));
+        let Some(table) = table.as_table_mut() else { continue };
+        match key.split_once('.') {
+            Some((section, key)) => {
+                let nested = table.entry(section).or_insert_with(|| Item::Table(Table::new()));
+                if let Some(nested) = nested.as_table_mut() {
+                    nested.insert(key, Item::Value(profile_value(setting)));
+                }
+            }
+            None => {
+                table.insert(key, Item::Value(profile_value(setting)));
+            }
+        }
+    }
+    doc.insert("profile", Item::Table(profiles));
+}
+
+fn add_sources(files: &mut BTreeMap<PathBuf, String>, dir: &Path, name: &str, kind: CrateKind, build_script: bool) {
+    let source = match kind {
+        CrateKind::Binary => ("main.rs", "fn main() {\n    println!(\"Hello, world!\");\n}\n".to_string()),
+        CrateKind::Library => {
+            ("lib.rs", format!("//! {}\n\npub fn add(left: u64, right: u64) -> u64 {{\n    left + right\n}}\n", name))
+        }
+    };
+    files.insert(dir.join("src").join(source.0), source.1);
+    if build_script {
+        files.insert(dir.join("build.rs"), build_script_source(dir));
+    }
+}
+
+/// `build.rs` of the package in `dir`, relative to the project root
+///
+/// Build scripts run in their package's directory; a workspace member
+/// points cargo-optimize at the root, whose `.cargo/config.toml` cargo reads.
+fn build_script_source(dir: &Path) -> String {
+    let depth = dir.components().count();
+    let call = if depth == 0 {
+        "cargo_optimize::auto_configure();".to_string()
+    } else {
+        let root = vec![".."; depth].join("/");
+        format!(
+            "let root = std::path::Path::new(env!(\"CARGO_MANIFEST_DIR\")).join(\"{}\");\n    \
+             cargo_optimize::mvp::auto_configure_with_options_at(Default::default(), Some(&root));",
+            root
+        )
+    };
+    format!("fn main() {{\n    // Configures the fastest linker for this machine\n    {}\n}}\n", call)
+}
+
+fn optimize_config(template: &Template) -> String {
+    let mut out = String::from(
+        "# cargo-optimize settings, shared by everyone building this project.\n\
+         # Machine-specific values are detected on each machine.\n\n[global]\n",
+    );
+    match template.archetype {
+        // Keeps analysis from choosing other profile settings than the manifest's
+        Some(archetype) => out.push_str(&format!("template = \"{}\"\n", archetype)),
+        None => out.push_str("# template = \"cli\"\n"),
+    }
+    out
+}
+
+fn gitignore() -> String {
+    let audit_dir = Path::new(AUDIT_LOG).parent().map(crate::paths::to_slash).unwrap_or_default();
+    format!(
+        "/target\n\n# cargo-optimize: per-machine state, history, backups and audit log\n\
+         /.cargo/{state}\n/.cargo/{state}.*\n/.cargo/{metrics}\n/.cargo/{metrics}.*\n/.cargo/backups/\n/{audit}/\n",
+        state = STATE_FILE_NAME,
+        metrics = METRICS_FILE_NAME,
+        audit = audit_dir,
+    )
+}
+
+fn alias_config() -> String {
+    let (name, command) = AliasTarget::Installed.alias();
+    let mut aliases = Table::new();
+    aliases.insert(name, value(command));
+    let mut doc = DocumentMut::new();
+    doc.insert("alias", Item::Table(aliases));
+    format!("# `cargo {}` installs the cargo-optimize version this project uses\n{}", name, doc)
+}
+
+/// Path and content of the CI pipeline for `ci`
+fn pipeline(ci: CiEnvironment, integration: Integration) -> Result<(&'static str, String)> {
+    let cache = cache_snippet_for(ci, &CacheLayout::default()).map(|snippet| snippet.content);
+    let apply = integration == Integration::InstalledBinary;
+    let (path, content) = match (ci, cache) {
+        (CiEnvironment::GitHubActions, Some(cache)) => {
+            let mut out = String::from(
+                "name: CI\n\non:\n  push:\n    branches: [main]\n  pull_request:\n\njobs:\n  build:\n    \
+                 runs-on: ubuntu-latest\n    steps:\n      - uses: actions/checkout@v4\n",
+            );
+            out.push_str(&indent(&cache, 6));
+            if apply {
+                out.push_str(
+                    "      - name: Apply cargo-optimize\n        run: cargo optimize-install && cargo optimize\n",
+                );
+            }
+            out.push_str("      - name: Build\n        run: cargo build --all-targets\n");
+            out.push_str("      - name: Test\n        run: cargo test\n");
+            (".github/workflows/ci.yml", out)
+        }
+        (CiEnvironment::GitLabCi, Some(cache)) => {
+            let mut out = cache;
+            out.push_str("\nbuild:\n  image: rust:latest\n  script:\n");
+            if apply {
+                out.push_str("    - cargo optimize-install && cargo optimize\n");
+            }
+            out.push_str("    - cargo build --all-targets\n    - cargo test\n");
+            (".gitlab-ci.yml", out)
+        }
+        (CiEnvironment::AzurePipelines, Some(cache)) => {
+            let mut out = String::from("trigger:\n- main\n\npool:\n  vmImage: ubuntu-latest\n\n");
+            out.push_str(&cache);
+            if apply {
+                out.push_str(
+                    "- script: cargo optimize-install && cargo optimize\n  displayName: Apply cargo-optimize\n",
+                );
+            }
+            out.push_str("- script: cargo build --all-targets\n  displayName: Build\n");
+            out.push_str("- script: cargo test\n  displayName: Test\n");
+            ("azure-pipelines.yml", out)
+        }
+        (ci, _) => bail!("No CI pipeline template for {:?}; use ci::cache_snippet_for for its cache configuration", ci),
+    };
+    Ok((path, content))
+}
+
+fn indent(text: &str, width: usize) -> String {
+    text.lines()
+        .map(|line| if line.is_empty() { "\n".to_string() } else { format!("{:width$}{}\n", "", line, width = width) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_scaffold() {
+        let template = Template::package(CrateKind::Binary).with_archetype(ProjectTemplate::Cli);
+        let scaffold = new_project("fast-cli", &template).unwrap();
+        let paths: Vec<String> = scaffold.files.keys().map(|path| crate::paths::to_slash(path)).collect();
+        assert_eq!(
+            paths,
+            [".github/workflows/ci.yml", ".gitignore", "Cargo.toml", "build.rs", "cargo-optimize.toml", "src/main.rs"]
+        );
+
+        let manifest: DocumentMut = scaffold.file("Cargo.toml").unwrap().parse().unwrap();
+        assert_eq!(manifest["package"]["name"].as_str(), Some("fast-cli"));
+        assert_eq!(manifest["build-dependencies"]["cargo-optimize"].as_str(), Some(requirement().as_str()));
+        assert_eq!(manifest["profile"]["dev"]["debug"].as_str(), Some("line-tables-only"));
+        assert_eq!(manifest["profile"]["release"]["codegen-units"].as_integer(), Some(1));
+        assert_eq!(manifest["profile"]["release"]["strip"].as_bool(), Some(true));
+        assert!(scaffold.file("cargo-optimize.toml").unwrap().contains("template = \"cli\""));
+        assert!(scaffold.file(".gitignore").unwrap().contains("/.cargo/cargo-optimize-state.json.*\n"));
+        let workflow = scaffold.file(".github/workflows/ci.yml").unwrap();
+        assert!(workflow.contains("      - name: Cache cargo\n        uses: actions/cache@v4\n"), "{}", workflow);
+        assert!(!workflow.contains("optimize-install"));
+
+        assert!(new_project("1st", &template).is_err());
+        assert!(new_project("app", &template.clone().with_ci(Some(CiEnvironment::Jenkins))).is_err());
+    }
+
+    #[test]
+    fn test_workspace_scaffold() {
+        let template = Template::workspace(CrateKind::Binary, ["server", "core"])
+            .with_archetype(ProjectTemplate::Embedded)
+            .with_integration(Integration::InstalledBinary)
+            .with_ci(Some(CiEnvironment::GitLabCi));
+        let scaffold = new_project("fleet", &template).unwrap();
+
+        let root: DocumentMut = scaffold.file("Cargo.toml").unwrap().parse().unwrap();
+        assert_eq!(root["workspace"]["members"].as_array().unwrap().len(), 2);
+        assert_eq!(root["profile"]["release"]["build-override"]["opt-level"].as_integer(), Some(0));
+        assert_eq!(root["profile"]["release"]["lto"].as_bool(), Some(true));
+        let server = scaffold.file("crates/server/Cargo.toml").unwrap();
+        assert!(server.contains("version = { workspace = true }") && !server.contains("build-dependencies"));
+        assert!(scaffold.file("crates/server/src/main.rs").is_some());
+        assert!(scaffold.file("crates/core/src/lib.rs").is_some());
+        assert!(scaffold.file("crates/server/build.rs").is_none());
+        assert!(scaffold
+            .file(".cargo/config.toml")
+            .unwrap()
+            .contains("optimize-install = \"install --locked cargo-optimize@"));
+        assert!(scaffold.file(".gitlab-ci.yml").unwrap().contains("    - cargo optimize-install && cargo optimize\n"));
+
+        // One build script, in the first member, configures the workspace root
+        let scaffold = new_project("fleet", &template.clone().with_integration(Integration::BuildScript)).unwrap();
+        let build_script = scaffold.file("crates/server/build.rs").unwrap();
+        assert!(build_script.contains(".join(\"../..\")"), "{}", build_script);
+        assert!(scaffold.file("crates/core/build.rs").is_none());
+    }
+}
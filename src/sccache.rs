@@ -0,0 +1,275 @@
+//! Heuristics for when sccache costs more than it saves.
+//!
+//! sccache does not cache incremental builds and adds a client/server
+//! round-trip to every rustc invocation. On small local projects, whose
+//! dependencies are compiled once and then reused from `target/`, that
+//! overhead is rarely paid back. The cumulative hit statistics of the
+//! sccache server (`sccache --show-stats`) are recorded on every apply; when
+//! the measured hit rate stays below the configured floor, apply removes
+//! the wrapper and says why.
+//!
+//! [`health`] checks the local cache directory itself.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+use crate::analysis::ProjectAnalysis;
+use crate::config::BuildContext;
+
+pub mod health;
+
+/// Explanation id given when the wrapper is removed for a low hit rate
+pub const LOW_HIT_RATE_ID: &str = "CO-SCCACHE-002";
+
+/// Explanation id of storage backend variables written to `[env]`
+pub const BACKEND_ID: &str = "CO-SCCACHE-004";
+
+/// Readings that must stay below the floor before sccache is disabled
+pub const MIN_SAMPLES: usize = 3;
+
+/// Readings kept in the history
+pub const MAX_SAMPLES: usize = 20;
+
+/// Compile requests a reading needs before its hit rate means anything
+const MIN_REQUESTS: u64 = 50;
+
+/// Dependency count below which a single-package project counts as small
+const SMALL_PROJECT_DEPENDENCIES: usize = 40;
+
+/// Cumulative statistics of the running sccache server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SccacheStats {
+    /// Compilations served from the cache
+    pub hits: u64,
+    /// Cacheable compilations that missed
+    pub misses: u64,
+}
+
+impl SccacheStats {
+    /// Parse the output of `sccache --show-stats --stats-format=json`
+    ///
+    /// Hits and misses are summed over all languages.
+    pub fn parse_json(output: &str) -> Option<Self> {
+        let json: Value = serde_json::from_str(output).ok()?;
+        let stats = json.get("stats")?;
+        Some(SccacheStats {
+            hits: sum_counts(stats.get("cache_hits")?),
+            misses: sum_counts(stats.get("cache_misses")?),
+        })
+    }
+
+    /// Cacheable compile requests
+    pub fn requests(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    /// Share of requests served from the cache, from 0 to 1
+    pub fn hit_rate(&self) -> f64 {
+        match self.requests() {
+            0 => 0.0,
+            requests => self.hits as f64 / requests as f64,
+        }
+    }
+}
+
+/// Counts are `{"counts": {"Rust": 12, "C/C++": 3}}` in current sccache versions
+fn sum_counts(value: &Value) -> u64 {
+    value
+        .get("counts")
+        .and_then(Value::as_object)
+        .map(|counts| counts.values().filter_map(Value::as_u64).sum())
+        .or_else(|| value.as_u64())
+        .unwrap_or(0)
+}
+
+/// Statistics of the local sccache server, if sccache is installed and running
+pub fn current_stats() -> Option<SccacheStats> {
+    let output = Command::new("sccache")
+        .args(["--show-stats", "--stats-format=json"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stats = SccacheStats::parse_json(&String::from_utf8_lossy(&output.stdout));
+    if stats.is_none() {
+        debug!("Could not parse sccache statistics");
+    }
+    stats
+}
+
+/// One reading of the server statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SccacheSample {
+    /// When the reading was taken (seconds since the Unix epoch)
+    pub recorded_at: u64,
+    /// Statistics at that time
+    pub stats: SccacheStats,
+}
+
+/// Readings of the sccache statistics taken on past applies
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SccacheHistory {
+    /// Readings, oldest first
+    #[serde(default)]
+    pub samples: Vec<SccacheSample>,
+}
+
+impl SccacheHistory {
+    /// Whether no reading was recorded
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Add a reading
+    ///
+    /// Readings without new requests since the previous one carry no
+    /// information and are dropped, as are the oldest beyond [`MAX_SAMPLES`].
+    pub fn record(&mut self, stats: SccacheStats) {
+        if self.samples.last().is_some_and(|last| last.stats == stats) {
+            return;
+        }
+        let recorded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.samples.push(SccacheSample { recorded_at, stats });
+        let excess = self.samples.len().saturating_sub(MAX_SAMPLES);
+        self.samples.drain(..excess);
+    }
+
+    /// Hit rate of the latest meaningful reading, if the last [`MIN_SAMPLES`]
+    /// meaningful readings were all below `floor`
+    pub fn below_floor(&self, floor: f64) -> Option<f64> {
+        let recent: Vec<&SccacheSample> = self
+            .samples
+            .iter()
+            .rev()
+            .filter(|sample| sample.stats.requests() >= MIN_REQUESTS)
+            .take(MIN_SAMPLES)
+            .collect();
+        if recent.len() < MIN_SAMPLES || recent.iter().any(|sample| sample.stats.hit_rate() >= floor) {
+            return None;
+        }
+        Some(recent[0].stats.hit_rate())
+    }
+}
+
+/// Size of the project, as far as sccache's payoff is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProjectSize {
+    /// Workspace members
+    pub members: usize,
+    /// Dependencies in the resolved graph
+    pub dependencies: usize,
+}
+
+impl ProjectSize {
+    /// Size of an analyzed project
+    ///
+    /// `None` for a preliminary analysis: before `Cargo.lock` exists only the
+    /// declared dependencies are known, which would make any project look small.
+    pub fn from_analysis(analysis: &ProjectAnalysis) -> Option<Self> {
+        if analysis.preliminary {
+            return None;
+        }
+        Some(ProjectSize {
+            members: analysis.workspace_members.len(),
+            dependencies: analysis.total_dependencies,
+        })
+    }
+
+    /// Whether dependencies compiled once locally outweigh anything a cache can save
+    pub fn is_small(&self) -> bool {
+        self.members <= 1 && self.dependencies < SMALL_PROJECT_DEPENDENCIES
+    }
+}
+
+/// What to do about sccache
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SccacheVerdict {
+    /// Keep the wrapper
+    Keep,
+    /// Small local project: recommend removing the wrapper
+    RecommendDisable {
+        /// Dependencies of the project
+        dependencies: usize,
+    },
+    /// The measured hit rate stayed below the floor: remove the wrapper
+    Disable {
+        /// Latest measured hit rate
+        hit_rate: f64,
+    },
+}
+
+/// Decide whether sccache pays off
+///
+/// Measurements win over size: a low hit rate disables sccache in any
+/// context, while the size heuristic only applies to developer machines,
+/// where `target/` persists between builds.
+pub fn assess(
+    size: Option<ProjectSize>,
+    history: &SccacheHistory,
+    floor: f64,
+    context: BuildContext,
+) -> SccacheVerdict {
+    if let Some(hit_rate) = history.below_floor(floor) {
+        return SccacheVerdict::Disable { hit_rate };
+    }
+    match size {
+        Some(size) if context == BuildContext::Local && size.is_small() => {
+            SccacheVerdict::RecommendDisable { dependencies: size.dependencies }
+        }
+        _ => SccacheVerdict::Keep,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(readings: &[(u64, u64)]) -> SccacheHistory {
+        let mut history = SccacheHistory::default();
+        for (hits, misses) in readings {
+            history.record(SccacheStats { hits: *hits, misses: *misses });
+        }
+        history
+    }
+
+    #[test]
+    fn test_parse_stats_json() {
+        let output = r#"{"stats":{"compile_requests":120,"cache_hits":{"counts":{"Rust":30,"C/C++":2}},
+            "cache_misses":{"counts":{"Rust":88}},"cache_errors":{"counts":{}}}}"#;
+        let stats = SccacheStats::parse_json(output).unwrap();
+        assert_eq!(stats, SccacheStats { hits: 32, misses: 88 });
+        assert!((stats.hit_rate() - 32.0 / 120.0).abs() < 1e-9);
+        assert_eq!(SccacheStats::parse_json("not json"), None);
+        assert_eq!(SccacheStats::default().hit_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_assessment() {
+        let small = Some(ProjectSize { members: 1, dependencies: 12 });
+        let large = Some(ProjectSize { members: 6, dependencies: 300 });
+
+        // Too few meaningful readings to judge the hit rate
+        let sparse = history(&[(1, 9), (5, 60), (5, 60), (6, 70)]);
+        assert_eq!(sparse.samples.len(), 3);
+        assert_eq!(assess(large, &sparse, 0.2, BuildContext::Local), SccacheVerdict::Keep);
+        assert_eq!(
+            assess(small, &sparse, 0.2, BuildContext::Local),
+            SccacheVerdict::RecommendDisable { dependencies: 12 }
+        );
+        assert_eq!(assess(small, &sparse, 0.2, BuildContext::Ci), SccacheVerdict::Keep);
+
+        let cold = history(&[(5, 60), (8, 90), (10, 140)]);
+        assert!(matches!(
+            assess(large, &cold, 0.2, BuildContext::Ci),
+            SccacheVerdict::Disable { hit_rate } if (hit_rate - 10.0 / 150.0).abs() < 1e-9
+        ));
+
+        let warming = history(&[(5, 60), (8, 90), (100, 140)]);
+        assert_eq!(assess(large, &warming, 0.2, BuildContext::Local), SccacheVerdict::Keep);
+    }
+}
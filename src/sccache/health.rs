@@ -0,0 +1,388 @@
+//! Health of the local sccache cache directory.
+//!
+//! A cache grown past its cap, entries sccache cannot read, or truncated
+//! entries left by a crash do not make builds fail: sccache treats them as
+//! misses and the hit rate quietly drops. [`check`] looks for all three and
+//! for a falling hit rate in the statistics recorded on past applies.
+//! [`repair`] removes corrupt entries only, or rotates the whole directory
+//! when it is unreadable or over its cap. The directory keeps its path, so
+//! `SCCACHE_DIR` and the sccache config stay valid.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use super::{SccacheHistory, MIN_REQUESTS};
+
+/// sccache's default cache size cap (10 GiB)
+pub const DEFAULT_CACHE_SIZE: u64 = 10 << 30;
+
+/// Share of the earlier hit rate below which the latest one counts as falling
+const FALLING_HIT_RATE_RATIO: f64 = 0.5;
+
+/// Earlier hit rate below which a drop is not worth reporting
+const MIN_HEALTHY_HIT_RATE: f64 = 0.2;
+
+/// Unreadable paths listed in an issue
+const MAX_LISTED_PATHS: usize = 5;
+
+/// Entries are zip archives
+const ENTRY_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Where the local cache lives and how large it may grow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheLocation {
+    /// Cache directory
+    pub dir: PathBuf,
+    /// Size cap in bytes
+    pub max_size: u64,
+}
+
+impl CacheLocation {
+    /// Parse the output of `sccache --show-stats --stats-format=json`
+    ///
+    /// `None` unless the server uses a local disk cache.
+    pub fn parse_json(output: &str) -> Option<Self> {
+        let json: Value = serde_json::from_str(output).ok()?;
+        let location = json.get("cache_location")?.as_str()?;
+        let dir = location.strip_prefix("Local disk: ")?.trim_matches('"');
+        Some(CacheLocation {
+            dir: PathBuf::from(dir),
+            max_size: json.get("max_cache_size").and_then(Value::as_u64).unwrap_or(DEFAULT_CACHE_SIZE),
+        })
+    }
+
+    /// Location used by the running server, or the one `SCCACHE_DIR` and
+    /// `SCCACHE_CACHE_SIZE` select
+    pub fn detect() -> Option<Self> {
+        let running = Command::new("sccache")
+            .args(["--show-stats", "--stats-format=json"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| Self::parse_json(&String::from_utf8_lossy(&output.stdout)));
+        running.or_else(|| {
+            let dir = std::env::var_os("SCCACHE_DIR").map(PathBuf::from).or_else(default_dir)?;
+            let max_size = std::env::var("SCCACHE_CACHE_SIZE")
+                .ok()
+                .and_then(|size| parse_size(&size))
+                .unwrap_or(DEFAULT_CACHE_SIZE);
+            Some(CacheLocation { dir, max_size })
+        })
+    }
+}
+
+/// sccache's default cache directory on this platform
+fn default_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("Mozilla").join("sccache").join("cache"))
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Caches").join("Mozilla.sccache"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".cache")))
+            .map(|dir| dir.join("sccache"))
+    }
+}
+
+/// Parse a cache size such as `10G` or `512M`, as sccache does
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (digits, shift) = match size.char_indices().last()? {
+        (i, 'K' | 'k') => (&size[..i], 10),
+        (i, 'M' | 'm') => (&size[..i], 20),
+        (i, 'G' | 'g') => (&size[..i], 30),
+        (i, 'T' | 't') => (&size[..i], 40),
+        _ => (size, 0),
+    };
+    digits.trim().parse::<u64>().ok().map(|value| value << shift)
+}
+
+/// Something degrading the cache
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthIssue {
+    /// The cache is larger than its cap, so sccache keeps evicting
+    OverCap {
+        /// Measured size in bytes
+        size: u64,
+        /// Cap in bytes
+        cap: u64,
+    },
+    /// Files or directories the current user cannot read
+    Unreadable {
+        /// Up to [`MAX_LISTED_PATHS`] of them
+        paths: Vec<PathBuf>,
+        /// How many there are
+        count: usize,
+    },
+    /// Entries that are empty or not archives, such as ones a crash truncated
+    Corrupt {
+        /// The entries
+        entries: Vec<PathBuf>,
+    },
+    /// The hit rate since the last reading dropped well below the earlier one
+    HitRateFalling {
+        /// Hit rate over the earlier readings
+        earlier: f64,
+        /// Hit rate since the reading before the last
+        latest: f64,
+    },
+}
+
+impl HealthIssue {
+    /// One-line description for reports
+    pub fn describe(&self) -> String {
+        let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        match self {
+            HealthIssue::OverCap { size, cap } => {
+                format!("cache holds {:.1} MB, over its cap of {:.1} MB", mb(*size), mb(*cap))
+            }
+            HealthIssue::Unreadable { paths, count } => {
+                let listed: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+                format!("{} path(s) cannot be read, e.g. {}", count, listed.join(", "))
+            }
+            HealthIssue::Corrupt { entries } => format!("{} corrupt cache entries", entries.len()),
+            HealthIssue::HitRateFalling { earlier, latest } => format!(
+                "hit rate fell from {:.0}% to {:.0}% since the last readings",
+                earlier * 100.0,
+                latest * 100.0
+            ),
+        }
+    }
+}
+
+/// Result of [`check`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheHealth {
+    /// The cache checked
+    pub location: CacheLocation,
+    /// Bytes of all readable files
+    pub size: u64,
+    /// What is wrong, empty for a healthy cache
+    pub issues: Vec<HealthIssue>,
+}
+
+impl CacheHealth {
+    /// Whether nothing is wrong
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Whether the directory needs replacing rather than pruning
+    fn needs_rotation(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| matches!(issue, HealthIssue::OverCap { .. } | HealthIssue::Unreadable { .. }))
+    }
+}
+
+/// Walk the cache directory and look at the recorded statistics
+///
+/// A missing directory is healthy: sccache has not cached anything yet.
+pub fn check(location: &CacheLocation, history: &SccacheHistory) -> CacheHealth {
+    let mut walk = Walk::default();
+    if location.dir.exists() {
+        walk.visit(&location.dir, 0);
+    }
+
+    let mut issues = Vec::new();
+    if walk.size > location.max_size {
+        issues.push(HealthIssue::OverCap { size: walk.size, cap: location.max_size });
+    }
+    if !walk.unreadable.is_empty() {
+        issues.push(HealthIssue::Unreadable {
+            count: walk.unreadable.len(),
+            paths: walk.unreadable.into_iter().take(MAX_LISTED_PATHS).collect(),
+        });
+    }
+    if !walk.corrupt.is_empty() {
+        issues.push(HealthIssue::Corrupt { entries: walk.corrupt });
+    }
+    if let Some((earlier, latest)) = hit_rate_trend(history) {
+        if earlier >= MIN_HEALTHY_HIT_RATE && latest < earlier * FALLING_HIT_RATE_RATIO {
+            issues.push(HealthIssue::HitRateFalling { earlier, latest });
+        }
+    }
+    CacheHealth { location: location.clone(), size: walk.size, issues }
+}
+
+#[derive(Default)]
+struct Walk {
+    size: u64,
+    unreadable: Vec<PathBuf>,
+    corrupt: Vec<PathBuf>,
+}
+
+impl Walk {
+    fn visit(&mut self, dir: &Path, depth: usize) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("Cannot read {:?}: {}", dir, e);
+                self.unreadable.push(dir.to_path_buf());
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                self.unreadable.push(path);
+                continue;
+            };
+            if metadata.is_dir() {
+                self.visit(&path, depth + 1);
+                continue;
+            }
+            self.size += metadata.len();
+            // Entries sit in two levels of fan-out directories, named by their hash
+            if depth < 2 || !is_hash(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let mut magic = [0; 4];
+            match File::open(&path).and_then(|mut file| file.read_exact(&mut magic)) {
+                Ok(()) if magic == ENTRY_MAGIC => {}
+                Ok(()) => self.corrupt.push(path),
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => self.unreadable.push(path),
+                Err(_) => self.corrupt.push(path),
+            }
+        }
+    }
+}
+
+fn is_hash(name: &str) -> bool {
+    name.len() >= 32 && name.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Hit rate over the earlier readings and over the latest interval
+///
+/// Readings are cumulative since the server started; a reading lower than
+/// the one before it starts a new series. Intervals need enough requests
+/// to mean anything.
+fn hit_rate_trend(history: &SccacheHistory) -> Option<(f64, f64)> {
+    let mut intervals = Vec::new();
+    for pair in history.samples.windows(2) {
+        let (before, after) = (pair[0].stats, pair[1].stats);
+        let (hits, misses) = match (after.hits.checked_sub(before.hits), after.misses.checked_sub(before.misses)) {
+            (Some(hits), Some(misses)) => (hits, misses),
+            // The server restarted; its counts start from zero
+            _ => (after.hits, after.misses),
+        };
+        if hits + misses >= MIN_REQUESTS {
+            intervals.push((hits, misses));
+        }
+    }
+    let (latest, earlier) = intervals.split_last()?;
+    let (hits, misses) = earlier.iter().fold((0, 0), |(h, m), (hits, misses)| (h + hits, m + misses));
+    if hits + misses == 0 {
+        return None;
+    }
+    let rate = |hits: u64, misses: u64| hits as f64 / (hits + misses) as f64;
+    Some((rate(hits, misses), rate(latest.0, latest.1)))
+}
+
+/// What [`repair`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// Corrupt entries were deleted, everything else kept
+    RemovedCorrupt {
+        /// Entries deleted
+        removed: usize,
+    },
+    /// The directory was moved aside and replaced by an empty one
+    Rotated {
+        /// Where the old cache now is
+        previous: PathBuf,
+    },
+}
+
+/// Fix what [`check`] found
+///
+/// Corrupt entries are deleted one by one. A directory over its cap or with
+/// unreadable files is rotated instead: the sccache server is stopped (it
+/// starts again on the next compile), the directory renamed to
+/// `<dir>.old` — replacing an earlier rotation — and an empty one created
+/// at the same path. A falling hit rate alone needs no repair. Returns
+/// `None` when nothing was done.
+pub fn repair(health: &CacheHealth) -> Result<Option<Repair>> {
+    crate::output::ensure_enabled()?;
+    let dir = &health.location.dir;
+    if health.needs_rotation() {
+        let _ = Command::new("sccache").arg("--stop-server").output();
+        let previous = rotated_path(dir);
+        if previous.exists() {
+            fs::remove_dir_all(&previous).with_context(|| format!("Failed to remove {:?}", previous))?;
+        }
+        fs::rename(dir, &previous).with_context(|| format!("Failed to move {:?} aside", dir))?;
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        debug!("Rotated sccache cache {:?} to {:?}", dir, previous);
+        return Ok(Some(Repair::Rotated { previous }));
+    }
+
+    let corrupt = health.issues.iter().find_map(|issue| match issue {
+        HealthIssue::Corrupt { entries } => Some(entries),
+        _ => None,
+    });
+    let Some(entries) = corrupt else { return Ok(None) };
+    for entry in entries {
+        fs::remove_file(entry).with_context(|| format!("Failed to remove corrupt entry {:?}", entry))?;
+    }
+    Ok(Some(Repair::RemovedCorrupt { removed: entries.len() }))
+}
+
+/// Where a rotated cache directory is moved
+pub fn rotated_path(dir: &Path) -> PathBuf {
+    let mut name = dir.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    dir.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sccache::SccacheStats;
+
+    #[test]
+    fn test_corrupt_entries_are_removed_and_the_rest_kept() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let fan_out = dir.path().join("a").join("b");
+        fs::create_dir_all(&fan_out).unwrap();
+        let good = fan_out.join("ab".repeat(32));
+        let truncated = fan_out.join("cd".repeat(32));
+        fs::write(&good, b"PK\x03\x04rest of the archive").unwrap();
+        fs::write(&truncated, b"").unwrap();
+        fs::write(dir.path().join("config"), b"not an entry").unwrap();
+
+        let location = CacheLocation { dir: dir.path().to_path_buf(), max_size: DEFAULT_CACHE_SIZE };
+        let health = check(&location, &SccacheHistory::default());
+        assert_eq!(health.issues, [HealthIssue::Corrupt { entries: vec![truncated.clone()] }]);
+        assert_eq!(repair(&health).unwrap(), Some(Repair::RemovedCorrupt { removed: 1 }));
+        assert!(good.exists() && !truncated.exists());
+        assert!(check(&location, &SccacheHistory::default()).is_healthy());
+
+        let capped = CacheLocation { max_size: 8, ..location };
+        assert!(matches!(check(&capped, &SccacheHistory::default()).issues[..], [HealthIssue::OverCap { .. }]));
+    }
+
+    #[test]
+    fn test_falling_hit_rate_and_location_parsing() {
+        let mut history = SccacheHistory::default();
+        for (hits, misses) in [(0, 0), (80, 20), (160, 40), (170, 130)] {
+            history.record(SccacheStats { hits, misses });
+        }
+        let (earlier, latest) = hit_rate_trend(&history).unwrap();
+        assert!((earlier - 0.8).abs() < 1e-9 && (latest - 0.1).abs() < 1e-9);
+
+        let output = r#"{"stats":{},"cache_location":"Local disk: \"/home/dev/.cache/sccache\"","max_cache_size":1024}"#;
+        let location = CacheLocation::parse_json(output).unwrap();
+        assert_eq!(location, CacheLocation { dir: PathBuf::from("/home/dev/.cache/sccache"), max_size: 1024 });
+        assert_eq!(CacheLocation::parse_json(r#"{"cache_location":"S3, bucket: b"}"#), None);
+        assert_eq!(parse_size("10G"), Some(10 << 30));
+        assert_eq!(rotated_path(Path::new("/c/sccache")), PathBuf::from("/c/sccache.old"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Distributed compilation with sccache-dist.
+//!
+//! Organizations with a build farm run an sccache-dist scheduler that hands
+//! compilations to build servers. The client side is sccache itself, set as
+//! the rustc wrapper, with a `[dist]` section in its client config naming
+//! the scheduler. [`detect`] finds the scheduler in cargo-optimize.toml or in
+//! that client config, [`probe`] checks with a plain TCP connect that it
+//! answers, and [`client_config`] renders the client section when it is
+//! missing. When the farm is unreachable sccache compiles locally, so a
+//! wrapper written earlier is left in place.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::proxy::{self, Proxy};
+
+/// Explanation id of a wrapper written for sccache-dist
+pub const DIST_ID: &str = "CO-SCCACHE-003";
+
+/// Environment variable that makes sccache skip the farm and compile locally
+pub const NO_DIST_ENV_VAR: &str = "SCCACHE_NO_DIST_COMPILE";
+
+/// Port the sccache-dist scheduler listens on unless configured otherwise
+const DEFAULT_SCHEDULER_PORT: u16 = 10600;
+
+/// How long the connectivity probe waits for the scheduler
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the scheduler URL was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SchedulerSource {
+    /// `sccache_dist_scheduler` under `[global]` in cargo-optimize.toml
+    Settings,
+    /// `[dist] scheduler_url` in the sccache client config at this path
+    ClientConfig(PathBuf),
+}
+
+/// A configured sccache-dist scheduler
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistScheduler {
+    /// Scheduler URL, e.g. `https://sccache.example.com`
+    pub url: String,
+    /// Where it was configured
+    pub source: SchedulerSource,
+    /// Whether the sccache client config names this scheduler
+    pub client_configured: bool,
+}
+
+/// Outcome of the connectivity probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reachability {
+    /// The scheduler accepted a connection
+    Reachable {
+        /// Time to connect
+        latency_ms: u64,
+    },
+    /// It did not, with the reason
+    Unreachable {
+        /// Resolution or connection error
+        reason: String,
+    },
+}
+
+/// Path of the sccache client config: `SCCACHE_CONF`, or the platform default
+pub fn client_config_path<F>(lookup: F) -> Option<PathBuf>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(path) = lookup("SCCACHE_CONF") {
+        return Some(PathBuf::from(path));
+    }
+    if cfg!(windows) {
+        return lookup("APPDATA").map(|dir| Path::new(&dir).join("Mozilla").join("sccache").join("config").join("config"));
+    }
+    if cfg!(target_os = "macos") {
+        return lookup("HOME")
+            .map(|home| Path::new(&home).join("Library").join("Application Support").join("Mozilla.sccache").join("config"));
+    }
+    lookup("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| lookup("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("sccache").join("config"))
+}
+
+/// `[dist] scheduler_url` of an sccache client config
+pub fn configured_scheduler(client_config: &str) -> Option<String> {
+    let doc = client_config.parse::<toml_edit::DocumentMut>().ok()?;
+    doc.get("dist")?.get("scheduler_url")?.as_str().map(str::to_string)
+}
+
+/// Find the scheduler: cargo-optimize.toml first, then the sccache client config
+pub fn detect(configured: Option<&str>, client_config: Option<&Path>) -> Option<DistScheduler> {
+    let client_url = client_config
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| configured_scheduler(&content));
+    match (configured, client_url) {
+        (Some(url), client_url) => Some(DistScheduler {
+            url: url.to_string(),
+            source: SchedulerSource::Settings,
+            client_configured: client_url.as_deref().is_some_and(|client| same_scheduler(client, url)),
+        }),
+        (None, Some(url)) => Some(DistScheduler {
+            url,
+            source: SchedulerSource::ClientConfig(client_config?.to_path_buf()),
+            client_configured: true,
+        }),
+        (None, None) => None,
+    }
+}
+
+fn same_scheduler(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+/// Host and port to connect to for a scheduler URL
+///
+/// Explicit ports win; otherwise `https` means 443, `http` 80 and a bare
+/// host the scheduler's default port.
+pub fn scheduler_address(url: &str) -> Option<(String, u16)> {
+    let (default_port, rest) = match url.split_once("://") {
+        Some(("https", rest)) => (443, rest),
+        Some(("http", rest)) => (80, rest),
+        Some(_) => return None,
+        None => (DEFAULT_SCHEDULER_PORT, url),
+    };
+    let authority = rest.split('/').next()?;
+    if authority.is_empty() {
+        return None;
+    }
+    // IPv6 literals keep their colons inside brackets
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+        _ => (authority, default_port),
+    };
+    Some((host.trim_start_matches('[').trim_end_matches(']').to_string(), port))
+}
+
+/// Check that the scheduler accepts connections, waiting at most two seconds
+pub fn probe(url: &str) -> Reachability {
+    probe_with(url, &Proxy::default())
+}
+
+/// Check that the scheduler accepts connections, through `proxy` unless `NO_PROXY` lists it
+///
+/// Through a proxy the probe asks for a tunnel with `CONNECT`, as sccache
+/// does, so a proxy refusing the scheduler or wanting credentials is
+/// reported as such rather than as a timeout.
+pub fn probe_with(url: &str, proxy: &Proxy) -> Reachability {
+    let Some((host, port)) = scheduler_address(url) else {
+        return Reachability::Unreachable { reason: format!("`{}` is not a scheduler URL", url) };
+    };
+    if let Some(through) = proxy.for_url(url) {
+        let started = Instant::now();
+        // Plain HTTP goes through the proxy as a request; only TLS needs a tunnel
+        let reached = if url.to_ascii_lowercase().starts_with("https://") {
+            proxy::connect_through(through, &host, port, PROBE_TIMEOUT)
+        } else {
+            proxy::get_through(through, &host, port, PROBE_TIMEOUT)
+        };
+        return match reached {
+            Ok(_) => Reachability::Reachable { latency_ms: started.elapsed().as_millis() as u64 },
+            Err(failure) => Reachability::Unreachable { reason: failure.to_string() },
+        };
+    }
+    let address = match (host.as_str(), port).to_socket_addrs().map(|mut addresses| addresses.next()) {
+        Ok(Some(address)) => address,
+        Ok(None) => return Reachability::Unreachable { reason: format!("{} has no address", host) },
+        Err(e) => {
+            let error = format!("cannot resolve {}: {}", host, e);
+            return Reachability::Unreachable { reason: proxy::explain(&error, None) };
+        }
+    };
+    let started = Instant::now();
+    match TcpStream::connect_timeout(&address, PROBE_TIMEOUT) {
+        Ok(_) => Reachability::Reachable { latency_ms: started.elapsed().as_millis() as u64 },
+        Err(e) => Reachability::Unreachable { reason: proxy::explain(&format!("{}:{}: {}", host, port, e), None) },
+    }
+}
+
+/// Client config section pointing sccache at the scheduler
+///
+/// The token is left as a placeholder: it is a secret the farm's admins hand out.
+pub fn client_config(url: &str) -> String {
+    format!(
+        "[dist]\n\
+         scheduler_url = {}\n\
+         # Toolchains are packaged and sent to the build servers automatically on Linux\n\
+         toolchains = []\n\
+         \n\
+         [dist.auth]\n\
+         type = \"token\"\n\
+         token = \"<client token>\"\n",
+        toml_edit::Value::from(url)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheduler_address() {
+        assert_eq!(scheduler_address("https://sccache.example.com"), Some(("sccache.example.com".to_string(), 443)));
+        assert_eq!(scheduler_address("http://10.0.0.5:10600/"), Some(("10.0.0.5".to_string(), 10600)));
+        assert_eq!(scheduler_address("scheduler.internal"), Some(("scheduler.internal".to_string(), 10600)));
+        assert_eq!(scheduler_address("http://[::1]:8080"), Some(("::1".to_string(), 8080)));
+        assert_eq!(scheduler_address("ftp://example.com"), None);
+    }
+
+    #[test]
+    fn test_detect_and_probe() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = temp.path().join("config");
+        assert_eq!(detect(None, Some(&config)), None);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        fs::write(&config, client_config(&url)).unwrap();
+        let scheduler = detect(None, Some(&config)).unwrap();
+        assert_eq!(scheduler.source, SchedulerSource::ClientConfig(config.clone()));
+        assert!(matches!(probe(&scheduler.url), Reachability::Reachable { .. }));
+
+        // A scheduler named only in cargo-optimize.toml needs a client config
+        let scheduler = detect(Some("https://farm.example.com"), Some(&config)).unwrap();
+        assert_eq!(scheduler.source, SchedulerSource::Settings);
+        assert!(!scheduler.client_configured);
+
+        drop(listener);
+        assert!(matches!(probe(&url), Reachability::Unreachable { .. }));
+
+        // URLs are written as TOML strings, whatever they contain
+        let url = r#"http://farm.example.com/"quoted"\path"#;
+        assert_eq!(configured_scheduler(&client_config(url)).as_deref(), Some(url));
+    }
+
+    #[test]
+    fn test_probe_through_proxy() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = Proxy {
+            https: Some(format!("http://{}", listener.local_addr().unwrap())),
+            no_proxy: vec![".internal".to_string()],
+            ..Default::default()
+        };
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 512]).unwrap();
+            stream.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").unwrap();
+        });
+        let Reachability::Unreachable { reason } = probe_with("https://farm.example.com", &proxy) else {
+            panic!("the proxy wants credentials");
+        };
+        assert!(reason.contains("requires authentication"), "{}", reason);
+        server.join().unwrap();
+
+        // Hosts in NO_PROXY are probed directly
+        let Reachability::Unreachable { reason } = probe_with("https://farm.internal:1", &proxy) else {
+            panic!("nothing listens on farm.internal");
+        };
+        assert!(!reason.contains("requires authentication"), "{}", reason);
+    }
+}
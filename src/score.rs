@@ -0,0 +1,432 @@
+//! How optimized a project already is.
+//!
+//! [`compute`] grades a project from 0 to 100 across five categories: the
+//! linker, compiler caching, profile settings, dependency hygiene and CI
+//! settings. Each category is a list of checks worth a number of points;
+//! categories that do not apply (no CI config, no project analysis, a
+//! platform without fast linkers) are left out of the total instead of
+//! counting as failures. The missed check worth the most overall becomes the
+//! top next action.
+//!
+//! Scores serialize to JSON for dashboards and can be kept over time with
+//! [`crate::metrics::MetricsHistory::record_score`].
+
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use toml_edit::{Array, DocumentMut, Item};
+
+use crate::analysis::{ImpactLevel, ProjectAnalysis};
+use crate::capabilities::{Availability, Feature};
+use crate::linkers::Platform;
+use crate::sccache::ProjectSize;
+use crate::toolchain::ToolchainSpec;
+
+/// CI configuration files read for the CI category, relative to the project root
+const CI_CONFIG_PATHS: &[&str] = &[".gitlab-ci.yml", ".circleci/config.yml", "azure-pipelines.yml", ".travis.yml"];
+
+/// Directory of GitHub Actions workflows
+const GITHUB_WORKFLOWS: &str = ".github/workflows";
+
+/// Area of the build a check belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Fast linker
+    Linker,
+    /// Incremental compilation and compiler caches
+    Caching,
+    /// Profile settings
+    Profiles,
+    /// Dependency features and workspace default members
+    Dependencies,
+    /// CI caching and settings
+    Ci,
+}
+
+impl Category {
+    /// Every category, in report order
+    pub const ALL: [Category; 5] =
+        [Category::Linker, Category::Caching, Category::Profiles, Category::Dependencies, Category::Ci];
+
+    /// Name used in output and JSON
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Linker => "linker",
+            Category::Caching => "caching",
+            Category::Profiles => "profiles",
+            Category::Dependencies => "dependency hygiene",
+            Category::Ci => "CI settings",
+        }
+    }
+
+    /// Share of the total score, in percent
+    pub fn weight(self) -> u32 {
+        match self {
+            Category::Linker => 25,
+            Category::Caching => 20,
+            Category::Profiles => 25,
+            Category::Dependencies => 15,
+            Category::Ci => 15,
+        }
+    }
+}
+
+/// One graded property of the project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    /// Short description of what is checked
+    pub name: &'static str,
+    /// Points the check is worth within its category
+    pub points: u32,
+    /// Whether the project passes
+    pub passed: bool,
+    /// What to do to pass
+    pub action: String,
+}
+
+/// Score of one category
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CategoryScore {
+    /// Category graded
+    pub category: Category,
+    /// Score from 0 to 100
+    pub score: u8,
+    /// Checks of the category
+    pub checks: Vec<Check>,
+}
+
+/// Score of a project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Score {
+    /// Weighted score over the applicable categories, from 0 to 100
+    pub total: u8,
+    /// Applicable categories
+    pub categories: Vec<CategoryScore>,
+    /// Missed check that would raise the total the most
+    pub next_action: Option<String>,
+}
+
+impl Score {
+    /// Score of a category, if it applies to the project
+    pub fn category(&self, category: Category) -> Option<&CategoryScore> {
+        self.categories.iter().find(|score| score.category == category)
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Optimization score: {}/100", self.total)?;
+        for category in &self.categories {
+            writeln!(f, "  {:<20} {:>3}", category.category.name(), category.score)?;
+        }
+        if let Some(action) = &self.next_action {
+            writeln!(f, "Next: {}", action)?;
+        }
+        Ok(())
+    }
+}
+
+/// What the score is computed from
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScoreInput<'a> {
+    /// The project's `.cargo/config.toml`
+    pub config: Option<&'a DocumentMut>,
+    /// The project's `Cargo.toml`, for profiles defined there
+    pub manifest: Option<&'a DocumentMut>,
+    /// Host platform
+    pub platform: Option<Platform>,
+    /// Active toolchain
+    pub toolchain: Option<ToolchainSpec>,
+    /// Project analysis, for dependency hygiene
+    pub analysis: Option<&'a ProjectAnalysis>,
+    /// Contents of the project's CI configuration files
+    pub ci_configs: &'a [String],
+}
+
+/// Grade a project
+pub fn compute(input: &ScoreInput<'_>) -> Score {
+    let categories: Vec<CategoryScore> = Category::ALL
+        .iter()
+        .filter_map(|category| {
+            let checks = checks(*category, input);
+            let possible: u32 = checks.iter().map(|check| check.points).sum();
+            if possible == 0 {
+                return None;
+            }
+            let earned: u32 = checks.iter().filter(|check| check.passed).map(|check| check.points).sum();
+            let score = (earned * 100 / possible) as u8;
+            Some(CategoryScore { category: *category, score, checks })
+        })
+        .collect();
+
+    let weights: u32 = categories.iter().map(|score| score.category.weight()).sum();
+    let weighted: u32 = categories.iter().map(|score| score.category.weight() * u32::from(score.score)).sum();
+    let total = weighted.checked_div(weights).unwrap_or(100) as u8;
+
+    // A check is worth its share of its category times the category's weight
+    let gain = |score: &CategoryScore, check: &Check| {
+        let possible: u32 = score.checks.iter().map(|check| check.points).sum();
+        check.points * score.category.weight() * 1000 / possible
+    };
+    let next_action = categories
+        .iter()
+        .flat_map(|score| score.checks.iter().filter(|check| !check.passed).map(move |check| (score, check)))
+        .max_by_key(|(score, check)| gain(score, check))
+        .map(|(_, check)| check.action.clone());
+
+    Score { total, categories, next_action }
+}
+
+/// Grade the project at `project_root` from its files
+///
+/// Reads `.cargo/config.toml`, `Cargo.toml` and the CI configuration; missing
+/// or unparsable files count as empty.
+pub fn for_project(
+    project_root: &Path,
+    platform: Option<Platform>,
+    toolchain: Option<ToolchainSpec>,
+    analysis: Option<&ProjectAnalysis>,
+) -> Score {
+    let parse = |path: &Path| fs::read_to_string(path).ok().and_then(|content| content.parse::<DocumentMut>().ok());
+    let config = parse(&project_root.join(".cargo").join("config.toml"));
+    let manifest = parse(&project_root.join("Cargo.toml"));
+    let ci_configs = ci_configs(project_root);
+    compute(&ScoreInput {
+        config: config.as_ref(),
+        manifest: manifest.as_ref(),
+        platform,
+        toolchain,
+        analysis,
+        ci_configs: &ci_configs,
+    })
+}
+
+/// Contents of the CI configuration files of a project
+pub fn ci_configs(project_root: &Path) -> Vec<String> {
+    let mut paths: Vec<_> = fs::read_dir(project_root.join(GITHUB_WORKFLOWS))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yml" || ext == "yaml"))
+        .collect();
+    paths.sort();
+    paths.extend(CI_CONFIG_PATHS.iter().map(|path| project_root.join(path)));
+    paths.iter().filter_map(|path| fs::read_to_string(path).ok()).collect()
+}
+
+fn checks(category: Category, input: &ScoreInput<'_>) -> Vec<Check> {
+    let check = |name, points, passed, action: &str| Check { name, points, passed, action: action.to_string() };
+    let profile = |name: &str, key: &str| {
+        [input.config, input.manifest]
+            .into_iter()
+            .flatten()
+            .find_map(|doc| lookup(doc, &["profile", name, key]).cloned())
+    };
+    match category {
+        Category::Linker => {
+            let Some(platform) = input.platform.filter(|platform| platform.capabilities().linker_config) else {
+                return Vec::new();
+            };
+            let default_lld = platform == Platform::Linux
+                && input
+                    .toolchain
+                    .is_some_and(|toolchain| Feature::RustLldDefault.availability(&toolchain) == Availability::Default);
+            let configured = input.config.is_some_and(|doc| has_fast_linker(doc, platform));
+            vec![check(
+                "fast linker",
+                100,
+                default_lld || configured,
+                "Configure a fast linker such as mold or lld (`cargo optimize` picks the best installed one)",
+            )]
+        }
+        Category::Caching => {
+            let incremental = profile("dev", "incremental").and_then(|item| item.as_bool()) != Some(false)
+                && input.config.and_then(|doc| lookup(doc, &["build", "incremental"])).and_then(Item::as_bool) != Some(false);
+            let mut checks = vec![check(
+                "incremental dev builds",
+                50,
+                incremental,
+                "Re-enable incremental compilation for dev builds (`profile.dev.incremental = true`)",
+            )];
+            let small = input.analysis.and_then(ProjectSize::from_analysis).is_some_and(|size| size.is_small());
+            if !small {
+                let wrapper = input
+                    .config
+                    .and_then(|doc| lookup(doc, &["build", "rustc-wrapper"]))
+                    .and_then(Item::as_str)
+                    .is_some_and(|wrapper| wrapper.contains("sccache"));
+                checks.push(check(
+                    "compiler cache",
+                    50,
+                    wrapper,
+                    "Share compiled dependencies across projects with `build.rustc-wrapper = \"sccache\"`",
+                ));
+            }
+            checks
+        }
+        Category::Profiles => {
+            let reduced_debug = profile("dev", "debug").is_some_and(|item| {
+                matches!(item.as_str(), Some("line-tables-only" | "limited" | "none"))
+                    || matches!(item.as_integer(), Some(0 | 1))
+                    || item.as_bool() == Some(false)
+            });
+            let tuned_release = profile("release", "lto").is_some() || profile("release", "codegen-units").is_some();
+            let mut checks = vec![
+                check(
+                    "dev debug info reduced",
+                    40,
+                    reduced_debug,
+                    "Set `profile.dev.debug = \"line-tables-only\"`: less debug info links faster",
+                ),
+                check(
+                    "release code generation tuned",
+                    30,
+                    tuned_release,
+                    "Set `profile.release.lto = \"thin\"` for faster binaries at a small build-time cost",
+                ),
+            ];
+            if input.platform == Some(Platform::Linux) {
+                checks.push(check(
+                    "unpacked debug info",
+                    30,
+                    profile("dev", "split-debuginfo").and_then(|item| item.as_str().map(String::from)).as_deref()
+                        == Some("unpacked"),
+                    "Set `profile.dev.split-debuginfo = \"unpacked\"` so the linker skips debug info",
+                ));
+            }
+            checks
+        }
+        Category::Dependencies => {
+            let Some(analysis) = input.analysis else {
+                return Vec::new();
+            };
+            let heavy = analysis.features.suggestions.iter().find(|s| s.impact == ImpactLevel::High);
+            let mut checks = vec![check(
+                "lean dependency features",
+                50,
+                heavy.is_none(),
+                &heavy.map_or_else(String::new, |s| format!("{}: {}", s.package, s.suggestion)),
+            )];
+            if analysis.is_workspace {
+                let suggestion = analysis.default_members.as_ref();
+                checks.push(check(
+                    "workspace default members",
+                    50,
+                    suggestion.is_none(),
+                    &suggestion.map_or_else(String::new, |s| {
+                        format!("Set `workspace.default-members = {}` to skip unused members", Array::from_iter(&s.members))
+                    }),
+                ));
+            }
+            checks
+        }
+        Category::Ci => {
+            if input.ci_configs.is_empty() {
+                return Vec::new();
+            }
+            let any = |patterns: &[&str]| input.ci_configs.iter().any(|ci| patterns.iter().any(|p| ci.contains(p)));
+            vec![
+                check(
+                    "CI dependency cache",
+                    60,
+                    any(&["Swatinem/rust-cache", "actions/cache", "sccache"]),
+                    "Cache ~/.cargo and target/ between CI runs (e.g. with Swatinem/rust-cache)",
+                ),
+                check(
+                    "incremental off in CI",
+                    40,
+                    any(&["CARGO_INCREMENTAL: 0", "CARGO_INCREMENTAL: \"0\"", "CARGO_INCREMENTAL=0"]),
+                    "Set `CARGO_INCREMENTAL=0` in CI, where incremental artifacts are never reused",
+                ),
+            ]
+        }
+    }
+}
+
+/// Whether the config selects a fast linker for the platform's target
+fn has_fast_linker(doc: &DocumentMut, platform: Platform) -> bool {
+    const FAST: &[&str] = &["rust-lld", "lld-link", "ld.lld", "lld", "mold", "wild", "ld64.sold", "sold", "zld", "gold"];
+    let target = platform.target_triple().and_then(|triple| lookup(doc, &["target", triple]));
+    let linker = target
+        .and_then(|table| table.get("linker"))
+        .and_then(Item::as_str)
+        .and_then(|linker| Path::new(linker).file_stem().map(|stem| stem.to_string_lossy().into_owned()));
+    if linker.is_some_and(|linker| FAST.contains(&linker.as_str())) {
+        return true;
+    }
+    let flags = [target.and_then(|table| table.get("rustflags")), lookup(doc, &["build", "rustflags"])];
+    flags.into_iter().flatten().filter_map(Item::as_array).flatten().filter_map(|flag| flag.as_str()).any(|flag| {
+        ["-fuse-ld=", "--ld-path="]
+            .iter()
+            .filter_map(|prefix| flag.split_once(prefix).map(|(_, name)| name))
+            .any(|name| FAST.contains(&Path::new(name).file_name().and_then(|n| n.to_str()).unwrap_or(name)))
+    })
+}
+
+fn lookup<'a>(doc: &'a DocumentMut, keys: &[&str]) -> Option<&'a Item> {
+    keys.iter().try_fold(doc.as_item(), |item, key| item.get(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::RustVersion;
+
+    fn doc(content: &str) -> DocumentMut {
+        content.parse().unwrap()
+    }
+
+    #[test]
+    fn test_unconfigured_project_scores_low_with_next_action() {
+        let config = doc("");
+        let score = compute(&ScoreInput {
+            config: Some(&config),
+            platform: Some(Platform::Linux),
+            toolchain: Some(ToolchainSpec::stable(RustVersion::new(1, 85, 0))),
+            ..ScoreInput::default()
+        });
+        assert_eq!(score.category(Category::Linker).unwrap().score, 0);
+        assert_eq!(score.category(Category::Caching).unwrap().score, 50);
+        assert!(score.category(Category::Ci).is_none());
+        assert!(score.category(Category::Dependencies).is_none());
+        // The linker check carries the most weight
+        assert!(score.next_action.as_deref().unwrap().contains("fast linker"));
+        // Linker 0, caching 50 and profiles 0, weighted 25:20:25
+        assert_eq!(score.total, 14);
+        assert!(score.to_string().starts_with(&format!("Optimization score: {}/100", score.total)));
+    }
+
+    #[test]
+    fn test_tuned_project_scores_full_marks() {
+        let config = doc(
+            "[target.x86_64-unknown-linux-gnu]\nlinker = \"clang\"\nrustflags = [\"-C\", \"link-arg=-fuse-ld=mold\"]\n\
+             [build]\nrustc-wrapper = \"sccache\"\n\
+             [profile.dev]\ndebug = \"line-tables-only\"\nsplit-debuginfo = \"unpacked\"\n",
+        );
+        let manifest = doc("[package]\nname = \"demo\"\n[profile.release]\nlto = \"thin\"\n");
+        let ci = vec!["steps:\n  - uses: Swatinem/rust-cache@v2\nenv:\n  CARGO_INCREMENTAL: 0\n".to_string()];
+        let score = compute(&ScoreInput {
+            config: Some(&config),
+            manifest: Some(&manifest),
+            platform: Some(Platform::Linux),
+            ci_configs: &ci,
+            ..ScoreInput::default()
+        });
+        assert_eq!(score.total, 100, "{}", score);
+        assert_eq!(score.next_action, None);
+        assert_eq!(score.categories.len(), 4);
+
+        // rust-lld is the default linker of newer toolchains
+        let empty = doc("");
+        let score = compute(&ScoreInput {
+            config: Some(&empty),
+            platform: Some(Platform::Linux),
+            toolchain: Some(ToolchainSpec::stable(RustVersion::new(1, 90, 0))),
+            ..ScoreInput::default()
+        });
+        assert_eq!(score.category(Category::Linker).unwrap().score, 100);
+    }
+}
@@ -0,0 +1,216 @@
+//! Persistent state carried between cargo-optimize runs.
+//!
+//! Stored as JSON in `.cargo/cargo-optimize-state.json` next to the config it
+//! describes. Missing state is treated as empty, and a corrupt file is moved
+//! aside in favor of the previous snapshot, so it never blocks an apply.
+//! Changes go through [`State::update`] or [`State::lock`], which hold the
+//! file's lock against concurrent build scripts (see [`crate::store`]).
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::advisor::Rejection;
+use crate::hardware::disk_io::DiskBenchmark;
+use crate::journal::ApplyJournal;
+use crate::sccache::SccacheHistory;
+use crate::store::{self, Locked};
+
+/// File name of the state file inside `.cargo/`
+pub const STATE_FILE_NAME: &str = "cargo-optimize-state.json";
+
+/// Most job count adjustments kept; older ones are dropped
+pub const MAX_JOBS_ADJUSTMENTS: usize = 20;
+
+/// An apply that was deferred and should run on the next invocation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingApply {
+    /// When the apply was deferred (seconds since the Unix epoch)
+    pub queued_at: u64,
+    /// Why it was deferred
+    pub reason: String,
+}
+
+/// Machine and project a memory adjustment was made for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryFingerprint {
+    /// Logical CPU cores
+    pub cpu_count: usize,
+    /// Total memory in bytes
+    pub total_memory: u64,
+    /// SHA-256 of `Cargo.lock`, or of `Cargo.toml` without a lock file
+    pub project_sha256: Option<String>,
+}
+
+/// Settings lowered because builds came close to running out of memory
+///
+/// Kept while the fingerprint matches; reverted from `changes` once the
+/// machine or the project changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryAdjustment {
+    /// When the adjustment was applied (seconds since the Unix epoch)
+    pub recorded_at: u64,
+    /// Machine and project the adjustment was made for
+    pub fingerprint: MemoryFingerprint,
+    /// Keys lowered, with their previous values
+    pub changes: ApplyJournal,
+}
+
+/// `build.jobs` adapted to the load of a shared machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobsAdjustment {
+    /// When the adjustment was applied (seconds since the Unix epoch)
+    pub recorded_at: u64,
+    /// Cores other workloads were keeping busy, rounded
+    pub busy_cores: usize,
+    /// Jobs the build would run without adapting
+    pub base_jobs: usize,
+    /// Jobs written
+    pub jobs: usize,
+}
+
+/// Flags the stable fallback of per-profile rustflags added to `target.<triple>.rustflags`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedRustflags {
+    /// Target triple of the rustflags array
+    pub target: String,
+    /// The flags added, in order
+    pub flags: Vec<String>,
+}
+
+/// A linker rolled back because builds failed with it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkerIncompatibility {
+    /// Linker name, as in the linker registry
+    pub linker: String,
+    /// What failed, from the build output
+    pub reason: String,
+    /// When it was rolled back (seconds since the Unix epoch)
+    pub recorded_at: u64,
+}
+
+/// State persisted between runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    /// Apply deferred by a previous run, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_apply: Option<PendingApply>,
+    /// Changes made to the config so far, for uninstalling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journal: Option<ApplyJournal>,
+    /// sccache statistics read on past applies
+    #[serde(default, skip_serializing_if = "SccacheHistory::is_empty")]
+    pub sccache: SccacheHistory,
+    /// Settings lowered for memory pressure, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryAdjustment>,
+    /// cargo-optimize version of the last apply that changed the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied_version: Option<String>,
+    /// Linker written by an apply that no guarded build has succeeded with yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unverified_linker: Option<String>,
+    /// Linkers rolled back after breaking a build, which are not configured again
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub incompatible_linkers: Vec<LinkerIncompatibility>,
+    /// Last disk benchmark of the target directory's filesystem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_benchmark: Option<DiskBenchmark>,
+    /// Suggestions the user declined, which are not repeated
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejections: Vec<Rejection>,
+    /// Job counts adapted to system load, oldest first (at most [`MAX_JOBS_ADJUSTMENTS`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub jobs_adjustments: Vec<JobsAdjustment>,
+    /// Flags added to a target's rustflags for every profile, removed on the next apply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_rustflags: Option<SharedRustflags>,
+}
+
+impl State {
+    /// Path of the state file for a `.cargo` directory
+    pub fn path_in(cargo_dir: &Path) -> PathBuf {
+        cargo_dir.join(STATE_FILE_NAME)
+    }
+
+    /// Load state, falling back to empty state if the file is missing or invalid
+    pub fn load(path: &Path) -> Self {
+        store::read(path).value
+    }
+
+    /// Replace the saved state atomically
+    ///
+    /// Overwrites changes other processes made since this state was loaded;
+    /// prefer [`State::update`].
+    pub fn save(&self, path: &Path) -> Result<()> {
+        store::write(path, self)
+    }
+
+    /// Change the saved state under its lock, writing only if `change` modified it
+    pub fn update<R>(path: &Path, change: impl FnOnce(&mut State) -> R) -> Result<R> {
+        store::update(path, change)
+    }
+
+    /// Lock the saved state for changes spanning other work, committed with [`Locked::commit`]
+    pub fn lock(path: &Path) -> Result<Locked<State>> {
+        Locked::acquire(path)
+    }
+
+    /// Fold the changes of an apply into the journal
+    pub fn record_apply(&mut self, journal: ApplyJournal) {
+        match &mut self.journal {
+            Some(existing) => existing.merge(journal),
+            None if !journal.is_empty() => self.journal = Some(journal),
+            None => {}
+        }
+    }
+
+    /// Whether `linker` was rolled back after breaking a build
+    pub fn is_incompatible(&self, linker: &str) -> bool {
+        self.incompatible_linkers.iter().any(|incompatibility| incompatibility.linker == linker)
+    }
+
+    /// Record an adapted job count, unless it is the same as the last one
+    ///
+    /// Returns whether it was recorded.
+    pub fn record_jobs_adjustment(&mut self, adjustment: JobsAdjustment) -> bool {
+        if self.jobs_adjustments.last().is_some_and(|last| last.jobs == adjustment.jobs) {
+            return false;
+        }
+        self.jobs_adjustments.push(adjustment);
+        let excess = self.jobs_adjustments.len().saturating_sub(MAX_JOBS_ADJUSTMENTS);
+        self.jobs_adjustments.drain(..excess);
+        true
+    }
+
+    /// Queue an apply for the next run
+    pub fn queue_apply(&mut self, reason: impl Into<String>) {
+        let queued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.pending_apply = Some(PendingApply { queued_at, reason: reason.into() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let temp = TempDir::new().unwrap();
+        let path = State::path_in(temp.path());
+        assert_eq!(State::load(&path), State::default());
+
+        let mut state = State::default();
+        state.queue_apply("cargo build in progress");
+        state.save(&path).unwrap();
+        assert_eq!(State::load(&path), state);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(State::load(&path), State::default());
+    }
+}
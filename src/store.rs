@@ -0,0 +1,282 @@
+//! Concurrent access to the state and metrics files.
+//!
+//! Build scripts of every package in a workspace may run cargo-optimize at
+//! the same time, each reading and writing `.cargo/cargo-optimize-state.json`
+//! and `.cargo/cargo-optimize-metrics.json`. Readers take no lock: files are
+//! only ever replaced by an atomic rename, so a read sees one complete
+//! snapshot. Writers hold an exclusive lock on the sidecar `<file>.lock`
+//! from reading the latest snapshot until its successor is in place, so no
+//! update is lost to a concurrent one.
+//!
+//! Every snapshot carries a `revision`, one higher than the one it replaced,
+//! and the replaced snapshot is kept as `<file>.prev`. A file that no longer
+//! parses is moved aside to `<file>.corrupt-<secs>` for inspection, and
+//! reading falls back to the previous snapshot, or to an empty value.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+use crate::transaction::atomic_write;
+
+/// Extension of the lock file kept next to a stored file
+pub const LOCK_EXTENSION: &str = "lock";
+
+/// Extension of the previous snapshot kept next to a stored file
+pub const PREVIOUS_EXTENSION: &str = "prev";
+
+/// Prefix of the extension of a quarantined file, followed by the time it was moved aside
+pub const CORRUPT_EXTENSION: &str = "corrupt";
+
+/// A stored value with its revision, as written to disk
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    /// Number of writes the file has seen; files from before revisions start at 0
+    #[serde(default)]
+    revision: u64,
+    #[serde(flatten)]
+    value: T,
+}
+
+/// A value read from a stored file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<T> {
+    /// Revision of the snapshot, 0 for a missing file
+    pub revision: u64,
+    /// The value
+    pub value: T,
+}
+
+/// `path` with `extension` appended to its file name (e.g. `state.json.lock`)
+pub fn sidecar(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+/// Read the latest snapshot at `path` without locking
+///
+/// A file that does not parse is quarantined and the previous snapshot read
+/// instead; the lock is only taken then, so a snapshot written in the
+/// meantime is not moved aside. Without either, the value is empty at
+/// revision 0.
+pub fn read<T: DeserializeOwned + Default>(path: &Path) -> Snapshot<T> {
+    match parse::<T>(path) {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => previous(path),
+        Err(e) => match lock(path) {
+            Ok(_lock) => read_locked(path),
+            Err(lock_error) => {
+                warn!("Ignoring corrupt {}: {:#} ({:#})", path.display(), e, lock_error);
+                Snapshot { revision: 0, value: T::default() }
+            }
+        },
+    }
+}
+
+/// Read the latest snapshot at `path` while holding its lock
+fn read_locked<T: DeserializeOwned + Default>(path: &Path) -> Snapshot<T> {
+    match parse(path) {
+        Ok(Some(snapshot)) => return snapshot,
+        Ok(None) => {}
+        Err(e) => match quarantine(path) {
+            Ok(Some(moved)) => warn!(
+                "Moved corrupt {} aside to {}, using its previous snapshot: {:#}",
+                path.display(),
+                moved.display(),
+                e
+            ),
+            Ok(None) => {}
+            Err(quarantine_error) => warn!("Ignoring corrupt {}: {:#} ({:#})", path.display(), e, quarantine_error),
+        },
+    }
+    previous(path)
+}
+
+/// The previous snapshot of `path`, which stands in while the file is missing after a quarantine
+fn previous<T: DeserializeOwned + Default>(path: &Path) -> Snapshot<T> {
+    match parse(&sidecar(path, PREVIOUS_EXTENSION)) {
+        Ok(Some(snapshot)) => {
+            debug!("Reading {} from revision {} of its previous snapshot", path.display(), snapshot.revision);
+            snapshot
+        }
+        _ => Snapshot { revision: 0, value: T::default() },
+    }
+}
+
+/// Parse the snapshot at `path`; `None` if the file does not exist
+fn parse<T: DeserializeOwned>(path: &Path) -> Result<Option<Snapshot<T>>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let versioned: Versioned<T> =
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(Snapshot { revision: versioned.revision, value: versioned.value }))
+}
+
+/// Take the exclusive lock of the file at `path`, waiting for other writers
+///
+/// The lock is released when the returned file is dropped.
+pub fn lock(path: &Path) -> Result<File> {
+    let lock_path = sidecar(path, LOCK_EXTENSION);
+    if let Some(parent) = lock_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+    lock.lock().with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+    Ok(lock)
+}
+
+/// Move the file at `path` aside as `<file>.corrupt-<secs>`, if it exists
+///
+/// Returns where it went. Callers hold the lock of the file.
+pub fn quarantine(path: &Path) -> Result<Option<PathBuf>> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let destination = (0..)
+        .map(|n| match n {
+            0 => sidecar(path, &format!("{}-{}", CORRUPT_EXTENSION, secs)),
+            n => sidecar(path, &format!("{}-{}-{}", CORRUPT_EXTENSION, secs, n)),
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_default();
+    match fs::rename(path, &destination) {
+        Ok(()) => Ok(Some(destination)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to move {} aside", path.display())),
+    }
+}
+
+/// Exclusive write access to a stored value
+///
+/// Holds the lock of the file until dropped. Changes are written by
+/// [`Locked::commit`]; dropping the guard without committing discards them.
+pub struct Locked<T> {
+    path: PathBuf,
+    // Released when the guard is dropped
+    _lock: File,
+    original: Snapshot<T>,
+    value: T,
+}
+
+impl<T: Serialize + DeserializeOwned + Default + Clone + PartialEq> Locked<T> {
+    /// Lock the file at `path`, waiting for other writers, and read its latest snapshot
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let lock = lock(path)?;
+        let original: Snapshot<T> = read_locked(path);
+        let value = original.value.clone();
+        Ok(Locked { path: path.to_path_buf(), _lock: lock, original, value })
+    }
+
+    /// Revision the value was read at
+    pub fn revision(&self) -> u64 {
+        self.original.revision
+    }
+
+    /// Write the value as the next revision, unless it is unchanged
+    ///
+    /// Returns the revision now on disk.
+    pub fn commit(self) -> Result<u64> {
+        if self.value == self.original.value {
+            return Ok(self.original.revision);
+        }
+        let revision = self.original.revision + 1;
+        let content = serde_json::to_string_pretty(&Versioned { revision, value: &self.value })
+            .with_context(|| format!("Failed to serialize {}", self.path.display()))?;
+        if self.path.exists() {
+            if let Err(e) = fs::copy(&self.path, sidecar(&self.path, PREVIOUS_EXTENSION)) {
+                debug!("Failed to keep the previous snapshot of {}: {}", self.path.display(), e);
+            }
+        }
+        atomic_write(&self.path, &content)?;
+        Ok(revision)
+    }
+}
+
+impl<T> Deref for Locked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Locked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Change the value stored at `path` under its lock
+///
+/// Writes only when `change` modified the value.
+pub fn update<T, R>(path: &Path, change: impl FnOnce(&mut T) -> R) -> Result<R>
+where
+    T: Serialize + DeserializeOwned + Default + Clone + PartialEq,
+{
+    let mut locked = Locked::acquire(path)?;
+    let result = change(&mut locked);
+    locked.commit()?;
+    Ok(result)
+}
+
+/// Replace the value stored at `path` under its lock
+pub fn write<T>(path: &Path, value: &T) -> Result<()>
+where
+    T: Serialize + DeserializeOwned + Default + Clone + PartialEq,
+{
+    update(path, |stored: &mut T| stored.clone_from(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    struct Counter {
+        #[serde(default)]
+        count: u32,
+    }
+
+    #[test]
+    fn test_revisions_and_quarantine() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("counter.json");
+        assert_eq!(read::<Counter>(&path), Snapshot { revision: 0, value: Counter::default() });
+
+        update(&path, |counter: &mut Counter| counter.count = 1).unwrap();
+        update(&path, |counter: &mut Counter| counter.count = 2).unwrap();
+        // Unchanged values are not written
+        update(&path, |_: &mut Counter| {}).unwrap();
+        assert_eq!(read::<Counter>(&path), Snapshot { revision: 2, value: Counter { count: 2 } });
+
+        // Files from before revisions read as revision 0
+        fs::write(&path, r#"{"count": 7}"#).unwrap();
+        assert_eq!(read::<Counter>(&path).revision, 0);
+        update(&path, |counter: &mut Counter| counter.count += 1).unwrap();
+
+        fs::write(&path, r#"{"count": "#).unwrap();
+        assert_eq!(read::<Counter>(&path), Snapshot { revision: 0, value: Counter { count: 7 } });
+        assert!(!path.exists());
+        let quarantined: Vec<_> = fs::read_dir(temp.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.starts_with("counter.json.corrupt-"))
+            .collect();
+        assert_eq!(quarantined.len(), 1, "{:?}", quarantined);
+    }
+}
@@ -0,0 +1,345 @@
+//! Strict mode: fail instead of degrading when required optimizations are missing.
+//!
+//! cargo-optimize normally degrades gracefully: without a fast linker or
+//! sccache the build still runs, just slower. Teams that mandate them, in CI
+//! for instance, declare them in `cargo-optimize.toml`:
+//!
+//! ```toml
+//! [global]
+//! required = ["linker", "sccache"]
+//! ```
+//!
+//! After configuring, [`check`] verifies each requirement against the config
+//! cargo reads and the tools installed. An unmet requirement fails the
+//! build script, and the run of `cargo optimize` or the apply with
+//! [`ExitStatus::RequirementUnmet`](crate::exit::ExitStatus::RequirementUnmet),
+//! with the steps to meet it.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use toml_edit::DocumentMut;
+
+use crate::config::links_with_rust_lld;
+use crate::linkers::{self, Invocation, LinkerSpec, Platform};
+use crate::nix::{self, NixEnvironment};
+use crate::toolchain::ToolchainInfo;
+use crate::tools;
+
+/// An optimization the project cannot build without
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Requirement {
+    /// A fast linker for the host target
+    Linker,
+    /// sccache as the rustc wrapper
+    Sccache,
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Requirement::Linker => "linker",
+            Requirement::Sccache => "sccache",
+        })
+    }
+}
+
+/// A requirement the configured build does not meet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnmetRequirement {
+    /// The requirement
+    pub requirement: Requirement,
+    /// Why it is not met
+    pub reason: String,
+    /// Steps meeting it, in order
+    pub remediation: Vec<String>,
+}
+
+impl fmt::Display for UnmetRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "required {} missing: {}", self.requirement, self.reason)?;
+        for (step, remedy) in self.remediation.iter().enumerate() {
+            write!(f, "\n  {}. {}", step + 1, remedy)?;
+        }
+        Ok(())
+    }
+}
+
+/// Requirements declared in `[global] required` of the project's `cargo-optimize.toml`
+///
+/// Empty without the file. Fails when the file cannot be parsed or names an
+/// unknown requirement, so a typo does not silently turn strict mode off.
+pub fn declared(project_root: &Path) -> Result<Vec<Requirement>> {
+    let path = project_root.join("cargo-optimize.toml");
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    let declared: Declared = toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(declared.global.required)
+}
+
+/// The part of `cargo-optimize.toml` [`declared`] reads
+#[derive(Deserialize, Default)]
+struct Declared {
+    #[serde(default)]
+    global: DeclaredGlobal,
+}
+
+#[derive(Deserialize, Default)]
+struct DeclaredGlobal {
+    #[serde(default)]
+    required: Vec<Requirement>,
+}
+
+/// Check `required` against the cargo config at `config_path` and the installed tools
+pub fn check(config_path: &Path, required: &[Requirement]) -> Vec<UnmetRequirement> {
+    if required.is_empty() {
+        return Vec::new();
+    }
+    let content = fs::read_to_string(config_path).ok();
+    // rustc only runs when its default linker could meet the requirement
+    let toolchain = required.contains(&Requirement::Linker).then(|| ToolchainInfo::detect().ok()).flatten();
+    check_config_with(
+        content.as_deref(),
+        required,
+        Platform::current(),
+        toolchain.as_ref(),
+        |var| std::env::var(var).ok(),
+        |command| which::which(command).is_ok(),
+    )
+}
+
+/// Check `required` against config `content`, variables looked up with `lookup` and commands `exists` finds
+pub fn check_config<L, E>(
+    content: Option<&str>,
+    required: &[Requirement],
+    platform: Option<Platform>,
+    lookup: L,
+    exists: E,
+) -> Vec<UnmetRequirement>
+where
+    L: Fn(&str) -> Option<String>,
+    E: Fn(&str) -> bool,
+{
+    check_config_with(content, required, platform, None, lookup, exists)
+}
+
+/// Check like [`check_config`], knowing the `toolchain` cargo builds with
+///
+/// A toolchain that links with its bundled rust-lld by default (rustc 1.90
+/// and later on x86_64 Linux) meets the linker requirement without any config.
+pub fn check_config_with<L, E>(
+    content: Option<&str>,
+    required: &[Requirement],
+    platform: Option<Platform>,
+    toolchain: Option<&ToolchainInfo>,
+    lookup: L,
+    exists: E,
+) -> Vec<UnmetRequirement>
+where
+    L: Fn(&str) -> Option<String>,
+    E: Fn(&str) -> bool,
+{
+    let doc = content.and_then(|content| content.parse::<DocumentMut>().ok()).unwrap_or_default();
+    let mut unmet = Vec::new();
+    for &requirement in required {
+        let reason = match requirement {
+            Requirement::Linker => check_linker(&doc, platform, toolchain, &exists),
+            Requirement::Sccache => check_sccache(&doc, &lookup, &exists),
+        };
+        if let Some(reason) = reason {
+            if !unmet.iter().any(|u: &UnmetRequirement| u.requirement == requirement) {
+                unmet.push(UnmetRequirement { requirement, reason, remediation: remediation(requirement, platform) });
+            }
+        }
+    }
+    unmet
+}
+
+/// Why the linker requirement is not met, if it is not
+fn check_linker<E: Fn(&str) -> bool>(
+    doc: &DocumentMut,
+    platform: Option<Platform>,
+    toolchain: Option<&ToolchainInfo>,
+    exists: &E,
+) -> Option<String> {
+    let Some(platform) = platform else {
+        return Some("no fast linkers are known for this platform".to_string());
+    };
+    let Some(target) = platform.target_triple() else {
+        return Some(format!("no {} target triple is known for {}", platform.name(), linkers::host_arch()));
+    };
+    let table = doc.get("target").and_then(|t| t.get(target));
+    let linker = table.and_then(|t| t.get("linker")).and_then(|l| l.as_str()).unwrap_or_default();
+    let rustflags: Vec<&str> = table
+        .and_then(|t| t.get("rustflags"))
+        .and_then(|r| r.as_array())
+        .map(|flags| flags.iter().filter_map(|flag| flag.as_str()).collect())
+        .unwrap_or_default();
+    let selects = |spec: &LinkerSpec| match (spec.invocation, spec.link_arg) {
+        (Invocation::Direct(executable), _) => {
+            let name = Path::new(linker).file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            !linker.is_empty() && name.trim_end_matches(".exe") == executable.trim_end_matches(".exe")
+        }
+        (Invocation::Driver, Some(arg)) => rustflags.iter().any(|flag| flag.ends_with(arg)),
+        (Invocation::Driver, None) => false,
+    };
+    let Some(spec) = linkers::for_platform(platform).find(|spec| selects(spec)) else {
+        // Nothing in the config turns the bundled rust-lld off again
+        let keeps_default = linker.is_empty()
+            && !rustflags.iter().any(|flag| ["fuse-ld", "linker-features", "link-self-contained"].iter().any(|arg| flag.contains(arg)));
+        if keeps_default && toolchain.is_some_and(|toolchain| toolchain.host == target && links_with_rust_lld(toolchain)) {
+            return None;
+        }
+        return Some(format!("no fast linker is configured for {} in .cargo/config.toml", target));
+    };
+    if !spec.is_installed_with(exists) {
+        return Some(format!("{} is configured but not installed", spec.name));
+    }
+    if spec.invocation == Invocation::Driver && !linker.is_empty() && !exists(linker) {
+        return Some(format!("{} is configured through {}, which is not installed", spec.name, linker));
+    }
+    None
+}
+
+/// Why the sccache requirement is not met, if it is not
+fn check_sccache<L, E>(doc: &DocumentMut, lookup: &L, exists: &E) -> Option<String>
+where
+    L: Fn(&str) -> Option<String>,
+    E: Fn(&str) -> bool,
+{
+    // The environment overrides the config, as it does for cargo
+    let wrapper = ["RUSTC_WRAPPER", "CARGO_BUILD_RUSTC_WRAPPER"]
+        .iter()
+        .find_map(|var| lookup(var).filter(|value| !value.is_empty()))
+        .or_else(|| doc.get("build")?.get("rustc-wrapper")?.as_str().map(str::to_string));
+    match wrapper {
+        None => Some("build.rustc-wrapper is not set".to_string()),
+        Some(wrapper) if !wrapper.contains("sccache") => Some(format!("the rustc wrapper is {}", wrapper)),
+        Some(wrapper) if !exists(&wrapper) => Some(format!("{} is configured but not installed", wrapper)),
+        Some(_) => None,
+    }
+}
+
+/// Steps meeting `requirement` on this machine
+fn remediation(requirement: Requirement, platform: Option<Platform>) -> Vec<String> {
+    let nix = NixEnvironment::detect().is_some();
+    match requirement {
+        Requirement::Linker => {
+            let Some(platform) = platform else {
+                return vec!["Remove \"linker\" from `required` in cargo-optimize.toml on this platform".to_string()];
+            };
+            let os_release = fs::read_to_string("/etc/os-release").unwrap_or_default();
+            let install = linkers::for_platform(platform).find_map(|spec| {
+                if nix {
+                    nix::package_for(spec.name)
+                        .map(|package| format!("Add pkgs.{} to the packages of the Nix dev shell", package))
+                } else {
+                    tools::package_command(spec.name, platform, &os_release)
+                        .map(|command| format!("Install {}: {}", spec.name, command))
+                }
+            });
+            let mut steps: Vec<String> = install.into_iter().collect();
+            steps.push("Run `cargo optimize` to configure it in .cargo/config.toml".to_string());
+            steps
+        }
+        Requirement::Sccache => {
+            let install = if nix {
+                "Add pkgs.sccache to the packages of the Nix dev shell"
+            } else {
+                "Install sccache: cargo install sccache --locked"
+            };
+            vec![
+                install.to_string(),
+                "Set `use_sccache = true` in [global] of cargo-optimize.toml and run the apply again, \
+                 or set build.rustc-wrapper = \"sccache\" in .cargo/config.toml"
+                    .to_string(),
+            ]
+        }
+    }
+}
+
+/// Fail the running build script over `unmet` requirements
+///
+/// Cargo shows the standard error of a failed build script, so the
+/// remediation steps end up right under the error.
+pub fn fail_build(unmet: &[UnmetRequirement]) -> ! {
+    for requirement in unmet {
+        eprintln!("error: cargo-optimize: {}", requirement);
+    }
+    eprintln!("error: cargo-optimize: strict mode is on because cargo-optimize.toml declares `required`");
+    std::process::exit(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOLD: &str = r#"
+[target.x86_64-unknown-linux-gnu]
+linker = "clang"
+rustflags = ["-C", "link-arg=-fuse-ld=mold"]
+"#;
+
+    #[test]
+    fn test_linker_requirement() {
+        let installed = |command: &str| command == "clang" || command == "mold";
+        let check = |content: Option<&str>, exists: &dyn Fn(&str) -> bool| {
+            check_config(content, &[Requirement::Linker], Some(Platform::Linux), |_| None, exists)
+        };
+        assert!(check(Some(MOLD), &installed).is_empty());
+
+        let unmet = check(None, &installed);
+        assert_eq!(unmet.len(), 1);
+        assert_eq!(unmet[0].reason, "no fast linker is configured for x86_64-unknown-linux-gnu in .cargo/config.toml");
+        assert!(!unmet[0].remediation.is_empty());
+
+        let unmet = check(Some(MOLD), &|command| command == "clang");
+        assert_eq!(unmet[0].reason, "mold is configured but not installed");
+        let unmet = check(Some(MOLD), &|command| command == "mold");
+        assert_eq!(unmet[0].reason, "mold is configured through clang, which is not installed");
+    }
+
+    #[test]
+    fn test_rust_lld_default_meets_the_linker_requirement() {
+        if linkers::host_arch() != "x86_64" {
+            return;
+        }
+        let toolchain = |version: &str| {
+            ToolchainInfo::parse_verbose_version(&format!("release: {}\nhost: x86_64-unknown-linux-gnu\n", version)).unwrap()
+        };
+        let check = |content: Option<&str>, toolchain: &ToolchainInfo| {
+            check_config_with(content, &[Requirement::Linker], Some(Platform::Linux), Some(toolchain), |_| None, |_| false)
+        };
+        assert!(check(None, &toolchain("1.90.0")).is_empty());
+        assert_eq!(check(None, &toolchain("1.89.0")).len(), 1);
+        // A config going back to the system linker does not count
+        let bfd = "[target.x86_64-unknown-linux-gnu]\nrustflags = [\"-C\", \"link-arg=-fuse-ld=bfd\"]\n";
+        assert_eq!(check(Some(bfd), &toolchain("1.90.0")).len(), 1);
+        let cc = "[target.x86_64-unknown-linux-gnu]\nlinker = \"cc\"\n";
+        assert_eq!(check(Some(cc), &toolchain("1.90.0")).len(), 1);
+    }
+
+    #[test]
+    fn test_sccache_requirement() {
+        let config = "[build]\nrustc-wrapper = \"sccache\"\n";
+        let required = [Requirement::Sccache, Requirement::Sccache];
+        let installed = |command: &str| command == "sccache";
+        assert!(check_config(Some(config), &required, None, |_| None, installed).is_empty());
+
+        let unmet = check_config(None, &required, None, |_| None, installed);
+        assert_eq!(unmet.len(), 1);
+        assert_eq!(unmet[0].reason, "build.rustc-wrapper is not set");
+
+        // RUSTC_WRAPPER wins over the config
+        let env = |var: &str| (var == "RUSTC_WRAPPER").then(|| "ccache".to_string());
+        let unmet = check_config(Some(config), &required, None, env, installed);
+        assert_eq!(unmet[0].reason, "the rustc wrapper is ccache");
+        let unmet = check_config(Some(config), &required, None, |_| None, |_| false);
+        assert_eq!(unmet[0].reason, "sccache is configured but not installed");
+        assert!(unmet[0].to_string().starts_with("required sccache missing: sccache is configured"));
+    }
+}
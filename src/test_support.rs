@@ -0,0 +1,309 @@
+//! Fake environment for testing build scripts that call cargo-optimize.
+//!
+//! Available with the `test-support` feature. A build script calling
+//! [`crate::auto_configure`] depends on the machine it runs on: which linkers
+//! and C compilers are installed, the platform, the cores, and the working
+//! directory it writes `.cargo/config.toml` into. [`FakeEnvironment`]
+//! pretends all of these for the current thread, so the build script's own
+//! logic can be unit tested with a plain function call, and [`TestProject`]
+//! scaffolds the project it runs in:
+//!
+//! ```no_run
+//! use cargo_optimize::test_support::{FakeEnvironment, TestProject};
+//!
+//! let project = TestProject::builder("app").build().unwrap();
+//! let _env = FakeEnvironment::new().with_linkers(["lld"]).with_cpus(8, 4).in_project(&project).install();
+//! cargo_optimize::auto_configure();
+//! assert!(project.cargo_config().unwrap().contains("lld"));
+//! ```
+//!
+//! Everything the environment does not say is pretended too: no command is
+//! installed unless listed, and hardware is [`get_fallback_hardware`] with
+//! the given overrides. The fake only applies to the thread that installed
+//! it and ends when the guard is dropped.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+use crate::hardware::{get_fallback_hardware, HardwareInfo};
+use crate::linkers::{self, Invocation, Platform};
+use crate::mvp::DriverProbe;
+use crate::policy::Policy;
+
+thread_local! {
+    static ACTIVE: RefCell<Option<FakeEnvironment>> = const { RefCell::new(None) };
+}
+
+/// Machine a build script pretends to run on
+#[derive(Debug, Clone, Default)]
+pub struct FakeEnvironment {
+    platform: Option<Option<Platform>>,
+    arch: Option<String>,
+    commands: BTreeSet<String>,
+    cpu_count: Option<usize>,
+    physical_cpu_count: Option<usize>,
+    total_memory: Option<u64>,
+    project_root: Option<PathBuf>,
+    vars: BTreeMap<String, String>,
+    policy: Option<Policy>,
+}
+
+impl FakeEnvironment {
+    /// An environment with nothing installed on the real platform
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pretend to run on `platform`
+    ///
+    /// Linker detection follows it; the layout of a merged config still
+    /// follows the platform the tests are compiled for.
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(Some(platform));
+        self
+    }
+
+    /// Pretend to run on a platform cargo-optimize has no linker settings for
+    pub fn with_unsupported_platform(mut self) -> Self {
+        self.platform = Some(None);
+        self
+    }
+
+    /// Pretend the environment variable `key` is set to `value`
+    ///
+    /// Read by the `CARGO_OPTIMIZE_DISABLE` kill switch, output mode
+    /// detection and IDE detection, which see no other variables while the
+    /// environment is active.
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.insert(key.into(), value.into());
+        self
+    }
+
+    /// Pretend `policy` is the machine policy
+    ///
+    /// Without it the environment has no machine policy, whatever is
+    /// installed on the machine running the tests.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Pretend the CPU architecture is `arch` (as in `std::env::consts::ARCH`, e.g. "aarch64")
+    ///
+    /// The target triple linker settings are written for follows it.
+    pub fn with_arch(mut self, arch: impl Into<String>) -> Self {
+        self.arch = Some(arch.into());
+        self
+    }
+
+    /// Pretend `linkers` (registry names such as `mold`) are installed
+    ///
+    /// Linkers invoked through a C compiler also get the first compiler they
+    /// can be driven by.
+    pub fn with_linkers<I, S>(mut self, linkers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for name in linkers {
+            let Some(spec) = linkers::find(name.as_ref()) else {
+                panic!("unknown linker {:?}", name.as_ref());
+            };
+            self.commands.extend(spec.detect.first().map(|command| command.to_string()));
+            if spec.invocation == Invocation::Driver {
+                self.commands.extend(spec.drivers.first().map(|driver| driver.to_string()));
+            }
+        }
+        self
+    }
+
+    /// Pretend `commands` are installed, by executable name
+    pub fn with_commands<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.commands.extend(commands.into_iter().map(Into::into));
+        self
+    }
+
+    /// Pretend the machine has `logical` and `physical` cores
+    pub fn with_cpus(mut self, logical: usize, physical: usize) -> Self {
+        self.cpu_count = Some(logical);
+        self.physical_cpu_count = Some(physical);
+        self
+    }
+
+    /// Pretend the machine has `bytes` of memory, half of it available
+    pub fn with_memory(mut self, bytes: u64) -> Self {
+        self.total_memory = Some(bytes);
+        self
+    }
+
+    /// Run in `project` instead of the current directory
+    pub fn in_project(mut self, project: &TestProject) -> Self {
+        self.project_root = Some(project.root().to_path_buf());
+        self
+    }
+
+    /// Hardware [`HardwareInfo::detect`] reports under this environment
+    pub fn hardware(&self) -> HardwareInfo {
+        let mut hardware = get_fallback_hardware();
+        if let Some(cpu_count) = self.cpu_count {
+            hardware.cpu_count = cpu_count;
+        }
+        if let Some(physical_cpu_count) = self.physical_cpu_count {
+            hardware.physical_cpu_count = physical_cpu_count;
+        }
+        if let Some(total_memory) = self.total_memory {
+            hardware.total_memory = total_memory;
+            hardware.available_memory = total_memory / 2;
+        }
+        hardware
+    }
+
+    /// Make this the environment of the current thread until the guard drops
+    pub fn install(self) -> FakeEnvironmentGuard {
+        let previous = ACTIVE.with(|active| active.replace(Some(self)));
+        FakeEnvironmentGuard { previous }
+    }
+}
+
+/// Restores the previous environment of the thread when dropped
+#[derive(Debug)]
+#[must_use = "the fake environment ends when the guard is dropped"]
+pub struct FakeEnvironmentGuard {
+    previous: Option<FakeEnvironment>,
+}
+
+impl Drop for FakeEnvironmentGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        ACTIVE.with(|active| *active.borrow_mut() = previous);
+    }
+}
+
+fn with_active<T>(f: impl FnOnce(&FakeEnvironment) -> Option<T>) -> Option<T> {
+    ACTIVE.with(|active| active.borrow().as_ref().and_then(f))
+}
+
+/// Pretended platform, `Some(None)` for an unsupported one, if a fake environment is active
+pub(crate) fn platform() -> Option<Option<Platform>> {
+    with_active(|env| env.platform)
+}
+
+/// Pretended machine policy, if a fake environment is active
+pub(crate) fn policy() -> Option<Option<Policy>> {
+    with_active(|env| Some(env.policy.clone()))
+}
+
+/// Pretended value of environment variable `key`, if a fake environment is active
+pub(crate) fn env_var(key: &str) -> Option<Option<String>> {
+    with_active(|env| Some(env.vars.get(key).cloned()))
+}
+
+/// Pretended CPU architecture, if a fake environment is active
+pub(crate) fn arch() -> Option<String> {
+    with_active(|env| env.arch.clone())
+}
+
+/// Whether `command` is pretended installed, if a fake environment is active
+pub(crate) fn command_exists(command: &str) -> Option<bool> {
+    with_active(|env| Some(env.commands.contains(command)))
+}
+
+/// How a pretended C compiler answers a linker probe
+pub(crate) fn driver_probe(driver: &str) -> Option<DriverProbe> {
+    with_active(|env| {
+        Some(if env.commands.contains(driver) {
+            DriverProbe::Supported
+        } else {
+            DriverProbe::NotInstalled
+        })
+    })
+}
+
+/// Pretended hardware, if a fake environment is active
+pub(crate) fn hardware() -> Option<HardwareInfo> {
+    with_active(|env| Some(env.hardware()))
+}
+
+/// Directory a build script pretends to run in
+pub(crate) fn project_root() -> Option<PathBuf> {
+    with_active(|env| env.project_root.clone())
+}
+
+/// Throwaway Cargo project in a temporary directory, removed when dropped
+#[derive(Debug)]
+pub struct TestProject {
+    dir: TempDir,
+}
+
+impl TestProject {
+    /// Start a project named `name`
+    pub fn builder(name: impl Into<String>) -> TestProjectBuilder {
+        TestProjectBuilder { name: name.into(), files: Vec::new() }
+    }
+
+    /// Directory holding `Cargo.toml`
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Content of the file at `relative`, if it exists
+    pub fn read(&self, relative: impl AsRef<Path>) -> Option<String> {
+        fs::read_to_string(self.root().join(relative)).ok()
+    }
+
+    /// Content of `.cargo/config.toml`, if it exists
+    pub fn cargo_config(&self) -> Option<String> {
+        self.read(".cargo/config.toml")
+    }
+}
+
+/// Builder of a [`TestProject`]
+#[derive(Debug, Clone)]
+pub struct TestProjectBuilder {
+    name: String,
+    files: Vec<(PathBuf, String)>,
+}
+
+impl TestProjectBuilder {
+    /// Add a file at `relative`, replacing a generated one
+    pub fn file(mut self, relative: impl Into<PathBuf>, content: impl Into<String>) -> Self {
+        let relative = relative.into();
+        self.files.retain(|(path, _)| *path != relative);
+        self.files.push((relative, content.into()));
+        self
+    }
+
+    /// Start from an existing `.cargo/config.toml`
+    pub fn cargo_config(self, content: impl Into<String>) -> Self {
+        self.file(".cargo/config.toml", content)
+    }
+
+    /// Add a `cargo-optimize.toml`
+    pub fn optimize_config(self, content: impl Into<String>) -> Self {
+        self.file("cargo-optimize.toml", content)
+    }
+
+    /// Write the project: a library with a `Cargo.toml`, plus the added files
+    pub fn build(self) -> io::Result<TestProject> {
+        let dir = TempDir::new()?;
+        let manifest = format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n", self.name);
+        let generated = [(PathBuf::from("Cargo.toml"), manifest), (PathBuf::from("src/lib.rs"), String::new())];
+        for (relative, content) in generated.into_iter().chain(self.files) {
+            let path = dir.path().join(&relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, content)?;
+        }
+        Ok(TestProject { dir })
+    }
+}
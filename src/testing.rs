@@ -0,0 +1,197 @@
+//! Proptest strategies for cargo config files.
+//!
+//! Available with the `property-tests` feature. The generated configs contain
+//! what real ones do and what merging has to preserve: nested and implicit
+//! tables, arrays, inline tables, comment lines and CRLF line endings. Table
+//! and key names come from small pools that overlap the settings
+//! cargo-optimize writes, so merges regularly hit existing keys.
+
+use proptest::prelude::*;
+use proptest::sample::subsequence;
+
+/// Tables a generated config may contain
+pub const TABLE_PATHS: &[&[&str]] = &[
+    &["build"],
+    &["profile", "dev"],
+    &["profile", "release"],
+    &["profile", "dev", "package", "serde"],
+    &["target", "x86_64-unknown-linux-gnu"],
+    &["target", "x86_64-pc-windows-msvc"],
+    &["target", "aarch64-pc-windows-msvc"],
+    &["alias"],
+    &["env"],
+    &["net"],
+    &["registries", "crates-io"],
+    &["unstable"],
+    &["custom", "nested"],
+];
+
+/// Keys a generated table may contain; disjoint from the table names above
+pub const KEYS: &[&str] = &[
+    "jobs",
+    "incremental",
+    "linker",
+    "rustflags",
+    "opt-level",
+    "codegen-units",
+    "custom-key",
+    "quoted key",
+];
+
+/// A generated TOML value
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSpec {
+    /// Integer
+    Integer(i64),
+    /// Boolean
+    Bool(bool),
+    /// String, escaped on rendering
+    String(String),
+    /// Array of strings
+    Array(Vec<String>),
+    /// Inline table of integers
+    InlineTable(Vec<(String, i64)>),
+}
+
+/// A generated `[table]` with its entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSpec {
+    /// Table path, rendered as a dotted header
+    pub path: Vec<String>,
+    /// Comment line above the header
+    pub comment: Option<String>,
+    /// Key, value and optional comment line above the key
+    pub entries: Vec<(String, ValueSpec, Option<String>)>,
+}
+
+/// A generated cargo config
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSpec {
+    /// Tables in file order
+    pub tables: Vec<TableSpec>,
+    /// Comment at the top of the file
+    pub header: Option<String>,
+    /// Use `\r\n` line endings
+    pub crlf: bool,
+}
+
+impl ConfigSpec {
+    /// Render the config as TOML text
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if let Some(header) = &self.header {
+            out.push_str(&format!("# {}\n\n", header));
+        }
+        for table in &self.tables {
+            if let Some(comment) = &table.comment {
+                out.push_str(&format!("# {}\n", comment));
+            }
+            let header: Vec<String> = table.path.iter().map(|s| render_key(s)).collect();
+            out.push_str(&format!("[{}]\n", header.join(".")));
+            for (key, value, comment) in &table.entries {
+                if let Some(comment) = comment {
+                    out.push_str(&format!("# {}\n", comment));
+                }
+                out.push_str(&format!("{} = {}\n", render_key(key), value.render()));
+            }
+            out.push('\n');
+        }
+        if self.crlf {
+            out = out.replace('\n', "\r\n");
+        }
+        out
+    }
+}
+
+impl ValueSpec {
+    /// Render the value in TOML syntax
+    pub fn render(&self) -> String {
+        match self {
+            ValueSpec::Integer(i) => i.to_string(),
+            ValueSpec::Bool(b) => b.to_string(),
+            ValueSpec::String(s) => toml_edit::Value::from(s.as_str()).to_string(),
+            ValueSpec::Array(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|s| toml_edit::Value::from(s.as_str()).to_string())
+                    .collect();
+                format!("[{}]", items.join(", "))
+            }
+            ValueSpec::InlineTable(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(k, v)| format!("{} = {}", k, v)).collect();
+                format!("{{ {} }}", entries.join(", "))
+            }
+        }
+    }
+}
+
+fn render_key(key: &str) -> String {
+    if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        key.to_string()
+    } else {
+        format!("\"{}\"", key)
+    }
+}
+
+fn arb_comment() -> impl Strategy<Value = Option<String>> {
+    proptest::option::of("[a-zA-Z0-9 .,:=\\[\\]-]{0,24}")
+}
+
+/// Strategy for a TOML value
+pub fn arb_value() -> impl Strategy<Value = ValueSpec> {
+    let text = "[a-zA-Z0-9 _./\\\\\"'=-]{0,12}";
+    prop_oneof![
+        (-64i64..4096).prop_map(ValueSpec::Integer),
+        any::<bool>().prop_map(ValueSpec::Bool),
+        text.prop_map(ValueSpec::String),
+        proptest::collection::vec(text, 0..4).prop_map(ValueSpec::Array),
+        proptest::collection::btree_map("[a-z]{1,6}", -8i64..8, 0..3)
+            .prop_map(|map| ValueSpec::InlineTable(map.into_iter().collect())),
+    ]
+}
+
+/// Strategy for a table at `path`
+pub fn arb_table(path: &'static [&'static str]) -> impl Strategy<Value = TableSpec> {
+    let entries = subsequence(KEYS, 0..=4)
+        .prop_flat_map(|keys| {
+            keys.into_iter()
+                .map(|key| (Just(key.to_string()), arb_value(), arb_comment()))
+                .collect::<Vec<_>>()
+        });
+    (arb_comment(), entries).prop_map(move |(comment, entries)| TableSpec {
+        path: path.iter().map(|s| s.to_string()).collect(),
+        comment,
+        entries,
+    })
+}
+
+/// Strategy for a complete config
+pub fn arb_config() -> impl Strategy<Value = ConfigSpec> {
+    let tables = subsequence(TABLE_PATHS, 0..=TABLE_PATHS.len())
+        .prop_shuffle()
+        .prop_flat_map(|paths| paths.into_iter().map(arb_table).collect::<Vec<_>>());
+    (tables, arb_comment(), any::<bool>())
+        .prop_map(|(tables, header, crlf)| ConfigSpec { tables, header, crlf })
+}
+
+/// Strategy for the text of a complete config
+pub fn arb_config_text() -> impl Strategy<Value = String> {
+    arb_config().prop_map(|config| config.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::test_runner::TestRunner;
+
+    #[test]
+    fn test_generated_configs_parse() {
+        let mut runner = TestRunner::default();
+        runner
+            .run(&arb_config_text(), |text| {
+                prop_assert!(text.parse::<toml_edit::DocumentMut>().is_ok(), "invalid TOML:\n{}", text);
+                Ok(())
+            })
+            .unwrap();
+    }
+}
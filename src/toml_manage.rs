@@ -0,0 +1,278 @@
+//! Managed sections in TOML config files (feature `toml-manage`).
+//!
+//! The primitives cargo-optimize uses to edit `.cargo/config.toml`, without
+//! any of the optimization logic, for other build tooling:
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use cargo_optimize::toml_manage::ManagedDocument;
+//! use toml_edit::{value, Table};
+//!
+//! let mut doc = ManagedDocument::open(".cargo/config.toml", "my-tool")?;
+//! let mut env = Table::new();
+//! env.insert("MY_TOOL_CACHE", value("/tmp/my-tool"));
+//! doc.upsert_table(&["env"], env)?;
+//! for change in doc.diff()? {
+//!     println!("{}: {:?} -> {:?}", change.dotted_key(), change.previous, change.written);
+//! }
+//! doc.save()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A managed table carries a `# <owner>: managed` comment above its header.
+//! Tables without the marker belong to the user and are never replaced or
+//! removed. The file is read once, formatting and comments elsewhere are
+//! preserved, and saving writes it with a single atomic rename.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml_edit::{DocumentMut, Item, Table};
+
+pub use crate::journal::JournalEntry as Change;
+use crate::journal::ApplyJournal;
+use crate::transaction::ConfigTransaction;
+
+/// A TOML file opened for editing its managed tables
+#[derive(Debug, Clone)]
+pub struct ManagedDocument {
+    transaction: ConfigTransaction,
+    doc: DocumentMut,
+    owner: String,
+}
+
+impl ManagedDocument {
+    /// Open `path` for tables managed by `owner` (e.g. "my-tool")
+    ///
+    /// A missing file is treated as empty and created on save.
+    pub fn open(path: impl Into<PathBuf>, owner: &str) -> Result<Self> {
+        let owner = owner.trim();
+        if owner.is_empty() || owner.contains('\n') {
+            anyhow::bail!("Invalid owner {:?}", owner);
+        }
+        let transaction = ConfigTransaction::begin(path)?;
+        let doc = match transaction.original() {
+            Some(content) => content
+                .parse::<DocumentMut>()
+                .with_context(|| format!("Failed to parse {:?}", transaction.path()))?,
+            None => DocumentMut::new(),
+        };
+        Ok(ManagedDocument { transaction, doc, owner: owner.to_string() })
+    }
+
+    /// Path of the file
+    pub fn path(&self) -> &Path {
+        self.transaction.path()
+    }
+
+    /// Current document, including changes not saved yet
+    pub fn document(&self) -> &DocumentMut {
+        &self.doc
+    }
+
+    /// Comment marking the tables of this owner
+    pub fn marker(&self) -> String {
+        format!("# {}: managed", self.owner)
+    }
+
+    /// Whether the table at `key` exists and is managed by this owner
+    pub fn is_managed(&self, key: &[&str]) -> bool {
+        lookup(self.doc.as_table(), key).is_some_and(|table| has_marker(table, &self.marker()))
+    }
+
+    /// Keys of every table managed by this owner
+    pub fn managed_tables(&self) -> Vec<Vec<String>> {
+        let mut found = Vec::new();
+        collect_managed(self.doc.as_table(), &self.marker(), &mut Vec::new(), &mut found);
+        found
+    }
+
+    /// Insert or replace the managed table at `key`
+    ///
+    /// Fails if a table the owner does not manage already exists there.
+    /// Missing parent tables are created without headers of their own.
+    pub fn upsert_table(&mut self, key: &[&str], mut table: Table) -> Result<()> {
+        let (name, parents) = key.split_last().context("Empty table key")?;
+        let marker = self.marker();
+        let parent = parent_table_mut(self.doc.as_table_mut(), parents)?;
+        match parent.get(name) {
+            Some(Item::Table(existing)) if !has_marker(existing, &marker) => {
+                anyhow::bail!("[{}] exists and is not managed by {}", key.join("."), self.owner)
+            }
+            Some(Item::Table(_)) | None => {}
+            Some(_) => anyhow::bail!("{} exists and is not a table", key.join(".")),
+        }
+        table.set_implicit(false);
+        table.decor_mut().set_prefix(format!("\n{}\n", marker));
+        parent.insert(name, Item::Table(table));
+        Ok(())
+    }
+
+    /// Remove the managed table at `key`
+    ///
+    /// Returns false if there is no managed table there; tables the owner
+    /// does not manage are left alone. Parents emptied by the removal are
+    /// dropped if they only existed implicitly.
+    pub fn remove_table(&mut self, key: &[&str]) -> bool {
+        if !self.is_managed(key) {
+            return false;
+        }
+        remove_at(self.doc.as_table_mut(), key);
+        true
+    }
+
+    /// Changes made since the file was opened, key by key
+    pub fn diff(&self) -> Result<Vec<Change>> {
+        Ok(ApplyJournal::record(self.transaction.original(), &self.doc.to_string())?.entries)
+    }
+
+    /// Whether saving would change the file
+    pub fn is_changed(&self) -> bool {
+        self.transaction.original() != Some(self.doc.to_string().as_str())
+    }
+
+    /// Copy the content the file was opened with into `dir`
+    ///
+    /// Returns the path of the backup (`<file name>.<unix seconds>.bak`).
+    pub fn backup(&self, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create backup directory {:?}", dir))?;
+        let name = self
+            .path()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "config.toml".to_string());
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let backup = dir.join(format!("{}.{}.bak", name, secs));
+        self.transaction.write_backup(&backup)?;
+        Ok(backup)
+    }
+
+    /// Write the document atomically
+    ///
+    /// Returns false without touching disk when nothing changed.
+    pub fn save(mut self) -> Result<bool> {
+        if !self.is_changed() {
+            return Ok(false);
+        }
+        self.transaction.stage(self.doc.to_string());
+        self.transaction.commit()
+    }
+}
+
+fn has_marker(table: &Table, marker: &str) -> bool {
+    table
+        .decor()
+        .prefix()
+        .and_then(|prefix| prefix.as_str())
+        .is_some_and(|prefix| prefix.lines().any(|line| line.trim() == marker))
+}
+
+fn lookup<'a>(table: &'a Table, key: &[&str]) -> Option<&'a Table> {
+    key.iter().try_fold(table, |table, segment| table.get(segment)?.as_table())
+}
+
+fn parent_table_mut<'a>(table: &'a mut Table, key: &[&str]) -> Result<&'a mut Table> {
+    let mut current = table;
+    for (depth, segment) in key.iter().enumerate() {
+        let item = current.entry(segment).or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        current = item
+            .as_table_mut()
+            .with_context(|| format!("{} exists and is not a table", key[..=depth].join(".")))?;
+    }
+    Ok(current)
+}
+
+fn remove_at(table: &mut Table, key: &[&str]) {
+    let Some((first, rest)) = key.split_first() else { return };
+    if rest.is_empty() {
+        table.remove(first);
+        return;
+    }
+    let Some(child) = table.get_mut(first).and_then(Item::as_table_mut) else { return };
+    remove_at(child, rest);
+    if child.is_empty() && child.is_implicit() {
+        table.remove(first);
+    }
+}
+
+fn collect_managed(table: &Table, marker: &str, path: &mut Vec<String>, found: &mut Vec<Vec<String>>) {
+    for (key, item) in table.iter() {
+        if let Some(child) = item.as_table() {
+            path.push(key.to_string());
+            if has_marker(child, marker) {
+                found.push(path.clone());
+            }
+            collect_managed(child, marker, path, found);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use toml_edit::value;
+
+    fn table(key: &str, text: &str) -> Table {
+        let mut table = Table::new();
+        table.insert(key, value(text));
+        table
+    }
+
+    #[test]
+    fn test_upsert_and_remove_managed_tables() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(&path, "# user settings\n[alias]\nb = \"build\"\n").unwrap();
+
+        let mut doc = ManagedDocument::open(&path, "my-tool").unwrap();
+        assert!(doc.upsert_table(&["alias"], table("t", "test")).is_err());
+        doc.upsert_table(&["target", "x86_64-unknown-linux-gnu"], table("linker", "clang")).unwrap();
+        doc.upsert_table(&["env"], table("CACHE", "/tmp/a")).unwrap();
+        doc.upsert_table(&["env"], table("CACHE", "/tmp/b")).unwrap();
+        assert_eq!(
+            doc.managed_tables(),
+            vec![vec!["target".to_string(), "x86_64-unknown-linux-gnu".to_string()], vec!["env".to_string()]]
+        );
+
+        let diff = doc.diff().unwrap();
+        assert!(diff.iter().any(|c| c.dotted_key() == "env.CACHE" && c.written.as_deref() == Some("\"/tmp/b\"")));
+        assert!(doc.save().unwrap());
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# user settings\n[alias]\nb = \"build\"\n"));
+        assert!(content.contains("# my-tool: managed\n[target.x86_64-unknown-linux-gnu]"));
+
+        let mut doc = ManagedDocument::open(&path, "my-tool").unwrap();
+        assert!(!doc.remove_table(&["alias"]));
+        assert!(doc.remove_table(&["target", "x86_64-unknown-linux-gnu"]));
+        assert!(doc.remove_table(&["env"]));
+        let backup = doc.backup(&temp.path().join("backups")).unwrap();
+        assert_eq!(fs::read_to_string(backup).unwrap(), content);
+        doc.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# user settings\n[alias]\nb = \"build\"\n");
+    }
+
+    #[test]
+    fn test_owners_do_not_touch_each_other() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        let mut doc = ManagedDocument::open(&path, "tool-a").unwrap();
+        doc.upsert_table(&["env"], table("A", "1")).unwrap();
+        assert!(doc.clone().save().unwrap());
+        assert!(!ManagedDocument::open(&path, "tool-a").unwrap().save().unwrap());
+
+        let mut other = ManagedDocument::open(&path, "tool-b").unwrap();
+        assert!(!other.is_managed(&["env"]));
+        assert!(!other.remove_table(&["env"]));
+        assert!(other.upsert_table(&["env"], table("B", "2")).is_err());
+        assert!(ManagedDocument::open(&path, "").is_err());
+    }
+}
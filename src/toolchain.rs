@@ -0,0 +1,465 @@
+//! Rust toolchain detection.
+//!
+//! Parses `rustc -vV` output into a [`ToolchainInfo`] so that settings which
+//! depend on the compiler version or release channel (e.g. `[unstable]` keys
+//! that only work on nightly) can be gated correctly.
+
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use toml_edit::{DocumentMut, Item};
+
+use crate::proxy::{self, Proxy};
+
+/// Server rustup downloads from unless `RUSTUP_DIST_SERVER` says otherwise
+const DEFAULT_DIST_SERVER: &str = "https://static.rust-lang.org";
+
+/// Rust release channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Stable release
+    Stable,
+    /// Beta release
+    Beta,
+    /// Nightly build
+    Nightly,
+}
+
+/// A Rust version number (major.minor.patch)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RustVersion {
+    /// Major version
+    pub major: u32,
+    /// Minor version
+    pub minor: u32,
+    /// Patch version
+    pub patch: u32,
+}
+
+impl RustVersion {
+    /// Create a new version
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        RustVersion { major, minor, patch }
+    }
+}
+
+impl PartialOrd for RustVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RustVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch))
+    }
+}
+
+impl FromStr for RustVersion {
+    type Err = anyhow::Error;
+
+    /// Parse "1.75", "1.75.0" or "1.75.0-nightly" (pre-release suffix ignored)
+    fn from_str(s: &str) -> Result<Self> {
+        let core = s.trim().split(['-', '+']).next().unwrap_or_default();
+        let mut parts = core.split('.');
+        let mut next = |name: &str, required: bool| -> Result<u32> {
+            match parts.next() {
+                Some(p) => p
+                    .parse::<u32>()
+                    .with_context(|| format!("Invalid {} version component in '{}'", name, s)),
+                None if required => anyhow::bail!("Missing {} version component in '{}'", name, s),
+                None => Ok(0),
+            }
+        };
+
+        let major = next("major", true)?;
+        let minor = next("minor", true)?;
+        let patch = next("patch", false)?;
+        Ok(RustVersion::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for RustVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Information about the active Rust toolchain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolchainInfo {
+    /// Compiler version
+    pub version: RustVersion,
+    /// Release channel
+    pub channel: Channel,
+    /// Host target triple
+    pub host: String,
+}
+
+impl ToolchainInfo {
+    /// Detect the active toolchain by running `rustc -vV`
+    pub fn detect() -> Result<Self> {
+        let output = Command::new("rustc")
+            .arg("-vV")
+            .output()
+            .context("Failed to run rustc -vV")?;
+
+        if !output.status.success() {
+            anyhow::bail!("rustc -vV exited with {}", output.status);
+        }
+
+        Self::parse_verbose_version(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Parse the output of `rustc -vV`
+    pub fn parse_verbose_version(output: &str) -> Result<Self> {
+        let mut release = None;
+        let mut host = None;
+
+        for line in output.lines() {
+            if let Some(value) = line.strip_prefix("release:") {
+                release = Some(value.trim().to_string());
+            } else if let Some(value) = line.strip_prefix("host:") {
+                host = Some(value.trim().to_string());
+            }
+        }
+
+        let release = release.context("rustc -vV output has no release line")?;
+        let channel = if release.contains("nightly") || release.contains("dev") {
+            Channel::Nightly
+        } else if release.contains("beta") {
+            Channel::Beta
+        } else {
+            Channel::Stable
+        };
+
+        Ok(ToolchainInfo {
+            version: release.parse()?,
+            channel,
+            host: host.unwrap_or_else(|| "unknown".to_string()),
+        })
+    }
+
+    /// Whether unstable (`-Z`) cargo features can be used
+    pub fn is_nightly(&self) -> bool {
+        self.channel == Channel::Nightly
+    }
+}
+
+/// A toolchain to check generated configuration against (version + channel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolchainSpec {
+    /// Compiler version
+    pub version: RustVersion,
+    /// Release channel
+    pub channel: Channel,
+}
+
+impl ToolchainSpec {
+    /// A stable toolchain of the given version
+    pub const fn stable(version: RustVersion) -> Self {
+        ToolchainSpec { version, channel: Channel::Stable }
+    }
+
+    /// A nightly toolchain of the given version
+    pub const fn nightly(version: RustVersion) -> Self {
+        ToolchainSpec { version, channel: Channel::Nightly }
+    }
+
+    /// Whether this toolchain is at least the given version
+    pub fn supports(&self, version: RustVersion) -> bool {
+        self.version >= version
+    }
+}
+
+impl From<&ToolchainInfo> for ToolchainSpec {
+    fn from(info: &ToolchainInfo) -> Self {
+        ToolchainSpec { version: info.version, channel: info.channel }
+    }
+}
+
+impl FromStr for ToolchainSpec {
+    type Err = anyhow::Error;
+
+    /// Parse "1.70", "1.70.0", "1.80.0-beta" or "1.80.0-nightly"
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let channel = if s.contains("nightly") {
+            Channel::Nightly
+        } else if s.contains("beta") {
+            Channel::Beta
+        } else {
+            Channel::Stable
+        };
+        Ok(ToolchainSpec { version: s.parse()?, channel })
+    }
+}
+
+impl fmt::Display for ToolchainSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.channel {
+            Channel::Stable => write!(f, "{}", self.version),
+            Channel::Beta => write!(f, "{}-beta", self.version),
+            Channel::Nightly => write!(f, "{}-nightly", self.version),
+        }
+    }
+}
+
+/// List the targets known to the active rustc (`rustc --print target-list`)
+pub fn target_list() -> Result<Vec<String>> {
+    let output = Command::new("rustc")
+        .args(["--print", "target-list"])
+        .output()
+        .context("Failed to run rustc --print target-list")?;
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// List the rustup components installed for the active toolchain
+pub fn installed_components() -> Result<Vec<String>> {
+    let output = Command::new("rustup")
+        .args(["component", "list", "--installed"])
+        .output()
+        .context("Failed to run rustup component list")?;
+
+    if !output.status.success() {
+        anyhow::bail!("rustup component list exited with {}", output.status);
+    }
+
+    Ok(parse_installed_components(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse `rustup component list --installed` output
+pub fn parse_installed_components(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(|line| line.trim().trim_end_matches(" (installed)").to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Whether a component (e.g. "rust-src", "llvm-tools") is in the installed list
+///
+/// Installed components are listed with a host-triple suffix
+/// (`llvm-tools-x86_64-unknown-linux-gnu`), and some still carry the legacy
+/// `-preview` name.
+pub fn has_component(installed: &[String], component: &str) -> bool {
+    let component = component.trim_end_matches("-preview");
+    installed.iter().any(|name| {
+        let name = name.replace("-preview", "");
+        name == component || name.starts_with(&format!("{}-", component))
+    })
+}
+
+/// Install a component with `rustup component add`, downloading through `proxy`
+///
+/// A failed download that points at the proxy setup says what to fix.
+pub fn install_component(component: &str, proxy: &Proxy) -> Result<()> {
+    let mut command = Command::new("rustup");
+    command.args(["component", "add", component]);
+    proxy.configure(&mut command);
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run rustup component add {}", component))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let dist_server = std::env::var("RUSTUP_DIST_SERVER").unwrap_or_else(|_| DEFAULT_DIST_SERVER.to_string());
+        anyhow::bail!(
+            "rustup component add {} exited with {}: {}",
+            component,
+            output.status,
+            proxy::explain(&stderr, proxy.for_url(&dist_server))
+        );
+    }
+    Ok(())
+}
+
+/// Detect the minimum supported Rust version of a project
+///
+/// Reads `package.rust-version` from the root `Cargo.toml`, following
+/// `rust-version.workspace = true` to `workspace.package.rust-version`. For
+/// virtual workspaces the lowest `rust-version` across the members is used,
+/// since every member has to build with the generated configuration.
+pub fn detect_msrv(project_root: &Path) -> Result<Option<RustVersion>> {
+    let manifest = read_manifest(&project_root.join("Cargo.toml"))?;
+    let workspace_msrv = manifest
+        .get("workspace")
+        .and_then(|ws| ws.get("package"))
+        .and_then(|pkg| pkg.get("rust-version"))
+        .and_then(Item::as_str)
+        .map(str::parse::<RustVersion>)
+        .transpose()?;
+
+    if let Some(msrv) = package_msrv(&manifest, workspace_msrv)? {
+        return Ok(Some(msrv));
+    }
+
+    let mut lowest = workspace_msrv;
+    for member in workspace_members(&manifest, project_root) {
+        let Ok(member_manifest) = read_manifest(&member.join("Cargo.toml")) else {
+            continue;
+        };
+        if let Some(msrv) = package_msrv(&member_manifest, workspace_msrv)? {
+            lowest = Some(lowest.map_or(msrv, |current| current.min(msrv)));
+        }
+    }
+
+    Ok(lowest)
+}
+
+fn read_manifest(path: &Path) -> Result<DocumentMut> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    content
+        .parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn package_msrv(manifest: &DocumentMut, workspace_msrv: Option<RustVersion>) -> Result<Option<RustVersion>> {
+    let Some(rust_version) = manifest.get("package").and_then(|pkg| pkg.get("rust-version")) else {
+        return Ok(None);
+    };
+
+    if let Some(version) = rust_version.as_str() {
+        return Ok(Some(version.parse()?));
+    }
+
+    let inherits = rust_version
+        .get("workspace")
+        .and_then(Item::as_bool)
+        .unwrap_or(false);
+    Ok(if inherits { workspace_msrv } else { None })
+}
+
+/// Member directories listed in `workspace.members` (supports trailing `/*`)
+fn workspace_members(manifest: &DocumentMut, project_root: &Path) -> Vec<std::path::PathBuf> {
+    let Some(members) = manifest
+        .get("workspace")
+        .and_then(|ws| ws.get("members"))
+        .and_then(Item::as_array)
+    else {
+        return Vec::new();
+    };
+
+    let mut dirs = Vec::new();
+    for member in members.iter().filter_map(|m| m.as_str()) {
+        if let Some(parent) = member.strip_suffix("/*") {
+            if let Ok(entries) = fs::read_dir(project_root.join(parent)) {
+                let mut found: Vec<_> = entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.join("Cargo.toml").is_file())
+                    .collect();
+                found.sort();
+                dirs.extend(found);
+            }
+        } else {
+            dirs.push(project_root.join(member));
+        }
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!("1.75.0".parse::<RustVersion>().unwrap(), RustVersion::new(1, 75, 0));
+        assert_eq!("1.70".parse::<RustVersion>().unwrap(), RustVersion::new(1, 70, 0));
+        assert_eq!("1.80.0-nightly".parse::<RustVersion>().unwrap(), RustVersion::new(1, 80, 0));
+        assert!("1".parse::<RustVersion>().is_err());
+        assert!("abc".parse::<RustVersion>().is_err());
+        assert!(RustVersion::new(1, 70, 0) < RustVersion::new(1, 75, 0));
+    }
+
+    #[test]
+    fn test_parse_verbose_version() {
+        let stable = "rustc 1.75.0 (82e1608df 2023-12-21)\n\
+                      binary: rustc\n\
+                      host: x86_64-unknown-linux-gnu\n\
+                      release: 1.75.0\n";
+        let info = ToolchainInfo::parse_verbose_version(stable).unwrap();
+        assert_eq!(info.channel, Channel::Stable);
+        assert_eq!(info.version, RustVersion::new(1, 75, 0));
+        assert_eq!(info.host, "x86_64-unknown-linux-gnu");
+        assert!(!info.is_nightly());
+
+        let nightly = "host: aarch64-apple-darwin\nrelease: 1.78.0-nightly\n";
+        let info = ToolchainInfo::parse_verbose_version(nightly).unwrap();
+        assert!(info.is_nightly());
+
+        assert!(ToolchainInfo::parse_verbose_version("host: x").is_err());
+    }
+
+    #[test]
+    fn test_parse_toolchain_spec() {
+        let spec: ToolchainSpec = "1.56".parse().unwrap();
+        assert_eq!(spec, ToolchainSpec::stable(RustVersion::new(1, 56, 0)));
+        assert_eq!(spec.to_string(), "1.56.0");
+
+        let spec: ToolchainSpec = "1.80.0-nightly".parse().unwrap();
+        assert_eq!(spec.channel, Channel::Nightly);
+        assert_eq!(spec.to_string(), "1.80.0-nightly");
+        assert!(spec.supports(RustVersion::new(1, 59, 0)));
+        assert!(!spec.supports(RustVersion::new(1, 81, 0)));
+    }
+
+    #[test]
+    fn test_component_matching() {
+        let installed = parse_installed_components(
+            "cargo-x86_64-unknown-linux-gnu\nrust-src\nllvm-tools-preview-x86_64-unknown-linux-gnu\n",
+        );
+        assert_eq!(installed.len(), 3);
+        assert!(has_component(&installed, "rust-src"));
+        assert!(has_component(&installed, "llvm-tools"));
+        assert!(has_component(&installed, "llvm-tools-preview"));
+        assert!(!has_component(&installed, "rust-analyzer"));
+        assert!(!has_component(&["rust-std-x86_64-unknown-linux-gnu".to_string()], "rust-src"));
+    }
+
+    #[test]
+    fn test_detect_msrv() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"a\"\nrust-version = \"1.63\"\n").unwrap();
+        assert_eq!(detect_msrv(root).unwrap(), Some(RustVersion::new(1, 63, 0)));
+
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n[workspace.package]\nrust-version = \"1.70\"\n",
+        )
+        .unwrap();
+        for (name, rust_version) in [("a", "rust-version.workspace = true"), ("b", "rust-version = \"1.65\"")] {
+            let member = root.join("crates").join(name);
+            fs::create_dir_all(&member).unwrap();
+            fs::write(
+                member.join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\n{}\n", name, rust_version),
+            )
+            .unwrap();
+        }
+        assert_eq!(detect_msrv(root).unwrap(), Some(RustVersion::new(1, 65, 0)));
+
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"a\"\n").unwrap();
+        assert_eq!(detect_msrv(root).unwrap(), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        // rustc is always available when running the test suite
+        let info = ToolchainInfo::detect().unwrap();
+        assert!(info.version >= RustVersion::new(1, 0, 0));
+    }
+}
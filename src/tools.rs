@@ -0,0 +1,511 @@
+//! Installing fast linkers.
+//!
+//! When no fast linker is installed, [`install_linker`] either downloads an
+//! official release into a directory cargo-optimize manages or returns the
+//! package command for the running distribution, then detects linkers again.
+//! Nothing happens unless `global.linker_install.mode` opts in. In a Nix
+//! environment nothing is installed outside the store: the outcome names
+//! the nixpkgs package to add to the dev shell instead. Downloads
+//! are only unpacked when their SHA-256 matches the one pinned in the
+//! config or shipped with [`LINKER_RELEASES`]: checksums are never fetched
+//! alongside the archive they vouch for, so an archive without one is not
+//! downloaded and the package command is returned instead. Downloads go
+//! through the [`Proxy`] in effect.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info};
+
+use crate::config::ConfigManager;
+use crate::linkers::{self, LinkerSpec, Platform};
+use crate::mvp::{self, LinkerCandidate};
+use crate::output;
+use crate::proxy::{self, Proxy};
+
+/// What to do when no fast linker is installed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkerInstallMode {
+    /// Install nothing and recommend nothing
+    #[default]
+    Off,
+    /// Recommend the package command for the running system
+    PackageCommand,
+    /// Download an official release, falling back to the package command
+    Download,
+}
+
+/// Fast-linker installation settings (`[global.linker_install]`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LinkerInstallSettings {
+    /// What [`install_linker`] may do
+    #[serde(default)]
+    pub mode: LinkerInstallMode,
+    /// Where downloaded linkers are unpacked; `$CARGO_HOME/cargo-optimize/tools` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    /// Expected SHA-256 of release archives, keyed by archive file name
+    /// (e.g. "mold-2.34.1-x86_64-linux.tar.gz"); wins over the checksum
+    /// shipped with the release
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sha256: BTreeMap<String, String>,
+}
+
+/// An official release archive of a linker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkerRelease {
+    /// Registry name of the linker
+    pub linker: &'static str,
+    /// Release version
+    pub version: &'static str,
+    /// Platform the binaries run on
+    pub platform: Platform,
+    /// CPU architectures with a release archive
+    pub arches: &'static [&'static str],
+    /// Download URL; `{version}` and `{archive}` are filled in
+    pub url: &'static str,
+    /// Archive file name; `{version}` and `{arch}` are filled in
+    pub archive: &'static str,
+    /// SHA-256 of the archive per CPU architecture, as published with the release
+    pub sha256: &'static [(&'static str, &'static str)],
+}
+
+impl LinkerRelease {
+    /// File name of the archive for `arch`
+    pub fn archive_name(&self, arch: &str) -> String {
+        self.archive.replace("{version}", self.version).replace("{arch}", arch)
+    }
+
+    /// Download URL of the archive for `arch`
+    pub fn url(&self, arch: &str) -> String {
+        self.url.replace("{version}", self.version).replace("{archive}", &self.archive_name(arch))
+    }
+
+    /// SHA-256 the archive for `arch` must have: the one pinned in `settings`, else the shipped one
+    pub fn expected_sha256<'a>(&'a self, arch: &str, settings: &'a LinkerInstallSettings) -> Option<&'a str> {
+        settings.sha256.get(&self.archive_name(arch)).map(String::as_str).or_else(|| {
+            self.sha256.iter().find(|(known, _)| *known == arch).map(|(_, sha256)| *sha256)
+        })
+    }
+}
+
+/// Official releases cargo-optimize can download
+pub const LINKER_RELEASES: &[LinkerRelease] = &[LinkerRelease {
+    linker: "mold",
+    version: "2.34.1",
+    platform: Platform::Linux,
+    arches: &["x86_64", "aarch64"],
+    url: "https://github.com/rui314/mold/releases/download/v{version}/{archive}",
+    archive: "mold-{version}-{arch}-linux.tar.gz",
+    // Not pinned yet: until they are, only digests from the config download mold
+    sha256: &[],
+}];
+
+/// Distribution IDs (`ID`/`ID_LIKE` in `/etc/os-release`) with their package manager and install command
+const PACKAGE_MANAGERS: &[(&str, &str, &str)] = &[
+    ("debian", "apt", "sudo apt install"),
+    ("ubuntu", "apt", "sudo apt install"),
+    ("fedora", "dnf", "sudo dnf install"),
+    ("rhel", "dnf", "sudo dnf install"),
+    ("arch", "pacman", "sudo pacman -S"),
+    ("suse", "zypper", "sudo zypper install"),
+    ("opensuse", "zypper", "sudo zypper install"),
+    ("alpine", "apk", "sudo apk add"),
+];
+
+/// Package providing a linker, per package manager
+const PACKAGES: &[(&str, &str, &str)] = &[
+    ("mold", "apt", "mold"),
+    ("mold", "dnf", "mold"),
+    ("mold", "pacman", "mold"),
+    ("mold", "zypper", "mold"),
+    ("mold", "apk", "mold"),
+    ("mold", "pkg", "mold"),
+    ("lld", "apt", "lld"),
+    ("lld", "dnf", "lld"),
+    ("lld", "pacman", "lld"),
+    ("lld", "zypper", "lld"),
+    ("lld", "apk", "lld"),
+    ("lld", "brew", "llvm"),
+    ("gold", "apt", "binutils"),
+    ("gold", "dnf", "binutils-gold"),
+    ("gold", "pacman", "binutils"),
+];
+
+/// What [`install_linker`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallOutcome {
+    /// `global.linker_install.mode` is off
+    Disabled,
+    /// Already on `PATH` or in the tools directory
+    AlreadyInstalled,
+    /// Downloaded, verified and unpacked; the `bin` directory to put on `PATH`
+    Downloaded(PathBuf),
+    /// The package command to run
+    PackageCommand(String),
+    /// The nixpkgs attribute to add to the packages of the Nix dev shell
+    NixPackage(String),
+    /// Neither a release nor a package is known for this system
+    Unavailable,
+}
+
+/// Result of [`install_linker`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkerInstallation {
+    /// Linker asked for
+    pub linker: String,
+    /// What was done
+    pub outcome: InstallOutcome,
+    /// Linkers detected afterwards, the tools directory included
+    pub linkers: Vec<LinkerCandidate>,
+}
+
+/// Linkers [`install_linker`] can download or name a package for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linker {
+    /// mold, downloadable on Linux
+    Mold,
+    /// LLVM's lld
+    Lld,
+    /// GNU gold
+    Gold,
+}
+
+impl Linker {
+    /// Registry entry of the linker
+    pub fn spec(self) -> &'static LinkerSpec {
+        let name = match self {
+            Linker::Mold => "mold",
+            Linker::Lld => "lld",
+            Linker::Gold => "gold",
+        };
+        linkers::find(name).expect("installable linkers are in the registry")
+    }
+}
+
+/// Install `linker` as far as the `[global.linker_install]` of the current project allows
+///
+/// Reads `cargo-optimize.toml` from the current directory and downloads
+/// through its proxy settings; see [`install_linker_with`].
+pub fn install_linker(linker: Linker) -> Result<LinkerInstallation> {
+    let manager = ConfigManager::new()?;
+    let global = &manager.config().global;
+    install_linker_with(linker.spec(), &global.linker_install, &Proxy::from_env(&global.proxy))
+}
+
+/// Install `spec` on this machine as far as `settings` allow
+///
+/// Fails when the linker does not run on this platform, or when a download
+/// cannot be fetched or does not match its checksum. Releases without a
+/// known checksum are skipped for the package command. Downloads go through
+/// `proxy`.
+pub fn install_linker_with(spec: &LinkerSpec, settings: &LinkerInstallSettings, proxy: &Proxy) -> Result<LinkerInstallation> {
+    output::ensure_enabled()?;
+    let Some(platform) = Platform::current() else {
+        bail!("{} cannot be installed on this platform", spec.name);
+    };
+    if !spec.supports(platform) {
+        bail!("{} does not run on {}", spec.name, platform.name());
+    }
+    let dir = tools_dir(settings);
+    let exists = |command: &str| {
+        mvp::command_exists(command) || dir.as_deref().and_then(|dir| managed_command(dir, command)).is_some()
+    };
+
+    let outcome = if settings.mode == LinkerInstallMode::Off {
+        InstallOutcome::Disabled
+    } else if spec.is_installed_with(exists) {
+        InstallOutcome::AlreadyInstalled
+    } else if crate::nix::NixEnvironment::detect().is_some() {
+        match crate::nix::package_for(spec.name) {
+            Some(package) => InstallOutcome::NixPackage(package.to_string()),
+            None => InstallOutcome::Unavailable,
+        }
+    } else {
+        let release = (settings.mode == LinkerInstallMode::Download)
+            .then(|| release_for(spec.name, platform, std::env::consts::ARCH))
+            .flatten();
+        let os_release = fs::read_to_string("/etc/os-release").unwrap_or_default();
+        download_or_package(spec, platform, release, settings, dir.as_deref(), &os_release, proxy)?
+    };
+
+    let linkers = mvp::detect_linkers_on(platform, None, exists, |command| {
+        match dir.as_deref().and_then(|dir| managed_command(dir, command)) {
+            Some(path) => mvp::locate_command(&path.to_string_lossy()),
+            None => mvp::locate_command(command),
+        }
+    });
+    Ok(LinkerInstallation { linker: spec.name.to_string(), outcome, linkers })
+}
+
+/// Directory downloaded linkers are unpacked into
+pub fn tools_dir(settings: &LinkerInstallSettings) -> Option<PathBuf> {
+    if let Some(dir) = &settings.dir {
+        return Some(dir.clone());
+    }
+    let cargo_home = std::env::var_os("CARGO_HOME").map(PathBuf::from).or_else(|| {
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| Path::new(&home).join(".cargo"))
+    })?;
+    Some(cargo_home.join("cargo-optimize").join("tools"))
+}
+
+/// Directories of the releases in [`LINKER_RELEASES`] unpacked into `dir`
+///
+/// Anything else in `dir` was not put there by cargo-optimize.
+pub fn unpacked_releases(dir: &Path) -> Vec<PathBuf> {
+    LINKER_RELEASES
+        .iter()
+        .flat_map(|release| release.arches.iter().map(move |arch| release.archive_name(arch)))
+        .map(|archive| dir.join(archive.trim_end_matches(".tar.gz")))
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+/// Path of `command` in the `bin` directory of a release unpacked into `dir`
+pub fn managed_command(dir: &Path, command: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path().join("bin").join(command))
+        .find(|path| path.is_file())
+}
+
+/// The downloadable release of `linker` for `platform` and `arch`
+pub fn release_for(linker: &str, platform: Platform, arch: &str) -> Option<&'static LinkerRelease> {
+    LINKER_RELEASES
+        .iter()
+        .find(|release| release.linker == linker && release.platform == platform && release.arches.contains(&arch))
+}
+
+/// Download `release` when its checksum is known, else name the package command
+fn download_or_package(
+    spec: &LinkerSpec,
+    platform: Platform,
+    release: Option<&LinkerRelease>,
+    settings: &LinkerInstallSettings,
+    dir: Option<&Path>,
+    os_release: &str,
+    proxy: &Proxy,
+) -> Result<InstallOutcome> {
+    let arch = std::env::consts::ARCH;
+    if let (Some((release, expected)), Some(dir)) = (release.and_then(|release| verifiable(release, arch, settings)), dir) {
+        return Ok(InstallOutcome::Downloaded(download(release, arch, expected, dir, proxy)?));
+    }
+    Ok(match package_command(spec.name, platform, os_release) {
+        Some(command) => InstallOutcome::PackageCommand(command),
+        None => InstallOutcome::Unavailable,
+    })
+}
+
+/// `release` with the SHA-256 its archive for `arch` must have, when one is known
+///
+/// Without a checksum the release is not downloaded at all.
+fn verifiable<'a>(
+    release: &'a LinkerRelease,
+    arch: &str,
+    settings: &'a LinkerInstallSettings,
+) -> Option<(&'a LinkerRelease, &'a str)> {
+    match release.expected_sha256(arch, settings) {
+        Some(expected) => Some((release, expected)),
+        None => {
+            debug!(
+                "No checksum known for {}; pin one under [global.linker_install.sha256] to download it",
+                release.archive_name(arch)
+            );
+            None
+        }
+    }
+}
+
+/// The command installing `linker` with the system package manager
+///
+/// On Linux the distribution is read from `os_release`, the content of
+/// `/etc/os-release`; `ID` wins over `ID_LIKE`.
+pub fn package_command(linker: &str, platform: Platform, os_release: &str) -> Option<String> {
+    let (manager, install) = match platform {
+        Platform::Linux => {
+            let field = |key: &str| {
+                os_release
+                    .lines()
+                    .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                    .map(|value| value.trim().trim_matches('"').to_string())
+                    .unwrap_or_default()
+            };
+            let ids = format!("{} {}", field("ID"), field("ID_LIKE"));
+            ids.split_whitespace().find_map(|id| {
+                PACKAGE_MANAGERS
+                    .iter()
+                    .find(|(known, _, _)| *known == id)
+                    .map(|(_, manager, install)| (*manager, *install))
+            })?
+        }
+        Platform::FreeBsd => ("pkg", "sudo pkg install"),
+        Platform::MacOs => ("brew", "brew install"),
+        _ => return None,
+    };
+    PACKAGES
+        .iter()
+        .find(|(name, known, _)| *name == linker && *known == manager)
+        .map(|(_, _, package)| format!("{} {}", install, package))
+}
+
+/// Whether the SHA-256 of `content` is `expected` (hex, any case)
+pub fn checksum_matches(content: &[u8], expected: &str) -> bool {
+    let actual: String = Sha256::digest(content).iter().map(|byte| format!("{:02x}", byte)).collect();
+    actual.eq_ignore_ascii_case(expected.trim())
+}
+
+/// Download `release` into `dir`, checking it against `expected`, and return its `bin` directory
+fn download(release: &LinkerRelease, arch: &str, expected: &str, dir: &Path, proxy: &Proxy) -> Result<PathBuf> {
+    let archive = release.archive_name(arch);
+    let unpacked = dir.join(archive.trim_end_matches(".tar.gz"));
+    let bin_dir = unpacked.join("bin");
+    if bin_dir.join(release.linker).is_file() {
+        debug!("{} is already unpacked in {:?}", archive, unpacked);
+        return Ok(bin_dir);
+    }
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let partial = dir.join(format!(".{}.{}.part", archive, std::process::id()));
+    let url = release.url(arch);
+    info!("Downloading {}", url);
+    let result = (|| -> Result<()> {
+        fetch(&url, &partial, proxy)?;
+        let content = fs::read(&partial).with_context(|| format!("Failed to read {:?}", partial))?;
+        if !checksum_matches(&content, expected) {
+            bail!("Checksum mismatch for {}: the download does not match the pinned SHA-256", archive);
+        }
+        run(Command::new("tar").arg("-xzf").arg(&partial).arg("-C").arg(dir))
+    })();
+    let _ = fs::remove_file(&partial);
+    result?;
+
+    if !bin_dir.join(release.linker).is_file() {
+        bail!("{} did not contain bin/{}", archive, release.linker);
+    }
+    Ok(bin_dir)
+}
+
+/// Download `url` to `path` with curl, naming the proxy problem when one is the cause
+fn fetch(url: &str, path: &Path, proxy: &Proxy) -> Result<()> {
+    let mut command = Command::new("curl");
+    command.args(["--fail", "--location", "--silent", "--show-error", "--output"]).arg(path).arg(url);
+    proxy.configure(&mut command);
+    let output = command.output().context("Failed to run curl")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("Downloading {} failed: {}", url, proxy::explain(&stderr, proxy.for_url(url)));
+    }
+    Ok(())
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command.status().with_context(|| format!("Failed to run {}", program))?;
+    if !status.success() {
+        bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_command() {
+        let ubuntu = "NAME=\"Ubuntu\"\nID=ubuntu\nID_LIKE=debian\n";
+        assert_eq!(package_command("mold", Platform::Linux, ubuntu).as_deref(), Some("sudo apt install mold"));
+        let rocky = "ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n";
+        assert_eq!(package_command("gold", Platform::Linux, rocky).as_deref(), Some("sudo dnf install binutils-gold"));
+        assert_eq!(package_command("lld", Platform::MacOs, "").as_deref(), Some("brew install llvm"));
+        assert_eq!(package_command("mold", Platform::Linux, "ID=gentoo\n"), None);
+        assert_eq!(package_command("wild", Platform::Linux, ubuntu), None);
+    }
+
+    #[test]
+    fn test_releases_and_checksums() {
+        for linker in [Linker::Mold, Linker::Lld, Linker::Gold] {
+            assert!(PACKAGES.iter().any(|(name, _, _)| *name == linker.spec().name), "{:?}", linker);
+        }
+        let release = release_for(Linker::Mold.spec().name, Platform::Linux, "x86_64").unwrap();
+        assert_eq!(release.archive_name("x86_64"), "mold-2.34.1-x86_64-linux.tar.gz");
+        assert_eq!(
+            release.url("x86_64"),
+            "https://github.com/rui314/mold/releases/download/v2.34.1/mold-2.34.1-x86_64-linux.tar.gz"
+        );
+        assert!(release_for("mold", Platform::Linux, "riscv64").is_none());
+        assert!(checksum_matches(b"abc", "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD"));
+        assert!(!checksum_matches(b"abd", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"));
+
+        // The config pins over the shipped checksum
+        let shipped = LinkerRelease { sha256: &[("x86_64", "aa")], ..*release };
+        let mut settings = LinkerInstallSettings::default();
+        assert_eq!(shipped.expected_sha256("x86_64", &settings), Some("aa"));
+        assert_eq!(shipped.expected_sha256("aarch64", &settings), None);
+        settings.sha256.insert("mold-2.34.1-x86_64-linux.tar.gz".to_string(), "bb".to_string());
+        assert_eq!(shipped.expected_sha256("x86_64", &settings), Some("bb"));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("mold-2.34.1-x86_64-linux/bin")).unwrap();
+        fs::write(dir.path().join("mold-2.34.1-x86_64-linux/bin/mold"), "").unwrap();
+        assert_eq!(
+            managed_command(dir.path(), "mold"),
+            Some(dir.path().join("mold-2.34.1-x86_64-linux/bin/mold"))
+        );
+        assert_eq!(managed_command(dir.path(), "wild"), None);
+    }
+
+    #[test]
+    fn test_release_without_checksum_falls_back() {
+        let arch = std::env::consts::ARCH;
+        let release = LinkerRelease { sha256: &[], ..LINKER_RELEASES[0] };
+        let mut settings = LinkerInstallSettings { mode: LinkerInstallMode::Download, ..Default::default() };
+        let dir = tempfile::TempDir::new().unwrap();
+
+        // Without a known checksum nothing is fetched and the package command is returned
+        let spec = Linker::Mold.spec();
+        let outcome = |os_release| {
+            download_or_package(spec, Platform::Linux, Some(&release), &settings, Some(dir.path()), os_release, &Proxy::default())
+        };
+        assert_eq!(outcome("ID=ubuntu\n").unwrap(), InstallOutcome::PackageCommand("sudo apt install mold".to_string()));
+        assert_eq!(outcome("ID=gentoo\n").unwrap(), InstallOutcome::Unavailable);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        // A checksum pinned in the config makes the release downloadable
+        settings.sha256.insert(release.archive_name(arch), "bb".to_string());
+        assert_eq!(verifiable(&release, arch, &settings).map(|(_, expected)| expected), Some("bb"));
+    }
+
+    #[test]
+    fn test_download_checks_the_checksum() {
+        let mirror = tempfile::TempDir::new().unwrap();
+        let unpacked = mirror.path().join("mold-2.34.1-x86_64-linux");
+        fs::create_dir_all(unpacked.join("bin")).unwrap();
+        fs::write(unpacked.join("bin").join("mold"), "#!/bin/sh\n").unwrap();
+        let archive = mirror.path().join("mold-2.34.1-x86_64-linux.tar.gz");
+        run(Command::new("tar").arg("-czf").arg(&archive).arg("-C").arg(mirror.path()).arg("mold-2.34.1-x86_64-linux"))
+            .unwrap();
+        let url = format!("file://{}/{{archive}}", mirror.path().display());
+        let release = LinkerRelease { url: Box::leak(url.into_boxed_str()), ..LINKER_RELEASES[0] };
+        let dir = tempfile::TempDir::new().unwrap();
+
+        // A tampered archive is neither unpacked nor left behind
+        let wrong = "0".repeat(64);
+        let error = download(&release, "x86_64", &wrong, dir.path(), &Proxy::default()).unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"), "{:#}", error);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let digest = Sha256::digest(fs::read(&archive).unwrap());
+        let expected: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let bin_dir = download(&release, "x86_64", &expected, dir.path(), &Proxy::default()).unwrap();
+        assert!(bin_dir.join("mold").is_file());
+    }
+}
@@ -0,0 +1,225 @@
+//! Single-read, single-write transactions for `.cargo/config.toml`.
+//!
+//! A [`ConfigTransaction`] reads the config file exactly once when it is
+//! opened. Validation, backups and merging all work from that in-memory copy.
+//! On commit the final content is written to a temporary file next to the
+//! config, fsynced, and atomically renamed into place. A crash therefore
+//! never leaves a half-written config behind.
+
+use anyhow::Result;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+use crate::paths::{io_context, long_path};
+
+/// A pending change to a config file
+#[derive(Debug, Clone)]
+pub struct ConfigTransaction {
+    path: PathBuf,
+    original: Option<String>,
+    staged: Option<String>,
+}
+
+impl ConfigTransaction {
+    /// Open a transaction, reading the current file content once
+    ///
+    /// A missing file is not an error; [`original`](Self::original) is `None`.
+    pub fn begin(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let original = read_with_retry(&path)?;
+        Ok(ConfigTransaction { path, original, staged: None })
+    }
+
+    /// Path of the config file this transaction targets
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Content of the file when the transaction was opened
+    pub fn original(&self) -> Option<&str> {
+        self.original.as_deref()
+    }
+
+    /// Stage the final content to be written on commit
+    pub fn stage(&mut self, content: impl Into<String>) {
+        self.staged = Some(content.into());
+    }
+
+    /// Content that will be written on commit, if any
+    pub fn staged(&self) -> Option<&str> {
+        self.staged.as_deref()
+    }
+
+    /// The config as it stands once committed: the staged content, else the original
+    ///
+    /// Steps after staging read this instead of the file, which another
+    /// process may be rewriting. Empty when missing or unparsable.
+    pub fn document(&self) -> DocumentMut {
+        self.staged
+            .as_deref()
+            .or(self.original.as_deref())
+            .and_then(|content| content.parse::<DocumentMut>().ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether the staged content differs from the original
+    pub fn is_changed(&self) -> bool {
+        match &self.staged {
+            Some(staged) => self.original.as_deref() != Some(staged.as_str()),
+            None => false,
+        }
+    }
+
+    /// Write the original content to `backup_path` from memory
+    pub fn write_backup(&self, backup_path: &Path) -> Result<()> {
+        let content = self.original.as_deref().unwrap_or("# No previous configuration\n");
+        io_context(fs::write(long_path(backup_path), content), "write backup file", backup_path)
+    }
+
+    /// Write the staged content atomically
+    ///
+    /// Returns `false` without touching disk when nothing was staged.
+    pub fn commit(self) -> Result<bool> {
+        let Some(content) = self.staged else {
+            return Ok(false);
+        };
+        atomic_write(&self.path, &content)?;
+        Ok(true)
+    }
+}
+
+/// Write `content` to `path` via a temporary file, fsync and atomic rename
+pub fn atomic_write(path: &Path, content: &str) -> Result<()> {
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    io_context(fs::create_dir_all(long_path(parent)), "create parent directory", parent)?;
+
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("config.toml");
+    let temp_path = parent.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = (|| -> Result<()> {
+        let mut file = io_context(File::create(long_path(&temp_path)), "create temporary file", &temp_path)?;
+        io_context(file.write_all(content.as_bytes()), "write temporary file", &temp_path)?;
+        io_context(file.sync_all(), "sync temporary file", &temp_path)?;
+        drop(file);
+
+        rename_with_retry(&temp_path, path)?;
+
+        // Persist the rename itself; directories cannot be opened on Windows
+        #[cfg(unix)]
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(long_path(&temp_path));
+    }
+    result
+}
+
+/// Read a file with retry for Windows file locking; `None` if it does not exist
+fn read_with_retry(path: &Path) -> Result<Option<String>> {
+    let mut retries = 3;
+    loop {
+        match fs::read_to_string(long_path(path)) {
+            Ok(content) => return Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                retries -= 1;
+                if retries == 0 {
+                    return io_context(Err(e), "read", path);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+/// Rename with retry for Windows file locking
+fn rename_with_retry(from: &Path, to: &Path) -> Result<()> {
+    let mut retries = 3;
+    loop {
+        match fs::rename(long_path(from), long_path(to)) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                retries -= 1;
+                if retries == 0 {
+                    return io_context(Err(e), "replace", to);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_transaction_reads_once_and_commits_atomically() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join(".cargo").join("config.toml");
+
+        let mut tx = ConfigTransaction::begin(&path).unwrap();
+        assert!(tx.original().is_none());
+        tx.stage("[build]\njobs = 4\n");
+        assert!(tx.is_changed());
+        assert!(tx.commit().unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[build]\njobs = 4\n");
+
+        let mut tx = ConfigTransaction::begin(&path).unwrap();
+        // Later changes on disk do not affect the in-memory original
+        fs::write(&path, "changed").unwrap();
+        assert_eq!(tx.original(), Some("[build]\njobs = 4\n"));
+        assert_eq!(tx.document()["build"]["jobs"].as_integer(), Some(4));
+
+        let backup = temp.path().join("backup.toml");
+        tx.write_backup(&backup).unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "[build]\njobs = 4\n");
+
+        tx.stage("[build]\njobs = 4\n");
+        assert!(!tx.is_changed());
+
+        // No temporary files are left behind
+        let leftovers: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_atomic_write_beyond_max_path() {
+        let temp = TempDir::new().unwrap();
+        let mut dir = temp.path().to_path_buf();
+        while dir.as_os_str().len() <= crate::paths::MAX_PATH {
+            dir.push("deeply-nested-workspace-member");
+        }
+        let path = dir.join(".cargo").join("config.toml");
+
+        atomic_write(&path, "[build]\njobs = 2\n").unwrap();
+        let tx = ConfigTransaction::begin(&path).unwrap();
+        assert_eq!(tx.original(), Some("[build]\njobs = 2\n"));
+    }
+
+    #[test]
+    fn test_commit_without_staged_content_is_noop() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        let tx = ConfigTransaction::begin(&path).unwrap();
+        assert!(!tx.commit().unwrap());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,593 @@
+//! Feature unification across workspace members.
+//!
+//! Cargo resolves dependency features for the packages being built. Building
+//! one member (`cargo build -p api`, `cargo test -p cli`) can therefore
+//! compile a dependency with other features than a workspace-wide build, and
+//! switching between members recompiles it and everything above it each
+//! time. A workspace-hack crate that every member depends on, enabling the
+//! union of the features, makes every build resolve the same features.
+//!
+//! [`unification_report`] simulates the per-member builds with guppy and
+//! quantifies what the differences cost. It also records which members
+//! enable which features, so a handful of differences can be fixed by
+//! enabling the missing features in the members that lack them
+//! ([`UnificationReport::replumbing`]). For more, the advisor recommends
+//! `cargo hakari`; [`generate`] writes a minimal workspace-hack crate
+//! instead, for users who agreed to have their manifests edited.
+
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use guppy::graph::cargo::{BuildPlatform, CargoOptions, CargoResolverVersion, CargoSet};
+use guppy::graph::feature::StandardFeatures;
+use guppy::graph::{PackageGraph, PackageMetadata, PackageSource};
+use guppy::PackageId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table, Value};
+use tracing::debug;
+
+use crate::analysis;
+use crate::audit;
+use crate::output;
+use crate::transaction::atomic_write;
+
+/// Name of the generated crate, matching what `cargo hakari init` suggests
+pub const DEFAULT_CRATE_NAME: &str = "workspace-hack";
+
+/// Extra dependency build cost, in percent of the total, from which a
+/// workspace-hack crate is recommended
+const MIN_BENEFIT_PERCENT: u64 = 5;
+
+/// Manifest edits up to which enabling the missing features directly is
+/// recommended instead of a workspace-hack crate
+pub const MAX_REPLUMBING: usize = 4;
+
+/// Where a dependency comes from, as written into the generated manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DependencySource {
+    /// crates.io
+    CratesIo,
+    /// A path outside the workspace, relative to the workspace root
+    Path(PathBuf),
+    /// A git repository at a commit
+    Git {
+        /// Repository URL
+        url: String,
+        /// Locked commit
+        rev: Option<String>,
+    },
+    /// Another registry; not written into a generated crate
+    Other(String),
+}
+
+/// A dependency whose features depend on which members are built
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnifiedDependency {
+    /// Crate name
+    pub name: String,
+    /// Resolved version
+    pub version: String,
+    /// Where it comes from
+    pub source: DependencySource,
+    /// Compiled for the host (build scripts, proc macros) rather than the target
+    pub host: bool,
+    /// Features enabled when the whole workspace is built
+    pub features: Vec<String>,
+    /// Distinct feature sets it is compiled with across member and workspace builds
+    pub variants: usize,
+    /// Members whose own build enables other features
+    pub members: Vec<String>,
+    /// Relative build cost (see [`analysis::estimate_build_cost`])
+    pub cost: u64,
+    /// Features each of those members' own build lacks
+    #[serde(default)]
+    pub missing: Vec<MissingFeatures>,
+    /// Members whose own build enables each feature of the workspace build
+    #[serde(default)]
+    pub enabled_by: BTreeMap<String, Vec<String>>,
+}
+
+/// Features of a dependency a member's own build does not enable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingFeatures {
+    /// The member
+    pub member: String,
+    /// Features the workspace build enables and the member's build does not
+    pub features: Vec<String>,
+    /// Whether the member depends on the dependency directly (not only through other crates)
+    pub direct: bool,
+}
+
+/// Manifest edit giving a member's build the features the workspace build uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replumbing {
+    /// Member whose `Cargo.toml` to edit
+    pub member: String,
+    /// Dependency to enable the features of
+    pub dependency: String,
+    /// Features to enable
+    pub features: Vec<String>,
+    /// Members whose builds already enable them
+    pub enabled_by: Vec<String>,
+    /// Build cost of the dependency in percent of the dependency build
+    pub cost_percent: u64,
+    /// `cargo add` making the edit, when it can express it
+    pub command: Option<String>,
+}
+
+/// Result of simulating every member's build against the workspace build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnificationReport {
+    /// Workspace root
+    pub workspace_root: PathBuf,
+    /// Member directories, relative to the workspace root
+    pub members: Vec<PathBuf>,
+    /// Workspace-hack crate the workspace already has, if any
+    pub existing_hack: Option<String>,
+    /// Dependencies compiled with more than one feature set
+    pub dependencies: Vec<UnifiedDependency>,
+    /// Build cost of every dependency of the workspace build
+    pub total_cost: u64,
+}
+
+impl UnificationReport {
+    /// Compilations a workspace-hack would save when switching through every member
+    pub fn extra_builds(&self) -> usize {
+        self.dependencies.iter().map(|dependency| dependency.variants - 1).sum()
+    }
+
+    /// Build cost of those compilations
+    pub fn extra_cost(&self) -> u64 {
+        self.dependencies.iter().map(|dependency| dependency.cost * (dependency.variants as u64 - 1)).sum()
+    }
+
+    /// Extra cost in percent of the dependency build cost
+    pub fn benefit_percent(&self) -> u64 {
+        (self.extra_cost() * 100).checked_div(self.total_cost).unwrap_or(0)
+    }
+
+    /// Manifest edits that make every member build its dependencies like the workspace build
+    ///
+    /// Host dependencies and ones from other registries get no command; the
+    /// edit goes into `[build-dependencies]` or needs the registry spelled out.
+    pub fn replumbing(&self) -> Vec<Replumbing> {
+        let mut edits = Vec::new();
+        for dependency in &self.dependencies {
+            for missing in &dependency.missing {
+                let mut enabled_by: Vec<String> = missing
+                    .features
+                    .iter()
+                    .flat_map(|feature| dependency.enabled_by.get(feature).into_iter().flatten())
+                    .filter(|member| **member != missing.member)
+                    .cloned()
+                    .collect();
+                enabled_by.sort();
+                enabled_by.dedup();
+                let features = missing.features.join(",");
+                let command = match (&dependency.source, missing.direct) {
+                    _ if dependency.host => None,
+                    (_, true) => Some(format!("cargo add -p {} {} --features {}", missing.member, dependency.name, features)),
+                    (DependencySource::CratesIo, false) => Some(format!(
+                        "cargo add -p {} {}@{} --features {}",
+                        missing.member, dependency.name, dependency.version, features
+                    )),
+                    (DependencySource::Path(path), false) => Some(format!(
+                        "cargo add -p {} --path {} --features {}",
+                        missing.member,
+                        self.workspace_root.join(path).display(),
+                        features
+                    )),
+                    _ => None,
+                };
+                edits.push(Replumbing {
+                    member: missing.member.clone(),
+                    dependency: dependency.name.clone(),
+                    features: missing.features.clone(),
+                    enabled_by,
+                    cost_percent: (dependency.cost * 100).checked_div(self.total_cost).unwrap_or(0),
+                    command,
+                });
+            }
+        }
+        edits
+    }
+
+    /// Whether the differences cost enough to fix, by whatever means
+    pub fn worth_fixing(&self) -> bool {
+        self.members.len() > 1 && !self.dependencies.is_empty() && self.benefit_percent() >= MIN_BENEFIT_PERCENT
+    }
+
+    /// Whether a workspace-hack crate is worth adding
+    ///
+    /// Needs several members, no workspace-hack yet, and differences costing
+    /// at least [`MIN_BENEFIT_PERCENT`] of the dependency build.
+    pub fn recommends_hack(&self) -> bool {
+        self.worth_fixing() && self.existing_hack.is_none()
+    }
+}
+
+/// Whether `cargo hakari` is installed
+pub fn hakari_installed() -> bool {
+    std::process::Command::new("cargo")
+        .args(["hakari", "--version"])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Simulate the builds of the workspace at `manifest_path`
+pub fn unification_report(manifest_path: Option<&Path>) -> Result<UnificationReport> {
+    let mut cmd = MetadataCommand::new();
+    if let Some(path) = manifest_path {
+        cmd.manifest_path(path);
+    }
+    let metadata = cmd.exec().context("Failed to execute cargo metadata")?;
+    unification_report_from(&analysis::package_graph(&metadata)?)
+}
+
+/// Simulate every member's build, tests included, and the workspace build
+pub fn unification_report_from(graph: &PackageGraph) -> Result<UnificationReport> {
+    let mut options = CargoOptions::new();
+    options.set_resolver(CargoResolverVersion::V2).set_include_dev(true);
+    let resolve = |ids: Vec<&PackageId>| -> Result<CargoSet<'_>> {
+        graph
+            .resolve_ids(ids)
+            .context("Failed to resolve workspace members")?
+            .to_feature_set(StandardFeatures::Default)
+            .into_cargo_set(&options)
+            .context("Failed to simulate cargo build")
+    };
+
+    let workspace = graph.workspace();
+    let members: Vec<PackageMetadata<'_>> = workspace.iter().collect();
+    let unified = features_by_dependency(&resolve(members.iter().map(|member| member.id()).collect())?);
+    let mut per_member = Vec::new();
+    for member in &members {
+        let direct: BTreeSet<&PackageId> = member
+            .direct_links()
+            .filter(|link| link.normal().is_present())
+            .map(|link| link.to().id())
+            .collect();
+        per_member.push((member.name(), features_by_dependency(&resolve(vec![member.id()])?), direct));
+    }
+
+    let mut total_cost = 0;
+    let mut dependencies = Vec::new();
+    for ((id, platform), features) in &unified {
+        let package = graph.metadata(id).context("Dependency missing from the package graph")?;
+        let root = package.manifest_path().parent().map(|dir| dir.as_std_path()).unwrap_or(Path::new("."));
+        let cost = analysis::estimate_build_cost(
+            analysis::rust_source_bytes(root),
+            package.has_build_script(),
+            package.is_proc_macro(),
+        );
+        total_cost += cost;
+
+        let own: Vec<(&str, &BTreeSet<String>, bool)> = per_member
+            .iter()
+            .filter_map(|(member, built, direct)| {
+                built.get(&(id.clone(), *platform)).map(|features| (*member, features, direct.contains(id)))
+            })
+            .collect();
+        let builds: Vec<&BTreeSet<String>> = own.iter().map(|(_, features, _)| *features).collect();
+        let variants = variants(features, &builds);
+        if variants > 1 {
+            dependencies.push(UnifiedDependency {
+                name: package.name().to_string(),
+                version: package.version().to_string(),
+                source: dependency_source(package.source()),
+                host: *platform == BuildPlatform::Host,
+                features: features.iter().cloned().collect(),
+                variants,
+                members: own
+                    .iter()
+                    .filter(|(_, built, _)| *built != features)
+                    .map(|(member, _, _)| member.to_string())
+                    .collect(),
+                cost,
+                missing: own
+                    .iter()
+                    .map(|(member, built, direct)| MissingFeatures {
+                        member: member.to_string(),
+                        features: features.difference(built).cloned().collect(),
+                        direct: *direct,
+                    })
+                    .filter(|missing| !missing.features.is_empty())
+                    .collect(),
+                enabled_by: features
+                    .iter()
+                    .map(|feature| {
+                        let members = own
+                            .iter()
+                            .filter(|(_, built, _)| built.contains(feature))
+                            .map(|(member, _, _)| member.to_string())
+                            .collect();
+                        (feature.clone(), members)
+                    })
+                    .collect(),
+            });
+        }
+    }
+    dependencies.sort_by(|a, b| (b.cost * b.variants as u64).cmp(&(a.cost * a.variants as u64)).then(a.name.cmp(&b.name)));
+
+    let workspace_root = workspace.root().as_std_path().to_path_buf();
+    let existing_hack = members
+        .iter()
+        .map(|member| member.name())
+        .find(|name| name.contains("workspace-hack") || name.ends_with("-hakari"))
+        .map(str::to_string)
+        .or_else(|| workspace_root.join(".config/hakari.toml").is_file().then(|| "cargo-hakari".to_string()));
+    Ok(UnificationReport {
+        members: members
+            .iter()
+            .filter_map(|member| match member.source() {
+                PackageSource::Workspace(path) => Some(path.as_std_path().to_path_buf()),
+                _ => None,
+            })
+            .collect(),
+        workspace_root,
+        existing_hack,
+        dependencies,
+        total_cost,
+    })
+}
+
+/// Named features of every non-workspace package in a simulated build
+fn features_by_dependency(set: &CargoSet<'_>) -> BTreeMap<(PackageId, BuildPlatform), BTreeSet<String>> {
+    let mut features = BTreeMap::new();
+    for (platform, feature_set) in set.all_features() {
+        for list in feature_set.packages_with_features(guppy::graph::DependencyDirection::Forward) {
+            if list.package().in_workspace() {
+                continue;
+            }
+            features.insert(
+                (list.package().id().clone(), platform),
+                list.named_features().map(str::to_string).collect(),
+            );
+        }
+    }
+    features
+}
+
+/// Distinct feature sets among the workspace build and the member builds
+fn variants(unified: &BTreeSet<String>, member_builds: &[&BTreeSet<String>]) -> usize {
+    let mut distinct: Vec<&BTreeSet<String>> = vec![unified];
+    for features in member_builds {
+        if !distinct.contains(features) {
+            distinct.push(features);
+        }
+    }
+    distinct.len()
+}
+
+fn dependency_source(source: PackageSource<'_>) -> DependencySource {
+    match source {
+        PackageSource::Workspace(path) | PackageSource::Path(path) => DependencySource::Path(path.as_std_path().to_path_buf()),
+        PackageSource::External(source) if source == PackageSource::CRATES_IO_REGISTRY => DependencySource::CratesIo,
+        PackageSource::External(source) => match source.strip_prefix("git+") {
+            Some(git) => {
+                let (location, rev) = match git.split_once('#') {
+                    Some((location, rev)) => (location, Some(rev.to_string())),
+                    None => (git, None),
+                };
+                let url = location.split_once('?').map(|(url, _)| url).unwrap_or(location);
+                DependencySource::Git { url: url.to_string(), rev }
+            }
+            None => DependencySource::Other(source.to_string()),
+        },
+    }
+}
+
+/// `Cargo.toml` of a workspace-hack crate enabling the workspace build's features
+///
+/// Only one version of each crate can be listed; dependencies from other
+/// registries are left out.
+pub fn render_manifest(name: &str, dependencies: &[UnifiedDependency]) -> String {
+    let mut doc = DocumentMut::new();
+    let mut package = Table::new();
+    package.decor_mut().set_prefix(
+        "# Generated by cargo-optimize: every member depends on this crate so that all\n\
+         # builds enable the same dependency features. Regenerate it when dependencies change.\n",
+    );
+    package.insert("name", toml_edit::value(name));
+    package.insert("version", toml_edit::value("0.1.0"));
+    package.insert("edition", toml_edit::value("2021"));
+    package.insert("publish", toml_edit::value(false));
+    doc.insert("package", Item::Table(package));
+
+    for (section, host) in [("dependencies", false), ("build-dependencies", true)] {
+        let mut table = Table::new();
+        for dependency in dependencies.iter().filter(|dependency| dependency.host == host) {
+            if table.contains_key(&dependency.name) {
+                debug!("{} is listed in another version already", dependency.name);
+                continue;
+            }
+            let mut entry = InlineTable::new();
+            match &dependency.source {
+                DependencySource::CratesIo => {
+                    entry.insert("version", dependency.version.as_str().into());
+                }
+                DependencySource::Path(path) => {
+                    entry.insert("path", Path::new("..").join(path).to_string_lossy().as_ref().into());
+                }
+                DependencySource::Git { url, rev } => {
+                    entry.insert("git", url.as_str().into());
+                    if let Some(rev) = rev {
+                        entry.insert("rev", rev.as_str().into());
+                    }
+                }
+                DependencySource::Other(source) => {
+                    debug!("Leaving {} from {} out of the workspace-hack crate", dependency.name, source);
+                    continue;
+                }
+            }
+            if !dependency.features.iter().any(|feature| feature == "default") {
+                entry.insert("default-features", false.into());
+            }
+            let features: Array = dependency.features.iter().filter(|feature| *feature != "default").collect();
+            if !features.is_empty() {
+                entry.insert("features", Value::Array(features));
+            }
+            table.insert(&dependency.name, toml_edit::value(entry));
+        }
+        if !table.is_empty() {
+            doc.insert(section, Item::Table(table));
+        }
+    }
+    doc.to_string()
+}
+
+/// Write a minimal workspace-hack crate and make every member depend on it
+///
+/// Edits the workspace manifest and every member's `Cargo.toml`, so only
+/// call this once the user agreed. All manifests are rendered before the
+/// first one is written; returns the files written.
+pub fn generate(report: &UnificationReport, name: &str) -> Result<Vec<PathBuf>> {
+    output::ensure_enabled()?;
+    if let Some(existing) = &report.existing_hack {
+        bail!("the workspace already unifies features with {}", existing);
+    }
+    if report.dependencies.is_empty() {
+        bail!("every member already builds its dependencies with the same features");
+    }
+    let crate_dir = report.workspace_root.join(name);
+    if crate_dir.exists() {
+        bail!("{} already exists", crate_dir.display());
+    }
+
+    let mut files = vec![
+        (crate_dir.join("Cargo.toml"), render_manifest(name, &report.dependencies)),
+        (
+            crate_dir.join("src").join("lib.rs"),
+            "//! Feature unification only; see Cargo.toml.\n".to_string(),
+        ),
+    ];
+
+    let root_manifest = report.workspace_root.join("Cargo.toml");
+    let mut root = read_manifest(&root_manifest)?;
+    let members = root
+        .get_mut("workspace")
+        .and_then(Item::as_table_like_mut)
+        .context("the root Cargo.toml has no [workspace] table")?
+        .entry("members")
+        .or_insert(toml_edit::value(Array::new()))
+        .as_array_mut()
+        .context("workspace.members is not an array")?;
+    if !members.iter().any(|member| member.as_str() == Some(name)) {
+        members.push(name);
+    }
+
+    let mut member_manifests = BTreeMap::new();
+    member_manifests.insert(root_manifest.clone(), root);
+    for member in &report.members {
+        let manifest = report.workspace_root.join(member).join("Cargo.toml");
+        let mut doc = match member_manifests.remove(&manifest) {
+            Some(doc) => doc,
+            None => read_manifest(&manifest)?,
+        };
+        let path = member.components().fold(PathBuf::new(), |path, _| path.join("..")).join(name);
+        let mut entry = InlineTable::new();
+        entry.insert("path", crate::paths::to_slash(&path).as_str().into());
+        doc.entry("dependencies")
+            .or_insert(Item::Table(Table::new()))
+            .as_table_like_mut()
+            .with_context(|| format!("[dependencies] of {} is not a table", manifest.display()))?
+            .insert(name, toml_edit::value(entry));
+        member_manifests.insert(manifest, doc);
+    }
+    files.extend(member_manifests.into_iter().map(|(path, doc)| (path, doc.to_string())));
+
+    for (path, content) in &files {
+        let before = fs::read_to_string(path).ok();
+        atomic_write(path, content)?;
+        audit::record(&report.workspace_root, "workspace-hack", path, before.as_deref(), Some(content));
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+fn read_manifest(path: &Path) -> Result<DocumentMut> {
+    fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .parse()
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_variants_and_benefit() {
+        let unified = features(&["default", "std", "derive"]);
+        let alone = features(&["default", "std"]);
+        assert_eq!(variants(&unified, &[&unified, &unified]), 1);
+        assert_eq!(variants(&unified, &[&alone, &alone, &unified]), 2);
+
+        let dependency = |cost, variants| UnifiedDependency {
+            name: "serde".to_string(),
+            version: "1.0.200".to_string(),
+            source: DependencySource::CratesIo,
+            host: false,
+            features: vec!["default".to_string(), "derive".to_string()],
+            variants,
+            members: vec!["cli".to_string()],
+            cost,
+            missing: Vec::new(),
+            enabled_by: BTreeMap::new(),
+        };
+        let mut report = UnificationReport {
+            workspace_root: PathBuf::from("/ws"),
+            members: vec![PathBuf::from("api"), PathBuf::from("cli")],
+            existing_hack: None,
+            dependencies: vec![dependency(30, 2), dependency(10, 3)],
+            total_cost: 400,
+        };
+        assert_eq!(report.extra_builds(), 3);
+        assert_eq!(report.extra_cost(), 50);
+        assert_eq!(report.benefit_percent(), 12);
+        assert!(report.recommends_hack());
+        report.existing_hack = Some("workspace-hack".to_string());
+        assert!(!report.recommends_hack());
+    }
+
+    #[test]
+    fn test_render_manifest() {
+        let dependency = |name: &str, source, host, features: &[&str]| UnifiedDependency {
+            name: name.to_string(),
+            version: "1.2.3".to_string(),
+            source,
+            host,
+            features: features.iter().map(|feature| feature.to_string()).collect(),
+            variants: 2,
+            members: Vec::new(),
+            cost: 1,
+            missing: Vec::new(),
+            enabled_by: BTreeMap::new(),
+        };
+        let manifest = render_manifest(
+            "workspace-hack",
+            &[
+                dependency("serde", DependencySource::CratesIo, false, &["default", "derive", "std"]),
+                dependency("shared", DependencySource::Path(PathBuf::from("vendor/shared")), false, &["fast"]),
+                dependency("internal", DependencySource::Other("registry+https://example.com".into()), false, &[]),
+                dependency("cc", DependencySource::CratesIo, true, &["parallel"]),
+            ],
+        );
+        let doc: DocumentMut = manifest.parse().unwrap();
+        assert_eq!(doc["package"]["publish"].as_bool(), Some(false));
+        assert_eq!(doc["dependencies"]["serde"]["version"].as_str(), Some("1.2.3"));
+        assert!(doc["dependencies"]["serde"].get("default-features").is_none());
+        assert_eq!(doc["dependencies"]["serde"]["features"].as_array().unwrap().len(), 2);
+        assert_eq!(doc["dependencies"]["shared"]["path"].as_str(), Some("../vendor/shared"));
+        assert_eq!(doc["dependencies"]["shared"]["default-features"].as_bool(), Some(false));
+        assert!(doc["dependencies"].get("internal").is_none());
+        assert!(doc["build-dependencies"]["cc"].is_inline_table());
+    }
+}
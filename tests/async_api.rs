@@ -0,0 +1,51 @@
+//! Tests for the non-blocking API (feature `async`).
+//!
+//! Run with `cargo test --features async --test async_api`.
+
+#![cfg(feature = "async")]
+
+use cargo_optimize::analysis::ProjectAnalysis;
+use cargo_optimize::Optimizer;
+use std::fs;
+use tempfile::TempDir;
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to build runtime")
+}
+
+#[test]
+fn test_apply_async_writes_config() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let optimizer = Optimizer::new(temp_dir.path());
+    
+    runtime()
+        .block_on(optimizer.apply_async())
+        .expect("Failed to apply");
+    
+    let content = fs::read_to_string(temp_dir.path().join(".cargo").join("config.toml"))
+        .expect("Config not written");
+    assert!(content.parse::<toml_edit::DocumentMut>().is_ok());
+}
+
+#[test]
+fn test_load_async_analyzes_project() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("Cargo.toml"),
+        "[package]\nname = \"async-probe\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    fs::create_dir(temp_dir.path().join("src")).unwrap();
+    fs::write(temp_dir.path().join("src").join("lib.rs"), "").unwrap();
+    
+    let analysis = runtime()
+        .block_on(ProjectAnalysis::load_async(Some(temp_dir.path().join("Cargo.toml"))))
+        .expect("Failed to analyze");
+    assert_eq!(analysis.workspace_members[0].name, "async-probe");
+    
+    // Errors come back as errors, not panics
+    let missing = runtime().block_on(ProjectAnalysis::load_async(Some(temp_dir.path().join("missing.toml"))));
+    assert!(missing.is_err());
+}
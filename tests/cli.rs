@@ -0,0 +1,40 @@
+//! Exit codes of the `cargo-optimize` binary
+
+use cargo_optimize::exit::ExitStatus;
+use cargo_optimize::state::{LinkerIncompatibility, State};
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn cargo_optimize(dir: &Path, args: &[&str]) -> Option<ExitStatus> {
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-optimize"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("CARGO_OPTIMIZE_DISABLE")
+        .output()
+        .expect("Failed to run cargo-optimize");
+    ExitStatus::from_code(output.status.code()? as u8)
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_apply_exits_rolled_back_after_a_linker_rollback() {
+    let temp = TempDir::new().unwrap();
+    let state_path = State::path_in(&temp.path().join(".cargo"));
+    State::update(&state_path, |state| {
+        state.incompatible_linkers.push(LinkerIncompatibility {
+            linker: "mold".to_string(),
+            reason: "mold: fatal: unknown option".to_string(),
+            recorded_at: 0,
+        })
+    })
+    .unwrap();
+
+    assert_eq!(cargo_optimize(temp.path(), &["apply"]), Some(ExitStatus::RolledBack));
+}
+
+#[test]
+fn test_unknown_command_is_a_usage_error() {
+    let temp = TempDir::new().unwrap();
+    assert_eq!(cargo_optimize(temp.path(), &["frobnicate"]), Some(ExitStatus::Usage));
+}
@@ -92,15 +92,14 @@ pub mod integration_test_runner {
     
     /// Helper function to run all integration tests and collect results
     pub fn run_integration_test_suite() -> IntegrationTestResults {
-        let mut results = IntegrationTestResults::default();
-        
         // Run test modules and collect results
-        results.total_tests = 10; // Update based on actual test count
-        results.passed_tests = 10; // Will be updated based on actual results
-        results.failed_tests = 0;
-        results.execution_time = Duration::from_secs(5); // Placeholder
-        
-        results
+        IntegrationTestResults {
+            total_tests: 10, // Update based on actual test count
+            passed_tests: 10, // Will be updated based on actual results
+            failed_tests: 0,
+            execution_time: Duration::from_secs(5), // Placeholder
+            ..Default::default()
+        }
     }
 }
 
@@ -294,7 +294,7 @@ under normal and stress conditions.
         );
         
         let summary_path = "issue/mvp/003/foundation/foundation_summary_report.md";
-        if let Ok(_) = fs::write(summary_path, summary_content) {
+        if fs::write(summary_path, summary_content).is_ok() {
             println!("✅ Foundation summary report generated: {}", summary_path);
         }
     }
@@ -410,7 +410,7 @@ All foundation tests have been completed successfully:
         );
         
         let handoff_path = "issue/mvp/003/foundation/foundation_to_qa_handoff.md";
-        if let Ok(_) = fs::write(handoff_path, handoff_content) {
+        if fs::write(handoff_path, handoff_content).is_ok() {
             println!("✅ Handoff package generated: {}", handoff_path);
         }
     }
@@ -422,10 +422,10 @@ All foundation tests have been completed successfully:
         }
         
         issues.iter().map(|issue| {
-            format!("  - severity: \"{}\"\n    description: \"{}\"\n    ticket_id: \"{}\"", 
+            format!("  - severity: \"{}\"\n    description: \"{}\"\n    ticket_id: \"{}_{}\"", 
                     issue.severity, 
                     issue.description.replace("\"", "\\\""),
-                    format!("{}_{}", issue.test_module, issue.severity))
+                    issue.test_module, issue.severity)
         }).collect::<Vec<_>>().join("\n")
     }
     
@@ -442,8 +442,8 @@ All foundation tests have been completed successfully:
             (IssueSeverity::Low, Self::count_issues_by_severity(issues, IssueSeverity::Low)),
         ] {
             if count > 0 {
-                result.push_str(&format!("- **{}**: {} issues\n", 
-                    format!("{:?}", severity), count));
+                result.push_str(&format!("- **{:?}**: {} issues\n", 
+                    severity, count));
             }
         }
         
@@ -0,0 +1,212 @@
+//! Stress tests for concurrent access to the state and metrics files
+//!
+//! Dozens of writers, threads and processes, update the same file while
+//! readers poll it. Every update must survive, and every read must see a
+//! complete snapshot whose content matches its revision.
+
+use cargo_optimize::metrics::{BuildRecord, MetricsHistory};
+use cargo_optimize::state::{LinkerIncompatibility, State};
+use cargo_optimize::store;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use tempfile::TempDir;
+
+/// Writer threads per test
+const THREADS: usize = 32;
+
+/// Writer processes of the multi-process test
+const PROCESSES: usize = 8;
+
+/// Updates made by each writer
+const UPDATES: usize = 4;
+
+/// Reader threads polling while the writers run
+const READERS: usize = 4;
+
+const CHILD_FILE_VAR: &str = "CARGO_OPTIMIZE_STRESS_FILE";
+const CHILD_WRITER_VAR: &str = "CARGO_OPTIMIZE_STRESS_WRITER";
+
+/// One update of a writer: a linker entry unique to the writer and update
+fn add_entry(path: &Path, writer: &str, update: usize) {
+    State::update(path, |state| {
+        state.incompatible_linkers.push(LinkerIncompatibility {
+            linker: format!("{}-{}", writer, update),
+            reason: "stress test".to_string(),
+            recorded_at: 0,
+        })
+    })
+    .expect("update failed");
+}
+
+/// Poll `path` until `done`, checking every snapshot read
+///
+/// Each update adds one entry, so a complete snapshot has as many entries
+/// as its revision, and revisions never go back.
+fn poll(path: &Path, done: &AtomicBool) -> usize {
+    let mut last = 0;
+    let mut reads = 0;
+    while !done.load(Ordering::Acquire) {
+        let snapshot = store::read::<State>(path);
+        assert_eq!(snapshot.value.incompatible_linkers.len() as u64, snapshot.revision);
+        assert!(snapshot.revision >= last, "revision went back from {} to {}", last, snapshot.revision);
+        last = snapshot.revision;
+        reads += 1;
+    }
+    reads
+}
+
+/// No corrupt files were quarantined, and only the lock and previous snapshot sit next to the file
+fn assert_clean(dir: &Path) {
+    let mut names: Vec<String> =
+        std::fs::read_dir(dir).unwrap().filter_map(|entry| entry.ok()?.file_name().into_string().ok()).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        ["cargo-optimize-state.json", "cargo-optimize-state.json.lock", "cargo-optimize-state.json.prev"]
+    );
+}
+
+#[test]
+fn test_concurrent_state_writers_lose_nothing() {
+    let temp = TempDir::new().unwrap();
+    let path = State::path_in(temp.path());
+    let done = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let readers: Vec<_> = (0..READERS).map(|_| scope.spawn(|| poll(&path, &done))).collect();
+        let writers: Vec<_> = (0..THREADS)
+            .map(|writer| {
+                let path = &path;
+                scope.spawn(move || {
+                    (0..UPDATES).for_each(|update| add_entry(path, &format!("thread{}", writer), update))
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        done.store(true, Ordering::Release);
+        for reader in readers {
+            assert!(reader.join().unwrap() > 0);
+        }
+    });
+
+    let snapshot = store::read::<State>(&path);
+    assert_eq!(snapshot.revision, (THREADS * UPDATES) as u64);
+    let mut linkers: Vec<&str> =
+        snapshot.value.incompatible_linkers.iter().map(|entry| entry.linker.as_str()).collect();
+    linkers.sort_unstable();
+    linkers.dedup();
+    assert_eq!(linkers.len(), THREADS * UPDATES);
+    assert_clean(temp.path());
+}
+
+/// Writer process of [`test_concurrent_state_writer_processes`]; does nothing when run on its own
+#[test]
+fn child_writer() {
+    let (Some(file), Some(writer)) = (std::env::var_os(CHILD_FILE_VAR), std::env::var(CHILD_WRITER_VAR).ok()) else {
+        return;
+    };
+    let path = PathBuf::from(file);
+    for update in 0..UPDATES {
+        add_entry(&path, &writer, update);
+    }
+}
+
+#[test]
+fn test_concurrent_state_writer_processes() {
+    let temp = TempDir::new().unwrap();
+    let path = State::path_in(temp.path());
+    let done = AtomicBool::new(false);
+    let exe = std::env::current_exe().unwrap();
+
+    thread::scope(|scope| {
+        let reader = scope.spawn(|| poll(&path, &done));
+        let children: Vec<_> = (0..PROCESSES)
+            .map(|writer| {
+                Command::new(&exe)
+                    .args(["--exact", "child_writer", "--test-threads=1", "--quiet"])
+                    .env(CHILD_FILE_VAR, &path)
+                    .env(CHILD_WRITER_VAR, format!("process{}", writer))
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .expect("failed to start writer process")
+            })
+            .collect();
+        // Threads of this process write at the same time
+        let threads: Vec<_> = (0..THREADS)
+            .map(|writer| {
+                let path = &path;
+                scope.spawn(move || {
+                    (0..UPDATES).for_each(|update| add_entry(path, &format!("thread{}", writer), update))
+                })
+            })
+            .collect();
+        for mut child in children {
+            assert!(child.wait().unwrap().success(), "writer process failed");
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        done.store(true, Ordering::Release);
+        reader.join().unwrap();
+    });
+
+    let state = State::load(&path);
+    assert_eq!(state.incompatible_linkers.len(), (PROCESSES + THREADS) * UPDATES);
+    assert_eq!(store::read::<State>(&path).revision, ((PROCESSES + THREADS) * UPDATES) as u64);
+    assert_clean(temp.path());
+}
+
+#[test]
+fn test_concurrent_metrics_writers() {
+    let temp = TempDir::new().unwrap();
+    let path = MetricsHistory::path_in(temp.path());
+
+    thread::scope(|scope| {
+        for writer in 0..THREADS {
+            let path = &path;
+            scope.spawn(move || {
+                for update in 0..2 {
+                    let record = BuildRecord::new((writer * 2 + update) as f64, "dev");
+                    MetricsHistory::update(path, |history| history.record(record)).expect("update failed");
+                }
+            });
+        }
+    });
+
+    let history = MetricsHistory::load(&path);
+    assert_eq!(history.records.len(), THREADS * 2);
+    let mut durations: Vec<u64> = history.records.iter().map(|record| record.duration_secs as u64).collect();
+    durations.sort_unstable();
+    assert_eq!(durations, (0..(THREADS * 2) as u64).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_corrupt_state_is_quarantined() {
+    let temp = TempDir::new().unwrap();
+    let path = State::path_in(temp.path());
+    add_entry(&path, "first", 0);
+    add_entry(&path, "second", 0);
+
+    // A truncated write, e.g. from a filesystem without atomic renames
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, &content[..content.len() / 2]).unwrap();
+
+    // The previous snapshot is recovered and the broken file kept for inspection
+    let state = State::load(&path);
+    assert_eq!(state.incompatible_linkers.len(), 1);
+    assert_eq!(state.incompatible_linkers[0].linker, "first-0");
+    let quarantined = std::fs::read_dir(temp.path())
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with("cargo-optimize-state.json.corrupt-"))
+        .count();
+    assert_eq!(quarantined, 1);
+
+    // Writing again continues from the recovered snapshot
+    add_entry(&path, "third", 0);
+    assert_eq!(store::read::<State>(&path).value.incompatible_linkers.len(), 2);
+}
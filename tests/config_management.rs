@@ -11,6 +11,7 @@
 //! when tests run in parallel. Tests no longer change the current directory.
 
 use cargo_optimize::config::*;
+use cargo_optimize::purge::ArtifactKind;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tempfile::TempDir;
@@ -84,6 +85,30 @@ fn test_percentage_parsing() {
     assert!(JobCount::parse("invalid").is_err());
 }
 
+#[test]
+fn test_resource_expressions() {
+    let job = JobCount::parse("min(8, 75%)").unwrap();
+    assert_eq!(job, JobCount::Expression("min(8, 75%)".to_string()));
+    let cores = num_cpus::get();
+    assert_eq!(job.to_count(), ((cores as f64 * 0.75).round() as usize).clamp(1, 8));
+    
+    let job = JobCount::parse("50% physical").unwrap();
+    assert_eq!(job.to_count(), ((num_cpus::get_physical() as f64 * 0.5).round() as usize).max(1));
+    assert_eq!(JobCount::parse("logical-1").unwrap().to_count(), cores.saturating_sub(1).max(1));
+    
+    let error = format!("{:#}", JobCount::parse("min(8, 75").unwrap_err());
+    assert!(error.contains("expected `,` or `)`"), "{}", error);
+    
+    // Invalid expressions are rejected when loading the config
+    let parsed = toml::from_str::<std::collections::HashMap<String, JobCount>>("jobs = \"all-but-two\"");
+    assert!(parsed.unwrap_err().to_string().contains("unknown name `all`"));
+    
+    assert_eq!(CacheSize::parse("2048").unwrap(), CacheSize::Megabytes(2048));
+    let size = CacheSize::parse("min(4096, 10%)").unwrap();
+    assert!((100..=4096).contains(&size.to_megabytes()));
+    assert!(CacheSize::parse("10% physical").is_err());
+}
+
 #[test]
 fn test_cache_size_percentage() {
     // Test percentage cache size
@@ -264,6 +289,32 @@ fn test_backup_restore() {
     assert_eq!(restored_content, original_content);
 }
 
+#[test]
+fn test_backup_metadata_and_integrity() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[build]\njobs = 2\n").expect("Failed to write config");
+    
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_BACKUP_METADATA_");
+    let backup_path = manager.create_backup().expect("Failed to create backup");
+    
+    let backups = manager.list_backups().expect("Failed to list backups");
+    assert_eq!(backups.len(), 1);
+    assert_eq!(backups[0].path, backup_path);
+    let metadata = backups[0].metadata.as_ref().expect("backup should have metadata");
+    assert_eq!(metadata.origin, config_path);
+    assert_eq!(metadata.reason, "manual backup");
+    assert_eq!(metadata.tool_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(metadata.sha256.len(), 64);
+    assert!(backups[0].verify().is_ok());
+    
+    // A tampered backup is refused
+    fs::write(&backup_path, "[build]\njobs = 64\n").unwrap();
+    fs::write(&config_path, "[build]\njobs = 8\n").unwrap();
+    assert!(manager.restore_from_backup(&backup_path).is_err());
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), "[build]\njobs = 8\n");
+}
+
 #[test]
 fn test_backup_cleanup() {
     let temp_dir = setup_test_env();
@@ -316,10 +367,10 @@ fn test_hardware_detection() {
     
     // Check that hardware optimization was applied
     // After hardware detection, profiles should have job counts set
-    for (_name, profile) in &config.profiles {
-        if profile.jobs.is_some() {
+    for profile in config.profiles.values() {
+        if let Some(jobs) = &profile.jobs {
             // If jobs is set, it should be a reasonable value
-            let job_count = profile.jobs.as_ref().unwrap().to_count();
+            let job_count = jobs.to_count();
             assert!(job_count > 0 && job_count <= num_cpus::get() * 2);
         }
     }
@@ -381,12 +432,8 @@ fn test_linker_configuration() {
     // Check for target configuration (if linker was detected)
     // This depends on the system having a compatible linker
     if doc.get("target").is_some() {
-        let target = if cfg!(target_os = "windows") {
-            "x86_64-pc-windows-msvc"
-        } else {
-            "x86_64-unknown-linux-gnu"
-        };
-        
+        let platform = cargo_optimize::linkers::Platform::current().expect("a linker was written for this platform");
+        let target = platform.target_triple().unwrap();
         assert!(doc["target"].get(target).is_some());
     }
 }
@@ -551,3 +598,2503 @@ max_backups = 3
     // Clean up
     std::env::remove_var(format!("{}GLOBAL__VERBOSE", env_prefix));
 }
+
+#[test]
+fn test_low_disk_skips_disk_hungry_settings() {
+    use cargo_optimize::hardware::{get_fallback_hardware, DiskInfo, HardwareInfo};
+    use cargo_optimize::report::DecisionOutcome;
+    
+    let temp_dir = setup_test_env();
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_LOW_DISK_");
+    
+    // Pretend the project disk has only 1 GB free
+    let hardware = HardwareInfo {
+        disks: vec![DiskInfo {
+            mount_point: if cfg!(windows) { "C:\\".to_string() } else { "/".to_string() },
+            total_space: 100 * 1024 * 1024 * 1024,
+            available_space: 1024 * 1024 * 1024,
+            file_system: "ext4".to_string(),
+            is_ssd: None,
+        }],
+        ..get_fallback_hardware()
+    };
+    
+    // Give the backups directory some content so it shows up as a cleanup candidate
+    fs::write(temp_dir.path().join(".cargo").join("backups").join("config_backup_1.toml"), "# old").unwrap();
+    
+    let report = manager.apply_with_hardware(&hardware).expect("apply should succeed");
+    
+    let sccache = report.decision_for("sccache").expect("sccache decision recorded");
+    assert_eq!(sccache.outcome, DecisionOutcome::Skipped);
+    assert!(sccache.reason.contains("low disk space"));
+    
+    let dev = report.decision_for("profile.dev.incremental").expect("incremental decision recorded");
+    assert_eq!(dev.outcome, DecisionOutcome::Skipped);
+    
+    assert!(report.cleanup_candidates.iter().any(|c| c.label == "cargo-optimize backups"));
+    assert!(!report.recommendations.is_empty());
+    
+    // Incremental must be written as disabled for the dev profile
+    let content = fs::read_to_string(temp_dir.path().join(".cargo").join("config.toml")).unwrap();
+    let doc = content.parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["profile"]["dev"]["incremental"].as_bool(), Some(false));
+}
+
+#[test]
+fn test_enough_disk_keeps_settings() {
+    use cargo_optimize::hardware::{get_fallback_hardware, DiskInfo, HardwareInfo};
+    
+    let temp_dir = setup_test_env();
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_ENOUGH_DISK_");
+    
+    let hardware = HardwareInfo {
+        disks: vec![DiskInfo {
+            mount_point: if cfg!(windows) { "C:\\".to_string() } else { "/".to_string() },
+            total_space: 100 * 1024 * 1024 * 1024,
+            available_space: 50 * 1024 * 1024 * 1024,
+            file_system: "ext4".to_string(),
+            is_ssd: None,
+        }],
+        ..get_fallback_hardware()
+    };
+    
+    let report = manager.apply_with_hardware(&hardware).expect("apply should succeed");
+    assert!(report.decision_for("sccache").is_none());
+    assert!(report.cleanup_candidates.is_empty());
+    
+    let content = fs::read_to_string(temp_dir.path().join(".cargo").join("config.toml")).unwrap();
+    let doc = content.parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["profile"]["dev"]["incremental"].as_bool(), Some(true));
+}
+
+#[test]
+fn test_build_std_written_on_nightly_and_removed_on_stable() {
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    fs::write(temp_dir.path().join("custom-target.json"), "{}").unwrap();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    
+    let nightly = ToolchainInfo::parse_verbose_version("host: x86_64-unknown-linux-gnu\nrelease: 1.80.0-nightly\n").unwrap();
+    let stable = ToolchainInfo::parse_verbose_version("host: x86_64-unknown-linux-gnu\nrelease: 1.80.0\n").unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_BUILD_STD_").with_toolchain(nightly);
+    manager.config_mut().global.build_std = Some(BuildStdSettings {
+        crates: vec!["core".to_string(), "alloc".to_string()],
+        features: vec!["panic_immediate_abort".to_string()],
+        target: Some("custom-target.json".to_string()),
+    });
+    let report = manager.apply_with_report().unwrap();
+    assert!(report.decision_for("unstable.build-std").is_some());
+    
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    let build_std = doc["unstable"]["build-std"].as_array().unwrap();
+    assert_eq!(build_std.len(), 2);
+    assert_eq!(doc["build"]["target"].as_str(), Some("custom-target.json"));
+    
+    // Switching back to stable removes the managed keys
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_BUILD_STD_STABLE_").with_toolchain(stable);
+    manager.config_mut().global.build_std = Some(BuildStdSettings {
+        crates: vec!["core".to_string()],
+        features: vec![],
+        target: Some("custom-target.json".to_string()),
+    });
+    let report = manager.apply_with_report().unwrap();
+    let decision = report.decision_for("unstable.build-std").unwrap();
+    assert!(decision.reason.contains("removed"));
+    
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert!(!doc.contains_key("unstable"));
+    assert!(doc["build"].get("target").is_none());
+}
+
+#[test]
+fn test_build_std_target_validation() {
+    let temp_dir = setup_test_env();
+    let known = vec!["thumbv7em-none-eabihf".to_string()];
+    
+    assert!(validate_build_std_target("thumbv7em-none-eabihf", temp_dir.path(), &known).is_ok());
+    assert!(validate_build_std_target("not-a-target", temp_dir.path(), &known).is_err());
+    assert!(validate_build_std_target("missing.json", temp_dir.path(), &known).is_err());
+    
+    // Unstable keys the user wrote themselves are never touched
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[unstable]\nbuild-std = [\"std\"]\n").unwrap();
+    let stable = cargo_optimize::toolchain::ToolchainInfo::parse_verbose_version("release: 1.80.0\n").unwrap();
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_BUILD_STD_USER_").with_toolchain(stable);
+    manager.config_mut().global.build_std = None;
+    manager.apply().unwrap();
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["unstable"]["build-std"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_profile_rustflags_gated_by_toolchain() {
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let nightly = ToolchainInfo::parse_verbose_version("release: 1.80.0-nightly\n").unwrap();
+    let stable = ToolchainInfo::parse_verbose_version("release: 1.80.0\n").unwrap();
+    let native = vec!["-C".to_string(), "target-cpu=native".to_string()];
+    
+    // Nightly: per-profile rustflags with the unstable feature enabled
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PROFILE_RUSTFLAGS_").with_toolchain(nightly);
+    for profile in manager.config_mut().profiles.values_mut() {
+        profile.rustflags.clear();
+    }
+    let release = manager.config_mut().profiles.get_mut("release").unwrap();
+    release.rustflags = vec!["-C".to_string(), "lto=true".to_string()];
+    release.rustflags.extend(native.clone());
+    let report = manager.apply_with_report().unwrap();
+    assert!(report.decision_for("profile.release.rustflags").is_some());
+    
+    let content = fs::read_to_string(&config_path).unwrap();
+    assert!(content.contains("CO-RUSTFLAGS-001"));
+    let doc = content.parse::<DocumentMut>().unwrap();
+    let flags: Vec<&str> = doc["profile"]["release"]["rustflags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect();
+    // `-C lto` is left to the profile's own `lto` key
+    assert_eq!(flags, ["-C", "target-cpu=native"]);
+    assert_eq!(doc["unstable"]["profile-rustflags"].as_bool(), Some(true));
+    
+    // Stable: profile-specific flags are skipped and the managed keys removed
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PROFILE_RUSTFLAGS_STABLE_").with_toolchain(stable.clone());
+    for profile in manager.config_mut().profiles.values_mut() {
+        profile.rustflags.clear();
+    }
+    manager.config_mut().profiles.get_mut("release").unwrap().rustflags = native.clone();
+    let report = manager.apply_with_report().unwrap();
+    let decision = report.decision_for("profile.release.rustflags").unwrap();
+    assert!(decision.reason.contains("nightly"));
+    
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert!(doc["profile"]["release"].get("rustflags").is_none());
+    assert!(!doc.contains_key("unstable"));
+    
+    // Stable: flags shared by every profile fall back to the target's rustflags
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PROFILE_RUSTFLAGS_SHARED_").with_toolchain(stable);
+    for profile in manager.config_mut().profiles.values_mut() {
+        profile.rustflags = native.clone();
+    }
+    let report = manager.apply_with_report().unwrap();
+    let content = fs::read_to_string(&config_path).unwrap();
+    if let Some(platform) = cargo_optimize::linkers::Platform::current() {
+        let decision = report.decision_for(&format!("target.{}.rustflags", platform.target_triple().unwrap())).unwrap();
+        assert!(decision.reason.contains("shared by every profile"));
+        assert!(content.contains("target-cpu=native"));
+    }
+}
+
+#[test]
+fn test_shared_rustflags_are_taken_back() {
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::report::SkipReason;
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let triple = Platform::Linux.target_triple().unwrap();
+    fs::write(&config_path, format!("[target.{}]\nrustflags = [\"-C\", \"force-frame-pointers=yes\"] # mine\n", triple)).unwrap();
+    let target_flags = || {
+        let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+        let flags = doc["target"][triple]["rustflags"].clone();
+        let values: Vec<String> =
+            flags.as_array().unwrap().iter().filter_map(|v| v.as_str()).map(String::from).collect();
+        (values, flags.as_value().unwrap().decor().suffix().and_then(|s| s.as_str()).unwrap_or_default().to_string())
+    };
+    let apply = |prefix: &str, release: &str, flags: &[&str]| {
+        let toolchain = ToolchainInfo::parse_verbose_version(&format!("release: {}\n", release)).unwrap();
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_platform(Some(Platform::Linux))
+            .with_toolchain(toolchain)
+            .with_installed_commands(vec!["rustc".to_string()]);
+        for profile in manager.config_mut().profiles.values_mut() {
+            profile.rustflags = flags.iter().map(|flag| flag.to_string()).collect();
+        }
+        manager.apply_with_report().unwrap()
+    };
+    
+    // Stable: the shared flags are appended, -Z is left out and the user's comment stays
+    let report = apply("TEST_SHARED_RUSTFLAGS_STABLE_", "1.80.0", &["-C", "target-cpu=native", "-Z", "share-generics"]);
+    let setting = format!("target.{}.rustflags", triple);
+    let skipped: Vec<_> = report.skipped_because(SkipReason::UnsupportedToolchain).filter(|d| d.setting == setting).collect();
+    assert_eq!(skipped.len(), 1);
+    assert!(skipped[0].reason.contains("-Z share-generics"));
+    let (flags, comment) = target_flags();
+    assert_eq!(flags, ["-C", "force-frame-pointers=yes", "-C", "target-cpu=native"]);
+    assert!(comment.contains("mine") && !comment.contains("CO-RUSTFLAGS-002"));
+    
+    // Applying again does not add them twice
+    apply("TEST_SHARED_RUSTFLAGS_AGAIN_", "1.80.0", &["-C", "target-cpu=native"]);
+    assert_eq!(target_flags().0, ["-C", "force-frame-pointers=yes", "-C", "target-cpu=native"]);
+    
+    // No flags: only what cargo-optimize added is taken out
+    apply("TEST_SHARED_RUSTFLAGS_CLEARED_", "1.80.0", &[]);
+    assert_eq!(target_flags().0, ["-C", "force-frame-pointers=yes"]);
+    
+    // Nightly writes them per profile, not in the target's rustflags as well
+    apply("TEST_SHARED_RUSTFLAGS_STABLE_AGAIN_", "1.80.0", &["-C", "target-cpu=native"]);
+    apply("TEST_SHARED_RUSTFLAGS_NIGHTLY_", "1.80.0-nightly", &["-C", "target-cpu=native"]);
+    assert_eq!(target_flags().0, ["-C", "force-frame-pointers=yes"]);
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert!(doc["profile"]["release"]["rustflags"].as_array().is_some());
+    
+    // An array cargo-optimize creates is marked as managed, and goes with its flags
+    fs::write(&config_path, "").unwrap();
+    apply("TEST_SHARED_RUSTFLAGS_FRESH_", "1.80.0", &["-C", "target-cpu=native"]);
+    let (flags, comment) = target_flags();
+    assert_eq!(flags, ["-C", "target-cpu=native"]);
+    assert!(comment.contains("CO-RUSTFLAGS-002"));
+    apply("TEST_SHARED_RUSTFLAGS_GONE_", "1.80.0", &[]);
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert!(doc.get("target").and_then(|targets| targets.get(triple)).is_none());
+}
+
+#[test]
+fn test_machine_policy_compliance() {
+    use cargo_optimize::policy::Policy;
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[build]\nrustc-wrapper = \"ccache\"\nrustflags = [\"-C\", \"target-cpu=native\"]\n").unwrap();
+    
+    let policy_path = temp_dir.path().join("policy.toml");
+    fs::write(
+        &policy_path,
+        "sccache_endpoint = \"https://sccache.example.com\"\nsccache_bucket = \"builds\"\nforbidden_flags = [\"-C target-cpu\"]\n",
+    )
+    .unwrap();
+    let policy = Policy::load(&policy_path).unwrap();
+    assert_eq!(policy.source, policy_path);
+    
+    let nightly = ToolchainInfo::parse_verbose_version("release: 1.80.0-nightly\n").unwrap();
+    
+    // Without sccache the wrapper would break every build
+    let report = create_test_manager_with_prefix(temp_dir.path(), "TEST_POLICY_MISSING_")
+        .with_toolchain(nightly.clone())
+        .with_installed_commands(Vec::new())
+        .with_policy(Some(policy.clone()))
+        .plan_with_hardware(&cargo_optimize::hardware::get_fallback_hardware())
+        .unwrap()
+        .1;
+    let skipped = report.decision_for("build.rustc-wrapper").unwrap();
+    assert_eq!(skipped.skip_reason, Some(cargo_optimize::report::SkipReason::MissingTool));
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_POLICY_")
+        .with_toolchain(nightly)
+        .with_installed_commands(vec!["sccache".to_string()])
+        .with_policy(Some(policy));
+    for profile in manager.config_mut().profiles.values_mut() {
+        profile.rustflags.clear();
+    }
+    manager.config_mut().profiles.get_mut("release").unwrap().rustflags =
+        vec!["-C".to_string(), "target-cpu=native".to_string()];
+    let report = manager.apply_with_report().unwrap();
+    
+    let conflict = |setting: &str| report.policy_conflicts.iter().find(|c| c.setting == setting);
+    assert!(conflict("build.rustc-wrapper").unwrap().reason.contains("ccache"));
+    // Flags the user wrote are reported, flags cargo-optimize wrote are removed
+    assert!(conflict("build.rustflags").unwrap().reason.contains("manually"));
+    assert!(conflict("profile.release.rustflags").unwrap().reason.contains("removed"));
+    
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["build"]["rustc-wrapper"].as_str(), Some("sccache"));
+    assert_eq!(doc["env"]["SCCACHE_ENDPOINT"].as_str(), Some("https://sccache.example.com"));
+    assert_eq!(doc["env"]["SCCACHE_BUCKET"].as_str(), Some("builds"));
+    assert_eq!(doc["build"]["rustflags"].as_array().unwrap().len(), 2);
+    assert!(doc["profile"]["release"].get("rustflags").is_none());
+    
+    fs::write(&policy_path, "allowed_linkers = [\"fastld\"]\n").unwrap();
+    assert!(Policy::load(&policy_path).is_err());
+}
+
+#[test]
+fn test_validate_against_toolchains() {
+    use cargo_optimize::toolchain::ToolchainSpec;
+    
+    let doc = r#"
+[profile.release]
+strip = true
+
+[target.x86_64-unknown-linux-gnu]
+rustflags = ["-Z", "share-generics=y"]
+"#
+    .parse::<DocumentMut>()
+    .unwrap();
+    
+    let toolchains: Vec<ToolchainSpec> = ["1.56", "1.75", "1.80.0-nightly"]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+    let results = validate_against_toolchains(&doc, &toolchains);
+    assert_eq!(results.len(), 3);
+    
+    // Old stable lacks `strip` and any -Z flag
+    let old = &results[0];
+    assert!(!old.is_compatible());
+    assert!(old.issues.iter().any(|i| i.key == "profile.release.strip"));
+    assert!(old.issues.iter().any(|i| i.key == "target.x86_64-unknown-linux-gnu.rustflags"));
+    
+    // Newer stable only trips over the nightly flag
+    assert_eq!(results[1].issues.len(), 1);
+    assert_eq!(results[1].issues[0].requirement, ToolchainRequirement::Nightly);
+    
+    assert!(results[2].is_compatible());
+}
+
+#[test]
+fn test_planned_config_toolchain_matrix() {
+    use cargo_optimize::toolchain::{RustVersion, ToolchainSpec};
+    
+    let temp_dir = setup_test_env();
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_TOOLCHAIN_MATRIX_");
+    
+    let results = manager
+        .validate_against_toolchains(&[
+            ToolchainSpec::stable(RustVersion::new(1, 40, 0)),
+            ToolchainSpec::stable(RustVersion::new(1, 70, 0)),
+        ])
+        .unwrap();
+    
+    // Profiles in .cargo/config.toml need Rust 1.43
+    assert!(results[0].issues.iter().any(|i| i.key == "profile"));
+    assert!(results[1].is_compatible());
+    
+    // Planning never writes the config
+    assert!(!temp_dir.path().join(".cargo").join("config.toml").exists());
+}
+
+#[test]
+fn test_respect_msrv_skips_unsupported_settings() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join("Cargo.toml"),
+        "[package]\nname = \"old\"\nversion = \"0.1.0\"\nrust-version = \"1.40\"\n",
+    )
+    .unwrap();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[build]\njobs = 2\n").unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_RESPECT_MSRV_");
+    manager.config_mut().global.respect_msrv = true;
+    let report = manager.apply_with_report().unwrap();
+    
+    // Profiles in .cargo/config.toml need 1.43, so none are written
+    let decision = report.decision_for("profile").expect("profile skip recorded");
+    assert!(decision.reason.contains("MSRV 1.40.0"));
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert!(!doc.contains_key("profile"));
+    assert!(doc.contains_key("build"));
+    
+    // Without the mode the profiles are written as usual
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_RESPECT_MSRV_OFF_");
+    manager.config_mut().global.respect_msrv = false;
+    let report = manager.apply_with_report().unwrap();
+    assert!(report.decision_for("profile").is_none());
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert!(doc.contains_key("profile"));
+}
+
+#[test]
+fn test_apply_transaction_single_read_single_write() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "# user config\n[build]\njobs = 2\n").unwrap();
+    
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_APPLY_TRANSACTION_");
+    let (transaction, _report) = manager.prepare_with_hardware(&get_fallback_hardware()).unwrap();
+    
+    // Nothing is written until commit, and the original is held in memory
+    assert_eq!(transaction.original(), Some("# user config\n[build]\njobs = 2\n"));
+    assert!(transaction.is_changed());
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), "# user config\n[build]\njobs = 2\n");
+    
+    let staged = transaction.staged().unwrap().to_string();
+    assert!(staged.contains("# user config"));
+    assert!(transaction.commit().unwrap());
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), staged);
+}
+
+#[test]
+fn test_project_template_applied_to_profiles() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    
+    // Detected template is used when none is configured
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_TEMPLATE_DETECTED_")
+        .with_detected_template(ProjectTemplate::WasmApp);
+    let report = manager.apply_with_report().unwrap();
+    assert!(report.decision_for("template").unwrap().reason.contains("wasm-app (detected"));
+    
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["profile"]["release"]["opt-level"].as_str(), Some("s"));
+    assert_eq!(doc["profile"]["release"]["lto"].as_bool(), Some(true));
+    assert_eq!(doc["profile"]["release"]["codegen-units"].as_integer(), Some(1));
+    assert_eq!(doc["profile"]["release"]["panic"].as_str(), Some("abort"));
+    
+    // A configured template overrides detection, and explicit profile values win
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_TEMPLATE_CONFIGURED_")
+        .with_detected_template(ProjectTemplate::WasmApp);
+    manager.config_mut().global.template = Some(ProjectTemplate::Server);
+    manager.config_mut().profiles.get_mut("release").unwrap().opt_level = Some("2".to_string());
+    let report = manager.apply_with_report().unwrap();
+    assert!(report.decision_for("template").unwrap().reason.contains("server (configured)"));
+    
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["profile"]["release"]["opt-level"].as_integer(), Some(2));
+    assert_eq!(doc["profile"]["release"]["lto"].as_str(), Some("thin"));
+}
+
+#[test]
+fn test_missing_component_recommends_install_command() {
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    fs::write(temp_dir.path().join("custom-target.json"), "{}").unwrap();
+    let nightly = ToolchainInfo::parse_verbose_version("release: 1.80.0-nightly\n").unwrap();
+    let build_std = BuildStdSettings {
+        crates: vec!["core".to_string()],
+        features: vec![],
+        target: Some("custom-target.json".to_string()),
+    };
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_COMPONENT_MISSING_")
+        .with_toolchain(nightly.clone())
+        .with_installed_components(vec!["cargo-x86_64-unknown-linux-gnu".to_string()]);
+    manager.config_mut().global.build_std = Some(build_std.clone());
+    let report = manager.apply_with_report().unwrap();
+    assert!(report
+        .recommendations
+        .iter()
+        .any(|r| r.contains("rustup component add rust-src")));
+    
+    // Nothing to advise once the component is present
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_COMPONENT_PRESENT_")
+        .with_toolchain(nightly)
+        .with_installed_components(vec!["rust-src".to_string()]);
+    manager.config_mut().global.build_std = Some(build_std);
+    let report = manager.apply_with_report().unwrap();
+    assert!(!report.recommendations.iter().any(|r| r.contains("rust-src")));
+}
+
+#[test]
+fn test_package_override_editor() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[profile.dev.package.core-types]\ndebug = 0\n").unwrap();
+    
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PACKAGE_OVERRIDE_");
+    let report = manager
+        .apply_package_overrides(&[PackageOverride {
+            profile: "dev".to_string(),
+            package: "core-types".to_string(),
+            settings: vec![
+                ("incremental".to_string(), "false".to_string()),
+                ("opt-level".to_string(), "1".to_string()),
+            ],
+        }])
+        .unwrap();
+    assert!(report.decision_for("profile.dev.package.core-types").is_some());
+    
+    let mut doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    let package = &doc["profile"]["dev"]["package"]["core-types"];
+    assert_eq!(package["incremental"].as_bool(), Some(false));
+    assert_eq!(package["opt-level"].as_integer(), Some(1));
+    assert_eq!(package["debug"].as_integer(), Some(0));
+    
+    // Keys cargo rejects in package overrides are refused
+    let invalid = PackageOverride {
+        profile: "dev".to_string(),
+        package: "core-types".to_string(),
+        settings: vec![("panic".to_string(), "abort".to_string())],
+    };
+    assert!(apply_package_override(&mut doc, &invalid).is_err());
+    
+    assert!(remove_package_override(&mut doc, "dev", "core-types"));
+    assert!(doc["profile"]["dev"].get("package").is_none());
+    assert!(!remove_package_override(&mut doc, "dev", "core-types"));
+}
+
+#[test]
+fn test_apply_deferred_while_cargo_build_running() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let lock_dir = temp_dir.path().join("target").join("debug");
+    fs::create_dir_all(&lock_dir).unwrap();
+    let lock_path = lock_dir.join(".cargo-lock");
+    fs::write(&lock_path, "").unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_BUILD_RUNNING_");
+    manager.config_mut().global.queue_when_busy = true;
+    
+    // Simulate a running build holding the target directory lock
+    let holder = fs::File::open(&lock_path).unwrap();
+    holder.lock().unwrap();
+    let report = manager.apply_with_report().unwrap();
+    let decision = report.decision_for("apply").unwrap();
+    assert!(decision.reason.contains("cargo build in progress"));
+    assert!(!config_path.exists());
+    assert!(manager.pending_apply().is_some());
+    drop(holder);
+    
+    // The next run carries out the queued change
+    let report = manager.apply_with_report().unwrap();
+    assert!(report.decision_for("apply").unwrap().reason.contains("queued earlier"));
+    assert!(config_path.exists());
+    assert!(manager.pending_apply().is_none());
+}
+
+#[test]
+fn test_target_dir_layouts() {
+    let temp_dir = setup_test_env();
+    let debug_dir = temp_dir.path().join("target").join("debug");
+    fs::create_dir_all(&debug_dir).unwrap();
+    fs::write(debug_dir.join("libdep.rlib"), vec![0u8; 4096]).unwrap();
+    
+    // Per profile: one alias per profile, dev and test stop sharing target/debug
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_TARGET_DIR_PROFILE_");
+    manager.config_mut().global.target_dir_layout = TargetDirLayout::PerProfile;
+    let (doc, report) = manager.plan().unwrap();
+    let dev_alias = doc["alias"]["build-dev"].as_array().unwrap();
+    assert_eq!(dev_alias.get(0).and_then(|v| v.as_str()), Some("build"));
+    assert!(dev_alias.get(4).and_then(|v| v.as_str()).unwrap().ends_with("dev"));
+    assert!(doc["alias"]["test-isolated"].is_array());
+    let tradeoff = &report.disk_tradeoffs[0];
+    assert_eq!(tradeoff.estimated_extra_bytes, 4096);
+    assert_eq!(tradeoff.target_dirs.len(), 4);
+    
+    // Per group: members get their own config pointing into target/<group>
+    let member = temp_dir.path().join("crates").join("tools");
+    fs::create_dir_all(&member).unwrap();
+    fs::write(member.join("Cargo.toml"), "[package]\nname = \"tools\"\nversion = \"0.1.0\"\n").unwrap();
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_TARGET_DIR_GROUP_");
+    manager.config_mut().global.target_dir_layout = TargetDirLayout::PerGroup;
+    manager.config_mut().global.member_groups.insert(
+        "tools".to_string(),
+        vec![PathBuf::from("crates/tools"), PathBuf::from("missing")],
+    );
+    let report = manager.apply_with_report().unwrap();
+    assert_eq!(report.disk_tradeoffs[0].target_dirs.len(), 1);
+    
+    let member_config = fs::read_to_string(member.join(".cargo").join("config.toml")).unwrap();
+    let member_doc: DocumentMut = member_config.parse().unwrap();
+    assert_eq!(member_doc["build"]["target-dir"].as_str(), Some("../../target/tools"));
+    assert!(!temp_dir.path().join("missing").join(".cargo").exists());
+}
+
+#[test]
+fn test_paths_with_spaces_and_non_ascii() {
+    let temp_dir = TempDir::new().unwrap();
+    let project = temp_dir.path().join("My Projects").join("Zoë's \"crate\" 日本語 \\ x");
+    fs::create_dir_all(project.join(".cargo")).unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(&project, "TEST_GNARLY_PATHS_");
+    manager.config_mut().global.target_dir_layout = TargetDirLayout::PerProfile;
+    manager.apply().unwrap();
+    
+    // The written config parses and gives the directory back unchanged
+    let content = fs::read_to_string(project.join(".cargo").join("config.toml")).unwrap();
+    let doc: DocumentMut = content.parse().expect("generated config is valid TOML");
+    let dev_alias = doc["alias"]["build-dev"].as_array().unwrap();
+    let target_dir = dev_alias.get(4).and_then(|v| v.as_str()).unwrap();
+    assert_eq!(Path::new(target_dir), std::path::absolute(project.join("target").join("dev")).unwrap());
+}
+
+#[test]
+fn test_uninstall_restores_original_config() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let original = "# my settings\n[build]\njobs = 3\n\n[profile.dev]\nincremental = false\n";
+    fs::write(&config_path, original).unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_UNINSTALL_");
+    manager.config_mut().global.default_jobs = Some(JobCount::Fixed(6));
+    manager.apply().unwrap();
+    assert_ne!(fs::read_to_string(&config_path).unwrap(), original);
+    
+    let report = manager.uninstall().unwrap();
+    assert_eq!(report.decision_for("uninstall").unwrap().outcome, cargo_optimize::report::DecisionOutcome::Applied);
+    let restored: DocumentMut = fs::read_to_string(&config_path).unwrap().parse().unwrap();
+    assert_eq!(restored["build"]["jobs"].as_integer(), Some(3));
+    assert_eq!(restored["profile"]["dev"]["incremental"].as_bool(), Some(false));
+    assert!(restored.get("target").is_none());
+    
+    // Nothing left to undo
+    let report = manager.uninstall().unwrap();
+    assert!(report.decision_for("uninstall").unwrap().reason.contains("no changes recorded"));
+}
+
+#[test]
+fn test_purge_lists_then_removes_everything() {
+    let temp_dir = setup_test_env();
+    let root = temp_dir.path();
+    let tools = root.join("opt-tools");
+    fs::create_dir_all(tools.join("mold-2.34.1-x86_64-linux").join("bin")).unwrap();
+    fs::write(tools.join("mold-2.34.1-x86_64-linux").join("bin").join("mold"), "binary").unwrap();
+    // The configured tools directory is shared with the user's own files
+    fs::write(tools.join("my-tool"), "mine").unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(root, "TEST_PURGE_");
+    manager.config_mut().global.linker_install.dir = Some(tools.clone());
+    manager.apply().unwrap();
+    manager.propose().unwrap();
+    let state = manager.state_path();
+    assert!(state.is_file());
+    
+    // The dry run lists and keeps everything
+    let listing = manager.purge(true).unwrap();
+    assert!(listing.dry_run);
+    let kinds: Vec<ArtifactKind> = listing.artifacts.iter().map(|artifact| artifact.kind).collect();
+    for kind in [ArtifactKind::Settings, ArtifactKind::State, ArtifactKind::Proposal, ArtifactKind::AuditLog, ArtifactKind::ToolDownloads] {
+        assert!(kinds.contains(&kind), "{:?} not in {}", kind, listing);
+    }
+    let downloads: Vec<&Path> =
+        listing.artifacts.iter().filter(|artifact| artifact.kind == ArtifactKind::ToolDownloads).map(|artifact| artifact.path.as_path()).collect();
+    assert_eq!(downloads, [tools.join("mold-2.34.1-x86_64-linux")]);
+    assert!(listing.to_string().starts_with("cargo-optimize would remove:\n"), "{}", listing);
+    assert!(state.is_file() && tools.is_dir());
+    
+    let summary = manager.purge(false).unwrap();
+    assert!(summary.failed.is_empty(), "{}", summary);
+    assert!(summary.bytes() > 0);
+    assert!(!root.join(".cargo").exists(), "the config cargo-optimize created and its directory are gone");
+    assert!(!root.join(".cargo-optimize").exists());
+    assert!(!tools.join("mold-2.34.1-x86_64-linux").exists());
+    assert_eq!(fs::read_to_string(tools.join("my-tool")).unwrap(), "mine");
+    assert!(manager.purge(true).unwrap().artifacts.is_empty());
+}
+
+#[test]
+fn test_sccache_backend_secrets_stay_out_of_files() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    std::env::set_var("CO_TEST_BACKEND_REDIS_PW", "s3cr3t-redis-pw");
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_SCCACHE_BACKEND_");
+    let backend = &mut manager.config_mut().global.sccache_backend;
+    backend.insert("SCCACHE_BUCKET".to_string(), "team-cache".to_string());
+    backend.insert(
+        "SCCACHE_REDIS".to_string(),
+        "redis://:${ENV:CO_TEST_BACKEND_REDIS_PW}@cache.internal:6379".to_string(),
+    );
+    backend.insert("SCCACHE_GCS_KEY_PATH".to_string(), "${ENV:CO_TEST_BACKEND_UNSET}".to_string());
+    backend.insert("AWS_SECRET_ACCESS_KEY".to_string(), "literal-key".to_string());
+    manager.config_mut().global.use_sccache = true;
+    let report = manager.apply_with_report().unwrap();
+    std::env::remove_var("CO_TEST_BACKEND_REDIS_PW");
+    
+    let content = fs::read_to_string(&config_path).unwrap();
+    let doc: DocumentMut = content.parse().unwrap();
+    assert_eq!(doc["env"]["SCCACHE_BUCKET"].as_str(), Some("team-cache"));
+    assert!(doc["env"].get("SCCACHE_REDIS").is_none());
+    assert!(doc["env"].get("AWS_SECRET_ACCESS_KEY").is_none());
+    assert!(!content.contains("s3cr3t-redis-pw") && !content.contains("literal-key"));
+    
+    let reason = |setting: &str| report.decision_for(setting).unwrap().reason.clone();
+    assert!(reason("env.SCCACHE_REDIS").contains("never written"));
+    assert!(reason("env.SCCACHE_GCS_KEY_PATH").contains("CO_TEST_BACKEND_UNSET is not set"));
+    assert!(reason("env.AWS_SECRET_ACCESS_KEY").contains("looks like a secret"));
+    assert!(report
+        .recommendations
+        .iter()
+        .any(|r| r.contains("SCCACHE_REDIS=\"redis://:${CO_TEST_BACKEND_REDIS_PW}@cache.internal:6379\"")));
+    
+    // The resolved secret is masked wherever it would be shown
+    let mut shown = report.clone();
+    shown.recommend("connect with s3cr3t-redis-pw");
+    assert!(!shown.to_string().contains("s3cr3t-redis-pw"));
+    
+    // Dropping a variable removes what was written for it
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_SCCACHE_BACKEND_");
+    manager.config_mut().global.use_sccache = true;
+    manager.apply().unwrap();
+    let doc: DocumentMut = fs::read_to_string(&config_path).unwrap().parse().unwrap();
+    assert!(doc.get("env").and_then(|env| env.get("SCCACHE_BUCKET")).is_none());
+}
+
+#[test]
+fn test_third_party_provider() {
+    use cargo_optimize::providers::{OptimizationProvider, ProviderAction, ProviderContext};
+    use cargo_optimize::report::DecisionOutcome;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    
+    struct AcmeCache {
+        applied: Arc<AtomicUsize>,
+        reverted: Arc<AtomicUsize>,
+    }
+    
+    impl OptimizationProvider for AcmeCache {
+        fn name(&self) -> &str {
+            "acme-cache"
+        }
+        
+        fn detect(&self, _context: &ProviderContext<'_>) -> Result<(), String> {
+            Ok(())
+        }
+        
+        fn plan(&self, _context: &ProviderContext<'_>) -> Vec<ProviderAction> {
+            vec![
+                ProviderAction::set(&["env", "ACME_CACHE_URL"], "https://cache.acme.test", "company cache"),
+                ProviderAction::set(&["build", "jobs"], 2, "fewer jobs for the cache daemon"),
+            ]
+        }
+        
+        fn apply(&self, _context: &ProviderContext<'_>, applied: &[ProviderAction]) -> anyhow::Result<()> {
+            self.applied.fetch_add(applied.len(), Ordering::SeqCst);
+            Ok(())
+        }
+        
+        fn revert(&self, _context: &ProviderContext<'_>) -> anyhow::Result<()> {
+            self.reverted.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let applied = Arc::new(AtomicUsize::new(0));
+    let reverted = Arc::new(AtomicUsize::new(0));
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PROVIDER_")
+        .with_provider(AcmeCache { applied: applied.clone(), reverted: reverted.clone() });
+    manager.config_mut().global.default_jobs = Some(JobCount::Fixed(6));
+    let report = manager.apply_with_report().unwrap();
+    
+    // The built-in job count wins; the rest is written and reported
+    let doc: DocumentMut = fs::read_to_string(&config_path).unwrap().parse().unwrap();
+    assert_eq!(doc["build"]["jobs"].as_integer(), Some(6));
+    assert_eq!(doc["env"]["ACME_CACHE_URL"].as_str(), Some("https://cache.acme.test"));
+    let url = report.decision_for("env.ACME_CACHE_URL").unwrap();
+    assert_eq!(url.outcome, DecisionOutcome::Applied);
+    assert_eq!(url.reason, "company cache (acme-cache)");
+    assert!(report
+        .skipped_decisions()
+        .any(|d| d.setting == "build.jobs" && d.reason.contains("conflicts with a built-in optimization")));
+    assert_eq!(report.providers[0].actions.len(), 1);
+    assert_eq!(applied.load(Ordering::SeqCst), 1);
+    
+    manager.uninstall().unwrap();
+    let restored = fs::read_to_string(&config_path).unwrap_or_default();
+    assert!(!restored.contains("ACME_CACHE_URL"));
+    assert_eq!(reverted.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_providers_in_the_build_script_path() {
+    use cargo_optimize::audit::{AuditLog, AuditQuery};
+    use cargo_optimize::providers::{OptimizationProvider, ProviderAction, ProviderContext};
+    use cargo_optimize::report::{DecisionOutcome, SkipReason};
+    
+    struct AcmeCache;
+    
+    impl OptimizationProvider for AcmeCache {
+        fn name(&self) -> &str {
+            "acme-cache"
+        }
+        
+        fn detect(&self, _context: &ProviderContext<'_>) -> Result<(), String> {
+            Ok(())
+        }
+        
+        fn plan(&self, _context: &ProviderContext<'_>) -> Vec<ProviderAction> {
+            vec![
+                ProviderAction::set(&["env", "ACME_CACHE_URL"], "https://cache.acme.test", "company cache"),
+                ProviderAction::set(&["env", "ACME_REGION"], "us", "nearest region"),
+                ProviderAction::set(&["env", "ACME_TOKEN"], "secret", "cache credentials"),
+            ]
+        }
+    }
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let original = "[env]\nACME_REGION = \"eu\" # mine\n";
+    fs::write(&config_path, original).unwrap();
+    fs::write(temp_dir.path().join("cargo-optimize.toml"), "[deny]\nfeatures = [\"env.ACME_TOKEN\"]\n").unwrap();
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PROVIDER_BUILD_SCRIPT_").with_provider(AcmeCache);
+    
+    // A value set by hand stays, and the deny list holds
+    let report = manager.apply_providers().unwrap();
+    let written = fs::read_to_string(&config_path).unwrap();
+    assert!(written.contains("ACME_CACHE_URL = \"https://cache.acme.test\" # provider: acme-cache"), "{}", written);
+    assert!(written.contains("ACME_REGION = \"eu\" # mine"), "{}", written);
+    assert!(!written.contains("ACME_TOKEN"), "{}", written);
+    let region = report.decision_for("env.ACME_REGION").unwrap();
+    assert_eq!(region.skip_reason, Some(SkipReason::UserOptOut), "{}", report);
+    assert_ne!(report.decision_for("env.ACME_TOKEN").unwrap().outcome, DecisionOutcome::Applied);
+    
+    // Its own values are replaced on the next run
+    let report = manager.apply_providers().unwrap();
+    assert_eq!(report.decision_for("env.ACME_CACHE_URL").unwrap().outcome, DecisionOutcome::Applied);
+    
+    let entries = AuditLog::new(temp_dir.path()).query(&AuditQuery::default()).unwrap();
+    assert_eq!(entries.iter().map(|entry| entry.action.as_str()).collect::<Vec<_>>(), ["providers"]);
+    manager.uninstall().unwrap();
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), original);
+}
+
+#[test]
+fn test_audit_log_records_every_write() {
+    use cargo_optimize::audit::{self, AuditLog, AuditQuery};
+    use sha2::{Digest, Sha256};
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let original = "[build]\njobs = 3\n";
+    fs::write(&config_path, original).unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_AUDIT_");
+    manager.config_mut().global.default_jobs = Some(JobCount::Fixed(6));
+    manager.apply().unwrap();
+    let applied = fs::read_to_string(&config_path).unwrap();
+    manager.uninstall().unwrap();
+    
+    let log = AuditLog::new(temp_dir.path());
+    let config = AuditQuery { file: Some(".cargo/config.toml".to_string()), ..Default::default() };
+    let entries = log.query(&config).unwrap();
+    let actions: Vec<&str> = entries.iter().map(|entry| entry.action.as_str()).collect();
+    assert_eq!(actions, ["apply", "uninstall"]);
+    
+    // The hashes chain from the original content through the apply and back
+    let hash = |content: &str| format!("{:x}", Sha256::digest(content.as_bytes()));
+    assert_eq!(entries[0].before.as_deref(), Some(hash(original).as_str()));
+    assert_eq!(entries[0].after.as_deref(), Some(hash(&applied).as_str()));
+    assert_eq!(entries[1].before, entries[0].after);
+    assert_eq!(entries[1].version, env!("CARGO_PKG_VERSION"));
+    
+    let rendered = audit::render(&entries);
+    assert!(rendered.contains("`apply` .cargo/config.toml modified"), "{}", rendered);
+    assert!(rendered.contains("`uninstall` .cargo/config.toml modified"), "{}", rendered);
+}
+
+#[test]
+fn test_managed_values_carry_explanation_ids() {
+    use cargo_optimize::explain;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_EXPLAIN_IDS_");
+    manager.apply().expect("Failed to apply config");
+    
+    let content = fs::read_to_string(&config_path).expect("Failed to read config");
+    let doc: DocumentMut = content.parse().expect("Invalid TOML");
+    let incremental = doc["profile"]["dev"]["incremental"]
+        .as_value()
+        .expect("profile.dev.incremental not written");
+    let comment = incremental.decor().suffix().and_then(|s| s.as_str()).unwrap_or_default();
+    assert_eq!(explain::id_from_comment(comment), Some("CO-INCR-001"));
+    
+    // Every id in the config resolves to an explanation
+    let ids: Vec<&str> = content
+        .lines()
+        .filter_map(|line| line.split_once(explain::COMMENT_PREFIX))
+        .map(|(_, rest)| rest.split_whitespace().next().unwrap_or_default())
+        .collect();
+    assert!(!ids.is_empty());
+    for id in ids {
+        let explanation = cargo_optimize::explain(id).expect("unknown id in config");
+        assert!(!explanation.disable.is_empty());
+    }
+    assert!(cargo_optimize::explain("CO-UNKNOWN-000").is_none());
+}
+
+#[test]
+fn test_context_scoped_settings() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join("cargo-optimize.toml"),
+        r#"
+[global]
+verbose = false
+
+[contexts.ci.global]
+verbose = true
+default_jobs = 2
+
+[contexts.ci.profiles.dev]
+incremental = false
+
+[contexts.local.profiles.dev]
+incremental = true
+"#,
+    )
+    .unwrap();
+    
+    let ci = ConfigManager::new_with_context(temp_dir.path(), "TEST_CONTEXT_CI_", BuildContext::Ci)
+        .expect("Failed to create config manager");
+    assert_eq!(ci.context(), BuildContext::Ci);
+    assert!(ci.config().global.verbose);
+    assert_eq!(ci.config().global.default_jobs, Some(JobCount::Fixed(2)));
+    assert_eq!(ci.config().profiles["dev"].incremental, Some(false));
+    
+    let local = ConfigManager::new_with_context(temp_dir.path(), "TEST_CONTEXT_LOCAL_", BuildContext::Local)
+        .expect("Failed to create config manager");
+    assert!(!local.config().global.verbose);
+    assert_eq!(local.config().profiles["dev"].incremental, Some(true));
+    
+    // Detection: explicit override wins over CI variables
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |key: &str| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+    };
+    assert_eq!(BuildContext::from_env_lookup(env(&[("GITLAB_CI", "true")])), BuildContext::Ci);
+    assert_eq!(
+        BuildContext::from_env_lookup(env(&[("CI", "true"), (CONTEXT_ENV_VAR, "local")])),
+        BuildContext::Local
+    );
+    assert_eq!(BuildContext::from_env_lookup(env(&[(CONTEXT_ENV_VAR, "bogus")])), BuildContext::Local);
+}
+
+#[test]
+fn test_cargo_target_dir_env_reconciliation() {
+    use cargo_optimize::report::TargetDirSource;
+    
+    let temp_dir = setup_test_env();
+    let env_dir = temp_dir.path().join("shared-target");
+    let member = temp_dir.path().join("crates").join("app");
+    fs::create_dir_all(&member).unwrap();
+    fs::write(member.join("Cargo.toml"), "[package]\nname = \"app\"\nversion = \"0.1.0\"\n").unwrap();
+    let manager = |prefix: &str, layout: TargetDirLayout, policy: TargetDirEnvPolicy| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_target_dir_env(Some(env_dir.clone()));
+        manager.config_mut().global.target_dir_layout = layout;
+        manager.config_mut().global.target_dir_env_policy = policy;
+        manager.config_mut().global.member_groups.insert("apps".to_string(), vec![PathBuf::from("crates/app")]);
+        manager
+    };
+    
+    // Respect: per-profile dirs live under the user's directory
+    let (doc, report) = manager("TEST_TDENV_RESPECT_", TargetDirLayout::PerProfile, TargetDirEnvPolicy::Respect)
+        .plan()
+        .unwrap();
+    let dir = doc["alias"]["build-dev"].as_array().unwrap().get(4).and_then(|v| v.as_str()).unwrap().to_string();
+    assert!(Path::new(&dir).starts_with(&env_dir));
+    let effective = report.effective_target_dir.as_ref().unwrap();
+    assert_eq!(effective.source, TargetDirSource::Environment);
+    assert_eq!(effective.path, env_dir);
+    
+    // Respect: member configs cargo would ignore are not written
+    let report = manager("TEST_TDENV_GROUP_", TargetDirLayout::PerGroup, TargetDirEnvPolicy::Respect)
+        .apply_with_report()
+        .unwrap();
+    assert!(report.decision_for("target-dir layout").unwrap().reason.contains("CARGO_TARGET_DIR"));
+    assert!(!member.join(".cargo").exists());
+    
+    // Skip: nothing target-dir related at all
+    let (doc, _) = manager("TEST_TDENV_SKIP_", TargetDirLayout::PerProfile, TargetDirEnvPolicy::Skip)
+        .plan()
+        .unwrap();
+    assert!(doc.get("alias").is_none());
+    
+    // Warn: written anyway, with a recommendation
+    let report = manager("TEST_TDENV_WARN_", TargetDirLayout::PerGroup, TargetDirEnvPolicy::Warn)
+        .apply_with_report()
+        .unwrap();
+    assert!(member.join(".cargo").join("config.toml").exists());
+    assert!(report.recommendations.iter().any(|r| r.contains("CARGO_TARGET_DIR")));
+    
+    // Without the variable, build.target-dir from the config is effective
+    fs::write(temp_dir.path().join(".cargo").join("config.toml"), "[build]\ntarget-dir = \"out\"\n").unwrap();
+    let (_, report) = create_test_manager_with_prefix(temp_dir.path(), "TEST_TDENV_NONE_")
+        .with_target_dir_env(None)
+        .plan()
+        .unwrap();
+    let effective = report.effective_target_dir.as_ref().unwrap();
+    assert_eq!(effective.source, TargetDirSource::Config);
+    assert_eq!(effective.path, temp_dir.path().join("out"));
+    assert!(report.localized(cargo_optimize::i18n::Lang::En).contains("set by build.target-dir"));
+}
+
+#[test]
+fn test_ide_checks_bypass_sccache() {
+    use cargo_optimize::ide::WrapperScope;
+    use cargo_optimize::report::DecisionOutcome;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[build]\nrustc-wrapper = \"sccache\"\n").unwrap();
+    fs::create_dir(temp_dir.path().join(".vscode")).unwrap();
+    fs::create_dir(temp_dir.path().join(".idea")).unwrap();
+    let ra_config = temp_dir.path().join("rust-analyzer.toml");
+    const SETTING: &str = "rust-analyzer.toml: check.extraEnv.RUSTC_WRAPPER";
+    
+    // Builds keep sccache, rust-analyzer's checks bypass it
+    let report = create_test_manager_with_prefix(temp_dir.path(), "TEST_IDE_BYPASS_").apply_with_report().unwrap();
+    assert!(fs::read_to_string(&config_path).unwrap().contains("rustc-wrapper = \"sccache\""));
+    let content = fs::read_to_string(&ra_config).unwrap();
+    assert!(content.contains("[check.extraEnv]\nRUSTC_WRAPPER = \"\" # cargo-optimize: CO-IDE-001"), "{}", content);
+    assert_eq!(report.decision_for(SETTING).unwrap().outcome, DecisionOutcome::Applied);
+    assert!(report.recommendations.iter().any(|r| r.contains("RustRover") && r.contains("RUSTC_WRAPPER=")));
+    
+    // Checks through the wrapper too once the scope says so
+    let mut all = create_test_manager_with_prefix(temp_dir.path(), "TEST_IDE_ALL_");
+    all.config_mut().global.wrapper_scope = WrapperScope::All;
+    let report = all.apply_with_report().unwrap();
+    assert!(!fs::read_to_string(&ra_config).unwrap().contains("RUSTC_WRAPPER"));
+    assert!(report.decision_for(SETTING).unwrap().reason.starts_with("removed"));
+    assert!(!report.recommendations.iter().any(|r| r.contains("RustRover")));
+}
+
+#[test]
+fn test_nextest_config_generation() {
+    use cargo_optimize::hardware::{get_fallback_hardware, HardwareInfo};
+    
+    let temp_dir = setup_test_env();
+    let nextest_path = temp_dir.path().join(".config").join("nextest.toml");
+    let hardware = HardwareInfo {
+        cpu_count: 8,
+        available_memory: 16 * 1024 * 1024 * 1024,
+        ..get_fallback_hardware()
+    };
+    
+    // Without nextest nothing is written
+    create_test_manager_with_prefix(temp_dir.path(), "TEST_NEXTEST_MISSING_")
+        .with_nextest_installed(false)
+        .apply_with_hardware(&hardware)
+        .unwrap();
+    assert!(!nextest_path.exists());
+    
+    // Five integration test binaries plus the unit tests
+    fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+    fs::create_dir_all(temp_dir.path().join("tests")).unwrap();
+    for name in ["a", "b", "c", "d", "e"] {
+        fs::write(temp_dir.path().join("tests").join(format!("{}.rs", name)), "").unwrap();
+    }
+    
+    let report = create_test_manager_with_prefix(temp_dir.path(), "TEST_NEXTEST_LOCAL_")
+        .with_nextest_installed(true)
+        .apply_with_hardware(&hardware)
+        .unwrap();
+    assert!(report.decision_for(".config/nextest.toml: profile.default").is_some());
+    assert!(report.recommendations.iter().any(|r| r.contains("2.0x") && r.contains("cargo nextest run")));
+    
+    let doc = fs::read_to_string(&nextest_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["profile"]["default"]["test-threads"].as_integer(), Some(8));
+    assert_eq!(doc["profile"]["default"]["fail-fast"].as_bool(), Some(true));
+    
+    // CI adds its own profile next to the local one
+    let report = ConfigManager::new_with_context(temp_dir.path(), "TEST_NEXTEST_CI_", BuildContext::Ci)
+        .unwrap()
+        .with_nextest_installed(true)
+        .apply_with_hardware(&hardware)
+        .unwrap();
+    assert!(report.recommendations.iter().any(|r| r.contains("--profile ci")));
+    let doc = fs::read_to_string(&nextest_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["profile"]["ci"]["retries"].as_integer(), Some(2));
+    assert_eq!(doc["profile"]["default"]["retries"].as_integer(), Some(0));
+}
+
+#[test]
+fn test_pull_request_mode_propose_and_accept() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let proposed_dir = temp_dir.path().join(".cargo-optimize").join("proposed");
+    fs::write(&config_path, "[alias]\nb = \"build\"\n").unwrap();
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PR_MODE_").with_nextest_installed(false);
+    manager.config_mut().global.pull_request_mode = true;
+    let report = manager.apply_with_hardware(&get_fallback_hardware()).unwrap();
+    assert!(report.recommendations.iter().any(|r| r.contains("CHANGES.md")));
+    
+    // Live config untouched, full rendered file and summary staged
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), "[alias]\nb = \"build\"\n");
+    let proposed = fs::read_to_string(proposed_dir.join(".cargo").join("config.toml")).unwrap();
+    assert!(proposed.contains("b = \"build\"") && proposed.contains("[profile."));
+    let changes = fs::read_to_string(proposed_dir.join("CHANGES.md")).unwrap();
+    assert!(changes.contains("## `.cargo/config.toml`"));
+    assert!(changes.contains("| `profile."));
+    
+    let report = manager.accept().unwrap();
+    assert!(report.decision_for(".cargo/config.toml").is_some());
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), proposed);
+    assert!(!proposed_dir.exists());
+    assert!(manager.accept().is_err());
+    
+    // A live edit after proposing invalidates the proposal
+    manager.config_mut().profiles.get_mut("dev").unwrap().opt_level = Some("2".to_string());
+    manager.propose_with_hardware(&get_fallback_hardware()).unwrap();
+    fs::write(&config_path, "[alias]\nt = \"test\"\n").unwrap();
+    let error = manager.accept().unwrap_err().to_string();
+    assert!(error.contains("changed since the proposal"), "{}", error);
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), "[alias]\nt = \"test\"\n");
+}
+
+#[test]
+fn test_sccache_disabled_when_it_does_not_pay_off() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::sccache::{ProjectSize, SccacheStats};
+    use cargo_optimize::state::State;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let state_path = temp_dir.path().join(".cargo").join("cargo-optimize-state.json");
+    fs::write(&config_path, "[build]\nrustc-wrapper = \"sccache\"\n").unwrap();
+    
+    let apply = |prefix: &str, hits: u64, misses: u64, size: ProjectSize| {
+        create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_nextest_installed(false)
+            .with_sccache_stats(SccacheStats { hits, misses })
+            .with_project_size(size)
+            .apply_with_hardware(&get_fallback_hardware())
+            .unwrap()
+    };
+    let small = ProjectSize { members: 1, dependencies: 8 };
+    let large = ProjectSize { members: 4, dependencies: 250 };
+    
+    // A small local project only gets a recommendation
+    let report = apply("TEST_SCCACHE_SMALL_", 5, 95, small);
+    assert!(report.recommendations.iter().any(|r| r.contains("8 dependencies")));
+    
+    // A hit rate staying below the floor removes the wrapper
+    let report = apply("TEST_SCCACHE_LOW_1_", 10, 190, large);
+    assert!(report.decision_for("build.rustc-wrapper").is_none());
+    assert_eq!(State::load(&state_path).sccache.samples.len(), 2);
+    let report = apply("TEST_SCCACHE_LOW_2_", 12, 288, large);
+    let decision = report.decision_for("build.rustc-wrapper").expect("wrapper removed");
+    assert!(decision.reason.contains("CO-SCCACHE-002") && decision.reason.contains("below 20%"));
+    
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert!(doc["build"].get("rustc-wrapper").is_none());
+    assert!(State::load(&state_path).sccache.is_empty());
+    
+    // A configurable floor of 0 keeps the wrapper
+    fs::write(&config_path, "[build]\nrustc-wrapper = \"sccache\"\n").unwrap();
+    for (i, misses) in [300, 400, 500].into_iter().enumerate() {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), &format!("TEST_SCCACHE_FLOOR_{}_", i))
+            .with_nextest_installed(false)
+            .with_sccache_stats(SccacheStats { hits: 1, misses })
+            .with_project_size(large);
+        manager.config_mut().global.sccache_min_hit_rate = 0.0;
+        manager.apply_with_hardware(&get_fallback_hardware()).unwrap();
+    }
+    let doc = fs::read_to_string(&config_path).unwrap().parse::<DocumentMut>().unwrap();
+    assert_eq!(doc["build"]["rustc-wrapper"].as_str(), Some("sccache"));
+}
+
+#[test]
+fn test_debug_capture_and_replay() {
+    use cargo_optimize::debug;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[registry]\ntoken = \"hunter2\"\n\n[alias]\nt = \"test\"\n").unwrap();
+    fs::write(temp_dir.path().join("cargo-optimize.toml"), "[profiles.dev]\nopt_level = \"1\"\n").unwrap();
+    
+    let output = TempDir::new().unwrap();
+    let capture = output.path().join("capture");
+    let manifest = debug::capture(temp_dir.path(), &capture).unwrap();
+    for file in ["capture.json", "environment.txt", "plan.toml", "report.txt", "log.txt", "project/cargo-optimize.toml"] {
+        assert!(capture.join(file).is_file(), "{} missing", file);
+    }
+    let captured_config = fs::read_to_string(capture.join("project/.cargo/config.toml")).unwrap();
+    assert!(!captured_config.contains("hunter2"));
+    assert!(captured_config.contains(debug::REDACTED));
+    assert!(manifest.hardware.cpu_count > 0);
+    
+    // Capturing into a folder with content is refused
+    assert!(debug::capture(temp_dir.path(), &capture).is_err());
+    
+    let replay = debug::replay(&capture).unwrap();
+    assert!(replay.matches(), "captured:\n{:?}\nreplayed:\n{}", replay.captured_plan, replay.plan);
+    assert!(replay.plan.contains("opt-level = 1"));
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), "[registry]\ntoken = \"hunter2\"\n\n[alias]\nt = \"test\"\n");
+}
+
+#[test]
+fn test_platform_capabilities_limit_plan() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    fs::write(temp_dir.path().join(".cargo").join("config.toml"), "[alias]\nt = \"test\"\n").unwrap();
+    
+    // illumos: generic settings only, and the report says why there is no linker
+    let (doc, report) = create_test_manager_with_prefix(temp_dir.path(), "TEST_PLATFORM_ILLUMOS_")
+        .with_platform(Some(Platform::Illumos))
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    assert!(doc.get("target").is_none());
+    assert!(doc["profile"]["dev"].get("incremental").is_some());
+    let decision = report.decision_for("linker").expect("linker decision");
+    assert!(decision.reason.contains("illumos"));
+    
+    // Unknown platforms get nothing but a clear report
+    let (doc, report) = create_test_manager_with_prefix(temp_dir.path(), "TEST_PLATFORM_UNKNOWN_")
+        .with_platform(None)
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    assert_eq!(doc.to_string(), "[alias]\nt = \"test\"\n");
+    let decision = report.decision_for("platform").expect("platform decision");
+    assert!(decision.reason.contains("no optimizations available for this platform"));
+}
+
+#[test]
+fn test_optimization_level_presets_drive_plan() {
+    use cargo_optimize::config::OptimizationLevel;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    let plan = |prefix: &str, level: OptimizationLevel| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix);
+        manager.config_mut().global.optimization_level = level;
+        manager
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(vec!["mold".to_string(), "ld.lld".to_string(), "clang".to_string()])
+            .plan_with_hardware(&get_fallback_hardware())
+            .unwrap()
+    };
+    
+    // Conservative: a proven linker instead of mold, and no sccache
+    let (doc, report) = plan("TEST_PRESET_CONSERVATIVE_", OptimizationLevel::Conservative);
+    let rustflags = doc["target"]["x86_64-unknown-linux-gnu"]["rustflags"].to_string();
+    assert!(rustflags.contains("-fuse-ld=lld"), "{}", rustflags);
+    assert!(report.decision_for("sccache").expect("sccache decision").reason.contains("conservative"));
+    assert!(doc["profile"].get("release").and_then(|release| release.get("lto")).is_none());
+    
+    // Aggressive: the fastest linker and the preset's profile settings
+    let (doc, _) = plan("TEST_PRESET_AGGRESSIVE_", OptimizationLevel::Aggressive);
+    let rustflags = doc["target"]["x86_64-unknown-linux-gnu"]["rustflags"].to_string();
+    assert!(rustflags.contains("-fuse-ld=mold"), "{}", rustflags);
+    assert_eq!(doc["profile"]["dev"]["debug"].as_str(), Some("line-tables-only"));
+    assert_eq!(doc["profile"]["release"]["lto"].as_str(), Some("fat"));
+}
+
+#[test]
+fn test_embedded_project_keeps_target_and_link_settings() {
+    use cargo_optimize::config::{JobCount, OptimizationLevel};
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::report::DecisionOutcome;
+    
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".cargo").join("config.toml"),
+        "[build]\ntarget = \"thumbv7em-none-eabihf\"\n\n\
+         [target.thumbv7em-none-eabihf]\nrunner = \"probe-rs run --chip STM32F411RETx\"\n",
+    )
+    .unwrap();
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_EMBEDDED_");
+    manager.config_mut().global.optimization_level = OptimizationLevel::Aggressive;
+    manager.config_mut().global.default_jobs = Some(JobCount::Fixed(6));
+    let (doc, report) = manager
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(vec!["mold".to_string(), "clang".to_string(), "sccache".to_string()])
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    
+    let template = report.decision_for("template").expect("template decision");
+    assert_eq!(template.reason, "embedded (detected: builds for thumbv7em-none-eabihf)");
+    
+    // No host linker, no native CPU, and the runner is untouched
+    let rendered = doc.to_string();
+    assert!(doc["target"].get("x86_64-unknown-linux-gnu").is_none(), "{}", rendered);
+    assert!(!rendered.contains("target-cpu"), "{}", rendered);
+    assert_eq!(doc["target"]["thumbv7em-none-eabihf"]["runner"].as_str(), Some("probe-rs run --chip STM32F411RETx"));
+    assert_eq!(doc["build"]["target"].as_str(), Some("thumbv7em-none-eabihf"));
+    for setting in ["linker", "target-cpu=native"] {
+        let decision = report.decision_for(setting).expect(setting);
+        assert_eq!(decision.outcome, DecisionOutcome::Skipped);
+        assert!(decision.reason.starts_with("embedded project"), "{}", decision.reason);
+    }
+    
+    // The embedded-safe set still applies
+    assert_eq!(doc["profile"]["dev"]["build-override"]["opt-level"].as_integer(), Some(0));
+    assert_eq!(doc["profile"]["release"]["opt-level"].as_str(), Some("z"));
+    assert_eq!(doc["build"]["jobs"].as_integer(), Some(6));
+    assert!(!report.decisions.iter().any(|d| d.setting == "sccache" && d.outcome == DecisionOutcome::Skipped));
+}
+
+#[test]
+fn test_competing_tools_adjust_plan() {
+    use cargo_optimize::conflicts::ToolEnvironment;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[build]\njobs = 4\n\n[profile.dev]\nincremental = false # measured\n").unwrap();
+    fs::write(
+        temp_dir.path().join("Dockerfile"),
+        "FROM rust\nCOPY recipe.json recipe.json\nRUN cargo chef cook --release --recipe-path recipe.json\n\
+         COPY . .\nRUN cargo build --release\n",
+    )
+    .unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git").arg("-C").arg(temp_dir.path()).args(args).status().unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "-q"]);
+    git(&["add", ".cargo/config.toml"]);
+    
+    let mut tools = ToolEnvironment::detect(temp_dir.path(), &config_path);
+    // Independent of a Nix shell running the tests
+    tools.nix = None;
+    assert!(tools.config_committed);
+    assert_eq!(tools.chef_dockerfiles.len(), 1);
+    assert!(!tools.chef_dockerfiles[0].copies_cargo_config);
+    
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_CONFLICTS_");
+    manager.config_mut().global.default_jobs = Some(JobCount::Fixed(8));
+    manager.config_mut().global.target_dir_layout = TargetDirLayout::PerProfile;
+    let (doc, report) = manager
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(Vec::new())
+        .with_tool_environment(tools)
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    
+    // Hand-set values survive, managed ones are added next to them
+    assert_eq!(doc["build"]["jobs"].as_integer(), Some(4));
+    assert_eq!(doc["profile"]["dev"]["incremental"].as_bool(), Some(false));
+    assert_eq!(doc["profile"]["release"]["incremental"].as_bool(), Some(false));
+    let skipped: Vec<&str> = report.skipped_decisions().map(|d| d.setting.as_str()).collect();
+    assert!(skipped.contains(&"build.jobs") && skipped.contains(&"profile.dev.incremental"), "{:?}", skipped);
+    
+    // cargo-chef keeps the shared target dir and asks for the config in the image
+    assert!(doc.get("alias").is_none());
+    assert!(report.decision_for("target-dir layout").unwrap().reason.contains("cargo-chef"));
+    assert!(report.recommendations.iter().any(|r| r.contains("COPY .cargo .cargo")));
+    let tools: Vec<&str> = report.tool_conflicts.iter().map(|c| c.tool.as_str()).collect();
+    assert_eq!(tools, ["cargo-chef", "hand-tuned config"]);
+    
+    // A wrapper in the environment is reported and never touched
+    fs::write(&config_path, "[build]\nrustc-wrapper = \"sccache\"\n").unwrap();
+    let tools = ToolEnvironment {
+        rustc_wrapper_env: Some(("RUSTC_WRAPPER".to_string(), "/opt/bin/sccache".to_string())),
+        ..ToolEnvironment::default()
+    };
+    let (doc, report) = create_test_manager_with_prefix(temp_dir.path(), "TEST_CONFLICTS_WRAPPER_")
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(Vec::new())
+        .with_tool_environment(tools)
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    assert_eq!(doc["build"]["rustc-wrapper"].as_str(), Some("sccache"));
+    assert!(report.tool_conflicts[0].reason.contains("RUSTC_WRAPPER=/opt/bin/sccache"));
+    assert!(report.localized(cargo_optimize::i18n::Lang::En).contains("Other build tools:"));
+}
+
+#[test]
+fn test_memory_pressure_lowers_jobs_and_reverts() {
+    use cargo_optimize::hardware::{get_fallback_hardware, HardwareInfo};
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::metrics::{BuildRecord, MetricsHistory};
+    use cargo_optimize::state::State;
+    
+    let temp_dir = setup_test_env();
+    let cargo_dir = temp_dir.path().join(".cargo");
+    fs::write(cargo_dir.join("config.toml"), "[build]\njobs = 8\n\n[profile.release]\nlto = \"thin\"\n").unwrap();
+    let hardware = HardwareInfo { cpu_count: 8, total_memory: 16 << 30, ..get_fallback_hardware() };
+    
+    let mut history = MetricsHistory::default();
+    let mut record = BuildRecord::new(120.0, "release");
+    record.cpu_count = 8;
+    record.peak_memory_bytes = Some(15 << 30);
+    record.memory_budget_bytes = Some(16 << 30);
+    history.record(record);
+    history.save(&MetricsHistory::path_in(&cargo_dir)).unwrap();
+    
+    let manager = |prefix: &str| {
+        create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(Vec::new())
+    };
+    
+    // Recommended by default
+    let (doc, report) = manager("TEST_MEMORY_RECOMMEND_").plan_with_hardware(&hardware).unwrap();
+    assert_eq!(doc["build"]["jobs"].as_integer(), Some(8));
+    assert!(
+        report.recommendations.iter().any(|r| r.contains("`build.jobs = 5`") && r.contains("`profile.release.lto = false`")),
+        "{:?}",
+        report.recommendations
+    );
+    
+    // Applied and remembered with the apply policy
+    let mut applying = manager("TEST_MEMORY_APPLY_");
+    applying.config_mut().global.memory_pressure_policy = MemoryPressurePolicy::Apply;
+    applying.apply_with_hardware(&hardware).unwrap();
+    let written: DocumentMut = fs::read_to_string(cargo_dir.join("config.toml")).unwrap().parse().unwrap();
+    assert_eq!(written["build"]["jobs"].as_integer(), Some(5));
+    assert_eq!(written["profile"]["release"]["lto"].as_bool(), Some(false));
+    assert!(written.to_string().contains("CO-MEMORY-001"));
+    let state = State::load(&State::path_in(&cargo_dir));
+    assert_eq!(state.memory.as_ref().map(|m| m.changes.entries.len()), Some(2));
+    
+    // Kept on the same machine, reverted on another
+    let (doc, _) = manager("TEST_MEMORY_KEEP_").plan_with_hardware(&hardware).unwrap();
+    assert_eq!(doc["build"]["jobs"].as_integer(), Some(5));
+    let larger = HardwareInfo { total_memory: 64 << 30, ..hardware.clone() };
+    let report = manager("TEST_MEMORY_REVERT_").apply_with_hardware(&larger).unwrap();
+    assert!(report.decision_for("memory pressure").unwrap().reason.starts_with("reverted"));
+    let written: DocumentMut = fs::read_to_string(cargo_dir.join("config.toml")).unwrap().parse().unwrap();
+    assert_eq!(written["build"]["jobs"].as_integer(), Some(8));
+    assert_eq!(written["profile"]["release"]["lto"].as_str(), Some("thin"));
+    assert!(State::load(&State::path_in(&cargo_dir)).memory.is_none());
+}
+
+#[test]
+fn test_adaptive_jobs() {
+    use cargo_optimize::config::{AdaptiveJobsSettings, JobCount};
+    use cargo_optimize::hardware::load::SystemLoad;
+    use cargo_optimize::hardware::{get_fallback_hardware, HardwareInfo};
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::metrics::{BuildRecord, MetricsHistory};
+    use cargo_optimize::state::State;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    
+    let temp_dir = setup_test_env();
+    let cargo_dir = temp_dir.path().join(".cargo");
+    let hardware = HardwareInfo { cpu_count: 16, ..get_fallback_hardware() };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let build = |finished_at: u64, secs: f64, load: f64| BuildRecord {
+        finished_at,
+        background_load: Some(load),
+        ..BuildRecord::new(secs, "dev")
+    };
+    let history_path = MetricsHistory::path_in(&cargo_dir);
+    let mut history = MetricsHistory::default();
+    history.record(build(now - 120, 100.0, 6.0));
+    history.record(build(now - 60, 90.0, 6.0));
+    history.save(&history_path).unwrap();
+    
+    let manager = |prefix: &str, load: f64| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(Vec::new())
+            .with_system_load(SystemLoad { busy_cores: load, cpu_pressure: Some(20.0) });
+        manager.config_mut().global.adaptive_jobs =
+            Some(AdaptiveJobsSettings { min: JobCount::Fixed(2), max: Some(JobCount::Fixed(12)) });
+        manager
+    };
+    
+    // 6 cores busy (median of now and two builds): 10 of 16 left
+    let report = manager("TEST_ADAPTIVE_BUSY_", 4.0).apply_with_hardware(&hardware).unwrap();
+    let reason = &report.decision_for("build.jobs").unwrap().reason;
+    assert!(reason.starts_with("6.0 of 16 cores busy with other work (median of now and 2 recent builds)"), "{}", reason);
+    assert!(reason.contains("waiting for a CPU 20%"));
+    let written: DocumentMut = fs::read_to_string(cargo_dir.join("config.toml")).unwrap().parse().unwrap();
+    assert_eq!(written["build"]["jobs"].as_integer(), Some(10));
+    assert!(written.to_string().contains("CO-JOBS-002"));
+    let adjustments = State::load(&State::path_in(&cargo_dir)).jobs_adjustments;
+    assert_eq!(adjustments.iter().map(|a| (a.busy_cores, a.jobs)).collect::<Vec<_>>(), [(6, 10)]);
+    
+    // Builds after the adjustment were faster; the machine is quiet again, capped at the maximum
+    let at = adjustments[0].recorded_at;
+    history.record(build(at + 1, 60.0, 1.0));
+    history.record(build(at + 2, 50.0, 1.0));
+    history.save(&history_path).unwrap();
+    let report = manager("TEST_ADAPTIVE_QUIET_", 0.0).apply_with_hardware(&hardware).unwrap();
+    let reason = &report.decision_for("build.jobs").unwrap().reason;
+    assert!(reason.contains("12 jobs within 2..=12"), "{}", reason);
+    assert!(reason.contains("since the last adjustment to 10 jobs, builds took 42% less"), "{}", reason);
+    let written: DocumentMut = fs::read_to_string(cargo_dir.join("config.toml")).unwrap().parse().unwrap();
+    assert_eq!(written["build"]["jobs"].as_integer(), Some(12));
+    assert_eq!(State::load(&State::path_in(&cargo_dir)).jobs_adjustments.len(), 2);
+    
+    // Turning the mode off removes the adapted value
+    let mut fixed = create_test_manager_with_prefix(temp_dir.path(), "TEST_ADAPTIVE_OFF_")
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(Vec::new());
+    fixed.config_mut().global.adaptive_jobs = None;
+    let report = fixed.apply_with_hardware(&hardware).unwrap();
+    assert!(report.decision_for("build.jobs").unwrap().reason.starts_with("no longer adapted"));
+    let written: DocumentMut = fs::read_to_string(cargo_dir.join("config.toml")).unwrap().parse().unwrap();
+    assert!(written.get("build").and_then(|build| build.get("jobs")).is_none());
+}
+
+#[test]
+fn test_branch_switch_cost_recommendation() {
+    use cargo_optimize::hardware::{get_fallback_hardware, DiskInfo, HardwareInfo};
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::metrics::{BranchActivity, BuildRecord, MetricsHistory};
+    
+    let temp_dir = setup_test_env();
+    let cargo_dir = temp_dir.path().join(".cargo");
+    fs::create_dir_all(temp_dir.path().join("target/debug")).unwrap();
+    fs::write(temp_dir.path().join("target/debug/libdemo.rlib"), vec![0u8; 8 << 20]).unwrap();
+    
+    let mut history = MetricsHistory::default();
+    for (branch, secs) in [("main", 30.0), ("main", 12.0), ("feature", 95.0), ("feature", 10.0), ("main", 85.0), ("main", 14.0)] {
+        history.record(BuildRecord::new(secs, "dev").with_branch(Some(branch.to_string())));
+    }
+    history.save(&MetricsHistory::path_in(&cargo_dir)).unwrap();
+    
+    let activity = BranchActivity {
+        days: 14,
+        switches: 40,
+        branches: ["main", "feature", "fix"].into_iter().map(String::from).collect(),
+    };
+    let hardware = |available_bytes: u64| HardwareInfo {
+        disks: vec![DiskInfo {
+            mount_point: if cfg!(windows) { "C:\\".to_string() } else { "/".to_string() },
+            total_space: 100 << 30,
+            available_space: available_bytes,
+            file_system: "ext4".to_string(),
+            is_ssd: None,
+        }],
+        ..get_fallback_hardware()
+    };
+    let plan = |prefix: &str, available_bytes: u64| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(Vec::new())
+            .with_branch_activity(activity.clone());
+        manager.config_mut().global.min_free_disk_gb = 10.0;
+        manager.plan_with_hardware(&hardware(available_bytes)).unwrap().1
+    };
+    
+    // 90s after a switch against 12s on the same branch, 20 switches a week
+    let report = plan("TEST_BRANCH_DISK_", 500 << 30);
+    let advice = report.recommendations.iter().find(|r| r.contains("branch switch")).expect("branch advice");
+    assert!(advice.contains("take 90s against 12s") && advice.contains("about 26 minutes a week"), "{}", advice);
+    assert!(advice.contains("CARGO_TARGET_DIR=target/branch-"), "{}", advice);
+    
+    // Two more copies of the target directory do not fit in the spare disk
+    let mut report = plan("TEST_BRANCH_SCCACHE_", (10 << 30) + (8 << 20));
+    report.recommendations.retain(|r| r.contains("branch switch"));
+    assert!(report.recommendations[0].contains("enable sccache"), "{:?}", report.recommendations);
+}
+
+#[test]
+fn test_no_external_tools_uses_only_the_toolchain() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    let toolchain = |release: &str| ToolchainInfo::parse_verbose_version(&format!("release: {}\n", release)).unwrap();
+    let plan = |prefix: &str, platform: Platform, release: &str| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_platform(Some(platform))
+            .with_toolchain(toolchain(release))
+            .with_installed_commands(vec!["rustc".to_string(), "mold".to_string(), "clang".to_string()]);
+        manager.config_mut().global.no_external_tools = true;
+        manager.plan_with_hardware(&get_fallback_hardware()).unwrap()
+    };
+    
+    // Installed linkers are ignored; an older rustc is told to update
+    let (doc, report) = plan("TEST_NO_TOOLS_LINUX_", Platform::Linux, "1.85.0");
+    assert!(doc.get("target").is_none());
+    assert!(report.decision_for("linker").unwrap().reason.contains("system linker"));
+    assert!(report.recommendations.iter().any(|r| r.contains("rustup update stable")));
+    assert_eq!(doc["profile"]["dev"]["debug"].as_str(), Some("line-tables-only"));
+    assert!(report.decision_for("sccache").unwrap().reason.contains("no_external_tools"));
+    
+    let (_, report) = plan("TEST_NO_TOOLS_LINUX_NEW_", Platform::Linux, "1.90.0");
+    assert!(report.decision_for("linker").unwrap().reason.contains("already links with the bundled rust-lld"));
+    assert!(!report.recommendations.iter().any(|r| r.contains("rustup update")));
+    
+    // rust-lld ships with the toolchain on Windows
+    let (doc, _) = plan("TEST_NO_TOOLS_WINDOWS_", Platform::Windows, "1.85.0");
+    assert_eq!(doc["target"][Platform::Windows.target_triple().unwrap()]["linker"].as_str(), Some("rust-lld"));
+    
+    let (doc, report) = plan("TEST_NO_TOOLS_MACOS_", Platform::MacOs, "1.85.0");
+    assert!(doc.get("target").is_none());
+    assert!(report.decision_for("linker").unwrap().reason.contains("macOS"));
+}
+
+#[test]
+fn test_skipped_decisions_are_bounded() {
+    use cargo_optimize::i18n::Lang;
+    use cargo_optimize::report::{bounded, OptimizationReport, SkipReason, MAX_SKIPPED_DECISIONS};
+    
+    let mut report = OptimizationReport::new();
+    report.applied("linker", "mold");
+    for index in 0..MAX_SKIPPED_DECISIONS + 5 {
+        report.skipped(format!("provider {}", index), SkipReason::NotNeeded, "not a match");
+    }
+    report.applied("sccache", "installed");
+    report.skipped("linker lld", SkipReason::RolledBack, "rolled back after it broke a build");
+    report.skipped("build.rustc-wrapper", SkipReason::Policy, "forbidden by the machine policy");
+    
+    // The report keeps every decision; only what is rendered is bounded
+    assert_eq!(report.skipped_decisions().count(), MAX_SKIPPED_DECISIONS + 7);
+    let (listed, more_skipped) = bounded(&report.decisions);
+    assert_eq!(more_skipped, 5);
+    assert_eq!(listed.len(), MAX_SKIPPED_DECISIONS + 4);
+    let rendered = report.localized(Lang::En);
+    assert!(!rendered.contains("provider 100 "), "{}", rendered);
+    for setting in ["sccache", "linker lld", "build.rustc-wrapper"] {
+        assert!(rendered.contains(&format!("  - {} [", setting)), "{}", rendered);
+    }
+    assert!(rendered.contains("  - 5 more skipped\n"), "{}", rendered);
+}
+
+#[test]
+fn test_skipped_settings_say_why() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::i18n::Lang;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::report::{DecisionOutcome, SkipReason};
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    let toolchain = ToolchainInfo::parse_verbose_version("release: 1.85.0\n").unwrap();
+    let plan = |prefix: &str, no_external_tools: bool| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_platform(Some(Platform::Linux))
+            .with_toolchain(toolchain.clone())
+            .with_installed_commands(vec!["rustc".to_string()]);
+        manager.config_mut().global.no_external_tools = no_external_tools;
+        manager.plan_with_hardware(&get_fallback_hardware()).unwrap().1
+    };
+    
+    // A linker that is not installed is reported, not left out
+    let report = plan("TEST_SKIP_WHY_MISSING_", false);
+    let linker = report.decision_for("linker").unwrap();
+    assert_eq!(linker.outcome, DecisionOutcome::Skipped);
+    assert_eq!(linker.skip_reason, Some(SkipReason::MissingTool));
+    assert!(report.localized(Lang::En).contains("linker [skipped: missing-tool]"));
+    
+    let report = plan("TEST_SKIP_WHY_OPT_OUT_", true);
+    assert_eq!(report.decision_for("sccache").unwrap().skip_reason, Some(SkipReason::UserOptOut));
+    assert!(report.skipped_because(SkipReason::UserOptOut).any(|d| d.setting == "sccache"));
+    assert!(report.decisions.iter().all(|d| (d.outcome == DecisionOutcome::Applied) == d.skip_reason.is_none()));
+}
+
+#[test]
+fn test_version_gated_cargo_features() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let plan = |prefix: &str, release: &str, configure: &dyn Fn(&mut Config)| {
+        let toolchain = ToolchainInfo::parse_verbose_version(&format!("release: {}\n", release)).unwrap();
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix)
+            .with_platform(Some(Platform::Linux))
+            .with_toolchain(toolchain)
+            .with_installed_commands(Vec::new());
+        configure(manager.config_mut());
+        manager.plan_with_hardware(&get_fallback_hardware()).unwrap()
+    };
+    
+    // Sparse is written where cargo supports it but does not default to it
+    let (doc, report) = plan("TEST_SPARSE_OLD_", "1.69.0", &|_| {});
+    assert_eq!(doc["registries"]["crates-io"]["protocol"].as_str(), Some("sparse"));
+    assert!(report.decision_for("registries.crates-io.protocol").is_some());
+    fs::write(&config_path, doc.to_string()).unwrap();
+    let (doc, _) = plan("TEST_SPARSE_NEW_", "1.85.0", &|_| {});
+    assert!(doc.get("registries").is_none(), "{}", doc);
+    let (doc, _) = plan("TEST_SPARSE_ANCIENT_", "1.60.0", &|_| {});
+    assert!(doc.get("registries").is_none());
+    
+    fs::write(&config_path, "[registries.crates-io]\nprotocol = \"git\"\n").unwrap();
+    let (doc, _) = plan("TEST_SPARSE_USER_", "1.69.0", &|_| {});
+    assert_eq!(doc["registries"]["crates-io"]["protocol"].as_str(), Some("git"));
+    fs::remove_file(&config_path).unwrap();
+    
+    // gitoxide needs nightly, the aggressive level and the opt-in
+    let aggressive = |config: &mut Config| {
+        config.global.gitoxide = true;
+        config.global.optimization_level = OptimizationLevel::Aggressive;
+    };
+    let (doc, report) = plan("TEST_GITOXIDE_", "1.87.0-nightly", &aggressive);
+    assert_eq!(doc["unstable"]["gitoxide"].as_array().map(|a| a.len()), Some(1));
+    assert!(report.decision_for("unstable.gitoxide").is_some());
+    fs::write(&config_path, doc.to_string()).unwrap();
+    
+    let (doc, report) = plan("TEST_GITOXIDE_STABLE_", "1.87.0", &aggressive);
+    assert!(doc.get("unstable").is_none(), "{}", doc);
+    assert!(report.decision_for("unstable.gitoxide").unwrap().reason.contains("nightly"));
+    let (_, report) = plan("TEST_GITOXIDE_BALANCED_", "1.87.0-nightly", &|config| config.global.gitoxide = true);
+    assert!(report.decision_for("unstable.gitoxide").unwrap().reason.contains("aggressive"));
+}
+
+#[test]
+fn test_custom_profiles_from_manifest_and_config() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join("Cargo.toml"),
+        "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n\
+         [profile.profiling]\ninherits = \"release\"\ndebug = true\n\n\
+         [profile.release-lto]\ninherits = \"release\"\n\n[profile.release]\nlto = \"thin\"\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("cargo-optimize.toml"),
+        "[profiles.release-lto]\nlto = \"fat\"\ncodegen_units = 1\n\n\
+         [profiles.ci]\ninherits = \"dev\"\ndebug = \"0\"\n\n\
+         [profiles.orphan]\nopt_level = \"1\"\n",
+    )
+    .unwrap();
+    
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_CUSTOM_PROFILES_");
+    let profiles = &manager.config().profiles;
+    assert_eq!(profiles["profiling"].name, "profiling");
+    assert_eq!(profiles["release-lto"].name, "release-lto");
+    assert_eq!(profiles["ci"].inherits.as_deref(), Some("dev"));
+    
+    let (doc, report) = manager
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(Vec::new())
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    
+    // Manifest profiles are managed without repeating `inherits`
+    assert_eq!(doc["profile"]["release-lto"]["lto"].as_str(), Some("fat"));
+    assert_eq!(doc["profile"]["release-lto"]["codegen-units"].as_integer(), Some(1));
+    assert!(doc["profile"]["release-lto"].get("inherits").is_none());
+    // Discovered profiles without settings are left alone
+    assert!(doc["profile"].get("profiling").is_none());
+    // Profiles only in cargo-optimize.toml need `inherits`
+    assert_eq!(doc["profile"]["ci"]["inherits"].as_str(), Some("dev"));
+    assert_eq!(doc["profile"]["ci"]["debug"].as_integer(), Some(0));
+    assert!(doc["profile"].get("orphan").is_none());
+    assert!(report.decision_for("profile.orphan").unwrap().reason.contains("inherits"));
+    
+    assert_eq!(
+        custom_profiles("[profile.dev]\nopt-level = 1\n[profile.a]\ninherits = \"dev\"\n[profile.b]\n")
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![("a".to_string(), Some("dev".to_string())), ("b".to_string(), None)]
+    );
+}
+
+#[test]
+fn test_container_build_guidance() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    fs::write(temp_dir.path().join("Cargo.toml"), "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n").unwrap();
+    fs::write(
+        temp_dir.path().join("Dockerfile"),
+        "FROM rust:1.85 AS builder\nWORKDIR /build\nCOPY . .\nRUN cargo build --release\n\n\
+         FROM debian:bookworm-slim\nCOPY --from=builder /build/target/release/demo /usr/local/bin/\n",
+    )
+    .unwrap();
+    fs::create_dir_all(temp_dir.path().join("docker")).unwrap();
+    fs::write(temp_dir.path().join("docker/web.Dockerfile"), "FROM nginx\nCOPY . /usr/share/nginx/html\n").unwrap();
+    
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_CONTAINER_BUILDS_");
+    let (_, report) = manager
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(Vec::new())
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    
+    // Only the Dockerfile running cargo is reported
+    assert_eq!(report.container_builds.len(), 1);
+    let build = &report.container_builds[0];
+    assert_eq!(build.dockerfile, std::path::PathBuf::from("Dockerfile"));
+    assert_eq!(build.advice.len(), 3);
+    assert!(build.advice[0].starts_with("Dockerfile:3:"));
+    assert!(build.snippet.contains("WORKDIR /build\n"));
+    assert!(build.snippet.contains("--mount=type=cache,target=/build/target"));
+    assert!(build.snippet.contains("cp target/release/demo /usr/local/bin/demo"));
+    
+    let rendered = report.localized(cargo_optimize::i18n::Lang::En);
+    assert!(rendered.contains("Container builds:\n"));
+    assert!(rendered.contains("Recommended build stage for Dockerfile:\n      # syntax=docker/dockerfile:1\n"));
+}
+
+#[test]
+fn test_target_dir_migration() {
+    use cargo_optimize::relocation::MigrationMode;
+    
+    let temp_dir = setup_test_env();
+    let target = temp_dir.path().join("target");
+    for output in ["debug", "release"] {
+        fs::create_dir_all(target.join(output).join(".fingerprint").join("dep-1a2b")).unwrap();
+        fs::create_dir_all(target.join(output).join("deps")).unwrap();
+        fs::write(target.join(output).join(".fingerprint").join("dep-1a2b").join("lib-dep"), "0123abcd").unwrap();
+        fs::write(target.join(output).join("deps").join("libdep-1a2b.rlib"), vec![0u8; 1024]).unwrap();
+    }
+    
+    let manager = |prefix: &str, mode| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix);
+        manager.config_mut().global.target_dir_layout = TargetDirLayout::PerProfile;
+        manager.config_mut().global.target_dir_migration = mode;
+        manager
+    };
+    
+    let (_, report) = manager("TEST_MIGRATION_OFF_", MigrationMode::Off).plan().unwrap();
+    assert!(report.relocations.is_empty());
+    
+    // dev and test both start from target/debug; release already builds into target/release
+    let report = manager("TEST_MIGRATION_COPY_", MigrationMode::Copy).apply_with_report().unwrap();
+    let destinations: Vec<PathBuf> = report.relocations.iter().map(|r| r.to.clone()).collect();
+    assert!(destinations.iter().any(|to| to.ends_with("dev/debug")), "{:?}", destinations);
+    assert!(destinations.iter().any(|to| to.ends_with("bench/release")), "{:?}", destinations);
+    let decision = report.decision_for("target-dir migration").unwrap();
+    assert!(decision.reason.starts_with("copied 3 output dir(s) (6 files"), "{}", decision.reason);
+    for copied in ["dev/debug", "test/debug", "bench/release"] {
+        assert!(target.join(copied).join("deps").join("libdep-1a2b.rlib").is_file(), "{}", copied);
+    }
+    assert!(target.join("debug").join(".fingerprint").is_dir());
+    
+    // Destinations cargo already uses are left alone on the next apply
+    let report = manager("TEST_MIGRATION_AGAIN_", MigrationMode::Move).apply_with_report().unwrap();
+    assert!(report.decision_for("target-dir migration").is_none());
+    assert!(target.join("debug").join(".fingerprint").is_dir());
+}
+
+#[test]
+fn test_manifest_profile_dry_run_and_save() {
+    let temp_dir = setup_test_env();
+    let manifest = "\
+[package]
+name = \"demo\"   # hand-aligned
+version = \"0.1.0\"
+
+[dependencies]
+anyhow = \"1\"
+
+[profile.dev]
+opt-level = 0 # fast builds
+";
+    fs::write(temp_dir.path().join("Cargo.toml"), manifest).unwrap();
+    // Entries of the user's own in `.cargo` stay there
+    let config = "[profile.dev]\ndebug-assertions = false # mine\nrustflags = [\"-Zshare-generics\"]\n";
+    fs::write(temp_dir.path().join(".cargo").join("config.toml"), config).unwrap();
+    
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_MANIFEST_PROFILES_");
+    let hardware = cargo_optimize::hardware::get_fallback_hardware();
+    let edit = manager.plan_manifest_profiles(&hardware).unwrap();
+    
+    // The dry run touches nothing and only adds lines
+    let diff = edit.diff();
+    assert!(edit.is_changed(), "{}", diff);
+    assert!(diff.starts_with("--- "), "{}", diff);
+    assert!(!diff.lines().skip(2).any(|line| line.starts_with('-')), "{}", diff);
+    assert_eq!(fs::read_to_string(temp_dir.path().join("Cargo.toml")).unwrap(), manifest);
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".cargo").join("config.toml")).unwrap(), config);
+    
+    assert!(edit.save().unwrap());
+    let written = fs::read_to_string(temp_dir.path().join("Cargo.toml")).unwrap();
+    assert!(written.starts_with(&manifest[..manifest.len() - 1]), "{}", written);
+    let doc: DocumentMut = written.parse().unwrap();
+    assert!(doc["profile"].as_table().unwrap().len() > 1, "{}", written);
+    for copied in ["debug-assertions", "rustflags", "# mine", "cargo-optimize:"] {
+        assert!(!written.contains(copied), "{}", written);
+    }
+}
+
+#[test]
+fn test_upgrade_preview() {
+    use cargo_optimize::migration::ChangeKind;
+    
+    let temp_dir = setup_test_env();
+    let hardware = cargo_optimize::hardware::get_fallback_hardware();
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_UPGRADE_PREVIEW_");
+    
+    // Before the first apply every planned setting is new
+    let preview = manager.preview_upgrade(&hardware).unwrap();
+    assert!(preview.applied_version.is_none());
+    assert!(!preview.is_empty());
+    assert!(preview.changes.iter().all(|change| change.kind == ChangeKind::Added && !change.previously_written));
+    
+    manager.apply_with_hardware(&hardware).unwrap();
+    let preview = manager.preview_upgrade(&hardware).unwrap();
+    assert!(preview.is_empty(), "{}", preview.render());
+    assert_eq!(preview.applied_version.as_deref(), Some(cargo_optimize::version()));
+    
+    // Different defaults change a value the earlier apply wrote
+    manager.config_mut().profiles.get_mut("dev").unwrap().incremental = Some(false);
+    let config_before = fs::read_to_string(temp_dir.path().join(".cargo").join("config.toml")).unwrap();
+    let preview = manager.preview_upgrade(&hardware).unwrap();
+    let change = preview.behavior_changes().find(|change| change.dotted_key() == "profile.dev.incremental");
+    assert_eq!(change.map(|change| change.upcoming.as_deref()), Some(Some("false")), "{}", preview.render());
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".cargo").join("config.toml")).unwrap(), config_before);
+}
+
+#[test]
+fn test_sccache_dist_scheduler() {
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::sccache_dist::Reachability;
+    
+    let temp_dir = setup_test_env();
+    let hardware = cargo_optimize::hardware::get_fallback_hardware();
+    let manager = |reachability: Reachability, scheduler: Option<&str>, installed: &[&str]| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_SCCACHE_DIST_")
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(installed.iter().map(|command| command.to_string()).collect())
+            .with_sccache_dist_reachability(reachability);
+        manager.config_mut().global.sccache_dist_scheduler = scheduler.map(str::to_string);
+        manager
+    };
+    let farm = Some("https://sccache.example.com");
+    let wrapper = |doc: &DocumentMut| doc.get("build").and_then(|build| build.get("rustc-wrapper")).map(|item| item.to_string());
+    
+    // Not installed: nothing written, install advice given
+    let (doc, report) = manager(Reachability::Reachable { latency_ms: 3 }, farm, &[]).plan_with_hardware(&hardware).unwrap();
+    assert_eq!(wrapper(&doc), None);
+    assert!(report.decision_for("sccache-dist").unwrap().reason.contains("not installed"));
+    assert!(report.recommendations.iter().any(|r| r.contains("cargo install sccache")), "{:?}", report.recommendations);
+    
+    let report = manager(Reachability::Reachable { latency_ms: 3 }, farm, &["sccache"]).apply_with_hardware(&hardware).unwrap();
+    assert!(report.decision_for("build.rustc-wrapper").unwrap().reason.contains("https://sccache.example.com (3 ms away)"));
+    assert!(report.recommendations.iter().any(|r| r.contains("SCCACHE_NO_DIST_COMPILE=1")), "{:?}", report.recommendations);
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let doc: DocumentMut = fs::read_to_string(&config_path).unwrap().parse().unwrap();
+    assert!(wrapper(&doc).unwrap().contains("\"sccache\" # cargo-optimize: CO-SCCACHE-003"), "{:?}", wrapper(&doc));
+    
+    // sccache falls back to local builds, so the wrapper stays while the farm is down
+    let unreachable = Reachability::Unreachable { reason: "connection refused".to_string() };
+    let (doc, report) = manager(unreachable.clone(), farm, &["sccache"]).plan_with_hardware(&hardware).unwrap();
+    assert!(wrapper(&doc).is_some());
+    assert!(report.decision_for("sccache-dist").unwrap().reason.contains("unreachable: connection refused"));
+    
+    // Without a scheduler the wrapper goes again
+    let (doc, _) = manager(unreachable, None, &["sccache"]).plan_with_hardware(&hardware).unwrap();
+    assert_eq!(wrapper(&doc), None);
+}
+
+#[test]
+fn test_cli_flags_instead_of_writing() {
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[profile.dev]\nincremental = false\n").unwrap();
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_CLI_FLAGS_")
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(Vec::new());
+    
+    let flags = manager.cli_flags_with_hardware(&cargo_optimize::hardware::get_fallback_hardware()).unwrap();
+    
+    let args = flags.args();
+    assert!(args.windows(2).any(|pair| pair == ["--config", "profile.dev.incremental=true"]), "{:?}", args);
+    assert!(args.iter().step_by(2).all(|flag| flag == "--config"), "{:?}", args);
+    assert!(args.iter().skip(1).step_by(2).all(|arg| !arg.contains(' ')), "{:?}", args);
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), "[profile.dev]\nincremental = false\n");
+}
+
+#[test]
+fn test_nix_shell_settings() {
+    use cargo_optimize::conflicts::ToolEnvironment;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::nix::{NixEnvironment, NixFormat};
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let original = "[target.x86_64-unknown-linux-gnu]\nlinker = \"/nix/store/abc-clang-17/bin/clang\"\n";
+    fs::write(&config_path, original).unwrap();
+    let nix = NixEnvironment::from_lookup(|var| (var == "IN_NIX_SHELL").then(|| "impure".to_string()), None);
+    let manager = || {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_NIX_");
+        manager.config_mut().global.default_jobs = Some(JobCount::Fixed(8));
+        manager
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(vec!["mold".to_string(), "clang".to_string()])
+            .with_tool_environment(ToolEnvironment { nix: nix.clone(), ..ToolEnvironment::default() })
+    };
+    
+    // Store paths the user wrote are reported, not rewritten behind their back
+    let (_, report) = manager().plan_with_hardware(&get_fallback_hardware()).unwrap();
+    let conflict = report.tool_conflicts.iter().find(|c| c.tool == "nix").expect("no Nix conflict");
+    assert!(conflict.reason.contains("a Nix shell"), "{}", conflict.reason);
+    assert!(
+        report.recommendations.iter().any(|r| r.contains("`target.x86_64-unknown-linux-gnu.linker`")),
+        "{:?}",
+        report.recommendations
+    );
+    
+    // The snippet refers to tools by name and leaves the config alone
+    let snippet = manager().nix_snippet_with_hardware(Some(NixFormat::Devenv), &get_fallback_hardware()).unwrap();
+    assert!(snippet.env.contains(&("CARGO_BUILD_JOBS".to_string(), "8".to_string())), "{:?}", snippet.env);
+    assert!(snippet.env.contains(&("CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_LINKER".to_string(), "clang".to_string())));
+    assert!(snippet.packages.contains(&"mold".to_string()), "{:?}", snippet.packages);
+    assert!(snippet.render().starts_with("# devenv.nix\n{ pkgs, ... }:\n"));
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), original);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_linker_rollback_after_broken_build() {
+    use cargo_optimize::config::OptimizationLevel;
+    use cargo_optimize::guardrail;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::state::State;
+    use std::process::Command;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "[build]\njobs = 4\n").unwrap();
+    let manager = || {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_GUARDRAIL_");
+        manager.config_mut().global.optimization_level = OptimizationLevel::Aggressive;
+        manager
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(vec!["mold".to_string(), "ld.lld".to_string(), "clang".to_string()])
+    };
+    let shell = |script: &str| {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    };
+    
+    manager().apply_with_hardware(&get_fallback_hardware()).unwrap();
+    assert!(fs::read_to_string(&config_path).unwrap().contains("-fuse-ld=mold"));
+    assert_eq!(manager().unverified_linker().as_deref(), Some("mold"));
+    
+    // A failure in the code is not the linker's fault
+    let build = guardrail::guard(&manager(), shell("echo 'error[E0308]: mismatched types' >&2; exit 101")).unwrap();
+    assert!(build.failure.is_none() && build.rollback.is_none());
+    
+    let build = guardrail::guard(
+        &manager(),
+        shell("echo '  = note: mold: fatal: unknown command line option: --no-undefined-version' >&2; exit 101"),
+    )
+    .unwrap();
+    assert!(!build.status.success());
+    let rollback = build.rollback.expect("linker rolled back");
+    assert!(rollback.decision_for("target.x86_64-unknown-linux-gnu.linker").is_some(), "{}", rollback);
+    let content = fs::read_to_string(&config_path).unwrap();
+    assert!(!content.contains("mold") && content.contains("jobs = "), "{}", content);
+    let state = State::load(&manager().state_path());
+    assert!(state.is_incompatible("mold") && state.unverified_linker.is_none());
+    
+    // The next apply skips mold, and a successful build confirms its choice
+    let report = manager().apply_with_hardware(&get_fallback_hardware()).unwrap();
+    assert!(report.decision_for("linker mold").unwrap().reason.starts_with("rolled back"));
+    assert!(fs::read_to_string(&config_path).unwrap().contains("-fuse-ld=lld"));
+    assert_eq!(manager().unverified_linker().as_deref(), Some("lld"));
+    assert!(guardrail::guard(&manager(), shell("true")).unwrap().status.success());
+    assert_eq!(manager().unverified_linker(), None);
+}
+
+#[test]
+fn test_linker_install_recommendation_is_opt_in() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::tools::{self, LinkerInstallMode};
+    
+    let temp_dir = setup_test_env();
+    let plan = |prefix: &str, mode: LinkerInstallMode| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), prefix);
+        manager.config_mut().global.linker_install.mode = mode;
+        let (_, report) = manager
+            .with_platform(Some(Platform::Linux))
+            .with_installed_commands(vec!["clang".to_string()])
+            .plan_with_hardware(&get_fallback_hardware())
+            .unwrap();
+        report.recommendations.into_iter().filter(|r| r.starts_with("No fast linker")).collect::<Vec<_>>()
+    };
+    
+    assert!(plan("TEST_LINKER_INSTALL_OFF_", LinkerInstallMode::Off).is_empty());
+    let os_release = fs::read_to_string("/etc/os-release").unwrap_or_default();
+    let expected: Vec<String> = tools::package_command("mold", Platform::Linux, &os_release)
+        .map(|command| format!("No fast linker is installed; install mold with: {}", command))
+        .into_iter()
+        .collect();
+    assert_eq!(plan("TEST_LINKER_INSTALL_PACKAGE_", LinkerInstallMode::PackageCommand), expected);
+}
+
+#[test]
+fn test_effective_config_provenance() {
+    let temp_dir = setup_test_env();
+    let config_file = temp_dir.path().join("cargo-optimize.toml");
+    fs::write(
+        &config_file,
+        r#"
+[global]
+verbose = true
+min_free_disk_gb = 2.5
+
+[contexts.ci.global]
+min_free_disk_gb = 1.0
+
+[profiles.release]
+opt_level = "3"
+"#,
+    )
+    .unwrap();
+    std::env::set_var("TEST_EFFECTIVE_GLOBAL__DEFAULT_JOBS", "12");
+    let effective = effective_in(temp_dir.path(), "TEST_EFFECTIVE_", BuildContext::Ci).unwrap();
+    std::env::remove_var("TEST_EFFECTIVE_GLOBAL__DEFAULT_JOBS");
+    
+    let jobs = effective.get("global.default_jobs").expect("jobs setting");
+    assert_eq!(jobs.to_string(), "global.default_jobs=12 from env TEST_EFFECTIVE_GLOBAL__DEFAULT_JOBS");
+    assert_eq!(effective.config.global.default_jobs, Some(JobCount::Fixed(12)));
+    assert_eq!(effective.get("global.verbose").unwrap().source, ConfigSource::File(config_file.clone()));
+    assert_eq!(
+        effective.get("global.min_free_disk_gb").unwrap().source,
+        ConfigSource::Context { file: config_file.clone(), context: BuildContext::Ci }
+    );
+    assert_eq!(effective.get("profiles.release.opt_level").unwrap().value, "\"3\"");
+    assert_eq!(effective.get("global.use_sccache").unwrap().source, ConfigSource::Default);
+    // Profiles without a job count get the optimization level's share of the cores
+    assert_eq!(
+        effective.get("profiles.dev.jobs").unwrap().source,
+        ConfigSource::Derived("hardware detection")
+    );
+    assert!(effective.render().contains("global.verbose=true from file "), "{}", effective.render());
+}
+
+#[test]
+fn test_deny_list_suppresses_settings() {
+    use cargo_optimize::config::OptimizationLevel;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::report::DecisionOutcome;
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join("cargo-optimize.toml"),
+        r#"
+[global]
+optimization_level = "aggressive"
+
+[deny]
+flags = ["-C target-cpu=native"]
+linkers = ["mold"]
+features = ["profile.*.lto"]
+"#,
+    )
+    .unwrap();
+    let nightly = ToolchainInfo::parse_verbose_version("release: 1.80.0-nightly\n").unwrap();
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_DENY_LIST_").with_toolchain(nightly);
+    assert_eq!(manager.config().global.optimization_level, OptimizationLevel::Aggressive);
+    let (doc, report) = manager
+        .with_platform(Some(Platform::Linux))
+        .with_installed_commands(vec!["mold".to_string(), "ld.lld".to_string(), "clang".to_string()])
+        .plan_with_hardware(&get_fallback_hardware())
+        .unwrap();
+    
+    let rendered = doc.to_string();
+    assert!(rendered.contains("-fuse-ld=lld"), "{}", rendered);
+    assert!(!rendered.contains("mold") && !rendered.contains("target-cpu"), "{}", rendered);
+    assert!(doc["profile"]["release"].get("lto").is_none(), "{}", rendered);
+    
+    let suppressed: Vec<&str> = report
+        .decisions
+        .iter()
+        .filter(|decision| decision.outcome == DecisionOutcome::Suppressed)
+        .map(|decision| decision.setting.as_str())
+        .collect();
+    assert!(suppressed.contains(&"linker mold"), "{:?}", suppressed);
+    assert!(suppressed.contains(&"profile.release.lto"), "{:?}", suppressed);
+    assert!(suppressed.iter().any(|setting| setting.ends_with(".rustflags")), "{:?}", suppressed);
+    assert!(report.to_string().contains("[suppressed by policy]"));
+    
+    fs::write(temp_dir.path().join("cargo-optimize.toml"), "[deny]\nlinkers = [\"fastld\"]\n").unwrap();
+    let error = ConfigManager::new_with_base_dir(temp_dir.path(), "TEST_DENY_LIST_INVALID_").err().unwrap();
+    assert!(format!("{:#}", error).contains("unknown linker `fastld`"), "{:#}", error);
+}
+
+#[test]
+fn test_dev_drive_recommendation() {
+    use cargo_optimize::config::DevDrivePolicy;
+    use cargo_optimize::hardware::dev_drive::VolumeKind;
+    use cargo_optimize::hardware::{get_fallback_hardware, DiskInfo};
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::report::DecisionOutcome;
+    use std::collections::BTreeMap;
+    
+    let temp_dir = setup_test_env();
+    let dev_drive = TempDir::new().unwrap();
+    let dev_drive_mount = dev_drive.path().canonicalize().unwrap().to_string_lossy().into_owned();
+    let disk = |mount_point: &str, file_system: &str| DiskInfo {
+        mount_point: mount_point.to_string(),
+        total_space: 500 << 30,
+        available_space: 200 << 30,
+        file_system: file_system.to_string(),
+        is_ssd: Some(true),
+    };
+    let mut hardware = get_fallback_hardware();
+    hardware.disks = vec![disk("/", "NTFS"), disk(&dev_drive_mount, "ReFS")];
+    let plan = |kinds: BTreeMap<String, VolumeKind>, policy: DevDrivePolicy| {
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_DEV_DRIVE_")
+            .with_platform(Some(Platform::Windows))
+            .with_target_dir_env(None)
+            .with_volume_kinds(kinds);
+        manager.config_mut().global.dev_drive_policy = policy;
+        manager.plan_with_hardware(&hardware).unwrap()
+    };
+    
+    // No Dev Drive yet: how to create one, with the projected gain
+    let (_, report) = plan(BTreeMap::new(), DevDrivePolicy::Relocate);
+    assert!(report.recommendations.iter().any(|r| r.contains("-DevDrive") && r.contains("% faster")), "{:?}", report.recommendations);
+    
+    // An existing Dev Drive is only recommended by default
+    let kinds = BTreeMap::from([(dev_drive_mount.clone(), VolumeKind::DevDrive { trusted: true })]);
+    let (doc, report) = plan(kinds.clone(), DevDrivePolicy::Recommend);
+    assert!(doc.get("build").and_then(|build| build.get("target-dir")).is_none());
+    assert!(report.recommendations.iter().any(|r| r.contains(&dev_drive_mount) && r.contains("relocate")), "{:?}", report.recommendations);
+    
+    // With consent the target directory moves there
+    let (doc, report) = plan(kinds, DevDrivePolicy::Relocate);
+    let target_dir = doc["build"]["target-dir"].as_str().unwrap();
+    assert!(target_dir.starts_with(&dev_drive_mount), "{}", target_dir);
+    assert!(doc.to_string().contains("CO-DEVDRIVE-001"));
+    assert_eq!(report.decision_for("build.target-dir").unwrap().outcome, DecisionOutcome::Applied);
+}
+
+#[test]
+fn test_current_keys_are_not_deprecated() {
+    let temp_dir = setup_test_env();
+    let config_file = temp_dir.path().join("cargo-optimize.toml");
+    let content = "[global]\n# CI images lack clippy\nallow_installs = true\nverbose = true\nauto_migrate = true\n";
+    fs::write(&config_file, content).unwrap();
+    
+    // Keys of the current release load without warnings and are never rewritten
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_CURRENT_KEYS_");
+    assert!(manager.config().global.allow_installs);
+    assert!(manager.deprecations().is_empty());
+    assert_eq!(fs::read_to_string(&config_file).unwrap(), content);
+}
+
+#[test]
+fn test_disk_benchmark_shifts_recommendations() {
+    use cargo_optimize::hardware::disk_io::DiskBenchmark;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    let mut hardware = get_fallback_hardware();
+    hardware.cpu_count = 16;
+    hardware.total_memory = 64 << 30;
+    let plan = |sequential_mb_per_sec, random_write_iops, jobs: Option<usize>| {
+        let benchmark = DiskBenchmark {
+            dir: temp_dir.path().join("target"),
+            sequential_mb_per_sec,
+            random_write_iops,
+            measured_at: 0,
+        };
+        let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_DISK_BENCHMARK_")
+            .with_platform(Some(Platform::Linux))
+            .with_target_dir_env(None)
+            .with_disk_benchmark(benchmark);
+        manager.config_mut().global.default_jobs = jobs.map(JobCount::Fixed);
+        manager.plan_with_hardware(&hardware).unwrap().1.recommendations
+    };
+    
+    // A network disk: less debug info and a target directory in memory
+    let recommendations = plan(125, 3000, None);
+    assert!(recommendations.iter().any(|r| r.contains("IO-bound") && r.contains("line-tables-only")), "{:?}", recommendations);
+    assert!(recommendations.iter().any(|r| r.contains("/dev/shm/")), "{:?}", recommendations);
+    
+    // NVMe: the disk keeps up, so idle cores are what is left
+    let recommendations = plan(3000, 200_000, Some(4));
+    assert!(recommendations.iter().all(|r| !r.contains("line-tables-only")), "{:?}", recommendations);
+    assert!(recommendations.iter().any(|r| r.contains("CPU-bound") && r.contains("raise it to 16")), "{:?}", recommendations);
+}
+
+#[test]
+fn test_rejected_advice_is_not_repeated() {
+    use cargo_optimize::hardware::topology::{CpuTopology, NumaNode};
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::i18n::Lang;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    let node = |id, cpus: std::ops::Range<usize>| NumaNode { id, cpus: cpus.collect(), memory_bytes: None };
+    let mut hardware = get_fallback_hardware();
+    hardware.cpu_count = 64;
+    hardware.available_memory = 256 << 30;
+    hardware.topology = CpuTopology { sockets: 2, numa_nodes: vec![node(0, 0..32), node(1, 32..64)], core_types: None };
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_REJECTED_ADVICE_")
+        .with_platform(Some(Platform::Linux));
+    let plan = || manager.plan_with_hardware(&hardware).unwrap().1;
+    
+    let report = plan();
+    let numa = report.advice.iter().find(|advice| advice.key.as_deref() == Some("numa-binding")).unwrap();
+    assert!(report.recommendations.iter().any(|r| r.contains("numactl")));
+    manager.reject_advice(numa, "the build machine is shared, binding starves other jobs").unwrap();
+    
+    let report = plan();
+    assert!(report.recommendations.iter().all(|r| !r.contains("numactl")), "{:?}", report.recommendations);
+    assert_eq!(report.rejected_advice[0].key.as_deref(), Some("numa-binding"));
+    assert!(report.localized(Lang::En).contains("1 suggestion(s) you rejected earlier are not shown again"));
+    assert_eq!(manager.rejections()[0].reason, "the build machine is shared, binding starves other jobs");
+    
+    assert_eq!(manager.clear_rejections(Some("numa-binding")).unwrap().len(), 1);
+    assert!(manager.rejections().is_empty());
+    assert!(plan().recommendations.iter().any(|r| r.contains("numactl")));
+}
+
+#[test]
+fn test_failed_phase_is_rolled_back_alone() {
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::i18n::Lang;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::phases::{ApplyPhase, PhaseStatus};
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    fs::write(&config_path, "# team settings\n[build]\njobs = 3\n").unwrap();
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_PHASED_APPLY_")
+        .with_platform(Some(Platform::Linux))
+        .with_target_dir_env(None)
+        .with_phase_probe(|phase, _| match phase {
+            ApplyPhase::Profiles => anyhow::bail!("build probe failed"),
+            _ => Ok(()),
+        });
+    
+    let report = manager.apply_with_hardware(&get_fallback_hardware()).unwrap();
+    let profiles = report.phases.iter().find(|outcome| outcome.phase == ApplyPhase::Profiles).unwrap();
+    assert_eq!(profiles.status, PhaseStatus::RolledBack);
+    assert_eq!(profiles.detail.as_deref(), Some("build probe failed"));
+    assert!(report.phases.iter().all(|outcome| outcome.status != PhaseStatus::Applied || outcome.phase < ApplyPhase::Profiles));
+    
+    let written = fs::read_to_string(&config_path).unwrap();
+    assert!(written.starts_with("# team settings\n[build]\njobs = 3\n"), "{}", written);
+    assert!(!written.contains("[profile"), "{}", written);
+    let setting = &profiles.settings[0];
+    assert!(report.decision_for(setting).unwrap().reason.starts_with("rolled back with the profiles phase"));
+    assert!(report.localized(Lang::En).contains("profiles [rolled back]"));
+}
+
+#[test]
+fn test_doctor_repairs_corrupt_sccache_entries() {
+    use cargo_optimize::sccache::health::{CacheLocation, DEFAULT_CACHE_SIZE};
+    
+    let temp_dir = setup_test_env();
+    let cache_dir = temp_dir.path().join("sccache");
+    let fan_out = cache_dir.join("0").join("1");
+    fs::create_dir_all(&fan_out).unwrap();
+    let entry = fan_out.join("0123456789abcdef".repeat(4));
+    fs::write(&entry, b"").unwrap();
+    let location = CacheLocation { dir: cache_dir, max_size: DEFAULT_CACHE_SIZE };
+    let mut manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_DOCTOR_SCCACHE_")
+        .with_sccache_cache(location);
+    
+    let report = manager.doctor().unwrap();
+    assert!(report.recommendations[0].ends_with("1 corrupt cache entries"), "{:?}", report.recommendations);
+    assert!(report.decision_for("sccache.cache").unwrap().reason.starts_with("not repaired"));
+    assert!(entry.exists());
+    
+    manager.config_mut().global.sccache_auto_repair = true;
+    let report = manager.doctor().unwrap();
+    assert_eq!(report.decision_for("sccache.cache").unwrap().reason, "removed 1 corrupt entries, kept the rest");
+    assert!(!entry.exists());
+    assert!(!temp_dir.path().join(".cargo").join("config.toml").exists());
+    assert!(manager.doctor().unwrap().recommendations.is_empty());
+}
+
+#[test]
+fn test_verify_reports_drift_and_exit_status() {
+    use cargo_optimize::exit::ExitStatus;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::linkers::Platform;
+    
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".cargo").join("config.toml");
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_VERIFY_DRIFT_")
+        .with_platform(Some(Platform::Linux))
+        .with_target_dir_env(None);
+    let hardware = get_fallback_hardware();
+    
+    let verification = manager.verify_with_hardware(&hardware).unwrap();
+    assert!(verification.is_drifted());
+    assert_eq!(verification.exit_status(), ExitStatus::Drift);
+    assert!(!config_path.exists(), "verify must not write the config");
+    
+    let report = manager.apply_with_hardware(&hardware).unwrap();
+    assert_eq!(ExitStatus::of_report(&report), ExitStatus::Success);
+    let verification = manager.verify_with_hardware(&hardware).unwrap();
+    assert!(verification.drifted.is_empty(), "{:?}", verification.drifted);
+    assert_eq!(verification.exit_status(), ExitStatus::Success);
+    
+    // A hand edit of a managed value is drift; a comment is not
+    let applied = fs::read_to_string(&config_path).unwrap();
+    fs::write(&config_path, format!("# reviewed\n{}", applied)).unwrap();
+    assert!(!manager.verify_with_hardware(&hardware).unwrap().is_drifted());
+    let mut doc: DocumentMut = applied.parse().unwrap();
+    let (key, _) = doc["profile"]["dev"].as_table().unwrap().iter().next().map(|(k, v)| (k.to_string(), v.clone())).unwrap();
+    doc["profile"]["dev"][&key] = toml_edit::value("edited");
+    fs::write(&config_path, doc.to_string()).unwrap();
+    let verification = manager.verify_with_hardware(&hardware).unwrap();
+    assert_eq!(verification.drifted, [format!("profile.dev.{}", key)]);
+    assert_eq!(verification.exit_status().code(), 2);
+    
+    let unsupported = create_test_manager_with_prefix(temp_dir.path(), "TEST_VERIFY_UNSUPPORTED_").with_platform(None);
+    assert_eq!(unsupported.verify_with_hardware(&hardware).unwrap().exit_status(), ExitStatus::UnsupportedPlatform);
+}
+
+#[test]
+fn test_required_optimizations() {
+    use cargo_optimize::exit::ExitStatus;
+    use cargo_optimize::hardware::get_fallback_hardware;
+    use cargo_optimize::i18n::Lang;
+    use cargo_optimize::linkers::Platform;
+    use cargo_optimize::strict::{self, Requirement};
+    
+    let temp_dir = setup_test_env();
+    let config_file = temp_dir.path().join("cargo-optimize.toml");
+    fs::write(&config_file, "[global]\nuse_sccache = false\nrequired = [\"sccache\"]\n").unwrap();
+    assert_eq!(strict::declared(temp_dir.path()).unwrap(), [Requirement::Sccache]);
+    
+    let manager = create_test_manager_with_prefix(temp_dir.path(), "TEST_REQUIRED_")
+        .with_platform(Some(Platform::Linux))
+        .with_target_dir_env(None);
+    assert_eq!(manager.config().global.required, [Requirement::Sccache]);
+    let report = manager.apply_with_hardware(&get_fallback_hardware()).unwrap();
+    
+    // The wrapper cargo runs with can come from the environment of the test run
+    let wrapper_env = ["RUSTC_WRAPPER", "CARGO_BUILD_RUSTC_WRAPPER"].iter().any(|var| std::env::var_os(var).is_some());
+    if !wrapper_env {
+        assert_eq!(report.unmet_requirements.len(), 1);
+        assert_eq!(report.unmet_requirements[0].reason, "build.rustc-wrapper is not set");
+        assert_eq!(ExitStatus::of_report(&report), ExitStatus::RequirementUnmet);
+        let rendered = report.localized(Lang::En);
+        assert!(rendered.contains("Required optimizations missing:\n  - sccache: build.rustc-wrapper is not set"));
+    }
+    
+    // A typo fails instead of silently turning strict mode off
+    fs::write(&config_file, "[global]\nrequired = [\"linkr\"]\n").unwrap();
+    assert!(strict::declared(temp_dir.path()).is_err());
+}
+
+#[test]
+fn test_toolchain_scoped_settings() {
+    use cargo_optimize::toolchain::ToolchainInfo;
+    
+    let temp_dir = setup_test_env();
+    let config_file = temp_dir.path().join("cargo-optimize.toml");
+    fs::write(
+        &config_file,
+        r#"
+[global]
+verbose = false
+
+[toolchain."nightly".global]
+verbose = true
+
+[toolchain.">=1.78".profiles.release]
+strip = "symbols"
+
+[toolchain."nightly, >=1.80".profiles.release]
+strip = "debuginfo"
+"#,
+    )
+    .unwrap();
+    let toolchain = |release: &str| ToolchainInfo::parse_verbose_version(&format!("release: {}\n", release)).unwrap();
+    let manager = |release: &str| {
+        create_test_manager_with_prefix(temp_dir.path(), "TEST_TOOLCHAIN_SECTIONS_").with_toolchain(toolchain(release))
+    };
+    
+    let old_stable = manager("1.75.0");
+    assert!(!old_stable.config().global.verbose);
+    assert_ne!(old_stable.config().profiles["release"].strip.as_deref(), Some("symbols"));
+    
+    let stable = manager("1.80.0");
+    assert!(!stable.config().global.verbose);
+    assert_eq!(stable.config().profiles["release"].strip.as_deref(), Some("symbols"));
+    
+    // Later sections win
+    let nightly = manager("1.81.0-nightly");
+    assert!(nightly.config().global.verbose);
+    assert_eq!(nightly.config().profiles["release"].strip.as_deref(), Some("debuginfo"));
+    
+    fs::write(&config_file, "[toolchain.\"~1.78\".global]\nverbose = true\n").unwrap();
+    let error = ConfigManager::new_with_base_dir(temp_dir.path(), "TEST_TOOLCHAIN_INVALID_").err().unwrap();
+    assert!(format!("{:#}", error).contains("invalid toolchain selector `~1.78`"), "{:#}", error);
+}
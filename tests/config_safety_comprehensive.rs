@@ -548,7 +548,7 @@ mod platform_tests {
         let content = fs::read_to_string(&config_path).unwrap();
         
         // Check for Windows-specific target
-        assert!(content.contains("x86_64-pc-windows-msvc"), 
+        assert!(content.contains(cargo_optimize::linkers::Platform::Windows.target_triple().unwrap()), 
                 "Should use Windows MSVC target, got: {}", content);
         assert!(content.contains("rust-lld") || content.contains("lld-link"), 
                 "Should configure Windows linker");
@@ -813,6 +813,7 @@ mod error_recovery_tests {
         // Reset permissions for cleanup
         let metadata = fs::metadata(&config_path).unwrap();
         let mut permissions = metadata.permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
         permissions.set_readonly(false);
         let _ = fs::set_permissions(&config_path, permissions);
         
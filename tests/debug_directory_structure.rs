@@ -55,18 +55,14 @@ auto_detect_hardware = false
     
     // Check what was created
     println!("\nDirectory contents:");
-    for entry in fs::read_dir(temp_dir.path()).unwrap() {
-        if let Ok(entry) = entry {
-            println!("  {:?}", entry.path());
-        }
+    for entry in fs::read_dir(temp_dir.path()).unwrap().flatten() {
+        println!("  {:?}", entry.path());
     }
     
     if cargo_dir.exists() {
         println!("\n.cargo contents:");
-        for entry in fs::read_dir(&cargo_dir).unwrap() {
-            if let Ok(entry) = entry {
-                println!("  {:?}", entry.path());
-            }
+        for entry in fs::read_dir(&cargo_dir).unwrap().flatten() {
+            println!("  {:?}", entry.path());
         }
     }
     
@@ -0,0 +1,33 @@
+//! Builds the fixture workspace in `tests/fixtures/` with the config
+//! cargo-optimize applies on this machine.
+//!
+//! Skipped unless `CARGO_OPTIMIZE_E2E=1` is set, since it runs real cargo
+//! builds: `CARGO_OPTIMIZE_E2E=1 cargo test --test end_to_end -- --nocapture`.
+
+mod support;
+
+use std::process::Command;
+use support::e2e::{enabled, Fixture};
+
+#[test]
+fn test_fixture_workspace_builds_with_applied_config() {
+    if !enabled() {
+        return;
+    }
+    let fixture = Fixture::copy("e2e-workspace");
+    let run = fixture.run_pipeline().unwrap_or_else(|e| panic!("{}", e));
+    assert!(!run.config.is_empty(), "apply wrote no config: {:?}", run.report.decisions);
+    
+    // The binary linked with the applied linker settings runs
+    let binary = fixture.target_dir().join("debug").join(format!("e2e-app{}", std::env::consts::EXE_SUFFIX));
+    let output = Command::new(&binary).output().expect("Failed to run the fixture binary");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "e2e-app ok: 3 words\n");
+    fixture.cargo(&["test", "--workspace", "--offline"]).unwrap_or_else(|e| panic!("{}", e));
+    
+    let timings = run.timings;
+    eprintln!(
+        "{} CPUs: detect {:?}, plan {:?}, apply {:?}, build {:?}",
+        run.hardware.cpu_count, timings.detect, timings.plan, timings.apply, timings.build
+    );
+    eprintln!("applied config:\n{}", run.config);
+}
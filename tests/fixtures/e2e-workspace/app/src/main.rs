@@ -0,0 +1,4 @@
+fn main() {
+    let words = e2e_core::word_count("linked by cargo-optimize");
+    println!("e2e-app ok: {} words", words);
+}
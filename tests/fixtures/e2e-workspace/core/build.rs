@@ -0,0 +1,4 @@
+// A build script, so the build runs host code compiled with the applied config too
+fn main() {
+    println!("cargo:rustc-env=E2E_CORE_PROFILE={}", std::env::var("PROFILE").unwrap_or_default());
+}
@@ -0,0 +1,9 @@
+//! Library half of the end-to-end fixture
+
+/// Profile the build script ran for
+pub const PROFILE: &str = env!("E2E_CORE_PROFILE");
+
+/// Number of whitespace-separated words in `text`
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
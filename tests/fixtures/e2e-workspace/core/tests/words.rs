@@ -0,0 +1,5 @@
+#[test]
+fn counts_words() {
+    assert_eq!(e2e_core::word_count("a b  c"), 3);
+    assert!(!e2e_core::PROFILE.is_empty());
+}
@@ -0,0 +1,72 @@
+//! Tests for the git hooks integration
+//!
+//! Each test creates its own repository, so git must be installed.
+
+use cargo_optimize::hooks::{install_git_hooks, uninstall_git_hooks, BLOCK_BEGIN};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .expect("Failed to run git");
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_install_is_idempotent_and_removable() {
+    let repo = TempDir::new().unwrap();
+    git(repo.path(), &["init", "-q"]);
+    let hooks = repo.path().join(".git").join("hooks");
+    fs::create_dir_all(&hooks).unwrap();
+    fs::write(hooks.join("post-merge"), "#!/bin/sh\necho existing\n").unwrap();
+    
+    let written = install_git_hooks(repo.path()).unwrap();
+    assert_eq!(written.len(), 2);
+    let merge_hook = fs::read_to_string(hooks.join("post-merge")).unwrap();
+    assert!(merge_hook.starts_with("#!/bin/sh\necho existing\n"));
+    assert_eq!(merge_hook.matches(BLOCK_BEGIN).count(), 1);
+    
+    install_git_hooks(repo.path()).unwrap();
+    assert_eq!(fs::read_to_string(hooks.join("post-merge")).unwrap(), merge_hook);
+    
+    uninstall_git_hooks(repo.path()).unwrap();
+    assert_eq!(fs::read_to_string(hooks.join("post-merge")).unwrap(), "#!/bin/sh\necho existing\n");
+    assert!(!hooks.join("post-checkout").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_post_checkout_reports_drift() {
+    let repo = TempDir::new().unwrap();
+    let root = repo.path();
+    git(root, &["init", "-q", "-b", "main"]);
+    fs::write(root.join("README"), "readme").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-qm", "initial"]);
+    
+    git(root, &["checkout", "-qb", "settings"]);
+    fs::write(root.join("cargo-optimize.toml"), "[global]\nverbose = true\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-qm", "settings"]);
+    
+    install_git_hooks(root).unwrap();
+    let switch = |branch: &str| {
+        let output = Command::new("git").arg("-C").arg(root).args(["checkout", "-q", branch]).output().unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr)
+    };
+    assert!(switch("main").contains("re-run cargo-optimize"));
+    
+    // Unrelated changes stay quiet
+    git(root, &["checkout", "-qb", "docs"]);
+    fs::write(root.join("README"), "more").unwrap();
+    git(root, &["commit", "-qam", "docs"]);
+    assert!(!switch("main").contains("cargo-optimize"));
+}
@@ -0,0 +1,50 @@
+//! End-to-end golden-file tests of config generation
+//!
+//! Plans the config for every environment in `support::matrix()` and compares
+//! it with the committed file in `tests/golden/`. After an intended behavior
+//! change, regenerate with `UPDATE_GOLDEN=1 cargo test --test golden_configs`
+//! and review the diff of the golden files.
+
+mod support;
+
+use std::collections::HashSet;
+use std::fs;
+use support::{check_golden, golden_dir, matrix, Environment, HAND_TUNED_CONFIG};
+use toml_edit::DocumentMut;
+
+#[test]
+fn test_generated_configs_match_golden_files() {
+    let mismatches: Vec<String> = matrix()
+        .iter()
+        .filter_map(|environment| check_golden(&environment.name(), &environment.generate()).err())
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n\n"));
+}
+
+#[test]
+fn test_every_golden_file_belongs_to_the_matrix() {
+    let names: HashSet<String> = matrix().iter().map(Environment::name).collect();
+    assert_eq!(names.len(), matrix().len(), "environment names must be unique");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        return;
+    }
+    for entry in fs::read_dir(golden_dir()).unwrap() {
+        let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+        assert!(names.contains(&name), "stale golden file {}", name);
+    }
+}
+
+#[test]
+fn test_generation_is_deterministic_and_preserves_hand_tuning() {
+    for environment in matrix() {
+        let generated = environment.generate();
+        assert_eq!(generated, environment.generate(), "{} is not deterministic", environment.name());
+        
+        if environment.existing == Some(HAND_TUNED_CONFIG) {
+            let doc: DocumentMut = generated.parse().unwrap();
+            assert!(generated.starts_with("# Team settings"), "{}", environment.name());
+            assert_eq!(doc["alias"]["b"].as_str(), Some("build"));
+            assert!(generated.contains("# measured: fat LTO is not worth it"));
+        }
+    }
+}
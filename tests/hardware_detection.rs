@@ -64,6 +64,7 @@ fn test_cpu_percentage_calculations() {
         os_name: "Test OS".to_string(),
         os_version: "1.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     // Test logical CPU calculations
@@ -95,6 +96,7 @@ fn test_memory_percentage_calculations() {
         os_name: "Test OS".to_string(),
         os_version: "1.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     assert_eq!(hw.memory_percentage(100.0), 8 * 1024 * 1024 * 1024);
@@ -130,6 +132,7 @@ fn test_disk_percentage_calculations() {
         os_name: "Linux".to_string(),
         os_version: "5.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     // Test root mount
@@ -225,6 +228,7 @@ fn test_recommended_parallelism() {
         os_name: "Test OS".to_string(),
         os_version: "1.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     // Should be limited by CPU (75% of 16 = 12)
@@ -242,6 +246,7 @@ fn test_recommended_parallelism() {
         os_name: "Test OS".to_string(),
         os_version: "1.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     // Should be limited by memory (2GB / 500MB = 4)
@@ -259,6 +264,7 @@ fn test_recommended_parallelism() {
         os_name: "Test OS".to_string(),
         os_version: "1.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     // Should return 1 (minimum)
@@ -282,6 +288,7 @@ fn test_check_resources_sufficient() {
         os_name: "Test OS".to_string(),
         os_version: "1.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     // Should pass with reasonable requirements
@@ -307,6 +314,7 @@ fn test_check_resources_insufficient() {
         os_name: "Test OS".to_string(),
         os_version: "1.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     // Should fail with excessive memory requirement
@@ -376,6 +384,7 @@ fn test_hardware_summary_format() {
         os_name: "Linux".to_string(),
         os_version: "5.15.0".to_string(),
         arch: "x86_64".to_string(),
+        topology: Default::default(),
     };
     
     let summary = hw.summary();
@@ -424,6 +433,7 @@ fn test_edge_cases() {
         os_name: "Test".to_string(),
         os_version: "1.0".to_string(),
         arch: "test".to_string(),
+        topology: Default::default(),
     };
     
     // Should still return minimum of 1
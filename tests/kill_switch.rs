@@ -0,0 +1,170 @@
+//! `CARGO_OPTIMIZE_DISABLE` stops every entry point that writes
+//!
+//! The variable is set for the whole process, so these tests live in their
+//! own binary.
+
+use cargo_optimize::config::{ConfigManager, PackageOverride};
+use cargo_optimize::guardrail::LinkerFailure;
+use cargo_optimize::report::{OptimizationReport, SkipReason};
+use cargo_optimize::sccache::health::CacheLocation;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A project with a config of its own, and a manager for it with the kill switch set
+fn disabled_project(env_prefix: &str) -> (TempDir, ConfigManager) {
+    std::env::set_var("CARGO_OPTIMIZE_DISABLE", "1");
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join(".cargo")).unwrap();
+    fs::write(temp.path().join(".cargo").join("config.toml"), CONFIG).unwrap();
+    let manager = ConfigManager::new_with_base_dir(temp.path(), env_prefix).unwrap();
+    (temp, manager)
+}
+
+const CONFIG: &str = "[build]\njobs = 3\n";
+
+/// The files under `root`, relative to it
+fn files(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                found.push(path.strip_prefix(root).unwrap().to_path_buf());
+            }
+        }
+    }
+    found
+}
+
+fn assert_untouched(root: &Path) {
+    assert_eq!(files(root), [Path::new(".cargo").join("config.toml")]);
+    assert_eq!(fs::read_to_string(root.join(".cargo").join("config.toml")).unwrap(), CONFIG);
+}
+
+fn assert_opted_out(report: &OptimizationReport, setting: &str) {
+    let decision = report.decision_for(setting).unwrap();
+    assert_eq!(decision.skip_reason, Some(SkipReason::UserOptOut), "{}", report);
+    assert!(decision.reason.contains("CARGO_OPTIMIZE_DISABLE"), "{}", decision.reason);
+}
+
+fn assert_disabled<T: std::fmt::Debug>(result: anyhow::Result<T>) {
+    let error = result.unwrap_err();
+    assert!(error.to_string().contains("disabled by CARGO_OPTIMIZE_DISABLE"), "{:#}", error);
+}
+
+#[test]
+fn test_manager_writers_skip() {
+    let (temp, manager) = disabled_project("TEST_KILL_SWITCH_MANAGER_");
+    assert_opted_out(&manager.apply_with_report().unwrap(), "apply");
+    assert_opted_out(&manager.propose().unwrap(), "propose");
+    assert_opted_out(&manager.accept().unwrap(), "accept");
+    assert_opted_out(&manager.uninstall().unwrap(), "uninstall");
+    let failure = LinkerFailure { meaning: "unknown option".to_string(), line: "mold: fatal".to_string() };
+    assert_opted_out(&manager.rollback_linker(&failure).unwrap(), "rollback");
+    let overrides = [PackageOverride {
+        profile: "dev".to_string(),
+        package: "syn".to_string(),
+        settings: vec![("opt-level".to_string(), "3".to_string())],
+    }];
+    assert_opted_out(&manager.apply_package_overrides(&overrides).unwrap(), "package overrides");
+    assert_disabled(manager.purge(false));
+    assert_untouched(temp.path());
+}
+
+#[test]
+fn test_doctor_does_not_repair() {
+    let (temp, manager) = disabled_project("TEST_KILL_SWITCH_DOCTOR_");
+    let cache = temp.path().join("sccache");
+    fs::create_dir_all(&cache).unwrap();
+    fs::write(cache.join("entry"), vec![0u8; 4096]).unwrap();
+    let mut manager = manager.with_sccache_cache(CacheLocation { dir: cache.clone(), max_size: 1 });
+    manager.config_mut().global.sccache_auto_repair = true;
+
+    let report = manager.doctor().unwrap();
+    assert_opted_out(&report, "sccache.cache");
+    assert!(cache.join("entry").is_file());
+    assert!(!temp.path().join("sccache.old").exists());
+}
+
+#[test]
+fn test_git_hooks_are_not_installed() {
+    let (temp, _) = disabled_project("TEST_KILL_SWITCH_HOOKS_");
+    assert_disabled(cargo_optimize::hooks::install_git_hooks(temp.path()));
+    assert_untouched(temp.path());
+}
+
+#[test]
+fn test_workspace_hack_is_not_generated() {
+    use cargo_optimize::workspace_hack::{self, DependencySource, UnificationReport, UnifiedDependency};
+
+    let (temp, _) = disabled_project("TEST_KILL_SWITCH_HACK_");
+    let report = UnificationReport {
+        workspace_root: temp.path().to_path_buf(),
+        members: vec![PathBuf::from("app")],
+        existing_hack: None,
+        dependencies: vec![UnifiedDependency {
+            name: "serde".to_string(),
+            version: "1.0.0".to_string(),
+            source: DependencySource::CratesIo,
+            host: false,
+            features: vec!["derive".to_string()],
+            variants: 2,
+            members: vec!["app".to_string()],
+            cost: 10,
+            missing: Vec::new(),
+            enabled_by: BTreeMap::new(),
+        }],
+        total_cost: 10,
+    };
+    assert_disabled(workspace_hack::generate(&report, "workspace-hack"));
+    assert_untouched(temp.path());
+}
+
+#[test]
+fn test_linkers_are_not_installed() {
+    use cargo_optimize::proxy::Proxy;
+    use cargo_optimize::tools::{self, Linker, LinkerInstallMode, LinkerInstallSettings};
+
+    let (temp, _) = disabled_project("TEST_KILL_SWITCH_TOOLS_");
+    let settings = LinkerInstallSettings {
+        mode: LinkerInstallMode::Download,
+        dir: Some(temp.path().join("tools")),
+        sha256: BTreeMap::new(),
+    };
+    assert_disabled(tools::install_linker_with(Linker::Mold.spec(), &settings, &Proxy::default()));
+    assert_untouched(temp.path());
+}
+
+#[test]
+fn test_projects_are_not_scaffolded() {
+    use cargo_optimize::scaffold::{new_project, CrateKind, Scaffold, Template};
+
+    let (temp, _) = disabled_project("TEST_KILL_SWITCH_SCAFFOLD_");
+    assert_disabled(new_project("app", &Template::package(CrateKind::Binary)));
+    let scaffold = Scaffold {
+        name: "app".to_string(),
+        files: BTreeMap::from([(PathBuf::from("Cargo.toml"), "[package]\nname = \"app\"\n".to_string())]),
+    };
+    assert_disabled(scaffold.write_to(temp.path()));
+    assert_untouched(temp.path());
+}
+
+#[test]
+fn test_build_weight_is_not_measured() {
+    let (temp, _) = disabled_project("TEST_KILL_SWITCH_WEIGHT_");
+    assert_disabled(cargo_optimize::analysis::build_weight::measure(temp.path()));
+    assert_untouched(temp.path());
+}
+
+#[test]
+fn test_ci_artifact_is_not_written() {
+    let (temp, _) = disabled_project("TEST_KILL_SWITCH_CI_");
+    let path = temp.path().join("cargo-optimize-report");
+    assert_disabled(cargo_optimize::report::ci::ci_artifact_in(temp.path(), &path));
+    assert_untouched(temp.path());
+}
@@ -0,0 +1,111 @@
+//! Property tests for the config merge engine (feature `property-tests`).
+//!
+//! Arbitrary valid configs are optimized with the real apply path and checked
+//! for the invariants users rely on:
+//! - the result parses
+//! - keys cargo-optimize does not manage are untouched
+//! - applying twice changes nothing the second time
+//! - uninstalling restores the original configuration
+//!
+//! Run with `cargo test --features property-tests --test merge_properties`.
+
+#![cfg(feature = "property-tests")]
+
+use cargo_optimize::config::ConfigManager;
+use cargo_optimize::hardware::HardwareInfo;
+use cargo_optimize::testing::arb_config_text;
+use proptest::prelude::*;
+use std::fs;
+use std::sync::OnceLock;
+use tempfile::TempDir;
+use toml_edit::{DocumentMut, Item, Table};
+
+fn hardware() -> &'static HardwareInfo {
+    static HARDWARE: OnceLock<HardwareInfo> = OnceLock::new();
+    HARDWARE.get_or_init(HardwareInfo::detect)
+}
+
+/// Keys cargo-optimize may write
+fn is_managed(path: &[String]) -> bool {
+    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+    match path.as_slice() {
+        ["build", "jobs" | "target" | "rustc-wrapper"] => true,
+        ["profile", _, key] => !matches!(*key, "custom-key" | "quoted key"),
+        ["target", _, "linker" | "rustflags"] => true,
+        ["unstable", ..] | ["alias", ..] => true,
+        _ => false,
+    }
+}
+
+/// All leaf values with their dotted paths, formatting ignored
+fn leaves(table: &Table, path: &mut Vec<String>, out: &mut Vec<(Vec<String>, String)>) {
+    for (key, item) in table.iter() {
+        path.push(key.to_string());
+        match item {
+            Item::Table(table) => leaves(table, path, out),
+            Item::Value(value) => {
+                let mut value = value.clone();
+                value.decor_mut().clear();
+                out.push((path.clone(), value.to_string()));
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+}
+
+fn leaves_of(content: &str) -> Vec<(Vec<String>, String)> {
+    let doc: DocumentMut = content.parse().expect("valid TOML");
+    let mut out = Vec::new();
+    leaves(doc.as_table(), &mut Vec::new(), &mut out);
+    out
+}
+
+fn semantic(content: &str) -> toml::Table {
+    toml::from_str(content).expect("valid TOML")
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn apply_preserves_idempotent_and_reversible(original in proptest::option::of(arb_config_text())) {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(".cargo").join("config.toml");
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        if let Some(original) = &original {
+            fs::write(&config_path, original).unwrap();
+        }
+        let manager = ConfigManager::new_with_base_dir(temp_dir.path(), "PROPTEST_MERGE_").unwrap();
+
+        // Parseability
+        manager.apply_with_hardware(hardware()).unwrap();
+        let applied = fs::read_to_string(&config_path).unwrap();
+        prop_assert!(applied.parse::<DocumentMut>().is_ok(), "unparsable result:\n{}", applied);
+
+        // Preservation of unrelated keys
+        if let Some(original) = &original {
+            let after = leaves_of(&applied);
+            for leaf in leaves_of(original).into_iter().filter(|(path, _)| !is_managed(path)) {
+                prop_assert!(after.contains(&leaf), "lost {:?} in:\n{}", leaf, applied);
+            }
+            for line in original.lines().filter(|line| line.starts_with('#')) {
+                prop_assert!(applied.contains(line.trim_end()), "lost comment {:?}", line);
+            }
+        }
+
+        // Idempotence
+        manager.apply_with_hardware(hardware()).unwrap();
+        prop_assert_eq!(&fs::read_to_string(&config_path).unwrap(), &applied);
+
+        // Reversibility
+        manager.uninstall().unwrap();
+        match &original {
+            Some(original) => {
+                let restored = fs::read_to_string(&config_path).unwrap();
+                prop_assert_eq!(semantic(&restored), semantic(original));
+            }
+            None => prop_assert!(!config_path.exists()),
+        }
+    }
+}
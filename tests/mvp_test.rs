@@ -2,6 +2,8 @@
 //! 
 //! Standard tests using #[test] attributes for nextest/pytest compatibility
 
+#![allow(clippy::assertions_on_constants)]
+
 use std::path::Path;
 
 #[test]
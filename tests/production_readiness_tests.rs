@@ -1,6 +1,8 @@
 //! Production Readiness Test Runner
 //! Executes all production readiness tests for cargo-optimize
 
+#![allow(clippy::assertions_on_constants, clippy::module_inception)]
+
 // Include all production validation test modules
 mod production_validation {
     pub mod accessibility_tests;
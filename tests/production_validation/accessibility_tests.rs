@@ -69,13 +69,11 @@ mod accessibility_tests {
         // Test that output is structured for screen readers
         
         // Verify output has proper structure
-        let output = format!(
-            "cargo-optimize v0.1.0\n\
+        let output = "cargo-optimize v0.1.0\n\
              Status: Checking for fast linkers...\n\
              Found: rust-lld\n\
              Action: Configuring .cargo/config.toml\n\
-             Result: Success"
-        );
+             Result: Success".to_string();
         
         // Check for proper line breaks and structure
         assert!(output.contains("\n"), "Output should have line breaks");
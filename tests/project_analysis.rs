@@ -399,3 +399,754 @@ fn test_impact_levels() {
     assert_eq!(format!("{:?}", ImpactLevel::Medium), "Medium");
     assert_eq!(format!("{:?}", ImpactLevel::Low), "Low");
 }
+
+#[test]
+fn test_default_members_suggestion() {
+    use cargo_optimize::analysis::apply_default_members;
+    
+    let workspace_dir = create_workspace_project();
+    // Turn crate-b into an application
+    let crate_b_src = workspace_dir.path().join("crate-b").join("src");
+    fs::write(crate_b_src.join("main.rs"), "fn main() {}").expect("Failed to write main.rs");
+    let manifest_path = workspace_dir.path().join("Cargo.toml");
+    
+    let analysis = analyze_project(Some(&manifest_path))
+        .expect("Failed to analyze workspace");
+    let suggestion = analysis.default_members.clone()
+        .expect("Workspace with an app and an internal lib should get a suggestion");
+    assert_eq!(suggestion.members, vec!["crate-b".to_string()]);
+    assert_eq!(suggestion.excluded, vec!["crate-a".to_string()]);
+    assert!(analysis.summary().contains("default-members"));
+    
+    // Applying preserves the rest of the manifest and is picked up on re-analysis
+    apply_default_members(&manifest_path, &suggestion).expect("Failed to apply default-members");
+    let content = fs::read_to_string(&manifest_path).unwrap();
+    assert!(content.contains("default-members = [\"crate-b\"]"));
+    assert!(content.contains("[workspace.package]"));
+    assert!(apply_default_members(&manifest_path, &suggestion).is_err());
+    
+    let analysis = analyze_project(Some(&manifest_path))
+        .expect("Failed to analyze workspace");
+    assert!(analysis.default_members.is_none());
+}
+
+#[test]
+fn test_no_default_members_suggestion_for_libraries_only() {
+    let workspace_dir = create_workspace_project();
+    let manifest_path = workspace_dir.path().join("Cargo.toml");
+    
+    let analysis = analyze_project(Some(&manifest_path))
+        .expect("Failed to analyze workspace");
+    assert!(analysis.default_members.is_none());
+}
+
+#[test]
+fn test_template_detection() {
+    use cargo_optimize::analysis::{detect_template, TargetAnalysis};
+    use cargo_optimize::config::ProjectTemplate;
+    use std::collections::HashSet;
+    
+    let cli = TargetAnalysis { binaries: vec!["tool".to_string()], ..Default::default() };
+    let library = TargetAnalysis { libraries: vec!["lib".to_string()], ..Default::default() };
+    let no_deps = HashSet::new();
+    let server_deps: HashSet<String> = ["axum".to_string()].into_iter().collect();
+    
+    assert_eq!(detect_template(&[], &cli, &no_deps), Some(ProjectTemplate::Cli));
+    assert_eq!(detect_template(&[], &cli, &server_deps), Some(ProjectTemplate::Server));
+    assert_eq!(detect_template(&[], &library, &no_deps), None);
+    assert_eq!(
+        detect_template(&["thumbv7em-none-eabihf".to_string()], &cli, &no_deps),
+        Some(ProjectTemplate::Embedded)
+    );
+    assert_eq!(
+        detect_template(&["wasm32-unknown-unknown".to_string()], &library, &no_deps),
+        Some(ProjectTemplate::WasmApp)
+    );
+}
+
+#[test]
+fn test_embedded_signal_from_runner() {
+    use cargo_optimize::analysis::embedded_signal;
+    
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+    assert_eq!(embedded_signal(dir.path()), None);
+    
+    // A host runner is not a signal
+    let config = dir.path().join(".cargo/config.toml");
+    fs::write(&config, "[target.x86_64-unknown-linux-gnu]\nrunner = \"valgrind\"\n").unwrap();
+    assert_eq!(embedded_signal(dir.path()), None);
+    
+    fs::write(&config, "[target.'cfg(all(target_arch = \"arm\", target_os = \"none\"))']\nrunner = \"my-flasher\"\n").unwrap();
+    assert!(embedded_signal(dir.path()).unwrap().contains("my-flasher"));
+    
+    fs::write(&config, "[target.riscv32imc-esp-espidf]\nrunner = [\"espflash\", \"flash\", \"--monitor\"]\n").unwrap();
+    assert_eq!(
+        embedded_signal(dir.path()).as_deref(),
+        Some("runner `espflash flash --monitor` for target.riscv32imc-esp-espidf")
+    );
+    
+    fs::write(&config, "[build]\ntarget = \"thumbv6m-none-eabi\"\n").unwrap();
+    assert_eq!(embedded_signal(dir.path()).as_deref(), Some("builds for thumbv6m-none-eabi"));
+}
+
+#[test]
+fn test_template_detected_from_project() {
+    let project = create_test_project(r#"
+[package]
+name = "tool"
+version = "0.1.0"
+edition = "2021"
+"#);
+    fs::write(project.path().join("src").join("main.rs"), "fn main() {}").unwrap();
+    
+    let analysis = analyze_project(Some(&project.path().join("Cargo.toml")))
+        .expect("Failed to analyze project");
+    assert_eq!(analysis.template, Some(cargo_optimize::config::ProjectTemplate::Cli));
+    assert!(analysis.summary().contains("Detected template: cli"));
+}
+
+#[test]
+fn test_member_churn_from_git_log() {
+    use cargo_optimize::analysis::{churn_from_git_log, PackageInfo};
+    
+    let root = Path::new("/ws");
+    let member = |name: &str, path: &str| PackageInfo {
+        name: name.to_string(),
+        version: "0.1.0".to_string(),
+        path: root.join(path),
+        dependencies: 0,
+        dev_dependencies: 0,
+        build_dependencies: 0,
+        is_application: false,
+    };
+    let members = [member("root-app", ""), member("core-types", "crates/core-types")];
+    
+    let log = "commit:aaa\n\nsrc/main.rs\ncrates/core-types/src/lib.rs\n\
+               commit:bbb\n\nsrc/main.rs\nsrc/cli.rs\n\
+               commit:ccc\n\nREADME.md\n";
+    let churn = churn_from_git_log(log, root, &members);
+    
+    // Nested member changes are not attributed to the root package
+    assert_eq!(churn[0].package, "root-app");
+    assert_eq!(churn[0].commits, 3);
+    assert_eq!(churn[1].commits, 1);
+}
+
+#[test]
+fn test_dependency_report() {
+    use cargo_optimize::analysis::{dependency_report, estimate_build_cost};
+    
+    let project = create_test_project(r#"
+[package]
+name = "report-app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+heavy = { path = "heavy", features = ["extra"] }
+light = { path = "light" }
+"#);
+    let write_crate = |name: &str, manifest_extra: &str, source: &str| {
+        let dir = project.path().join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!("[package]\nname = \"{}\"\nversion = \"0.2.0\"\nedition = \"2021\"\nlicense = \"MIT, Apache-2.0\"\n{}", name, manifest_extra),
+        ).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), source).unwrap();
+        dir
+    };
+    let heavy = write_crate("heavy", "\n[features]\nextra = []\nunused = []\n", &"// padding\n".repeat(1024));
+    fs::write(heavy.join("build.rs"), "fn main() {}").unwrap();
+    write_crate("light", "", "// small");
+    
+    let report = dependency_report(Some(&project.path().join("Cargo.toml"))).unwrap();
+    assert_eq!(report.dependencies.len(), 2);
+    
+    // Costliest first; the heavy crate is the critical path below the member
+    let heavy = &report.dependencies[0];
+    assert_eq!(heavy.name, "heavy");
+    assert!(heavy.build_script);
+    assert_eq!(heavy.features, vec!["extra"]);
+    assert_eq!(heavy.estimated_cost, estimate_build_cost(11 * 1024, true, false));
+    assert!(heavy.on_critical_path);
+    assert!(!report.dependencies[1].on_critical_path);
+    assert_eq!(report.critical_path, vec!["report-app", "heavy"]);
+    
+    let csv = report.to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("name,version,source,license,estimated_cost"));
+    assert!(lines[1].starts_with("heavy,0.2.0,,\"MIT, Apache-2.0\","));
+    assert!(lines[1].ends_with(",true,false,true,extra"));
+    
+    let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+    assert_eq!(json["dependencies"][0]["name"], "heavy");
+    assert_eq!(json["critical_path"][1], "heavy");
+}
+
+#[test]
+fn test_dependency_graph_export() {
+    use cargo_optimize::analysis::export_graph;
+    use cargo_optimize::analysis::graph::{GraphFormat, GraphOptions};
+    
+    let project = create_test_project(r#"
+[package]
+name = "graph-app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+heavy = { path = "heavy" }
+light = { path = "light" }
+"#);
+    let write_crate = |name: &str, dependencies: &str, source: &str| {
+        let dir = project.path().join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!("[package]\nname = \"{}\"\nversion = \"0.2.0\"\nedition = \"2021\"\n\n[dependencies]\n{}", name, dependencies),
+        ).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), source).unwrap();
+    };
+    write_crate("heavy", "base = { path = \"../base\" }", &"// padding\n".repeat(1024));
+    write_crate("light", "", "// small");
+    write_crate("base", "", "// small");
+    let manifest = project.path().join("Cargo.toml");
+    
+    // The chain through heavy is highlighted, light is not on it
+    let dot = export_graph(Some(&manifest), GraphFormat::Dot, &GraphOptions::default()).unwrap();
+    assert!(dot.starts_with("digraph dependencies {"));
+    assert!(dot.contains("critical path: graph-app -> heavy -> base"));
+    let line = |name: &str| dot.lines().find(|l| l.contains(&format!("label=\"{} ", name))).unwrap().to_string();
+    assert!(line("heavy").contains("color=\"#d62728\""));
+    assert!(!line("light").contains("color="));
+    assert!(line("graph-app").contains("peripheries=2"));
+    assert_eq!(dot.matches(" -> n").count(), 3);
+    
+    let mermaid = export_graph(Some(&manifest), GraphFormat::Mermaid, &GraphOptions::default()).unwrap();
+    assert!(mermaid.starts_with("flowchart LR"));
+    assert_eq!(mermaid.matches(" ==> ").count(), 2);
+    assert_eq!(mermaid.matches(" --> ").count(), 1);
+    
+    // Capped graphs keep the member and the critical path
+    let capped = GraphOptions { max_nodes: 1, ..GraphOptions::default() };
+    let dot = export_graph(Some(&manifest), GraphFormat::Dot, &capped).unwrap();
+    assert!(dot.contains("3 of 4 packages shown"));
+    assert!(!dot.contains("label=\"light "));
+    
+    // Excluding heavy hides base with it; focusing on heavy draws only its subtree
+    let excluded = GraphOptions { exclude: vec!["heavy".to_string()], ..GraphOptions::default() };
+    let dot = export_graph(Some(&manifest), GraphFormat::Dot, &excluded).unwrap();
+    assert!(!dot.contains("label=\"heavy ") && !dot.contains("label=\"base "));
+    let focused = GraphOptions { focus: Some("heavy".to_string()), ..GraphOptions::default() };
+    let dot = export_graph(Some(&manifest), GraphFormat::Dot, &focused).unwrap();
+    assert!(dot.contains("label=\"base ") && !dot.contains("label=\"graph-app "));
+}
+
+#[test]
+fn test_git_and_patched_dependency_costs() {
+    use cargo_optimize::analysis::{dependency_report, UncachedSource};
+    use std::process::Command;
+    
+    // A git repository with one crate, followed at its default branch
+    let upstream = TempDir::new().unwrap();
+    fs::create_dir_all(upstream.path().join("src")).unwrap();
+    fs::write(
+        upstream.path().join("Cargo.toml"),
+        "[package]\nname = \"tracked\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    ).unwrap();
+    fs::write(upstream.path().join("src").join("lib.rs"), "pub fn f() {}\n").unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "init.defaultBranch=main"])
+            .args(args)
+            .current_dir(upstream.path())
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-qm", "init"]);
+    
+    let project = create_test_project(&format!(r#"
+[package]
+name = "patch-app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+tracked = {{ git = "file://{}" }}
+middle = {{ path = "middle" }}
+
+[patch.crates-io]
+shim = {{ path = "shim" }}
+"#, upstream.path().display()));
+    let write_crate = |name: &str, deps: &str| {
+        let dir = project.path().join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!("[package]\nname = \"{}\"\nversion = \"0.2.0\"\nedition = \"2021\"\n\n[dependencies]\n{}", name, deps),
+        ).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), "// lib").unwrap();
+    };
+    write_crate("middle", "shim = { path = \"../shim\" }\n");
+    write_crate("shim", "");
+    
+    let report = dependency_report(Some(&project.path().join("Cargo.toml"))).unwrap();
+    assert_eq!(report.uncached.len(), 2, "{:?}", report.uncached);
+    
+    let shim = report.uncached.iter().find(|d| d.name == "shim").unwrap();
+    assert_eq!(shim.source, UncachedSource::Path);
+    assert!(shim.patched);
+    assert_eq!(shim.dependents, 2);
+    assert_eq!(shim.rebuild_cost, shim.cascade_cost * 4);
+    
+    let tracked = report.uncached.iter().find(|d| d.name == "tracked").unwrap();
+    assert!(matches!(
+        &tracked.source,
+        UncachedSource::GitBranch { branch: None, locked_rev: Some(_), .. }
+    ));
+    assert_eq!(tracked.dependents, 1);
+    
+    // Unrelated path dependencies are not reported
+    assert!(report.uncached.iter().all(|d| d.name != "middle"));
+    
+    let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+    assert_eq!(json["uncached"][0]["name"], "shim");
+    assert_eq!(json["uncached"][0]["source"]["kind"], "path");
+}
+
+#[test]
+fn test_feature_unification_and_workspace_hack() {
+    use cargo_optimize::advisor::Advisor;
+    use cargo_optimize::i18n::Lang;
+    use cargo_optimize::workspace_hack::{self, DependencySource, DEFAULT_CRATE_NAME};
+    
+    // Two members enable different features of a dependency outside the workspace
+    let project = TempDir::new().unwrap();
+    let write_crate = |dir: &str, manifest: &str| {
+        let dir = project.path().join(dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), "// lib\n".repeat(400)).unwrap();
+    };
+    fs::write(
+        project.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"api\", \"cli\"]\nexclude = [\"vendor/shared\"]\nresolver = \"2\"\n",
+    )
+    .unwrap();
+    write_crate(
+        "vendor/shared",
+        "[package]\nname = \"shared\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[features]\nfast = []\nsmall = []\n",
+    );
+    write_crate(
+        "api",
+        "[package]\nname = \"api\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+         [dependencies]\nshared = { path = \"../vendor/shared\", features = [\"fast\"] }\n",
+    );
+    write_crate(
+        "cli",
+        "[package]\nname = \"cli\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+         [dependencies]\nshared = { path = \"../vendor/shared\", features = [\"small\"] }\n",
+    );
+    
+    let report = workspace_hack::unification_report(Some(&project.path().join("Cargo.toml"))).unwrap();
+    assert_eq!(report.members.len(), 2);
+    assert_eq!(report.dependencies.len(), 1, "{:?}", report.dependencies);
+    let shared = &report.dependencies[0];
+    assert_eq!(shared.name, "shared");
+    assert_eq!(shared.features, ["fast", "small"]);
+    assert_eq!(shared.variants, 3);
+    assert_eq!(shared.members, ["api", "cli"]);
+    assert_eq!(shared.source, DependencySource::Path("vendor/shared".into()));
+    assert_eq!(report.benefit_percent(), 200);
+    
+    let mut advisor = Advisor::with_lang(Lang::En);
+    advisor.advise_workspace_hack(&report, false);
+    assert_eq!(advisor.advice()[0].command.as_deref(), Some("cargo install cargo-hakari --locked"));
+    assert!(advisor.advice()[0].message.starts_with("1 dependencies are compiled with other features"));
+    
+    // Two edits are few enough to name instead of adding a crate
+    assert_eq!(shared.enabled_by["small"], ["cli"]);
+    assert_eq!(shared.missing[0].member, "api");
+    assert_eq!(shared.missing[0].features, ["small"]);
+    assert!(shared.missing[0].direct);
+    let mut advisor = Advisor::with_lang(Lang::En);
+    advisor.advise_feature_unification(&report, false);
+    let commands: Vec<_> = advisor.advice().iter().filter_map(|advice| advice.command.as_deref()).collect();
+    assert_eq!(commands, ["cargo add -p api shared --features small", "cargo add -p cli shared --features fast"]);
+    assert!(advisor.advice()[0].message.starts_with("Building api on its own compiles shared without small, which cli enable"));
+    assert_eq!(advisor.advice()[0].key.as_deref(), Some("unify-features:api:shared"));
+    
+    // The generated crate unifies the features for every member
+    let written = workspace_hack::generate(&report, DEFAULT_CRATE_NAME).unwrap();
+    assert_eq!(written.len(), 5);
+    let hack = fs::read_to_string(project.path().join("workspace-hack").join("Cargo.toml")).unwrap();
+    assert!(hack.contains("shared = { path = \"../vendor/shared\", default-features = false, features = [\"fast\", \"small\"] }"), "{}", hack);
+    let api = fs::read_to_string(project.path().join("api").join("Cargo.toml")).unwrap();
+    assert!(api.contains("workspace-hack = { path = \"../workspace-hack\" }"), "{}", api);
+    
+    let report = workspace_hack::unification_report(Some(&project.path().join("Cargo.toml"))).unwrap();
+    assert_eq!(report.existing_hack.as_deref(), Some("workspace-hack"));
+    assert!(report.dependencies.is_empty(), "{:?}", report.dependencies);
+    assert!(workspace_hack::generate(&report, DEFAULT_CRATE_NAME).is_err());
+}
+
+#[test]
+fn test_example_and_bench_target_costs() {
+    use cargo_optimize::advisor::Advisor;
+    use cargo_optimize::analysis::targets::{self, AuxKind, Remedy};
+    use cargo_optimize::i18n::Lang;
+    
+    let project = create_test_project(
+        "[package]\nname = \"gallery\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+         [features]\ngpu = []\n\n[[example]]\nname = \"gpu-demo\"\nrequired-features = [\"gpu\"]\n",
+    );
+    let root = project.path();
+    fs::create_dir_all(root.join("examples").join("viewer")).unwrap();
+    fs::create_dir_all(root.join("benches")).unwrap();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    let body = "fn main() {}\n".to_string() + &"// demo\n".repeat(1000);
+    for i in 0..11 {
+        fs::write(root.join("examples").join(format!("demo{}.rs", i)), &body).unwrap();
+    }
+    fs::write(root.join("examples").join("viewer").join("main.rs"), &body).unwrap();
+    fs::write(root.join("examples").join("gpu-demo.rs"), &body).unwrap();
+    fs::write(root.join("benches").join("parse.rs"), &body).unwrap();
+    fs::write(root.join("tests").join("smoke.rs"), "#[test]\nfn smoke() {}\n").unwrap();
+    
+    let report = targets::target_report(Some(&root.join("Cargo.toml"))).unwrap();
+    assert_eq!(report.count(AuxKind::Example), 13);
+    assert_eq!(report.count(AuxKind::Bench), 1);
+    assert_eq!(report.count(AuxKind::Test), 1);
+    
+    // The gated example is already opt-in; the other twelve warrant their own crate
+    let suggestions = report.suggestions();
+    assert_eq!(suggestions.len(), 1, "{:?}", suggestions);
+    assert_eq!(suggestions[0].remedy, Remedy::SeparateCrate);
+    assert_eq!(suggestions[0].targets, 12);
+    assert!(suggestions[0].savings_percent >= 50, "{:?}", suggestions[0]);
+    
+    let mut advisor = Advisor::with_lang(Lang::En);
+    advisor.advise_targets(&report);
+    assert!(advisor.advice()[0].message.starts_with("gallery has 12 targets in examples/"), "{}", advisor);
+    
+    let analysis = analyze_project(Some(&root.join("Cargo.toml"))).unwrap();
+    assert_eq!(analysis.target_costs, report);
+    assert!(analysis.summary().contains("  - gallery: 12 examples, ~"), "{}", analysis.summary());
+}
+
+#[test]
+fn test_ci_artifact_leaves_the_project_untouched() {
+    use cargo_optimize::report::{ci::ci_artifact_in, CiArtifact};
+    
+    let temp_dir = create_test_project(r#"
+[package]
+name = "ci-report"
+version = "0.1.0"
+edition = "2021"
+"#);
+    let project = temp_dir.path();
+    let files = |dir: &Path| {
+        let mut names: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        names
+    };
+    
+    // Without a lock file cargo would write one, so the analysis is skipped
+    let out = TempDir::new().unwrap();
+    let artifact = ci_artifact_in(project, &out.path().join("report")).unwrap();
+    assert!(artifact.analysis.is_none());
+    assert!(artifact.analysis_skipped.as_deref().unwrap().contains("--locked"), "{:?}", artifact.analysis_skipped);
+    assert_eq!(files(project), ["Cargo.toml", "src"]);
+    assert!(!project.join(".cargo").exists());
+    
+    let status = std::process::Command::new("cargo")
+        .args(["generate-lockfile", "--offline"])
+        .current_dir(project)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let lock = fs::read_to_string(project.join("Cargo.lock")).unwrap();
+    
+    let artifact = ci_artifact_in(project, &out.path().join("report.json")).unwrap();
+    assert_eq!(artifact.analysis.as_ref().unwrap().workspace_members, ["ci-report"]);
+    assert_eq!(files(project), ["Cargo.lock", "Cargo.toml", "src"]);
+    assert_eq!(fs::read_to_string(project.join("Cargo.lock")).unwrap(), lock);
+    
+    let (json, markdown) = CiArtifact::paths(&out.path().join("report"));
+    let parsed: serde_json::Value = serde_json::from_str(&fs::read_to_string(json).unwrap()).unwrap();
+    assert_eq!(parsed["version"], 1);
+    assert_eq!(parsed["score"]["total"].as_u64(), Some(u64::from(artifact.score.total)));
+    assert!(parsed["planned_config"].is_string());
+    let markdown = fs::read_to_string(markdown).unwrap();
+    assert!(markdown.starts_with("# cargo-optimize report"), "{}", markdown);
+    assert!(markdown.contains(&format!("**Optimization score: {}/100**", artifact.score.total)));
+    assert_eq!(files(out.path()), ["report.json", "report.md"]);
+}
+
+#[test]
+fn test_quick_analysis_freshness() {
+    use cargo_optimize::analysis::quick::{quick_analysis, Freshness, QUICK_ANALYSIS_BUDGET};
+    use std::time::{Duration, SystemTime};
+    
+    let temp_dir = create_test_project(r#"
+[package]
+name = "quick-analysis"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "quick"
+path = "src/main.rs"
+"#);
+    let project = temp_dir.path();
+    fs::write(project.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::create_dir(project.join(".cargo")).unwrap();
+    fs::write(project.join(".cargo/config.toml"), "[build]\ntarget = \"thumbv7em-none-eabihf\"\n").unwrap();
+    
+    // Nothing cached yet: only what the project files say
+    let quick = quick_analysis(project);
+    assert_eq!(quick.metadata, Freshness::Missing);
+    assert_eq!(quick.configured_targets, ["thumbv7em-none-eabihf"]);
+    assert_eq!(quick.template_freshness, Freshness::Live);
+    assert!(quick.workspace_members.is_empty() && quick.total_dependencies.is_none());
+    assert!(quick.skipped.iter().any(|skipped| skipped == "bottlenecks"));
+    
+    fs::remove_dir_all(project.join(".cargo")).unwrap();
+    analyze_project(Some(&project.join("Cargo.toml"))).expect("Failed to analyze project");
+    let quick = quick_analysis(project);
+    assert_eq!(quick.metadata, Freshness::Cached);
+    assert_eq!(quick.workspace_members, ["quick-analysis"]);
+    assert_eq!(quick.total_dependencies, Some(0));
+    assert_eq!(quick.targets.as_ref().unwrap().binaries, ["quick"]);
+    assert_eq!(quick.template_freshness, Freshness::Cached);
+    assert!(quick.elapsed < QUICK_ANALYSIS_BUDGET * 10, "{:?}", quick.elapsed);
+    
+    // A manifest edited after the cache was written
+    let manifest = fs::File::options().write(true).open(project.join("Cargo.toml")).unwrap();
+    manifest.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+    let quick = quick_analysis(project);
+    assert_eq!(quick.metadata, Freshness::Stale);
+    assert_eq!(quick.workspace_members, ["quick-analysis"]);
+    let json = serde_json::to_value(&quick).unwrap();
+    assert_eq!(json["metadata"], "stale");
+}
+
+#[test]
+fn test_source_stats_leave_out_vendored_and_generated_code() {
+    use cargo_optimize::analysis::sources::ExclusionReason;
+    
+    let project_dir = create_test_project(r#"
+[package]
+name = "sources-test"
+version = "0.1.0"
+edition = "2021"
+"#);
+    let root = project_dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    };
+    write("src/lib.rs", "//! Hand-written\npub mod api;\npub mod bindings;\npub mod user;\n");
+    write("src/api.rs", "pub fn api() {}\n");
+    write("src/bindings.rs", "/* automatically generated by rust-bindgen 0.69.4 */\npub const A: u32 = 1;\npub const B: u32 = 2;\n");
+    write("src/user.pb.rs", "pub struct User;\n");
+    write("src/schema.rs", "pub struct Schema;\npub struct Table;\n");
+    write("vendor/zlib-sys/src/lib.rs", "pub fn inflate() {}\npub fn deflate() {}\n");
+    write("vendor/ours/src/lib.rs", "pub fn patched() {}\n");
+    write("fixtures/big.rs", "pub fn fixture() {}\n");
+    write(".gitattributes", "src/schema.rs linguist-generated\n*.md linguist-documentation\n");
+    write("cargo-optimize.toml", "[analysis]\nexclude = [\"fixtures/\"]\ninclude = [\"vendor/ours/\"]\n");
+    
+    let analysis = analyze_project(Some(&root.join("Cargo.toml"))).expect("Failed to analyze project");
+    let sources = &analysis.metrics.sources;
+    // lib.rs, api.rs and the included vendored fork
+    assert_eq!((sources.files, sources.lines), (3, 6), "{:?}", sources);
+    let reasons: Vec<&ExclusionReason> = sources.excluded.iter().map(|excluded| &excluded.reason).collect();
+    assert_eq!(reasons.len(), 5, "{:?}", reasons);
+    assert!(reasons.contains(&&ExclusionReason::Vendored("vendor".into())));
+    assert!(reasons.contains(&&ExclusionReason::GitAttributes("src/schema.rs".to_string())));
+    assert!(reasons.contains(&&ExclusionReason::GeneratedName("**/*.pb.rs")));
+    assert!(reasons.contains(&&ExclusionReason::Configured("fixtures/".to_string())));
+    assert!(reasons.iter().any(|reason| matches!(reason, ExclusionReason::GeneratedHeader(_))));
+    assert_eq!(sources.excluded_lines(), 9);
+    assert_eq!(analysis.metrics.estimated_loc, 6);
+}
+
+#[test]
+fn test_new_project_without_lockfile_is_preliminary() {
+    let temp_dir = create_test_project(r#"
+[package]
+name = "brand-new"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+helper = { path = "helper" }
+"#);
+    let project = temp_dir.path();
+    fs::create_dir_all(project.join("helper").join("src")).unwrap();
+    fs::write(
+        project.join("helper").join("Cargo.toml"),
+        "[package]\nname = \"helper\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    fs::write(project.join("helper").join("src").join("lib.rs"), "").unwrap();
+    
+    // Analyzing does not resolve the project, so no lock file is written
+    let analysis = analyze_project(Some(&project.join("Cargo.toml"))).expect("Failed to analyze project");
+    assert!(analysis.preliminary);
+    assert!(!project.join("Cargo.lock").exists());
+    assert_eq!((analysis.direct_dependencies, analysis.total_dependencies), (1, 1));
+    assert!(analysis.bottlenecks.is_empty());
+    assert_eq!(analysis.metrics.crate_count, 2);
+    assert!(analysis.summary().starts_with("Preliminary: no Cargo.lock yet"), "{}", analysis.summary());
+    assert_eq!(cargo_optimize::sccache::ProjectSize::from_analysis(&analysis), None);
+    
+    // Still no lock file: nothing to refresh
+    let analysis = analysis.refresh().unwrap();
+    assert!(analysis.preliminary);
+    
+    let status = std::process::Command::new("cargo")
+        .args(["generate-lockfile", "--offline"])
+        .current_dir(project)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let analysis = analysis.refresh().unwrap();
+    assert!(!analysis.preliminary);
+    assert_eq!(analysis.total_dependencies, 1);
+    assert!(!analysis.summary().contains("Preliminary"));
+}
+
+#[test]
+fn test_broken_members_are_left_out() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    fs::write(
+        root.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\", \"tools/gen\"]\nresolver = \"2\"\n\n\
+         [workspace.package]\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    let member = |path: &str, manifest: &str| {
+        let dir = root.join(path);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), "").unwrap();
+    };
+    member("crates/core", "[package]\nname = \"core\"\nversion.workspace = true\nedition.workspace = true\n");
+    member(
+        "crates/app",
+        "[package]\nname = \"app\"\nversion.workspace = true\nedition.workspace = true\n\n\
+         [dependencies]\ncore = { path = \"../core\" }\n",
+    );
+    // A syntax error, and a member cargo cannot load
+    member("crates/broken", "[package\nname = \"broken\"\n");
+    member("tools/gen", "[package]\nname = \"gen\"\nversion.workspace = true\nedition = \"2015\"\nlicense.workspace = true\n");
+    
+    let analysis = analyze_project(Some(&root.join("Cargo.toml"))).expect("Failed to analyze the healthy members");
+    let mut names: Vec<&str> = analysis.workspace_members.iter().map(|m| m.name.as_str()).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["app", "core"]);
+    assert_eq!(analysis.workspace_root, root);
+    assert!(analysis.workspace_members.iter().all(|m| m.path.starts_with(root)));
+    assert_eq!(analysis.metrics.internal_dependencies, 1);
+    
+    let skipped: Vec<&Path> = analysis.member_diagnostics.iter().map(|d| d.path.as_path()).collect();
+    assert_eq!(skipped, [root.join("crates/broken"), root.join("tools/gen")]);
+    assert!(analysis.member_diagnostics[0].error.contains("does not parse"), "{:?}", analysis.member_diagnostics);
+    assert!(analysis.member_diagnostics[1].error.contains("license"), "{:?}", analysis.member_diagnostics);
+    assert!(analysis.summary().contains("Members left out of the analysis"));
+    
+    // The workspace itself is untouched, and a healthy one has no diagnostics
+    assert!(fs::read_to_string(root.join("Cargo.toml")).unwrap().contains("tools/gen"));
+    fs::write(root.join("crates/broken/Cargo.toml"), "[package]\nname = \"broken\"\nversion = \"0.1.0\"\n").unwrap();
+    fs::write(root.join("tools/gen/Cargo.toml"), "[package]\nname = \"gen\"\nversion = \"0.1.0\"\n").unwrap();
+    let analysis = analyze_project(Some(&root.join("Cargo.toml"))).unwrap();
+    assert_eq!(analysis.workspace_members.len(), 4);
+    assert!(analysis.member_diagnostics.is_empty());
+}
+
+#[test]
+fn test_broken_members_fail_a_locked_analysis() {
+    let workspace_dir = create_workspace_project();
+    let root = workspace_dir.path();
+    fs::write(root.join("crate-b").join("Cargo.toml"), "[package\n").unwrap();
+    
+    assert!(cargo_optimize::analysis::analyze_project_locked(Some(&root.join("Cargo.toml"))).is_err());
+    // Analyzing the broken member itself still fails
+    assert!(analyze_project(Some(&root.join("crate-b").join("Cargo.toml"))).is_err());
+    let analysis = analyze_project(Some(&root.join("Cargo.toml"))).expect("Failed to analyze crate-a");
+    assert_eq!(analysis.workspace_members.len(), 1);
+    assert_eq!(analysis.member_diagnostics.len(), 1);
+}
+
+#[test]
+fn test_what_commands_build() {
+    use cargo_optimize::analysis::simulate::{simulate, CargoCommand, UnitMode};
+    use cargo_optimize::metrics::{BuildRecord, MetricsHistory};
+    
+    let project = create_test_project(r#"
+[package]
+name = "sim-app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+heavy = { path = "heavy" }
+
+[dev-dependencies]
+tester = { path = "tester" }
+"#);
+    for (name, source) in [("heavy", "// padding\n".repeat(1024)), ("tester", "// small".to_string())] {
+        let dir = project.path().join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!("[package]\nname = \"{}\"\nversion = \"0.2.0\"\nedition = \"2021\"\n", name),
+        ).unwrap();
+        fs::write(dir.join("src").join("lib.rs"), source).unwrap();
+    }
+    fs::create_dir(project.path().join("tests")).unwrap();
+    fs::write(project.path().join("tests").join("it.rs"), "#[test]\nfn it() {}\n").unwrap();
+    fs::create_dir(project.path().join("examples")).unwrap();
+    fs::write(project.path().join("examples").join("demo.rs"), "fn main() {}\n").unwrap();
+    let manifest = project.path().join("Cargo.toml");
+    
+    let summaries = simulate(Some(&manifest), &CargoCommand::ALL).unwrap();
+    let [build, check, test, doc] = &summaries[..] else { panic!("expected four summaries") };
+    let heavy = |summary: &cargo_optimize::analysis::simulate::CommandSummary| {
+        summary.heavy.iter().map(|c| c.name.clone()).collect::<Vec<_>>()
+    };
+    
+    // test builds the dev-dependency, the integration test and the example on top
+    assert!(test.units > build.units);
+    assert!(heavy(test).contains(&"tester".to_string()));
+    assert!(!heavy(build).contains(&"tester".to_string()));
+    assert!(heavy(build).contains(&"heavy".to_string()));
+    assert!(test.units_by_mode.contains_key(&UnitMode::Test));
+    assert!(!check.units_by_mode.contains_key(&UnitMode::Build));
+    assert!(doc.units_by_mode.contains_key(&UnitMode::Doc));
+    assert!(build.to_string().starts_with("cargo build: "));
+    
+    // Nothing measured yet, then scaled from the recorded builds
+    assert_eq!(build.estimated_secs, None);
+    let history_path = MetricsHistory::path_in(&project.path().join(".cargo"));
+    let mut history = MetricsHistory::default();
+    for secs in [50.0, 60.0, 70.0] {
+        history.record(BuildRecord::new(secs, "dev"));
+    }
+    history.save(&history_path).unwrap();
+    let summaries = simulate(Some(&manifest), &[CargoCommand::Build, CargoCommand::Check]).unwrap();
+    assert_eq!(summaries[0].estimated_secs, Some(60.0));
+    assert!(summaries[1].estimated_secs.unwrap() < 60.0);
+    assert!(summaries[0].estimate_basis.contains("3 recorded dev builds"));
+}
@@ -32,6 +32,7 @@ fn test_output_consistency() -> bool {
     // Test that the same input always produces the same output
     let temp_dir = TempDir::new().unwrap();
     let temp_path = temp_dir.path();
+    let original_dir = std::env::current_dir().unwrap();
     std::env::set_current_dir(temp_path).unwrap();
     
     fs::create_dir_all(".cargo").unwrap();
@@ -63,6 +64,9 @@ fn test_output_consistency() -> bool {
         }
     }
     
+    // Restore the working directory before the temp dir is dropped
+    std::env::set_current_dir(original_dir).unwrap();
+    
     // All outputs should be identical
     if outputs.len() == 5 {
         let first = &outputs[0];
@@ -91,11 +95,10 @@ fn test_snapshot_comparisons() -> bool {
     use cargo_optimize::mvp;
     
     // Create golden master snapshots for linker detection
-    let expected_linkers = if cfg!(target_os = "windows") {
-        vec!["rust-lld", "lld-link", "link", "default"]
-    } else {
-        vec!["mold", "lld", "gold", "ld", "default"]
-    };
+    let mut expected_linkers: Vec<&str> = cargo_optimize::linkers::Platform::current()
+        .map(|platform| cargo_optimize::linkers::for_platform(platform).map(|spec| spec.name).collect())
+        .unwrap_or_default();
+    expected_linkers.push("default");
     
     let detected_linker_result = mvp::detect_best_linker();
     
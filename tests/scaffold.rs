@@ -0,0 +1,48 @@
+//! Generated projects are accepted by cargo and by cargo-optimize
+
+use cargo_optimize::config::{ConfigManager, ProjectTemplate};
+use cargo_optimize::scaffold::{new_project, CrateKind, Integration, Template};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Names of the packages cargo finds in the project at `root`, without resolving dependencies
+fn package_names(root: &std::path::Path) -> Vec<String> {
+    let output = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+        .args(["metadata", "--no-deps", "--offline", "--format-version", "1"])
+        .current_dir(root)
+        .output()
+        .expect("failed to run cargo metadata");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut names: Vec<String> = metadata["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|package| package["name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_generated_projects_load() {
+    let temp = TempDir::new().unwrap();
+
+    let template = Template::package(CrateKind::Binary).with_archetype(ProjectTemplate::Server);
+    let root = new_project("api", &template).unwrap().write_to(temp.path()).unwrap();
+    assert_eq!(package_names(&root), ["api"]);
+    let manager = ConfigManager::new_with_base_dir(&root, "TEST_SCAFFOLD_PACKAGE_").unwrap();
+    assert_eq!(manager.config().global.template, Some(ProjectTemplate::Server));
+
+    let template = Template::workspace(CrateKind::Binary, ["cli", "engine"])
+        .with_integration(Integration::InstalledBinary)
+        .with_ci(None);
+    let scaffold = new_project("tools", &template).unwrap();
+    let root = scaffold.write_to(temp.path()).unwrap();
+    assert_eq!(package_names(&root), ["cli", "engine"]);
+    assert!(root.join(".cargo/config.toml").is_file());
+    assert!(!root.join(".github").exists());
+
+    // Never writes into a directory that has content
+    assert!(scaffold.write_to(temp.path()).is_err());
+}
@@ -8,6 +8,8 @@
 //!
 //! Test execution: cargo nextest run --test security_resilience_tests
 
+#![allow(clippy::assertions_on_constants)]
+
 use cargo_optimize::mvp::detect_best_linker;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -258,7 +260,7 @@ fn test_security_symlink_attacks() {
         assert!(metadata.file_type().is_symlink());
         
         // Should refuse to write to symlinks
-        let write_result = fs::write(&ctx.config_path, "malicious content");
+        let _write_result = fs::write(&ctx.config_path, "malicious content");
         // In production, this should be prevented
     }
 }
@@ -299,7 +301,7 @@ fn test_security_env_var_injection() {
     
     // Should not contain shell commands
     match linker {
-        Ok(ref l) if l == "rust-lld" || l == "mold" || l == "lld" || l == "gold" || l == "lld-link" || l == "default" => {
+        Ok(ref l) if cargo_optimize::linkers::find(l).is_some() || l == "default" => {
             // Valid linker types only
             assert!(!l.contains("$"), "Should not contain shell variables");
             assert!(!l.contains("rm"), "Should not contain dangerous commands");
@@ -430,7 +432,7 @@ fn test_chaos_random_failure_injection() {
     let _ctx = SecurityTestContext::new();
     
     // Simulate various failure conditions
-    let failure_scenarios = vec![
+    let failure_scenarios = [
         // Disk full
         || -> Result<(), String> {
             Err("No space left on device".to_string())
@@ -502,7 +504,7 @@ fn test_chaos_resource_exhaustion() {
     // Should either succeed or fail with clear error (not panic)
     match result {
         Ok(_) => assert!(true, "Operation succeeded despite resource pressure"),
-        Err(e) => assert!(e.to_string().len() > 0, "Should have clear error message"),
+        Err(e) => assert!(!e.to_string().is_empty(), "Should have clear error message"),
     }
     
     // Cleanup
@@ -0,0 +1,156 @@
+//! End-to-end runs of the whole pipeline against a fixture workspace.
+//!
+//! [`Fixture::copy`] copies a workspace from `tests/fixtures/` into a scratch
+//! directory, and [`Fixture::run_pipeline`] does what a user's first build
+//! does: detect the hardware, plan, apply the config to the copy and build
+//! it with cargo on this machine, with whatever linkers and caches are
+//! installed. Each step is timed. Building takes long enough that these runs
+//! only happen with [`E2E_ENV_VAR`] set; tests call [`enabled`] first:
+//!
+//! ```text
+//! CARGO_OPTIMIZE_E2E=1 cargo test --test end_to_end
+//! ```
+
+use cargo_optimize::config::ConfigManager;
+use cargo_optimize::hardware::HardwareInfo;
+use cargo_optimize::report::OptimizationReport;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Environment variable enabling the end-to-end runs
+pub const E2E_ENV_VAR: &str = "CARGO_OPTIMIZE_E2E";
+
+/// Whether end-to-end runs are enabled, printing why not otherwise
+pub fn enabled() -> bool {
+    let enabled = std::env::var_os(E2E_ENV_VAR).is_some_and(|value| !value.is_empty() && value != "0");
+    if !enabled {
+        eprintln!("skipped: set {}=1 to build the fixture workspace end to end", E2E_ENV_VAR);
+    }
+    enabled
+}
+
+/// Directory of the fixture workspaces
+pub fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+/// Time each step of a pipeline run took
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    /// Hardware detection
+    pub detect: Duration,
+    /// Planning the config
+    pub plan: Duration,
+    /// Writing the config
+    pub apply: Duration,
+    /// `cargo build` with the written config
+    pub build: Duration,
+}
+
+/// What a pipeline run did
+#[derive(Debug)]
+pub struct PipelineRun {
+    /// Hardware the plan was made for
+    pub hardware: HardwareInfo,
+    /// Report of the apply
+    pub report: OptimizationReport,
+    /// `.cargo/config.toml` after the apply
+    pub config: String,
+    /// Time each step took
+    pub timings: Timings,
+}
+
+/// A fixture workspace copied into a scratch directory
+pub struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    /// Copy `tests/fixtures/<name>` into a fresh scratch directory
+    pub fn copy(name: &str) -> Self {
+        let dir = TempDir::new().expect("Failed to create fixture directory");
+        copy_dir(&fixtures_dir().join(name), dir.path());
+        Fixture { dir }
+    }
+
+    /// Root of the copy
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Target directory builds of the copy use
+    pub fn target_dir(&self) -> PathBuf {
+        self.path().join("target")
+    }
+
+    /// Detect, plan, apply and build, failing on the first step that fails
+    pub fn run_pipeline(&self) -> Result<PipelineRun, String> {
+        let mut timings = Timings::default();
+
+        let started = Instant::now();
+        let hardware = HardwareInfo::detect();
+        timings.detect = started.elapsed();
+
+        let manager = ConfigManager::new_with_base_dir(self.path(), "CARGO_OPTIMIZE_E2E_")
+            .map_err(|e| format!("Failed to load the config: {:#}", e))?;
+        let started = Instant::now();
+        manager.plan_with_hardware(&hardware).map_err(|e| format!("Planning failed: {:#}", e))?;
+        timings.plan = started.elapsed();
+
+        let started = Instant::now();
+        let report = manager.apply_with_hardware(&hardware).map_err(|e| format!("Apply failed: {:#}", e))?;
+        timings.apply = started.elapsed();
+        let config = fs::read_to_string(self.path().join(".cargo").join("config.toml")).unwrap_or_default();
+
+        let started = Instant::now();
+        self.cargo(&["build", "--workspace", "--all-targets", "--offline"])?;
+        timings.build = started.elapsed();
+
+        Ok(PipelineRun { hardware, report, config, timings })
+    }
+
+    /// Run cargo in the copy, returning its stdout or, on failure, its stderr
+    ///
+    /// Settings of the cargo running the tests are cleared so only the
+    /// applied config takes effect.
+    pub fn cargo(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+            .args(args)
+            .current_dir(self.path())
+            .env("CARGO_TARGET_DIR", self.target_dir())
+            .env_remove("RUSTFLAGS")
+            .env_remove("CARGO_ENCODED_RUSTFLAGS")
+            .env_remove("RUSTC_WRAPPER")
+            .env_remove("CARGO_BUILD_RUSTC_WRAPPER")
+            .output()
+            .map_err(|e| format!("Failed to run cargo: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "cargo {} failed with the applied config:\n{}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Copy `from` into `to` recursively, leaving out build output
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).expect("Failed to create fixture directory");
+    for entry in fs::read_dir(from).expect("Failed to read fixture").flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        if name == "target" || name == "Cargo.lock" {
+            continue;
+        }
+        if path.is_dir() {
+            copy_dir(&path, &to.join(&name));
+        } else {
+            fs::copy(&path, to.join(&name)).expect("Failed to copy fixture file");
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Simulated build environments for end-to-end config generation tests.
+//!
+//! An [`Environment`] fixes everything planning would otherwise detect on the
+//! machine running the tests: host platform, toolchain, installed commands,
+//! hardware, machine policy, other build tooling and the project's existing
+//! `.cargo/config.toml`.
+//! [`Environment::generate`] plans in a scratch project and returns the
+//! rendered config, which [`assert_golden`] compares with the file committed
+//! under `tests/golden/`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the golden files after an intended
+//! behavior change, then review the diff.
+
+#![allow(dead_code)]
+
+pub mod e2e;
+
+use cargo_optimize::config::{BuildContext, ConfigManager};
+use cargo_optimize::conflicts::ToolEnvironment;
+use cargo_optimize::hardware::{DiskInfo, HardwareInfo};
+use cargo_optimize::linkers::Platform;
+use cargo_optimize::sccache::SccacheStats;
+use cargo_optimize::toolchain::ToolchainInfo;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+
+/// CPU architecture of the simulated machines, so the golden files do not follow the host
+pub const ARCH: &str = "x86_64";
+
+/// A config a team tuned by hand, with comments and settings cargo-optimize also manages
+pub const HAND_TUNED_CONFIG: &str = "\
+# Team settings - keep in sync with CI
+[build]
+jobs = 4
+
+[alias]
+b = \"build\"
+
+[profile.release]
+lto = \"thin\" # measured: fat LTO is not worth it
+";
+
+/// Toolchains of the matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toolchain {
+    /// Stable release
+    Stable,
+    /// Nightly build
+    Nightly,
+}
+
+impl Toolchain {
+    fn name(self) -> &'static str {
+        match self {
+            Toolchain::Stable => "stable",
+            Toolchain::Nightly => "nightly",
+        }
+    }
+
+    fn info(self, platform: Platform) -> ToolchainInfo {
+        let release = match self {
+            Toolchain::Stable => "1.85.0",
+            Toolchain::Nightly => "1.87.0-nightly",
+        };
+        let host = platform.target_triple_for(ARCH).expect("every platform has an x86_64 triple");
+        let output = format!("release: {}\nhost: {}\n", release, host);
+        ToolchainInfo::parse_verbose_version(&output).expect("valid rustc -vV output")
+    }
+}
+
+/// One point of the environment matrix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    /// Host platform
+    pub platform: Platform,
+    /// Active toolchain
+    pub toolchain: Toolchain,
+    /// Commands on PATH besides rustc (linkers and C compiler drivers)
+    pub commands: Vec<&'static str>,
+    /// Content of `.cargo/config.toml` before planning
+    pub existing: Option<&'static str>,
+}
+
+impl Environment {
+    /// File name of the golden config, e.g. `linux-stable-mold-clang-fresh.toml`
+    pub fn name(&self) -> String {
+        let commands = if self.commands.is_empty() { "bare".to_string() } else { self.commands.join("-") };
+        let existing = if self.existing.is_some() { "hand-tuned" } else { "fresh" };
+        format!(
+            "{}-{}-{}-{}.toml",
+            self.platform.name().to_lowercase(),
+            self.toolchain.name(),
+            commands.replace('.', "_"),
+            existing
+        )
+    }
+
+    /// Hardware of the simulated machine: 8 cores, 16 GiB, plenty of disk
+    pub fn hardware(&self) -> HardwareInfo {
+        HardwareInfo {
+            cpu_count: 8,
+            physical_cpu_count: 4,
+            total_memory: 16 << 30,
+            available_memory: 12 << 30,
+            disks: vec![DiskInfo {
+                mount_point: "/".to_string(),
+                total_space: 500 << 30,
+                available_space: 200 << 30,
+                file_system: "ext4".to_string(),
+                is_ssd: Some(true),
+            }],
+            os_name: self.platform.name().to_string(),
+            os_version: "simulated".to_string(),
+            arch: ARCH.to_string(),
+            topology: Default::default(),
+        }
+    }
+
+    /// Plan in a scratch project and return the config it would write
+    pub fn generate(&self) -> String {
+        static MANAGERS: AtomicUsize = AtomicUsize::new(0);
+        let project = TempDir::new().expect("Failed to create temp dir");
+        let cargo_dir = project.path().join(".cargo");
+        fs::create_dir_all(&cargo_dir).expect("Failed to create .cargo dir");
+        if let Some(existing) = self.existing {
+            fs::write(cargo_dir.join("config.toml"), existing).expect("Failed to write existing config");
+        }
+
+        // Keep the live hardware out of the configuration, then apply the simulated one
+        let prefix = format!("GOLDEN_{}_", MANAGERS.fetch_add(1, Ordering::Relaxed));
+        let auto_detect = format!("{}GLOBAL__AUTO_DETECT_HARDWARE", prefix);
+        std::env::set_var(&auto_detect, "false");
+        let manager = ConfigManager::new_with_context(project.path(), &prefix, BuildContext::Local);
+        std::env::remove_var(&auto_detect);
+        let mut manager = manager.expect("Failed to create config manager");
+
+        let hardware = self.hardware();
+        manager
+            .config_mut()
+            .apply_hardware_optimizations_for(hardware.cpu_count, hardware.total_memory);
+        let commands = std::iter::once("rustc").chain(self.commands.iter().copied());
+        let manager = manager
+            .with_platform(Some(self.platform))
+            .with_arch(ARCH)
+            .with_toolchain(self.toolchain.info(self.platform))
+            .with_installed_commands(commands.map(str::to_string).collect())
+            .with_installed_components(Vec::new())
+            .with_nextest_installed(false)
+            .with_sccache_stats(SccacheStats::default())
+            .with_target_dir_env(None)
+            .with_tool_environment(ToolEnvironment::default())
+            .with_policy(None);
+
+        let (doc, _) = manager.plan_with_hardware(&hardware).expect("Planning failed");
+        doc.to_string()
+    }
+}
+
+/// Every combination of platform, toolchain, installed commands and existing config
+pub fn matrix() -> Vec<Environment> {
+    let command_sets: &[(Platform, &[&[&'static str]])] = &[
+        (Platform::Linux, &[&[], &["mold", "clang"], &["ld.lld", "gold", "gcc"]]),
+        // rust-lld ships with every toolchain
+        (Platform::Windows, &[&[]]),
+        (Platform::MacOs, &[&[], &["ld64.sold", "clang"]]),
+        (Platform::FreeBsd, &[&[], &["mold", "clang"]]),
+        (Platform::Illumos, &[&["gcc"]]),
+    ];
+    let mut matrix = Vec::new();
+    for (platform, sets) in command_sets {
+        for commands in *sets {
+            for toolchain in [Toolchain::Stable, Toolchain::Nightly] {
+                for existing in [None, Some(HAND_TUNED_CONFIG)] {
+                    matrix.push(Environment {
+                        platform: *platform,
+                        toolchain,
+                        commands: commands.to_vec(),
+                        existing,
+                    });
+                }
+            }
+        }
+    }
+    matrix
+}
+
+/// Directory of the golden files
+pub fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// Compare `actual` with the golden file `name`, or rewrite it with `UPDATE_GOLDEN=1`
+///
+/// Returns a description of the mismatch instead of panicking, so a test can
+/// report every differing environment at once.
+pub fn check_golden(name: &str, actual: &str) -> Result<(), String> {
+    let path = golden_dir().join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden_dir()).expect("Failed to create golden dir");
+        fs::write(&path, actual).expect("Failed to write golden file");
+        return Ok(());
+    }
+    match fs::read_to_string(&path) {
+        Ok(expected) if expected == actual => Ok(()),
+        Ok(expected) => Err(format!(
+            "{} differs from the generated config\n--- expected\n{}\n--- generated\n{}",
+            name, expected, actual
+        )),
+        Err(_) => Err(format!("{} is missing; run with UPDATE_GOLDEN=1 to create it", name)),
+    }
+}
+
+/// Assert that `actual` matches the golden file `name`
+pub fn assert_golden(name: &str, actual: &str) {
+    if let Err(mismatch) = check_golden(name, actual) {
+        panic!("{}", mismatch);
+    }
+}
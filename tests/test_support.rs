@@ -0,0 +1,193 @@
+//! Tests of the fake environment downstream build scripts are tested with
+//!
+//! Run with `cargo test --features test-support`.
+
+#![cfg(feature = "test-support")]
+
+use cargo_optimize::hardware::HardwareInfo;
+use cargo_optimize::linkers::Platform;
+use cargo_optimize::test_support::{FakeEnvironment, TestProject};
+
+#[test]
+fn test_build_script_runs_against_pretended_linkers() {
+    let Some(platform) = Platform::current() else {
+        return;
+    };
+    let linker = match platform {
+        Platform::Windows => "rust-lld",
+        _ => "lld",
+    };
+    let project = TestProject::builder("app").file("build.rs", "fn main() {}\n").build().unwrap();
+    {
+        let _env = FakeEnvironment::new().with_linkers([linker]).in_project(&project).install();
+        cargo_optimize::auto_configure();
+    }
+    let config = project.cargo_config().expect("auto_configure wrote no config");
+    assert!(config.contains(platform.target_triple().unwrap()), "{}", config);
+    assert!(config.contains("lld"), "{}", config);
+    assert_eq!(project.read("build.rs").as_deref(), Some("fn main() {}\n"));
+    
+    // Nothing installed: the build script leaves the project alone
+    let bare = TestProject::builder("bare").build().unwrap();
+    let _env = FakeEnvironment::new().in_project(&bare).install();
+    cargo_optimize::auto_configure();
+    assert_eq!(bare.cargo_config(), None);
+}
+
+#[test]
+fn test_build_script_skips_say_why() {
+    use cargo_optimize::mvp::{auto_configure_report, MvpConfig};
+    use cargo_optimize::report::{DecisionOutcome, OptimizationReport, SkipReason};
+    
+    let Some(platform) = Platform::current() else {
+        return;
+    };
+    let linker = match platform {
+        Platform::Windows => "rust-lld",
+        _ => "lld",
+    };
+    let project = TestProject::builder("app").build().unwrap();
+    let run = |env: FakeEnvironment| -> OptimizationReport {
+        let _env = env.in_project(&project).install();
+        auto_configure_report(MvpConfig::default(), None).1
+    };
+    let why = |report: &OptimizationReport, setting: &str| report.decision_for(setting).and_then(|d| d.skip_reason);
+    
+    let report = run(FakeEnvironment::new().with_linkers([linker]).with_env("CARGO_OPTIMIZE_DISABLE", "1"));
+    assert_eq!(why(&report, "apply"), Some(SkipReason::UserOptOut));
+    let report = run(FakeEnvironment::new().with_linkers([linker]).with_env("RUSTC_WRAPPER", "rust-analyzer"));
+    assert_eq!(why(&report, "apply"), Some(SkipReason::Context));
+    let report = run(FakeEnvironment::new().with_unsupported_platform());
+    assert_eq!(why(&report, "platform"), Some(SkipReason::UnsupportedPlatform));
+    let report = run(FakeEnvironment::new());
+    assert_eq!(why(&report, "linker"), Some(SkipReason::MissingTool));
+    assert_eq!(project.cargo_config(), None);
+    
+    let report = run(FakeEnvironment::new().with_linkers([linker]));
+    assert_eq!(report.decision_for("linker").unwrap().outcome, DecisionOutcome::Applied);
+    let report = run(FakeEnvironment::new().with_linkers([linker]));
+    assert_eq!(why(&report, "linker"), Some(SkipReason::NotNeeded));
+}
+
+#[test]
+fn test_pretended_env_drives_output_mode() {
+    use cargo_optimize::output::OutputMode;
+    
+    let build_script = || {
+        FakeEnvironment::new()
+            .with_env("OUT_DIR", "/tmp/out")
+            .with_env("TARGET", "x86_64-unknown-linux-gnu")
+            .with_env("CARGO_MANIFEST_DIR", "/tmp/app")
+    };
+    {
+        let _env = build_script().install();
+        assert_eq!(OutputMode::detect(), OutputMode::BuildScript);
+    }
+    {
+        let _env = build_script().with_env("CARGO_OPTIMIZE_QUIET", "1").install();
+        assert_eq!(OutputMode::detect(), OutputMode::QuietBuildScript);
+    }
+    let _env = FakeEnvironment::new().with_env("CARGO_OPTIMIZE_QUIET", "1").install();
+    assert_eq!(OutputMode::detect(), OutputMode::Quiet);
+}
+
+#[test]
+fn test_pretended_hardware_is_scoped_to_the_guard() {
+    let real = HardwareInfo::detect().cpu_count;
+    {
+        let _env = FakeEnvironment::new().with_cpus(96, 48).with_memory(64 << 30).install();
+        let hardware = HardwareInfo::detect();
+        assert_eq!((hardware.cpu_count, hardware.physical_cpu_count), (96, 48));
+        assert_eq!(hardware.available_memory, 32 << 30);
+        
+        let _inner = FakeEnvironment::new().with_cpus(4, 4).install();
+        assert_eq!(HardwareInfo::detect().cpu_count, 4);
+    }
+    assert_eq!(HardwareInfo::detect().cpu_count, real);
+}
+
+#[test]
+fn test_pretended_platform_drives_detection() {
+    let _env = FakeEnvironment::new().with_platform(Platform::Windows).with_linkers(["rust-lld"]).install();
+    assert_eq!(Platform::current(), Some(Platform::Windows));
+    assert_eq!(cargo_optimize::mvp::detect_best_linker().unwrap(), "rust-lld");
+}
+
+#[test]
+fn test_pretended_windows_arm64_gets_its_own_target() {
+    let project = TestProject::builder("app").build().unwrap();
+    {
+        let _env = FakeEnvironment::new()
+            .with_platform(Platform::Windows)
+            .with_arch("aarch64")
+            .with_linkers(["rust-lld"])
+            .in_project(&project)
+            .install();
+        assert_eq!(Platform::Windows.target_triple(), Some("aarch64-pc-windows-msvc"));
+        cargo_optimize::auto_configure();
+    }
+    let config = project.cargo_config().expect("auto_configure wrote no config");
+    assert!(config.contains("[target.aarch64-pc-windows-msvc]"), "{}", config);
+    assert!(!config.contains("x86_64"), "{}", config);
+    assert!(config.contains("rust-lld"), "{}", config);
+}
+
+#[test]
+fn test_architecture_without_a_triple_is_unsupported() {
+    use cargo_optimize::mvp::{auto_configure_report, MvpConfig};
+    use cargo_optimize::report::SkipReason;
+    
+    let project = TestProject::builder("app").build().unwrap();
+    let report = {
+        let _env = FakeEnvironment::new()
+            .with_platform(Platform::MacOs)
+            .with_arch("riscv64")
+            .with_linkers(["sold"])
+            .in_project(&project)
+            .install();
+        assert_eq!(Platform::current(), None);
+        auto_configure_report(MvpConfig::default(), None).1
+    };
+    let decision = report.decision_for("platform").unwrap();
+    assert_eq!(decision.skip_reason, Some(SkipReason::UnsupportedPlatform));
+    assert_eq!(project.cargo_config(), None);
+}
+
+#[test]
+fn test_build_script_respects_a_rolled_back_linker() {
+    use cargo_optimize::config::{BuildContext, ConfigManager};
+    use cargo_optimize::guardrail;
+    use cargo_optimize::mvp::{auto_configure_report, MvpConfig};
+    use cargo_optimize::report::SkipReason;
+    
+    let project = TestProject::builder("app").build().unwrap();
+    let env = || {
+        FakeEnvironment::new()
+            .with_platform(Platform::Linux)
+            .with_arch("x86_64")
+            .with_linkers(["mold", "lld"])
+            .in_project(&project)
+    };
+    {
+        let _env = env().install();
+        auto_configure_report(MvpConfig::default(), None);
+    }
+    assert!(project.cargo_config().unwrap().contains("mold"));
+    
+    // A guarded build sees the linker the build script wrote, and takes it back
+    let manager = ConfigManager::new_with_context(project.root(), "TEST_MVP_ROLLBACK_", BuildContext::Local).unwrap();
+    assert_eq!(manager.unverified_linker().as_deref(), Some("mold"));
+    let failure = guardrail::detect_failure("  = note: mold: fatal: unknown command line option\n", "mold").unwrap();
+    manager.rollback_linker(&failure).unwrap();
+    assert!(!project.cargo_config().unwrap_or_default().contains("mold"));
+    
+    // The next build script run keeps it out
+    let report = {
+        let _env = env().install();
+        auto_configure_report(MvpConfig::default(), None).1
+    };
+    let config = project.cargo_config().unwrap();
+    assert!(config.contains("lld") && !config.contains("mold"), "{}", config);
+    assert_eq!(report.decision_for("linker mold").and_then(|d| d.skip_reason), Some(SkipReason::RolledBack));
+    assert_eq!(manager.unverified_linker().as_deref(), Some("lld"));
+}